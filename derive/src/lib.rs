@@ -0,0 +1,224 @@
+//! `#[derive(VertexLayout)]`, re-exported by miniquad with the "derive" feature.
+//!
+//! No syn/quote on purpose: the input is always a plain struct with named
+//! fields, so a tiny hand-written parser keeps miniquad dependency-free.
+
+extern crate proc_macro;
+
+use proc_macro::{Delimiter, TokenStream, TokenTree};
+
+/// Implements `miniquad::VertexLayout` for a `#[repr(C)]` struct with named
+/// fields. Each field becomes a `VertexAttribute` with the field name and the
+/// `VertexField::FORMAT` of its type.
+///
+/// Both can be overridden per field:
+/// ```ignore
+/// #[repr(C)]
+/// #[derive(VertexLayout)]
+/// struct Vertex {
+///     #[vertex(name = "in_pos")]
+///     pos: [f32; 2],
+///     #[vertex(format = Float2)]
+///     uv: glam::Vec2,
+///     color: [u8; 4],
+/// }
+/// ```
+///
+/// Structs it can not describe are compile errors, without `#[repr(C)]`
+/// rust is free to reorder the fields:
+/// ```compile_fail
+/// #[derive(miniquad::VertexLayout)]
+/// struct Vertex {
+///     pos: [f32; 2],
+/// }
+/// ```
+/// Tuple structs have no names for the attributes:
+/// ```compile_fail
+/// #[repr(C)]
+/// #[derive(miniquad::VertexLayout)]
+/// struct Vertex([f32; 2], [f32; 2]);
+/// ```
+/// Only `name` and `format` could be overridden:
+/// ```compile_fail
+/// #[repr(C)]
+/// #[derive(miniquad::VertexLayout)]
+/// struct Vertex {
+///     #[vertex(offset = 8)]
+///     pos: [f32; 2],
+/// }
+/// ```
+#[proc_macro_derive(VertexLayout, attributes(vertex))]
+pub fn derive_vertex_layout(input: TokenStream) -> TokenStream {
+    match vertex_layout(input) {
+        Ok(res) => res.parse().unwrap(),
+        Err(err) => format!("compile_error!({:?});", err).parse().unwrap(),
+    }
+}
+
+struct Field {
+    name: String,
+    ty: String,
+    format: Option<String>,
+}
+
+fn vertex_layout(input: TokenStream) -> Result<String, String> {
+    let mut tokens = input.into_iter().peekable();
+    let mut repr_c = false;
+    let mut name = None;
+    let mut body = None;
+
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Punct(ref punct) if punct.as_char() == '#' => {
+                if let Some(TokenTree::Group(group)) = tokens.next() {
+                    let attr = group.stream().to_string().replace(' ', "");
+                    if attr.starts_with("repr(") && attr.contains('C') {
+                        repr_c = true;
+                    }
+                }
+            }
+            TokenTree::Ident(ref ident) if ident.to_string() == "struct" => {
+                name = tokens.next().map(|name| name.to_string());
+            }
+            TokenTree::Ident(ref ident) if ident.to_string() == "enum" => {
+                return Err("VertexLayout can only be derived for structs".to_string());
+            }
+            TokenTree::Punct(ref punct) if punct.as_char() == '<' && name.is_some() => {
+                return Err("VertexLayout can not be derived for generic structs".to_string());
+            }
+            TokenTree::Group(group) if name.is_some() => {
+                if group.delimiter() != Delimiter::Brace {
+                    return Err("VertexLayout needs a struct with named fields".to_string());
+                }
+                body = Some(group.stream());
+                break;
+            }
+            _ => {}
+        }
+    }
+    let name = name.ok_or("VertexLayout can only be derived for structs")?;
+    let body = body.ok_or("VertexLayout needs a struct with named fields")?;
+    if !repr_c {
+        // attributes are tightly packed in the declaration order,
+        // rust is free to reorder fields without repr(C)
+        return Err(format!(
+            "{} should be #[repr(C)] to derive VertexLayout",
+            name
+        ));
+    }
+
+    let attributes = fields(body)?
+        .iter()
+        .map(|field| {
+            let format = match &field.format {
+                Some(format) => format!("::miniquad::VertexFormat::{}", format),
+                None => format!("<{} as ::miniquad::VertexField>::FORMAT", field.ty),
+            };
+            format!(
+                "::miniquad::VertexAttribute::with_buffer({:?}, {}, buffer_index),",
+                field.name, format
+            )
+        })
+        .collect::<String>();
+
+    Ok(format!(
+        "impl ::miniquad::VertexLayout for {} {{
+            fn attributes(buffer_index: usize) -> ::std::vec::Vec<::miniquad::VertexAttribute> {{
+                ::std::vec![{}]
+            }}
+        }}",
+        name, attributes
+    ))
+}
+
+fn fields(body: TokenStream) -> Result<Vec<Field>, String> {
+    let mut fields = vec![];
+    let mut tokens = body.into_iter().peekable();
+
+    loop {
+        let mut attr_name = None;
+        let mut format = None;
+
+        // attributes and visibility
+        let name = loop {
+            match tokens.next() {
+                None => return Ok(fields),
+                Some(TokenTree::Punct(ref punct)) if punct.as_char() == '#' => {
+                    if let Some(TokenTree::Group(group)) = tokens.next() {
+                        vertex_attr(group.stream(), &mut attr_name, &mut format)?;
+                    }
+                }
+                Some(TokenTree::Ident(ident)) if ident.to_string() == "pub" => {
+                    if let Some(TokenTree::Group(_)) = tokens.peek() {
+                        tokens.next();
+                    }
+                }
+                Some(TokenTree::Ident(ident)) => break ident.to_string(),
+                Some(token) => return Err(format!("unexpected token in a struct: {}", token)),
+            }
+        };
+
+        match tokens.next() {
+            Some(TokenTree::Punct(ref punct)) if punct.as_char() == ':' => {}
+            _ => return Err(format!("expected ':' after field {}", name)),
+        }
+
+        // the type is everything up to the next comma outside of <>
+        let mut ty = TokenStream::new();
+        let mut depth = 0;
+        for token in tokens.by_ref() {
+            if let TokenTree::Punct(ref punct) = token {
+                match punct.as_char() {
+                    ',' if depth == 0 => break,
+                    '<' => depth += 1,
+                    '>' => depth -= 1,
+                    _ => {}
+                }
+            }
+            ty.extend(Some(token));
+        }
+
+        fields.push(Field {
+            name: attr_name.unwrap_or(name),
+            ty: ty.to_string(),
+            format,
+        });
+    }
+}
+
+// #[vertex(name = "in_pos", format = Float2)]
+fn vertex_attr(
+    attr: TokenStream,
+    name: &mut Option<String>,
+    format: &mut Option<String>,
+) -> Result<(), String> {
+    let mut tokens = attr.into_iter();
+    match tokens.next() {
+        Some(TokenTree::Ident(ident)) if ident.to_string() == "vertex" => {}
+        // doc comments and other attributes
+        _ => return Ok(()),
+    }
+    let args = match tokens.next() {
+        Some(TokenTree::Group(group)) => group.stream().into_iter().collect::<Vec<_>>(),
+        _ => return Err("expected #[vertex(name = \"..\", format = ..)]".to_string()),
+    };
+    for arg in
+        args.split(|token| matches!(token, TokenTree::Punct(punct) if punct.as_char() == ','))
+    {
+        match arg {
+            [TokenTree::Ident(key), TokenTree::Punct(eq), value] if eq.as_char() == '=' => {
+                match &*key.to_string() {
+                    "name" => {
+                        let value = value.to_string();
+                        *name = Some(value.trim_matches('"').to_string());
+                    }
+                    "format" => *format = Some(value.to_string()),
+                    key => return Err(format!("unknown vertex attribute argument: {}", key)),
+                }
+            }
+            [] => {}
+            _ => return Err("expected #[vertex(name = \"..\", format = ..)]".to_string()),
+        }
+    }
+    Ok(())
+}
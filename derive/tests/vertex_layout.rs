@@ -0,0 +1,79 @@
+use miniquad::{VertexFormat, VertexLayout};
+
+fn layout<V: VertexLayout>() -> Vec<(&'static str, VertexFormat, usize)> {
+    V::attributes(1)
+        .iter()
+        .map(|attr| (attr.name, attr.format, attr.buffer_index))
+        .collect()
+}
+
+#[test]
+fn field_names_and_formats() {
+    #[repr(C)]
+    #[derive(VertexLayout)]
+    struct Vertex {
+        pos: [f32; 3],
+        uv: [f32; 2],
+        color: [u8; 4],
+    }
+
+    assert_eq!(
+        layout::<Vertex>(),
+        [
+            ("pos", VertexFormat::Float3, 1),
+            ("uv", VertexFormat::Float2, 1),
+            ("color", VertexFormat::Byte4, 1),
+        ]
+    );
+    assert_eq!(Vertex::buffer_layout().stride, 24);
+}
+
+#[test]
+fn doc_comments_and_visibility() {
+    /// A vertex
+    #[repr(C)]
+    #[derive(VertexLayout)]
+    pub struct Vertex {
+        /// Position in clip space
+        pub pos: [f32; 2],
+        #[doc = "Texture coordinates"]
+        pub(crate) uv: [f32; 2],
+        index: u32,
+    }
+
+    assert_eq!(
+        layout::<Vertex>(),
+        [
+            ("pos", VertexFormat::Float2, 1),
+            ("uv", VertexFormat::Float2, 1),
+            ("index", VertexFormat::Int1, 1),
+        ]
+    );
+}
+
+#[test]
+fn overrides() {
+    #[repr(C)]
+    struct Uv(f32, f32);
+
+    #[repr(C)]
+    #[derive(VertexLayout)]
+    struct Vertex {
+        #[vertex(name = "in_pos")]
+        pos: [f32; 2],
+        #[vertex(format = Float2)]
+        uv: Uv,
+        /// Overrides next to a doc comment
+        #[vertex(name = "in_color", format = Byte4)]
+        color: u32,
+    }
+
+    assert_eq!(
+        layout::<Vertex>(),
+        [
+            ("in_pos", VertexFormat::Float2, 1),
+            ("uv", VertexFormat::Float2, 1),
+            ("in_color", VertexFormat::Byte4, 1),
+        ]
+    );
+}
@@ -53,17 +53,18 @@ impl Stage {
 
 impl EventHandler for Stage {
 
-    fn update(&mut self) { 
-        let position = get_window_position();
+    fn update(&mut self) { }
 
-        if position == self.position {
+    fn window_moved_event(&mut self, x: u32, y: u32) {
+        // a maximized or minimized window position is not the one to restore
+        if window_state() != WindowState::Normal || (x, y) == self.position {
             return;
         }
 
         // write the position to a file
-        println!("Updating window_position.txt with new position {:?}", position);
-        std::fs::write("target/window_position.txt", format!("{:?},{:?}", position.0, position.1)).unwrap();
-        self.position = position;
+        println!("Updating window_position.txt with new position {:?}", (x, y));
+        std::fs::write("target/window_position.txt", format!("{:?},{:?}", x, y)).unwrap();
+        self.position = (x, y);
     }
 
     fn draw(&mut self) { }
@@ -5,22 +5,37 @@ import javax.microedition.khronos.opengles.GL10;
 
 import android.app.Activity;
 import android.os.Bundle;
+import android.os.BatteryManager;
 import android.os.Build;
+import android.os.PowerManager;
+import android.os.VibrationEffect;
+import android.os.Vibrator;
 import android.util.Log;
 
 import android.view.View;
 import android.view.Surface;
 import android.view.Window;
+import android.window.BackEvent;
+import android.window.OnBackAnimationCallback;
+import android.window.OnBackInvokedCallback;
+import android.window.OnBackInvokedDispatcher;
 import android.view.WindowInsets;
+import android.view.WindowInsetsController;
 import android.view.WindowManager.LayoutParams;
 import android.view.SurfaceView;
 import android.view.SurfaceHolder;
 import android.view.MotionEvent;
 import android.view.KeyEvent;
+import android.view.InputDevice;
+import android.hardware.input.InputManager;
 import android.view.inputmethod.InputMethodManager;
 
+import android.content.BroadcastReceiver;
+import android.content.ComponentCallbacks2;
 import android.content.Context;
+import android.text.InputType;
 import android.content.Intent;
+import android.content.IntentFilter;
 
 import android.graphics.Color;
 import android.graphics.Insets;
@@ -42,6 +57,7 @@ class QuadSurface
     implements
         View.OnTouchListener,
         View.OnKeyListener,
+        InputManager.InputDeviceListener,
         SurfaceHolder.Callback {
 
     public QuadSurface(Context context){
@@ -53,6 +69,55 @@ class QuadSurface
         requestFocus();
         setOnTouchListener(this);
         setOnKeyListener(this);
+
+        InputManager inputManager = (InputManager)context.getSystemService(Context.INPUT_SERVICE);
+        inputManager.registerInputDeviceListener(this, null);
+        for (int deviceId : inputManager.getInputDeviceIds()) {
+            onInputDeviceAdded(deviceId);
+        }
+    }
+
+    private static boolean isGamepad(int sources) {
+        return (sources & InputDevice.SOURCE_GAMEPAD) == InputDevice.SOURCE_GAMEPAD
+            || (sources & InputDevice.SOURCE_JOYSTICK) == InputDevice.SOURCE_JOYSTICK;
+    }
+
+    @Override
+    public void onInputDeviceAdded(int deviceId) {
+        InputDevice device = InputDevice.getDevice(deviceId);
+        if (device != null && isGamepad(device.getSources())) {
+            QuadNative.surfaceOnGamepadConnected(deviceId, device.getName());
+        }
+    }
+
+    @Override
+    public void onInputDeviceRemoved(int deviceId) {
+        QuadNative.surfaceOnGamepadDisconnected(deviceId);
+    }
+
+    @Override
+    public void onInputDeviceChanged(int deviceId) {
+    }
+
+    // the analog triggers are either LTRIGGER/RTRIGGER or BRAKE/GAS
+    @Override
+    public boolean onGenericMotionEvent(MotionEvent event) {
+        if ((event.getSource() & InputDevice.SOURCE_JOYSTICK) == InputDevice.SOURCE_JOYSTICK
+            && event.getAction() == MotionEvent.ACTION_MOVE) {
+            QuadNative.surfaceOnGamepadAxes(event.getDeviceId(),
+                                            event.getAxisValue(MotionEvent.AXIS_X),
+                                            event.getAxisValue(MotionEvent.AXIS_Y),
+                                            event.getAxisValue(MotionEvent.AXIS_Z),
+                                            event.getAxisValue(MotionEvent.AXIS_RZ),
+                                            event.getAxisValue(MotionEvent.AXIS_HAT_X),
+                                            event.getAxisValue(MotionEvent.AXIS_HAT_Y),
+                                            Math.max(event.getAxisValue(MotionEvent.AXIS_LTRIGGER),
+                                                     event.getAxisValue(MotionEvent.AXIS_BRAKE)),
+                                            Math.max(event.getAxisValue(MotionEvent.AXIS_RTRIGGER),
+                                                     event.getAxisValue(MotionEvent.AXIS_GAS)));
+            return true;
+        }
+        return super.onGenericMotionEvent(event);
     }
 
     @Override
@@ -80,6 +145,14 @@ class QuadSurface
 
     }
 
+    private static void sendTouch(MotionEvent event, int index, int phase) {
+        QuadNative.surfaceOnTouch(event.getPointerId(index), phase,
+                                  event.getX(index), event.getY(index),
+                                  event.getPressure(index),
+                                  event.getTouchMajor(index), event.getTouchMinor(index),
+                                  event.getDeviceId(), event.getEventTime());
+    }
+
     @Override
     public boolean onTouch(View v, MotionEvent event) {
         int pointerCount = event.getPointerCount();
@@ -88,49 +161,31 @@ class QuadSurface
         switch(action) {
         case MotionEvent.ACTION_MOVE: {
             for (int i = 0; i < pointerCount; i++) {
-                final int id = event.getPointerId(i);
-                final float x = event.getX(i);
-                final float y = event.getY(i);
-                QuadNative.surfaceOnTouch(id, 0, x, y);
+                sendTouch(event, i, 0);
             }
             break;
         }
         case MotionEvent.ACTION_UP: {
-            final int id = event.getPointerId(0);
-            final float x = event.getX(0);
-            final float y = event.getY(0);
-            QuadNative.surfaceOnTouch(id, 1, x, y);
+            sendTouch(event, 0, 1);
             break;
         }
         case MotionEvent.ACTION_DOWN: {
-            final int id = event.getPointerId(0);
-            final float x = event.getX(0);
-            final float y = event.getY(0);
-            QuadNative.surfaceOnTouch(id, 2, x, y);
+            sendTouch(event, 0, 2);
             break;
         }
         case MotionEvent.ACTION_POINTER_UP: {
             final int pointerIndex = event.getActionIndex();
-            final int id = event.getPointerId(pointerIndex);
-            final float x = event.getX(pointerIndex);
-            final float y = event.getY(pointerIndex);
-            QuadNative.surfaceOnTouch(id, 1, x, y);
+            sendTouch(event, pointerIndex, 1);
             break;
         }
         case MotionEvent.ACTION_POINTER_DOWN: {
             final int pointerIndex = event.getActionIndex();
-            final int id = event.getPointerId(pointerIndex);
-            final float x = event.getX(pointerIndex);
-            final float y = event.getY(pointerIndex);
-            QuadNative.surfaceOnTouch(id, 2, x, y);
+            sendTouch(event, pointerIndex, 2);
             break;
         }
         case MotionEvent.ACTION_CANCEL: {
             for (int i = 0; i < pointerCount; i++) {
-                final int id = event.getPointerId(i);
-                final float x = event.getX(i);
-                final float y = event.getY(i);
-                QuadNative.surfaceOnTouch(id, 3, x, y);
+                sendTouch(event, i, 3);
             }
             break;
         }
@@ -147,12 +202,26 @@ class QuadSurface
     @SuppressWarnings("deprecation")
     @Override
     public boolean onKey(View v, int keyCode, KeyEvent event) {
+        // to the activity, for onBackPressed
+        if (keyCode == KeyEvent.KEYCODE_BACK) {
+            return false;
+        }
+
+        // the gamepad buttons go to gamepad_event, not the keyboard ones
+        boolean dpad = keyCode >= KeyEvent.KEYCODE_DPAD_UP && keyCode <= KeyEvent.KEYCODE_DPAD_RIGHT;
+        if (isGamepad(event.getSource()) && (KeyEvent.isGamepadButton(keyCode) || dpad)) {
+            if (event.getRepeatCount() == 0 && event.getAction() != KeyEvent.ACTION_MULTIPLE) {
+                QuadNative.surfaceOnGamepadButton(event.getDeviceId(), keyCode, event.getAction() == KeyEvent.ACTION_DOWN);
+            }
+            return true;
+        }
+
         if (event.getAction() == KeyEvent.ACTION_DOWN && keyCode != 0) {
-            QuadNative.surfaceOnKeyDown(keyCode);
+            QuadNative.surfaceOnKeyDown(keyCode, event.getScanCode(), event.getMetaState(), event.getRepeatCount() > 0, event.getEventTime());
         }
 
         if (event.getAction() == KeyEvent.ACTION_UP && keyCode != 0) {
-            QuadNative.surfaceOnKeyUp(keyCode);
+            QuadNative.surfaceOnKeyUp(keyCode, event.getScanCode(), event.getMetaState(), event.getEventTime());
         }
         
         if (event.getAction() == KeyEvent.ACTION_UP || event.getAction() == KeyEvent.ACTION_MULTIPLE) {
@@ -172,6 +241,15 @@ class QuadSurface
         return true;
     }
 
+    // window::set_keyboard_type, TYPE_NULL for the key events
+    private int inputType = InputType.TYPE_NULL;
+
+    public void setInputType(int inputType) {
+        this.inputType = inputType;
+        InputMethodManager imm = (InputMethodManager)getContext().getSystemService(Context.INPUT_METHOD_SERVICE);
+        imm.restartInput(this);
+    }
+
     // There is an Android bug when screen is in landscape,
     // the keyboard inset height is reported as 0.
     // This code is a workaround which fixes the bug.
@@ -181,6 +259,7 @@ class QuadSurface
     public InputConnection onCreateInputConnection(EditorInfo outAttrs) {
         InputConnection connection = super.onCreateInputConnection(outAttrs);
         outAttrs.imeOptions |= EditorInfo.IME_FLAG_NO_FULLSCREEN;
+        outAttrs.inputType = inputType;
         return connection;
     }
 
@@ -197,16 +276,19 @@ class ResizingLayout
 
     public ResizingLayout(Context context){
         super(context);
-        // When viewing in landscape mode with keyboard shown, there are
-        // gaps on both sides so we fill the negative space with black.
         setBackgroundColor(Color.BLACK);
         setOnApplyWindowInsetsListener(this);
     }
 
+    // the keyboard is over the surface, keyboard_insets_changed tells how
+    // much of it
     @Override
     public WindowInsets onApplyWindowInsets(View v, WindowInsets insets) {
         Insets imeInsets = insets.getInsets(WindowInsets.Type.ime());
-        v.setPadding(0, 0, 0, imeInsets.bottom);
+        QuadNative.surfaceOnKeyboardInsets(imeInsets.bottom);
+        // none of the system bars while they fit the window around the surface
+        Insets safeInsets = insets.getInsets(WindowInsets.Type.systemBars() | WindowInsets.Type.displayCutout());
+        QuadNative.surfaceOnSafeAreaInsets(safeInsets.left, safeInsets.top, safeInsets.right, safeInsets.bottom);
         return insets;
     }
 }
@@ -216,6 +298,13 @@ public class MainActivity extends Activity {
 
     private QuadSurface view;
 
+    private BroadcastReceiver powerReceiver = new BroadcastReceiver() {
+        @Override
+        public void onReceive(Context context, Intent intent) {
+            powerChanged();
+        }
+    };
+
     static {
         System.loadLibrary("LIBRARY_NAME");
     }
@@ -234,9 +323,57 @@ public class MainActivity extends Activity {
 
         QuadNative.activityOnCreate(this);
 
+        // the battery one is sticky, received right away
+        IntentFilter powerFilter = new IntentFilter(Intent.ACTION_BATTERY_CHANGED);
+        powerFilter.addAction(PowerManager.ACTION_POWER_SAVE_MODE_CHANGED);
+        registerReceiver(powerReceiver, powerFilter);
+
+        // back_requested_event, the activity is finished from there. With
+        // android:enableOnBackInvokedCallback these and not onBackPressed.
+        if (Build.VERSION.SDK_INT >= 34) {
+            getOnBackInvokedDispatcher().registerOnBackInvokedCallback(
+                OnBackInvokedDispatcher.PRIORITY_DEFAULT,
+                new OnBackAnimationCallback() {
+                    @Override
+                    public void onBackStarted(BackEvent event) {
+                        QuadNative.activityOnBackProgress(0);
+                    }
+
+                    @Override
+                    public void onBackProgressed(BackEvent event) {
+                        QuadNative.activityOnBackProgress(event.getProgress());
+                    }
+
+                    @Override
+                    public void onBackCancelled() {
+                        QuadNative.activityOnBackProgress(0);
+                    }
+
+                    @Override
+                    public void onBackInvoked() {
+                        QuadNative.activityOnBackPressed();
+                    }
+                });
+        } else if (Build.VERSION.SDK_INT >= 33) {
+            getOnBackInvokedDispatcher().registerOnBackInvokedCallback(
+                OnBackInvokedDispatcher.PRIORITY_DEFAULT,
+                new OnBackInvokedCallback() {
+                    @Override
+                    public void onBackInvoked() {
+                        QuadNative.activityOnBackPressed();
+                    }
+                });
+        }
+
         //% MAIN_ACTIVITY_ON_CREATE
     }
 
+    @Override
+    protected void onStart() {
+        super.onStart();
+        QuadNative.activityOnStart();
+    }
+
     @Override
     protected void onResume() {
         super.onResume();
@@ -245,24 +382,23 @@ public class MainActivity extends Activity {
         //% MAIN_ACTIVITY_ON_RESUME
     }
 
+    // back_requested_event, the activity is finished from there
     @Override
     public void onBackPressed() {
-        Log.w("SAPP", "onBackPressed");
-
-        // TODO: here is the place to handle request_quit/order_quit/cancel_quit
-
-        super.onBackPressed();
+        QuadNative.activityOnBackPressed();
     }
 
     @Override
     protected void onStop() {
         super.onStop();
+        QuadNative.activityOnStop();
     }
 
     @Override
     protected void onDestroy() {
         super.onDestroy();
 
+        unregisterReceiver(powerReceiver);
         QuadNative.activityOnDestroy();
     }
 
@@ -274,11 +410,39 @@ public class MainActivity extends Activity {
         //% MAIN_ACTIVITY_ON_PAUSE
     }
 
+    @Override
+    protected void onSaveInstanceState(Bundle outState) {
+        super.onSaveInstanceState(outState);
+        QuadNative.activityOnSaveState();
+    }
+
+    @Override
+    public void onLowMemory() {
+        super.onLowMemory();
+        QuadNative.activityOnLowMemory();
+    }
+
+    // low in the foreground, or about to be killed in the background
+    @Override
+    public void onTrimMemory(int level) {
+        super.onTrimMemory(level);
+        if (level >= ComponentCallbacks2.TRIM_MEMORY_RUNNING_LOW && level != ComponentCallbacks2.TRIM_MEMORY_UI_HIDDEN) {
+            QuadNative.activityOnLowMemory();
+        }
+    }
+
     @Override
     protected void onActivityResult(int requestCode, int resultCode, Intent data) {
         //% MAIN_ACTIVITY_ON_ACTIVITY_RESULT
     }
 
+    // conf.platform.android_cutout_mode, for fullscreen and immersive mode
+    private int cutoutMode = LayoutParams.LAYOUT_IN_DISPLAY_CUTOUT_MODE_SHORT_EDGES;
+
+    public void setCutoutMode(int mode) {
+        cutoutMode = mode;
+    }
+
     public void setFullScreen(final boolean fullscreen) {
         runOnUiThread(new Runnable() {
                 @Override
@@ -287,7 +451,7 @@ public class MainActivity extends Activity {
 
                     if (fullscreen) {
                         getWindow().setFlags(LayoutParams.FLAG_LAYOUT_NO_LIMITS, LayoutParams.FLAG_LAYOUT_NO_LIMITS);
-                        getWindow().getAttributes().layoutInDisplayCutoutMode = LayoutParams.LAYOUT_IN_DISPLAY_CUTOUT_MODE_SHORT_EDGES;
+                        getWindow().getAttributes().layoutInDisplayCutoutMode = cutoutMode;
                         if (Build.VERSION.SDK_INT >= 30) {
                             getWindow().setDecorFitsSystemWindows(false);
                         } else {
@@ -307,7 +471,142 @@ public class MainActivity extends Activity {
             });
     }
 
-    public void showKeyboard(final boolean show) {
+    // the system bars come back over the window for a while on a swipe
+    public void setImmersive(final boolean immersive) {
+        runOnUiThread(new Runnable() {
+                @Override
+                public void run() {
+                    Window window = getWindow();
+                    if (Build.VERSION.SDK_INT >= 28) {
+                        LayoutParams params = window.getAttributes();
+                        params.layoutInDisplayCutoutMode = immersive ? cutoutMode : LayoutParams.LAYOUT_IN_DISPLAY_CUTOUT_MODE_DEFAULT;
+                        window.setAttributes(params);
+                    }
+
+                    if (Build.VERSION.SDK_INT >= 30) {
+                        window.setDecorFitsSystemWindows(!immersive);
+                        WindowInsetsController controller = window.getInsetsController();
+                        if (immersive) {
+                            controller.setSystemBarsBehavior(WindowInsetsController.BEHAVIOR_SHOW_TRANSIENT_BARS_BY_SWIPE);
+                            controller.hide(WindowInsets.Type.systemBars());
+                        } else {
+                            controller.show(WindowInsets.Type.systemBars());
+                        }
+                    } else {
+                        int uiOptions = 0;
+                        if (immersive) {
+                            uiOptions = View.SYSTEM_UI_FLAG_LAYOUT_STABLE
+                                | View.SYSTEM_UI_FLAG_LAYOUT_HIDE_NAVIGATION
+                                | View.SYSTEM_UI_FLAG_LAYOUT_FULLSCREEN
+                                | View.SYSTEM_UI_FLAG_HIDE_NAVIGATION
+                                | View.SYSTEM_UI_FLAG_FULLSCREEN
+                                | View.SYSTEM_UI_FLAG_IMMERSIVE_STICKY;
+                        }
+                        window.getDecorView().setSystemUiVisibility(uiOptions);
+                    }
+                }
+            });
+    }
+
+    public void setSystemBarsColor(final int color) {
+        runOnUiThread(new Runnable() {
+                @Override
+                public void run() {
+                    Window window = getWindow();
+                    window.addFlags(LayoutParams.FLAG_DRAWS_SYSTEM_BAR_BACKGROUNDS);
+                    window.setStatusBarColor(color);
+                    window.setNavigationBarColor(color);
+
+                    // dark icons on the light colors
+                    if (Build.VERSION.SDK_INT >= 30) {
+                        int light = WindowInsetsController.APPEARANCE_LIGHT_STATUS_BARS
+                            | WindowInsetsController.APPEARANCE_LIGHT_NAVIGATION_BARS;
+                        boolean isLight = Color.luminance(color) > 0.5f;
+                        window.getInsetsController().setSystemBarsAppearance(isLight ? light : 0, light);
+                    }
+                }
+            });
+    }
+
+    public void setKeyboardType(final int inputType) {
+        runOnUiThread(new Runnable() {
+                @Override
+                public void run() {
+                    view.setInputType(inputType);
+                }
+            });
+    }
+
+    public void setKeepScreenOn(final boolean keepOn) {
+        runOnUiThread(new Runnable() {
+                @Override
+                public void run() {
+                    if (keepOn) {
+                        getWindow().addFlags(LayoutParams.FLAG_KEEP_SCREEN_ON);
+                    } else {
+                        getWindow().clearFlags(LayoutParams.FLAG_KEEP_SCREEN_ON);
+                    }
+                }
+            });
+    }
+
+    // the BCP-47 tags, comma separated
+    // for fs::data_dir and fs::cache_dir
+    public String getStoragePath(boolean cache) {
+        java.io.File dir = cache ? getCacheDir() : getFilesDir();
+        if (dir == null) {
+            return null;
+        }
+        return dir.getAbsolutePath();
+    }
+
+    public String getLocales() {
+        if (Build.VERSION.SDK_INT >= 24) {
+            return getResources().getConfiguration().getLocales().toLanguageTags();
+        }
+        return getResources().getConfiguration().locale.toLanguageTag();
+    }
+
+    // the last ACTION_BATTERY_CHANGED and the battery saver
+    private void powerChanged() {
+        Intent battery = registerReceiver(null, new IntentFilter(Intent.ACTION_BATTERY_CHANGED));
+        boolean present = false;
+        float level = -1;
+        int status = BatteryManager.BATTERY_STATUS_UNKNOWN;
+        boolean plugged = false;
+        if (battery != null) {
+            present = battery.getBooleanExtra(BatteryManager.EXTRA_PRESENT, true);
+            int scale = battery.getIntExtra(BatteryManager.EXTRA_SCALE, -1);
+            if (scale > 0) {
+                level = battery.getIntExtra(BatteryManager.EXTRA_LEVEL, -1) / (float)scale;
+            }
+            status = battery.getIntExtra(BatteryManager.EXTRA_STATUS, status);
+            plugged = battery.getIntExtra(BatteryManager.EXTRA_PLUGGED, 0) != 0;
+        }
+        PowerManager power = (PowerManager)getSystemService(Context.POWER_SERVICE);
+        QuadNative.activityOnPowerChanged(present, level, status, plugged, power.isPowerSaveMode());
+    }
+
+    // the amplitudes from 0 to 255, a VibrationEffect has one for the
+    // vibrator, the strongest of the two
+    public void rumbleGamepad(int deviceId, int lowFrequency, int highFrequency, int duration) {
+        InputDevice device = InputDevice.getDevice(deviceId);
+        if (device == null || Build.VERSION.SDK_INT < 26) {
+            return;
+        }
+        Vibrator vibrator = device.getVibrator();
+        if (!vibrator.hasVibrator()) {
+            return;
+        }
+        int amplitude = Math.max(lowFrequency, highFrequency);
+        if (amplitude == 0 || duration == 0) {
+            vibrator.cancel();
+        } else {
+            vibrator.vibrate(VibrationEffect.createOneShot(duration, amplitude));
+        }
+    }
+
+        public void showKeyboard(final boolean show) {
         runOnUiThread(new Runnable() {
                 @Override
                 public void run() {
@@ -9,16 +9,29 @@ import android.view.Surface;
 public class QuadNative {
     // belongs to MainActivity class
     public native static void activityOnCreate(Object activity);
+    public native static void activityOnStart();
+    public native static void activityOnStop();
     public native static void activityOnResume();
     public native static void activityOnPause();
     public native static void activityOnDestroy();
+    public native static void activityOnLowMemory();
+    public native static void activityOnSaveState();
+    public native static void activityOnBackPressed();
+    public native static void activityOnBackProgress(float progress);
+    public native static void activityOnPowerChanged(boolean present, float level, int status, boolean plugged, boolean powerSaver);
 
     // belongs to QuadSurface class
     public native static void surfaceOnSurfaceCreated(Surface surface);
     public native static void surfaceOnSurfaceDestroyed(Surface surface);
-    public native static void surfaceOnTouch(int id, int phase, float x, float y);
+    public native static void surfaceOnTouch(int id, int phase, float x, float y, float pressure, float touchMajor, float touchMinor, int deviceId, long eventTime);
     public native static void surfaceOnSurfaceChanged(Surface surface, int width, int height);
-    public native static void surfaceOnKeyDown(int keycode);
-    public native static void surfaceOnKeyUp(int keycode);
+    public native static void surfaceOnKeyDown(int keycode, int scancode, int metaState, boolean repeat, long eventTime);
+    public native static void surfaceOnKeyUp(int keycode, int scancode, int metaState, long eventTime);
     public native static void surfaceOnCharacter(int character);
+    public native static void surfaceOnKeyboardInsets(int height);
+    public native static void surfaceOnSafeAreaInsets(int left, int top, int right, int bottom);
+    public native static void surfaceOnGamepadConnected(int deviceId, String name);
+    public native static void surfaceOnGamepadDisconnected(int deviceId);
+    public native static void surfaceOnGamepadButton(int deviceId, int keycode, boolean down);
+    public native static void surfaceOnGamepadAxes(int deviceId, float x, float y, float z, float rz, float hatX, float hatY, float leftTrigger, float rightTrigger);
 }
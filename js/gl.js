@@ -11,12 +11,42 @@
 const version = "0.3.12";
 
 const canvas = document.querySelector("#glcanvas");
-const gl = canvas.getContext("webgl");
-if (gl === null) {
-    alert("Unable to initialize WebGL. Your browser or machine may not support it.");
-}
+// created by init_webgl, called by the wasm side with the version from Conf
+var gl = null;
+var webgl_version = 1;
+var timer_query_supported = false;
 
 var clipboard = null;
+// css "cursor" value of the last set_mouse_cursor_from_rgba
+var custom_cursor = "default";
+// navigator.getGamepads(), taken again every frame by sapp_gamepads_poll
+var gamepads = [];
+// the screen Wake Lock of sapp_set_keep_screen_on, released by the browser
+// while the page is hidden and requested again when it is visible
+var keep_screen_on = false;
+var wake_lock = null;
+
+function request_wake_lock() {
+    if (!navigator.wakeLock || wake_lock != null) {
+        return;
+    }
+    navigator.wakeLock.request("screen").then(function (lock) {
+        if (!keep_screen_on) {
+            lock.release();
+            return;
+        }
+        wake_lock = lock;
+        lock.addEventListener("release", function () {
+            wake_lock = null;
+        });
+    }).catch(function () { });
+}
+
+document.addEventListener("visibilitychange", function () {
+    if (keep_screen_on && document.visibilityState == "visible") {
+        request_wake_lock();
+    }
+});
 
 var plugins = [];
 var wasm_memory;
@@ -69,28 +99,69 @@ function acquireInstancedArraysExtension(ctx) {
 function acquireDisjointTimerQueryExtension(ctx) {
     var ext = ctx.getExtension('EXT_disjoint_timer_query');
     if (ext) {
+        timer_query_supported = true;
         ctx['createQuery'] = function () { return ext['createQueryEXT'](); };
         ctx['beginQuery'] = function (target, query) { return ext['beginQueryEXT'](target, query); };
         ctx['endQuery'] = function (target) { return ext['endQueryEXT'](target); };
         ctx['deleteQuery'] = function (query) { ext['deleteQueryEXT'](query); };
         ctx['getQueryObject'] = function (query, pname) { return ext['getQueryObjectEXT'](query, pname); };
+        ctx['queryCounter'] = function (query, target) { ext['queryCounterEXT'](query, target); };
     }
 }
 
-try {
-    gl.getExtension("EXT_shader_texture_lod");
-    gl.getExtension("OES_standard_derivatives");
-} catch (e) {
-    console.warn(e);
+function acquireDisjointTimerQueryWebGL2Extension(ctx) {
+    // queries are core in WebGL2, the extension only adds TIME_ELAPSED and TIMESTAMP
+    var ext = ctx.getExtension('EXT_disjoint_timer_query_webgl2');
+    if (ext) {
+        timer_query_supported = true;
+        ctx['getQueryObject'] = function (query, pname) { return ctx.getQueryParameter(query, pname); };
+        ctx['queryCounter'] = function (query, target) { ext['queryCounterEXT'](query, target); };
+    }
 }
 
-acquireVertexArrayObjectExtension(gl);
-acquireInstancedArraysExtension(gl);
-acquireDisjointTimerQueryExtension(gl);
+function init_webgl(version) {
+    if (version == 2) {
+        gl = canvas.getContext("webgl2");
+        if (gl === null) {
+            console.warn("WebGL2 is not supported, falling back to WebGL1");
+        } else {
+            webgl_version = 2;
+        }
+    }
+    if (gl === null) {
+        gl = canvas.getContext("webgl");
+    }
+    if (gl === null) {
+        alert("Unable to initialize WebGL. Your browser or machine may not support it.");
+        return;
+    }
 
-// https://developer.mozilla.org/en-US/docs/Web/API/WEBGL_depth_texture
-if (gl.getExtension('WEBGL_depth_texture') == null) {
-    alert("Cant initialize WEBGL_depth_texture extension");
+    acquire_extensions();
+}
+
+// extensions belong to the context, so this is repeated after a context restore
+function acquire_extensions() {
+    if (webgl_version == 2) {
+        // vertex arrays, instancing and depth textures are core in WebGL2
+        acquireDisjointTimerQueryWebGL2Extension(gl);
+        return;
+    }
+
+    try {
+        gl.getExtension("EXT_shader_texture_lod");
+        gl.getExtension("OES_standard_derivatives");
+    } catch (e) {
+        console.warn(e);
+    }
+
+    acquireVertexArrayObjectExtension(gl);
+    acquireInstancedArraysExtension(gl);
+    acquireDisjointTimerQueryExtension(gl);
+
+    // https://developer.mozilla.org/en-US/docs/Web/API/WEBGL_depth_texture
+    if (gl.getExtension('WEBGL_depth_texture') == null) {
+        alert("Cant initialize WEBGL_depth_texture extension");
+    }
 }
 
 function getArray(ptr, arr, n) {
@@ -139,6 +210,20 @@ function UTF8ToString(ptr, maxBytesToRead) {
     return str;
 }
 
+// write WebGLActiveInfo into glGetActiveUniform/glGetActiveAttrib out pointers
+function writeActiveInfo(info, bufSize, length, size, type, name) {
+    if (info == null) {
+        getArray(length, Int32Array, 1)[0] = 0;
+        return;
+    }
+    let heap = getArray(name, Uint8Array, bufSize);
+    let written = stringToUTF8(info.name, heap, 0, bufSize - 1);
+    heap[written] = 0;
+    getArray(length, Int32Array, 1)[0] = written;
+    getArray(size, Int32Array, 1)[0] = info.size;
+    getArray(type, Uint32Array, 1)[0] = info.type;
+}
+
 function stringToUTF8(str, heap, outIdx, maxBytesToWrite) {
     var startIdx = outIdx;
     var endIdx = outIdx + maxBytesToWrite;
@@ -428,6 +513,46 @@ const SAPP_MODIFIER_SHIFT = 1;
 const SAPP_MODIFIER_CTRL = 2;
 const SAPP_MODIFIER_ALT = 4;
 const SAPP_MODIFIER_SUPER = 8;
+const SAPP_MODIFIER_CAPS_LOCK = 16;
+const SAPP_MODIFIER_NUM_LOCK = 32;
+
+// of a KeyboardEvent, MouseEvent or WheelEvent
+function get_modifiers(event) {
+    var modifiers = 0;
+    if (event.ctrlKey) {
+        modifiers |= SAPP_MODIFIER_CTRL;
+    }
+    if (event.shiftKey) {
+        modifiers |= SAPP_MODIFIER_SHIFT;
+    }
+    if (event.altKey) {
+        modifiers |= SAPP_MODIFIER_ALT;
+    }
+    if (event.metaKey) {
+        modifiers |= SAPP_MODIFIER_SUPER;
+    }
+    if (event.getModifierState("CapsLock")) {
+        modifiers |= SAPP_MODIFIER_CAPS_LOCK;
+    }
+    if (event.getModifierState("NumLock")) {
+        modifiers |= SAPP_MODIFIER_NUM_LOCK;
+    }
+    return modifiers;
+}
+
+// window::event_timestamp, before the input events
+function event_timestamp(event) {
+    wasm_exports.event_timestamp(event.timeStamp / 1000.0);
+}
+
+// of a PointerEvent, buttons 1 is the tip, 2 the barrel button, and 32 the eraser
+function pen_event(event) {
+    if (event.pointerType == "pen") {
+        event_timestamp(event);
+        var relative_position = mouse_relative_position(event.clientX, event.clientY);
+        wasm_exports.pen(relative_position.x, relative_position.y, event.pressure, event.tiltX, event.tiltY, event.buttons);
+    }
+}
 
 function into_sapp_mousebutton(btn) {
     switch (btn) {
@@ -592,6 +717,44 @@ function mouse_relative_position(clientX, clientY) {
     return { x, y };
 }
 
+// window::lock_orientation, 0 for any, 1 for portrait and 2 for landscape.
+// The browsers lock it only in fullscreen, so again on every fullscreen.
+var orientation_lock = 0;
+
+function lock_orientation() {
+    if (screen.orientation == undefined || screen.orientation.lock == undefined) {
+        return;
+    }
+    if (orientation_lock == 0) {
+        screen.orientation.unlock();
+    } else {
+        screen.orientation.lock(orientation_lock == 1 ? "portrait" : "landscape").catch(function () {});
+    }
+}
+document.addEventListener("fullscreenchange", lock_orientation);
+
+// env(safe-area-inset-*) is only readable from a style, this div has them as
+// its padding
+var safe_area = null;
+
+// the safe area of the viewport, for the part of the canvas in it
+function safe_area_changed() {
+    if (safe_area == null) {
+        safe_area = document.createElement("div");
+        safe_area.style.cssText = "position: fixed; visibility: hidden; pointer-events: none; " +
+            "padding: env(safe-area-inset-top) env(safe-area-inset-right) env(safe-area-inset-bottom) env(safe-area-inset-left);";
+        document.body.appendChild(safe_area);
+    }
+    var style = getComputedStyle(safe_area);
+    var rect = canvas.getBoundingClientRect();
+    var dpr = dpi_scale();
+    wasm_exports.safe_area_changed(
+        Math.max(0, parseFloat(style.paddingLeft) - rect.left) * dpr,
+        Math.max(0, parseFloat(style.paddingTop) - rect.top) * dpr,
+        Math.max(0, parseFloat(style.paddingRight) - (window.innerWidth - rect.right)) * dpr,
+        Math.max(0, parseFloat(style.paddingBottom) - (window.innerHeight - rect.bottom)) * dpr);
+}
+
 var emscripten_shaders_hack = false;
 
 var importObject = {
@@ -624,6 +787,21 @@ var importObject = {
         now: function () {
             return Date.now() / 1000.0;
         },
+        // the clock of event.timeStamp
+        sapp_timestamp_now: function () {
+            return performance.now() / 1000.0;
+        },
+        sapp_dark_theme: function () {
+            return window.matchMedia != undefined && window.matchMedia("(prefers-color-scheme: dark)").matches;
+        },
+        sapp_locale: function (ptr, len) {
+            var languages = navigator.languages || [navigator.language];
+            var tags = new TextEncoder().encode(languages.join(","));
+            if (tags.length <= len) {
+                new Uint8Array(wasm_memory.buffer, ptr, tags.length).set(tags);
+            }
+            return tags.length;
+        },
         canvas_width: function () {
             return Math.floor(canvas.width);
         },
@@ -639,6 +817,18 @@ var importObject = {
         glClearStencil: function (s) {
             gl.clearStencil(s);
         },
+        glClearBufferfv: function (buffer, drawbuffer, value) {
+            gl.clearBufferfv(buffer, drawbuffer, getArray(value, Float32Array, 4));
+        },
+        glDrawBuffers: function (n, bufs) {
+            gl.drawBuffers(Array.from(getArray(bufs, Uint32Array, n)));
+        },
+        glReadBuffer: function (src) {
+            gl.readBuffer(src);
+        },
+        glInvalidateFramebuffer: function (target, numAttachments, attachments) {
+            gl.invalidateFramebuffer(target, Array.from(getArray(attachments, Uint32Array, numAttachments)));
+        },
         glColorMask: function (red, green, blue, alpha) {
             gl.colorMask(red, green, blue, alpha);
         },
@@ -774,10 +964,16 @@ var importObject = {
                 return -1;
             }
         },
+        glUniformMatrix3fv: function (location, count, transpose, value) {
+            GL.validateGLObjectID(GL.uniforms, location, 'glUniformMatrix3fv', 'location');
+            assert((value & 3) == 0, 'Pointer to float data passed to glUniformMatrix3fv must be aligned to four bytes!');
+            var view = getArray(value, Float32Array, 9 * count);
+            gl.uniformMatrix3fv(GL.uniforms[location], !!transpose, view);
+        },
         glUniformMatrix4fv: function (location, count, transpose, value) {
             GL.validateGLObjectID(GL.uniforms, location, 'glUniformMatrix4fv', 'location');
             assert((value & 3) == 0, 'Pointer to float data passed to glUniformMatrix4fv must be aligned to four bytes!');
-            var view = getArray(value, Float32Array, 16);
+            var view = getArray(value, Float32Array, 16 * count);
             gl.uniformMatrix4fv(GL.uniforms[location], !!transpose, view);
         },
         glUseProgram: function (program) {
@@ -961,7 +1157,25 @@ var importObject = {
                 array[i] = log.charCodeAt(i);
             }
         },
+        glGetActiveUniform: function (program, index, bufSize, length, size, type, name) {
+            GL.validateGLObjectID(GL.programs, program, 'glGetActiveUniform', 'program');
+            var info = gl.getActiveUniform(GL.programs[program], index);
+            writeActiveInfo(info, bufSize, length, size, type, name);
+        },
+        glGetActiveAttrib: function (program, index, bufSize, length, size, type, name) {
+            GL.validateGLObjectID(GL.programs, program, 'glGetActiveAttrib', 'program');
+            var info = gl.getActiveAttrib(GL.programs[program], index);
+            writeActiveInfo(info, bufSize, length, size, type, name);
+        },
         glGetString: function (id) {
+            // VENDOR and RENDERER are always "WebKit"/"WebKit WebGL" or similar,
+            // the real ones are behind an extension, when the browser allows it
+            if (id == 0x1F00 || id == 0x1F01) {
+                var ext = gl.getExtension('WEBGL_debug_renderer_info');
+                if (ext) {
+                    id = id == 0x1F00 ? ext.UNMASKED_VENDOR_WEBGL : ext.UNMASKED_RENDERER_WEBGL;
+                }
+            }
             // getParameter returns "any": it could be GLenum, String or whatever,
             // depending on the id.
             var parameter = gl.getParameter(id).toString();
@@ -1005,6 +1219,24 @@ var importObject = {
         glVertexAttribDivisor: function (index, divisor) {
             gl.vertexAttribDivisor(index, divisor);
         },
+        glTransformFeedbackVaryings: function (program, count, varyings, bufferMode) {
+            GL.validateGLObjectID(GL.programs, program, 'glTransformFeedbackVaryings', 'program');
+            var names = [];
+            for (var i = 0; i < count; i++) {
+                names.push(UTF8ToString(getArray(varyings + i * 4, Uint32Array, 1)[0]));
+            }
+            gl.transformFeedbackVaryings(GL.programs[program], names, bufferMode);
+        },
+        glBindBufferBase: function (target, index, buffer) {
+            GL.validateGLObjectID(GL.buffers, buffer, 'glBindBufferBase', 'buffer');
+            gl.bindBufferBase(target, index, GL.buffers[buffer]);
+        },
+        glBeginTransformFeedback: function (primitiveMode) {
+            gl.beginTransformFeedback(primitiveMode);
+        },
+        glEndTransformFeedback: function () {
+            gl.endTransformFeedback();
+        },
         glDrawArraysInstanced: function (mode, first, count, primcount) {
             gl.drawArraysInstanced(mode, first, count, primcount);
         },
@@ -1060,7 +1292,7 @@ var importObject = {
         },
         glDeleteQueries: function (n, ids) {
             for (var i = 0; i < n; i++) {
-                var id = getArray(textures + i * 4, Uint32Array, 1)[0];
+                var id = getArray(ids + i * 4, Uint32Array, 1)[0];
                 var query = GL.timerQueries[id];
                 if (!query) {
                     continue;
@@ -1077,6 +1309,10 @@ var importObject = {
         glEndQuery: function (target) {
             gl.endQuery(target);
         },
+        glQueryCounter: function (id, target) {
+            GL.validateGLObjectID(GL.timerQueries, id, 'glQueryCounter', 'id');
+            gl.queryCounter(GL.timerQueries[id], target);
+        },
         glGetQueryObjectiv: function (id, pname, ptr) {
             GL.validateGLObjectID(GL.timerQueries, id, 'glGetQueryObjectiv', 'id');
             let result = gl.getQueryObject(GL.timerQueries[id], pname);
@@ -1099,41 +1335,69 @@ var importObject = {
         },
         run_animation_loop: function (ptr) {
             canvas.onmousemove = function (event) {
+                event_timestamp(event);
                 var relative_position = mouse_relative_position(event.clientX, event.clientY);
                 var x = relative_position.x;
                 var y = relative_position.y;
 
                 // TODO: do not send mouse_move when cursor is captured
-                wasm_exports.mouse_move(Math.floor(x), Math.floor(y));
+                wasm_exports.mouse_move(Math.floor(x), Math.floor(y), get_modifiers(event));
 
                 // TODO: check that mouse is captured?
                 if (event.movementX != 0 || event.movementY != 0) {
                     wasm_exports.raw_mouse_move(Math.floor(event.movementX), Math.floor(event.movementY));
                 }
             };
+            // the mouse events of a drag keep coming to the canvas when it
+            // leaves it, captured until the button is up
+            canvas.onpointerdown = function (event) {
+                if (event.pointerType == "mouse") {
+                    canvas.setPointerCapture(event.pointerId);
+                }
+                pen_event(event);
+            };
+            // pens also get the compatibility mouse events
+            canvas.onpointermove = pen_event;
+            canvas.onpointerup = pen_event;
+            canvas.onmouseenter = function (event) {
+                event_timestamp(event);
+                wasm_exports.mouse_enter();
+            };
+            canvas.onmouseleave = function (event) {
+                event_timestamp(event);
+                wasm_exports.mouse_leave();
+            };
             canvas.onmousedown = function (event) {
+                event_timestamp(event);
                 var relative_position = mouse_relative_position(event.clientX, event.clientY);
                 var x = relative_position.x;
                 var y = relative_position.y;
 
                 var btn = into_sapp_mousebutton(event.button);
-                wasm_exports.mouse_down(x, y, btn);
+                wasm_exports.mouse_down(x, y, btn, get_modifiers(event));
             };
             // SO WEB SO CONSISTENT
             canvas.addEventListener('wheel',
                 function (event) {
                     event.preventDefault();
-                    wasm_exports.mouse_wheel(-event.deltaX, -event.deltaY);
+                    event_timestamp(event);
+                    wasm_exports.mouse_wheel(-event.deltaX, -event.deltaY, event.deltaMode, get_modifiers(event));
                 });
             canvas.onmouseup = function (event) {
+                event_timestamp(event);
                 var relative_position = mouse_relative_position(event.clientX, event.clientY);
                 var x = relative_position.x;
                 var y = relative_position.y;
 
                 var btn = into_sapp_mousebutton(event.button);
-                wasm_exports.mouse_up(x, y, btn);
+                wasm_exports.mouse_up(x, y, btn, get_modifiers(event));
+                // back and forward would leave the page
+                if (event.button == 3 || event.button == 4) {
+                    event.preventDefault();
+                }
             };
             canvas.onkeydown = function (event) {
+                event_timestamp(event);
                 var sapp_key_code = into_sapp_keycode(event.code);
                 switch (sapp_key_code) {
                     //  space, arrows - prevent scrolling of the page
@@ -1146,44 +1410,35 @@ var importObject = {
                     case 258:
                     // quote and slash are Quick Find on Firefox
                     case 39: case 47:
-                        event.preventDefault();
+                        // a dead key on some layouts, the browser composes the next keypress
+                        if (event.key != "Dead") {
+                            event.preventDefault();
+                        }
                         break;
                 }
 
-                var modifiers = 0;
-                if (event.ctrlKey) {
-                    modifiers |= SAPP_MODIFIER_CTRL;
-                }
-                if (event.shiftKey) {
-                    modifiers |= SAPP_MODIFIER_SHIFT;
-                }
-                if (event.altKey) {
-                    modifiers |= SAPP_MODIFIER_ALT;
-                }
+                var modifiers = get_modifiers(event);
                 wasm_exports.key_down(sapp_key_code, modifiers, event.repeat);
                 // for "space", "quote", and "slash" preventDefault will prevent
-                // key_press event, so send it here instead
+                // key_press event, so send it here instead, the character the
+                // layout has on the key, not the US one
                 if (sapp_key_code == 32 || sapp_key_code == 39 || sapp_key_code == 47) {
-                    wasm_exports.key_press(sapp_key_code);
+                    var chars = Array.from(event.key);
+                    if (chars.length == 1 && !event.ctrlKey) {
+                        wasm_exports.key_press(chars[0].codePointAt(0));
+                    }
                 }
             };
             canvas.onkeyup = function (event) {
+                event_timestamp(event);
                 var sapp_key_code = into_sapp_keycode(event.code);
 
-                var modifiers = 0;
-                if (event.ctrlKey) {
-                    modifiers |= SAPP_MODIFIER_CTRL;
-                }
-                if (event.shiftKey) {
-                    modifiers |= SAPP_MODIFIER_SHIFT;
-                }
-                if (event.altKey) {
-                    modifiers |= SAPP_MODIFIER_ALT;
-                }
+                var modifiers = get_modifiers(event);
 
                 wasm_exports.key_up(sapp_key_code, modifiers);
             };
             canvas.onkeypress = function (event) {
+                event_timestamp(event);
                 var sapp_key_code = into_sapp_keycode(event.code);
 
                 // firefox do not send onkeypress events for ctrl+keys and delete key while chrome do
@@ -1196,40 +1451,79 @@ var importObject = {
 
             canvas.addEventListener("touchstart", function (event) {
                 event.preventDefault();
+                event_timestamp(event);
 
                 for (const touch of event.changedTouches) {
                     let relative_position = mouse_relative_position(touch.clientX, touch.clientY);
-                    wasm_exports.touch(SAPP_EVENTTYPE_TOUCHES_BEGAN, touch.identifier, relative_position.x, relative_position.y);
+                    wasm_exports.touch(SAPP_EVENTTYPE_TOUCHES_BEGAN, touch.identifier, relative_position.x, relative_position.y, touch.force || 0, touch.radiusX || 0, touch.radiusY || 0);
                 }
             });
             canvas.addEventListener("touchend", function (event) {
                 event.preventDefault();
+                event_timestamp(event);
 
                 for (const touch of event.changedTouches) {
                     let relative_position = mouse_relative_position(touch.clientX, touch.clientY);
-                    wasm_exports.touch(SAPP_EVENTTYPE_TOUCHES_ENDED, touch.identifier, relative_position.x, relative_position.y);
+                    wasm_exports.touch(SAPP_EVENTTYPE_TOUCHES_ENDED, touch.identifier, relative_position.x, relative_position.y, touch.force || 0, touch.radiusX || 0, touch.radiusY || 0);
                 }
             });
             canvas.addEventListener("touchcancel", function (event) {
                 event.preventDefault();
+                event_timestamp(event);
 
                 for (const touch of event.changedTouches) {
                     let relative_position = mouse_relative_position(touch.clientX, touch.clientY);
-                    wasm_exports.touch(SAPP_EVENTTYPE_TOUCHES_CANCELED, touch.identifier, relative_position.x, relative_position.y);
+                    wasm_exports.touch(SAPP_EVENTTYPE_TOUCHES_CANCELED, touch.identifier, relative_position.x, relative_position.y, touch.force || 0, touch.radiusX || 0, touch.radiusY || 0);
                 }
             });
             canvas.addEventListener("touchmove", function (event) {
                 event.preventDefault();
+                event_timestamp(event);
 
                 for (const touch of event.changedTouches) {
                     let relative_position = mouse_relative_position(touch.clientX, touch.clientY);
-                    wasm_exports.touch(SAPP_EVENTTYPE_TOUCHES_MOVED, touch.identifier, relative_position.x, relative_position.y);
+                    wasm_exports.touch(SAPP_EVENTTYPE_TOUCHES_MOVED, touch.identifier, relative_position.x, relative_position.y, touch.force || 0, touch.radiusX || 0, touch.radiusY || 0);
                 }
             });
 
+            // a background tab, or a minimized browser window
+            document.addEventListener("visibilitychange", function () {
+                wasm_exports.occluded(document.hidden);
+            });
+            if (document.hidden) {
+                wasm_exports.occluded(true);
+            }
+
+            if (window.matchMedia != undefined) {
+                window.matchMedia("(prefers-color-scheme: dark)").addEventListener("change", function (event) {
+                    wasm_exports.theme_changed(event.matches);
+                });
+            }
+
+            // Chromium only
+            if (navigator.getBattery != undefined) {
+                navigator.getBattery().then(function (battery) {
+                    var power_changed = function () {
+                        wasm_exports.power_changed(battery.level, battery.charging);
+                    };
+                    power_changed();
+                    battery.addEventListener("levelchange", power_changed);
+                    battery.addEventListener("chargingchange", power_changed);
+                });
+            }
+
+            // cancel_quit has the browser ask before leaving the page
+            window.addEventListener("beforeunload", function (event) {
+                if (wasm_exports.quit_requested()) {
+                    event.preventDefault();
+                    event.returnValue = "";
+                }
+            });
             window.onresize = function () {
                 resize(canvas, wasm_exports.resize);
+                safe_area_changed();
             };
+            safe_area_changed();
             window.addEventListener("copy", function (e) {
                 if (clipboard != null) {
                     event.clipboardData.setData('text/plain', clipboard);
@@ -1262,8 +1556,30 @@ var importObject = {
                 e.preventDefault();
             };
 
+            // dragenter/dragleave fire for every element under the cursor,
+            // the counter is back to 0 only when the drag left the page
+            var drag_depth = 0;
+            window.ondragenter = function (e) {
+                if (!e.dataTransfer.types.includes("Files")) {
+                    return;
+                }
+                if (drag_depth++ == 0) {
+                    wasm_exports.on_files_hovered();
+                }
+            };
+
+            window.ondragleave = function (e) {
+                if (!e.dataTransfer.types.includes("Files")) {
+                    return;
+                }
+                if (--drag_depth == 0) {
+                    wasm_exports.on_files_hover_cancelled();
+                }
+            };
+
             window.ondrop = async function (e) {
                 e.preventDefault();
+                drag_depth = 0;
 
                 wasm_exports.on_files_dropped_start();
 
@@ -1288,7 +1604,7 @@ var importObject = {
             let lastFocus = document.hasFocus();
             var checkFocus = function () {
                 let hasFocus = document.hasFocus();
-                if (lastFocus == hasFocus) {
+                if (lastFocus != hasFocus) {
                     wasm_exports.focus(hasFocus);
                     lastFocus = hasFocus;
                 }
@@ -1297,6 +1613,16 @@ var importObject = {
             window.addEventListener("focus", checkFocus);
             window.addEventListener("blur", checkFocus);
 
+            canvas.addEventListener("webglcontextlost", function (e) {
+                // without preventDefault the context is never restored
+                e.preventDefault();
+                wasm_exports.context_lost();
+            });
+            canvas.addEventListener("webglcontextrestored", function (e) {
+                acquire_extensions();
+                wasm_exports.context_restored();
+            });
+
             window.requestAnimationFrame(animation);
         },
 
@@ -1346,7 +1672,14 @@ var importObject = {
         },
         sapp_set_cursor_grab: function (grab) {
             if (grab) {
-                canvas.requestPointerLock();
+                // unadjustedMovement disables the OS acceleration for movementX/Y,
+                // not supported everywhere, fallback to the plain pointer lock
+                var lock = canvas.requestPointerLock({ unadjustedMovement: true });
+                if (lock && lock.catch) {
+                    lock.catch(function () {
+                        canvas.requestPointerLock();
+                    });
+                }
             } else {
                 document.exitPointerLock();
             }
@@ -1354,6 +1687,27 @@ var importObject = {
         sapp_set_cursor: function (ptr, len) {
             canvas.style.cursor = UTF8ToString(ptr, len);
         },
+        sapp_create_custom_cursor: function (ptr, width, height, hotspot_x, hotspot_y) {
+            var cursor_canvas = document.createElement("canvas");
+            cursor_canvas.width = width;
+            cursor_canvas.height = height;
+            var pixels = new Uint8ClampedArray(wasm_memory.buffer, ptr, width * height * 4);
+            // copy out of the wasm memory, ImageData does not accept views into shared memory
+            cursor_canvas.getContext("2d").putImageData(new ImageData(new Uint8ClampedArray(pixels), width, height), 0, 0);
+            custom_cursor = "url(" + cursor_canvas.toDataURL() + ") " + hotspot_x + " " + hotspot_y + ", auto";
+        },
+        sapp_set_custom_cursor: function () {
+            canvas.style.cursor = custom_cursor;
+        },
+        sapp_is_elapsed_timer_supported: function () {
+            return timer_query_supported;
+        },
+        init_webgl: function (version) {
+            init_webgl(version);
+        },
+        webgl_version: function () {
+            return webgl_version;
+        },
         sapp_is_fullscreen: function () {
             let fullscreenElement = document.fullscreenElement;
 
@@ -1366,10 +1720,68 @@ var importObject = {
                 canvas.requestFullscreen();
             }
         },
+        sapp_lock_orientation: function (orientation) {
+            orientation_lock = orientation;
+            lock_orientation();
+        },
         sapp_set_window_size: function (new_width, new_height) {
             canvas.width = new_width;
             canvas.height = new_height;
             resize(canvas, wasm_exports.resize);
+        },
+        sapp_gamepads_poll: function () {
+            gamepads = navigator.getGamepads ? Array.from(navigator.getGamepads()) : [];
+            return gamepads.length;
+        },
+        // the other mappings have no known layout
+        sapp_gamepad_connected: function (index) {
+            var gamepad = gamepads[index];
+            return gamepad != null && gamepad.connected && gamepad.mapping == "standard";
+        },
+        sapp_gamepad_button: function (index, button) {
+            var gamepad_button = gamepads[index].buttons[button];
+            return gamepad_button != undefined && gamepad_button.pressed;
+        },
+        sapp_gamepad_button_value: function (index, button) {
+            var gamepad_button = gamepads[index].buttons[button];
+            return gamepad_button != undefined ? gamepad_button.value : 0.0;
+        },
+        sapp_gamepad_axis: function (index, axis) {
+            var value = gamepads[index].axes[axis];
+            return value != undefined ? value : 0.0;
+        },
+        // the utf-8 length of the id, written to ptr if it fits in len
+        sapp_gamepad_id: function (index, ptr, len) {
+            var id = new TextEncoder().encode(gamepads[index].id);
+            if (id.length <= len) {
+                new Uint8Array(wasm_memory.buffer, ptr, id.length).set(id);
+            }
+            return id.length;
+        },
+        // playEffect with 0 and 0 would not stop the effect playing
+        sapp_gamepad_rumble: function (index, low_frequency, high_frequency, duration) {
+            var gamepad = gamepads[index];
+            if (gamepad == null || !gamepad.vibrationActuator) {
+                return;
+            }
+            if (low_frequency == 0 && high_frequency == 0) {
+                gamepad.vibrationActuator.reset();
+            } else {
+                gamepad.vibrationActuator.playEffect("dual-rumble", {
+                    duration: duration,
+                    strongMagnitude: low_frequency,
+                    weakMagnitude: high_frequency
+                });
+            }
+        },
+        sapp_set_keep_screen_on: function (keep_on) {
+            keep_screen_on = keep_on;
+            if (keep_on) {
+                request_wake_lock();
+            } else if (wake_lock != null) {
+                wake_lock.release();
+                wake_lock = null;
+            }
         }
     }
 };
@@ -39,6 +39,9 @@
 //! dpi_scale   -> 1.0
 //! ```
 
+/// The environment variable `MINIQUAD_X11_GL`, "glx" or "egl", takes the
+/// place of `Platform::linux_x11_gl`, for the users of a driver broken
+/// with one of them.
 #[derive(Debug)]
 pub enum LinuxX11Gl {
     /// Use libGLX.so/libGLX.so.0 and its funciton for creating OpenGL context
@@ -54,6 +57,16 @@ pub enum LinuxX11Gl {
     EGLWithGLXFallback,
 }
 
+/// The GPU of the hybrid graphics laptops on Linux, see `Platform::linux_gpu`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LinuxGpu {
+    /// The one the driver picks, usually the integrated one.
+    Default,
+    Integrated,
+    /// The discrete one, with PRIME render offload.
+    Discrete,
+}
+
 #[derive(Debug)]
 pub enum LinuxBackend {
     X11Only,
@@ -62,12 +75,81 @@ pub enum LinuxBackend {
     WaylandWithX11Fallback,
 }
 
+#[derive(Debug)]
+pub enum WindowsGl {
+    /// Create OpenGL context with opengl32.dll and WGL.
+    /// The default option.
+    WGLOnly,
+    /// Create OpenGL ES context with libEGL.dll, usually ANGLE's libEGL.dll
+    /// and libGLESv2.dll shipped next to the executable.
+    /// If there is no libEGL.dll or it fails to create a context - use WGL.
+    EGLWithWGLFallback,
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum AppleGfxApi {
     OpenGl,
     Metal,
 }
 
+/// What the main loop does while the window is occluded, see
+/// `EventHandler::window_occluded`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BackgroundMode {
+    /// `update` and `draw` go on as usual. Browsers and iOS stop the frames
+    /// of hidden apps anyway, whatever the mode, and Android has no `draw`
+    /// without a surface.
+    Continue,
+    /// `update` and `draw` at most this many times per second.
+    Throttle(u32),
+    /// No `update` and `draw` until the window is visible again, the
+    /// events are still looked at 10 times per second.
+    Pause,
+}
+
+/// The orientations of the screen, see `Conf::supported_orientations`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Orientation {
+    /// Every orientation the device allows.
+    Any,
+    /// Taller than wide, upside down too where the device allows it.
+    Portrait,
+    /// Wider than tall, either way by the sensor.
+    Landscape,
+}
+
+/// Where the window goes around the display cutout on Android, in
+/// fullscreen and immersive mode. LayoutParams.layoutInDisplayCutoutMode.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AndroidCutoutMode {
+    /// Into the cutout only when it is within a system bar.
+    Default,
+    /// Into the cutout on the short edges of the screen, in both orientations.
+    ShortEdges,
+    /// Never into the cutout, the area next to it stays black.
+    Never,
+    /// Into the cutouts on every edge, from Android 11.
+    Always,
+}
+
+/// OpenGL ES version to ask for on EGL platforms and on web, where
+/// GLES2 means WebGL1 and GLES3 means WebGL2.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GlesVersion {
+    Gles2,
+    /// Falls back to GLES2/WebGL1 if the context could not be created.
+    /// Check `ctx.info()` for the version actually created.
+    Gles3,
+}
+
+/// The desktop OpenGL profile of WGL and GLX, see `Platform::gl_profile`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GlProfile {
+    Core,
+    /// With the deprecated functions of before 3.2, where the driver has it.
+    Compatibility,
+}
+
 /// Platform specific settings.
 #[derive(Debug)]
 pub struct Platform {
@@ -76,6 +158,25 @@ pub struct Platform {
     /// try EGL. If EGL also fails - panic.
     pub linux_x11_gl: LinuxX11Gl,
 
+    /// Which GPU renders on X11 and Wayland, from the PRIME variables read
+    /// when libGL and libEGL are loaded: DRI_PRIME for Mesa, and with the
+    /// NVIDIA driver __NV_PRIME_RENDER_OFFLOAD and __GLX_VENDOR_LIBRARY_NAME.
+    /// The ones already in the environment are kept, so the users can still
+    /// choose. `ContextInfo::renderer` tells which one it was.
+    ///
+    /// NOTE: the environment can only be changed safely while there is no
+    /// other thread. When threads were spawned before `start`, this is
+    /// ignored and the variables have to be exported before running the
+    /// program instead.
+    ///
+    /// Defaults to LinuxGpu::Default.
+    pub linux_gpu: LinuxGpu,
+
+    /// On Windows the context could be created either with WGL or EGL,
+    /// EGL being useful with ANGLE on machines with broken OpenGL drivers.
+    /// Default is WGLOnly.
+    pub windows_gl: WindowsGl,
+
     /// Wayland or X11. Defaults to X11WithWaylandFallback - miniquad will try
     /// to load "libX11.so", but if there is no - will try to initialize
     /// through wayland natively. If both  fails (no graphics server at
@@ -104,20 +205,97 @@ pub struct Platform {
     /// TODO: Document(and check) what does it actually mean on android. Transparent window?
     pub framebuffer_alpha: bool,
 
+    /// Which OpenGL ES context to create on Android, Linux EGL and web.
+    ///
+    /// Defaults to GlesVersion::Gles2.
+    pub gles_version: GlesVersion,
+
+    /// The desktop OpenGL version to ask WGL and GLX for, like (4, 3) for the
+    /// compute shaders. Panics if the driver can't make it.
+    ///
+    /// Defaults to None: 3.2, or 2.1 when it can't, on WGL and 2.1 on GLX.
+    pub gl_version: Option<(i32, i32)>,
+
+    /// The profile of a desktop OpenGL context from 3.2 on, with WGL and GLX.
+    ///
+    /// Defaults to GlProfile::Core.
+    pub gl_profile: GlProfile,
+
+    /// A debug context, for glDebugMessageCallback, with WGL, GLX and EGL
+    /// (EGL_KHR_create_context).
+    ///
+    /// Defaults to false.
+    pub gl_debug: bool,
+
+    /// An sRGB capable framebuffer, with WGL and GLX the conversion still
+    /// needs glEnable(GL_FRAMEBUFFER_SRGB). An sRGB window surface with EGL
+    /// (EGL_KHR_gl_colorspace). Ignored when the driver has none.
+    ///
+    /// Defaults to false.
+    pub srgb_framebuffer: bool,
+
     /// Whether to draw the default window decorations on Wayland.
     /// Only works when using the Wayland backend.
     pub wayland_use_fallback_decorations: bool,
+
+    /// The id the desktop matches the window with, for its icon and to group
+    /// the taskbar entries. On Linux the name of the .desktop file without
+    /// the extension, WM_CLASS on X11 and the app_id on Wayland. The
+    /// AppUserModelID on Windows, like "Company.Game", and the process name
+    /// on macOS, for the applications without a bundle.
+    /// Ignored on mobile and web.
+    ///
+    /// Defaults to None, the executable.
+    pub app_id: Option<String>,
+
+    /// How the window covers the display cutout on Android, in fullscreen
+    /// and with `window::set_fullscreen_immersive`.
+    ///
+    /// Defaults to AndroidCutoutMode::ShortEdges.
+    pub android_cutout_mode: AndroidCutoutMode,
+
+    /// On macOS, a titlebar without its background, with
+    /// `fullsize_content_view` the content shows through it.
+    ///
+    /// Defaults to false.
+    pub titlebar_transparent: bool,
+
+    /// On macOS, the content covers the whole window, titlebar included, for
+    /// the applications that draw a titlebar of their own. The traffic light
+    /// buttons stay over it. On Windows the titlebar goes and the resize
+    /// borders stay, `EventHandler::hit_test` says where the caption is.
+    ///
+    /// Defaults to false.
+    pub fullsize_content_view: bool,
+
+    /// On macOS, where the close button goes, in points from the left and
+    /// the top of the window, the two other buttons follow it.
+    ///
+    /// Defaults to None, where the titlebar has them.
+    pub traffic_light_position: Option<(f32, f32)>,
 }
 
 impl Default for Platform {
     fn default() -> Platform {
         Platform {
             linux_x11_gl: LinuxX11Gl::GLXWithEGLFallback,
+            linux_gpu: LinuxGpu::Default,
+            windows_gl: WindowsGl::WGLOnly,
             swap_interval: None,
             linux_backend: LinuxBackend::X11Only,
             apple_gfx_api: AppleGfxApi::OpenGl,
             framebuffer_alpha: false,
             wayland_use_fallback_decorations: true,
+            gles_version: GlesVersion::Gles2,
+            gl_version: None,
+            gl_profile: GlProfile::Core,
+            gl_debug: false,
+            srgb_framebuffer: false,
+            app_id: None,
+            android_cutout_mode: AndroidCutoutMode::ShortEdges,
+            titlebar_transparent: false,
+            fullsize_content_view: false,
+            traffic_light_position: None,
         }
     }
 }
@@ -155,9 +333,55 @@ pub struct Conf {
     /// - taskbar and titlebar icons on Windows.
     /// - dock and titlebar icon on  MacOs.
     /// - TODO: favicon on HTML5
-    /// - TODO: taskbar and titlebar(highly dependent on the WM) icons on Linux
+    /// - taskbar and titlebar(highly dependent on the WM) icons on X11.
+    ///   Wayland compositors use the icon from the .desktop file instead.
+    ///
+    /// Could be changed later with `window::set_window_icon`.
     pub icon: Option<Icon>,
 
+    /// Frames per second to sleep down to, for when vsync is off or not
+    /// available and nothing else stops the main loop from running as fast
+    /// as it can. Ignored on wasm, it is always vsynced. On iOS the rate of
+    /// the CADisplayLink, 60 by default and up to 120 on ProMotion displays
+    /// with CADisableMinimumFrameDurationOnPhone in the Info.plist.
+    ///
+    /// Could be changed later with `window::set_target_fps`.
+    ///
+    /// Default: None
+    pub max_fps: Option<u32>,
+
+    /// Run `update` and `draw` only after events, `window::request_redraw`
+    /// and `window::schedule_update`, with the main loop asleep in between.
+    /// For tools and other applications that do not animate all the time.
+    /// Ignored on wasm, Android and iOS.
+    ///
+    /// Default: false
+    pub wait_for_events: bool,
+
+    /// `update` and `draw` while the window is occluded, minimized or in
+    /// the background.
+    ///
+    /// Could be changed later with `window::set_background_mode`.
+    ///
+    /// Default: BackgroundMode::Continue
+    pub background_mode: BackgroundMode,
+
+    /// The orientations the screen rotates to, on Android, iOS and the web.
+    /// iOS needs them in the UISupportedInterfaceOrientations of the
+    /// Info.plist too, and browsers lock the orientation only in fullscreen.
+    ///
+    /// Could be changed later with `window::lock_orientation`.
+    ///
+    /// Default: Orientation::Any
+    pub supported_orientations: Orientation,
+
+    /// A framebuffer with an alpha channel composited with the desktop,
+    /// the alpha of the clear color shows what is behind the window.
+    /// Needs a compositing window manager on X11. Ignored on wasm, Android and iOS.
+    ///
+    /// Default: false
+    pub transparent: bool,
+
     /// Platform specific settings. Hints to OS for context creation, driver-specific
     /// settings etc.
     pub platform: Platform,
@@ -204,6 +428,11 @@ impl Default for Conf {
             sample_count: 1,
             window_resizable: true,
             icon: Some(Icon::miniquad_logo()),
+            max_fps: None,
+            wait_for_events: false,
+            background_mode: BackgroundMode::Continue,
+            supported_orientations: Orientation::Any,
+            transparent: false,
             platform: Default::default(),
         }
     }
@@ -221,6 +450,11 @@ impl Default for Conf {
             sample_count: 1,
             window_resizable: false,
             icon: Some(Icon::miniquad_logo()),
+            max_fps: None,
+            wait_for_events: false,
+            background_mode: BackgroundMode::Continue,
+            supported_orientations: Orientation::Any,
+            transparent: false,
             platform: Default::default(),
         }
     }
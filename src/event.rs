@@ -3,9 +3,129 @@ pub enum MouseButton {
     Right,
     Left,
     Middle,
+    /// The side buttons, X1 and X2 on Windows.
+    Back,
+    Forward,
+    /// The buttons after those, counting `Left` as 1, so the first is `Other(6)`.
+    Other(u8),
     Unknown,
 }
 
+/// How far a `mouse_scroll_event` scrolls. Positive `y` is the wheel turned
+/// away from the user, the content moves down, and positive `x` moves the
+/// content right.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ScrollDelta {
+    /// Wheel notches, fractions of one on high resolution wheels.
+    Lines(f32, f32),
+    /// From trackpads and smooth scrolling, in the same pixels as the mouse
+    /// events.
+    Pixels(f32, f32),
+}
+
+/// What `touch_details_event` knows about a touch besides where it is.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct TouchDetails {
+    /// From 0 to 1, None where the screen does not sense pressure.
+    pub pressure: Option<f32>,
+    /// The half axes of the contact ellipse, in the same pixels as the
+    /// touch position, zero where unknown.
+    pub radius: (f32, f32),
+    /// The touchscreen or touchpad of the touch, 0 where unknown.
+    pub device_id: u64,
+}
+
+/// A pen or stylus over a tablet or a screen, see `pen_event`.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct PenState {
+    /// From 0 to 1, 0 while hovering.
+    pub pressure: f32,
+    /// In degrees from -90 to 90, positive when the pen leans right and
+    /// towards the user, zero where unknown.
+    pub tilt: (f32, f32),
+    /// The tip touches the surface.
+    pub contact: bool,
+    /// A side button is held.
+    pub barrel_button: bool,
+    /// The eraser end, or a pen switched to erasing.
+    pub eraser: bool,
+}
+
+/// The buttons of the standard gamepad layout, by where they are on the
+/// controller: `South` is A on Xbox controllers and Cross on PlayStation ones.
+#[derive(Debug, Copy, Clone, PartialEq, Hash, Eq)]
+pub enum GamepadButton {
+    South,
+    East,
+    West,
+    North,
+    LeftShoulder,
+    RightShoulder,
+    /// Back, View, Share or Minus.
+    Select,
+    /// Menu, Options or Plus.
+    Start,
+    /// The Xbox, PlayStation or Home button, not everywhere.
+    Guide,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// The sticks and the triggers. The triggers are axes only, see
+/// `GamepadEvent::AxisChanged`.
+#[derive(Debug, Copy, Clone, PartialEq, Hash, Eq)]
+pub enum GamepadAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GamepadEvent {
+    /// Plugged in, or there already when the app starts.
+    Connected,
+    Disconnected,
+    ButtonDown(GamepadButton),
+    ButtonUp(GamepadButton),
+    /// The sticks from -1 to 1, right and down are positive, the triggers
+    /// from 0 to 1.
+    AxisChanged(GamepadAxis, f32),
+}
+
+/// A gamepad as of its last `gamepad_event`, see `window::gamepads`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GamepadState {
+    /// The name the platform has for the controller, empty where unknown.
+    pub name: String,
+    pub(crate) buttons: [bool; 15],
+    pub(crate) axes: [f32; 6],
+}
+
+impl GamepadState {
+    pub fn button(&self, button: GamepadButton) -> bool {
+        self.buttons[button as usize]
+    }
+
+    pub fn axis(&self, axis: GamepadAxis) -> f32 {
+        self.axes[axis as usize]
+    }
+
+    pub(crate) fn set_button(&mut self, button: GamepadButton, down: bool) {
+        self.buttons[button as usize] = down;
+    }
+
+    pub(crate) fn set_axis(&mut self, axis: GamepadAxis, value: f32) {
+        self.axes[axis as usize] = value;
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct Touch {
     pub id: u32,
@@ -138,12 +258,23 @@ pub enum KeyCode {
     Unknown,
 }
 
+/// A physical key, whatever the keyboard layout has on it: the USB HID usage
+/// of the key on the keyboard page. `ScanCode(0x1A)` is the key with W on a
+/// US QWERTY keyboard and Z on an AZERTY one. `ScanCode(0)` is unknown.
+#[derive(Debug, Copy, Clone, PartialEq, Hash, Eq, Default)]
+pub struct ScanCode(pub u16);
+
+/// The modifier keys held, and the lock keys on, when the event happened.
+/// `window::modifiers` is the latest one.
 #[derive(Debug, Copy, Clone, PartialEq, Default)]
 pub struct KeyMods {
     pub shift: bool,
     pub ctrl: bool,
     pub alt: bool,
     pub logo: bool,
+    pub caps_lock: bool,
+    /// Not on macOS, it has no num lock.
+    pub num_lock: bool,
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
@@ -166,18 +297,130 @@ pub trait EventHandler {
     fn update(&mut self);
     fn draw(&mut self);
     fn resize_event(&mut self, _width: f32, _height: f32) {}
+    /// The user started resizing the window by dragging its border, the
+    /// `resize_event`s until `resize_ended_event` are from the drag.
+    /// Windows, Wayland and macOS.
+    fn resize_started_event(&mut self) {}
+    fn resize_ended_event(&mut self) {}
+    /// The window moved, `x` and `y` are the new `window::get_window_position`.
+    /// Windows, X11 and macOS, Wayland does not tell where windows are.
+    fn window_moved_event(&mut self, _x: u32, _y: u32) {}
+    /// `window::dpi_scale` changed, the window moved to a monitor with
+    /// another scale or the scale of the monitor changed. Only with
+    /// `Conf::high_dpi`, on Windows, Wayland and macOS. A `resize_event`
+    /// with the new framebuffer size follows.
+    fn dpi_scale_changed_event(&mut self, _dpi_scale: f32) {}
+    /// Also out of the window while a button pressed in it is held, until
+    /// it is up, with the coordinates out of the window then.
     fn mouse_motion_event(&mut self, _x: f32, _y: f32) {}
+    /// The pointer came over the window, the `mouse_motion_event`s follow.
+    /// Desktop platforms and web.
+    fn mouse_entered_event(&mut self) {}
+    /// The pointer left the window, no more `mouse_motion_event`s until it
+    /// is back, but for a drag that started in the window.
+    fn mouse_left_event(&mut self) {}
     fn mouse_wheel_event(&mut self, _x: f32, _y: f32) {}
     fn mouse_button_down_event(&mut self, _button: MouseButton, _x: f32, _y: f32) {}
     fn mouse_button_up_event(&mut self, _button: MouseButton, _x: f32, _y: f32) {}
+    /// `mouse_motion_event` with the modifiers, calls `mouse_motion_event`
+    /// by default. Same for the other `_keymods_event`s.
+    fn mouse_motion_keymods_event(&mut self, x: f32, y: f32, _keymods: KeyMods) {
+        self.mouse_motion_event(x, y);
+    }
+    fn mouse_wheel_keymods_event(&mut self, x: f32, y: f32, _keymods: KeyMods) {
+        self.mouse_wheel_event(x, y);
+    }
+    /// Sent with every `mouse_wheel_event`, which passes on the numbers of
+    /// the platform, a wheel notch is not the same on all of them there.
+    /// Use one or the other.
+    fn mouse_scroll_event(&mut self, _delta: ScrollDelta, _keymods: KeyMods) {}
+    /// A pinch, `magnification` is how much bigger the content should get
+    /// since the last one, 0.1 for 10%, negative to shrink it. From the
+    /// trackpad gestures on macOS and from ctrl+wheel elsewhere, which is
+    /// how the precision touchpads of Windows and the browsers send a pinch.
+    fn magnify_event(&mut self, _magnification: f32) {}
+    /// Two fingers turning on a trackpad, in degrees since the last one,
+    /// counterclockwise is positive. macOS only.
+    fn rotate_event(&mut self, _degrees: f32) {}
+    /// A double tap with two fingers on a trackpad, to zoom in on what is
+    /// under the pointer or back out. macOS only.
+    fn smart_magnify_event(&mut self) {}
+    /// How hard a trackpad click is pushed, from 0 to 1 in each `stage`:
+    /// 1 for a click, 2 for a force click and 0 once it is released.
+    /// macOS with the Force Touch trackpads only.
+    fn force_touch_event(&mut self, _pressure: f32, _stage: i32) {}
+    fn mouse_button_down_keymods_event(
+        &mut self,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+        _keymods: KeyMods,
+    ) {
+        self.mouse_button_down_event(button, x, y);
+    }
+    fn mouse_button_up_keymods_event(
+        &mut self,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+        _keymods: KeyMods,
+    ) {
+        self.mouse_button_up_event(button, x, y);
+    }
 
+    /// Typed text, one character at a time, after the keyboard layout, dead
+    /// keys and compose sequences. No control characters, Enter, Backspace,
+    /// Tab and such are only key events.
     fn char_event(&mut self, _character: char, _keymods: KeyMods, _repeat: bool) {}
 
+    /// The text an input method is composing changed, to show it at the
+    /// text cursor until it is committed. `cursor` is a byte offset in `text`,
+    /// an empty `text` is the end of the composition.
+    /// Windows, X11, Wayland and macOS.
+    fn ime_composition_event(&mut self, _text: &str, _cursor: usize) {}
+    /// The text of a finished composition, as `char_event`s by default.
+    fn ime_commit_event(&mut self, text: &str) {
+        for character in text.chars() {
+            self.char_event(character, KeyMods::default(), false);
+        }
+    }
+
     fn key_down_event(&mut self, _keycode: KeyCode, _keymods: KeyMods, _repeat: bool) {}
 
     fn key_up_event(&mut self, _keycode: KeyCode, _keymods: KeyMods) {}
 
-    /// Default implementation emulates mouse clicks
+    /// `key_down_event` with the physical key, for controls that stay in
+    /// place on any layout. Calls `key_down_event` by default.
+    fn key_down_scancode_event(
+        &mut self,
+        keycode: KeyCode,
+        _scancode: ScanCode,
+        keymods: KeyMods,
+        repeat: bool,
+    ) {
+        self.key_down_event(keycode, keymods, repeat);
+    }
+    /// `key_up_event` with the physical key, calls `key_up_event` by default.
+    fn key_up_scancode_event(&mut self, keycode: KeyCode, _scancode: ScanCode, keymods: KeyMods) {
+        self.key_up_event(keycode, keymods);
+    }
+
+    /// `touch_event` with the pressure and size of the touch, calls
+    /// `touch_event` by default.
+    fn touch_details_event(
+        &mut self,
+        phase: TouchPhase,
+        id: u64,
+        x: f32,
+        y: f32,
+        _details: TouchDetails,
+    ) {
+        self.touch_event(phase, id, x, y);
+    }
+
+    /// Default implementation emulates mouse clicks.
+    /// `id` is new for every touch and stays the same from `Started`
+    /// to `Ended` or `Cancelled`.
     fn touch_event(&mut self, phase: TouchPhase, _id: u64, x: f32, y: f32) {
         if phase == TouchPhase::Started {
             self.mouse_button_down_event(MouseButton::Left, x, y);
@@ -192,34 +435,184 @@ pub trait EventHandler {
         }
     }
 
+    /// A pen moved, touched or left the surface, or its pressure or buttons
+    /// changed, in the same pixels as the mouse events.
+    /// The emulated mouse events are still sent.
+    /// Windows 8 and newer, X11 with XInput2, macOS and web.
+    fn pen_event(&mut self, _x: f32, _y: f32, _pen: PenState) {}
+
+    /// A gamepad was connected or disconnected, or its buttons or axes
+    /// changed. `id` is new for every connection. The gamepads are polled
+    /// before `update`, with `conf.wait_for_events` only on the frames there
+    /// are.
+    /// Windows (XInput), Linux (evdev), macOS and iOS (GameController),
+    /// Android and web (the controllers with the standard mapping).
+    fn gamepad_event(&mut self, _id: u64, _event: GamepadEvent) {}
+
     /// Represents raw hardware mouse motion event
     /// Note that these events are delivered regardless of input focus and not in pixels, but in
     /// hardware units instead. And those units may be different from pixels depending on the target platform
     fn raw_mouse_motion(&mut self, _dx: f32, _dy: f32) {}
 
     /// Window has been minimized
-    /// Right now is only implemented on Android, X11 and wasm,
+    /// On Windows, X11 and macOS the window state changed, see `window::window_state`,
+    /// Wayland compositors do not tell about minimized windows.
     /// On Andoid window_minimized_event is called on a Pause ndk callback
-    /// On X11 and wasm it will be called on focus change events.
+    /// On wasm it will be called on focus change events.
     fn window_minimized_event(&mut self) {}
 
-    /// Window has been restored
-    /// Right now is only implemented on Android, X11 and wasm,
-    /// On Andoid window_minimized_event is called on a Pause ndk callback
-    /// On X11 and wasm it will be called on focus change events.
+    /// Window has been maximized, or restored from being minimized while maximized.
+    /// Windows, X11, Wayland and macOS.
+    fn window_maximized_event(&mut self) {}
+
+    /// Window has been restored, it is not minimized or maximized anymore
+    /// On Windows, X11, Wayland and macOS the window state changed, see `window::window_state`.
+    /// On Andoid window_restored_event is called on a Resume ndk callback
+    /// On wasm it will be called on focus change events.
     fn window_restored_event(&mut self) {}
 
+    /// The app is going to the background, onPause on Android and
+    /// applicationWillResignActive on iOS: the time to pause the audio and
+    /// the simulation. The GL context stays with all of its textures and
+    /// buffers, Android only destroys the surface and there are no `draw`s
+    /// until it is back, iOS stops the frames.
+    fn app_paused(&mut self) {}
+
+    /// The app is in the foreground again after `app_paused`, onResume on
+    /// Android and applicationDidBecomeActive on iOS.
+    fn app_resumed(&mut self) {}
+
+    /// The OS is short on memory and the app could be killed next, caches
+    /// should be freed. onLowMemory and onTrimMemory on Android,
+    /// applicationDidReceiveMemoryWarning on iOS.
+    fn low_memory(&mut self) {}
+
+    /// The last event before the OS can kill the app in the background
+    /// without telling, the state should be saved right away.
+    /// onSaveInstanceState on Android and applicationDidEnterBackground on
+    /// iOS.
+    fn save_state(&mut self) {}
+
+    /// Nothing of the window can be seen anymore, or part of it again, see
+    /// `window::occluded` and `conf.background_mode`. On macOS from the
+    /// occlusion state, in a browser from the Page Visibility API, on
+    /// Android between onStop and onStart, on iOS in the background.
+    /// Windows and X11 with a compositor only know about minimized windows,
+    /// Wayland needs a compositor with the xdg_toplevel suspended state.
+    fn window_occluded(&mut self, _occluded: bool) {}
+
+    /// The window got or lost the keyboard focus. The keys held when it is
+    /// lost are released first with `key_up_scancode_event`, the OS sends
+    /// their key ups to the window focused instead. On Android and iOS when
+    /// the app is paused or resumed.
+    fn focus_event(&mut self, _focused: bool) {}
+
+    /// The onscreen keyboard was shown, hidden or resized, on Android and
+    /// iOS. `height` is how much of the bottom of the window it covers, in
+    /// the same pixels as the touch events, and 0 once it is hidden. The
+    /// window stays the same size, a text field under the keyboard can be
+    /// moved up by `height`. See `window::keyboard_height`.
+    fn keyboard_insets_changed(&mut self, _height: f32) {}
+
+    /// The screen rotated between portrait and landscape, on Android, iOS
+    /// and the web, with the new size of the window in pixels. Before the
+    /// `resize_event` of the same size. See `window::orientation`.
+    fn orientation_changed(
+        &mut self,
+        _orientation: crate::conf::Orientation,
+        _width: f32,
+        _height: f32,
+    ) {
+    }
+
+    /// The insets of `window::safe_area_insets` changed.
+    fn safe_area_changed(&mut self, _insets: crate::SafeAreaInsets) {}
+
+    /// The OS switched between the light and the dark theme, see
+    /// `window::system_theme`.
+    fn theme_changed(&mut self, _theme: crate::Theme) {}
+
+    /// The battery, the charging or the power saving mode changed, see
+    /// `window::power_info`. Linux, macOS and iOS look every few seconds,
+    /// before `update`.
+    fn power_changed(&mut self, _power: crate::PowerInfo) {}
+
     /// This event is sent when the userclicks the window's close button
     /// or application code calls the ctx.request_quit() function. The event
     /// handler callback code can handle this event by calling
     /// ctx.cancel_quit() to cancel the quit.
     /// If the event is ignored, the application will quit as usual.
+    /// It is also sent for Cmd+Q and the Dock on macOS and for SIGTERM on
+    /// Linux. On Android and iOS it is sent when the app is terminated and
+    /// the quit can not be canceled. On wasm it is sent on beforeunload,
+    /// cancel_quit makes the browser ask the user before leaving the page.
+    /// The event handler is dropped once the application quits.
     fn quit_requested_event(&mut self) {}
 
+    /// The back button or gesture on Android. The activity finishes after it,
+    /// unless `window::cancel_back` is called from here.
+    fn back_requested_event(&mut self) {}
+
+    /// The back gesture of Android 14 is being dragged, from 0 to 1, for a
+    /// predictive animation of going back. 0 again if it is cancelled,
+    /// `back_requested_event` if it is not. Needs
+    /// `android:enableOnBackInvokedCallback="true"` in the manifest.
+    fn back_progress_event(&mut self, _progress: f32) {}
+
+    /// A `MenuItem::Action` of `window::set_menus` or `window::set_dock_menu`
+    /// was chosen.
+    fn menu_event(&mut self, _id: u32) {}
+
+    /// What the mouse at `x`, `y` does, for the titlebars drawn by the
+    /// application over a `conf.platform.fullsize_content_view`: the
+    /// `WindowArea::Caption` keeps the snapping of a window dragged by its
+    /// titlebar. Asked on every mouse move, so quick. Windows only.
+    fn hit_test(&mut self, _x: f32, _y: f32) -> crate::WindowArea {
+        crate::WindowArea::Client
+    }
+
     /// A file has been dropped over the application.
     /// Applications can request the number of dropped files with
     /// `ctx.dropped_file_count()`, path of an individual file with
     /// `ctx.dropped_file_path()`, and for wasm targets the file bytes
     /// can be requested with `ctx.dropped_file_bytes()`.
+    /// Implemented on Windows, X11, Wayland, macOS and wasm.
     fn files_dropped_event(&mut self) {}
+
+    /// Files are dragged over the window, followed by either
+    /// `files_dropped_event` or `files_hover_cancelled_event`.
+    /// Not sent on Windows, WM_DROPFILES only reports the drop itself.
+    fn files_hovered_event(&mut self) {}
+
+    /// The files dragged over the window left it without being dropped.
+    fn files_hover_cancelled_event(&mut self) {}
+
+    /// GL context was lost, all GPU resources are gone. Happens on Android
+    /// (EGL_CONTEXT_LOST, usually after resume) and on wasm (webglcontextlost).
+    /// No drawing is possible until `context_restored`.
+    fn context_lost(&mut self) {}
+
+    /// A new GL context replaced the lost one. Resources created before are
+    /// invalid: either re-create them or, if `ctx.retain_resources(true)` was
+    /// set before creating them, call `ctx.restore_resources()`.
+    fn context_restored(&mut self) {}
+
+    /// Data sent with an `EventSender`, in the order it was sent, right
+    /// before the next `update`. `data.downcast::<T>()` gets it back.
+    fn custom_event(&mut self, _data: Box<dyn std::any::Any + Send>) {}
+}
+
+/// Sends `EventHandler::custom_event`s to the main loop from any thread,
+/// waking it up with `conf.wait_for_events`. See `window::event_sender`.
+#[derive(Clone)]
+pub struct EventSender {
+    pub(crate) tx: std::sync::mpsc::Sender<Box<dyn std::any::Any + Send>>,
+}
+
+impl EventSender {
+    pub fn send<T: std::any::Any + Send>(&self, data: T) {
+        // the receiver lives as long as the application
+        let _ = self.tx.send(Box::new(data));
+        crate::window::request_redraw();
+    }
 }
@@ -43,6 +43,89 @@ pub fn load_file<F: Fn(Response) + 'static>(path: &str, on_loaded: F) {
     load_file_desktop(path, on_loaded);
 }
 
+/// A file read a part at a time, for the ones too big to be loaded whole
+/// by `load_file`, like music and videos. From the same places as
+/// `load_file`: the assets of the APK on Android and the main bundle on iOS.
+/// Not on the web, where the files are downloaded.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct FileReader {
+    #[cfg(target_os = "android")]
+    inner: crate::native::android::Asset,
+    #[cfg(not(target_os = "android"))]
+    inner: std::fs::File,
+    len: u64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileReader {
+    /// The size of the whole file, in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl std::io::Read for FileReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl std::io::Seek for FileReader {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// Opens `path` to be read by `FileReader`, see `load_file` for the paths.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_file(path: &str) -> Result<FileReader, Error> {
+    #[cfg(target_os = "android")]
+    {
+        let inner =
+            crate::native::android::Asset::open(path).ok_or(Error::AndroidAssetLoadingError)?;
+        let len = inner.len();
+        Ok(FileReader { inner, len })
+    }
+
+    #[cfg(not(target_os = "android"))]
+    {
+        #[cfg(target_os = "ios")]
+        let path = ios::resource_path(path).ok_or(Error::IOSAssetNoSuchFile)?;
+        let inner = std::fs::File::open(path)?;
+        let len = inner.metadata()?.len();
+        Ok(FileReader { inner, len })
+    }
+}
+
+/// Where to keep the saves and the settings: the internal storage of the
+/// application on Android and Application Support in its container on iOS.
+/// None elsewhere, where the directories are shared by the applications.
+pub fn data_dir() -> Option<std::path::PathBuf> {
+    #[cfg(target_os = "android")]
+    return unsafe { crate::native::android::storage_dir(false) };
+    #[cfg(target_os = "ios")]
+    return ios::storage_dir(false);
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    None
+}
+
+/// Like `data_dir`, for the files the OS may delete when the storage runs
+/// low.
+pub fn cache_dir() -> Option<std::path::PathBuf> {
+    #[cfg(target_os = "android")]
+    return unsafe { crate::native::android::storage_dir(true) };
+    #[cfg(target_os = "ios")]
+    return ios::storage_dir(true);
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    None
+}
+
 #[cfg(target_os = "android")]
 fn load_file_android<F: Fn(Response)>(path: &str, on_loaded: F) {
     fn load_file_sync(path: &str) -> Response {
@@ -2,23 +2,32 @@
 
 use crate::native::gl::*;
 
-use std::{error::Error, fmt::Display};
+use std::{borrow::Cow, error::Error, fmt::Display};
 
 //pub use texture::{FilterMode, TextureAccess, TextureFormat, TextureParams, TextureWrap};
 
+mod command_list;
 mod gl;
+mod preprocessor;
+mod shader_cache;
+mod upload_queue;
+mod vertex_layout;
 
 pub use gl::raw_gl;
 
 #[cfg(target_vendor = "apple")]
 mod metal;
 
+pub use command_list::CommandList;
 pub use gl::GlContext;
+pub use shader_cache::ShaderCache;
+pub use upload_queue::{Pending, UploadQueue};
+pub use vertex_layout::{VertexField, VertexLayout};
 
 #[cfg(target_vendor = "apple")]
 pub use metal::MetalContext;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum UniformType {
     /// One 32-bit wide float (equivalent to `f32`)
     Float1,
@@ -36,8 +45,13 @@ pub enum UniformType {
     Int3,
     /// Four unsigned 32-bit integers (equivalent to `[u32; 4]`)
     Int4,
+    /// Three by three matrix of 32-bit floats, tightly packed (equivalent to `[f32; 9]`).
+    /// On metal columns are padded to 16 bytes when applied, matching MSL `float3x3`.
+    Mat3,
     /// Four by four matrix of 32-bit floats
     Mat4,
+    /// GLSL `bool`, one 32-bit integer, zero being false (equivalent to `u32`)
+    Bool,
 }
 
 impl UniformType {
@@ -52,19 +66,102 @@ impl UniformType {
             UniformType::Int2 => 8,
             UniformType::Int3 => 12,
             UniformType::Int4 => 16,
+            UniformType::Mat3 => 36,
             UniformType::Mat4 => 64,
+            UniformType::Bool => 4,
         }
     }
 }
 
-#[derive(Clone)]
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Rust types that could be used with `set_uniform`.
+/// The trait is sealed: values are handed to the driver as raw bytes, so only
+/// the types below, without any padding or invalid bit patterns, implement it.
+pub trait Uniform: sealed::Sealed {
+    fn uniform_type() -> UniformType;
+
+    fn array_count(&self) -> usize {
+        1
+    }
+
+    /// `array_count` tightly packed values of `uniform_type`.
+    fn uniform_bytes(&self) -> Cow<'_, [u8]> {
+        let size = std::mem::size_of_val(self);
+        Cow::Borrowed(unsafe { std::slice::from_raw_parts(self as *const Self as *const u8, size) })
+    }
+}
+
+macro_rules! impl_uniform {
+    ($($t:ty => $uniform_type:ident),*) => {
+        $(impl sealed::Sealed for $t {}
+        impl Uniform for $t {
+            fn uniform_type() -> UniformType {
+                UniformType::$uniform_type
+            }
+        })*
+    };
+}
+
+impl_uniform!(
+    f32 => Float1,
+    [f32; 2] => Float2,
+    [f32; 3] => Float3,
+    [f32; 4] => Float4,
+    i32 => Int1,
+    [i32; 2] => Int2,
+    [i32; 3] => Int3,
+    [i32; 4] => Int4,
+    [f32; 9] => Mat3,
+    [f32; 16] => Mat4
+);
+
+impl sealed::Sealed for bool {}
+impl Uniform for bool {
+    fn uniform_type() -> UniformType {
+        UniformType::Bool
+    }
+
+    fn uniform_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned((*self as u32).to_ne_bytes().to_vec())
+    }
+}
+
+impl<T: Uniform> sealed::Sealed for [T] {}
+/// Uniform arrays, `&bones[..]` for `uniform mat4 bones[64]`.
+impl<T: Uniform> Uniform for [T] {
+    fn uniform_type() -> UniformType {
+        T::uniform_type()
+    }
+
+    fn array_count(&self) -> usize {
+        self.len()
+    }
+
+    fn uniform_bytes(&self) -> Cow<'_, [u8]> {
+        // bool is a single byte in rust, but 4 bytes in the shader
+        if std::mem::size_of::<T>() != T::uniform_type().size() {
+            return Cow::Owned(
+                self.iter()
+                    .flat_map(|value| value.uniform_bytes().into_owned())
+                    .collect(),
+            );
+        }
+        let size = std::mem::size_of_val(self);
+        Cow::Borrowed(unsafe { std::slice::from_raw_parts(self.as_ptr() as *const u8, size) })
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct UniformDesc {
     pub name: String,
     pub uniform_type: UniformType,
     pub array_count: usize,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct UniformBlockLayout {
     pub uniforms: Vec<UniformDesc>,
 }
@@ -78,6 +175,9 @@ impl UniformDesc {
         }
     }
 
+    /// Uniform array, `uniform mat4 bones[64]` would be
+    /// `UniformDesc::new("bones", UniformType::Mat4).array(64)`.
+    /// Uniforms struct should contain the whole array, `[[f32; 16]; 64]` here.
     pub fn array(self, array_count: usize) -> UniformDesc {
         UniformDesc {
             array_count,
@@ -86,12 +186,108 @@ impl UniformDesc {
     }
 }
 
+impl UniformBlockLayout {
+    /// Repack uniforms, tightly packed in the order of this layout, for a MSL
+    /// struct: `float3x3` is made of three 16-byte aligned `float3` columns,
+    /// so every Mat3 is aligned to 16 bytes and its columns are padded to
+    /// 16 bytes. Other uniforms are copied as is.
+    #[cfg(any(target_vendor = "apple", test))]
+    pub(crate) fn msl_bytes<'a>(&self, data: &'a [u8]) -> Cow<'a, [u8]> {
+        let has_mat3 = self
+            .uniforms
+            .iter()
+            .any(|uniform| uniform.uniform_type == UniformType::Mat3);
+        if !has_mat3 {
+            return Cow::Borrowed(data);
+        }
+
+        let mut res = Vec::with_capacity(data.len() * 4 / 3);
+        let mut offset = 0;
+        for uniform in &self.uniforms {
+            let size = uniform.uniform_type.size() * uniform.array_count;
+            let bytes = &data[offset.min(data.len())..(offset + size).min(data.len())];
+            offset += size;
+            if uniform.uniform_type != UniformType::Mat3 {
+                res.extend_from_slice(bytes);
+                continue;
+            }
+            for matrix in bytes.chunks(36) {
+                res.resize((res.len() + 15) / 16 * 16, 0);
+                for column in matrix.chunks(12) {
+                    res.extend_from_slice(column);
+                    res.resize(res.len() + 16 - column.len(), 0);
+                }
+            }
+        }
+        res.extend_from_slice(&data[offset.min(data.len())..]);
+        Cow::Owned(res)
+    }
+}
+
 #[derive(Clone)]
 pub struct ShaderMeta {
     pub uniforms: UniformBlockLayout,
     pub images: Vec<String>,
 }
 
+/// Active uniform, as reported by the driver.
+#[derive(Clone, Debug)]
+pub struct ReflectedUniform {
+    pub name: String,
+    /// None for types miniquad can't set as an uniform.
+    pub uniform_type: Option<UniformType>,
+    pub array_count: usize,
+    pub location: i32,
+}
+
+/// Active vertex attribute, as reported by the driver.
+#[derive(Clone, Debug)]
+pub struct ReflectedAttribute {
+    pub name: String,
+    /// None for types that could not be represented with VertexFormat.
+    pub format: Option<VertexFormat>,
+    pub array_count: usize,
+    pub location: i32,
+}
+
+/// Everything the driver knows about the linked program.
+/// Unused uniforms and attributes are optimized out by the drivers and will not
+/// be listed.
+#[derive(Clone, Debug, Default)]
+pub struct ShaderReflection {
+    pub uniforms: Vec<ReflectedUniform>,
+    /// Sampler uniforms
+    pub images: Vec<String>,
+    pub attributes: Vec<ReflectedAttribute>,
+}
+
+impl ShaderReflection {
+    /// Build uniform layout from the reflected uniforms.
+    /// Uniforms order is driver-specific, so the layout should not be used
+    /// with a `#[repr(C)]` struct directly, only with the order from this layout.
+    pub fn uniform_block_layout(&self) -> UniformBlockLayout {
+        UniformBlockLayout {
+            uniforms: self
+                .uniforms
+                .iter()
+                .filter_map(|uniform| {
+                    Some(UniformDesc::array(
+                        UniformDesc::new(&uniform.name, uniform.uniform_type?),
+                        uniform.array_count,
+                    ))
+                })
+                .collect(),
+        }
+    }
+
+    pub fn shader_meta(&self) -> ShaderMeta {
+        ShaderMeta {
+            uniforms: self.uniform_block_layout(),
+            images: self.images.clone(),
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum VertexFormat {
     /// One 32-bit wide float (equivalent to `f32`)
@@ -265,7 +461,9 @@ pub struct PipelineLayout {
 #[derive(Clone, Debug, Copy)]
 pub enum ShaderType {
     Vertex,
+    Geometry,
     Fragment,
+    Compute,
 }
 
 #[derive(Clone, Debug)]
@@ -277,6 +475,14 @@ pub enum ShaderError {
     LinkError(String),
     /// Shader strings should never contains \00 in the middle
     FFINulError(std::ffi::NulError),
+    /// Malformed or unresolved #include, see `new_shader_with_options`.
+    PreprocessorError(String),
+    /// `shader_recompile`: the uniforms or images of the new source do not
+    /// match the ShaderMeta of the shader.
+    LayoutMismatch(String),
+    /// The backend can not do this at all, for example `shader_recompile`
+    /// on metal.
+    NotSupported(String),
 }
 
 impl From<std::ffi::NulError> for ShaderError {
@@ -297,6 +503,70 @@ impl Error for ShaderError {
     }
 }
 
+/// Single message from a shader compiler or linker log.
+#[derive(Clone, Debug)]
+pub struct ShaderErrorLine {
+    /// Line in the shader source the message refers to, if the driver reported one.
+    pub line: Option<usize>,
+    /// Source string number of the line: 0 for the shader source itself,
+    /// N for the N-th file included with `new_shader_with_options`.
+    pub source: usize,
+    pub message: String,
+}
+
+impl ShaderError {
+    /// Split the driver log into separate messages and map them to the source lines.
+    /// Drivers do not agree on the log format, the most common "0:12(3): error", "0(12) : error"
+    /// and "ERROR: 0:12: " styles are recognized.
+    pub fn lines(&self) -> Vec<ShaderErrorLine> {
+        let log = match self {
+            ShaderError::CompilationError { error_message, .. } => error_message,
+            ShaderError::LinkError(error_message) => error_message,
+            ShaderError::FFINulError(_)
+            | ShaderError::PreprocessorError(_)
+            | ShaderError::LayoutMismatch(_)
+            | ShaderError::NotSupported(_) => return vec![],
+        };
+
+        log.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|message| {
+                let (source, line) = match parse_log_line_number(message) {
+                    Some((source, line)) => (source, Some(line)),
+                    None => (0, None),
+                };
+                ShaderErrorLine {
+                    line,
+                    source,
+                    message: message.to_string(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Source string number and line of a log message.
+fn parse_log_line_number(message: &str) -> Option<(usize, usize)> {
+    let message = message.trim_start();
+    let message = message
+        .strip_prefix("ERROR: ")
+        .or_else(|| message.strip_prefix("WARNING: "))
+        .unwrap_or(message);
+
+    // source string index, 0 unless the line comes from an #include
+    let index_len = message.find(|c: char| !c.is_ascii_digit())?;
+    if index_len == 0 {
+        return None;
+    }
+    let source = message[..index_len].parse().ok()?;
+    let rest = &message[index_len..];
+    let rest = rest.strip_prefix(':').or_else(|| rest.strip_prefix('('))?;
+    let line_len = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    Some((source, rest[..line_len].parse().ok()?))
+}
+
 /// List of all the possible formats of input data when uploading to texture.
 /// The list is built by intersection of texture formats supported by 3.3 core profile and webgl1.
 #[repr(u8)]
@@ -354,6 +624,11 @@ pub enum TextureAccess {
     Static,
     /// Can be written to from GPU
     RenderTarget,
+    /// Can be bound as a compute image with `apply_compute_bindings`.
+    /// On GL RGBA8 and RGBA16F 2D textures get immutable storage, GLES3.1
+    /// binds no other: `texture_resize` makes a new GL texture then, the
+    /// render passes using it are updated.
+    Storage,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -513,6 +788,78 @@ pub enum CompareFunc {
 
 type ColorMask = (bool, bool, bool, bool);
 
+/// A single render pass attachment: a texture, its mip level and, for
+/// `TextureKind::CubeMap`, the cube face from 0 to 5 (+X, -X, +Y, -Y, +Z, -Z).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RenderTarget {
+    pub texture: TextureId,
+    pub mip_level: u32,
+    pub layer: u32,
+}
+
+impl RenderTarget {
+    pub fn mip(texture: TextureId, mip_level: u32) -> RenderTarget {
+        RenderTarget {
+            texture,
+            mip_level,
+            layer: 0,
+        }
+    }
+
+    pub fn cube_face(texture: TextureId, face: u32) -> RenderTarget {
+        RenderTarget {
+            texture,
+            mip_level: 0,
+            layer: face,
+        }
+    }
+}
+
+impl From<TextureId> for RenderTarget {
+    fn from(texture: TextureId) -> RenderTarget {
+        RenderTarget::mip(texture, 0)
+    }
+}
+
+/// What to do with the attachment content when a pass begins.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadAction {
+    /// Clear with the value from `PassAction::Clear`, or keep the content
+    /// with `PassAction::Nothing`.
+    Clear,
+    /// Keep the content, even if `PassAction` asks for a clear.
+    Load,
+    /// The content is going to be overwritten anyway.
+    DontCare,
+}
+
+/// What to do with the attachment content when a pass ends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StoreAction {
+    Store,
+    /// The content is not needed after the pass, like depth buffer in most
+    /// of the passes.
+    DontCare,
+}
+
+/// Load and store actions of a single render pass attachment.
+/// `DontCare` saves a full attachment copy between GPU tile memory and
+/// VRAM on mobile GPUs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttachmentAction {
+    pub load: LoadAction,
+    pub store: StoreAction,
+}
+
+impl Default for AttachmentAction {
+    fn default() -> AttachmentAction {
+        AttachmentAction {
+            load: LoadAction::Clear,
+            store: StoreAction::Store,
+        }
+    }
+}
+
 pub enum PassAction {
     Nothing,
     Clear {
@@ -530,6 +877,14 @@ impl PassAction {
             stencil: None,
         }
     }
+
+    pub fn clear_depth(depth: f32) -> PassAction {
+        PassAction::Clear {
+            color: None,
+            depth: Some(depth),
+            stencil: None,
+        }
+    }
 }
 
 impl Default for PassAction {
@@ -551,11 +906,61 @@ pub const MAX_SHADERSTAGE_IMAGES: usize = 12;
 #[derive(Clone, Debug)]
 pub struct Features {
     pub instancing: bool,
+    /// Transform feedback capture, available on GL3+, GLES3 and WebGL2.
+    pub transform_feedback: bool,
+    /// Occlusion queries, available on GL3+ and GLES3.
+    pub occlusion_query: bool,
+    /// `QueryType::AnySamplesPassed` queries, GL3.3+, GL_ARB_occlusion_query2
+    /// and GLES3.
+    pub any_samples_query: bool,
+    /// `begin_conditional_render`, desktop GL3+ only.
+    pub conditional_render: bool,
+    /// Compute shaders, GL4.3+ and GLES3.1+.
+    pub compute: bool,
+    /// `BufferType::Storage`, GL4.3+ and GLES3.1+.
+    pub storage_buffers: bool,
+    /// `ShaderSource::GlslGeometry`, GL3.2+ and GLES3.2+.
+    pub geometry_shader: bool,
+    /// `shader_binary` and `new_shader_from_binary`, GL4.1+ and GLES3+,
+    /// and only if the driver supports at least one binary format.
+    pub program_binary: bool,
+    /// `TextureFormat::RGBA16F` textures, GL3+, GLES3 and WebGL2.
+    pub float_textures: bool,
+    /// Maximum width and height of a texture.
+    pub max_texture_size: u32,
+    /// Maximum `sample_count` for multisampled render targets,
+    /// 1 when multisampled render targets are not supported.
+    pub max_samples: u32,
+    /// Maximum amount of color attachments for `new_render_pass_mrt`.
+    pub max_color_attachments: u32,
+    /// `pipeline_set_attachment_blend`, GL4+ and GLES3.2+.
+    pub independent_blend: bool,
+    /// `BlendValue::Source1Color` and `BlendValue::Source1Alpha`, GL3.3+
+    /// or GL_EXT_blend_func_extended on GLES.
+    /// A single color attachment only.
+    pub dual_source_blending: bool,
 }
 
 impl Default for Features {
     fn default() -> Features {
-        Features { instancing: true }
+        Features {
+            instancing: true,
+            transform_feedback: false,
+            occlusion_query: false,
+            any_samples_query: false,
+            conditional_render: false,
+            compute: false,
+            storage_buffers: false,
+            geometry_shader: false,
+            program_binary: false,
+            float_textures: false,
+            // guaranteed by GLES2/WebGL1
+            max_texture_size: 2048,
+            max_samples: 1,
+            max_color_attachments: 1,
+            independent_blend: false,
+            dual_source_blending: false,
+        }
     }
 }
 
@@ -624,6 +1029,17 @@ pub enum BlendValue {
     SourceAlpha,
     DestinationColor,
     DestinationAlpha,
+    /// The second output of the fragment shader, dual-source blending
+    /// (`features.dual_source_blending`). The shader declares both outputs
+    /// for the same attachment, on GLSL 330 and GLSL ES 300
+    /// (with `#extension GL_EXT_blend_func_extended : require`):
+    /// ```glsl
+    /// layout(location = 0, index = 0) out vec4 color;
+    /// layout(location = 0, index = 1) out vec4 blend;
+    /// ```
+    /// and `[[color(0), index(1)]]` on Metal.
+    Source1Color,
+    Source1Alpha,
 }
 
 /// Blend factors.
@@ -645,18 +1061,44 @@ impl Default for Equation {
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum PrimitiveType {
     Triangles,
+    TriangleStrip,
     Lines,
+    LineStrip,
+}
+
+impl PrimitiveType {
+    // for FrameStats::triangles
+    fn triangle_count(self, num_elements: i32) -> u64 {
+        let triangles = match self {
+            PrimitiveType::Triangles => num_elements / 3,
+            PrimitiveType::TriangleStrip => num_elements - 2,
+            PrimitiveType::Lines | PrimitiveType::LineStrip => 0,
+        };
+        triangles.max(0) as u64
+    }
 }
 
 impl From<PrimitiveType> for GLenum {
     fn from(primitive_type: PrimitiveType) -> Self {
         match primitive_type {
             PrimitiveType::Triangles => GL_TRIANGLES,
+            PrimitiveType::TriangleStrip => GL_TRIANGLE_STRIP,
             PrimitiveType::Lines => GL_LINES,
+            PrimitiveType::LineStrip => GL_LINE_STRIP,
         }
     }
 }
 
+/// Rasterization mode for polygons.
+/// Only supported on desktop OpenGL, on GLES and WebGL anything but Fill is ignored.
+/// Metal supports Fill and Line.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PolygonMode {
+    Fill,
+    Line,
+    Point,
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct PipelineParams {
     pub cull_face: CullFace,
@@ -703,6 +1145,17 @@ pub struct PipelineParams {
     pub stencil_test: Option<StencilState>,
     pub color_write: ColorMask,
     pub primitive_type: PrimitiveType,
+    /// Enable GL_SAMPLE_ALPHA_TO_COVERAGE: fragment alpha will be used as a
+    /// coverage mask for the multisampled render target.
+    /// Has no effect without MSAA (sample_count > 1).
+    pub alpha_to_coverage: bool,
+    /// Wireframe/point rendering, mostly useful for debugging.
+    pub polygon_mode: PolygonMode,
+    /// Treat the maximum value of the index type (0xFF, 0xFFFF or 0xFFFFFFFF)
+    /// as a strip restart marker, allowing multiple strips in one draw call.
+    /// Needs GL3.1+ or GLES3, ignored on older GL, GLES2 and WebGL1.
+    /// WebGL2 always restarts strips, whatever this is set to.
+    pub primitive_restart: bool,
 }
 
 // TODO(next major version bump): should be PipelineId
@@ -722,6 +1175,9 @@ impl Default for PipelineParams {
             stencil_test: None,
             color_write: (true, true, true, true),
             primitive_type: PrimitiveType::Triangles,
+            alpha_to_coverage: false,
+            polygon_mode: PolygonMode::Fill,
+            primitive_restart: false,
         }
     }
 }
@@ -749,6 +1205,12 @@ pub struct Bindings {
 pub enum BufferType {
     VertexBuffer,
     IndexBuffer,
+    /// Shader storage buffer, accessible from shaders as a `buffer` block.
+    /// Bound with `apply_storage_buffers` or `apply_compute_bindings`.
+    ///
+    /// NOTE: requires `features.storage_buffers`, without it the buffer is
+    /// still made and updated but binding it does nothing.
+    Storage,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -758,13 +1220,6 @@ pub enum BufferUsage {
     Stream,
 }
 
-fn gl_buffer_target(buffer_type: &BufferType) -> GLenum {
-    match buffer_type {
-        BufferType::VertexBuffer => GL_ARRAY_BUFFER,
-        BufferType::IndexBuffer => GL_ELEMENT_ARRAY_BUFFER,
-    }
-}
-
 fn gl_usage(usage: &BufferUsage) -> GLenum {
     match usage {
         BufferUsage::Immutable => GL_STATIC_DRAW,
@@ -776,38 +1231,53 @@ fn gl_usage(usage: &BufferUsage) -> GLenum {
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct BufferId(usize);
 
-/// `ElapsedQuery` is used to measure duration of GPU operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryType {
+    /// Number of samples that passed the depth and stencil tests.
+    /// GLES3 can only tell if any samples passed, so the result there will be 0 or 1.
+    SamplesPassed,
+    /// 1 if any sample passed the depth and stencil tests, 0 otherwise.
+    /// Requires `features.any_samples_query`.
+    AnySamplesPassed,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct QueryId(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ComputePipeline(usize);
+
+/// `GpuTimer` is used to measure duration of GPU operations.
 ///
 /// Usual timing/profiling methods are difficult apply to GPU workloads as draw calls are submitted
 /// asynchronously effectively hiding execution time of individual operations from the user.
-/// `ElapsedQuery` allows to measure duration of individual rendering operations, as though the time
+/// `GpuTimer` allows to measure duration of individual rendering operations, as though the time
 /// was measured on GPU rather than CPU side.
 ///
-/// The query is created using [`ElapsedQuery::new()`] function.
-/// ```
-/// use miniquad::graphics::ElapsedQuery;
+/// The query is created using [`GpuTimer::new()`] function.
+/// ```no_run
+/// use miniquad::graphics::GpuTimer;
 /// // initialization
-/// let mut query = ElapsedQuery::new();
+/// let mut query = GpuTimer::new();
 /// ```
-/// Measurement is performed by calling [`ElapsedQuery::begin_query()`] and
-/// [`ElapsedQuery::end_query()`]
+/// Measurement is performed by calling [`GpuTimer::begin_query()`] and
+/// [`GpuTimer::end_query()`]
 ///
-/// ```
-/// # use miniquad::graphics::ElapsedQuery;
-/// # let mut query = ElapsedQuery::new();
+/// ```no_run
+/// # use miniquad::graphics::GpuTimer;
+/// # let mut query = GpuTimer::new();
 ///
 /// query.begin_query();
-/// // one or multiple calls to miniquad::GraphicsContext::draw()
+/// // one or multiple calls to miniquad::Context::draw()
 /// query.end_query();
 /// ```
 ///
 /// Retreival of measured duration is only possible at a later point in time. Often a frame or
-/// couple frames later. Measurement latency can especially be high on WASM/WebGL target.
+/// couple frames later, measurement latency can be checked only after `end_query()`.
 ///
-/// ```
-/// // couple frames later:
-/// # use miniquad::graphics::ElapsedQuery;
-/// # let mut query = ElapsedQuery::new();
+/// ```no_run
+/// # use miniquad::graphics::GpuTimer;
+/// # let mut query = GpuTimer::new();
 /// # query.begin_query();
 /// # query.end_query();
 /// if query.is_available() {
@@ -817,10 +1287,10 @@ pub struct BufferId(usize);
 /// ```
 ///
 /// And during finalization:
-/// ```
+/// ```no_run
 /// // clean-up
-/// # use miniquad::graphics::ElapsedQuery;
-/// # let mut query = ElapsedQuery::new();
+/// # use miniquad::graphics::GpuTimer;
+/// # let mut query = GpuTimer::new();
 /// # query.begin_query();
 /// # query.end_query();
 /// # if query.is_available() {
@@ -830,34 +1300,41 @@ pub struct BufferId(usize);
 /// query.delete();
 /// ```
 ///
-/// It is only possible to measure single query at once.
+/// It is only possible to measure single elapsed-time query at once. To measure
+/// overlapping intervals, e.g. each pass and the whole frame, use
+/// [`GpuTimer::query_timestamp()`] on multiple timers and subtract the results.
 ///
-/// On OpenGL/WebGL platforms implementation relies on [`EXT_disjoint_timer_query`] extension.
+/// On OpenGL platforms implementation relies on GL3.3 or [`ARB_timer_query`], on WebGL on
+/// [`EXT_disjoint_timer_query`] extension.
 ///
+/// [`ARB_timer_query`]: https://registry.khronos.org/OpenGL/extensions/ARB/ARB_timer_query.txt
 /// [`EXT_disjoint_timer_query`]: https://www.khronos.org/registry/OpenGL/extensions/EXT/EXT_disjoint_timer_query.txt
 ///
 #[derive(Clone, Copy)]
-pub struct ElapsedQuery {
+pub struct GpuTimer {
     gl_query: GLuint,
 }
 
-impl ElapsedQuery {
-    pub fn new() -> ElapsedQuery {
-        ElapsedQuery { gl_query: 0 }
+#[deprecated(note = "Renamed to GpuTimer")]
+pub type ElapsedQuery = GpuTimer;
+
+impl GpuTimer {
+    pub fn new() -> GpuTimer {
+        GpuTimer { gl_query: 0 }
     }
 
     /// Submit a beginning of elapsed-time query.
     ///
     /// Only a single query can be measured at any moment in time.
     ///
-    /// Use [`ElapsedQuery::end_query()`] to finish the query and
-    /// [`ElapsedQuery::get_result()`] to read the result when rendering is complete.
+    /// Use [`GpuTimer::end_query()`] to finish the query and
+    /// [`GpuTimer::get_result()`] to read the result when rendering is complete.
     ///
     /// The query can be used again after retriving the result.
     ///
     /// Implemented as `glBeginQuery(GL_TIME_ELAPSED, ...)` on OpenGL/WebGL platforms.
     ///
-    /// Use [`ElapsedQuery::is_supported()`] to check if functionality is available and the method can be called.
+    /// Use [`GpuTimer::is_supported()`] to check if functionality is available and the method can be called.
     pub fn begin_query(&mut self) {
         if self.gl_query == 0 {
             unsafe { glGenQueries(1, &mut self.gl_query) };
@@ -867,54 +1344,100 @@ impl ElapsedQuery {
 
     /// Submit an end of elapsed-time query that can be read later when rendering is complete.
     ///
-    /// This function is usd in conjunction with [`ElapsedQuery::begin_query()`] and
-    /// [`ElapsedQuery::get_result()`].
+    /// This function is usd in conjunction with [`GpuTimer::begin_query()`] and
+    /// [`GpuTimer::get_result()`].
     ///
     /// Implemented as `glEndQuery(GL_TIME_ELAPSED)` on OpenGL/WebGL platforms.
     pub fn end_query(&mut self) {
         unsafe { glEndQuery(GL_TIME_ELAPSED) };
     }
 
+    /// Record GPU time at the moment all previously submitted commands are complete.
+    ///
+    /// [`GpuTimer::get_result()`] will return an absolute time in nanoseconds, difference
+    /// between two timestamps is the GPU time spent between them.
+    /// Unlike [`GpuTimer::begin_query()`], any number of timestamp queries may be in flight.
+    ///
+    /// Implemented as `glQueryCounter(..., GL_TIMESTAMP)` on OpenGL/WebGL platforms.
+    pub fn query_timestamp(&mut self) {
+        if self.gl_query == 0 {
+            unsafe { glGenQueries(1, &mut self.gl_query) };
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if unsafe { crate::native::gl::is_gles() } {
+            unsafe { glQueryCounterEXT(self.gl_query, GL_TIMESTAMP) };
+            return;
+        }
+        unsafe { glQueryCounter(self.gl_query, GL_TIMESTAMP) };
+    }
+
     /// Retreieve measured duration in nanonseconds.
     ///
     /// Note that the result may be ready only couple frames later due to asynchronous nature of GPU
-    /// command submission. Use [`ElapsedQuery::is_available()`] to check if the result is
+    /// command submission. Use [`GpuTimer::is_available()`] to check if the result is
     /// available for retrieval.
     ///
-    /// Use [`ElapsedQuery::is_supported()`] to check if functionality is available and the method can be called.
+    /// Use [`GpuTimer::is_supported()`] to check if functionality is available and the method can be called.
     pub fn get_result(&self) -> u64 {
-        // let mut time: GLuint64 = 0;
-        // assert!(self.gl_query != 0);
-        // unsafe { glGetQueryObjectui64v(self.gl_query, GL_QUERY_RESULT, &mut time) };
-        // time
-        0
+        let mut time: GLuint64 = 0;
+        assert!(self.gl_query != 0);
+        #[cfg(not(target_arch = "wasm32"))]
+        if unsafe { crate::native::gl::is_gles() } {
+            unsafe { glGetQueryObjectui64vEXT(self.gl_query, GL_QUERY_RESULT, &mut time) };
+            return time;
+        }
+        unsafe { glGetQueryObjectui64v(self.gl_query, GL_QUERY_RESULT, &mut time) };
+        time
     }
 
     /// Reports whenever elapsed timer is supported and other methods can be invoked.
     pub fn is_supported() -> bool {
-        unimplemented!();
-        //unsafe { sapp_is_elapsed_timer_supported() }
+        #[cfg(target_arch = "wasm32")]
+        {
+            unsafe { crate::native::wasm::sapp_is_elapsed_timer_supported() }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        unsafe {
+            // GLES2 would need EXT-suffixed versions of all the query functions
+            if crate::native::gl::is_gles() {
+                return crate::native::gl::gl_version() >= (3, 0)
+                    && crate::native::gl::has_extension("GL_EXT_disjoint_timer_query");
+            }
+            crate::native::gl::gl_version() >= (3, 3)
+                || crate::native::gl::has_extension("GL_ARB_timer_query")
+        }
     }
 
     /// Reports whenever result of submitted query is available for retrieval with
-    /// [`ElapsedQuery::get_result()`].
+    /// [`GpuTimer::get_result()`].
     ///
     /// Note that the result may be ready only couple frames later due to asynchrnous nature of GPU
     /// command submission.
     ///
-    /// Use [`ElapsedQuery::is_supported()`] to check if functionality is available and the method can be called.
+    /// Use [`GpuTimer::is_supported()`] to check if functionality is available and the method can be called.
     pub fn is_available(&self) -> bool {
-        // let mut available: GLint = 0;
+        let mut available: GLint = 0;
 
-        // // begin_query was not called yet
-        // if self.gl_query == 0 {
-        //     return false;
-        // }
-
-        //unsafe { glGetQueryObjectiv(self.gl_query, GL_QUERY_RESULT_AVAILABLE, &mut available) };
-        //available != 0
+        // begin_query was not called yet
+        if self.gl_query == 0 {
+            return false;
+        }
 
-        false
+        // GLES3 only has the unsigned version
+        #[cfg(not(target_arch = "wasm32"))]
+        unsafe {
+            glGetQueryObjectuiv(
+                self.gl_query,
+                GL_QUERY_RESULT_AVAILABLE,
+                &mut available as *mut GLint as *mut GLuint,
+            )
+        };
+        #[cfg(target_arch = "wasm32")]
+        unsafe {
+            glGetQueryObjectiv(self.gl_query, GL_QUERY_RESULT_AVAILABLE, &mut available)
+        };
+        available != 0
     }
 
     /// Delete query.
@@ -942,7 +1465,9 @@ pub struct Arg<'a> {
 pub enum TextureSource<'a> {
     Empty,
     Bytes(&'a [u8]),
-    /// Array of `[cubemap_face][mipmap_level][bytes]`
+    /// Array of `[cubemap_face][mipmap_level][bytes]`.
+    /// On GLES2 and WebGL1 only the mip level 0 of non-power-of-two textures
+    /// is used.
     Array(&'a [&'a [&'a [u8]]]),
 }
 
@@ -1001,10 +1526,89 @@ impl<'a> UniformsSource<'a> {
     }
 }
 
+/// Linked program in a driver-specific format, see `shader_binary`.
+/// Binaries are only valid for the exact same GPU and driver version.
+#[derive(Clone, Debug)]
+pub struct ShaderBinary {
+    pub format: u32,
+    pub data: Vec<u8>,
+}
+
 #[derive(Debug)]
 pub enum ShaderSource<'a> {
-    Glsl { vertex: &'a str, fragment: &'a str },
-    Msl { program: &'a str },
+    Glsl {
+        vertex: &'a str,
+        fragment: &'a str,
+    },
+    /// Glsl with a geometry stage in between vertex and fragment shaders.
+    /// Shader creation fails with `ShaderError::NotSupported` when
+    /// `features.geometry_shader` is false.
+    GlslGeometry {
+        vertex: &'a str,
+        geometry: &'a str,
+        fragment: &'a str,
+    },
+    Msl {
+        program: &'a str,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlslVersion {
+    V100,
+    V130,
+    V330,
+    V300Es,
+}
+
+impl GlslVersion {
+    fn directive(&self) -> &'static str {
+        match self {
+            GlslVersion::V100 => "#version 100",
+            GlslVersion::V130 => "#version 130",
+            GlslVersion::V330 => "#version 330",
+            GlslVersion::V300Es => "#version 300 es",
+        }
+    }
+
+    fn number(&self) -> u32 {
+        match self {
+            GlslVersion::V100 => 100,
+            GlslVersion::V130 => 130,
+            GlslVersion::V330 => 330,
+            GlslVersion::V300Es => 300,
+        }
+    }
+
+    /// The most capable glsl version the context supports.
+    pub fn best_supported(info: &ContextInfo) -> GlslVersion {
+        let glsl = &info.glsl_support;
+        if glsl.v300es {
+            GlslVersion::V300Es
+        } else if glsl.v330 {
+            GlslVersion::V330
+        } else if glsl.v130 {
+            GlslVersion::V130
+        } else {
+            GlslVersion::V100
+        }
+    }
+}
+
+/// Preprocessing options for `new_shader_with_options`.
+#[derive(Default, Clone, Copy)]
+pub struct ShaderOptions<'a> {
+    /// `#define NAME VALUE` lines, inserted right after the version directive.
+    pub defines: &'a [(&'a str, &'a str)],
+    /// Called with "name" for each `#include "name"` or `#include <name>`
+    /// line, should return the source to be included.
+    pub include_resolver: Option<&'a dyn Fn(&str) -> Option<String>>,
+    /// Version directive to add when the source does not have one.
+    /// If None, `GlslVersion::best_supported` is used.
+    /// `MQ_GLSL_VERSION` and `MQ_GLSL_ES` follow the source's own `#version`
+    /// when it has one.
+    /// Ignored on metal.
+    pub version: Option<GlslVersion>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Hash, Eq)]
@@ -1036,6 +1640,17 @@ pub struct ContextInfo {
     pub backend: Backend,
     /// GL_VERSION_STRING from OpenGL. Would be empty on metal.
     pub gl_version_string: String,
+    /// GL_SHADING_LANGUAGE_VERSION from OpenGL. Would be empty on metal.
+    pub glsl_version_string: String,
+    /// GL_VENDOR from OpenGL, "Apple" on metal.
+    pub vendor: String,
+    /// GL_RENDERER from OpenGL, MTLDevice name on metal.
+    /// Together with `vendor` this is what usually identifies the driver
+    /// for driver-specific workarounds and bug reports.
+    pub renderer: String,
+    /// The GL context is from EGL: on Android and Wayland, on X11 instead of
+    /// GLX and on Windows with ANGLE instead of WGL. False on metal.
+    pub egl: bool,
     /// OpenGL provides an enumeration over GL_SHADING_LANGUAGE_VERSION,
     /// allowing to see which glsl versions are actually supported.
     /// Unfortunately, it only works on GL4.3+... and even there it is not quite correct.
@@ -1048,6 +1663,24 @@ pub struct ContextInfo {
     pub features: Features,
 }
 
+/// Per-frame rendering counters, see `RenderingBackend::frame_stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    pub draw_calls: u32,
+    /// Triangles from both `Triangles` and `TriangleStrip` draws, each
+    /// instance counted separately. Lines are not counted.
+    /// Index buffers stay on the GPU, so with `primitive_restart` a strip
+    /// draw is counted as one strip, restart markers included: the count is
+    /// an upper bound, `3 * restarts` triangles too much at most.
+    pub triangles: u64,
+    /// Textures bound with `apply_bindings`.
+    pub texture_binds: u32,
+    /// `apply_pipeline` calls with a pipeline different from the current one.
+    pub pipeline_switches: u32,
+    /// `buffer_update` calls.
+    pub buffer_uploads: u32,
+}
+
 pub trait RenderingBackend {
     fn info(&self) -> ContextInfo;
     /// For metal context's ShaderSource should contain MSL source string, for GL - glsl.
@@ -1077,6 +1710,120 @@ pub trait RenderingBackend {
         shader: ShaderSource,
         meta: ShaderMeta,
     ) -> Result<ShaderId, ShaderError>;
+    /// Get the linked program, to be loaded with `new_shader_from_binary`
+    /// on the next run, skipping shader compilation.
+    /// None if the driver could not provide the binary, or without
+    /// `features.program_binary`.
+    /// See `ShaderCache` for a simple on-disk cache.
+    fn shader_binary(&self, shader: ShaderId) -> Option<ShaderBinary>;
+    /// Create a shader from a binary returned by `shader_binary`.
+    /// The driver may reject the binary, for example after a driver update,
+    /// returning a `LinkError`; in that case the shader should be compiled
+    /// from the sources again. Without `features.program_binary` this is
+    /// always a `LinkError`.
+    fn new_shader_from_binary(
+        &mut self,
+        binary: &ShaderBinary,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError>;
+    /// List active uniforms and attributes of the shader.
+    ///
+    /// NOTE: OpenGL only, metal returns `ShaderError::NotSupported`.
+    fn shader_reflect(&self, shader: ShaderId) -> Result<ShaderReflection, ShaderError>;
+    /// Same as `new_shader`, but ShaderMeta is built from `shader_reflect`
+    /// instead of being declared manually. See `ShaderReflection::shader_meta`
+    /// for the uniform order caveats.
+    ///
+    /// NOTE: OpenGL only, metal returns `ShaderError::NotSupported`.
+    fn new_shader_reflected(&mut self, shader: ShaderSource) -> Result<ShaderId, ShaderError>;
+    /// Same as `new_shader`, but runs sources through a simple preprocessor first:
+    /// adds a `#version` directive if the source lacks one, and `#define`s
+    /// `options.defines` along with `MQ_GLSL_VERSION` (100, 130, 330 or 300)
+    /// and `MQ_GLSL_ES`, making it possible to write one source for GL, GLES and WebGL.
+    /// `#include`s are resolved with `options.include_resolver`.
+    ///
+    /// `#line` directives keep error line numbers matching the original
+    /// source and the included files. With GLSL lines of the N-th `#include`
+    /// (in the order of appearance, nested ones included) are reported with
+    /// source string number N, see `ShaderErrorLine::source`.
+    fn new_shader_with_options(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+        options: ShaderOptions,
+    ) -> Result<ShaderId, ShaderError> {
+        match shader {
+            ShaderSource::Glsl { vertex, fragment } => {
+                let version = options
+                    .version
+                    .unwrap_or_else(|| GlslVersion::best_supported(&self.info()));
+                let vertex = preprocessor::preprocess(vertex, &options, Some(version))?;
+                let fragment = preprocessor::preprocess(fragment, &options, Some(version))?;
+                self.new_shader(
+                    ShaderSource::Glsl {
+                        vertex: &vertex,
+                        fragment: &fragment,
+                    },
+                    meta,
+                )
+            }
+            ShaderSource::GlslGeometry {
+                vertex,
+                geometry,
+                fragment,
+            } => {
+                let version = options
+                    .version
+                    .unwrap_or_else(|| GlslVersion::best_supported(&self.info()));
+                let vertex = preprocessor::preprocess(vertex, &options, Some(version))?;
+                let geometry = preprocessor::preprocess(geometry, &options, Some(version))?;
+                let fragment = preprocessor::preprocess(fragment, &options, Some(version))?;
+                self.new_shader(
+                    ShaderSource::GlslGeometry {
+                        vertex: &vertex,
+                        geometry: &geometry,
+                        fragment: &fragment,
+                    },
+                    meta,
+                )
+            }
+            ShaderSource::Msl { program } => {
+                let program = preprocessor::preprocess(program, &options, None)?;
+                self.new_shader(ShaderSource::Msl { program: &program }, meta)
+            }
+        }
+    }
+    /// Same as `new_shader`, but declares vertex shader outputs that will be
+    /// captured with transform feedback.
+    /// OpenGL needs varyings to be known before the program is linked, so they
+    /// belong to the shader rather than the pipeline.
+    /// Each varying is written into its own buffer: `varyings[i]` goes to
+    /// `buffers[i]` of `begin_transform_feedback`.
+    ///
+    /// NOTE: requires `features.transform_feedback`, otherwise fails with
+    /// `ShaderError::NotSupported`.
+    fn new_shader_with_feedback(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+        varyings: &[&str],
+    ) -> Result<ShaderId, ShaderError>;
+    /// Replace shader program with a new source, keeping the same ShaderId,
+    /// so all the pipelines using this shader will pick up the new program.
+    /// ShaderMeta (and transform feedback varyings) from the shader creation
+    /// are reused, so the new source should declare the same uniforms and
+    /// Uniforms structs applied with this shader stay valid.
+    ///
+    /// If compilation fails, or an active uniform or image of the new
+    /// program does not match the ShaderMeta (`ShaderError::LayoutMismatch`),
+    /// the error is returned and the old program is left untouched.
+    ///
+    /// NOTE: OpenGL only, metal returns `ShaderError::NotSupported`.
+    fn shader_recompile(
+        &mut self,
+        shader: ShaderId,
+        source: ShaderSource,
+    ) -> Result<(), ShaderError>;
     fn new_texture(
         &mut self,
         access: TextureAccess,
@@ -1148,6 +1895,9 @@ pub trait RenderingBackend {
     ///
     /// Also note that if MipmapFilter is set to None, mipmaps will not be visible, even if
     /// generated.
+    ///
+    /// GLES2 and WebGL1 can't mipmap non-power-of-two textures, for those the
+    /// call is ignored with an error message.
     fn texture_generate_mipmaps(&mut self, texture: TextureId);
     fn texture_resize(&mut self, texture: TextureId, width: u32, height: u32, bytes: Option<&[u8]>);
     fn texture_read_pixels(&mut self, texture: TextureId, bytes: &mut [u8]);
@@ -1168,10 +1918,36 @@ pub trait RenderingBackend {
         self.new_render_pass_mrt(&[color_img], depth_img)
     }
     /// Same as "new_render_pass", but allows multiple color attachments.
+    /// `color_img[i]` is the target of the fragment shader output with
+    /// `layout(location = i)`, or `gl_FragData[i]`.
+    /// With no `color_img` at all the pass is depth-only, for shadow maps.
+    ///
+    /// NOTE: up to `features.max_color_attachments` attachments, at least 4 on
+    /// GLES3/WebGL2 and 8 on metal.
     fn new_render_pass_mrt(
         &mut self,
         color_img: &[TextureId],
         depth_img: Option<TextureId>,
+    ) -> RenderPass {
+        let color_img: Vec<RenderTarget> = color_img.iter().map(|img| (*img).into()).collect();
+        self.new_render_pass_targets(&color_img, depth_img.map(|img| img.into()))
+    }
+    /// Same as "new_render_pass_mrt", but renders to the given mip level
+    /// or cube map face of each texture instead of the level 0.
+    /// ```ignore
+    /// // bloom downsample chain in a single texture
+    /// let passes: Vec<RenderPass> = (1..mip_count)
+    ///     .map(|level| ctx.new_render_pass_targets(&[RenderTarget::mip(bloom, level)], None))
+    ///     .collect();
+    /// ```
+    ///
+    /// NOTE: `delete_render_pass` deletes attachments textures, with
+    /// multiple passes sharing a texture only one of them should be deleted.
+    /// GLES2 and WebGL1 can only render to the mip level 0.
+    fn new_render_pass_targets(
+        &mut self,
+        color: &[RenderTarget],
+        depth: Option<RenderTarget>,
     ) -> RenderPass;
     /// panics for depth-only or multiple color attachment render pass
     /// This function is, mostly, legacy. Using "render_pass_color_attachments"
@@ -1188,6 +1964,29 @@ pub trait RenderingBackend {
     }
     /// For depth-only render pass returns empty slice.
     fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId];
+    /// Size of the pass attachments, or the screen size in pixels for `None`,
+    /// the default framebuffer.
+    fn render_pass_size(&self, render_pass: Option<RenderPass>) -> (u32, u32);
+    /// Set load/store actions for each of `render_pass` color attachments and
+    /// for the depth attachment, by default all of them are
+    /// `AttachmentAction::default()`. The depth action applies to the stencil
+    /// of a packed depth-stencil target as well.
+    /// ```ignore
+    /// let dont_care = AttachmentAction {
+    ///     load: LoadAction::DontCare,
+    ///     store: StoreAction::DontCare,
+    /// };
+    /// ctx.render_pass_set_actions(pass, &[AttachmentAction::default()], dont_care);
+    /// ```
+    ///
+    /// NOTE: on GL `DontCare` requires GLES3, WebGL2 or GL4.3, otherwise it
+    /// is just a hint that is ignored.
+    fn render_pass_set_actions(
+        &mut self,
+        render_pass: RenderPass,
+        color: &[AttachmentAction],
+        depth: AttachmentAction,
+    );
     fn delete_render_pass(&mut self, render_pass: RenderPass);
     fn new_pipeline(
         &mut self,
@@ -1198,6 +1997,24 @@ pub trait RenderingBackend {
     ) -> Pipeline;
     fn apply_pipeline(&mut self, pipeline: &Pipeline);
     fn delete_pipeline(&mut self, pipeline: Pipeline);
+    /// Blend state for one color attachment of a MRT pass, replacing
+    /// `color_blend` and `alpha_blend` of the pipeline params for this
+    /// attachment only.
+    ///
+    /// NOTE: requires `features.independent_blend`, otherwise does nothing
+    /// and all the attachments use the pipeline params blend state.
+    /// ```ignore
+    /// let additive = BlendState::new(Equation::Add, BlendFactor::One, BlendFactor::One);
+    /// // attachment 0 is just overwritten, attachment 1 accumulates
+    /// ctx.pipeline_set_attachment_blend(pipeline, 1, Some(additive), None);
+    /// ```
+    fn pipeline_set_attachment_blend(
+        &mut self,
+        pipeline: Pipeline,
+        attachment: usize,
+        color_blend: Option<BlendState>,
+        alpha_blend: Option<BlendState>,
+    );
 
     /// Create a buffer resource object.
     /// ```ignore
@@ -1254,7 +2071,10 @@ pub trait RenderingBackend {
     fn delete_shader(&mut self, program: ShaderId);
 
     /// Set a new viewport rectangle.
-    /// Should be applied after begin_pass.
+    /// Should be applied after begin_pass. Works both for the default
+    /// framebuffer and offscreen passes, `begin_pass` resets the viewport to the
+    /// whole render target and `end_render_pass` restores the one that was
+    /// active before the pass began.
     fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32);
 
     /// Set a new scissor rectangle.
@@ -1276,10 +2096,33 @@ pub trait RenderingBackend {
         );
     }
 
+    /// Bind `buffers[i]` to `layout(binding = i) buffer` block of the
+    /// current pipeline.
+    ///
+    /// NOTE: requires `features.storage_buffers`, otherwise does nothing.
+    fn apply_storage_buffers(&mut self, buffers: &[BufferId]);
+
     fn apply_uniforms(&mut self, uniforms: UniformsSource) {
         self.apply_uniforms_from_bytes(uniforms.0.ptr as _, uniforms.0.size)
     }
     fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize);
+    /// Set a single uniform of the current pipeline by its name.
+    /// Uniform locations are looked up once and cached; in debug builds the
+    /// uniform type is validated against the shader reflection.
+    /// Prefer `set_uniform`, this is its non-generic part.
+    ///
+    /// `data` should contain `array_count` tightly packed values of `uniform_type`.
+    ///
+    /// Metal has no uniform locations: the uniform is written into a copy of
+    /// the uniforms applied last with this shader, and the whole block is
+    /// applied again.
+    fn set_uniform_from_bytes(
+        &mut self,
+        name: &str,
+        uniform_type: UniformType,
+        array_count: usize,
+        data: &[u8],
+    );
 
     fn clear(
         &mut self,
@@ -1287,7 +2130,20 @@ pub trait RenderingBackend {
         depth: Option<f32>,
         stencil: Option<i32>,
     );
+    /// Clear a single color attachment of the current pass, `clear`
+    /// clears all of them with the same color.
+    /// On metal this restarts the pass, the pipeline, bindings, uniforms,
+    /// viewport and scissor are applied again on the new one.
+    /// ```ignore
+    /// ctx.begin_pass(Some(gbuffer), PassAction::clear_depth(1.0));
+    /// ctx.clear_color_attachment(0, (0.0, 0.0, 0.0, 1.0));
+    /// ctx.clear_color_attachment(1, (0.5, 0.5, 1.0, 0.0));
+    /// ```
+    fn clear_color_attachment(&mut self, attachment: usize, color: (f32, f32, f32, f32));
     /// start rendering to the default frame buffer
+    ///
+    /// The default framebuffer is just a `None` render pass: clears,
+    /// viewport and scissor work exactly the same for both.
     fn begin_default_pass(&mut self, action: PassAction);
     /// start rendering to an offscreen framebuffer
     fn begin_pass(&mut self, pass: Option<RenderPass>, action: PassAction);
@@ -1295,6 +2151,8 @@ pub trait RenderingBackend {
     fn end_render_pass(&mut self);
 
     fn commit_frame(&mut self);
+    /// Counters of the last frame, updated on each `commit_frame`.
+    fn frame_stats(&self) -> FrameStats;
 
     /// Draw elements using currently applied bindings and pipeline.
     ///
@@ -1304,5 +2162,272 @@ pub trait RenderingBackend {
     ///
     /// NOTE: num_instances > 1 might be not supported by the GPU (gl2.1 and gles2).
     /// `features.instancing` check is required.
+    ///
+    /// During transform feedback the draw is not indexed, see
+    /// `begin_transform_feedback`.
     fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32);
+
+    /// Start capturing vertex shader outputs into `buffers`.
+    /// Should be called after apply_pipeline, with a pipeline that uses a shader
+    /// created by `new_shader_with_feedback`. Capture buffers are ordinary
+    /// vertex buffers, usually created with `BufferSource::empty`.
+    ///
+    /// With `discard` set, rasterization is disabled and draw calls will only
+    /// write to the capture buffers.
+    ///
+    /// The pipeline's primitive type should be `Triangles` or `Lines`, strips
+    /// can not be captured. Until `end_transform_feedback` draws do not use
+    /// the index buffer (GLES3 and WebGL2 do not allow indexed draws while
+    /// capturing): `base_element` and `num_elements` of `draw` are the first
+    /// vertex and the vertex count.
+    ///
+    /// NOTE: requires `features.transform_feedback`, without it this and
+    /// `end_transform_feedback` do nothing.
+    fn begin_transform_feedback(&mut self, buffers: &[BufferId], discard: bool);
+    fn end_transform_feedback(&mut self);
+
+    /// Start an occlusion query, draw calls issued before `end_query` will be counted.
+    /// Only one query can be active at a time.
+    /// The query should be deleted with `delete_query` once its result is no longer needed.
+    ///
+    /// Returns None if the GPU does not support `query_type`, see
+    /// `features.occlusion_query` and `features.any_samples_query`.
+    fn begin_query(&mut self, query_type: QueryType) -> Option<QueryId>;
+    fn end_query(&mut self, query: QueryId);
+    /// Returns None while the result is not yet available.
+    /// Results usually get ready a frame or two later, blocking on them
+    /// would stall the pipeline.
+    fn query_result(&self, query: QueryId) -> Option<u64>;
+    fn delete_query(&mut self, query: QueryId);
+
+    /// Skip draw calls until `end_conditional_render` if no samples passed
+    /// during `query`. With `wait` set to false GPU will not wait for the
+    /// query result and will draw if the result is not yet ready.
+    ///
+    /// NOTE: requires `features.conditional_render`, otherwise draw calls are
+    /// executed unconditionally.
+    fn begin_conditional_render(&mut self, query: QueryId, wait: bool);
+    fn end_conditional_render(&mut self);
+
+    /// Create a compute pipeline from a glsl compute shader source.
+    /// `meta.images` are ignored, images for load/store are bound by unit
+    /// index with `apply_compute_bindings`.
+    ///
+    /// Without `features.compute` this fails with `ShaderError::NotSupported`.
+    fn new_compute_pipeline(
+        &mut self,
+        source: &str,
+        meta: ShaderMeta,
+    ) -> Result<ComputePipeline, ShaderError>;
+    /// Uniforms applied with `apply_uniforms` after this call will go to the
+    /// compute pipeline, until the next `apply_pipeline`.
+    fn apply_compute_pipeline(&mut self, pipeline: &ComputePipeline);
+    /// Bind `storage_buffers[i]` to `layout(binding = i) buffer` blocks, the
+    /// same way as `apply_storage_buffers`, and `images[i]` to
+    /// `layout(binding = i) image2D` uniforms.
+    /// Images are bound for both reading and writing and should be RGBA8 or
+    /// RGBA16F textures made with `TextureAccess::Storage`, GLES3.1 does not
+    /// bind others. Fails with `ShaderError::NotSupported` for any other
+    /// image, nothing is bound then.
+    fn apply_compute_bindings(
+        &mut self,
+        storage_buffers: &[BufferId],
+        images: &[TextureId],
+    ) -> Result<(), ShaderError>;
+    /// Launch x * y * z work groups of the current compute pipeline.
+    fn dispatch(&mut self, x: u32, y: u32, z: u32);
+    /// Make writes from previous dispatches visible to all the following
+    /// GPU operations, both draws and dispatches.
+    fn memory_barrier(&mut self);
+    fn delete_compute_pipeline(&mut self, pipeline: ComputePipeline);
+
+    /// Keep CPU copies of texture pixels, buffer contents and shader sources
+    /// of all the resources created from now on, so `restore_resources` can
+    /// re-create them after a context loss. Costs as much memory as all the
+    /// uploaded data, false by default.
+    fn retain_resources(&mut self, retain: bool);
+    /// Re-create all the retained resources in a new GL context, usually in
+    /// `EventHandler::context_restored`. All the ids remain valid.
+    /// Render pass attachments, textures and buffers created while not
+    /// retaining are re-created empty. Shaders created while not retaining,
+    /// queries, compute pipelines and a transform feedback in progress are
+    /// not restored.
+    fn restore_resources(&mut self);
+
+    /// Make the backend forget everything it knows about the current GPU
+    /// state. Should be called after raw GL calls or other GL libraries
+    /// (video players, UI renderers) used the same context. Otherwise
+    /// miniquad may skip binds it believes are already done.
+    ///
+    /// All the bindings, pipeline and uniforms are lost: call between passes
+    /// and `apply_pipeline`/`apply_bindings` again afterwards.
+    fn reset_state_cache(&mut self);
+
+    /// Name the object for graphics debuggers like RenderDoc or Xcode, the
+    /// label also shows up in "gl-debug" messages.
+    ///
+    /// NOTE: on GL requires GLES3.2, GL4.3 or KHR_debug, otherwise does nothing.
+    fn texture_set_label(&mut self, texture: TextureId, label: &str);
+    fn buffer_set_label(&mut self, buffer: BufferId, label: &str);
+    fn shader_set_label(&mut self, shader: ShaderId, label: &str);
+    /// Group all the following commands under `name` in graphics debuggers,
+    /// until the matching `pop_debug_group`. Groups may be nested.
+    /// ```ignore
+    /// ctx.push_debug_group("shadow pass");
+    /// ctx.begin_pass(Some(shadow_pass), PassAction::Nothing);
+    /// ...
+    /// ctx.end_render_pass();
+    /// ctx.pop_debug_group();
+    /// ```
+    fn push_debug_group(&mut self, name: &str);
+    fn pop_debug_group(&mut self);
+}
+
+impl dyn RenderingBackend {
+    /// Set uniform of the current pipeline by name, an alternative to
+    /// `apply_uniforms` that does not depend on the uniform order.
+    /// ```ignore
+    /// ctx.set_uniform("u_time", &time);
+    /// ctx.set_uniform("bones", &bones[..]);
+    /// ```
+    pub fn set_uniform<T: Uniform + ?Sized>(&mut self, name: &str, value: &T) {
+        let data = value.uniform_bytes();
+        self.set_uniform_from_bytes(name, T::uniform_type(), value.array_count(), &data);
+    }
+
+    /// Play back all the commands recorded in `commands`, in order.
+    pub fn submit_commands(&mut self, commands: &CommandList) {
+        commands.play(self);
+    }
+
+    /// Run everything queued in `queue` so far, `Pending` handles of the
+    /// created resources become valid.
+    pub fn process_uploads(&mut self, queue: &UploadQueue) {
+        queue.process(self);
+    }
+
+    /// `new_pipeline` with a single vertex buffer of `V`s.
+    /// ```ignore
+    /// #[repr(C)]
+    /// #[derive(VertexLayout)]
+    /// struct Vertex {
+    ///     pos: [f32; 2],
+    ///     uv: [f32; 2],
+    /// }
+    /// let pipeline = ctx.new_pipeline_with_layout::<Vertex>(shader, PipelineParams::default());
+    /// ```
+    /// Panics if `V` has padding between fields, and, on GL, if a shader
+    /// attribute has no matching field or the component counts differ.
+    pub fn new_pipeline_with_layout<V: VertexLayout>(
+        &mut self,
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Pipeline {
+        vertex_layout::check_layout::<V>(self, shader);
+        self.new_pipeline(&[V::buffer_layout()], &V::attributes(0), shader, params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triangle_count() {
+        assert_eq!(PrimitiveType::Triangles.triangle_count(6), 2);
+        assert_eq!(PrimitiveType::TriangleStrip.triangle_count(5), 3);
+        assert_eq!(PrimitiveType::TriangleStrip.triangle_count(1), 0);
+        assert_eq!(PrimitiveType::LineStrip.triangle_count(5), 0);
+    }
+
+    #[test]
+    fn triangle_count_with_restart_is_upper_bound() {
+        // two 4-vertex strips and a restart marker in between: 4 triangles,
+        // counted as a single 9-vertex strip
+        let (elements, restarts, triangles) = (4 + 1 + 4, 1, 4);
+        let counted = PrimitiveType::TriangleStrip.triangle_count(elements);
+        assert!(counted >= triangles);
+        assert_eq!(counted, triangles + 3 * restarts);
+    }
+
+    fn log_lines(log: &str) -> Vec<(usize, Option<usize>)> {
+        ShaderError::LinkError(log.to_string())
+            .lines()
+            .iter()
+            .map(|line| (line.source, line.line))
+            .collect()
+    }
+
+    #[test]
+    fn log_lines_mesa_nvidia() {
+        assert_eq!(
+            log_lines("0:12(3): error: `x' undeclared\n0(7) : error C1008: undefined variable"),
+            vec![(0, Some(12)), (0, Some(7))]
+        );
+    }
+
+    #[test]
+    fn log_lines_angle() {
+        assert_eq!(
+            log_lines("ERROR: 0:12: 'x' : undeclared identifier\nERROR: 1 compilation errors."),
+            vec![(0, Some(12)), (0, None)]
+        );
+    }
+
+    #[test]
+    fn log_lines_apple() {
+        // the second message comes from the first included file
+        assert_eq!(
+            log_lines("ERROR: 0:12: Use of undeclared identifier 'x'\nERROR: 1:3: Invalid call"),
+            vec![(0, Some(12)), (1, Some(3))]
+        );
+    }
+
+    #[test]
+    fn uniform_bytes() {
+        let bytes = |values: &[u32]| {
+            values
+                .iter()
+                .flat_map(|v| v.to_ne_bytes())
+                .collect::<Vec<u8>>()
+        };
+        assert_eq!(true.uniform_bytes().into_owned(), bytes(&[1]));
+        assert_eq!(
+            [true, false, true][..].uniform_bytes().into_owned(),
+            bytes(&[1, 0, 1])
+        );
+        assert_eq!([true, false][..].array_count(), 2);
+        assert_eq!([3i32, 4][..].uniform_bytes().into_owned(), bytes(&[3, 4]));
+        assert!(matches!(
+            [[1f32; 4]; 2][..].uniform_bytes(),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn msl_mat3_padding() {
+        let floats = |floats: &[f32]| {
+            floats
+                .iter()
+                .flat_map(|f| f.to_ne_bytes())
+                .collect::<Vec<u8>>()
+        };
+        let layout = UniformBlockLayout {
+            uniforms: vec![
+                UniformDesc::new("a", UniformType::Float1),
+                UniformDesc::new("m", UniformType::Mat3),
+                UniformDesc::new("b", UniformType::Float1),
+            ],
+        };
+        let data = floats(&[1., 2., 3., 4., 5., 6., 7., 8., 9., 10., 11.]);
+        assert_eq!(
+            layout.msl_bytes(&data).into_owned(),
+            floats(&[1., 0., 0., 0., 2., 3., 4., 0., 5., 6., 7., 0., 8., 9., 10., 0., 11.])
+        );
+
+        let layout = UniformBlockLayout {
+            uniforms: vec![UniformDesc::new("a", UniformType::Float4)],
+        };
+        assert!(matches!(layout.msl_bytes(&data), Cow::Borrowed(_)));
+    }
 }
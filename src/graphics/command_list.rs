@@ -0,0 +1,156 @@
+use std::ops::Range;
+
+use super::*;
+
+enum Command {
+    ApplyPipeline(Pipeline),
+    ApplyBindings {
+        vertex_buffers: Range<usize>,
+        index_buffer: BufferId,
+        images: Range<usize>,
+    },
+    ApplyUniforms {
+        words: Range<usize>,
+        size: usize,
+    },
+    ApplyViewport(i32, i32, i32, i32),
+    ApplyScissorRect(i32, i32, i32, i32),
+    Draw(i32, i32, i32),
+}
+
+/// Draw commands recorded without any GPU calls, so on any thread, and
+/// played back later with `ctx.submit_commands` on the main thread.
+/// ```ignore
+/// let lists: Vec<CommandList> = chunks
+///     .par_iter()
+///     .map(|chunk| {
+///         let mut list = CommandList::new();
+///         for object in chunk {
+///             list.apply_bindings(&object.bindings);
+///             list.apply_uniforms(UniformsSource::table(&object.uniforms));
+///             list.draw(0, object.num_elements, 1);
+///         }
+///         list
+///     })
+///     .collect();
+/// ctx.apply_pipeline(&pipeline);
+/// for list in &lists {
+///     ctx.submit_commands(list);
+/// }
+/// ```
+#[derive(Default)]
+pub struct CommandList {
+    commands: Vec<Command>,
+    buffers: Vec<BufferId>,
+    images: Vec<TextureId>,
+    // u32 to keep uniforms aligned, all the uniform types are 4 bytes multiples
+    uniforms: Vec<u32>,
+}
+
+impl CommandList {
+    pub fn new() -> CommandList {
+        CommandList::default()
+    }
+
+    pub fn apply_pipeline(&mut self, pipeline: &Pipeline) {
+        self.commands.push(Command::ApplyPipeline(*pipeline));
+    }
+
+    pub fn apply_bindings(&mut self, bindings: &Bindings) {
+        self.apply_bindings_from_slice(
+            &bindings.vertex_buffers,
+            bindings.index_buffer,
+            &bindings.images,
+        );
+    }
+
+    pub fn apply_bindings_from_slice(
+        &mut self,
+        vertex_buffers: &[BufferId],
+        index_buffer: BufferId,
+        textures: &[TextureId],
+    ) {
+        let buffers_start = self.buffers.len();
+        self.buffers.extend_from_slice(vertex_buffers);
+        let images_start = self.images.len();
+        self.images.extend_from_slice(textures);
+        self.commands.push(Command::ApplyBindings {
+            vertex_buffers: buffers_start..self.buffers.len(),
+            index_buffer,
+            images: images_start..self.images.len(),
+        });
+    }
+
+    /// The data is copied, the source can be dropped right after the call.
+    pub fn apply_uniforms(&mut self, uniforms: UniformsSource) {
+        let size = uniforms.0.size;
+        let start = self.uniforms.len();
+        self.uniforms.resize(start + (size + 3) / 4, 0);
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                uniforms.0.ptr as *const u8,
+                self.uniforms[start..].as_mut_ptr() as *mut u8,
+                size,
+            );
+        }
+        self.commands.push(Command::ApplyUniforms {
+            words: start..self.uniforms.len(),
+            size,
+        });
+    }
+
+    pub fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.commands.push(Command::ApplyViewport(x, y, w, h));
+    }
+
+    pub fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.commands.push(Command::ApplyScissorRect(x, y, w, h));
+    }
+
+    pub fn draw(&mut self, base_element: i32, num_elements: i32, num_instances: i32) {
+        self.commands
+            .push(Command::Draw(base_element, num_elements, num_instances));
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Remove all the commands, keeping the allocated memory for the next frame.
+    pub fn clear(&mut self) {
+        self.commands.clear();
+        self.buffers.clear();
+        self.images.clear();
+        self.uniforms.clear();
+    }
+
+    pub(crate) fn play(&self, ctx: &mut dyn RenderingBackend) {
+        for command in &self.commands {
+            match command {
+                Command::ApplyPipeline(pipeline) => ctx.apply_pipeline(pipeline),
+                Command::ApplyBindings {
+                    vertex_buffers,
+                    index_buffer,
+                    images,
+                } => ctx.apply_bindings_from_slice(
+                    &self.buffers[vertex_buffers.clone()],
+                    *index_buffer,
+                    &self.images[images.clone()],
+                ),
+                Command::ApplyUniforms { words, size } => ctx.apply_uniforms_from_bytes(
+                    self.uniforms[words.clone()].as_ptr() as *const u8,
+                    *size,
+                ),
+                Command::ApplyViewport(x, y, w, h) => ctx.apply_viewport(*x, *y, *w, *h),
+                Command::ApplyScissorRect(x, y, w, h) => ctx.apply_scissor_rect(*x, *y, *w, *h),
+                Command::Draw(base_element, num_elements, num_instances) => {
+                    ctx.draw(*base_element, *num_elements, *num_instances)
+                }
+            }
+        }
+    }
+}
@@ -1,11 +1,13 @@
-use std::ffi::CString;
+use std::{cell::Cell, collections::HashMap, ffi::CString};
 
 use crate::{window, ResourceManager};
 
 mod cache;
+mod restore;
 
 use super::*;
 use cache::*;
+use restore::*;
 
 /// Raw OpenGL bindings
 /// Highly unsafe, some of the functions could be missing due to incompatible GL version
@@ -25,6 +27,7 @@ pub mod raw_gl {
 struct Buffer {
     gl_buf: GLuint,
     buffer_type: BufferType,
+    usage: BufferUsage,
     size: usize,
     // Dimension of the indices for this buffer,
     // used only as a type argument for glDrawElements and can be
@@ -43,12 +46,19 @@ struct ShaderInternal {
     program: GLuint,
     images: Vec<ShaderImage>,
     uniforms: Vec<ShaderUniform>,
+    // kept around for shader_recompile
+    meta: ShaderMeta,
+    feedback_varyings: Vec<String>,
+    // set_uniform lookups, the type is only known in debug builds
+    named_uniforms: HashMap<String, (UniformLocation, Option<UniformType>)>,
 }
 
 #[derive(Clone, Copy, Debug)]
 struct Texture {
     raw: GLuint,
     params: TextureParams,
+    // glTexStorage2D, for TextureAccess::Storage
+    immutable: bool,
 }
 
 /// Converts from TextureFormat to (internal_format, format, pixel_type)
@@ -95,10 +105,14 @@ impl From<BlendFactor> for GLenum {
             BlendFactor::Value(BlendValue::SourceAlpha) => GL_SRC_ALPHA,
             BlendFactor::Value(BlendValue::DestinationColor) => GL_DST_COLOR,
             BlendFactor::Value(BlendValue::DestinationAlpha) => GL_DST_ALPHA,
+            BlendFactor::Value(BlendValue::Source1Color) => GL_SRC1_COLOR,
+            BlendFactor::Value(BlendValue::Source1Alpha) => GL_SRC1_ALPHA,
             BlendFactor::OneMinusValue(BlendValue::SourceColor) => GL_ONE_MINUS_SRC_COLOR,
             BlendFactor::OneMinusValue(BlendValue::SourceAlpha) => GL_ONE_MINUS_SRC_ALPHA,
             BlendFactor::OneMinusValue(BlendValue::DestinationColor) => GL_ONE_MINUS_DST_COLOR,
             BlendFactor::OneMinusValue(BlendValue::DestinationAlpha) => GL_ONE_MINUS_DST_ALPHA,
+            BlendFactor::OneMinusValue(BlendValue::Source1Color) => GL_ONE_MINUS_SRC1_COLOR,
+            BlendFactor::OneMinusValue(BlendValue::Source1Alpha) => GL_ONE_MINUS_SRC1_ALPHA,
             BlendFactor::SourceAlphaSaturate => GL_SRC_ALPHA_SATURATE,
         }
     }
@@ -137,10 +151,16 @@ impl From<CompareFunc> for GLenum {
 impl Texture {
     pub fn new(
         ctx: &mut GlContext,
-        _access: TextureAccess,
+        access: TextureAccess,
         source: TextureSource,
         params: TextureParams,
     ) -> Texture {
+        let storage_format = match (access, &source) {
+            (TextureAccess::Storage, TextureSource::Empty | TextureSource::Bytes(_)) => {
+                ctx.storage_image_format(&params)
+            }
+            _ => None,
+        };
         if let TextureSource::Bytes(bytes_data) = source {
             assert_eq!(
                 params.format.size(params.width, params.height) as usize,
@@ -172,6 +192,37 @@ impl Texture {
             }
 
             match source {
+                #[cfg(not(target_arch = "wasm32"))]
+                TextureSource::Empty | TextureSource::Bytes(_) if storage_format.is_some() => {
+                    let sized_format = storage_format.unwrap();
+                    let levels = if params.allocate_mipmaps
+                        || params.mipmap_filter != MipmapFilterMode::None
+                    {
+                        32 - params.width.max(params.height).max(1).leading_zeros()
+                    } else {
+                        1
+                    };
+                    glTexStorage2D(
+                        GL_TEXTURE_2D,
+                        levels as _,
+                        sized_format,
+                        params.width as _,
+                        params.height as _,
+                    );
+                    if let TextureSource::Bytes(source) = source {
+                        glTexSubImage2D(
+                            GL_TEXTURE_2D,
+                            0,
+                            0,
+                            0,
+                            params.width as _,
+                            params.height as _,
+                            format,
+                            pixel_type,
+                            source.as_ptr() as *const _,
+                        );
+                    }
+                }
                 TextureSource::Empty => {
                     // not quite sure if glTexImage2D(null) is really a requirement
                     // but it was like this for quite a while and apparantly it works?
@@ -208,10 +259,24 @@ impl Texture {
                             "Cubemaps require TextureSource::Array of 6 textures."
                         );
                     }
+                    let mipmapped = array.iter().any(|mipmaps| mipmaps.len() > 1);
+                    let max_mipmaps = if mipmapped && !ctx.can_mipmap(params.width, params.height) {
+                        eprintln!("NPOT texture mipmaps are not supported by the GPU");
+                        eprintln!("Uploading the mip level 0 only");
+                        1
+                    } else {
+                        usize::MAX
+                    };
                     for (cubemap_face, mipmaps) in array.iter().enumerate() {
-                        if mipmaps.len() != 1 {
-                            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_BASE_LEVEL, 0);
-                            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MAX_LEVEL, array.len() as _);
+                        let mipmaps = &mipmaps[..mipmaps.len().min(max_mipmaps)];
+                        // GLES2 has no max level, the whole mip chain should be given
+                        if mipmaps.len() != 1 && ctx.gl3 {
+                            glTexParameteri(params.kind.into(), GL_TEXTURE_BASE_LEVEL, 0);
+                            glTexParameteri(
+                                params.kind.into(),
+                                GL_TEXTURE_MAX_LEVEL,
+                                mipmaps.len() as i32 - 1,
+                            );
                         }
                         for (mipmap_level, bytes) in mipmaps.iter().enumerate() {
                             let target = match params.kind {
@@ -224,8 +289,8 @@ impl Texture {
                                 target,
                                 mipmap_level as _,
                                 internal_format as i32,
-                                params.width as i32,
-                                params.height as i32,
+                                (params.width >> mipmap_level).max(1) as i32,
+                                (params.height >> mipmap_level).max(1) as i32,
                                 0,
                                 format,
                                 pixel_type,
@@ -258,6 +323,7 @@ impl Texture {
         Texture {
             raw: texture,
             params,
+            immutable: storage_format.is_some(),
         }
     }
 
@@ -268,6 +334,21 @@ impl Texture {
     }
 
     pub fn resize(&mut self, ctx: &mut GlContext, width: u32, height: u32, source: Option<&[u8]>) {
+        // immutable storage can not be reallocated, the texture is made again
+        if self.immutable {
+            let source = match source {
+                Some(source) => TextureSource::Bytes(source),
+                None => TextureSource::Empty,
+            };
+            let params = TextureParams {
+                width,
+                height,
+                ..self.params
+            };
+            self.delete();
+            *self = Texture::new(ctx, TextureAccess::Storage, source, params);
+            return;
+        }
         ctx.cache.store_texture_binding(0);
         ctx.cache.bind_texture(0, self.params.kind.into(), self.raw);
 
@@ -401,8 +482,13 @@ impl Texture {
 
 pub(crate) struct PipelineInternal {
     layout: Vec<Option<VertexAttributeInternal>>,
+    // kept around to find the attribute locations again after a relink
+    buffer_layout: Vec<BufferLayout>,
+    attributes: Vec<VertexAttribute>,
     shader: ShaderId,
     params: PipelineParams,
+    /// (attachment, color_blend, alpha_blend)
+    attachment_blends: Vec<(usize, Option<BlendState>, Option<BlendState>)>,
 }
 
 type UniformLocation = Option<GLint>;
@@ -424,8 +510,15 @@ fn get_uniform_location(program: GLuint, name: &str) -> Option<i32> {
 
 pub(crate) struct RenderPassInternal {
     gl_fb: GLuint,
+    // kept around for restore_resources
+    color_targets: Vec<RenderTarget>,
+    depth_target: Option<RenderTarget>,
     color_textures: Vec<TextureId>,
     depth_texture: Option<TextureId>,
+    // attachment size is the size of this mip level of the first texture
+    mip_level: u32,
+    color_actions: Vec<AttachmentAction>,
+    depth_action: AttachmentAction,
 }
 
 struct Textures(Vec<Texture>);
@@ -435,6 +528,7 @@ impl Textures {
             TextureIdInner::Raw(RawId::OpenGl(texture)) => Texture {
                 raw: texture,
                 params: Default::default(),
+                immutable: false,
             },
             #[cfg(target_vendor = "apple")]
             TextureIdInner::Raw(RawId::Metal(..)) => panic!("Metal texture in OpenGL context!"),
@@ -442,16 +536,42 @@ impl Textures {
         }
     }
 }
+struct Query {
+    gl_query: GLuint,
+    target: GLenum,
+}
+
 pub struct GlContext {
     shaders: ResourceManager<ShaderInternal>,
     pipelines: ResourceManager<PipelineInternal>,
     passes: ResourceManager<RenderPassInternal>,
     buffers: ResourceManager<Buffer>,
+    queries: ResourceManager<Query>,
+    compute_pipelines: ResourceManager<ShaderInternal>,
     textures: Textures,
     default_framebuffer: GLuint,
+    vao: GLuint,
     pub(crate) cache: GlCache,
 
     pub(crate) features: Features,
+    gles: bool,
+    gl2: bool,
+    // GL3, GLES3 or WebGL2. gl2 is false on WebGL1, that is not GL2 but GLES2
+    gl3: bool,
+    khr_debug: bool,
+    invalidate_framebuffer: bool,
+    // GL_PRIMITIVE_RESTART is GL3.1+, GLES3 has the fixed index one and
+    // WebGL2 always restarts
+    primitive_restart: bool,
+    // for ContextInfo::egl
+    egl: bool,
+    // between begin_transform_feedback and end_transform_feedback
+    transform_feedback: bool,
+    cur_pass: Option<RenderPass>,
+    retained: Option<Retained>,
+    // draw() takes &self, hence the Cell
+    stats: Cell<FrameStats>,
+    last_frame_stats: FrameStats,
 }
 
 impl GlContext {
@@ -464,19 +584,93 @@ impl GlContext {
             );
             let mut vao = 0;
 
+            crate::native::gl::enable_debug_output();
+
             glGenVertexArrays(1, &mut vao as *mut _);
             glBindVertexArray(vao);
+
+            // compute shaders and storage buffers are GL4.3 and GLES3.1 features
+            let gl43 = cfg!(not(target_arch = "wasm32"))
+                && if crate::native::gl::is_gles() {
+                    crate::native::gl::gl_version() >= (3, 1)
+                } else {
+                    crate::native::gl::gl_version() >= (4, 3)
+                };
+            // what GL starts with, in case a pass ends before any apply_viewport
+            let (screen_width, screen_height) = crate::window::screen_size();
+            let viewport = (0, 0, screen_width as i32, screen_height as i32);
             GlContext {
                 default_framebuffer,
+                vao,
                 shaders: ResourceManager::default(),
                 pipelines: ResourceManager::default(),
                 passes: ResourceManager::default(),
                 buffers: ResourceManager::default(),
+                queries: ResourceManager::default(),
+                compute_pipelines: ResourceManager::default(),
                 textures: Textures(vec![]),
                 features: Features {
                     instancing: !crate::native::gl::is_gl2(),
+                    // WebGL2 and GL3/GLES3
+                    transform_feedback: if cfg!(target_arch = "wasm32") {
+                        crate::native::gl::gl_version() >= (3, 0)
+                    } else {
+                        !crate::native::gl::is_gl2()
+                    },
+                    occlusion_query: cfg!(not(target_arch = "wasm32"))
+                        && !crate::native::gl::is_gl2(),
+                    any_samples_query: any_samples_query_supported(),
+                    conditional_render: cfg!(not(target_arch = "wasm32"))
+                        && !crate::native::gl::is_gl2()
+                        && !crate::native::gl::is_gles(),
+                    compute: gl43,
+                    storage_buffers: gl43,
+                    geometry_shader: cfg!(not(target_arch = "wasm32"))
+                        && crate::native::gl::gl_version() >= (3, 2),
+                    program_binary: program_binary_supported(),
+                    float_textures: crate::native::gl::gl_version() >= (3, 0),
+                    max_texture_size: get_integer(GL_MAX_TEXTURE_SIZE).max(0) as u32,
+                    max_samples: if crate::native::gl::gl_version() >= (3, 0) {
+                        get_integer(GL_MAX_SAMPLES).max(1) as u32
+                    } else {
+                        1
+                    },
+                    max_color_attachments: if crate::native::gl::gl_version() >= (3, 0) {
+                        get_integer(GL_MAX_COLOR_ATTACHMENTS).max(1) as u32
+                    } else {
+                        1
+                    },
+                    independent_blend: cfg!(not(target_arch = "wasm32"))
+                        && if crate::native::gl::is_gles() {
+                            crate::native::gl::gl_version() >= (3, 2)
+                        } else {
+                            crate::native::gl::gl_version() >= (4, 0)
+                        },
+                    dual_source_blending: dual_source_blending_supported(),
                     ..Default::default()
                 },
+                gles: crate::native::gl::is_gles(),
+                gl2: crate::native::gl::is_gl2(),
+                gl3: crate::native::gl::gl_version() >= (3, 0),
+                khr_debug: crate::native::gl::has_khr_debug(),
+                // GLES3, WebGL2 and GL4.3
+                invalidate_framebuffer: if crate::native::gl::is_gles() {
+                    crate::native::gl::gl_version() >= (3, 0)
+                } else {
+                    crate::native::gl::gl_version() >= (4, 3)
+                },
+                primitive_restart: cfg!(not(target_arch = "wasm32"))
+                    && if crate::native::gl::is_gles() {
+                        crate::native::gl::gl_version() >= (3, 0)
+                    } else {
+                        crate::native::gl::gl_version() >= (3, 1)
+                    },
+                egl: crate::native_display().lock().unwrap().egl,
+                transform_feedback: false,
+                cur_pass: None,
+                retained: None,
+                stats: Cell::new(FrameStats::default()),
+                last_frame_stats: FrameStats::default(),
                 cache: GlCache {
                     stored_index_buffer: 0,
                     stored_index_type: None,
@@ -485,11 +679,17 @@ impl GlContext {
                     index_type: None,
                     vertex_buffer: 0,
                     cur_pipeline: None,
+                    cur_compute_pipeline: None,
                     color_blend: None,
                     alpha_blend: None,
+                    indexed_blend: false,
                     stencil: None,
                     color_write: (true, true, true, true),
                     cull_face: CullFace::Nothing,
+                    polygon_mode: PolygonMode::Fill,
+                    primitive_restart: Some(false),
+                    viewport,
+                    stored_viewport: viewport,
                     stored_texture: 0,
                     stored_target: 0,
                     textures: [CachedTexture {
@@ -507,68 +707,485 @@ impl GlContext {
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn load_binary_internal(
+    binary: &ShaderBinary,
+    meta: ShaderMeta,
+) -> Result<ShaderInternal, ShaderError> {
+    let program = glCreateProgram();
+    glProgramBinary(
+        program,
+        binary.format,
+        binary.data.as_ptr() as *const _,
+        binary.data.len() as _,
+    );
+    program_internal(program, meta)
+}
+
 fn load_shader_internal(
     vertex_shader: &str,
+    geometry_shader: Option<&str>,
     fragment_shader: &str,
     meta: ShaderMeta,
+    feedback_varyings: &[&str],
+    binary_retrievable: bool,
 ) -> Result<ShaderInternal, ShaderError> {
     unsafe {
         let vertex_shader = load_shader(GL_VERTEX_SHADER, vertex_shader)?;
-        let fragment_shader = load_shader(GL_FRAGMENT_SHADER, fragment_shader)?;
+        let geometry_shader = match geometry_shader.map(|s| load_shader(GL_GEOMETRY_SHADER, s)) {
+            Some(Ok(geometry_shader)) => Some(geometry_shader),
+            Some(Err(err)) => {
+                glDeleteShader(vertex_shader);
+                return Err(err);
+            }
+            None => None,
+        };
+        let fragment_shader = match load_shader(GL_FRAGMENT_SHADER, fragment_shader) {
+            Ok(fragment_shader) => fragment_shader,
+            Err(err) => {
+                glDeleteShader(vertex_shader);
+                if let Some(geometry_shader) = geometry_shader {
+                    glDeleteShader(geometry_shader);
+                }
+                return Err(err);
+            }
+        };
 
         let program = glCreateProgram();
         glAttachShader(program, vertex_shader);
+        if let Some(geometry_shader) = geometry_shader {
+            glAttachShader(program, geometry_shader);
+        }
         glAttachShader(program, fragment_shader);
-        glLinkProgram(program);
+        let varyings = if !feedback_varyings.is_empty() {
+            set_feedback_varyings(program, feedback_varyings)
+        } else {
+            Ok(())
+        };
+        // for shader_binary, some drivers keep no binary without the hint
+        #[cfg(not(target_arch = "wasm32"))]
+        if binary_retrievable {
+            glProgramParameteri(program, GL_PROGRAM_BINARY_RETRIEVABLE_HINT, GL_TRUE as _);
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = binary_retrievable;
+        if varyings.is_ok() {
+            glLinkProgram(program);
+        }
 
         // delete no longer used shaders
         glDetachShader(program, vertex_shader);
+        glDetachShader(program, fragment_shader);
         glDeleteShader(vertex_shader);
         glDeleteShader(fragment_shader);
+        if let Some(geometry_shader) = geometry_shader {
+            glDetachShader(program, geometry_shader);
+            glDeleteShader(geometry_shader);
+        }
 
-        let mut link_status = 0;
-        glGetProgramiv(program, GL_LINK_STATUS, &mut link_status as *mut _);
-        if link_status == 0 {
-            let mut max_length: i32 = 0;
-            glGetProgramiv(program, GL_INFO_LOG_LENGTH, &mut max_length as *mut _);
+        if let Err(err) = varyings {
+            glDeleteProgram(program);
+            return Err(err);
+        }
 
-            let mut error_message = vec![0u8; max_length as usize + 1];
-            glGetProgramInfoLog(
+        let mut shader = program_internal(program, meta)?;
+        shader.feedback_varyings = feedback_varyings.iter().map(|v| v.to_string()).collect();
+        Ok(shader)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_compute_internal(source: &str, meta: ShaderMeta) -> Result<ShaderInternal, ShaderError> {
+    unsafe {
+        let compute_shader = load_shader(GL_COMPUTE_SHADER, source)?;
+
+        let program = glCreateProgram();
+        glAttachShader(program, compute_shader);
+        glLinkProgram(program);
+
+        glDetachShader(program, compute_shader);
+        glDeleteShader(compute_shader);
+
+        program_internal(program, meta)
+    }
+}
+
+unsafe fn program_internal(
+    program: GLuint,
+    meta: ShaderMeta,
+) -> Result<ShaderInternal, ShaderError> {
+    let mut link_status = 0;
+    glGetProgramiv(program, GL_LINK_STATUS, &mut link_status as *mut _);
+    if link_status == 0 {
+        let mut max_length: i32 = 0;
+        glGetProgramiv(program, GL_INFO_LOG_LENGTH, &mut max_length as *mut _);
+
+        let mut error_message = vec![0u8; max_length as usize + 1];
+        glGetProgramInfoLog(
+            program,
+            max_length,
+            &mut max_length as *mut _,
+            error_message.as_mut_ptr() as *mut _,
+        );
+        // some drivers do not provide any log at all
+        let log_length = (max_length as usize).saturating_sub(1);
+        let error_message =
+            std::string::String::from_utf8_lossy(&error_message[0..log_length]).to_string();
+        glDeleteProgram(program);
+        return Err(ShaderError::LinkError(error_message));
+    }
+
+    glUseProgram(program);
+
+    let (images, uniforms) = shader_locations(program, &meta);
+    Ok(ShaderInternal {
+        program,
+        images,
+        uniforms,
+        meta,
+        feedback_varyings: vec![],
+        named_uniforms: HashMap::new(),
+    })
+}
+
+fn shader_locations(program: GLuint, meta: &ShaderMeta) -> (Vec<ShaderImage>, Vec<ShaderUniform>) {
+    #[rustfmt::skip]
+    let images = meta.images.iter().map(|name| ShaderImage {
+        gl_loc: get_uniform_location(program, name),
+    }).collect();
+
+    #[rustfmt::skip]
+    let uniforms = meta.uniforms.uniforms.iter().scan(0, |offset, uniform| {
+        let res = ShaderUniform {
+            gl_loc: get_uniform_location(program, &uniform.name),
+            uniform_type: uniform.uniform_type,
+            array_count: uniform.array_count as _,
+        };
+        *offset += uniform.uniform_type.size() * uniform.array_count;
+        Some(res)
+    }).collect();
+
+    (images, uniforms)
+}
+
+/// The active uniforms of the program declared in the meta should have its
+/// type and at most its array size, uniforms only set by name may be
+/// undeclared. All the samplers should be declared, they are bound by index.
+fn check_layout(program: GLuint, meta: &ShaderMeta) -> Result<(), String> {
+    let reflection = unsafe { reflect_program(program) };
+    let declared_uniforms = &meta.uniforms.uniforms;
+    for uniform in &reflection.uniforms {
+        let declared = match declared_uniforms.iter().find(|u| u.name == uniform.name) {
+            Some(declared) => declared,
+            None => continue,
+        };
+        if uniform.uniform_type != Some(declared.uniform_type) {
+            return Err(format!(
+                "uniform {} is {:?}, declared as {:?}",
+                uniform.name, uniform.uniform_type, declared.uniform_type
+            ));
+        }
+        if uniform.array_count > declared.array_count {
+            return Err(format!(
+                "uniform {} has {} elements, declared with {}",
+                uniform.name, uniform.array_count, declared.array_count
+            ));
+        }
+    }
+    for image in &reflection.images {
+        if !meta.images.contains(image) {
+            return Err(format!("image {} is not declared", image));
+        }
+    }
+    Ok(())
+}
+
+unsafe fn get_integer(pname: GLenum) -> GLint {
+    let mut value = 0;
+    glGetIntegerv(pname, &mut value as *mut _);
+    value
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn program_binary_supported() -> bool {
+    let version = if crate::native::gl::is_gles() {
+        crate::native::gl::gl_version() >= (3, 0)
+    } else {
+        crate::native::gl::gl_version() >= (4, 1)
+            || crate::native::gl::has_extension("GL_ARB_get_program_binary")
+    };
+    if !version {
+        return false;
+    }
+    // a lot of drivers support the API, but no formats at all
+    let mut formats = 0;
+    glGetIntegerv(GL_NUM_PROGRAM_BINARY_FORMATS, &mut formats as *mut _);
+    formats > 0
+}
+
+#[cfg(target_arch = "wasm32")]
+unsafe fn program_binary_supported() -> bool {
+    false
+}
+
+unsafe fn set_uniform(gl_loc: GLint, uniform_type: UniformType, array_count: i32, data: *const u8) {
+    use UniformType::*;
+
+    let data_int = data as *const i32;
+    let data = data as *const f32;
+    match uniform_type {
+        Float1 => {
+            glUniform1fv(gl_loc, array_count, data);
+        }
+        Float2 => {
+            glUniform2fv(gl_loc, array_count, data);
+        }
+        Float3 => {
+            glUniform3fv(gl_loc, array_count, data);
+        }
+        Float4 => {
+            glUniform4fv(gl_loc, array_count, data);
+        }
+        Int1 => {
+            glUniform1iv(gl_loc, array_count, data_int);
+        }
+        Int2 => {
+            glUniform2iv(gl_loc, array_count, data_int);
+        }
+        Int3 => {
+            glUniform3iv(gl_loc, array_count, data_int);
+        }
+        Int4 => {
+            glUniform4iv(gl_loc, array_count, data_int);
+        }
+        Mat3 => {
+            glUniformMatrix3fv(gl_loc, array_count, 0, data);
+        }
+        Mat4 => {
+            glUniformMatrix4fv(gl_loc, array_count, 0, data);
+        }
+        Bool => {
+            glUniform1iv(gl_loc, array_count, data_int);
+        }
+    }
+}
+
+fn reflect_uniform_type(gl_type: GLenum) -> Option<UniformType> {
+    match gl_type {
+        GL_FLOAT => Some(UniformType::Float1),
+        GL_FLOAT_VEC2 => Some(UniformType::Float2),
+        GL_FLOAT_VEC3 => Some(UniformType::Float3),
+        GL_FLOAT_VEC4 => Some(UniformType::Float4),
+        GL_INT => Some(UniformType::Int1),
+        GL_INT_VEC2 => Some(UniformType::Int2),
+        GL_INT_VEC3 => Some(UniformType::Int3),
+        GL_INT_VEC4 => Some(UniformType::Int4),
+        GL_FLOAT_MAT3 => Some(UniformType::Mat3),
+        GL_FLOAT_MAT4 => Some(UniformType::Mat4),
+        GL_BOOL => Some(UniformType::Bool),
+        _ => None,
+    }
+}
+
+fn reflect_vertex_format(gl_type: GLenum) -> Option<VertexFormat> {
+    match gl_type {
+        GL_FLOAT => Some(VertexFormat::Float1),
+        GL_FLOAT_VEC2 => Some(VertexFormat::Float2),
+        GL_FLOAT_VEC3 => Some(VertexFormat::Float3),
+        GL_FLOAT_VEC4 => Some(VertexFormat::Float4),
+        GL_INT => Some(VertexFormat::Int1),
+        GL_INT_VEC2 => Some(VertexFormat::Int2),
+        GL_INT_VEC3 => Some(VertexFormat::Int3),
+        GL_INT_VEC4 => Some(VertexFormat::Int4),
+        GL_FLOAT_MAT4 => Some(VertexFormat::Mat4),
+        _ => None,
+    }
+}
+
+fn is_sampler(gl_type: GLenum) -> bool {
+    matches!(
+        gl_type,
+        GL_SAMPLER_2D | GL_SAMPLER_3D | GL_SAMPLER_CUBE | GL_SAMPLER_2D_ARRAY
+    )
+}
+
+unsafe fn reflect_program(program: GLuint) -> ShaderReflection {
+    // (name, array size, type) for each active uniform or attribute
+    unsafe fn active(program: GLuint, pname: GLenum) -> Vec<(String, usize, GLenum)> {
+        let mut count = 0;
+        glGetProgramiv(program, pname, &mut count as *mut _);
+
+        let mut res = vec![];
+        for i in 0..count.max(0) as GLuint {
+            let mut name = [0u8; 256];
+            let mut length = 0;
+            let mut size = 0;
+            let mut gl_type = 0;
+            let get = if pname == GL_ACTIVE_UNIFORMS {
+                glGetActiveUniform
+            } else {
+                glGetActiveAttrib
+            };
+            get(
                 program,
-                max_length,
-                &mut max_length as *mut _,
-                error_message.as_mut_ptr() as *mut _,
+                i,
+                name.len() as _,
+                &mut length as *mut _,
+                &mut size as *mut _,
+                &mut gl_type as *mut _,
+                name.as_mut_ptr() as *mut _,
             );
-            assert!(max_length >= 1);
-            let error_message =
-                std::string::String::from_utf8_lossy(&error_message[0..max_length as usize - 1]);
-            return Err(ShaderError::LinkError(error_message.to_string()));
+            let length = (length.max(0) as usize).min(name.len());
+            let name = String::from_utf8_lossy(&name[0..length]).to_string();
+            // arrays are reported as "name[0]"
+            let name = name
+                .strip_suffix("[0]")
+                .map(|n| n.to_string())
+                .unwrap_or(name);
+            res.push((name, size.max(1) as usize, gl_type));
         }
+        res
+    }
+
+    let mut reflection = ShaderReflection::default();
+    for (name, array_count, gl_type) in active(program, GL_ACTIVE_UNIFORMS) {
+        if is_sampler(gl_type) {
+            reflection.images.push(name);
+            continue;
+        }
+        reflection.uniforms.push(ReflectedUniform {
+            location: get_uniform_location(program, &name).unwrap_or(-1),
+            uniform_type: reflect_uniform_type(gl_type),
+            array_count,
+            name,
+        });
+    }
+    for (name, array_count, gl_type) in active(program, GL_ACTIVE_ATTRIBUTES) {
+        let cname = CString::new(name.as_str()).unwrap_or_else(|e| panic!("{}", e));
+        reflection.attributes.push(ReflectedAttribute {
+            location: glGetAttribLocation(program, cname.as_ptr()),
+            format: reflect_vertex_format(gl_type),
+            array_count,
+            name,
+        });
+    }
+    reflection
+}
 
-        glUseProgram(program);
+unsafe fn set_feedback_varyings(program: GLuint, varyings: &[&str]) -> Result<(), ShaderError> {
+    let varyings = varyings
+        .iter()
+        .map(|varying| CString::new(*varying))
+        .collect::<Result<Vec<_>, _>>()?;
+    let varyings_ptrs = varyings.iter().map(|v| v.as_ptr()).collect::<Vec<_>>();
+    glTransformFeedbackVaryings(
+        program,
+        varyings_ptrs.len() as _,
+        varyings_ptrs.as_ptr() as *const _,
+        GL_SEPARATE_ATTRIBS,
+    );
+    Ok(())
+}
 
-        #[rustfmt::skip]
-        let images = meta.images.iter().map(|name| ShaderImage {
-            gl_loc: get_uniform_location(program, name),
-        }).collect();
+/// Vertex attributes of a pipeline, indexed by their location in `program`.
+fn vertex_layout(
+    program: GLuint,
+    buffer_layout: &[BufferLayout],
+    attributes: &[VertexAttribute],
+) -> Vec<Option<VertexAttributeInternal>> {
+    #[derive(Clone, Copy, Default)]
+    struct BufferCacheData {
+        stride: i32,
+        offset: i64,
+    }
 
-        #[rustfmt::skip]
-        let uniforms = meta.uniforms.uniforms.iter().scan(0, |offset, uniform| {
-            let res = ShaderUniform {
-                gl_loc: get_uniform_location(program, &uniform.name),
-                uniform_type: uniform.uniform_type,
-                array_count: uniform.array_count as _,
-            };
-            *offset += uniform.uniform_type.size() * uniform.array_count;
-            Some(res)
-        }).collect();
+    let mut buffer_cache: Vec<BufferCacheData> =
+        vec![BufferCacheData::default(); buffer_layout.len()];
+
+    for VertexAttribute {
+        format,
+        buffer_index,
+        ..
+    } in attributes
+    {
+        let layout = buffer_layout.get(*buffer_index).unwrap_or_else(|| panic!());
+        let mut cache = buffer_cache
+            .get_mut(*buffer_index)
+            .unwrap_or_else(|| panic!());
+
+        if layout.stride == 0 {
+            cache.stride += format.size_bytes();
+        } else {
+            cache.stride = layout.stride;
+        }
+        // WebGL 1 limitation
+        assert!(cache.stride <= 255);
+    }
 
-        Ok(ShaderInternal {
-            program,
-            images,
-            uniforms,
+    let attributes_len = attributes
+        .iter()
+        .map(|layout| match layout.format {
+            VertexFormat::Mat4 => 4,
+            _ => 1,
         })
+        .sum();
+
+    let mut vertex_layout: Vec<Option<VertexAttributeInternal>> = vec![None; attributes_len];
+
+    for VertexAttribute {
+        name,
+        format,
+        buffer_index,
+    } in attributes
+    {
+        let mut buffer_data = &mut buffer_cache
+            .get_mut(*buffer_index)
+            .unwrap_or_else(|| panic!());
+        let layout = buffer_layout.get(*buffer_index).unwrap_or_else(|| panic!());
+
+        let cname = CString::new(*name).unwrap_or_else(|e| panic!("{}", e));
+        let attr_loc = unsafe { glGetAttribLocation(program, cname.as_ptr() as *const _) };
+        let attr_loc = if attr_loc == -1 { None } else { Some(attr_loc) };
+        let divisor = if layout.step_func == VertexStep::PerVertex {
+            0
+        } else {
+            layout.step_rate
+        };
+
+        let mut attributes_count: usize = 1;
+        let mut format = *format;
+
+        if format == VertexFormat::Mat4 {
+            format = VertexFormat::Float4;
+            attributes_count = 4;
+        }
+        for i in 0..attributes_count {
+            if let Some(attr_loc) = attr_loc {
+                let attr_loc = attr_loc as GLuint + i as GLuint;
+
+                let attr = VertexAttributeInternal {
+                    attr_loc,
+                    size: format.components(),
+                    type_: format.type_(),
+                    offset: buffer_data.offset,
+                    stride: buffer_data.stride,
+                    buffer_index: *buffer_index,
+                    divisor,
+                };
+
+                assert!(
+                    attr_loc < vertex_layout.len() as u32,
+                    "attribute: {} outside of allocated attributes array len: {}",
+                    name,
+                    vertex_layout.len()
+                );
+                vertex_layout[attr_loc as usize] = Some(attr);
+            }
+            buffer_data.offset += format.size_bytes() as i64
+        }
     }
+
+    vertex_layout
 }
 
 pub fn load_shader(shader_type: GLenum, source: &str) -> Result<GLuint, ShaderError> {
@@ -595,20 +1212,23 @@ pub fn load_shader(shader_type: GLenum, source: &str) -> Result<GLuint, ShaderEr
                 error_message.as_mut_ptr() as *mut _,
             );
 
-            assert!(max_length >= 1);
+            let log_length = (max_length as usize).saturating_sub(1);
             let mut error_message =
-                std::string::String::from_utf8_lossy(&error_message[0..max_length as usize - 1])
-                    .into_owned();
+                std::string::String::from_utf8_lossy(&error_message[0..log_length]).into_owned();
 
             // On Wasm + Chrome, for unknown reason, string with zero-terminator is returned. On Firefox there is no zero-terminators in JavaScript string.
             if error_message.ends_with('\0') {
                 error_message.pop();
             }
+            glDeleteShader(shader);
 
             return Err(ShaderError::CompilationError {
                 shader_type: match shader_type {
                     GL_VERTEX_SHADER => ShaderType::Vertex,
+                    GL_GEOMETRY_SHADER => ShaderType::Geometry,
                     GL_FRAGMENT_SHADER => ShaderType::Fragment,
+                    #[cfg(not(target_arch = "wasm32"))]
+                    GL_COMPUTE_SHADER => ShaderType::Compute,
                     _ => unreachable!(),
                 },
                 error_message,
@@ -620,17 +1240,260 @@ pub fn load_shader(shader_type: GLenum, source: &str) -> Result<GLuint, ShaderEr
 }
 
 impl GlContext {
+    /// GLES3.1 binds only textures with immutable storage as compute images,
+    /// `TextureAccess::Storage` textures of the formats that can be images
+    /// get it.
+    fn storage_image_format(&self, params: &TextureParams) -> Option<GLenum> {
+        if !self.features.compute || params.kind != TextureKind::Texture2D {
+            return None;
+        }
+        match params.format {
+            TextureFormat::RGBA8 => Some(GL_RGBA8),
+            TextureFormat::RGBA16F => Some(GL_RGBA16F),
+            _ => None,
+        }
+    }
+
+    fn glsl_sources<'a>(
+        &self,
+        shader: ShaderSource<'a>,
+    ) -> Result<(&'a str, Option<&'a str>, &'a str), ShaderError> {
+        match shader {
+            ShaderSource::Glsl { vertex, fragment } => Ok((vertex, None, fragment)),
+            ShaderSource::GlslGeometry { .. } if !self.features.geometry_shader => {
+                Err(ShaderError::NotSupported(
+                    "Geometry shaders are not supported by the GPU".to_string(),
+                ))
+            }
+            ShaderSource::GlslGeometry {
+                vertex,
+                geometry,
+                fragment,
+            } => Ok((vertex, Some(geometry), fragment)),
+            ShaderSource::Msl { .. } => panic!("Metal source on OpenGl context"),
+        }
+    }
+
+    /// A relinked program may put the attributes at other locations, look
+    /// them up again for every pipeline using the shader.
+    pub(super) fn update_pipeline_layouts(&mut self, shader: ShaderId) {
+        let program = self.shaders[shader.0].program;
+        for id in self.pipelines.ids() {
+            let pipeline = &mut self.pipelines[id];
+            if pipeline.shader == shader {
+                pipeline.layout =
+                    vertex_layout(program, &pipeline.buffer_layout, &pipeline.attributes);
+            }
+        }
+    }
+
+    /// Without storage buffer support they are plain buffers that are never bound.
+    pub(super) fn gl_buffer_target(&self, buffer_type: &BufferType) -> GLenum {
+        match buffer_type {
+            BufferType::VertexBuffer => GL_ARRAY_BUFFER,
+            BufferType::IndexBuffer => GL_ELEMENT_ARRAY_BUFFER,
+            BufferType::Storage if self.features.storage_buffers => GL_SHADER_STORAGE_BUFFER,
+            BufferType::Storage => GL_ARRAY_BUFFER,
+        }
+    }
+
+    /// Attach the new GL texture of a recreated `texture` to the passes using it.
+    fn update_framebuffers(&mut self, texture: TextureId) {
+        for id in self.passes.ids() {
+            let pass = &self.passes[id];
+            let uses_texture = pass
+                .color_targets
+                .iter()
+                .chain(&pass.depth_target)
+                .any(|target| target.texture == texture);
+            if !uses_texture {
+                continue;
+            }
+            let (color, depth) = (pass.color_targets.clone(), pass.depth_target);
+            unsafe {
+                glDeleteFramebuffers(1, &pass.gl_fb as *const _);
+                self.passes[id].gl_fb = self.create_framebuffer(&color, depth);
+            }
+        }
+    }
+
+    unsafe fn create_gl_buffer(
+        &mut self,
+        gl_target: GLenum,
+        gl_usage: GLenum,
+        index_type: Option<u32>,
+        size: usize,
+        data: *const std::ffi::c_void,
+    ) -> GLuint {
+        let mut gl_buf: u32 = 0;
+        glGenBuffers(1, &mut gl_buf as *mut _);
+        self.cache.store_buffer_binding(gl_target);
+        self.cache.bind_buffer(gl_target, gl_buf, index_type);
+
+        glBufferData(gl_target, size as _, std::ptr::null() as *const _, gl_usage);
+        if !data.is_null() {
+            glBufferSubData(gl_target, 0, size as _, data as _);
+        }
+        self.cache.restore_buffer_binding(gl_target);
+        gl_buf
+    }
+
+    unsafe fn create_framebuffer(
+        &self,
+        color_img: &[RenderTarget],
+        depth_img: Option<RenderTarget>,
+    ) -> GLuint {
+        let mut gl_fb = 0;
+        glGenFramebuffers(1, &mut gl_fb as *mut _);
+        glBindFramebuffer(GL_FRAMEBUFFER, gl_fb);
+        for (i, color_img) in color_img.iter().enumerate() {
+            self.framebuffer_texture(GL_COLOR_ATTACHMENT0 + i as u32, *color_img);
+        }
+        if let Some(depth_img) = depth_img {
+            self.framebuffer_texture(GL_DEPTH_ATTACHMENT, depth_img);
+        }
+        // depth-only pass, for shadow maps and such. GLES2 and WebGL1 have
+        // no glDrawBuffers but do not need it either
+        if color_img.is_empty() && self.gl3 {
+            let none = GL_NONE;
+            glDrawBuffers(1, &none as *const _);
+            glReadBuffer(GL_NONE);
+        }
+        if color_img.len() > 1 {
+            let mut attachments = vec![];
+            for i in 0..color_img.len() {
+                attachments.push(GL_COLOR_ATTACHMENT0 + i as u32);
+            }
+            glDrawBuffers(attachments.len() as _, attachments.as_ptr() as _);
+        }
+
+        glBindFramebuffer(GL_FRAMEBUFFER, self.default_framebuffer);
+        gl_fb
+    }
+
+    // GLES2 and WebGL1 can't mipmap non-power-of-two textures
+    fn can_mipmap(&self, width: u32, height: u32) -> bool {
+        self.gl3 || (width.is_power_of_two() && height.is_power_of_two())
+    }
+
+    unsafe fn framebuffer_texture(&self, attachment: GLenum, target: RenderTarget) {
+        let texture = self.textures.get(target.texture);
+        assert!(
+            target.mip_level == 0 || self.gl3,
+            "GLES2 and WebGL1 can only render to the mip level 0"
+        );
+        let tex_target = match texture.params.kind {
+            TextureKind::Texture2D => {
+                assert!(target.layer == 0, "Layer of a 2D texture should be 0");
+                GL_TEXTURE_2D
+            }
+            TextureKind::CubeMap => {
+                assert!(target.layer < 6, "Cube map face should be 0..6");
+                GL_TEXTURE_CUBE_MAP_POSITIVE_X + target.layer
+            }
+        };
+        glFramebufferTexture2D(
+            GL_FRAMEBUFFER,
+            attachment,
+            tex_target,
+            texture.raw,
+            target.mip_level as _,
+        );
+    }
+
+    // clear, keep or discard each attachment according to its LoadAction
+    fn load_pass_attachments(
+        &mut self,
+        pass: RenderPass,
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        let pass = &self.passes[pass.0];
+        let color_actions = pass.color_actions.clone();
+        let depth_action = pass.depth_action;
+
+        let mut attachments = vec![];
+        for (i, action) in color_actions.iter().enumerate() {
+            if action.load == LoadAction::DontCare {
+                attachments.push(GL_COLOR_ATTACHMENT0 + i as u32);
+            }
+        }
+        if depth_action.load == LoadAction::DontCare {
+            attachments.extend([GL_DEPTH_ATTACHMENT, GL_STENCIL_ATTACHMENT]);
+        }
+        self.invalidate_attachments(&attachments);
+
+        let (depth, stencil) = match depth_action.load {
+            LoadAction::Clear => (depth, stencil),
+            _ => (None, None),
+        };
+        if color_actions
+            .iter()
+            .all(|action| action.load == LoadAction::Clear)
+        {
+            self.clear(color, depth, stencil);
+        } else {
+            self.clear(None, depth, stencil);
+            if let Some(color) = color {
+                for (i, action) in color_actions.iter().enumerate() {
+                    if action.load == LoadAction::Clear {
+                        self.clear_color_attachment(i, color);
+                    }
+                }
+            }
+        }
+    }
+
+    fn invalidate_attachments(&self, attachments: &[GLenum]) {
+        if !self.invalidate_framebuffer || attachments.is_empty() {
+            return;
+        }
+        unsafe {
+            glInvalidateFramebuffer(GL_FRAMEBUFFER, attachments.len() as _, attachments.as_ptr());
+        }
+    }
+
+    fn count(&self, f: impl FnOnce(&mut FrameStats)) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn object_label(&self, identifier: GLenum, name: GLuint, label: &str) {
+        if self.khr_debug {
+            unsafe {
+                glObjectLabel(
+                    identifier,
+                    name,
+                    label.len() as _,
+                    label.as_ptr() as *const _,
+                );
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn object_label(&self, _identifier: GLenum, _name: GLuint, _label: &str) {}
+
     fn set_blend(&mut self, color_blend: Option<BlendState>, alpha_blend: Option<BlendState>) {
         if color_blend.is_none() && alpha_blend.is_some() {
             panic!("AlphaBlend without ColorBlend");
         }
-        if self.cache.color_blend == color_blend && self.cache.alpha_blend == alpha_blend {
+        // non-indexed calls set the state of all the attachments, the cache
+        // is valid again after them
+        let indexed = std::mem::replace(&mut self.cache.indexed_blend, false);
+        if !indexed
+            && self.cache.color_blend == color_blend
+            && self.cache.alpha_blend == alpha_blend
+        {
             return;
         }
 
         unsafe {
             if let Some(color_blend) = color_blend {
-                if self.cache.color_blend.is_none() {
+                if indexed || self.cache.color_blend.is_none() {
                     glEnable(GL_BLEND);
                 }
 
@@ -657,7 +1520,7 @@ impl GlContext {
                     glBlendFunc(src_rgb.into(), dst_rgb.into());
                     glBlendEquationSeparate(eq_rgb.into(), eq_rgb.into());
                 }
-            } else if self.cache.color_blend.is_some() {
+            } else if indexed || self.cache.color_blend.is_some() {
                 glDisable(GL_BLEND);
             }
         }
@@ -666,6 +1529,45 @@ impl GlContext {
         self.cache.alpha_blend = alpha_blend;
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    unsafe fn set_attachment_blend(
+        &mut self,
+        attachment: usize,
+        color_blend: Option<BlendState>,
+        alpha_blend: Option<BlendState>,
+    ) {
+        let buf = attachment as GLuint;
+        match color_blend {
+            Some(color_blend) => {
+                let alpha_blend = alpha_blend.unwrap_or(color_blend);
+                glEnablei(GL_BLEND, buf);
+                glBlendFuncSeparatei(
+                    buf,
+                    color_blend.sfactor.into(),
+                    color_blend.dfactor.into(),
+                    alpha_blend.sfactor.into(),
+                    alpha_blend.dfactor.into(),
+                );
+                glBlendEquationSeparatei(
+                    buf,
+                    color_blend.equation.into(),
+                    alpha_blend.equation.into(),
+                );
+            }
+            None => glDisablei(GL_BLEND, buf),
+        }
+        self.cache.indexed_blend = true;
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    unsafe fn set_attachment_blend(
+        &mut self,
+        _attachment: usize,
+        _color_blend: Option<BlendState>,
+        _alpha_blend: Option<BlendState>,
+    ) {
+    }
+
     fn set_stencil(&mut self, stencil_test: Option<StencilState>) {
         if self.cache.stencil == stencil_test {
             return;
@@ -734,6 +1636,47 @@ impl GlContext {
         self.cache.cull_face = cull_face;
     }
 
+    fn set_polygon_mode(&mut self, polygon_mode: PolygonMode) {
+        // glPolygonMode is not available on GLES and WebGL
+        if self.gles || self.cache.polygon_mode == polygon_mode {
+            return;
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        unsafe {
+            let mode = match polygon_mode {
+                PolygonMode::Fill => GL_FILL,
+                PolygonMode::Line => GL_LINE,
+                PolygonMode::Point => GL_POINT,
+            };
+            glPolygonMode(GL_FRONT_AND_BACK, mode);
+        }
+        self.cache.polygon_mode = polygon_mode;
+    }
+
+    fn set_primitive_restart(&mut self, primitive_restart: bool) {
+        if !self.primitive_restart || self.cache.primitive_restart == Some(primitive_restart) {
+            return;
+        }
+
+        // GLES3 only have a fixed restart index, on desktop GL the index
+        // is set in draw() based on the index buffer type
+        #[cfg(not(target_arch = "wasm32"))]
+        unsafe {
+            let cap = if self.gles {
+                GL_PRIMITIVE_RESTART_FIXED_INDEX
+            } else {
+                GL_PRIMITIVE_RESTART
+            };
+            if primitive_restart {
+                glEnable(cap);
+            } else {
+                glDisable(cap);
+            }
+        }
+        self.cache.primitive_restart = Some(primitive_restart);
+    }
+
     fn set_color_write(&mut self, color_write: ColorMask) {
         if self.cache.color_write == color_write {
             return;
@@ -744,13 +1687,50 @@ impl GlContext {
     }
 }
 
-impl RenderingBackend for GlContext {
+// empty when the string is not available, e.g. with a lost context
+unsafe fn gl_string(name: GLenum) -> String {
+    let string = glGetString(name);
+    if string.is_null() {
+        return String::new();
+    }
+    std::ffi::CStr::from_ptr(string as _)
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn any_samples_query_supported() -> bool {
+    use crate::native::gl::{gl_version, has_extension, is_gles};
+
+    if is_gles() {
+        gl_version() >= (3, 0)
+    } else {
+        gl_version() >= (3, 3) || has_extension("GL_ARB_occlusion_query2")
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+unsafe fn any_samples_query_supported() -> bool {
+    false
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn dual_source_blending_supported() -> bool {
+    use crate::native::gl::{gl_version, has_extension, is_gles};
+
+    (!is_gles() && gl_version() >= (3, 3))
+        || has_extension("GL_ARB_blend_func_extended")
+        || has_extension("GL_EXT_blend_func_extended")
+}
+
+#[cfg(target_arch = "wasm32")]
+unsafe fn dual_source_blending_supported() -> bool {
+    false
+}
+
+impl RenderingBackend for GlContext {
     fn info(&self) -> ContextInfo {
-        let version_string = unsafe { glGetString(super::gl::GL_VERSION) };
-        let gl_version_string = unsafe { std::ffi::CStr::from_ptr(version_string as _) }
-            .to_str()
-            .unwrap()
-            .to_string();
+        let gl_version_string = unsafe { gl_string(GL_VERSION) };
         let gles3 = gl_version_string.contains("OpenGL ES 3");
         let gles2 = !gles3 && gl_version_string.contains("OpenGL ES");
 
@@ -766,6 +1746,9 @@ impl RenderingBackend for GlContext {
         {
             // on web, miniquad always loads EXT_shader_texture_lod and OES_standard_derivatives
             glsl_support.v100_ext = true;
+            if unsafe { crate::native::gl::gl_version() } >= (3, 0) {
+                glsl_support.v300es = true;
+            }
         }
 
         #[cfg(not(target_arch = "wasm32"))]
@@ -787,6 +1770,10 @@ impl RenderingBackend for GlContext {
         ContextInfo {
             backend: Backend::OpenGl,
             gl_version_string,
+            glsl_version_string: unsafe { gl_string(GL_SHADING_LANGUAGE_VERSION) },
+            vendor: unsafe { gl_string(GL_VENDOR) },
+            renderer: unsafe { gl_string(GL_RENDERER) },
+            egl: self.egl,
             glsl_support,
             features: self.features.clone(),
         }
@@ -796,12 +1783,172 @@ impl RenderingBackend for GlContext {
         shader: ShaderSource,
         meta: ShaderMeta,
     ) -> Result<ShaderId, ShaderError> {
-        let (fragment, vertex) = match shader {
-            ShaderSource::Glsl { fragment, vertex } => (fragment, vertex),
-            _ => panic!("Metal source on OpenGl context"),
+        let (vertex, geometry, fragment) = self.glsl_sources(shader)?;
+        let shader = load_shader_internal(
+            vertex,
+            geometry,
+            fragment,
+            meta,
+            &[],
+            self.features.program_binary,
+        )?;
+        let id = self.shaders.add(shader);
+        self.retain_shader(id, RetainedShader::glsl(vertex, geometry, fragment));
+        Ok(ShaderId(id))
+    }
+
+    fn new_shader_with_feedback(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+        varyings: &[&str],
+    ) -> Result<ShaderId, ShaderError> {
+        if !self.features.transform_feedback {
+            return Err(ShaderError::NotSupported(
+                "Transform feedback is not supported by the GPU".to_string(),
+            ));
+        }
+        let (vertex, geometry, fragment) = self.glsl_sources(shader)?;
+        let shader = load_shader_internal(
+            vertex,
+            geometry,
+            fragment,
+            meta,
+            varyings,
+            self.features.program_binary,
+        )?;
+        let id = self.shaders.add(shader);
+        self.retain_shader(id, RetainedShader::glsl(vertex, geometry, fragment));
+        Ok(ShaderId(id))
+    }
+
+    fn shader_recompile(
+        &mut self,
+        shader: ShaderId,
+        source: ShaderSource,
+    ) -> Result<(), ShaderError> {
+        let (vertex, geometry, fragment) = self.glsl_sources(source)?;
+        let old = &self.shaders[shader.0];
+        let meta = old.meta.clone();
+        let varyings = old.feedback_varyings.clone();
+        let varyings = varyings.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+
+        let new = load_shader_internal(
+            vertex,
+            geometry,
+            fragment,
+            meta,
+            &varyings,
+            self.features.program_binary,
+        )?;
+        // load_shader_internal leaves the new program bound, the pipeline
+        // program will be re-bound with the next apply_pipeline
+        self.cache.cur_pipeline = None;
+        if let Err(err) = check_layout(new.program, &new.meta) {
+            unsafe { glDeleteProgram(new.program) };
+            return Err(ShaderError::LayoutMismatch(err));
+        }
+        let old = std::mem::replace(&mut self.shaders[shader.0], new);
+        unsafe { glDeleteProgram(old.program) };
+        self.update_pipeline_layouts(shader);
+        self.retain_shader(shader.0, RetainedShader::glsl(vertex, geometry, fragment));
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn shader_binary(&self, shader: ShaderId) -> Option<ShaderBinary> {
+        if !self.features.program_binary {
+            return None;
+        }
+        let program = self.shaders[shader.0].program;
+        unsafe {
+            let mut length = 0;
+            glGetProgramiv(program, GL_PROGRAM_BINARY_LENGTH, &mut length as *mut _);
+            if length <= 0 {
+                return None;
+            }
+            let mut data = vec![0u8; length as usize];
+            let mut format = 0;
+            glGetProgramBinary(
+                program,
+                length,
+                &mut length as *mut _,
+                &mut format as *mut _,
+                data.as_mut_ptr() as *mut _,
+            );
+            data.truncate(length.max(0) as usize);
+            if data.is_empty() {
+                return None;
+            }
+            Some(ShaderBinary { format, data })
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn new_shader_from_binary(
+        &mut self,
+        binary: &ShaderBinary,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        if !self.features.program_binary {
+            return Err(ShaderError::LinkError(
+                "Program binaries are not supported by the GPU".to_string(),
+            ));
+        }
+        let shader = unsafe { load_binary_internal(binary, meta)? };
+        let id = self.shaders.add(shader);
+        self.retain(|retained| {
+            drop(
+                retained
+                    .shaders
+                    .insert(id, RetainedShader::Binary(binary.clone())),
+            )
+        });
+        Ok(ShaderId(id))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn shader_binary(&self, _shader: ShaderId) -> Option<ShaderBinary> {
+        None
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn new_shader_from_binary(
+        &mut self,
+        _binary: &ShaderBinary,
+        _meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        Err(ShaderError::LinkError(
+            "Program binaries are not supported by WebGL".to_string(),
+        ))
+    }
+
+    fn shader_reflect(&self, shader: ShaderId) -> Result<ShaderReflection, ShaderError> {
+        Ok(unsafe { reflect_program(self.shaders[shader.0].program) })
+    }
+
+    fn new_shader_reflected(&mut self, shader: ShaderSource) -> Result<ShaderId, ShaderError> {
+        let (vertex, geometry, fragment) = self.glsl_sources(shader)?;
+        let empty = ShaderMeta {
+            uniforms: UniformBlockLayout { uniforms: vec![] },
+            images: vec![],
         };
-        let shader = load_shader_internal(vertex, fragment, meta)?;
-        Ok(ShaderId(self.shaders.add(shader)))
+        let mut shader = load_shader_internal(
+            vertex,
+            geometry,
+            fragment,
+            empty,
+            &[],
+            self.features.program_binary,
+        )?;
+        let meta = unsafe { reflect_program(shader.program) }.shader_meta();
+        let (images, uniforms) = shader_locations(shader.program, &meta);
+        shader.images = images;
+        shader.uniforms = uniforms;
+        shader.meta = meta;
+        let id = self.shaders.add(shader);
+        self.retain_shader(id, RetainedShader::glsl(vertex, geometry, fragment));
+        Ok(ShaderId(id))
     }
 
     fn new_texture(
@@ -810,18 +1957,32 @@ impl RenderingBackend for GlContext {
         source: TextureSource,
         params: TextureParams,
     ) -> TextureId {
+        let retained = self
+            .retained
+            .as_ref()
+            .map(|_| RetainedTexture::new(&source));
         let texture = Texture::new(self, access, source, params);
         self.textures.0.push(texture);
-        TextureId(TextureIdInner::Managed(self.textures.0.len() - 1))
+        let id = self.textures.0.len() - 1;
+        if let Some(texture) = retained {
+            self.retain(|retained| drop(retained.textures.insert(id, texture)));
+        }
+        TextureId(TextureIdInner::Managed(id))
     }
     fn delete_texture(&mut self, texture: TextureId) {
         let t = self.textures.get(texture);
         t.delete();
+        if let TextureIdInner::Managed(id) = texture.0 {
+            // 0 is never a texture name, restore skips it
+            self.textures.0[id].raw = 0;
+            self.retain(|retained| drop(retained.textures.remove(&id)));
+        }
     }
 
     fn delete_shader(&mut self, program: ShaderId) {
         unsafe { glDeleteProgram(self.shaders[program.0].program) };
         self.shaders.remove(program.0);
+        self.retain(|retained| drop(retained.shaders.remove(&program.0)));
         self.cache.cur_pipeline = None;
     }
 
@@ -829,6 +1990,26 @@ impl RenderingBackend for GlContext {
         self.pipelines.remove(pipeline.0);
     }
 
+    fn pipeline_set_attachment_blend(
+        &mut self,
+        pipeline: Pipeline,
+        attachment: usize,
+        color_blend: Option<BlendState>,
+        alpha_blend: Option<BlendState>,
+    ) {
+        if color_blend.is_none() && alpha_blend.is_some() {
+            panic!("AlphaBlend without ColorBlend");
+        }
+        if !self.features.independent_blend {
+            eprintln!("Per-attachment blending is not supported by the GPU");
+            eprintln!("Ignoring pipeline_set_attachment_blend");
+            return;
+        }
+        let blends = &mut self.pipelines[pipeline.0].attachment_blends;
+        blends.retain(|(i, _, _)| *i != attachment);
+        blends.push((attachment, color_blend, alpha_blend));
+    }
+
     fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap) {
         let t = self.textures.get(texture);
 
@@ -891,10 +2072,21 @@ impl RenderingBackend for GlContext {
         source: Option<&[u8]>,
     ) {
         let mut t = self.textures.get(texture);
+        let raw = t.raw;
         t.resize(self, width, height, source);
+        if t.raw != raw {
+            self.update_framebuffers(texture);
+        }
         match texture.0 {
             TextureIdInner::Managed(tex_id) => {
-                self.textures.0[tex_id].params = t.params;
+                self.textures.0[tex_id] = t;
+                self.retain(|retained| {
+                    let texture = match source {
+                        Some(source) => RetainedTexture::Bytes(source.to_vec()),
+                        None => RetainedTexture::Empty,
+                    };
+                    retained.textures.insert(tex_id, texture);
+                });
             }
             _ => {}
         };
@@ -905,6 +2097,11 @@ impl RenderingBackend for GlContext {
     }
     fn texture_generate_mipmaps(&mut self, texture: TextureId) {
         let t = self.textures.get(texture);
+        if !self.can_mipmap(t.params.width, t.params.height) {
+            eprintln!("NPOT texture mipmaps are not supported by the GPU");
+            eprintln!("Ignoring texture_generate_mipmaps");
+            return;
+        }
         self.cache.store_texture_binding(0);
         self.cache.bind_texture(0, t.params.kind.into(), t.raw);
         unsafe {
@@ -923,6 +2120,13 @@ impl RenderingBackend for GlContext {
     ) {
         let t = self.textures.get(texture);
         t.update_texture_part(self, x_offset, y_offset, width, height, source);
+        if let TextureIdInner::Managed(id) = texture.0 {
+            self.retain(|retained| {
+                if let Some(texture) = retained.textures.get_mut(&id) {
+                    texture.update_part(&t.params, x_offset, y_offset, width, height, source);
+                }
+            });
+        }
     }
     fn texture_params(&self, texture: TextureId) -> TextureParams {
         let texture = self.textures.get(texture);
@@ -933,51 +2137,33 @@ impl RenderingBackend for GlContext {
         RawId::OpenGl(texture.raw)
     }
 
-    fn new_render_pass_mrt(
+    fn new_render_pass_targets(
         &mut self,
-        color_img: &[TextureId],
-        depth_img: Option<TextureId>,
+        color_img: &[RenderTarget],
+        depth_img: Option<RenderTarget>,
     ) -> RenderPass {
         if color_img.is_empty() && depth_img.is_none() {
             panic!("Render pass should have at least one non-none target");
         }
-        let mut gl_fb = 0;
-
-        unsafe {
-            glGenFramebuffers(1, &mut gl_fb as *mut _);
-            glBindFramebuffer(GL_FRAMEBUFFER, gl_fb);
-            for (i, color_img) in color_img.iter().enumerate() {
-                glFramebufferTexture2D(
-                    GL_FRAMEBUFFER,
-                    GL_COLOR_ATTACHMENT0 + i as u32,
-                    GL_TEXTURE_2D,
-                    self.textures.get(*color_img).raw,
-                    0,
-                );
-            }
-            if let Some(depth_img) = depth_img {
-                glFramebufferTexture2D(
-                    GL_FRAMEBUFFER,
-                    GL_DEPTH_ATTACHMENT,
-                    GL_TEXTURE_2D,
-                    self.textures.get(depth_img).raw,
-                    0,
-                );
-            }
-            if color_img.len() > 1 {
-                let mut attachments = vec![];
-                for i in 0..color_img.len() {
-                    attachments.push(GL_COLOR_ATTACHMENT0 + i as u32);
-                }
-                glDrawBuffers(2, attachments.as_ptr() as _);
-            }
-
-            glBindFramebuffer(GL_FRAMEBUFFER, self.default_framebuffer);
-        }
+        assert!(
+            color_img.len() <= self.features.max_color_attachments as usize,
+            "Too many color attachments, max is {}",
+            self.features.max_color_attachments
+        );
+        let gl_fb = unsafe { self.create_framebuffer(color_img, depth_img) };
         let pass = RenderPassInternal {
             gl_fb,
-            color_textures: color_img.to_vec(),
-            depth_texture: depth_img,
+            color_targets: color_img.to_vec(),
+            depth_target: depth_img,
+            color_textures: color_img.iter().map(|target| target.texture).collect(),
+            depth_texture: depth_img.map(|target| target.texture),
+            mip_level: color_img
+                .first()
+                .copied()
+                .or(depth_img)
+                .map_or(0, |target| target.mip_level),
+            color_actions: vec![AttachmentAction::default(); color_img.len()],
+            depth_action: AttachmentAction::default(),
         };
 
         RenderPass(self.passes.add(pass))
@@ -985,6 +2171,21 @@ impl RenderingBackend for GlContext {
     fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
         &self.passes[render_pass.0].color_textures
     }
+    fn render_pass_set_actions(
+        &mut self,
+        render_pass: RenderPass,
+        color: &[AttachmentAction],
+        depth: AttachmentAction,
+    ) {
+        let pass = &mut self.passes[render_pass.0];
+        assert_eq!(
+            color.len(),
+            pass.color_textures.len(),
+            "Action count and color attachment count did not match!"
+        );
+        pass.color_actions = color.to_vec();
+        pass.depth_action = depth;
+    }
     fn delete_render_pass(&mut self, render_pass: RenderPass) {
         let pass_id = render_pass.0;
 
@@ -1008,111 +2209,27 @@ impl RenderingBackend for GlContext {
         shader: ShaderId,
         params: PipelineParams,
     ) -> Pipeline {
-        #[derive(Clone, Copy, Default)]
-        struct BufferCacheData {
-            stride: i32,
-            offset: i64,
-        }
-
-        let mut buffer_cache: Vec<BufferCacheData> =
-            vec![BufferCacheData::default(); buffer_layout.len()];
-
-        for VertexAttribute {
-            format,
-            buffer_index,
-            ..
-        } in attributes
-        {
-            let layout = buffer_layout.get(*buffer_index).unwrap_or_else(|| panic!());
-            let mut cache = buffer_cache
-                .get_mut(*buffer_index)
-                .unwrap_or_else(|| panic!());
-
-            if layout.stride == 0 {
-                cache.stride += format.size_bytes();
-            } else {
-                cache.stride = layout.stride;
-            }
-            // WebGL 1 limitation
-            assert!(cache.stride <= 255);
-        }
-
         let program = self.shaders[shader.0].program;
-
-        let attributes_len = attributes
-            .iter()
-            .map(|layout| match layout.format {
-                VertexFormat::Mat4 => 4,
-                _ => 1,
-            })
-            .sum();
-
-        let mut vertex_layout: Vec<Option<VertexAttributeInternal>> = vec![None; attributes_len];
-
-        for VertexAttribute {
-            name,
-            format,
-            buffer_index,
-        } in attributes
-        {
-            let mut buffer_data = &mut buffer_cache
-                .get_mut(*buffer_index)
-                .unwrap_or_else(|| panic!());
-            let layout = buffer_layout.get(*buffer_index).unwrap_or_else(|| panic!());
-
-            let cname = CString::new(*name).unwrap_or_else(|e| panic!("{}", e));
-            let attr_loc = unsafe { glGetAttribLocation(program, cname.as_ptr() as *const _) };
-            let attr_loc = if attr_loc == -1 { None } else { Some(attr_loc) };
-            let divisor = if layout.step_func == VertexStep::PerVertex {
-                0
-            } else {
-                layout.step_rate
-            };
-
-            let mut attributes_count: usize = 1;
-            let mut format = *format;
-
-            if format == VertexFormat::Mat4 {
-                format = VertexFormat::Float4;
-                attributes_count = 4;
-            }
-            for i in 0..attributes_count {
-                if let Some(attr_loc) = attr_loc {
-                    let attr_loc = attr_loc as GLuint + i as GLuint;
-
-                    let attr = VertexAttributeInternal {
-                        attr_loc,
-                        size: format.components(),
-                        type_: format.type_(),
-                        offset: buffer_data.offset,
-                        stride: buffer_data.stride,
-                        buffer_index: *buffer_index,
-                        divisor,
-                    };
-
-                    assert!(
-                        attr_loc < vertex_layout.len() as u32,
-                        "attribute: {} outside of allocated attributes array len: {}",
-                        name,
-                        vertex_layout.len()
-                    );
-                    vertex_layout[attr_loc as usize] = Some(attr);
-                }
-                buffer_data.offset += format.size_bytes() as i64
-            }
-        }
+        let vertex_layout = vertex_layout(program, buffer_layout, attributes);
 
         let pipeline = PipelineInternal {
             layout: vertex_layout,
+            buffer_layout: buffer_layout.to_vec(),
+            attributes: attributes.to_vec(),
             shader,
             params,
+            attachment_blends: vec![],
         };
 
         Pipeline(self.pipelines.add(pipeline))
     }
 
     fn apply_pipeline(&mut self, pipeline: &Pipeline) {
+        if self.cache.cur_pipeline != Some(*pipeline) {
+            self.count(|stats| stats.pipeline_switches += 1);
+        }
         self.cache.cur_pipeline = Some(*pipeline);
+        self.cache.cur_compute_pipeline = None;
 
         {
             let pipeline = &self.pipelines[pipeline.0];
@@ -1144,13 +2261,30 @@ impl RenderingBackend for GlContext {
                     glFrontFace(GL_CCW);
                 },
             }
+
+            if pipeline.params.alpha_to_coverage {
+                unsafe {
+                    glEnable(GL_SAMPLE_ALPHA_TO_COVERAGE);
+                }
+            } else {
+                unsafe {
+                    glDisable(GL_SAMPLE_ALPHA_TO_COVERAGE);
+                }
+            }
         }
 
         self.set_cull_face(self.pipelines[pipeline.0].params.cull_face);
+        self.set_polygon_mode(self.pipelines[pipeline.0].params.polygon_mode);
+        self.set_primitive_restart(self.pipelines[pipeline.0].params.primitive_restart);
         self.set_blend(
             self.pipelines[pipeline.0].params.color_blend,
             self.pipelines[pipeline.0].params.alpha_blend,
         );
+        for i in 0..self.pipelines[pipeline.0].attachment_blends.len() {
+            let (attachment, color_blend, alpha_blend) =
+                self.pipelines[pipeline.0].attachment_blends[i];
+            unsafe { self.set_attachment_blend(attachment, color_blend, alpha_blend) };
+        }
 
         self.set_stencil(self.pipelines[pipeline.0].params.stencil_test);
         self.set_color_write(self.pipelines[pipeline.0].params.color_write);
@@ -1162,7 +2296,7 @@ impl RenderingBackend for GlContext {
         usage: BufferUsage,
         data: BufferSource,
     ) -> BufferId {
-        let gl_target = gl_buffer_target(&type_);
+        let gl_target = self.gl_buffer_target(&type_);
         let gl_usage = gl_usage(&usage);
         let (size, element_size) = match &data {
             BufferSource::Slice(data) => (data.size, data.element_size),
@@ -1176,30 +2310,34 @@ impl RenderingBackend for GlContext {
             }
             BufferType::IndexBuffer => panic!("unsupported index buffer dimension"),
             BufferType::VertexBuffer => None,
+            BufferType::Storage => None,
         };
-        let mut gl_buf: u32 = 0;
-
-        unsafe {
-            glGenBuffers(1, &mut gl_buf as *mut _);
-            self.cache.store_buffer_binding(gl_target);
-            self.cache.bind_buffer(gl_target, gl_buf, index_type);
-
-            glBufferData(gl_target, size as _, std::ptr::null() as *const _, gl_usage);
-            if let BufferSource::Slice(data) = data {
+        let ptr = match data {
+            BufferSource::Slice(data) => {
                 debug_assert!(data.is_slice);
-                glBufferSubData(gl_target, 0, size as _, data.ptr as _);
+                data.ptr
             }
-            self.cache.restore_buffer_binding(gl_target);
-        }
-
+            BufferSource::Empty { .. } => std::ptr::null(),
+        };
+        let gl_buf = unsafe { self.create_gl_buffer(gl_target, gl_usage, index_type, size, ptr) };
         let buffer = Buffer {
             gl_buf,
             buffer_type: type_,
+            usage,
             size,
             index_type,
         };
 
-        BufferId(self.buffers.add(buffer))
+        let id = self.buffers.add(buffer);
+        self.retain(|retained| {
+            let data = if ptr.is_null() {
+                vec![0; size]
+            } else {
+                unsafe { std::slice::from_raw_parts(ptr as *const u8, size) }.to_vec()
+            };
+            retained.buffers.insert(id, data);
+        });
+        BufferId(id)
     }
 
     fn buffer_update(&mut self, buffer: BufferId, data: BufferSource) {
@@ -1208,7 +2346,8 @@ impl RenderingBackend for GlContext {
             _ => panic!("buffer_update expects BufferSource::slice"),
         };
         debug_assert!(data.is_slice);
-        let buffer = &self.buffers[buffer.0];
+        let id = buffer.0;
+        let buffer = &self.buffers[id];
 
         if matches!(buffer.buffer_type, BufferType::IndexBuffer) {
             assert!(buffer.index_type.is_some());
@@ -1219,12 +2358,19 @@ impl RenderingBackend for GlContext {
 
         assert!(size <= buffer.size);
 
-        let gl_target = gl_buffer_target(&buffer.buffer_type);
+        let gl_target = self.gl_buffer_target(&buffer.buffer_type);
         self.cache.store_buffer_binding(gl_target);
         self.cache
             .bind_buffer(gl_target, buffer.gl_buf, buffer.index_type);
         unsafe { glBufferSubData(gl_target, 0, size as _, data.ptr as _) };
         self.cache.restore_buffer_binding(gl_target);
+        self.retain(|retained| {
+            if let Some(retained) = retained.buffers.get_mut(&id) {
+                let data = unsafe { std::slice::from_raw_parts(data.ptr as *const u8, size) };
+                retained[..size].copy_from_slice(data);
+            }
+        });
+        self.count(|stats| stats.buffer_uploads += 1);
     }
 
     /// Size of buffer in bytes
@@ -1244,14 +2390,13 @@ impl RenderingBackend for GlContext {
         self.cache.clear_buffer_bindings();
         self.cache.clear_vertex_attributes();
         self.buffers.remove(buffer.0);
+        self.retain(|retained| drop(retained.buffers.remove(&buffer.0)));
     }
 
     /// Set a new viewport rectangle.
     /// Should be applied after begin_pass.
     fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
-        unsafe {
-            glViewport(x, y, w, h);
-        }
+        self.cache.set_viewport(x, y, w, h);
     }
 
     /// Set a new scissor rectangle.
@@ -1282,6 +2427,7 @@ impl RenderingBackend for GlContext {
                         .bind_texture(n, texture.params.kind.into(), texture.raw);
                     glUniform1i(gl_loc, n as i32);
                 }
+                self.count(|stats| stats.texture_binds += 1);
             }
         }
 
@@ -1344,60 +2490,105 @@ impl RenderingBackend for GlContext {
         }
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    fn apply_storage_buffers(&mut self, buffers: &[BufferId]) {
+        if !self.features.storage_buffers {
+            return;
+        }
+        for (n, buffer) in buffers.iter().enumerate() {
+            let buffer = &self.buffers[buffer.0];
+            assert!(
+                buffer.buffer_type == BufferType::Storage,
+                "apply_storage_buffers with a non-storage buffer"
+            );
+            unsafe {
+                glBindBufferBase(GL_SHADER_STORAGE_BUFFER, n as _, buffer.gl_buf);
+            }
+        }
+    }
+
+    // features.storage_buffers is false on WebGL
+    #[cfg(target_arch = "wasm32")]
+    fn apply_storage_buffers(&mut self, _buffers: &[BufferId]) {}
+
     fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
-        let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
-        let shader = &self.shaders[pip.shader.0];
+        let shader = match self.cache.cur_compute_pipeline {
+            Some(compute) => &self.compute_pipelines[compute.0],
+            None => {
+                let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
+                &self.shaders[pip.shader.0]
+            }
+        };
 
         let mut offset = 0;
 
         for (_, uniform) in shader.uniforms.iter().enumerate() {
-            use UniformType::*;
-
             assert!(
-                offset as i32 <= size as i32 - uniform.uniform_type.size() as i32 / 4,
+                offset * 4 + uniform.uniform_type.size() * uniform.array_count as usize <= size,
                 "Uniforms struct does not match shader uniforms layout"
             );
 
-            unsafe {
-                let data = (uniform_ptr as *const f32).offset(offset as isize);
-                let data_int = (uniform_ptr as *const i32).offset(offset as isize);
-
-                if let Some(gl_loc) = uniform.gl_loc {
-                    match uniform.uniform_type {
-                        Float1 => {
-                            glUniform1fv(gl_loc, uniform.array_count, data);
-                        }
-                        Float2 => {
-                            glUniform2fv(gl_loc, uniform.array_count, data);
-                        }
-                        Float3 => {
-                            glUniform3fv(gl_loc, uniform.array_count, data);
-                        }
-                        Float4 => {
-                            glUniform4fv(gl_loc, uniform.array_count, data);
-                        }
-                        Int1 => {
-                            glUniform1iv(gl_loc, uniform.array_count, data_int);
-                        }
-                        Int2 => {
-                            glUniform2iv(gl_loc, uniform.array_count, data_int);
-                        }
-                        Int3 => {
-                            glUniform3iv(gl_loc, uniform.array_count, data_int);
-                        }
-                        Int4 => {
-                            glUniform4iv(gl_loc, uniform.array_count, data_int);
-                        }
-                        Mat4 => {
-                            glUniformMatrix4fv(gl_loc, uniform.array_count, 0, data);
-                        }
-                    }
+            if let Some(gl_loc) = uniform.gl_loc {
+                unsafe {
+                    let data = (uniform_ptr as *const f32).offset(offset as isize);
+                    set_uniform(gl_loc, uniform.uniform_type, uniform.array_count, data as _);
                 }
             }
             offset += uniform.uniform_type.size() / 4 * uniform.array_count as usize;
         }
     }
 
+    fn set_uniform_from_bytes(
+        &mut self,
+        name: &str,
+        uniform_type: UniformType,
+        array_count: usize,
+        data: &[u8],
+    ) {
+        assert!(
+            data.len() >= uniform_type.size() * array_count,
+            "Not enough data for uniform \"{}\"",
+            name
+        );
+        let shader = match self.cache.cur_compute_pipeline {
+            Some(compute) => &mut self.compute_pipelines[compute.0],
+            None => {
+                let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
+                &mut self.shaders[pip.shader.0]
+            }
+        };
+        let program = shader.program;
+        let (gl_loc, reflected_type) = *shader
+            .named_uniforms
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                let reflected_type = if cfg!(debug_assertions) {
+                    let reflection = unsafe { reflect_program(program) };
+                    reflection
+                        .uniforms
+                        .iter()
+                        .find(|uniform| uniform.name == name)
+                        .and_then(|uniform| uniform.uniform_type)
+                } else {
+                    None
+                };
+                (get_uniform_location(program, name), reflected_type)
+            });
+
+        if let Some(reflected_type) = reflected_type {
+            debug_assert!(
+                reflected_type == uniform_type,
+                "Uniform \"{}\" is {:?} in the shader, not {:?}",
+                name,
+                reflected_type,
+                uniform_type
+            );
+        }
+        if let Some(gl_loc) = gl_loc {
+            unsafe { set_uniform(gl_loc, uniform_type, array_count as _, data.as_ptr()) };
+        }
+    }
+
     fn clear(
         &mut self,
         color: Option<(f32, f32, f32, f32)>,
@@ -1433,20 +2624,30 @@ impl RenderingBackend for GlContext {
         }
     }
 
+    fn clear_color_attachment(&mut self, attachment: usize, color: (f32, f32, f32, f32)) {
+        // no glClearBuffer on GLES2 and WebGL1, but there is only one
+        // attachment anyway
+        if !self.gl3 {
+            assert!(attachment == 0, "MRT is not supported by the GPU");
+            self.clear(Some(color), None, None);
+            return;
+        }
+        let (r, g, b, a) = color;
+        let color = [r, g, b, a];
+        unsafe {
+            glClearBufferfv(GL_COLOR, attachment as _, color.as_ptr());
+        }
+    }
+
     fn begin_default_pass(&mut self, action: PassAction) {
         self.begin_pass(None, action);
     }
 
-    fn begin_pass(&mut self, pass: Option<RenderPass>, action: PassAction) {
-        let (framebuffer, w, h) = match pass {
+    fn render_pass_size(&self, pass: Option<RenderPass>) -> (u32, u32) {
+        match pass {
             None => {
                 let (screen_width, screen_height) = window::screen_size();
-
-                (
-                    self.default_framebuffer,
-                    screen_width as i32,
-                    screen_height as i32,
-                )
+                (screen_width as u32, screen_height as u32)
             }
             Some(pass) => {
                 let pass = &self.passes[pass.0];
@@ -1458,41 +2659,81 @@ impl RenderingBackend for GlContext {
                     .copied()
                     .or(pass.depth_texture)
                     .unwrap();
+                let params = self.textures.get(texture).params;
                 (
-                    pass.gl_fb,
-                    self.textures.get(texture).params.width as i32,
-                    self.textures.get(texture).params.height as i32,
+                    (params.width >> pass.mip_level).max(1),
+                    (params.height >> pass.mip_level).max(1),
                 )
             }
+        }
+    }
+
+    fn begin_pass(&mut self, pass: Option<RenderPass>, action: PassAction) {
+        let framebuffer = match pass {
+            None => self.default_framebuffer,
+            Some(pass) => self.passes[pass.0].gl_fb,
         };
+        let (w, h) = self.render_pass_size(pass);
+        let (w, h) = (w as i32, h as i32);
+        self.cache.store_viewport();
+        self.cache.set_viewport(0, 0, w, h);
         unsafe {
             glBindFramebuffer(GL_FRAMEBUFFER, framebuffer);
-            glViewport(0, 0, w, h);
             glScissor(0, 0, w, h);
         }
-        match action {
-            PassAction::Nothing => {}
+        self.cur_pass = pass;
+        let (color, depth, stencil) = match action {
+            PassAction::Nothing => (None, None, None),
             PassAction::Clear {
                 color,
                 depth,
                 stencil,
-            } => {
-                self.clear(color, depth, stencil);
-            }
+            } => (color, depth, stencil),
+        };
+        // pass clears should not depend on the previous pipeline's write masks
+        if color.is_some() {
+            self.set_color_write((true, true, true, true));
+        }
+        if stencil.is_some() {
+            self.set_stencil(None);
+            unsafe { glStencilMaskSeparate(GL_FRONT_AND_BACK, !0) };
+        }
+        match pass {
+            None => self.clear(color, depth, stencil),
+            Some(pass) => self.load_pass_attachments(pass, color, depth, stencil),
         }
     }
 
     fn end_render_pass(&mut self) {
+        if let Some(pass) = self.cur_pass.take() {
+            let pass = &self.passes[pass.0];
+            let mut attachments = vec![];
+            for (i, action) in pass.color_actions.iter().enumerate() {
+                if action.store == StoreAction::DontCare {
+                    attachments.push(GL_COLOR_ATTACHMENT0 + i as u32);
+                }
+            }
+            if pass.depth_action.store == StoreAction::DontCare {
+                attachments.extend([GL_DEPTH_ATTACHMENT, GL_STENCIL_ATTACHMENT]);
+            }
+            self.invalidate_attachments(&attachments);
+        }
         unsafe {
             glBindFramebuffer(GL_FRAMEBUFFER, self.default_framebuffer);
             self.cache.bind_buffer(GL_ARRAY_BUFFER, 0, None);
             self.cache.bind_buffer(GL_ELEMENT_ARRAY_BUFFER, 0, None);
         }
+        self.cache.restore_viewport();
     }
 
     fn commit_frame(&mut self) {
         self.cache.clear_buffer_bindings();
         self.cache.clear_texture_bindings();
+        self.last_frame_stats = self.stats.take();
+    }
+
+    fn frame_stats(&self) -> FrameStats {
+        self.last_frame_stats
     }
 
     fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
@@ -1508,9 +2749,34 @@ impl RenderingBackend for GlContext {
         }
 
         let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
+        let triangles = pip.params.primitive_type.triangle_count(num_elements);
+        self.count(|stats| {
+            stats.draw_calls += 1;
+            stats.triangles += triangles * num_instances as u64;
+        });
         let primitive_type = pip.params.primitive_type.into();
+        // GLES3 and WebGL2 do not allow indexed draws while capturing
+        if self.transform_feedback {
+            unsafe {
+                glDrawArraysInstanced(primitive_type, base_element, num_elements, num_instances);
+            }
+            return;
+        }
+
         let index_type = self.cache.index_type.expect("Unset index buffer type");
 
+        #[cfg(not(target_arch = "wasm32"))]
+        if pip.params.primitive_restart && self.primitive_restart && !self.gles {
+            let restart_index = match index_type {
+                1 => 0xff,
+                2 => 0xffff,
+                _ => 0xffffffff,
+            };
+            unsafe {
+                glPrimitiveRestartIndex(restart_index);
+            }
+        }
+
         unsafe {
             glDrawElementsInstanced(
                 primitive_type,
@@ -1526,4 +2792,316 @@ impl RenderingBackend for GlContext {
             );
         }
     }
+
+    fn begin_transform_feedback(&mut self, buffers: &[BufferId], discard: bool) {
+        // no shader could have been created with varyings to capture
+        if !self.features.transform_feedback {
+            return;
+        }
+        assert!(
+            self.cache.cur_pipeline.is_some(),
+            "begin_transform_feedback without any binded pipeline"
+        );
+
+        let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
+        // on GLES3 and WebGL2 the draw mode should be exactly the capture mode
+        let primitive_mode = match pip.params.primitive_type {
+            PrimitiveType::Triangles => GL_TRIANGLES,
+            PrimitiveType::Lines => GL_LINES,
+            PrimitiveType::TriangleStrip | PrimitiveType::LineStrip => {
+                panic!("Transform feedback captures Triangles or Lines only, not strips")
+            }
+        };
+
+        unsafe {
+            for (n, buffer) in buffers.iter().enumerate() {
+                let buffer = self.buffers[buffer.0].gl_buf;
+                glBindBufferBase(GL_TRANSFORM_FEEDBACK_BUFFER, n as _, buffer);
+            }
+            if discard {
+                glEnable(GL_RASTERIZER_DISCARD);
+            }
+            glBeginTransformFeedback(primitive_mode);
+        }
+        self.transform_feedback = true;
+    }
+
+    fn end_transform_feedback(&mut self) {
+        if !self.transform_feedback {
+            return;
+        }
+        unsafe {
+            glEndTransformFeedback();
+            glDisable(GL_RASTERIZER_DISCARD);
+        }
+        self.transform_feedback = false;
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn begin_query(&mut self, query_type: QueryType) -> Option<QueryId> {
+        // GLES3 does not have GL_SAMPLES_PASSED
+        let target = match query_type {
+            QueryType::SamplesPassed if !self.gles => GL_SAMPLES_PASSED,
+            _ => GL_ANY_SAMPLES_PASSED,
+        };
+        let supported = match target {
+            GL_SAMPLES_PASSED => self.features.occlusion_query,
+            _ => self.features.any_samples_query,
+        };
+        if !supported {
+            return None;
+        }
+        let mut gl_query = 0;
+        unsafe {
+            glGenQueries(1, &mut gl_query);
+            glBeginQuery(target, gl_query);
+        }
+        Some(QueryId(self.queries.add(Query { gl_query, target })))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn end_query(&mut self, query: QueryId) {
+        unsafe { glEndQuery(self.queries[query.0].target) };
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn query_result(&self, query: QueryId) -> Option<u64> {
+        let gl_query = self.queries[query.0].gl_query;
+        let mut available: GLuint = 0;
+        unsafe { glGetQueryObjectuiv(gl_query, GL_QUERY_RESULT_AVAILABLE, &mut available) };
+        if available == 0 {
+            return None;
+        }
+        let mut result: GLuint = 0;
+        unsafe { glGetQueryObjectuiv(gl_query, GL_QUERY_RESULT, &mut result) };
+        Some(result as u64)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn delete_query(&mut self, query: QueryId) {
+        unsafe { glDeleteQueries(1, &self.queries[query.0].gl_query) };
+        self.queries.remove(query.0);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn begin_conditional_render(&mut self, query: QueryId, wait: bool) {
+        if !self.features.conditional_render {
+            return;
+        }
+        let mode = if wait {
+            GL_QUERY_WAIT
+        } else {
+            GL_QUERY_NO_WAIT
+        };
+        unsafe { glBeginConditionalRender(self.queries[query.0].gl_query, mode) };
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn end_conditional_render(&mut self) {
+        if self.features.conditional_render {
+            unsafe { glEndConditionalRender() };
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn begin_query(&mut self, _query_type: QueryType) -> Option<QueryId> {
+        None
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn end_query(&mut self, _query: QueryId) {}
+
+    #[cfg(target_arch = "wasm32")]
+    fn query_result(&self, _query: QueryId) -> Option<u64> {
+        None
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn delete_query(&mut self, _query: QueryId) {}
+
+    #[cfg(target_arch = "wasm32")]
+    fn begin_conditional_render(&mut self, _query: QueryId, _wait: bool) {}
+
+    #[cfg(target_arch = "wasm32")]
+    fn end_conditional_render(&mut self) {}
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn new_compute_pipeline(
+        &mut self,
+        source: &str,
+        meta: ShaderMeta,
+    ) -> Result<ComputePipeline, ShaderError> {
+        if !self.features.compute {
+            return Err(ShaderError::NotSupported(
+                "Compute shaders are not supported by the GPU".to_string(),
+            ));
+        }
+        let shader = load_compute_internal(source, meta)?;
+        Ok(ComputePipeline(self.compute_pipelines.add(shader)))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn apply_compute_pipeline(&mut self, pipeline: &ComputePipeline) {
+        self.cache.cur_compute_pipeline = Some(*pipeline);
+        self.cache.cur_pipeline = None;
+        unsafe {
+            glUseProgram(self.compute_pipelines[pipeline.0].program);
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn apply_compute_bindings(
+        &mut self,
+        storage_buffers: &[BufferId],
+        images: &[TextureId],
+    ) -> Result<(), ShaderError> {
+        let images = images
+            .iter()
+            .map(|image| {
+                let texture = self.textures.get(*image);
+                match self.storage_image_format(&texture.params) {
+                    // desktop GL binds mutable textures as well
+                    Some(format) if texture.immutable || !self.gles => Ok((texture, format)),
+                    _ => Err(ShaderError::NotSupported(format!(
+                        "{:?} texture without TextureAccess::Storage can't be used as a compute image",
+                        texture.params.format
+                    ))),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.apply_storage_buffers(storage_buffers);
+        unsafe {
+            for (n, (texture, format)) in images.into_iter().enumerate() {
+                glBindImageTexture(
+                    n as _,
+                    texture.raw,
+                    0,
+                    GL_FALSE as _,
+                    0,
+                    GL_READ_WRITE,
+                    format,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn dispatch(&mut self, x: u32, y: u32, z: u32) {
+        assert!(
+            self.cache.cur_compute_pipeline.is_some(),
+            "Dispatch without any binded compute pipeline"
+        );
+        unsafe {
+            glDispatchCompute(x, y, z);
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn memory_barrier(&mut self) {
+        unsafe {
+            glMemoryBarrier(GL_ALL_BARRIER_BITS);
+        }
+    }
+
+    fn delete_compute_pipeline(&mut self, pipeline: ComputePipeline) {
+        unsafe { glDeleteProgram(self.compute_pipelines[pipeline.0].program) };
+        self.compute_pipelines.remove(pipeline.0);
+        self.cache.cur_compute_pipeline = None;
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn new_compute_pipeline(
+        &mut self,
+        _source: &str,
+        _meta: ShaderMeta,
+    ) -> Result<ComputePipeline, ShaderError> {
+        Err(ShaderError::NotSupported(
+            "Compute shaders are not supported by WebGL".to_string(),
+        ))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn apply_compute_pipeline(&mut self, _pipeline: &ComputePipeline) {}
+
+    #[cfg(target_arch = "wasm32")]
+    fn apply_compute_bindings(
+        &mut self,
+        _storage_buffers: &[BufferId],
+        _images: &[TextureId],
+    ) -> Result<(), ShaderError> {
+        Ok(())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn dispatch(&mut self, _x: u32, _y: u32, _z: u32) {}
+
+    #[cfg(target_arch = "wasm32")]
+    fn memory_barrier(&mut self) {}
+
+    fn retain_resources(&mut self, retain: bool) {
+        if !retain {
+            self.retained = None;
+        } else if self.retained.is_none() {
+            self.retained = Some(Retained::default());
+        }
+    }
+
+    fn restore_resources(&mut self) {
+        unsafe { self.restore() }
+    }
+
+    fn reset_state_cache(&mut self) {
+        unsafe {
+            glBindVertexArray(self.vao);
+            glActiveTexture(GL_TEXTURE0);
+            glPixelStorei(GL_UNPACK_ALIGNMENT, 1);
+        }
+        self.cache.invalidate(self.gl3);
+        // make sure set_polygon_mode does not skip the call
+        self.cache.polygon_mode = PolygonMode::Point;
+        self.set_polygon_mode(PolygonMode::Fill);
+    }
+
+    fn texture_set_label(&mut self, texture: TextureId, label: &str) {
+        let raw = self.textures.get(texture).raw;
+        self.object_label(GL_TEXTURE, raw, label);
+    }
+
+    fn buffer_set_label(&mut self, buffer: BufferId, label: &str) {
+        let gl_buf = self.buffers[buffer.0].gl_buf;
+        self.object_label(GL_BUFFER, gl_buf, label);
+    }
+
+    fn shader_set_label(&mut self, shader: ShaderId, label: &str) {
+        let program = self.shaders[shader.0].program;
+        self.object_label(GL_PROGRAM, program, label);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn push_debug_group(&mut self, name: &str) {
+        if self.khr_debug {
+            unsafe {
+                glPushDebugGroup(
+                    GL_DEBUG_SOURCE_APPLICATION,
+                    0,
+                    name.len() as _,
+                    name.as_ptr() as *const _,
+                );
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn pop_debug_group(&mut self) {
+        if self.khr_debug {
+            unsafe { glPopDebugGroup() };
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn push_debug_group(&mut self, _name: &str) {}
+
+    #[cfg(target_arch = "wasm32")]
+    fn pop_debug_group(&mut self) {}
 }
@@ -35,11 +35,20 @@ pub struct GlCache {
     pub vertex_buffer: GLuint,
     pub textures: [CachedTexture; MAX_SHADERSTAGE_IMAGES],
     pub cur_pipeline: Option<Pipeline>,
+    pub cur_compute_pipeline: Option<ComputePipeline>,
     pub color_blend: Option<BlendState>,
     pub alpha_blend: Option<BlendState>,
+    /// Some attachments got their own blend state, so color_blend/alpha_blend
+    /// do not describe all of them.
+    pub indexed_blend: bool,
     pub stencil: Option<StencilState>,
     pub color_write: ColorMask,
     pub cull_face: CullFace,
+    pub polygon_mode: PolygonMode,
+    /// None when unknown, the cap is not touched by invalidate.
+    pub primitive_restart: Option<bool>,
+    pub viewport: (i32, i32, i32, i32),
+    pub stored_viewport: (i32, i32, i32, i32),
     pub attributes: [Option<CachedAttribute>; MAX_VERTEX_ATTRIBUTES],
 }
 
@@ -52,7 +61,7 @@ impl GlCache {
                     glBindBuffer(target, buffer);
                 }
             }
-        } else {
+        } else if target == GL_ELEMENT_ARRAY_BUFFER {
             if self.index_buffer != buffer {
                 self.index_buffer = buffer;
                 unsafe {
@@ -60,13 +69,19 @@ impl GlCache {
                 }
             }
             self.index_type = index_type;
+        } else {
+            // storage buffers are bound by index, the generic binding point
+            // is only used for uploads and is not cached
+            unsafe {
+                glBindBuffer(target, buffer);
+            }
         }
     }
 
     pub fn store_buffer_binding(&mut self, target: GLenum) {
         if target == GL_ARRAY_BUFFER {
             self.stored_vertex_buffer = self.vertex_buffer;
-        } else {
+        } else if target == GL_ELEMENT_ARRAY_BUFFER {
             self.stored_index_buffer = self.index_buffer;
             self.stored_index_type = self.index_type;
         }
@@ -78,7 +93,7 @@ impl GlCache {
                 self.bind_buffer(target, self.stored_vertex_buffer, None);
                 self.stored_vertex_buffer = 0;
             }
-        } else {
+        } else if target == GL_ELEMENT_ARRAY_BUFFER {
             if self.stored_index_buffer != 0 {
                 self.bind_buffer(target, self.stored_index_buffer, self.stored_index_type);
                 self.stored_index_buffer = 0;
@@ -108,6 +123,22 @@ impl GlCache {
         self.bind_texture(slot_index, self.stored_target, self.stored_texture);
     }
 
+    pub fn set_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.viewport = (x, y, w, h);
+        unsafe {
+            glViewport(x, y, w, h);
+        }
+    }
+
+    pub fn store_viewport(&mut self) {
+        self.stored_viewport = self.viewport;
+    }
+
+    pub fn restore_viewport(&mut self) {
+        let (x, y, w, h) = self.stored_viewport;
+        self.set_viewport(x, y, w, h);
+    }
+
     pub fn clear_buffer_bindings(&mut self) {
         self.bind_buffer(GL_ARRAY_BUFFER, 0, None);
         self.vertex_buffer = 0;
@@ -128,6 +159,61 @@ impl GlCache {
         }
     }
 
+    /// Forget all the cached bindings and put GL back into the state this
+    /// cache assumes, whatever was done with GL behind its back.
+    /// `gl3`: GL3, GLES3 or WebGL2, with texture arrays and row length.
+    pub fn invalidate(&mut self, gl3: bool) {
+        unsafe {
+            glBindBuffer(GL_ARRAY_BUFFER, 0);
+            glBindBuffer(GL_ELEMENT_ARRAY_BUFFER, 0);
+            glUseProgram(0);
+            for ix in 0..MAX_SHADERSTAGE_IMAGES {
+                glActiveTexture(GL_TEXTURE0 + ix as GLuint);
+                glBindTexture(GL_TEXTURE_2D, 0);
+                glBindTexture(GL_TEXTURE_CUBE_MAP, 0);
+                if gl3 {
+                    glBindTexture(GL_TEXTURE_2D_ARRAY, 0);
+                }
+            }
+            glActiveTexture(GL_TEXTURE0);
+            for attr_index in 0..MAX_VERTEX_ATTRIBUTES {
+                glDisableVertexAttribArray(attr_index as GLuint);
+            }
+            // texture uploads assume tightly packed rows
+            if gl3 {
+                glPixelStorei(GL_UNPACK_ROW_LENGTH, 0);
+            }
+            glDisable(GL_BLEND);
+            glDisable(GL_STENCIL_TEST);
+            glDisable(GL_CULL_FACE);
+            glColorMask(1, 1, 1, 1);
+            // miniquad never masks depth writes, clear() relies on it
+            glDepthMask(1);
+            let (x, y, w, h) = self.viewport;
+            glViewport(x, y, w, h);
+        }
+
+        self.vertex_buffer = 0;
+        self.index_buffer = 0;
+        self.index_type = None;
+        self.stored_vertex_buffer = 0;
+        self.stored_index_buffer = 0;
+        self.textures = [CachedTexture {
+            target: 0,
+            texture: 0,
+        }; MAX_SHADERSTAGE_IMAGES];
+        self.attributes = [None; MAX_VERTEX_ATTRIBUTES];
+        self.cur_pipeline = None;
+        self.cur_compute_pipeline = None;
+        self.color_blend = None;
+        self.alpha_blend = None;
+        self.indexed_blend = false;
+        self.stencil = None;
+        self.color_write = (true, true, true, true);
+        self.cull_face = CullFace::Nothing;
+        self.primitive_restart = None;
+    }
+
     pub fn clear_vertex_attributes(&mut self) {
         for attr_index in 0..MAX_VERTEX_ATTRIBUTES {
             let cached_attr = &mut self.attributes[attr_index];
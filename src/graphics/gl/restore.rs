@@ -0,0 +1,196 @@
+use super::*;
+
+pub(super) enum RetainedTexture {
+    Empty,
+    Bytes(Vec<u8>),
+    Array(Vec<Vec<Vec<u8>>>),
+}
+
+impl RetainedTexture {
+    pub fn new(source: &TextureSource) -> RetainedTexture {
+        match source {
+            TextureSource::Empty => RetainedTexture::Empty,
+            TextureSource::Bytes(bytes) => RetainedTexture::Bytes(bytes.to_vec()),
+            TextureSource::Array(faces) => RetainedTexture::Array(
+                faces
+                    .iter()
+                    .map(|mipmaps| mipmaps.iter().map(|bytes| bytes.to_vec()).collect())
+                    .collect(),
+            ),
+        }
+    }
+
+    fn with_source<R>(&self, f: impl FnOnce(TextureSource) -> R) -> R {
+        match self {
+            RetainedTexture::Empty => f(TextureSource::Empty),
+            RetainedTexture::Bytes(bytes) => f(TextureSource::Bytes(bytes)),
+            RetainedTexture::Array(faces) => {
+                let mipmaps: Vec<Vec<&[u8]>> = faces
+                    .iter()
+                    .map(|mipmaps| mipmaps.iter().map(|bytes| &bytes[..]).collect())
+                    .collect();
+                let faces: Vec<&[&[u8]]> = mipmaps.iter().map(|mipmaps| &mipmaps[..]).collect();
+                f(TextureSource::Array(&faces))
+            }
+        }
+    }
+
+    /// Same as glTexSubImage2D with level 0.
+    pub fn update_part(
+        &mut self,
+        params: &TextureParams,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        source: &[u8],
+    ) {
+        if let RetainedTexture::Empty = self {
+            let size = params.format.size(params.width, params.height);
+            *self = RetainedTexture::Bytes(vec![0; size as usize]);
+        }
+        let bytes = match self {
+            RetainedTexture::Bytes(bytes) => bytes,
+            RetainedTexture::Array(faces) => &mut faces[0][0],
+            RetainedTexture::Empty => unreachable!(),
+        };
+        let pixel = params.format.size(1, 1) as usize;
+        let row = width as usize * pixel;
+        for y in 0..height as usize {
+            let dst = ((y_offset as usize + y) * params.width as usize + x_offset as usize) * pixel;
+            bytes[dst..dst + row].copy_from_slice(&source[y * row..(y + 1) * row]);
+        }
+    }
+}
+
+pub(super) enum RetainedShader {
+    Glsl {
+        vertex: String,
+        geometry: Option<String>,
+        fragment: String,
+    },
+    #[cfg(not(target_arch = "wasm32"))]
+    Binary(ShaderBinary),
+}
+
+impl RetainedShader {
+    pub fn glsl(vertex: &str, geometry: Option<&str>, fragment: &str) -> RetainedShader {
+        RetainedShader::Glsl {
+            vertex: vertex.to_string(),
+            geometry: geometry.map(|geometry| geometry.to_string()),
+            fragment: fragment.to_string(),
+        }
+    }
+}
+
+/// CPU copies of everything needed to re-create GPU resources in a new
+/// context, keyed by the resource index.
+#[derive(Default)]
+pub(super) struct Retained {
+    pub textures: HashMap<usize, RetainedTexture>,
+    pub buffers: HashMap<usize, Vec<u8>>,
+    pub shaders: HashMap<usize, RetainedShader>,
+}
+
+impl GlContext {
+    pub(super) fn retain(&mut self, f: impl FnOnce(&mut Retained)) {
+        if let Some(retained) = &mut self.retained {
+            f(retained);
+        }
+    }
+
+    pub(super) fn retain_shader(&mut self, id: usize, shader: RetainedShader) {
+        self.retain(|retained| drop(retained.shaders.insert(id, shader)));
+    }
+
+    /// Re-create GL objects of all the resources, keeping their ids.
+    /// Textures and buffers without retained data are re-created empty.
+    pub(super) unsafe fn restore(&mut self) {
+        glGetIntegerv(
+            GL_FRAMEBUFFER_BINDING,
+            &mut self.default_framebuffer as *mut _ as *mut _,
+        );
+        crate::native::gl::enable_debug_output();
+        glGenVertexArrays(1, &mut self.vao as *mut _);
+        glBindVertexArray(self.vao);
+        self.cache.invalidate(self.gl3);
+        // the capture did not survive the old context
+        self.transform_feedback = false;
+
+        let retaining = self.retained.is_some();
+        let retained = self.retained.take().unwrap_or_default();
+
+        for id in 0..self.textures.0.len() {
+            let Texture {
+                raw,
+                params,
+                immutable,
+            } = self.textures.0[id];
+            if raw == 0 {
+                continue;
+            }
+            let access = match immutable {
+                true => TextureAccess::Storage,
+                false => TextureAccess::Static,
+            };
+            let new = match retained.textures.get(&id) {
+                Some(texture) => {
+                    texture.with_source(|source| Texture::new(self, access, source, params))
+                }
+                None => Texture::new(self, access, TextureSource::Empty, params),
+            };
+            self.textures.0[id] = new;
+        }
+
+        for id in self.buffers.ids() {
+            let buffer = &self.buffers[id];
+            let gl_target = self.gl_buffer_target(&buffer.buffer_type);
+            let (usage, index_type, size) = (buffer.usage, buffer.index_type, buffer.size);
+            let data = match retained.buffers.get(&id) {
+                Some(data) => data.as_ptr() as *const _,
+                None => std::ptr::null(),
+            };
+            let gl_buf = self.create_gl_buffer(gl_target, gl_usage(&usage), index_type, size, data);
+            self.buffers[id].gl_buf = gl_buf;
+        }
+
+        for (id, shader) in &retained.shaders {
+            let meta = self.shaders[*id].meta.clone();
+            let varyings = self.shaders[*id].feedback_varyings.clone();
+            let varyings = varyings.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+            let new = match shader {
+                RetainedShader::Glsl {
+                    vertex,
+                    geometry,
+                    fragment,
+                } => load_shader_internal(
+                    vertex,
+                    geometry.as_deref(),
+                    fragment,
+                    meta,
+                    &varyings,
+                    self.features.program_binary,
+                ),
+                #[cfg(not(target_arch = "wasm32"))]
+                RetainedShader::Binary(binary) => load_binary_internal(binary, meta),
+            };
+            match new {
+                Ok(new) => {
+                    self.shaders[*id] = new;
+                    self.update_pipeline_layouts(ShaderId(*id));
+                }
+                Err(err) => eprintln!("Failed to restore shader: {}", err),
+            }
+        }
+
+        for id in self.passes.ids() {
+            let pass = &self.passes[id];
+            let (color, depth) = (pass.color_targets.clone(), pass.depth_target);
+            self.passes[id].gl_fb = self.create_framebuffer(&color, depth);
+        }
+
+        if retaining {
+            self.retained = Some(retained);
+        }
+    }
+}
@@ -7,9 +7,13 @@ use crate::native::apple::{
 
 use super::*;
 
+use std::cell::Cell;
+
 // https://developer.apple.com/metal/Metal-Feature-Set-Tables.pdf
 const MAX_UNIFORM_BUFFER_SIZE: u64 = 4 * 1024 * 1024;
 const NUM_INFLIGHT_FRAMES: usize = 3;
+// every pipeline is made for this many color attachments
+const MAX_COLOR_ATTACHMENTS: usize = 8;
 #[cfg(any(target_os = "macos", all(target_os = "ios", target_arch = "x86_64")))]
 const UNIFORM_BUFFER_ALIGN: u64 = 256;
 #[cfg(all(target_os = "ios", not(target_arch = "x86_64")))]
@@ -50,7 +54,9 @@ impl From<UniformType> for MTLVertexFormat {
             UniformType::Int2 => MTLVertexFormat::Int2,
             UniformType::Int3 => MTLVertexFormat::Int3,
             UniformType::Int4 => MTLVertexFormat::Int4,
+            UniformType::Mat3 => MTLVertexFormat::Float3,
             UniformType::Mat4 => MTLVertexFormat::Float4,
+            UniformType::Bool => MTLVertexFormat::Int,
         }
     }
 }
@@ -79,6 +85,8 @@ impl From<BlendFactor> for MTLBlendFactor {
             BlendFactor::Value(BlendValue::SourceAlpha) => MTLBlendFactor::SourceAlpha,
             BlendFactor::Value(BlendValue::DestinationColor) => MTLBlendFactor::DestinationColor,
             BlendFactor::Value(BlendValue::DestinationAlpha) => MTLBlendFactor::DestinationAlpha,
+            BlendFactor::Value(BlendValue::Source1Color) => MTLBlendFactor::Source1Color,
+            BlendFactor::Value(BlendValue::Source1Alpha) => MTLBlendFactor::Source1Alpha,
             BlendFactor::OneMinusValue(BlendValue::SourceColor) => {
                 MTLBlendFactor::OneMinusSourceColor
             }
@@ -91,6 +99,12 @@ impl From<BlendFactor> for MTLBlendFactor {
             BlendFactor::OneMinusValue(BlendValue::DestinationAlpha) => {
                 MTLBlendFactor::OneMinusDestinationAlpha
             }
+            BlendFactor::OneMinusValue(BlendValue::Source1Color) => {
+                MTLBlendFactor::OneMinusSource1Color
+            }
+            BlendFactor::OneMinusValue(BlendValue::Source1Alpha) => {
+                MTLBlendFactor::OneMinusSource1Alpha
+            }
             BlendFactor::SourceAlphaSaturate => MTLBlendFactor::SourceAlphaSaturated,
         }
     }
@@ -121,6 +135,80 @@ impl From<Equation> for MTLBlendOperation {
     }
 }
 
+unsafe fn set_attachment_blend(
+    color_attachment: ObjcId,
+    color_blend: Option<BlendState>,
+    alpha_blend: Option<BlendState>,
+) {
+    let color_blend = match color_blend {
+        Some(color_blend) => color_blend,
+        None => {
+            msg_send_![color_attachment, setBlendingEnabled: false];
+            return;
+        }
+    };
+    msg_send_![color_attachment, setBlendingEnabled: true];
+
+    let BlendState {
+        equation: eq_rgb,
+        sfactor: src_rgb,
+        dfactor: dst_rgb,
+    } = color_blend;
+    let BlendState {
+        equation: eq_alpha,
+        sfactor: src_alpha,
+        dfactor: dst_alpha,
+    } = alpha_blend.unwrap_or(color_blend);
+    msg_send_![
+        color_attachment,
+        setRgbBlendOperation: MTLBlendOperation::from(eq_rgb)
+    ];
+    msg_send_![
+        color_attachment,
+        setAlphaBlendOperation: MTLBlendOperation::from(eq_alpha)
+    ];
+    msg_send_![
+        color_attachment,
+        setSourceRGBBlendFactor: MTLBlendFactor::from(src_rgb)
+    ];
+    msg_send_![
+        color_attachment,
+        setSourceAlphaBlendFactor: MTLBlendFactor::from(src_alpha)
+    ];
+    msg_send_![
+        color_attachment,
+        setDestinationRGBBlendFactor: MTLBlendFactor::from(dst_rgb)
+    ];
+    msg_send_![
+        color_attachment,
+        setDestinationAlphaBlendFactor: MTLBlendFactor::from(dst_alpha)
+    ];
+}
+
+unsafe fn new_pipeline_state(device: ObjcId, descriptor: ObjcId) -> ObjcId {
+    let mut error: ObjcId = nil;
+    let pipeline_state: ObjcId = msg_send![
+        device,
+        newRenderPipelineStateWithDescriptor: descriptor
+        error: &mut error
+    ];
+    if pipeline_state.is_null() {
+        let description: ObjcId = msg_send![error, localizedDescription];
+        let string = apple_util::nsstring_to_string(description);
+        panic!("newRenderPipelineStateWithDescriptor error: {}", string);
+    }
+    pipeline_state
+}
+
+impl From<StoreAction> for MTLStoreAction {
+    fn from(action: StoreAction) -> Self {
+        match action {
+            StoreAction::Store => MTLStoreAction::Store,
+            StoreAction::DontCare => MTLStoreAction::DontCare,
+        }
+    }
+}
+
 impl From<CompareFunc> for MTLCompareFunction {
     fn from(cf: CompareFunc) -> Self {
         match cf {
@@ -149,7 +237,9 @@ impl From<PrimitiveType> for MTLPrimitiveType {
     fn from(primitive_type: PrimitiveType) -> Self {
         match primitive_type {
             PrimitiveType::Triangles => MTLPrimitiveType::Triangle,
+            PrimitiveType::TriangleStrip => MTLPrimitiveType::TriangleStrip,
             PrimitiveType::Lines => MTLPrimitiveType::Line,
+            PrimitiveType::LineStrip => MTLPrimitiveType::LineStrip,
         }
     }
 }
@@ -220,7 +310,9 @@ struct ShaderUniform {
 struct ShaderInternal {
     vertex_function: ObjcId,
     fragment_function: ObjcId,
-    //uniforms: Vec<ShaderUniform>,
+    uniforms: UniformBlockLayout,
+    // tightly packed copy of the last applied uniforms, for set_uniform
+    uniform_data: Vec<u8>,
     // the distance, in bytes, between two uniforms in uniforms buffer
     //stride: u64,
 }
@@ -228,17 +320,33 @@ struct ShaderInternal {
 struct RenderPassInternal {
     render_pass_desc: ObjcId,
     texture: Vec<TextureId>,
-    _depth_texture: Option<TextureId>,
+    depth_texture: Option<TextureId>,
+    // attachment size is the size of this mip level of the first texture
+    mip_level: u32,
+    color_actions: Vec<AttachmentAction>,
+    depth_action: AttachmentAction,
+}
+
+// MTLLoadAction for an attachment, Clear without a clear value keeps the
+// content, same as GL
+fn load_action(action: LoadAction, clear: bool) -> MTLLoadAction {
+    match action {
+        LoadAction::Clear if clear => MTLLoadAction::Clear,
+        LoadAction::Clear | LoadAction::Load => MTLLoadAction::Load,
+        LoadAction::DontCare => MTLLoadAction::DontCare,
+    }
 }
 
 #[derive(Clone, Debug)]
 struct PipelineInternal {
     pipeline_state: ObjcId,
+    // kept to rebuild pipeline_state with per-attachment blending
+    descriptor: ObjcId,
     depth_stencil_state: ObjcId,
     //layout: Vec<BufferLayout>,
     //attributes: Vec<VertexAttributeInternal>,
-    _shader: ShaderId,
-    //params: PipelineParams,
+    shader: ShaderId,
+    params: PipelineParams,
 }
 
 #[derive(Clone, Copy)]
@@ -285,6 +393,20 @@ pub struct MetalContext {
     // cached pipeline from apply_pipeline
     current_pipeline: Option<Pipeline>,
     current_ub_offset: u64,
+    // pass and descriptor of the current render_encoder
+    current_pass: Option<RenderPass>,
+    current_pass_desc: ObjcId,
+    // store actions of the current pass colors and depth-stencil, the
+    // encoders are made with deferred ones and get these at end_render_pass
+    current_store_actions: (Vec<MTLStoreAction>, Option<MTLStoreAction>),
+    // state of the current render_encoder, clear_color_attachment applies it
+    // again on the encoder it starts
+    current_bindings: Option<(Vec<BufferId>, BufferId, Vec<TextureId>)>,
+    current_uniforms: Option<(ObjcId, u64)>,
+    current_viewport: Option<(i32, i32, i32, i32)>,
+    current_scissor: Option<(i32, i32, i32, i32)>,
+    stats: Cell<FrameStats>,
+    last_frame_stats: FrameStats,
 }
 
 impl MetalContext {
@@ -373,9 +495,39 @@ impl MetalContext {
                 uniform_buffers,
                 current_frame_index: 1,
                 current_ub_offset: 0,
+                current_pass: None,
+                current_pass_desc: nil,
+                current_store_actions: (vec![], None),
+                current_bindings: None,
+                current_uniforms: None,
+                current_viewport: None,
+                current_scissor: None,
+                stats: Cell::new(FrameStats::default()),
+                last_frame_stats: FrameStats::default(),
             }
         }
     }
+
+    unsafe fn set_store_actions(
+        &self,
+        colors: &[MTLStoreAction],
+        depth_stencil: Option<MTLStoreAction>,
+    ) {
+        let render_encoder = self.render_encoder.unwrap();
+        for (i, store) in colors.iter().enumerate() {
+            msg_send_![render_encoder, setColorStoreAction: *store atIndex: i];
+        }
+        if let Some(store) = depth_stencil {
+            msg_send_![render_encoder, setDepthStoreAction: store];
+            msg_send_![render_encoder, setStencilStoreAction: store];
+        }
+    }
+
+    fn count(&self, f: impl FnOnce(&mut FrameStats)) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
 }
 
 impl RenderingBackend for MetalContext {
@@ -383,8 +535,28 @@ impl RenderingBackend for MetalContext {
         ContextInfo {
             backend: Backend::Metal,
             gl_version_string: Default::default(),
+            glsl_version_string: Default::default(),
+            vendor: "Apple".to_string(),
+            renderer: unsafe { apple_util::nsstring_to_string(msg_send![self.device, name]) },
+            egl: false,
             glsl_support: Default::default(),
-            features: Features { instancing: true },
+            features: Features {
+                instancing: true,
+                transform_feedback: false,
+                occlusion_query: false,
+                any_samples_query: false,
+                conditional_render: false,
+                compute: false,
+                storage_buffers: false,
+                geometry_shader: false,
+                program_binary: false,
+                float_textures: true,
+                max_texture_size: 16384,
+                max_samples: 4,
+                max_color_attachments: MAX_COLOR_ATTACHMENTS as _,
+                independent_blend: true,
+                dual_source_blending: true,
+            },
         }
     }
     fn buffer_size(&mut self, buffer: BufferId) -> usize {
@@ -405,18 +577,36 @@ impl RenderingBackend for MetalContext {
             msg_send_![texture.texture, release];
         }
     }
-    fn apply_viewport(&mut self, _x: i32, _y: i32, _w: i32, _h: i32) {}
+    // features.storage_buffers is false, there is nothing to bind
+    fn apply_storage_buffers(&mut self, _buffers: &[BufferId]) {}
+    fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        assert!(self.render_encoder.is_some());
+
+        // metal's origin is top left, flip within the current render target
+        let (_, target_height) = self.render_pass_size(self.current_pass);
+        let vp = MTLViewport {
+            originX: x as _,
+            originY: (target_height as i32 - (y + h)) as _,
+            width: w as _,
+            height: h as _,
+            znear: 0.0,
+            zfar: 1.0,
+        };
+        unsafe { msg_send_![self.render_encoder.unwrap(), setViewport: vp] };
+        self.current_viewport = Some((x, y, w, h));
+    }
     fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
         assert!(self.render_encoder.is_some());
 
-        let (_, screen_height) = crate::window::screen_size();
+        let (_, target_height) = self.render_pass_size(self.current_pass);
         let r = MTLScissorRect {
             x: x as _,
-            y: (screen_height as i32 - (y + h)) as u64,
+            y: (target_height as i32 - (y + h)) as u64,
             width: w as _,
             height: h as _,
         };
         unsafe { msg_send_![self.render_encoder.unwrap(), setScissorRect: r] };
+        self.current_scissor = Some((x, y, w, h));
     }
     fn texture_set_min_filter(
         &mut self,
@@ -530,10 +720,10 @@ impl RenderingBackend for MetalContext {
         self.end_render_pass();
     }
 
-    fn new_render_pass_mrt(
+    fn new_render_pass_targets(
         &mut self,
-        color_img: &[TextureId],
-        depth_img: Option<TextureId>,
+        color_img: &[RenderTarget],
+        depth_img: Option<RenderTarget>,
     ) -> RenderPass {
         unsafe {
             let render_pass_desc =
@@ -541,17 +731,21 @@ impl RenderingBackend for MetalContext {
             msg_send_![render_pass_desc, retain];
             assert!(!render_pass_desc.is_null());
             for (i, color_img) in color_img.iter().enumerate() {
-                let color_texture = self.textures.get(*color_img).texture;
+                let color_texture = self.textures.get(color_img.texture).texture;
                 let color_attachment = msg_send_![msg_send_![render_pass_desc, colorAttachments], objectAtIndexedSubscript:i];
                 msg_send_![color_attachment, setTexture: color_texture];
+                msg_send_![color_attachment, setLevel: color_img.mip_level as u64];
+                msg_send_![color_attachment, setSlice: color_img.layer as u64];
                 msg_send_![color_attachment, setLoadAction: MTLLoadAction::Clear];
                 msg_send_![color_attachment, setStoreAction: MTLStoreAction::Store];
             }
             if let Some(depth_img) = depth_img {
-                let depth_texture = self.textures.get(depth_img).texture;
+                let depth_texture = self.textures.get(depth_img.texture).texture;
 
                 let depth_attachment = msg_send_![render_pass_desc, depthAttachment];
                 msg_send_![depth_attachment, setTexture: depth_texture];
+                msg_send_![depth_attachment, setLevel: depth_img.mip_level as u64];
+                msg_send_![depth_attachment, setSlice: depth_img.layer as u64];
                 msg_send_![depth_attachment, setLoadAction: MTLLoadAction::Clear];
                 msg_send_![depth_attachment, setStoreAction: MTLStoreAction::Store];
                 msg_send_![depth_attachment, setClearDepth:1.];
@@ -561,8 +755,15 @@ impl RenderingBackend for MetalContext {
             }
             let pass = RenderPassInternal {
                 render_pass_desc,
-                texture: color_img.to_vec(),
-                _depth_texture: depth_img,
+                texture: color_img.iter().map(|target| target.texture).collect(),
+                depth_texture: depth_img.map(|target| target.texture),
+                mip_level: color_img
+                    .first()
+                    .copied()
+                    .or(depth_img)
+                    .map_or(0, |target| target.mip_level),
+                color_actions: vec![AttachmentAction::default(); color_img.len()],
+                depth_action: AttachmentAction::default(),
             };
 
             self.passes.push(pass);
@@ -571,6 +772,22 @@ impl RenderingBackend for MetalContext {
         }
     }
 
+    fn render_pass_set_actions(
+        &mut self,
+        render_pass: RenderPass,
+        color: &[AttachmentAction],
+        depth: AttachmentAction,
+    ) {
+        let pass = &mut self.passes[render_pass.0];
+        assert_eq!(
+            color.len(),
+            pass.texture.len(),
+            "Action count and color attachment count did not match!"
+        );
+        pass.color_actions = color.to_vec();
+        pass.depth_action = depth;
+    }
+
     fn delete_render_pass(&mut self, render_pass: RenderPass) {
         let render_pass = &self.passes[render_pass.0];
         unsafe {
@@ -581,6 +798,28 @@ impl RenderingBackend for MetalContext {
     fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
         &self.passes[render_pass.0].texture
     }
+    fn render_pass_size(&self, render_pass: Option<RenderPass>) -> (u32, u32) {
+        match render_pass {
+            None => {
+                let (screen_width, screen_height) = crate::window::screen_size();
+                (screen_width as u32, screen_height as u32)
+            }
+            Some(pass) => {
+                let pass = &self.passes[pass.0];
+                let texture = pass
+                    .texture
+                    .first()
+                    .copied()
+                    .or(pass.depth_texture)
+                    .unwrap();
+                let params = self.textures.get(texture).params;
+                (
+                    (params.width >> pass.mip_level).max(1),
+                    (params.height >> pass.mip_level).max(1),
+                )
+            }
+        }
+    }
 
     fn new_buffer(&mut self, _: BufferType, _usage: BufferUsage, data: BufferSource) -> BufferId {
         let mut raw = [nil; BUFFERS_IN_ROTATION];
@@ -642,12 +881,61 @@ impl RenderingBackend for MetalContext {
             msg_send_![buffer.raw[buffer.next_value], didModifyRange:NSRange::new(0, data.size as u64)];
         }
         buffer.value = buffer.next_value;
+        self.count(|stats| stats.buffer_uploads += 1);
+    }
+
+    fn new_shader_with_feedback(
+        &mut self,
+        _shader: ShaderSource,
+        _meta: ShaderMeta,
+        _varyings: &[&str],
+    ) -> Result<ShaderId, ShaderError> {
+        Err(ShaderError::NotSupported(
+            "Transform feedback is not supported on metal".to_string(),
+        ))
+    }
+
+    fn shader_recompile(
+        &mut self,
+        _shader: ShaderId,
+        _source: ShaderSource,
+    ) -> Result<(), ShaderError> {
+        Err(ShaderError::NotSupported(
+            "shader_recompile is not supported on metal".to_string(),
+        ))
+    }
+
+    // features.program_binary is false, there is nothing to get or load
+    fn shader_binary(&self, _shader: ShaderId) -> Option<ShaderBinary> {
+        None
+    }
+
+    fn new_shader_from_binary(
+        &mut self,
+        _binary: &ShaderBinary,
+        _meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        Err(ShaderError::LinkError(
+            "Program binaries are not supported on metal".to_string(),
+        ))
+    }
+
+    fn shader_reflect(&self, _shader: ShaderId) -> Result<ShaderReflection, ShaderError> {
+        Err(ShaderError::NotSupported(
+            "Shader reflection is not supported on metal".to_string(),
+        ))
+    }
+
+    fn new_shader_reflected(&mut self, _shader: ShaderSource) -> Result<ShaderId, ShaderError> {
+        Err(ShaderError::NotSupported(
+            "Shader reflection is not supported on metal".to_string(),
+        ))
     }
 
     fn new_shader(
         &mut self,
         shader: ShaderSource,
-        _meta: ShaderMeta,
+        meta: ShaderMeta,
     ) -> Result<ShaderId, ShaderError> {
         unsafe {
             let program = match shader {
@@ -664,17 +952,31 @@ impl RenderingBackend for MetalContext {
             ];
             if library.is_null() {
                 let description: ObjcId = msg_send![error, localizedDescription];
-                let string = apple_util::nsstring_to_string(description);
-                panic!("Shader {}", string);
+                let error_message = apple_util::nsstring_to_string(description);
+                // MSL program contains both stages, metal does not tell which one failed
+                return Err(ShaderError::CompilationError {
+                    shader_type: ShaderType::Vertex,
+                    error_message,
+                });
             }
 
             let vertex_function: ObjcId = msg_send![library, newFunctionWithName: apple_util::str_to_nsstring("vertexShader")];
-            assert!(!vertex_function.is_null());
+            if vertex_function.is_null() {
+                return Err(ShaderError::LinkError(
+                    "vertexShader function not found".to_string(),
+                ));
+            }
             let fragment_function: ObjcId = msg_send![library, newFunctionWithName: apple_util::str_to_nsstring("fragmentShader")];
-            assert!(!fragment_function.is_null());
+            if fragment_function.is_null() {
+                return Err(ShaderError::LinkError(
+                    "fragmentShader function not found".to_string(),
+                ));
+            }
             let shader = ShaderInternal {
                 vertex_function,
                 fragment_function,
+                uniforms: meta.uniforms,
+                uniform_data: vec![],
             };
             self.shaders.push(shader);
             Ok(ShaderId(self.shaders.len() - 1))
@@ -911,53 +1213,16 @@ impl RenderingBackend for MetalContext {
             msg_send_![descriptor, setVertexFunction:shader_internal.vertex_function];
             msg_send_![descriptor, setFragmentFunction:shader_internal.fragment_function];
             msg_send_![descriptor, setVertexDescriptor: vertex_descriptor];
+            msg_send_![
+                descriptor,
+                setAlphaToCoverageEnabled: BOOL::from(params.alpha_to_coverage)
+            ];
             let color_attachments = msg_send_![descriptor, colorAttachments];
-            for i in 0..2 {
+            for i in 0..MAX_COLOR_ATTACHMENTS {
                 let color_attachment = msg_send_![color_attachments, objectAtIndexedSubscript: i];
                 let view_pixel_format: MTLPixelFormat = msg_send![self.view, colorPixelFormat];
                 msg_send_![color_attachment, setPixelFormat: view_pixel_format];
-                if let Some(color_blend) = params.color_blend {
-                    msg_send_![color_attachment, setBlendingEnabled: true];
-
-                    let BlendState {
-                        equation: eq_rgb,
-                        sfactor: src_rgb,
-                        dfactor: dst_rgb,
-                    } = color_blend;
-                    let BlendState {
-                        equation: eq_alpha,
-                        sfactor: src_alpha,
-                        dfactor: dst_alpha,
-                    } = color_blend;
-                    msg_send_![
-                        color_attachment,
-                        setRgbBlendOperation: MTLBlendOperation::from(eq_rgb)
-                    ];
-                    msg_send_![
-                        color_attachment,
-                        setAlphaBlendOperation: MTLBlendOperation::from(eq_alpha)
-                    ];
-                    msg_send_![
-                        color_attachment,
-                        setSourceRGBBlendFactor: MTLBlendFactor::from(src_rgb)
-                    ];
-                    msg_send_![
-                        color_attachment,
-                        setSourceRGBBlendFactor: MTLBlendFactor::from(src_rgb)
-                    ];
-                    msg_send_![
-                        color_attachment,
-                        setSourceAlphaBlendFactor: MTLBlendFactor::from(src_alpha)
-                    ];
-                    msg_send_![
-                        color_attachment,
-                        setDestinationRGBBlendFactor: MTLBlendFactor::from(dst_rgb)
-                    ];
-                    msg_send_![
-                        color_attachment,
-                        setDestinationAlphaBlendFactor: MTLBlendFactor::from(dst_alpha)
-                    ];
-                }
+                set_attachment_blend(color_attachment, params.color_blend, params.alpha_blend);
             }
             msg_send_![
                 descriptor,
@@ -968,17 +1233,7 @@ impl RenderingBackend for MetalContext {
                 setStencilAttachmentPixelFormat: MTLPixelFormat::Depth32Float_Stencil8
             ];
 
-            let mut error: ObjcId = nil;
-            let pipeline_state: ObjcId = msg_send![
-                self.device,
-                newRenderPipelineStateWithDescriptor: descriptor
-                error: &mut error
-            ];
-            if pipeline_state.is_null() {
-                let description: ObjcId = msg_send![error, localizedDescription];
-                let string = apple_util::nsstring_to_string(description);
-                panic!("newRenderPipelineStateWithDescriptor error: {}", string);
-            }
+            let pipeline_state = new_pipeline_state(self.device, descriptor);
 
             let depth_stencil_desc = msg_send_![class!(MTLDepthStencilDescriptor), new];
             msg_send_![depth_stencil_desc, setDepthWriteEnabled: BOOL::from(params.depth_write)];
@@ -1015,11 +1270,12 @@ impl RenderingBackend for MetalContext {
 
             let pipeline = PipelineInternal {
                 pipeline_state,
+                descriptor,
                 depth_stencil_state,
                 //layout: buffer_layout.to_vec(),
                 //attributes: vertex_layout,
-                _shader: shader,
-                //params,
+                shader,
+                params,
             };
 
             self.pipelines.push(pipeline);
@@ -1035,12 +1291,20 @@ impl RenderingBackend for MetalContext {
         );
         let render_encoder = self.render_encoder.unwrap();
 
+        if self.current_pipeline != Some(*pipeline) {
+            self.count(|stats| stats.pipeline_switches += 1);
+        }
         unsafe {
             self.current_pipeline = Some(*pipeline);
             let pipeline = &self.pipelines[pipeline.0];
 
             msg_send_![render_encoder, setRenderPipelineState: pipeline.pipeline_state];
             msg_send_![render_encoder, setDepthStencilState:pipeline.depth_stencil_state];
+            let fill_mode = match pipeline.params.polygon_mode {
+                PolygonMode::Line => MTLTriangleFillMode::Lines,
+                _ => MTLTriangleFillMode::Fill,
+            };
+            msg_send_![render_encoder, setTriangleFillMode: fill_mode];
             // render_encoder.set_front_facing_winding(pipeline.params.front_face_order.into());
             // render_encoder.set_cull_mode(pipeline.params.cull_face.into());
         }
@@ -1083,9 +1347,62 @@ impl RenderingBackend for MetalContext {
                     msg_send_![render_encoder, setFragmentTexture:texture
                                atIndex:n
                     ];
+                    self.count(|stats| stats.texture_binds += 1);
                 }
             }
         }
+        self.current_bindings = Some((vertex_buffers.to_vec(), index_buffer, textures.to_vec()));
+    }
+
+    fn set_uniform_from_bytes(
+        &mut self,
+        name: &str,
+        uniform_type: UniformType,
+        array_count: usize,
+        data: &[u8],
+    ) {
+        assert!(
+            data.len() >= uniform_type.size() * array_count,
+            "Not enough data for uniform \"{}\"",
+            name
+        );
+        assert!(
+            self.current_pipeline.is_some(),
+            "set_uniform before apply_pipeline"
+        );
+        let shader = self.pipelines[self.current_pipeline.unwrap().0].shader;
+        let shader = &mut self.shaders[shader.0];
+
+        let mut offset = 0;
+        let mut found = None;
+        for uniform in &shader.uniforms.uniforms {
+            let size = uniform.uniform_type.size() * uniform.array_count;
+            if uniform.name == name {
+                found = Some((uniform.uniform_type, uniform.array_count));
+                break;
+            }
+            offset += size;
+        }
+        // not in ShaderMeta, ignored the same way as an unknown name on GL
+        let (meta_type, meta_count) = match found {
+            Some(found) => found,
+            None => return,
+        };
+        debug_assert!(
+            meta_type == uniform_type,
+            "Uniform \"{}\" is {:?} in the shader, not {:?}",
+            name,
+            meta_type,
+            uniform_type
+        );
+
+        let size = uniform_type.size() * array_count.min(meta_count);
+        if shader.uniform_data.len() < offset + size {
+            shader.uniform_data.resize(offset + size, 0);
+        }
+        shader.uniform_data[offset..offset + size].copy_from_slice(&data[..size]);
+        let uniform_data = shader.uniform_data.clone();
+        self.apply_uniforms_from_bytes(uniform_data.as_ptr(), uniform_data.len());
     }
 
     fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
@@ -1102,6 +1419,14 @@ impl RenderingBackend for MetalContext {
 
         self.current_frame_index = (self.current_frame_index + 1) % NUM_INFLIGHT_FRAMES;
 
+        let shader = self.pipelines[self.current_pipeline.unwrap().0].shader;
+        let data = unsafe { std::slice::from_raw_parts(uniform_ptr as *const u8, size) };
+        let shader = &mut self.shaders[shader.0];
+        shader.uniform_data.clear();
+        shader.uniform_data.extend_from_slice(data);
+        let data = shader.uniforms.msl_bytes(data);
+        let size = data.len();
+
         assert!(size < MAX_UNIFORM_BUFFER_SIZE as usize);
 
         assert!(self.current_ub_offset < MAX_UNIFORM_BUFFER_SIZE);
@@ -1110,7 +1435,7 @@ impl RenderingBackend for MetalContext {
         unsafe {
             let dest: *mut std::ffi::c_void = msg_send![buffer, contents];
             std::ptr::copy(
-                uniform_ptr as _,
+                data.as_ptr() as _,
                 dest.add(self.current_ub_offset as usize),
                 size,
             );
@@ -1127,6 +1452,7 @@ impl RenderingBackend for MetalContext {
                        offset:self.current_ub_offset
                        atIndex:0];
         }
+        self.current_uniforms = Some((buffer, self.current_ub_offset));
         self.current_ub_offset = roundup_ub_buffer(self.current_ub_offset + size as u64);
     }
 
@@ -1161,7 +1487,7 @@ impl RenderingBackend for MetalContext {
                         .texture
                         .first()
                         .copied()
-                        //.or(pass.depth_texture)
+                        .or(pass.depth_texture)
                         .unwrap();
 
                     (
@@ -1173,23 +1499,52 @@ impl RenderingBackend for MetalContext {
             };
             assert!(!descriptor.is_null());
 
-            let color_attachments = msg_send_![descriptor, colorAttachments];
-            let color_attachment = msg_send_![color_attachments, objectAtIndexedSubscript: 0];
-
-            msg_send_![color_attachment, setStoreAction: MTLStoreAction::Store];
-
-            match action {
-                PassAction::Clear { color, .. } => {
-                    msg_send_![color_attachment, setLoadAction: MTLLoadAction::Clear];
+            let (clear_color, clear_depth) = match action {
+                PassAction::Clear { color, depth, .. } => (color, depth),
+                PassAction::Nothing => (None, None),
+            };
+            // the default pass has a single color attachment with default actions
+            let (color_actions, depth_action) = match pass {
+                None => (vec![AttachmentAction::default()], None),
+                Some(pass) => {
+                    let pass = &self.passes[pass.0];
+                    (
+                        pass.color_actions.clone(),
+                        pass.depth_texture.map(|_| pass.depth_action),
+                    )
+                }
+            };
 
-                    if let Some(color) = color {
-                        msg_send_![color_attachment, setClearColor:MTLClearColor::new(color.0 as _, color.1 as _, color.2 as _, color.3 as _)];
-                    }
+            let color_attachments = msg_send_![descriptor, colorAttachments];
+            let mut color_stores = vec![];
+            for (i, action) in color_actions.iter().enumerate() {
+                let color_attachment = msg_send_![color_attachments, objectAtIndexedSubscript: i];
+                let load: MTLLoadAction = load_action(action.load, clear_color.is_some());
+                msg_send_![color_attachment, setLoadAction: load];
+                msg_send_![color_attachment, setStoreAction: MTLStoreAction::Unknown];
+                color_stores.push(action.store.into());
+                if let (MTLLoadAction::Clear, Some(color)) = (load, clear_color) {
+                    msg_send_![color_attachment, setClearColor:MTLClearColor::new(color.0 as _, color.1 as _, color.2 as _, color.3 as _)];
                 }
-                PassAction::Nothing => {
-                    msg_send_![color_attachment, setLoadAction: MTLLoadAction::Load];
+            }
+            let depth_attachment = msg_send_![descriptor, depthAttachment];
+            let stencil_attachment = msg_send_![descriptor, stencilAttachment];
+            if let Some(action) = depth_action {
+                let load: MTLLoadAction = load_action(action.load, clear_depth.is_some());
+                msg_send_![depth_attachment, setLoadAction: load];
+                if let (MTLLoadAction::Clear, Some(depth)) = (load, clear_depth) {
+                    msg_send_![depth_attachment, setClearDepth: depth as f64];
                 }
             }
+            let depth_texture: ObjcId = msg_send![depth_attachment, texture];
+            let depth_stencil_store = if depth_texture.is_null() {
+                None
+            } else {
+                msg_send_![depth_attachment, setStoreAction: MTLStoreAction::Unknown];
+                msg_send_![stencil_attachment, setStoreAction: MTLStoreAction::Unknown];
+                // the view's own depth is never read back
+                Some(depth_action.map_or(MTLStoreAction::DontCare, |action| action.store.into()))
+            };
 
             let render_encoder = msg_send_![
                 self.command_buffer.unwrap(),
@@ -1212,6 +1567,9 @@ impl RenderingBackend for MetalContext {
             // });
 
             self.render_encoder = Some(render_encoder);
+            self.current_pass = pass;
+            self.current_pass_desc = descriptor;
+            self.current_store_actions = (color_stores, depth_stencil_store);
         }
     }
 
@@ -1222,10 +1580,18 @@ impl RenderingBackend for MetalContext {
         );
 
         let render_encoder = self.render_encoder.unwrap();
-        unsafe { msg_send_!(render_encoder, endEncoding) };
+        unsafe {
+            let (colors, depth_stencil) = &self.current_store_actions;
+            self.set_store_actions(colors, *depth_stencil);
+            msg_send_!(render_encoder, endEncoding)
+        };
 
         self.render_encoder = None;
         self.index_buffer = None;
+        self.current_bindings = None;
+        self.current_uniforms = None;
+        self.current_viewport = None;
+        self.current_scissor = None;
     }
 
     fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
@@ -1235,8 +1601,17 @@ impl RenderingBackend for MetalContext {
         let index_buffer = self.index_buffer.unwrap();
 
         assert!(base_element == 0); // TODO: figure indexBufferOffset/baseVertex
+        let pip = &self.pipelines[self.current_pipeline.unwrap().0];
+        // metal always restarts strips on the maximum index value, the
+        // count is an upper bound, see FrameStats::triangles
+        let triangles = pip.params.primitive_type.triangle_count(num_elements);
+        self.count(|stats| {
+            stats.draw_calls += 1;
+            stats.triangles += triangles * num_instances as u64;
+        });
+        let primitive_type: MTLPrimitiveType = pip.params.primitive_type.into();
         unsafe {
-            msg_send_![render_encoder, drawIndexedPrimitives:MTLPrimitiveType::Triangle
+            msg_send_![render_encoder, drawIndexedPrimitives:primitive_type
                        indexCount:num_elements as u64
                        indexType:MTLIndexType::UInt16
                        indexBuffer:index_buffer
@@ -1248,12 +1623,187 @@ impl RenderingBackend for MetalContext {
         }
     }
 
+    // features.transform_feedback is false, no shader can capture varyings
+    fn begin_transform_feedback(&mut self, _buffers: &[BufferId], _discard: bool) {}
+
+    fn end_transform_feedback(&mut self) {}
+
+    // features.occlusion_query is false, there are no queries to end or read
+    fn begin_query(&mut self, _query_type: QueryType) -> Option<QueryId> {
+        None
+    }
+    fn end_query(&mut self, _query: QueryId) {}
+    fn query_result(&self, _query: QueryId) -> Option<u64> {
+        None
+    }
+    fn delete_query(&mut self, _query: QueryId) {}
+    fn begin_conditional_render(&mut self, _query: QueryId, _wait: bool) {}
+    fn end_conditional_render(&mut self) {}
+
+    fn new_compute_pipeline(
+        &mut self,
+        _source: &str,
+        _meta: ShaderMeta,
+    ) -> Result<ComputePipeline, ShaderError> {
+        Err(ShaderError::NotSupported(
+            "Compute shaders are not supported on metal".to_string(),
+        ))
+    }
+    // features.compute is false and no ComputePipeline can be created
+    fn apply_compute_pipeline(&mut self, _pipeline: &ComputePipeline) {}
+    fn apply_compute_bindings(
+        &mut self,
+        _storage_buffers: &[BufferId],
+        _images: &[TextureId],
+    ) -> Result<(), ShaderError> {
+        Ok(())
+    }
+    fn dispatch(&mut self, _x: u32, _y: u32, _z: u32) {}
+    fn memory_barrier(&mut self) {}
+    fn delete_compute_pipeline(&mut self, _pipeline: ComputePipeline) {}
+
+    fn clear_color_attachment(&mut self, attachment: usize, color: (f32, f32, f32, f32)) {
+        assert!(
+            self.render_encoder.is_some(),
+            "clear_color_attachment before begin_pass"
+        );
+        let color_count = match self.current_pass {
+            None => 1,
+            Some(pass) => self.passes[pass.0].texture.len(),
+        };
+        assert!(attachment < color_count, "No such color attachment");
+
+        // metal clears only when an encoder starts, so end the current one
+        // and start a new one that keeps everything but this attachment.
+        // The pass store actions are for its last encoder only
+        unsafe {
+            let (colors, depth_stencil) = &self.current_store_actions;
+            let stores = vec![MTLStoreAction::Store; colors.len()];
+            self.set_store_actions(&stores, depth_stencil.map(|_| MTLStoreAction::Store));
+            msg_send_![self.render_encoder.unwrap(), endEncoding];
+
+            let descriptor = self.current_pass_desc;
+            let color_attachments = msg_send_![descriptor, colorAttachments];
+            for i in 0..color_count {
+                let color_attachment = msg_send_![color_attachments, objectAtIndexedSubscript: i];
+                if i == attachment {
+                    msg_send_![color_attachment, setLoadAction: MTLLoadAction::Clear];
+                    msg_send_![color_attachment, setClearColor:MTLClearColor::new(color.0 as _, color.1 as _, color.2 as _, color.3 as _)];
+                } else {
+                    msg_send_![color_attachment, setLoadAction: MTLLoadAction::Load];
+                }
+            }
+            let depth_attachment = msg_send_![descriptor, depthAttachment];
+            msg_send_![depth_attachment, setLoadAction: MTLLoadAction::Load];
+            let stencil_attachment = msg_send_![descriptor, stencilAttachment];
+            msg_send_![stencil_attachment, setLoadAction: MTLLoadAction::Load];
+
+            let render_encoder = msg_send_![
+                self.command_buffer.unwrap(),
+                renderCommandEncoderWithDescriptor: descriptor
+            ];
+            self.render_encoder = Some(render_encoder);
+        }
+
+        // the state lives on the encoder and has to be applied again
+        if let Some(pipeline) = self.current_pipeline {
+            self.apply_pipeline(&pipeline);
+        }
+        if let Some((vertex_buffers, index_buffer, textures)) = self.current_bindings.take() {
+            self.apply_bindings_from_slice(&vertex_buffers, index_buffer, &textures);
+        }
+        if let Some((buffer, offset)) = self.current_uniforms {
+            let render_encoder = self.render_encoder.unwrap();
+            unsafe {
+                msg_send_![render_encoder, setVertexBuffer:buffer offset:offset atIndex:0];
+                msg_send_![render_encoder, setFragmentBuffer:buffer offset:offset atIndex:0];
+            }
+        }
+        if let Some((x, y, w, h)) = self.current_viewport {
+            self.apply_viewport(x, y, w, h);
+        }
+        if let Some((x, y, w, h)) = self.current_scissor {
+            self.apply_scissor_rect(x, y, w, h);
+        }
+    }
+    fn frame_stats(&self) -> FrameStats {
+        self.last_frame_stats
+    }
+    fn retain_resources(&mut self, _retain: bool) {
+        // metal devices are never lost the same way GL contexts are
+    }
+    fn restore_resources(&mut self) {}
+    fn reset_state_cache(&mut self) {
+        // metal state lives in encoders, there is nothing cached across them
+    }
+    fn texture_set_label(&mut self, texture: TextureId, label: &str) {
+        let texture = self.textures.get(texture);
+        unsafe {
+            msg_send_![texture.texture, setLabel: apple_util::str_to_nsstring(label)];
+        }
+    }
+    fn buffer_set_label(&mut self, buffer: BufferId, label: &str) {
+        let buffer = &self.buffers[buffer.0];
+        unsafe {
+            for buffer in &buffer.raw {
+                msg_send_![*buffer, setLabel: apple_util::str_to_nsstring(label)];
+            }
+        }
+    }
+    fn shader_set_label(&mut self, shader: ShaderId, label: &str) {
+        let shader = &self.shaders[shader.0];
+        unsafe {
+            msg_send_![shader.vertex_function, setLabel: apple_util::str_to_nsstring(label)];
+            msg_send_![shader.fragment_function, setLabel: apple_util::str_to_nsstring(label)];
+        }
+    }
+    fn push_debug_group(&mut self, name: &str) {
+        unsafe {
+            if self.command_buffer.is_none() {
+                self.command_buffer = Some(msg_send![self.command_queue, commandBuffer]);
+            }
+            msg_send_![
+                self.command_buffer.unwrap(),
+                pushDebugGroup: apple_util::str_to_nsstring(name)
+            ];
+        }
+    }
+    fn pop_debug_group(&mut self) {
+        if let Some(command_buffer) = self.command_buffer {
+            unsafe {
+                msg_send_![command_buffer, popDebugGroup];
+            }
+        }
+    }
+
     fn delete_shader(&mut self, shader: ShaderId) {
         // TODO: place holder
     }
     fn delete_pipeline(&mut self, pipeline: Pipeline) {
         // TODO: place holder
     }
+    fn pipeline_set_attachment_blend(
+        &mut self,
+        pipeline: Pipeline,
+        attachment: usize,
+        color_blend: Option<BlendState>,
+        alpha_blend: Option<BlendState>,
+    ) {
+        if color_blend.is_none() && alpha_blend.is_some() {
+            panic!("AlphaBlend without ColorBlend");
+        }
+        let pipeline = &mut self.pipelines[pipeline.0];
+        unsafe {
+            let color_attachments = msg_send_![pipeline.descriptor, colorAttachments];
+            let color_attachment =
+                msg_send_![color_attachments, objectAtIndexedSubscript: attachment];
+            set_attachment_blend(color_attachment, color_blend, alpha_blend);
+
+            // pipeline states are immutable, build a new one
+            msg_send_![pipeline.pipeline_state, release];
+            pipeline.pipeline_state = new_pipeline_state(self.device, pipeline.descriptor);
+        }
+    }
 
     fn commit_frame(&mut self) {
         unsafe {
@@ -1270,6 +1820,7 @@ impl RenderingBackend for MetalContext {
         self.current_ub_offset = 0;
         self.current_pipeline = None;
         self.command_buffer = None;
+        self.last_frame_stats = self.stats.take();
         if (self.current_frame_index + 1) >= 3 {
             self.current_frame_index = 0;
         }
@@ -0,0 +1,316 @@
+use super::{GlslVersion, ShaderError, ShaderOptions};
+
+// protection against "a.glsl" including "b.glsl" including "a.glsl"
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Apply defines and resolve includes for a single shader stage.
+/// With `version` being None(metal) the source is left without a version directive.
+pub(crate) fn preprocess(
+    source: &str,
+    options: &ShaderOptions,
+    version: Option<GlslVersion>,
+) -> Result<String, ShaderError> {
+    let version_line = source
+        .lines()
+        .find(|line| line.trim_start().starts_with("#version"))
+        .map(|line| line.trim().to_string());
+
+    // the defines and the #line rule follow the version actually used
+    let number = match version_line.as_deref().and_then(parse_version) {
+        Some(number) => Some(number),
+        None => version.map(|version| {
+            let es = version == GlslVersion::V300Es || version == GlslVersion::V100;
+            (version.number(), es)
+        }),
+    };
+    let mut lines = LineDirectives {
+        // before glsl 3.30 and glsl es 3.00 #line N meant "the next line is N + 1"
+        zero_based: match number {
+            Some((number, false)) => number < 330,
+            Some((number, true)) => number < 300,
+            None => false,
+        },
+        // MSL takes a file name instead of the source string number
+        numbered: number.is_some(),
+        includes: 0,
+    };
+
+    let mut body = String::new();
+    let mut version_skipped = false;
+    for (n, line) in source.lines().enumerate() {
+        if !version_skipped && line.trim_start().starts_with("#version") {
+            version_skipped = true;
+            // keep the line count intact for error messages
+            body.push('\n');
+            continue;
+        }
+        resolve_line(line, n + 1, 0, options, 0, &mut lines, &mut body)?;
+    }
+
+    let mut res = String::new();
+    match (version_line, version) {
+        (Some(version_line), _) => {
+            res.push_str(&version_line);
+            res.push('\n');
+        }
+        (None, Some(version)) => {
+            res.push_str(version.directive());
+            res.push('\n');
+        }
+        _ => {}
+    }
+    if let Some((number, es)) = number {
+        res.push_str(&format!("#define MQ_GLSL_VERSION {}\n", number));
+        if es {
+            res.push_str("#define MQ_GLSL_ES 1\n");
+        }
+    }
+    for (name, value) in options.defines {
+        res.push_str(&format!("#define {} {}\n", name, value));
+    }
+
+    res.push_str(if lines.zero_based {
+        "#line 0\n"
+    } else {
+        "#line 1\n"
+    });
+    res.push_str(&body);
+
+    Ok(res)
+}
+
+/// `#line` directives around the inlined includes.
+struct LineDirectives {
+    zero_based: bool,
+    numbered: bool,
+    /// Includes inlined so far, the next one gets `includes + 1` as its
+    /// source string number.
+    includes: usize,
+}
+
+impl LineDirectives {
+    /// Make `next_line` of the `source` string the line after the directive.
+    fn directive(&self, next_line: usize, source: usize) -> String {
+        let line = if self.zero_based {
+            next_line - 1
+        } else {
+            next_line
+        };
+        if self.numbered {
+            format!("#line {} {}\n", line, source)
+        } else {
+            format!("#line {}\n", line)
+        }
+    }
+}
+
+/// The number of a "#version 300 es" like line and whether it is glsl es.
+fn parse_version(line: &str) -> Option<(u32, bool)> {
+    let mut words = line.trim().strip_prefix("#version")?.split_whitespace();
+    let number = words.next()?.parse().ok()?;
+    // glsl 100 only exists as glsl es
+    let es = words.next() == Some("es") || number == 100;
+    Some((number, es))
+}
+
+/// `line` is the `line_number` line of the `source` string.
+fn resolve_line(
+    line: &str,
+    line_number: usize,
+    source: usize,
+    options: &ShaderOptions,
+    depth: usize,
+    lines: &mut LineDirectives,
+    res: &mut String,
+) -> Result<(), ShaderError> {
+    let directive = match line.trim_start().strip_prefix("#include") {
+        Some(directive) => directive.trim(),
+        None => {
+            res.push_str(line);
+            res.push('\n');
+            return Ok(());
+        }
+    };
+
+    // "name" includes should always be resolved, <name> are passed through
+    // when the resolver does not know them, so MSL's <metal_stdlib> keeps working
+    let (name, system) = if let Some(name) = directive
+        .strip_prefix('"')
+        .and_then(|name| name.strip_suffix('"'))
+    {
+        (name, false)
+    } else if let Some(name) = directive
+        .strip_prefix('<')
+        .and_then(|name| name.strip_suffix('>'))
+    {
+        (name, true)
+    } else {
+        return Err(ShaderError::PreprocessorError(format!(
+            "Malformed {}",
+            line.trim()
+        )));
+    };
+
+    if depth >= MAX_INCLUDE_DEPTH {
+        return Err(ShaderError::PreprocessorError(format!(
+            "Include depth limit reached with \"{}\", recursive include?",
+            name
+        )));
+    }
+    let included = match options.include_resolver.and_then(|resolver| resolver(name)) {
+        Some(included) => included,
+        None if system => {
+            res.push_str(line);
+            res.push('\n');
+            return Ok(());
+        }
+        None => {
+            return Err(ShaderError::PreprocessorError(format!(
+                "Include \"{}\" not found",
+                name
+            )))
+        }
+    };
+
+    lines.includes += 1;
+    let included_source = lines.includes;
+    res.push_str(&lines.directive(1, included_source));
+    for (n, line) in included.lines().enumerate() {
+        resolve_line(line, n + 1, included_source, options, depth + 1, lines, res)?;
+    }
+    res.push_str(&lines.directive(line_number + 1, source));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options<'a>(
+        defines: &'a [(&'a str, &'a str)],
+        include_resolver: Option<&'a dyn Fn(&str) -> Option<String>>,
+    ) -> ShaderOptions<'a> {
+        ShaderOptions {
+            defines,
+            include_resolver,
+            version: None,
+        }
+    }
+
+    #[test]
+    fn version_from_backend() {
+        let res = preprocess(
+            "void main() {}",
+            &options(&[], None),
+            Some(GlslVersion::V100),
+        );
+        assert_eq!(
+            res.unwrap(),
+            "#version 100\n#define MQ_GLSL_VERSION 100\n#define MQ_GLSL_ES 1\n#line 0\nvoid main() {}\n"
+        );
+
+        let res = preprocess(
+            "void main() {}",
+            &options(&[], None),
+            Some(GlslVersion::V330),
+        );
+        assert_eq!(
+            res.unwrap(),
+            "#version 330\n#define MQ_GLSL_VERSION 330\n#line 1\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn version_from_source() {
+        let source = "#version 300 es\nvoid main() {}";
+        let res = preprocess(source, &options(&[], None), Some(GlslVersion::V100));
+        assert_eq!(
+            res.unwrap(),
+            "#version 300 es\n#define MQ_GLSL_VERSION 300\n#define MQ_GLSL_ES 1\n#line 1\n\nvoid main() {}\n"
+        );
+
+        let source = "#version 130\nvoid main() {}";
+        let res = preprocess(source, &options(&[], None), Some(GlslVersion::V330));
+        assert_eq!(
+            res.unwrap(),
+            "#version 130\n#define MQ_GLSL_VERSION 130\n#line 0\n\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn no_version_on_metal() {
+        let res = preprocess("kernel void f() {}", &options(&[], None), None);
+        assert_eq!(res.unwrap(), "#line 1\nkernel void f() {}\n");
+    }
+
+    #[test]
+    fn defines() {
+        let defines = [("LIGHTS", "4"), ("SHADOWS", "1")];
+        let res = preprocess(
+            "void main() {}",
+            &options(&defines, None),
+            Some(GlslVersion::V330),
+        );
+        assert_eq!(
+            res.unwrap(),
+            "#version 330\n#define MQ_GLSL_VERSION 330\n#define LIGHTS 4\n#define SHADOWS 1\n#line 1\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn includes() {
+        let resolver = |name: &str| match name {
+            "a.glsl" => Some("#include \"b.glsl\"\nfloat a;".to_string()),
+            "b.glsl" => Some("float b;".to_string()),
+            _ => None,
+        };
+        let source = "#include \"a.glsl\"\n#include <metal_stdlib>\nvoid main() {}";
+        let res = preprocess(source, &options(&[], Some(&resolver)), None);
+        assert_eq!(
+            res.unwrap(),
+            "#line 1\n#line 1\n#line 1\nfloat b;\n#line 2\nfloat a;\n#line 2\n#include <metal_stdlib>\nvoid main() {}\n"
+        );
+
+        let res = preprocess("#include \"c.glsl\"", &options(&[], Some(&resolver)), None);
+        assert!(matches!(res, Err(ShaderError::PreprocessorError(_))));
+
+        let res = preprocess("#include c.glsl", &options(&[], Some(&resolver)), None);
+        assert!(matches!(res, Err(ShaderError::PreprocessorError(_))));
+    }
+
+    #[test]
+    fn include_line_directives() {
+        let resolver = |_: &str| Some("float b;".to_string());
+        let source = "float a;\n#include \"b.glsl\"\n#include \"b.glsl\"\nvoid main() {}";
+        let res = preprocess(
+            source,
+            &options(&[], Some(&resolver)),
+            Some(GlslVersion::V330),
+        );
+        assert_eq!(
+            res.unwrap(),
+            "#version 330\n#define MQ_GLSL_VERSION 330\n#line 1\nfloat a;\n#line 1 1\nfloat b;\n#line 3 0\n#line 1 2\nfloat b;\n#line 4 0\nvoid main() {}\n"
+        );
+
+        let res = preprocess(
+            source,
+            &options(&[], Some(&resolver)),
+            Some(GlslVersion::V100),
+        );
+        assert_eq!(
+            res.unwrap(),
+            "#version 100\n#define MQ_GLSL_VERSION 100\n#define MQ_GLSL_ES 1\n#line 0\nfloat a;\n#line 0 1\nfloat b;\n#line 2 0\n#line 0 2\nfloat b;\n#line 3 0\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn recursive_include() {
+        let resolver = |_: &str| Some("#include \"self.glsl\"".to_string());
+        let res = preprocess(
+            "#include \"self.glsl\"",
+            &options(&[], Some(&resolver)),
+            None,
+        );
+        assert!(matches!(res, Err(ShaderError::PreprocessorError(_))));
+    }
+}
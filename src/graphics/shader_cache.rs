@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+
+use super::*;
+
+/// Simple on-disk cache of linked programs, one file per shader.
+/// Files are keyed by the GPU, the driver version string and the shader
+/// sources, so a driver update, another GPU or a change in the shader just
+/// leads to a recompilation.
+///
+/// ```ignore
+/// let cache = ShaderCache::new("shader_cache");
+/// let shader = cache.load_or_compile(&mut *ctx, source, meta)?;
+/// ```
+pub struct ShaderCache {
+    path: PathBuf,
+}
+
+impl ShaderCache {
+    pub fn new(path: impl Into<PathBuf>) -> ShaderCache {
+        ShaderCache { path: path.into() }
+    }
+
+    /// Load the shader from the cache, or compile and store it.
+    /// Without `features.program_binary` this is just `new_shader`.
+    pub fn load_or_compile(
+        &self,
+        ctx: &mut dyn RenderingBackend,
+        source: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        let info = ctx.info();
+        if !info.features.program_binary {
+            return ctx.new_shader(source, meta);
+        }
+
+        let file = self
+            .path
+            .join(format!("{:016x}.bin", cache_key(&info, &source)));
+        if let Some(binary) = std::fs::read(&file).ok().and_then(|data| decode(&data)) {
+            if let Ok(shader) = ctx.new_shader_from_binary(&binary, meta.clone()) {
+                return Ok(shader);
+            }
+        }
+
+        let shader = ctx.new_shader(source, meta)?;
+        if let Some(binary) = ctx.shader_binary(shader) {
+            // the cache is only an optimization, failing to write it is fine
+            let _ = std::fs::create_dir_all(&self.path);
+            let _ = std::fs::write(&file, encode(&binary));
+        }
+        Ok(shader)
+    }
+}
+
+// FNV-1a, DefaultHasher may change between Rust releases and the file names
+// should not
+struct Fnv(u64);
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    // length prefixed, so ("ab", "c") and ("a", "bc") differ
+    fn write_str(&mut self, s: &str) {
+        self.write(&(s.len() as u64).to_le_bytes());
+        self.write(s.as_bytes());
+    }
+}
+
+fn cache_key(info: &ContextInfo, source: &ShaderSource) -> u64 {
+    let mut hasher = Fnv(0xcbf29ce484222325);
+    hasher.write_str(&info.vendor);
+    hasher.write_str(&info.renderer);
+    hasher.write_str(&info.gl_version_string);
+    match source {
+        ShaderSource::Glsl { vertex, fragment } => {
+            hasher.write(&[0]);
+            hasher.write_str(vertex);
+            hasher.write_str(fragment);
+        }
+        ShaderSource::GlslGeometry {
+            vertex,
+            geometry,
+            fragment,
+        } => {
+            hasher.write(&[1]);
+            hasher.write_str(vertex);
+            hasher.write_str(geometry);
+            hasher.write_str(fragment);
+        }
+        ShaderSource::Msl { program } => {
+            hasher.write(&[2]);
+            hasher.write_str(program);
+        }
+    }
+    hasher.0
+}
+
+// format as little-endian u32, followed by the binary itself
+fn encode(binary: &ShaderBinary) -> Vec<u8> {
+    let mut data = binary.format.to_le_bytes().to_vec();
+    data.extend_from_slice(&binary.data);
+    data
+}
+
+fn decode(data: &[u8]) -> Option<ShaderBinary> {
+    if data.len() <= 4 {
+        return None;
+    }
+    let mut format = [0; 4];
+    format.copy_from_slice(&data[0..4]);
+    Some(ShaderBinary {
+        format: u32::from_le_bytes(format),
+        data: data[4..].to_vec(),
+    })
+}
@@ -0,0 +1,111 @@
+use std::sync::{Arc, Mutex};
+
+use super::*;
+
+type Upload = Box<dyn FnOnce(&mut dyn RenderingBackend) + Send>;
+
+/// Handle of a resource created with `UploadQueue`, becomes valid once the
+/// queue is processed on the main thread.
+pub struct Pending<T>(Arc<Mutex<Option<T>>>);
+
+impl<T: Copy> Pending<T> {
+    /// None until `process_uploads` created the resource.
+    pub fn get(&self) -> Option<T> {
+        *self.0.lock().unwrap()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.get().is_some()
+    }
+}
+
+impl<T> Clone for Pending<T> {
+    fn clone(&self) -> Pending<T> {
+        Pending(self.0.clone())
+    }
+}
+
+/// GPU resources creation from any thread. The data is moved into the queue,
+/// GPU calls happen later on the main thread within `ctx.process_uploads`.
+/// ```ignore
+/// let queue = UploadQueue::new();
+/// let loader = queue.clone();
+/// let tiles = std::thread::spawn(move || {
+///     let (bytes, params) = decode_png("tiles.png");
+///     loader.new_texture(bytes, params)
+/// })
+/// .join()
+/// .unwrap();
+/// ...
+/// // on the main thread, usually once per frame
+/// ctx.process_uploads(&queue);
+/// if let Some(tiles) = tiles.get() {
+///     ..
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct UploadQueue {
+    uploads: Arc<Mutex<Vec<Upload>>>,
+}
+
+impl UploadQueue {
+    pub fn new() -> UploadQueue {
+        UploadQueue::default()
+    }
+
+    /// `bytes` are the same as `TextureSource::Bytes` for `new_texture`.
+    pub fn new_texture(&self, bytes: Vec<u8>, params: TextureParams) -> Pending<TextureId> {
+        self.push_pending(move |ctx| {
+            ctx.new_texture(TextureAccess::Static, TextureSource::Bytes(&bytes), params)
+        })
+    }
+
+    pub fn new_buffer<T: Send + 'static>(
+        &self,
+        type_: BufferType,
+        usage: BufferUsage,
+        data: Vec<T>,
+    ) -> Pending<BufferId> {
+        self.push_pending(move |ctx| ctx.new_buffer(type_, usage, BufferSource::slice(&data)))
+    }
+
+    pub fn texture_update(&self, texture: TextureId, bytes: Vec<u8>) {
+        self.push(move |ctx| ctx.texture_update(texture, &bytes));
+    }
+
+    pub fn buffer_update<T: Send + 'static>(&self, buffer: BufferId, data: Vec<T>) {
+        self.push(move |ctx| ctx.buffer_update(buffer, BufferSource::slice(&data)));
+    }
+
+    /// Any other work that needs the context, executed in the queue order.
+    pub fn push(&self, f: impl FnOnce(&mut dyn RenderingBackend) + Send + 'static) {
+        self.uploads.lock().unwrap().push(Box::new(f));
+    }
+
+    pub fn len(&self) -> usize {
+        self.uploads.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn push_pending<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut dyn RenderingBackend) -> T + Send + 'static,
+    ) -> Pending<T> {
+        let pending = Pending(Arc::new(Mutex::new(None)));
+        let slot = pending.0.clone();
+        self.push(move |ctx| *slot.lock().unwrap() = Some(f(ctx)));
+        pending
+    }
+
+    pub(crate) fn process(&self, ctx: &mut dyn RenderingBackend) {
+        // the lock is released before running uploads, so uploads may queue
+        // more work or other threads may keep pushing in the meantime
+        let uploads = std::mem::take(&mut *self.uploads.lock().unwrap());
+        for upload in uploads {
+            upload(ctx);
+        }
+    }
+}
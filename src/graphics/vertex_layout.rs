@@ -0,0 +1,151 @@
+use super::*;
+
+/// Vertex struct description for `ctx.new_pipeline_with_layout`.
+/// Usually implemented with `#[derive(VertexLayout)]` ("derive" feature),
+/// which lists every field of a `#[repr(C)]` struct in declaration order.
+pub trait VertexLayout: Sized {
+    fn attributes(buffer_index: usize) -> Vec<VertexAttribute>;
+
+    fn buffer_layout() -> BufferLayout {
+        BufferLayout {
+            stride: std::mem::size_of::<Self>() as i32,
+            ..Default::default()
+        }
+    }
+}
+
+/// Rust types that could be used as a vertex struct field with
+/// `#[derive(VertexLayout)]`. Other types need `#[vertex(format = ..)]`.
+pub trait VertexField {
+    const FORMAT: VertexFormat;
+}
+
+macro_rules! vertex_field {
+    ($($ty:ty => $format:ident,)*) => {
+        $(impl VertexField for $ty {
+            const FORMAT: VertexFormat = VertexFormat::$format;
+        })*
+    };
+}
+
+vertex_field! {
+    f32 => Float1,
+    [f32; 1] => Float1,
+    [f32; 2] => Float2,
+    [f32; 3] => Float3,
+    [f32; 4] => Float4,
+    u8 => Byte1,
+    [u8; 1] => Byte1,
+    [u8; 2] => Byte2,
+    [u8; 3] => Byte3,
+    [u8; 4] => Byte4,
+    u16 => Short1,
+    [u16; 1] => Short1,
+    [u16; 2] => Short2,
+    [u16; 3] => Short3,
+    [u16; 4] => Short4,
+    u32 => Int1,
+    [u32; 1] => Int1,
+    [u32; 2] => Int2,
+    [u32; 3] => Int3,
+    [u32; 4] => Int4,
+    [f32; 16] => Mat4,
+    [[f32; 4]; 4] => Mat4,
+}
+
+// miniquad attributes are tightly packed, padding would shift all the
+// following fields
+fn check_padding<V: VertexLayout>(attributes: &[VertexAttribute]) {
+    let size: i32 = attributes.iter().map(|attr| attr.format.size_bytes()).sum();
+    assert!(
+        size as usize == std::mem::size_of::<V>(),
+        "{} has padding between fields: attributes take {} bytes, the struct is {} bytes",
+        std::any::type_name::<V>(),
+        size,
+        std::mem::size_of::<V>()
+    );
+}
+
+pub(crate) fn check_layout<V: VertexLayout>(ctx: &dyn RenderingBackend, shader: ShaderId) {
+    let attributes = V::attributes(0);
+    let name = std::any::type_name::<V>();
+    check_padding::<V>(&attributes);
+
+    // metal has no reflection
+    let reflection = match ctx.shader_reflect(shader) {
+        Ok(reflection) => reflection,
+        Err(_) => return,
+    };
+    for reflected in reflection.attributes {
+        // builtins, like gl_VertexID
+        if reflected.location == -1 {
+            continue;
+        }
+        let attr = attributes
+            .iter()
+            .find(|attr| attr.name == reflected.name)
+            .unwrap_or_else(|| panic!("{} has no field for attribute {}", name, reflected.name));
+        if let Some(format) = reflected.format {
+            assert!(
+                format.components() == attr.format.components(),
+                "{}: attribute {} is {:?} in the shader, but {:?} in the vertex",
+                name,
+                attr.name,
+                format,
+                attr.format
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Packed {
+        pos: [f32; 2],
+        color: [u8; 4],
+    }
+
+    impl VertexLayout for Packed {
+        fn attributes(buffer_index: usize) -> Vec<VertexAttribute> {
+            vec![
+                VertexAttribute::with_buffer("pos", VertexFormat::Float2, buffer_index),
+                VertexAttribute::with_buffer("color", VertexFormat::Byte4, buffer_index),
+            ]
+        }
+    }
+
+    // a byte of padding after color
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Padded {
+        color: [u8; 3],
+        pos: [f32; 2],
+    }
+
+    impl VertexLayout for Padded {
+        fn attributes(buffer_index: usize) -> Vec<VertexAttribute> {
+            vec![
+                VertexAttribute::with_buffer("color", VertexFormat::Byte3, buffer_index),
+                VertexAttribute::with_buffer("pos", VertexFormat::Float2, buffer_index),
+            ]
+        }
+    }
+
+    #[test]
+    fn packed() {
+        check_padding::<Packed>(&Packed::attributes(0));
+        assert_eq!(Packed::buffer_layout().stride, 12);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "has padding between fields: attributes take 11 bytes, the struct is 12 bytes"
+    )]
+    fn padded() {
+        check_padding::<Padded>(&Padded::attributes(0));
+    }
+}
@@ -16,6 +16,9 @@ pub use event::*;
 
 pub use graphics::*;
 
+#[cfg(feature = "derive")]
+pub use miniquad_derive::VertexLayout;
+
 mod default_icon;
 
 pub use native::gl;
@@ -46,6 +49,10 @@ impl<T> ResourceManager<T> {
         // Let it crash if the resource is not found
         self.resources.remove(&id).unwrap();
     }
+
+    pub fn ids(&self) -> Vec<usize> {
+        self.resources.keys().copied().collect()
+    }
 }
 
 impl<T> Index<usize> for ResourceManager<T> {
@@ -82,12 +89,33 @@ pub mod date {
 
 pub type Context = dyn RenderingBackend;
 
+#[cfg(target_os = "linux")]
+impl Context {
+    /// Rendering context without any window, for tests and CI.
+    /// Creates an EGL pbuffer surface of the given size, so it works without
+    /// a display server, as long as libEGL.so is available, Mesa's llvmpipe
+    /// is good enough.
+    ///
+    /// The context is current on the calling thread only and lives till the
+    /// end of the process. Several headless contexts, one per thread, work,
+    /// but the window state is per process: `window::screen_size` and the
+    /// default pass use the size of the first headless context created.
+    /// With more than one, render into `new_render_pass` targets only.
+    /// To get the rendered image, render into a `new_render_pass` texture
+    /// and read it back with `texture_read_pixels`.
+    pub fn headless(width: i32, height: i32) -> Option<Box<Context>> {
+        native::headless::init(width, height)?;
+        Some(Box::new(GlContext::new()))
+    }
+}
+
 use std::sync::{Mutex, OnceLock};
 
 static NATIVE_DISPLAY: OnceLock<Mutex<native::NativeDisplayData>> = OnceLock::new();
 
+// the display is set once per process, the next calls are ignored
 fn set_display(display: native::NativeDisplayData) {
-    NATIVE_DISPLAY.set(Mutex::new(display));
+    let _ = NATIVE_DISPLAY.set(Mutex::new(display));
 }
 fn native_display() -> &'static Mutex<native::NativeDisplayData> {
     NATIVE_DISPLAY
@@ -130,6 +158,7 @@ pub mod window {
     }
 
     /// The dpi scaling factor (window pixels to framebuffer pixels)
+    /// `EventHandler::dpi_scale_changed_event` tells when it changes.
     /// NOTE: [High DPI Rendering](../conf/index.html#high-dpi-rendering)
     pub fn dpi_scale() -> f32 {
         let d = native_display().lock().unwrap();
@@ -150,6 +179,8 @@ pub mod window {
     /// Window might not be actually closed right away (exit(0) might not
     /// happen in the order_quit implmentation) and execution might continue for some time after
     /// But the window is going to be inevitably closed at some point.
+    /// The event handler is dropped before the GL context goes away, so
+    /// its Drop can still free the GL resources.
     pub fn order_quit() {
         let mut d = native_display().lock().unwrap();
         d.quit_ordered = true;
@@ -179,15 +210,34 @@ pub mod window {
         let mut d = native_display().lock().unwrap();
         d.quit_requested = false;
     }
-    /// Capture mouse cursor to the current window
-    /// On WASM this will automatically hide cursor
-    /// On desktop this will bound cursor to windows border
+
+    /// Keeps the activity from finishing on the back button or gesture of
+    /// Android, from inside `EventHandler::back_requested_event`, to go back
+    /// to the menu instead.
+    pub fn cancel_back() {
+        let mut d = native_display().lock().unwrap();
+        d.back_requested = false;
+    }
+    /// Capture mouse cursor to the current window, see `CursorGrabMode`.
+    /// `true` is `CursorGrabMode::Confined`, `false` is `CursorGrabMode::None`.
     /// NOTICE: on desktop cursor will not be automatically released after window lost focus
     ///         so set_cursor_grab(false) on window's focus lost is recommended.
     /// TODO: implement window focus events
-    pub fn set_cursor_grab(grab: bool) {
+    pub fn set_cursor_grab(mode: impl Into<CursorGrabMode>) {
         let mut d = native_display().lock().unwrap();
-        d.native_requests.send(native::Request::SetCursorGrab(grab));
+        d.native_requests
+            .send(native::Request::SetCursorGrab(mode.into()));
+    }
+
+    /// Move the mouse cursor to `x`, `y` in window coordinates, the same
+    /// as in `mouse_motion_event`.
+    ///
+    /// Windows, X11 and macOS. A no-op on Wayland, wasm and mobile, where
+    /// the cursor position can't be changed by the application.
+    pub fn set_mouse_position(x: f32, y: f32) {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetMousePosition { x, y });
     }
 
     /// Show or hide the mouse cursor
@@ -203,6 +253,26 @@ pub mod window {
             .send(native::Request::SetMouseCursor(cursor_icon));
     }
 
+    /// Set the mouse cursor to a `width` x `height` image of RGBA pixels in
+    /// row-major order, `hotspot` is the pixel that is the actual pointer position.
+    /// `set_mouse_cursor` switches back to the system cursors.
+    ///
+    /// Windows, X11 (with libXcursor), Wayland, macOS and wasm. 32x32 and smaller
+    /// images are the safest choice, bigger ones could be cropped or ignored
+    /// by some systems.
+    pub fn set_mouse_cursor_from_rgba(rgba: &[u8], width: u32, height: u32, hotspot: (u32, u32)) {
+        assert_eq!(rgba.len(), (width * height * 4) as usize);
+        assert!(hotspot.0 < width && hotspot.1 < height);
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetCustomMouseCursor {
+                rgba: rgba.to_vec(),
+                width,
+                height,
+                hotspot,
+            });
+    }
+
     /// Set the application's window size.
     pub fn set_window_size(new_width: u32, new_height: u32) {
         let mut d = native_display().lock().unwrap();
@@ -212,23 +282,322 @@ pub mod window {
         });
     }
 
+    /// Show or hide the title bar and the borders, without recreating the
+    /// window. The content size stays the same. Windows, X11, Wayland and
+    /// macOS; on Wayland it is up to the compositor, or the fallback
+    /// decorations from `conf.platform.wayland_use_fallback_decorations`.
+    pub fn set_decorations(decorations: bool) {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetDecorations(decorations));
+    }
+
+    /// Change `conf.window_resizable`. A not resizable window keeps its
+    /// current size, whatever `set_min_size` and `set_max_size` say.
+    /// Windows, X11, Wayland and macOS.
+    pub fn set_resizable(resizable: bool) {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetResizable(resizable));
+    }
+
+    /// Keep the window above the other windows, even when it is not focused.
+    /// Windows, X11 and macOS, Wayland has no way to ask for it.
+    pub fn set_always_on_top(always_on_top: bool) {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetAlwaysOnTop(always_on_top));
+    }
+
+    /// The opacity of the whole window, decorations included, from 0 for
+    /// invisible to 1 for opaque. Unlike `Conf::transparent` it needs no alpha
+    /// in the framebuffer. Windows, X11 with a compositor and macOS.
+    pub fn set_opacity(opacity: f32) {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetOpacity(opacity.clamp(0., 1.)));
+    }
+
+    /// The smallest size the user can resize the window to, `None` for no
+    /// limit. Ignored on wasm and mobile.
+    pub fn set_min_size(size: Option<(u32, u32)>) {
+        let mut d = native_display().lock().unwrap();
+        d.size_constraints.min = size;
+        let constraints = d.size_constraints;
+        d.native_requests
+            .send(native::Request::SetSizeConstraints(constraints));
+    }
+
+    /// The biggest size the user can resize the window to, `None` for no
+    /// limit. Ignored on wasm and mobile.
+    pub fn set_max_size(size: Option<(u32, u32)>) {
+        let mut d = native_display().lock().unwrap();
+        d.size_constraints.max = size;
+        let constraints = d.size_constraints;
+        d.native_requests
+            .send(native::Request::SetSizeConstraints(constraints));
+    }
+
+    /// Keep the window content at `width:height` while the user resizes
+    /// the window, `None` to resize freely. Does not resize the window by
+    /// itself. Ignored on wasm, mobile and Wayland, which has no way to ask
+    /// the compositor for it.
+    pub fn set_aspect_ratio(ratio: Option<(u32, u32)>) {
+        let mut d = native_display().lock().unwrap();
+        d.size_constraints.aspect = ratio;
+        let constraints = d.size_constraints;
+        d.native_requests
+            .send(native::Request::SetSizeConstraints(constraints));
+    }
+
+    /// Replace the icon from `Conf::icon` with a `width` x `height` image of
+    /// RGBA pixels in row-major order. Windows, X11 and macOS only.
+    ///
+    /// Wayland has no way to set the icon from the application, the compositor
+    /// takes it from the .desktop file matching the application id.
+    pub fn set_window_icon(rgba: &[u8], width: u32, height: u32) {
+        assert_eq!(rgba.len(), (width * height * 4) as usize);
+        let mut d = native_display().lock().unwrap();
+        d.native_requests.send(native::Request::SetWindowIcon {
+            rgba: rgba.to_vec(),
+            width,
+            height,
+        });
+    }
+
     pub fn set_window_position(new_x: u32, new_y: u32) {
         let mut d = native_display().lock().unwrap();
         d.native_requests.send(native::Request::SetWindowPosition { new_x, new_y });
     }
 
-    /// Get the position of the window.
+    /// Get the position of the window, see `EventHandler::window_moved_event`.
+    /// Always `(0, 0)` on Wayland, wasm and mobile.
     pub fn get_window_position() -> (u32, u32) {
         let mut d = native_display().lock().unwrap();
         d.screen_position
     }
 
+    /// All the connected monitors, the primary one first. On Wayland there
+    /// is no primary monitor and the order is up to the compositor.
+    /// Empty on wasm and mobile.
+    pub fn monitors() -> Vec<Monitor> {
+        let d = native_display().lock().unwrap();
+        d.monitors.clone()
+    }
+
+    /// Index in `monitors()` of the monitor the window is on, the one with
+    /// the biggest part of the window when it spans several.
+    pub fn current_monitor() -> Option<usize> {
+        let d = native_display().lock().unwrap();
+        d.current_monitor
+    }
+
     pub fn set_fullscreen(fullscreen: bool) {
         let mut d = native_display().lock().unwrap();
         d.native_requests
             .send(native::Request::SetFullscreen(fullscreen));
     }
 
+    /// Fullscreen with the status and navigation bars hidden until a swipe
+    /// from the edge of the screen brings them over the window for a few
+    /// seconds. The window is laid out around the cutout by
+    /// `conf.platform.android_cutout_mode`. On iOS the status bar is hidden
+    /// and the home indicator fades out, a first swipe from an edge does not
+    /// leave the game. Elsewhere it is `set_fullscreen`.
+    pub fn set_fullscreen_immersive(immersive: bool) {
+        let mut d = native_display().lock().unwrap();
+        #[cfg(any(target_os = "android", target_os = "ios"))]
+        d.native_requests
+            .send(native::Request::SetFullscreenImmersive(immersive));
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        d.native_requests
+            .send(native::Request::SetFullscreen(immersive));
+    }
+
+    /// The background of the status and navigation bars, as (r, g, b, a)
+    /// from 0 to 1, with dark icons on the light colors from Android 11.
+    /// Only works on Android.
+    pub fn set_system_bars_color(color: (f32, f32, f32, f32)) {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetSystemBarsColor(color));
+    }
+
+    /// Maximize the window, like its title bar button. Windows, X11, Wayland
+    /// and macOS, where the window is zoomed.
+    pub fn maximize() {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetWindowState(WindowState::Maximized));
+    }
+
+    /// Minimize (iconify) the window. Windows, X11, Wayland and macOS.
+    pub fn minimize() {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetWindowState(WindowState::Minimized));
+    }
+
+    /// Bring the window back from being minimized or maximized. A minimized
+    /// window that was maximized before goes back to its normal size too.
+    /// Windows, X11 and macOS; Wayland can unmaximize, but a minimized
+    /// window only comes back when the user brings it back.
+    pub fn restore() {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetWindowState(WindowState::Normal));
+    }
+
+    /// The state from the last `window_minimized_event`,
+    /// `window_maximized_event` or `window_restored_event`. Always
+    /// `WindowState::Normal` on wasm and mobile.
+    pub fn window_state() -> WindowState {
+        let d = native_display().lock().unwrap();
+        d.window_state
+    }
+
+    /// From the last `EventHandler::window_occluded`, false on the
+    /// platforms that can't tell.
+    pub fn occluded() -> bool {
+        let d = native_display().lock().unwrap();
+        d.occluded
+    }
+
+    /// Flash the taskbar button or bounce the dock icon, to tell a player
+    /// it is their turn while the window is in the background. Stops by
+    /// itself once the window is focused, does nothing if it already is.
+    /// FlashWindowEx on Windows, `_NET_WM_STATE_DEMANDS_ATTENTION` on X11,
+    /// xdg-activation on Wayland (both types are the same there) and
+    /// requestUserAttention on macOS. Nothing on mobile and web.
+    pub fn request_user_attention(attention: UserAttentionType) {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::RequestUserAttention(attention));
+    }
+
+    /// A progress bar over the taskbar button, for the long exports and
+    /// bakes, from 0 to 1. None removes it. Only works on Windows.
+    pub fn set_taskbar_progress(progress: Option<f32>) {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetTaskbarProgress(progress));
+    }
+
+    /// A small `width` x `height` image of RGBA pixels over a corner of the
+    /// taskbar button, 16x16 at 100% scale, like a status or a count.
+    /// `description` is for the screen readers. Only works on Windows.
+    pub fn set_taskbar_overlay(rgba: &[u8], width: u32, height: u32, description: &str) {
+        assert_eq!(rgba.len(), (width * height * 4) as usize);
+        let overlay = (rgba.to_vec(), width, height, description.to_owned());
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetTaskbarOverlay(Some(overlay)));
+    }
+
+    /// Removes the image of `set_taskbar_overlay`.
+    pub fn clear_taskbar_overlay() {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetTaskbarOverlay(None));
+    }
+
+    /// The battery and the power saving mode, `EventHandler::power_changed`
+    /// tells about the changes. Games can drop the frame rate with
+    /// `set_target_fps` on a low battery.
+    /// Windows, Linux (/sys/class/power_supply, without power_saver),
+    /// macOS, iOS, Android and web (the Battery Status API, without
+    /// power_saver, not on every browser); `PowerState::Unknown` elsewhere.
+    pub fn power_info() -> PowerInfo {
+        let d = native_display().lock().unwrap();
+        d.power_info
+    }
+
+    /// The light or dark appearance the OS is set to, `EventHandler::theme_changed`
+    /// tells about the changes. The apps use the same for their UI.
+    /// Windows 10 and newer, macOS 10.14 and newer, web (prefers-color-scheme)
+    /// and Linux (org.freedesktop.appearance of the XDG desktop portal);
+    /// `Theme::Light` elsewhere and when the desktop has no preference.
+    pub fn system_theme() -> Theme {
+        let d = native_display().lock().unwrap();
+        d.system_theme
+    }
+
+    /// Exclusive fullscreen on the `monitor`-th of `monitors()`, switched to
+    /// the `mode`, one of its `video_modes`. `set_fullscreen(false)` goes
+    /// back to the window and restores the monitor's mode, it is also
+    /// restored on exit and on panic.
+    ///
+    /// Windows, X11 (with XRandR) and macOS. Wayland has no video modes for
+    /// applications, the window goes borderless fullscreen on the monitor
+    /// with the mode left as is. Ignored on wasm and mobile.
+    pub fn set_exclusive_fullscreen(monitor: usize, mode: VideoMode) {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetExclusiveFullscreen { monitor, mode });
+    }
+
+    /// Change the swap interval set with `conf.platform.swap_interval`:
+    /// 0 is no vsync, 1 is vsync and -1 is adaptive vsync, vsync that lets
+    /// the late frames tear. Returns false, with nothing changed, if the
+    /// platform can't do the interval; -1 needs EXT_swap_control_tear,
+    /// not available with EGL and macOS. Not supported on wasm and iOS.
+    pub fn set_swap_interval(interval: i32) -> bool {
+        let mut d = native_display().lock().unwrap();
+        let supported = match interval {
+            0 | 1 => d.swap_control,
+            -1 => d.adaptive_vsync,
+            _ => false,
+        };
+        if supported {
+            d.native_requests
+                .send(native::Request::SetSwapInterval(interval));
+        }
+        supported
+    }
+
+    /// Limit the frame rate to `fps`, `None` to go back to running frames as
+    /// fast as the swap interval lets them. See `conf.max_fps`.
+    pub fn set_target_fps(fps: Option<u32>) {
+        let mut d = native_display().lock().unwrap();
+        d.target_fps = fps;
+    }
+
+    /// Change `conf.background_mode`.
+    pub fn set_background_mode(mode: conf::BackgroundMode) {
+        let mut d = native_display().lock().unwrap();
+        d.background_mode = mode;
+    }
+
+    /// With `conf.wait_for_events`, run `update` and `draw` once as soon as
+    /// possible, even without events. Works from any thread.
+    pub fn request_redraw() {
+        let mut d = native_display().lock().unwrap();
+        d.redraw_requested = true;
+        if let Some(waker) = &d.waker {
+            waker();
+        }
+    }
+
+    /// With `conf.wait_for_events`, run `update` and `draw` after `timeout`
+    /// at the latest, for animations and timers. Only the earliest of the
+    /// scheduled updates that are not done yet is kept.
+    pub fn schedule_update(timeout: std::time::Duration) {
+        let mut d = native_display().lock().unwrap();
+        let deadline = std::time::Instant::now() + timeout;
+        d.update_deadline = Some(d.update_deadline.map_or(deadline, |old| old.min(deadline)));
+        if let Some(waker) = &d.waker {
+            waker();
+        }
+    }
+
+    /// A handle for other threads to send `EventHandler::custom_event`s.
+    pub fn event_sender() -> EventSender {
+        let d = native_display().lock().unwrap();
+        EventSender {
+            tx: d.custom_events_tx.clone(),
+        }
+    }
+
     /// Get current OS clipboard value
     pub fn clipboard_get() -> Option<String> {
         let mut d = native_display().lock().unwrap();
@@ -240,10 +609,48 @@ pub mod window {
         let mut d = native_display().lock().unwrap();
         d.clipboard.set(data)
     }
+
+    /// Whether the OS clipboard has a value of the given format, that
+    /// `clipboard_get`, `clipboard_get_html` or `clipboard_get_image` could read.
+    pub fn clipboard_has(format: ClipboardFormat) -> bool {
+        let mut d = native_display().lock().unwrap();
+        d.clipboard.has(format)
+    }
+
+    /// Get html from OS clipboard, Windows, X11 and macOS only.
+    pub fn clipboard_get_html() -> Option<String> {
+        let mut d = native_display().lock().unwrap();
+        d.clipboard.get_html()
+    }
+
+    /// Save html to OS clipboard, along with the plain `text` for applications
+    /// that do not take html. Only the `text` is saved where html is not supported.
+    pub fn clipboard_set_html(html: &str, text: &str) {
+        let mut d = native_display().lock().unwrap();
+        d.clipboard.set_html(html, text)
+    }
+
+    /// Get an image from OS clipboard, Windows and macOS only.
+    pub fn clipboard_get_image() -> Option<ClipboardImage> {
+        let mut d = native_display().lock().unwrap();
+        d.clipboard.get_image()
+    }
+
+    /// Save a `width` x `height` image of RGBA pixels in row-major order to OS
+    /// clipboard, Windows and macOS only.
+    pub fn clipboard_set_image(rgba: &[u8], width: u32, height: u32) {
+        assert_eq!(rgba.len(), (width * height * 4) as usize);
+        let mut d = native_display().lock().unwrap();
+        d.clipboard.set_image(rgba, width, height)
+    }
+
+    /// Number of files in the last `files_dropped_event`.
     pub fn dropped_file_count() -> usize {
         let d = native_display().lock().unwrap();
-        d.dropped_files.bytes.len()
+        d.dropped_files.paths.len()
     }
+    /// Contents of a dropped file. Only on wasm, where there is no other way
+    /// to read it, desktop platforms give just the `dropped_file_path`.
     pub fn dropped_file_bytes(index: usize) -> Option<Vec<u8>> {
         let d = native_display().lock().unwrap();
         d.dropped_files.bytes.get(index).cloned()
@@ -253,13 +660,245 @@ pub mod window {
         d.dropped_files.paths.get(index).cloned()
     }
 
+    /// The menus of the menu bar after the application menu and the Edit
+    /// menu miniquad makes, the standard ones with About, Hide, Quit and the
+    /// clipboard shortcuts. The items send `EventHandler::menu_event`.
+    /// Only works on macOS.
+    pub fn set_menus(menus: Vec<Menu>) {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests.send(native::Request::SetMenus(menus));
+    }
+
+    /// The items over the standard ones in the menu of the dock icon, with
+    /// the same `EventHandler::menu_event`. Only works on macOS.
+    pub fn set_dock_menu(items: Vec<MenuItem>) {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests.send(native::Request::SetDockMenu(items));
+    }
+
     /// Show/hide onscreen keyboard.
-    /// Only works on Android right now.
+    /// Only works on Android and iOS.
     pub fn show_keyboard(show: bool) {
         let mut d = native_display().lock().unwrap();
         d.native_requests.send(native::Request::ShowKeyboard(show));
     }
 
+    /// The layout of the onscreen keyboard, for the next time it is shown
+    /// and right away if it is. Only works on Android and iOS.
+    pub fn set_keyboard_type(keyboard_type: KeyboardType) {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetKeyboardType(keyboard_type));
+    }
+
+    /// How much of the bottom of the window the onscreen keyboard covers, 0
+    /// while it is hidden. See `EventHandler::keyboard_insets_changed`.
+    pub fn keyboard_height() -> f32 {
+        let d = native_display().lock().unwrap();
+        d.keyboard_height
+    }
+
+    /// Portrait or Landscape, whether the window is taller than wide.
+    /// `EventHandler::orientation_changed` tells when the screen rotates.
+    /// Only changes on Android, iOS and the web.
+    pub fn orientation() -> conf::Orientation {
+        let d = native_display().lock().unwrap();
+        d.orientation
+    }
+
+    /// The orientations the screen can rotate to from now on, like
+    /// `conf.supported_orientations`. `Orientation::Any` unlocks it.
+    /// setRequestedOrientation on Android, supportedInterfaceOrientations on
+    /// iOS and screen.orientation.lock on the web, where it takes effect in
+    /// fullscreen.
+    pub fn lock_orientation(orientation: conf::Orientation) {
+        let mut d = native_display().lock().unwrap();
+        d.supported_orientations = orientation;
+        d.native_requests
+            .send(native::Request::LockOrientation(orientation));
+    }
+
+    /// How much of each edge of the window is under a notch, a rounded
+    /// corner or the system bars, for the HUD to stay clear of it. In the
+    /// same pixels as the touch events. `EventHandler::safe_area_changed`
+    /// tells when they change, like on a rotation.
+    /// safeAreaInsets on iOS, the display cutout and the system bars on
+    /// Android and env(safe-area-inset-*) on the web, which needs
+    /// `viewport-fit=cover` in the viewport meta tag of the page. All 0 on
+    /// desktop.
+    pub fn safe_area_insets() -> SafeAreaInsets {
+        let d = native_display().lock().unwrap();
+        d.safe_area_insets
+    }
+
+    /// Turns text input on and off, for a text field getting and losing focus.
+    /// On Android and iOS it shows and hides the onscreen keyboard. On desktop
+    /// and the web it is on by default, and while it is off there are no
+    /// `char_event`s and the input method is off.
+    pub fn set_text_input_enabled(enabled: bool) {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetTextInputEnabled(enabled));
+    }
+
+    /// Where the text cursor is, in the same pixels as the mouse events, for
+    /// the input method to put its candidate window next to it.
+    pub fn set_ime_cursor_area(x: f32, y: f32, width: f32, height: f32) {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests.send(native::Request::SetImeCursorArea {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    /// The modifier keys held and the lock keys on, as of the last input
+    /// event. All off while the window does not have the focus.
+    pub fn modifiers() -> KeyMods {
+        let d = native_display().lock().unwrap();
+        d.keymods
+    }
+
+    /// The languages the user prefers for the interface, as BCP-47 tags like
+    /// "en-US", the first one first. GetUserPreferredUILanguages on Windows,
+    /// LANGUAGE, LC_ALL, LC_MESSAGES and LANG on Linux, NSLocale on macOS and
+    /// iOS, the configuration of the resources on Android and
+    /// navigator.languages on the web. Empty when there are none, like with
+    /// the "C" locale.
+    pub fn locale() -> Vec<String> {
+        native::locale()
+    }
+
+    /// If the key is held, between its `key_down_event` and `key_up_event`,
+    /// for the input read by polling instead of events. On focus loss the
+    /// keys are released, they don't stay stuck while the key ups go to
+    /// another window.
+    pub fn is_key_down(keycode: KeyCode) -> bool {
+        let d = native_display().lock().unwrap();
+        d.held_keys
+            .iter()
+            .any(|&(held_keycode, _)| held_keycode == keycode)
+    }
+
+    /// The keys held, in the order they were pressed.
+    pub fn keys_down() -> Vec<KeyCode> {
+        let d = native_display().lock().unwrap();
+        let mut keys: Vec<KeyCode> = vec![];
+        for &(keycode, _) in &d.held_keys {
+            if !keys.contains(&keycode) {
+                keys.push(keycode);
+            }
+        }
+        keys
+    }
+
+    /// When the mouse, keyboard, touch or pen event being handled happened,
+    /// in seconds, as the platform timestamped it. To the millisecond on
+    /// Windows, X11, Wayland and Android.
+    /// Only the differences between timestamps mean something, compare
+    /// them to `event_timestamp_now` for the input latency.
+    pub fn event_timestamp() -> f64 {
+        let d = native_display().lock().unwrap();
+        d.event_timestamp
+    }
+
+    /// When the frame being drawn has to be done by to be presented at the
+    /// next vsync, on the clock of `event_timestamp_now`. Android 13 and up,
+    /// where the frames are started by the vsync from AChoreographer, None
+    /// elsewhere and with the swap interval 0.
+    pub fn frame_deadline() -> Option<f64> {
+        let d = native_display().lock().unwrap();
+        d.frame_deadline
+    }
+
+    /// Now, on the clock of `event_timestamp`. On X11 the timestamps are the
+    /// server's, which is CLOCK_MONOTONIC for a local Xorg.
+    pub fn event_timestamp_now() -> f64 {
+        native::timestamp_now()
+    }
+
+    /// The connected gamepads with their ids, as of their last
+    /// `gamepad_event`, in the order they were connected.
+    pub fn gamepads() -> Vec<(u64, GamepadState)> {
+        let d = native_display().lock().unwrap();
+        d.gamepads
+            .iter()
+            .map(|(&id, state)| (id, state.clone()))
+            .collect()
+    }
+
+    /// Runs the motors of a gamepad from `window::gamepads` for `duration`,
+    /// the low frequency one (the heavy, left one) and the high frequency one
+    /// from 0 to 1. 0 on both stops them. Ignored for the gamepads without
+    /// force feedback.
+    /// Windows (XInput), Linux (evdev, with write access to the device), web
+    /// (with Gamepad.vibrationActuator), macOS 11 and iOS 14 (Core Haptics)
+    /// and Android (one motor at the strongest of the two, with the VIBRATE
+    /// permission).
+    pub fn set_gamepad_rumble(
+        id: u64,
+        low_frequency: f32,
+        high_frequency: f32,
+        duration: std::time::Duration,
+    ) {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests.send(native::Request::SetGamepadRumble {
+            id,
+            low_frequency: low_frequency.clamp(0., 1.),
+            high_frequency: high_frequency.clamp(0., 1.),
+            duration,
+        });
+    }
+
+    /// Keeps the display from sleeping and the screen saver from starting,
+    /// for the games played with a gamepad only. Off by default, and undone
+    /// when the app quits.
+    /// SetThreadExecutionState on Windows, an IOKit power assertion on
+    /// macOS, idleTimerDisabled on iOS, FLAG_KEEP_SCREEN_ON on Android, the
+    /// Wake Lock API on web (only while the page is visible), the
+    /// idle-inhibit protocol on Wayland and org.freedesktop.ScreenSaver on
+    /// X11 and the compositors without it.
+    pub fn set_keep_screen_on(keep_on: bool) {
+        let mut d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetKeepScreenOn(keep_on));
+    }
+
+    /// How many buttons the mouse has, to know if `MouseButton::Back` and
+    /// `Forward` can be bound. Only on Windows, None elsewhere.
+    pub fn mouse_button_count() -> Option<u32> {
+        #[cfg(target_os = "windows")]
+        return Some(native::windows::mouse_button_count());
+        #[cfg(not(target_os = "windows"))]
+        None
+    }
+
+    /// The physical key with `keycode` on a US QWERTY keyboard, to define the
+    /// default controls by the keys they are on. `ScanCode(0)` for `Unknown`.
+    pub fn keycode_to_scancode(keycode: KeyCode) -> ScanCode {
+        native::scancodes::keycode_to_scancode(keycode)
+    }
+
+    /// The `KeyCode` a US QWERTY keyboard has on the physical key, `Unknown`
+    /// for the keys without one.
+    pub fn scancode_to_keycode(scancode: ScanCode) -> KeyCode {
+        native::scancodes::scancode_to_keycode(scancode)
+    }
+
+    #[cfg(feature = "raw-window-handle")]
+    pub use crate::native::WindowHandle;
+
+    /// Native window handles, to plug in libraries that talk to the
+    /// windowing system directly: wgpu, OpenXR, video decoders, file dialogs.
+    /// `WindowHandle` implements `HasRawWindowHandle` and `HasRawDisplayHandle`.
+    /// Desktop platforms only, None elsewhere.
+    #[cfg(feature = "raw-window-handle")]
+    pub fn window_handle() -> Option<WindowHandle> {
+        let d = native_display().lock().unwrap();
+        d.window_handle
+    }
+
     #[cfg(target_vendor = "apple")]
     pub fn apple_gfx_api() -> crate::conf::AppleGfxApi {
         let d = native_display().lock().unwrap();
@@ -293,6 +932,213 @@ pub enum CursorIcon {
     NWSEResize,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Hash, Eq)]
+pub enum ClipboardFormat {
+    Text,
+    Html,
+    /// `ClipboardImage`, whatever image format the OS clipboard holds
+    /// is converted to RGBA.
+    Image,
+}
+
+impl ClipboardFormat {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ClipboardFormat::Text => "text/plain;charset=utf-8",
+            ClipboardFormat::Html => "text/html",
+            ClipboardFormat::Image => "image/png",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClipboardImage {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA pixels in row-major order, top row first.
+    pub rgba: Vec<u8>,
+}
+
+/// A display connected to the system, see `window::monitors`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monitor {
+    /// Like "DP-1" or "DELL U2720Q", could be empty.
+    pub name: String,
+    /// Top left corner on the desktop, in the same units as `set_window_position`:
+    /// pixels, or points on macOS and logical pixels on Wayland.
+    pub position: (i32, i32),
+    /// In millimeters, `(0, 0)` when the monitor does not report it.
+    pub physical_size: (u32, u32),
+    /// The current video mode, in pixels.
+    pub resolution: (u32, u32),
+    /// The current video mode refresh rate in Hz, if known.
+    pub refresh_rate: Option<f32>,
+    /// Everything `window::set_exclusive_fullscreen` could switch to,
+    /// empty where exclusive fullscreen is not supported.
+    pub video_modes: Vec<VideoMode>,
+    /// Like `window::dpi_scale`, 1.0 for 96 DPI. The same for all the
+    /// monitors on X11, where the scale is a system-wide setting.
+    pub dpi_scale: f32,
+}
+
+/// A monitor resolution in pixels and a refresh rate in Hz.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct VideoMode {
+    pub resolution: (u32, u32),
+    pub refresh_rate: Option<f32>,
+}
+
+/// See `window::window_state`. Fullscreen is a `Normal` window.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WindowState {
+    Normal,
+    Minimized,
+    Maximized,
+}
+
+/// See `EventHandler::hit_test`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WindowArea {
+    /// Mouse events for the application, as usual.
+    Client,
+    /// Drags the window, a double click maximizes it.
+    Caption,
+    /// The edges and corners resize the window.
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// See `window::request_user_attention`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UserAttentionType {
+    /// Until the window is focused: the dock icon keeps bouncing and the
+    /// taskbar button flashes with the window frame.
+    Critical,
+    /// The dock icon bounces once, the taskbar button is highlighted.
+    Informational,
+}
+
+/// See `window::set_keyboard_type`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KeyboardType {
+    /// The default one, every key is sent as it is typed.
+    Text,
+    /// The digits.
+    Number,
+    /// The digits and the decimal separator.
+    Decimal,
+    Phone,
+    /// Text with the @ and the . at hand.
+    Email,
+    /// Text with the / and the . at hand.
+    Url,
+}
+
+/// A menu of the menu bar, see `window::set_menus`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Menu {
+    pub title: String,
+    pub items: Vec<MenuItem>,
+}
+
+/// See `window::set_menus` and `window::set_dock_menu`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MenuItem {
+    /// Sends `EventHandler::menu_event` with `id` when chosen. `shortcut` is
+    /// the key with Cmd, an uppercase letter for Cmd+Shift.
+    Action {
+        id: u32,
+        title: String,
+        shortcut: Option<char>,
+    },
+    Separator,
+    Submenu(Menu),
+}
+
+/// See `window::power_info`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PowerState {
+    /// Not known on this platform or not yet.
+    Unknown,
+    /// On AC power, without a battery.
+    NoBattery,
+    OnBattery,
+    Charging,
+    /// Plugged in, with the battery full or not charging.
+    Charged,
+}
+
+/// See `window::power_info`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PowerInfo {
+    pub state: PowerState,
+    /// The charge of the battery, from 0 to 1, None without a battery or
+    /// when it is not known.
+    pub battery_level: Option<f32>,
+    /// Low Power Mode on macOS and iOS, Battery Saver on Windows and Android.
+    pub power_saver: bool,
+}
+
+impl Default for PowerInfo {
+    fn default() -> PowerInfo {
+        PowerInfo {
+            state: PowerState::Unknown,
+            battery_level: None,
+            power_saver: false,
+        }
+    }
+}
+
+/// See `window::safe_area_insets`.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct SafeAreaInsets {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+/// See `window::system_theme`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Hash, Eq)]
+pub enum CursorGrabMode {
+    /// The cursor moves freely.
+    None,
+    /// The cursor is visible, but can't leave the window.
+    /// Not available on macOS and WASM, behaves as `Locked` there.
+    Confined,
+    /// The cursor is hidden and stays in place, only `EventHandler::raw_mouse_motion`
+    /// deltas are reported, without OS acceleration and not limited by the
+    /// screen edges. Meant for first person camera controls.
+    /// Uses XInput2 raw events on X11, raw input on Windows,
+    /// pointer lock on WASM and the relative-pointer and pointer-constraints
+    /// protocols on Wayland, where the compositor may not have them.
+    /// On Windows a motion comes for every report of the mouse, at its
+    /// polling rate.
+    Locked,
+}
+
+impl From<bool> for CursorGrabMode {
+    fn from(grab: bool) -> CursorGrabMode {
+        if grab {
+            CursorGrabMode::Confined
+        } else {
+            CursorGrabMode::None
+        }
+    }
+}
+
 /// Start miniquad.
 pub fn start<F>(conf: conf::Conf, f: F)
 where
@@ -300,6 +1146,8 @@ where
 {
     #[cfg(target_os = "linux")]
     {
+        native::handle_sigterm();
+        native::set_gpu_hint(conf.platform.linux_gpu);
         let mut f = Some(f);
         let f = &mut f;
         match conf.platform.linux_backend {
@@ -1,10 +1,83 @@
-use std::sync::mpsc;
+use std::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
 
 #[derive(Default)]
 pub(crate) struct DroppedFiles {
     pub paths: Vec<std::path::PathBuf>,
     pub bytes: Vec<Vec<u8>>,
 }
+
+impl DroppedFiles {
+    /// Local paths from a "text/uri-list", the format X11 and Wayland file
+    /// managers use for drag and drop.
+    #[cfg(target_os = "linux")]
+    pub fn from_uri_list(list: &[u8]) -> DroppedFiles {
+        use std::os::unix::ffi::OsStringExt;
+
+        let paths = String::from_utf8_lossy(list)
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|uri| uri.trim().strip_prefix("file://"))
+            .map(|path| {
+                // file:///path or file://hostname/path
+                let path = &path[path.find('/').unwrap_or(path.len())..];
+                std::ffi::OsString::from_vec(percent_decode(path)).into()
+            })
+            .collect();
+        DroppedFiles {
+            paths,
+            bytes: vec![],
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn percent_decode(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) if bytes[i] == b'%' => {
+                res.push(byte);
+                i += 3;
+            }
+            _ => {
+                res.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    res
+}
+
+/// Index of the monitor with the biggest part of the `position`, `size` rectangle.
+pub(crate) fn overlapping_monitor(
+    monitors: &[crate::Monitor],
+    position: (i32, i32),
+    size: (i32, i32),
+) -> Option<usize> {
+    let overlap = |monitor: &crate::Monitor| {
+        let (x, y) = monitor.position;
+        let (w, h) = (monitor.resolution.0 as i32, monitor.resolution.1 as i32);
+        let dx = (position.0 + size.0).min(x + w) - position.0.max(x);
+        let dy = (position.1 + size.1).min(y + h) - position.1.max(y);
+        dx.max(0) as i64 * dy.max(0) as i64
+    };
+    monitors
+        .iter()
+        .enumerate()
+        .filter(|(_, monitor)| overlap(monitor) > 0)
+        .max_by_key(|(_, monitor)| overlap(monitor))
+        .map(|(i, _)| i)
+}
+
 pub(crate) struct NativeDisplayData {
     pub screen_width: i32,
     pub screen_height: i32,
@@ -13,9 +86,50 @@ pub(crate) struct NativeDisplayData {
     pub high_dpi: bool,
     pub quit_requested: bool,
     pub quit_ordered: bool,
+    // the Android back, not canceled by the event handler yet
+    pub back_requested: bool,
     pub native_requests: mpsc::Sender<Request>,
     pub clipboard: Box<dyn Clipboard>,
     pub dropped_files: DroppedFiles,
+    pub monitors: Vec<crate::Monitor>,
+    pub current_monitor: Option<usize>,
+    #[cfg(feature = "raw-window-handle")]
+    pub window_handle: Option<WindowHandle>,
+    // what window::set_swap_interval can do: 0 and 1, and -1 for adaptive vsync
+    pub swap_control: bool,
+    pub adaptive_vsync: bool,
+    pub target_fps: Option<u32>,
+    pub background_mode: crate::conf::BackgroundMode,
+    pub occluded: bool,
+    // conf.wait_for_events frames that are not for events
+    pub redraw_requested: bool,
+    pub update_deadline: Option<Instant>,
+    // wakes the main loop up from waiting for events, None where it never blocks
+    pub waker: Option<Box<dyn Fn() + Send>>,
+    pub custom_events_tx: mpsc::Sender<Box<dyn std::any::Any + Send>>,
+    pub custom_events: mpsc::Receiver<Box<dyn std::any::Any + Send>>,
+    pub size_constraints: SizeConstraints,
+    pub window_state: crate::WindowState,
+    pub system_theme: crate::Theme,
+    pub power_info: crate::PowerInfo,
+    // window::modifiers, the backends keep it current and reset it on focus loss
+    pub keymods: crate::KeyMods,
+    pub focused: bool,
+    // window::keyboard_height, the onscreen keyboard
+    pub keyboard_height: f32,
+    pub safe_area_insets: crate::SafeAreaInsets,
+    // window::orientation, Portrait or Landscape, and lock_orientation
+    pub orientation: crate::conf::Orientation,
+    pub supported_orientations: crate::conf::Orientation,
+    // window::is_key_down, the keys down, released by a focus loss
+    pub held_keys: Vec<(crate::KeyCode, crate::ScanCode)>,
+    // window::event_timestamp, in seconds on the clock of timestamp_now
+    pub event_timestamp: f64,
+    pub frame_deadline: Option<f64>,
+    // the GL context is from EGL, for ContextInfo::egl
+    pub egl: bool,
+    // window::gamepads, by id, so in the order they were connected
+    pub gamepads: std::collections::BTreeMap<u64, crate::GamepadState>,
 
     #[cfg(target_vendor = "apple")]
     pub view: crate::native::apple::frameworks::ObjcId,
@@ -36,6 +150,7 @@ impl NativeDisplayData {
         native_requests: mpsc::Sender<Request>,
         clipboard: Box<dyn Clipboard>,
     ) -> NativeDisplayData {
+        let (custom_events_tx, custom_events) = mpsc::channel();
         NativeDisplayData {
             screen_width,
             screen_height,
@@ -44,9 +159,44 @@ impl NativeDisplayData {
             high_dpi: false,
             quit_requested: false,
             quit_ordered: false,
+            back_requested: false,
             native_requests,
             clipboard,
             dropped_files: Default::default(),
+            monitors: vec![],
+            current_monitor: None,
+            #[cfg(feature = "raw-window-handle")]
+            window_handle: None,
+            swap_control: false,
+            adaptive_vsync: false,
+            target_fps: None,
+            background_mode: crate::conf::BackgroundMode::Continue,
+            occluded: false,
+            // the first frame does not wait for events
+            redraw_requested: true,
+            update_deadline: None,
+            waker: None,
+            custom_events_tx,
+            custom_events,
+            size_constraints: Default::default(),
+            window_state: crate::WindowState::Normal,
+            system_theme: crate::Theme::Light,
+            power_info: Default::default(),
+            egl: false,
+            keymods: Default::default(),
+            focused: true,
+            keyboard_height: 0.,
+            safe_area_insets: Default::default(),
+            orientation: if screen_height > screen_width {
+                crate::conf::Orientation::Portrait
+            } else {
+                crate::conf::Orientation::Landscape
+            },
+            supported_orientations: crate::conf::Orientation::Any,
+            held_keys: vec![],
+            event_timestamp: 0.,
+            frame_deadline: None,
+            gamepads: Default::default(),
             #[cfg(target_vendor = "apple")]
             gfx_api: crate::conf::AppleGfxApi::OpenGl,
             #[cfg(target_vendor = "apple")]
@@ -55,22 +205,802 @@ impl NativeDisplayData {
             view_ctrl: std::ptr::null_mut(),
         }
     }
+
+    /// `target_fps`, or the background one while the window is occluded.
+    /// 10 when paused, for the events that could make it visible again.
+    pub fn frame_rate(&self) -> Option<u32> {
+        use crate::conf::BackgroundMode;
+
+        if !self.occluded {
+            return self.target_fps;
+        }
+        match self.background_mode {
+            BackgroundMode::Continue => self.target_fps,
+            BackgroundMode::Throttle(fps) => {
+                Some(self.target_fps.map_or(fps, |target| target.min(fps)))
+            }
+            BackgroundMode::Pause => Some(10),
+        }
+    }
+
+    /// The main loops skip `update` and `draw`, the window is occluded
+    /// with `BackgroundMode::Pause`.
+    pub fn frames_paused(&self) -> bool {
+        self.occluded && self.background_mode == crate::conf::BackgroundMode::Pause
+    }
+
+    /// For `conf.wait_for_events`: how long the main loop may wait for events
+    /// before the next frame, zero if the frame is due now and None if only an
+    /// event can make one. The due requests are cleared.
+    pub fn frame_timeout(&mut self) -> Option<Duration> {
+        if std::mem::replace(&mut self.redraw_requested, false) {
+            return Some(Duration::ZERO);
+        }
+        // the quit_requested_event goes with the next frame
+        if self.quit_requested {
+            return Some(Duration::ZERO);
+        }
+        let deadline = self.update_deadline?;
+        let now = Instant::now();
+        if deadline <= now {
+            self.update_deadline = None;
+            return Some(Duration::ZERO);
+        }
+        Some(deadline - now)
+    }
+}
+
+/// Native handles of the window and the display connection it belongs to,
+/// see `window::window_handle`.
+#[cfg(feature = "raw-window-handle")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowHandle {
+    window: raw_window_handle::RawWindowHandle,
+    display: raw_window_handle::RawDisplayHandle,
+}
+// just identifiers, all the actual work with them is up to the user
+#[cfg(feature = "raw-window-handle")]
+unsafe impl Send for WindowHandle {}
+#[cfg(feature = "raw-window-handle")]
+unsafe impl Sync for WindowHandle {}
+
+#[cfg(feature = "raw-window-handle")]
+impl WindowHandle {
+    #[cfg(target_os = "linux")]
+    pub(crate) fn xlib(
+        display: *mut std::ffi::c_void,
+        screen: std::os::raw::c_int,
+        window: std::os::raw::c_ulong,
+    ) -> WindowHandle {
+        let mut window_handle = raw_window_handle::XlibWindowHandle::empty();
+        window_handle.window = window;
+        let mut display_handle = raw_window_handle::XlibDisplayHandle::empty();
+        display_handle.display = display;
+        display_handle.screen = screen;
+        WindowHandle {
+            window: window_handle.into(),
+            display: display_handle.into(),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(crate) fn wayland(
+        display: *mut std::ffi::c_void,
+        surface: *mut std::ffi::c_void,
+    ) -> WindowHandle {
+        let mut window_handle = raw_window_handle::WaylandWindowHandle::empty();
+        window_handle.surface = surface;
+        let mut display_handle = raw_window_handle::WaylandDisplayHandle::empty();
+        display_handle.display = display;
+        WindowHandle {
+            window: window_handle.into(),
+            display: display_handle.into(),
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    pub(crate) fn win32(
+        hwnd: *mut std::ffi::c_void,
+        hinstance: *mut std::ffi::c_void,
+    ) -> WindowHandle {
+        let mut window_handle = raw_window_handle::Win32WindowHandle::empty();
+        window_handle.hwnd = hwnd;
+        window_handle.hinstance = hinstance;
+        WindowHandle {
+            window: window_handle.into(),
+            display: raw_window_handle::WindowsDisplayHandle::empty().into(),
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    pub(crate) fn app_kit(
+        ns_window: *mut std::ffi::c_void,
+        ns_view: *mut std::ffi::c_void,
+    ) -> WindowHandle {
+        let mut window_handle = raw_window_handle::AppKitWindowHandle::empty();
+        window_handle.ns_window = ns_window;
+        window_handle.ns_view = ns_view;
+        WindowHandle {
+            window: window_handle.into(),
+            display: raw_window_handle::AppKitDisplayHandle::empty().into(),
+        }
+    }
+}
+
+#[cfg(feature = "raw-window-handle")]
+unsafe impl raw_window_handle::HasRawWindowHandle for WindowHandle {
+    fn raw_window_handle(&self) -> raw_window_handle::RawWindowHandle {
+        self.window
+    }
+}
+
+#[cfg(feature = "raw-window-handle")]
+unsafe impl raw_window_handle::HasRawDisplayHandle for WindowHandle {
+    fn raw_display_handle(&self) -> raw_window_handle::RawDisplayHandle {
+        self.display
+    }
+}
+
+/// `window::set_min_size`, `set_max_size` and `set_aspect_ratio`, sizes are
+/// of the window content, in the same units as `window::set_window_size`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SizeConstraints {
+    pub min: Option<(u32, u32)>,
+    pub max: Option<(u32, u32)>,
+    pub aspect: Option<(u32, u32)>,
 }
 
 #[derive(Debug)]
 pub enum Request {
-    SetCursorGrab(bool),
+    SetCursorGrab(crate::CursorGrabMode),
     ShowMouse(bool),
     SetMouseCursor(crate::CursorIcon),
     SetWindowSize { new_width: u32, new_height: u32 },
     SetWindowPosition{ new_x: u32, new_y: u32 },
     SetFullscreen(bool),
+    SetExclusiveFullscreen {
+        monitor: usize,
+        mode: crate::VideoMode,
+    },
+    SetSwapInterval(i32),
+    SetSizeConstraints(SizeConstraints),
+    SetWindowState(crate::WindowState),
+    SetDecorations(bool),
+    SetResizable(bool),
+    SetAlwaysOnTop(bool),
+    SetOpacity(f32),
+    RequestUserAttention(crate::UserAttentionType),
     ShowKeyboard(bool),
+    SetKeyboardType(crate::KeyboardType),
+    LockOrientation(crate::conf::Orientation),
+    SetFullscreenImmersive(bool),
+    SetSystemBarsColor((f32, f32, f32, f32)),
+    SetMenus(Vec<crate::Menu>),
+    SetDockMenu(Vec<crate::MenuItem>),
+    SetTaskbarProgress(Option<f32>),
+    // rgba, width, height and the description
+    SetTaskbarOverlay(Option<(Vec<u8>, u32, u32, String)>),
+    SetTextInputEnabled(bool),
+    SetImeCursorArea {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+    SetWindowIcon {
+        rgba: Vec<u8>,
+        width: u32,
+        height: u32,
+    },
+    SetCustomMouseCursor {
+        rgba: Vec<u8>,
+        width: u32,
+        height: u32,
+        hotspot: (u32, u32),
+    },
+    SetMousePosition {
+        x: f32,
+        y: f32,
+    },
+    SetGamepadRumble {
+        id: u64,
+        low_frequency: f32,
+        high_frequency: f32,
+        duration: std::time::Duration,
+    },
+    SetKeepScreenOn(bool),
 }
 
 pub trait Clipboard: Send + Sync {
     fn get(&mut self) -> Option<String>;
     fn set(&mut self, string: &str);
+
+    // html and images are optional, the defaults are for text-only clipboards
+    fn has(&mut self, format: crate::ClipboardFormat) -> bool {
+        format == crate::ClipboardFormat::Text && self.get().is_some()
+    }
+    fn get_html(&mut self) -> Option<String> {
+        None
+    }
+    fn set_html(&mut self, _html: &str, text: &str) {
+        self.set(text)
+    }
+    fn get_image(&mut self) -> Option<crate::ClipboardImage> {
+        None
+    }
+    fn set_image(&mut self, _rgba: &[u8], _width: u32, _height: u32) {}
+}
+
+/// Touch ids that are new for every touch. The platforms reuse the ids of
+/// the touches that ended, the first finger down is always 0 on Android.
+#[derive(Default)]
+pub(crate) struct TouchIds {
+    ids: std::collections::HashMap<u64, u64>,
+    next: u64,
+}
+
+impl TouchIds {
+    /// The id for the platform one, forgotten after `Ended` and `Cancelled`.
+    pub fn get(&mut self, phase: crate::TouchPhase, platform_id: u64) -> u64 {
+        use crate::TouchPhase;
+
+        let id = match self.ids.get(&platform_id) {
+            Some(&id) if phase != TouchPhase::Started => id,
+            _ => {
+                self.next += 1;
+                self.next - 1
+            }
+        };
+        if phase == TouchPhase::Ended || phase == TouchPhase::Cancelled {
+            self.ids.remove(&platform_id);
+        } else {
+            self.ids.insert(platform_id, id);
+        }
+        id
+    }
+}
+
+/// `EventHandler::custom_event` for everything from the `EventSender`s,
+/// the main loops call it right before `update`.
+pub(crate) fn dispatch_custom_events(event_handler: &mut dyn crate::EventHandler) {
+    let events: Vec<_> = crate::native_display()
+        .lock()
+        .unwrap()
+        .custom_events
+        .try_iter()
+        .collect();
+    for data in events {
+        event_handler.custom_event(data);
+    }
+}
+
+/// `window::window_state` and the matching `EventHandler` event,
+/// nothing if the window was in this state already.
+pub(crate) fn update_window_state(
+    state: crate::WindowState,
+    event_handler: &mut dyn crate::EventHandler,
+) {
+    let mut d = crate::native_display().lock().unwrap();
+    if d.window_state == state {
+        return;
+    }
+    d.window_state = state;
+    drop(d);
+    match state {
+        crate::WindowState::Normal => event_handler.window_restored_event(),
+        crate::WindowState::Minimized => event_handler.window_minimized_event(),
+        crate::WindowState::Maximized => event_handler.window_maximized_event(),
+    }
+}
+
+/// `window::system_theme` and `theme_changed`, nothing if the theme is
+/// the same.
+pub(crate) fn update_system_theme(
+    theme: crate::Theme,
+    event_handler: &mut dyn crate::EventHandler,
+) {
+    let mut d = crate::native_display().lock().unwrap();
+    if d.system_theme == theme {
+        return;
+    }
+    d.system_theme = theme;
+    drop(d);
+    event_handler.theme_changed(theme);
+}
+
+/// `window::keyboard_height` and `keyboard_insets_changed`, nothing if the
+/// height is the same.
+pub(crate) fn update_keyboard_height(height: f32, event_handler: &mut dyn crate::EventHandler) {
+    let mut d = crate::native_display().lock().unwrap();
+    if d.keyboard_height == height {
+        return;
+    }
+    d.keyboard_height = height;
+    drop(d);
+    event_handler.keyboard_insets_changed(height);
+}
+
+/// `window::safe_area_insets` and `safe_area_changed`, nothing if the insets
+/// are the same.
+pub(crate) fn update_safe_area_insets(
+    insets: crate::SafeAreaInsets,
+    event_handler: &mut dyn crate::EventHandler,
+) {
+    let mut d = crate::native_display().lock().unwrap();
+    if d.safe_area_insets == insets {
+        return;
+    }
+    d.safe_area_insets = insets;
+    drop(d);
+    event_handler.safe_area_changed(insets);
+}
+
+/// `window::orientation` and `orientation_changed` from the new screen size,
+/// before its `resize_event`. Nothing if the orientation is the same.
+pub(crate) fn update_orientation(event_handler: &mut dyn crate::EventHandler) {
+    let mut d = crate::native_display().lock().unwrap();
+    let orientation = if d.screen_height > d.screen_width {
+        crate::conf::Orientation::Portrait
+    } else {
+        crate::conf::Orientation::Landscape
+    };
+    if d.orientation == orientation {
+        return;
+    }
+    d.orientation = orientation;
+    let (width, height) = (d.screen_width as f32, d.screen_height as f32);
+    drop(d);
+    event_handler.orientation_changed(orientation, width, height);
+}
+
+/// `window::power_info` and `power_changed`, nothing if nothing changed.
+pub(crate) fn update_power_info(
+    power_info: crate::PowerInfo,
+    event_handler: &mut dyn crate::EventHandler,
+) {
+    let mut d = crate::native_display().lock().unwrap();
+    if d.power_info == power_info {
+        return;
+    }
+    d.power_info = power_info;
+    drop(d);
+    event_handler.power_changed(power_info);
+}
+
+/// `window::occluded` and `window_occluded`, nothing if it is the same.
+pub(crate) fn update_occluded(occluded: bool, event_handler: &mut dyn crate::EventHandler) {
+    let mut d = crate::native_display().lock().unwrap();
+    if d.occluded == occluded {
+        return;
+    }
+    d.occluded = occluded;
+    drop(d);
+    event_handler.window_occluded(occluded);
+}
+
+/// `window::get_window_position` and `window_moved_event`,
+/// nothing if the window did not move.
+pub(crate) fn update_window_position(
+    position: (u32, u32),
+    event_handler: &mut dyn crate::EventHandler,
+) {
+    let mut d = crate::native_display().lock().unwrap();
+    if d.screen_position == position {
+        return;
+    }
+    d.screen_position = position;
+    drop(d);
+    event_handler.window_moved_event(position.0, position.1);
+}
+
+/// `window::dpi_scale` and `dpi_scale_changed_event`,
+/// nothing if the scale is the same.
+pub(crate) fn update_dpi_scale(dpi_scale: f32, event_handler: &mut dyn crate::EventHandler) {
+    let mut d = crate::native_display().lock().unwrap();
+    if d.dpi_scale == dpi_scale {
+        return;
+    }
+    d.dpi_scale = dpi_scale;
+    drop(d);
+    event_handler.dpi_scale_changed_event(dpi_scale);
+}
+
+/// `magnify_event` from ctrl+wheel, what the pinch of the precision touchpads
+/// on Windows and of the browsers is, and the zoom of a mouse in most
+/// applications. macOS has its own gesture events. 10% per notch and per
+/// 10 pixels, in the pixels before the dpi scale.
+pub(crate) fn wheel_magnify(
+    delta: crate::event::ScrollDelta,
+    keymods: crate::KeyMods,
+    event_handler: &mut dyn crate::EventHandler,
+) {
+    use crate::event::ScrollDelta;
+
+    if !keymods.ctrl {
+        return;
+    }
+    let magnification = match delta {
+        ScrollDelta::Lines(_, y) => y * 0.1,
+        ScrollDelta::Pixels(_, y) => {
+            let dpi_scale = crate::native_display().lock().unwrap().dpi_scale;
+            y / dpi_scale * 0.01
+        }
+    };
+    if magnification != 0. {
+        event_handler.magnify_event(magnification);
+    }
+}
+
+/// `window::modifiers`, the default on focus loss.
+pub(crate) fn update_keymods(keymods: crate::KeyMods) {
+    let mut d = crate::native_display().lock().unwrap();
+    d.keymods = keymods;
+}
+
+/// `key_down_scancode_event`, the key is held until its `key_up` or a
+/// focus loss.
+pub(crate) fn key_down(
+    keycode: crate::KeyCode,
+    scancode: crate::ScanCode,
+    keymods: crate::KeyMods,
+    repeat: bool,
+    event_handler: &mut dyn crate::EventHandler,
+) {
+    let mut d = crate::native_display().lock().unwrap();
+    if !d.held_keys.contains(&(keycode, scancode)) {
+        d.held_keys.push((keycode, scancode));
+    }
+    drop(d);
+    event_handler.key_down_scancode_event(keycode, scancode, keymods, repeat);
+}
+
+/// `key_up_scancode_event`, the layout could have changed since the key
+/// down, either code is the same key.
+pub(crate) fn key_up(
+    keycode: crate::KeyCode,
+    scancode: crate::ScanCode,
+    keymods: crate::KeyMods,
+    event_handler: &mut dyn crate::EventHandler,
+) {
+    let mut d = crate::native_display().lock().unwrap();
+    d.held_keys.retain(|&(held_keycode, held_scancode)| {
+        held_keycode != keycode && (scancode.0 == 0 || held_scancode != scancode)
+    });
+    drop(d);
+    event_handler.key_up_scancode_event(keycode, scancode, keymods);
+}
+
+/// `focus_event`, nothing if the focus is the same. The key ups of the held
+/// keys would go to the window focused next, they are sent on focus loss,
+/// and the modifiers are reset.
+pub(crate) fn update_focus(focused: bool, event_handler: &mut dyn crate::EventHandler) {
+    let mut d = crate::native_display().lock().unwrap();
+    if d.focused == focused {
+        return;
+    }
+    d.focused = focused;
+    let held_keys = if focused {
+        vec![]
+    } else {
+        d.keymods = Default::default();
+        std::mem::take(&mut d.held_keys)
+    };
+    drop(d);
+    for (keycode, scancode) in held_keys {
+        event_handler.key_up_scancode_event(keycode, scancode, Default::default());
+    }
+    event_handler.focus_event(focused);
+}
+
+/// `quit_requested_event` for a pending `window::request_quit` or close
+/// button, the quit is ordered unless the event handler cancels it.
+pub(crate) fn handle_quit_request(event_handler: &mut dyn crate::EventHandler) {
+    let mut d = crate::native_display().lock().unwrap();
+    if d.quit_requested && !d.quit_ordered {
+        drop(d);
+        event_handler.quit_requested_event();
+        let mut d = crate::native_display().lock().unwrap();
+        if d.quit_requested {
+            d.quit_ordered = true;
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+static SIGTERM_RECEIVED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// SIGTERM as the close button, unless the application has a handler of
+/// its own.
+#[cfg(target_os = "linux")]
+pub(crate) fn handle_sigterm() {
+    extern "C" fn handler(_: libc::c_int) {
+        SIGTERM_RECEIVED.store(true, std::sync::atomic::Ordering::Relaxed);
+        // the signal could be handled by any thread, the main loop could
+        // be waiting in poll in another one
+        event_wait::wake_from_signal();
+    }
+    unsafe {
+        let previous = libc::signal(libc::SIGTERM, handler as libc::sighandler_t);
+        if previous != libc::SIG_DFL {
+            libc::signal(libc::SIGTERM, previous);
+        }
+    }
+}
+
+/// The PRIME variables for `conf.platform.linux_gpu`, before libGL or libEGL
+/// is loaded. The ones the user set are kept.
+/// Only done while the process has a single thread, `set_var` is a data
+/// race with any other thread reading the environment.
+#[cfg(target_os = "linux")]
+pub(crate) fn set_gpu_hint(gpu: crate::conf::LinuxGpu) {
+    if gpu == crate::conf::LinuxGpu::Default {
+        return;
+    }
+    let threads = std::fs::read_dir("/proc/self/task").map_or(0, |tasks| tasks.count());
+    if threads != 1 {
+        eprintln!("Threads were started before miniquad, ignoring linux_gpu");
+        eprintln!("Export DRI_PRIME and the other PRIME variables instead");
+        return;
+    }
+
+    let set_default = |name: &str, value: &str| {
+        if std::env::var_os(name).is_none() {
+            std::env::set_var(name, value);
+        }
+    };
+    // the NVIDIA driver is loaded, its offload does nothing otherwise
+    let nvidia = std::path::Path::new("/proc/driver/nvidia").exists();
+    match gpu {
+        crate::conf::LinuxGpu::Default => {}
+        crate::conf::LinuxGpu::Integrated => {
+            set_default("DRI_PRIME", "0");
+            if nvidia {
+                // the NVIDIA GPU can be the primary one
+                set_default("__GLX_VENDOR_LIBRARY_NAME", "mesa");
+                let mesa_egl = "/usr/share/glvnd/egl_vendor.d/50_mesa.json";
+                if std::path::Path::new(mesa_egl).exists() {
+                    set_default("__EGL_VENDOR_LIBRARY_FILENAMES", mesa_egl);
+                }
+            }
+        }
+        crate::conf::LinuxGpu::Discrete => {
+            set_default("DRI_PRIME", "1");
+            if nvidia {
+                // enough for EGL, GLX needs the vendor too
+                set_default("__NV_PRIME_RENDER_OFFLOAD", "1");
+                set_default("__GLX_VENDOR_LIBRARY_NAME", "nvidia");
+            }
+        }
+    }
+}
+
+/// `quit_requested` if there was a SIGTERM since the last call, the main
+/// loops waiting for events are woken up by the handler.
+#[cfg(target_os = "linux")]
+pub(crate) fn take_sigterm() {
+    if SIGTERM_RECEIVED.swap(false, std::sync::atomic::Ordering::Relaxed) {
+        crate::native_display().lock().unwrap().quit_requested = true;
+    }
+}
+
+/// `window::event_timestamp`, before the input events from the platform.
+pub(crate) fn update_event_timestamp(timestamp: f64) {
+    let mut d = crate::native_display().lock().unwrap();
+    d.event_timestamp = timestamp;
+}
+
+/// For the 32 bit millisecond timestamps of X11, Wayland and Windows, which
+/// wrap around every 49 days. The time of the event is the closest to now
+/// with these bits, X11 server time can be a little ahead.
+pub(crate) fn update_event_timestamp_ms(time: u32) {
+    let now = (timestamp_now() * 1000.) as u64;
+    let ago = (now as u32).wrapping_sub(time) as i32;
+    update_event_timestamp((now as f64 - ago as f64) / 1000.);
+}
+
+/// `window::event_timestamp_now`, the clock the platform timestamps the
+/// events with.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn timestamp_now() -> f64 {
+    let mut time: libc::timespec = unsafe { std::mem::zeroed() };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut time) };
+    time.tv_sec as f64 + time.tv_nsec as f64 / 1e9
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn timestamp_now() -> f64 {
+    unsafe { winapi::um::sysinfoapi::GetTickCount64() as f64 / 1000. }
+}
+
+#[cfg(target_vendor = "apple")]
+pub(crate) fn timestamp_now() -> f64 {
+    use crate::native::apple::frameworks::*;
+
+    unsafe {
+        let process_info: ObjcId = msg_send![class!(NSProcessInfo), processInfo];
+        msg_send![process_info, systemUptime]
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn timestamp_now() -> f64 {
+    unsafe { wasm::sapp_timestamp_now() }
+}
+
+// "en_US.UTF-8" to "en-US", None for "C" and "POSIX"
+#[cfg(target_os = "linux")]
+fn posix_locale_to_tag(locale: &str) -> Option<String> {
+    let language = locale.split(|c| c == '.' || c == '@').next().unwrap();
+    if language.is_empty() || language == "C" || language == "POSIX" {
+        return None;
+    }
+    Some(language.replace('_', "-"))
+}
+
+/// `window::locale`. LANGUAGE is the list of the languages preferred, but
+/// like gettext only if the locale is not "C".
+#[cfg(target_os = "linux")]
+pub(crate) fn locale() -> Vec<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty());
+    let locale = match locale.as_deref().and_then(posix_locale_to_tag) {
+        Some(locale) => locale,
+        None => return vec![],
+    };
+    let mut tags: Vec<String> = vec![];
+    let languages = std::env::var("LANGUAGE").unwrap_or_default();
+    let languages = languages.split(':').filter_map(posix_locale_to_tag);
+    for tag in languages.chain(Some(locale)) {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+#[cfg(target_os = "android")]
+pub(crate) fn locale() -> Vec<String> {
+    unsafe { android::locale() }
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn locale() -> Vec<String> {
+    use winapi::{shared::minwindef::DWORD, um::winnls::GetUserPreferredUILanguages};
+
+    // not in winapi 0.3
+    const MUI_LANGUAGE_NAME: DWORD = 0x8;
+
+    let mut count = 0;
+    let mut len = 0;
+    unsafe {
+        let ok = GetUserPreferredUILanguages(
+            MUI_LANGUAGE_NAME,
+            &mut count,
+            std::ptr::null_mut(),
+            &mut len,
+        );
+        if ok == 0 {
+            return vec![];
+        }
+        // the tags, each one null terminated, and a null at the end
+        let mut languages = vec![0u16; len as usize];
+        let ok = GetUserPreferredUILanguages(
+            MUI_LANGUAGE_NAME,
+            &mut count,
+            languages.as_mut_ptr(),
+            &mut len,
+        );
+        if ok == 0 {
+            return vec![];
+        }
+        languages
+            .split(|&c| c == 0)
+            .filter(|tag| !tag.is_empty())
+            .map(String::from_utf16_lossy)
+            .collect()
+    }
+}
+
+#[cfg(target_vendor = "apple")]
+pub(crate) fn locale() -> Vec<String> {
+    use crate::native::apple::{apple_util::nsstring_to_string, frameworks::*};
+
+    unsafe {
+        let languages: ObjcId = msg_send![class!(NSLocale), preferredLanguages];
+        let count: u64 = msg_send![languages, count];
+        (0..count)
+            .map(|i| {
+                let language: ObjcId = msg_send![languages, objectAtIndex: i];
+                nsstring_to_string(language)
+            })
+            .collect()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn locale() -> Vec<String> {
+    unsafe {
+        let len = wasm::sapp_locale(std::ptr::null_mut(), 0);
+        let mut languages = vec![0u8; len];
+        wasm::sapp_locale(languages.as_mut_ptr(), len);
+        String::from_utf8_lossy(&languages)
+            .split(',')
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| tag.to_owned())
+            .collect()
+    }
+}
+
+/// A gamepad id for `gamepad_event`, new for every connection.
+pub(crate) fn new_gamepad_id() -> u64 {
+    static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+}
+
+/// `window::gamepads` and the `gamepad_event`s from the last state of the
+/// gamepad to `state`, None when it is disconnected.
+pub(crate) fn update_gamepad(
+    id: u64,
+    state: Option<&crate::GamepadState>,
+    event_handler: &mut dyn crate::EventHandler,
+) {
+    use crate::{GamepadAxis::*, GamepadButton::*, GamepadEvent};
+
+    // in the order of the arrays of GamepadState
+    const BUTTONS: [crate::GamepadButton; 15] = [
+        South,
+        East,
+        West,
+        North,
+        LeftShoulder,
+        RightShoulder,
+        Select,
+        Start,
+        Guide,
+        LeftStick,
+        RightStick,
+        DPadUp,
+        DPadDown,
+        DPadLeft,
+        DPadRight,
+    ];
+    const AXES: [crate::GamepadAxis; 6] = [LeftX, LeftY, RightX, RightY, LeftTrigger, RightTrigger];
+
+    let mut d = crate::native_display().lock().unwrap();
+    let old = match state {
+        Some(state) if d.gamepads.get(&id) == Some(state) => return,
+        Some(state) => d.gamepads.insert(id, state.clone()),
+        None => d.gamepads.remove(&id),
+    };
+    drop(d);
+    let state = match state {
+        Some(state) => state,
+        None => {
+            if old.is_some() {
+                event_handler.gamepad_event(id, GamepadEvent::Disconnected);
+            }
+            return;
+        }
+    };
+    if old.is_none() {
+        event_handler.gamepad_event(id, GamepadEvent::Connected);
+    }
+    let old = old.unwrap_or_default();
+    for button in BUTTONS {
+        match (old.button(button), state.button(button)) {
+            (false, true) => event_handler.gamepad_event(id, GamepadEvent::ButtonDown(button)),
+            (true, false) => event_handler.gamepad_event(id, GamepadEvent::ButtonUp(button)),
+            _ => {}
+        }
+    }
+    for axis in AXES {
+        if old.axis(axis) != state.axis(axis) {
+            let value = state.axis(axis);
+            event_handler.gamepad_event(id, GamepadEvent::AxisChanged(axis, value));
+        }
+    }
 }
 
 pub mod module;
@@ -102,9 +1032,27 @@ pub mod macos;
 #[cfg(target_os = "ios")]
 pub mod ios;
 
-#[cfg(any(target_os = "android", target_os = "linux"))]
+#[cfg(any(target_os = "android", target_os = "linux", target_os = "windows"))]
 pub mod egl;
 
+#[cfg(target_os = "linux")]
+pub mod headless;
+
+#[cfg(not(any(target_arch = "wasm32", target_os = "ios")))]
+pub mod frame_limiter;
+
+#[cfg(target_os = "linux")]
+pub mod event_wait;
+
+#[cfg(target_os = "linux")]
+pub mod evdev;
+
+#[cfg(target_os = "linux")]
+pub mod dbus;
+
+#[cfg(target_os = "linux")]
+pub mod power_supply;
+
 // there is no glGetProcAddr on webgl, so its impossible to make "gl" module work
 // on macos.. well, there is, but way easier to just statically link to gl
 #[cfg(not(target_arch = "wasm32"))]
@@ -114,3 +1062,45 @@ pub mod gl;
 pub use wasm::webgl as gl;
 
 pub mod query_stab;
+
+pub mod scancodes;
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("/tmp/a%20b"), b"/tmp/a b");
+        assert_eq!(percent_decode("/%C3%A9t%C3%A9"), "/été".as_bytes());
+        // not an escape, kept as is
+        assert_eq!(percent_decode("/100%"), b"/100%");
+        assert_eq!(percent_decode("/%zz%2"), b"/%zz%2");
+    }
+
+    #[test]
+    fn uri_list() {
+        let list = b"# a comment\r\n\
+                     file:///home/user/a%20b.png\r\n\
+                     file://hostname/tmp/c.txt\r\n\
+                     https://example.com/d.txt\r\n";
+        let files = DroppedFiles::from_uri_list(list);
+        assert_eq!(
+            files.paths,
+            [
+                PathBuf::from("/home/user/a b.png"),
+                PathBuf::from("/tmp/c.txt")
+            ]
+        );
+        assert!(files.bytes.is_empty());
+    }
+
+    #[test]
+    fn empty_uri_list() {
+        assert!(DroppedFiles::from_uri_list(b"").paths.is_empty());
+        assert!(DroppedFiles::from_uri_list(b"# only a comment\n")
+            .paths
+            .is_empty());
+    }
+}
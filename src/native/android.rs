@@ -1,15 +1,22 @@
 use crate::{
-    event::{EventHandler, KeyCode, KeyMods, TouchPhase},
+    conf::{AndroidCutoutMode, Orientation},
+    event::{
+        EventHandler, GamepadAxis, GamepadButton, GamepadState, KeyCode, KeyMods, ScanCode,
+        TouchDetails, TouchPhase,
+    },
     native::{
         egl::{self, LibEgl},
-        NativeDisplayData,
+        frame_limiter::FrameLimiter,
+        scancodes, NativeDisplayData,
     },
+    KeyboardType, PowerState,
 };
 
-use std::{cell::RefCell, sync::mpsc, thread};
+use std::{cell::RefCell, collections::HashMap, sync::mpsc, thread, time::Duration};
 
 pub use crate::native::gl::{self, *};
 
+mod choreographer;
 mod keycodes;
 
 pub use ndk_sys;
@@ -51,23 +58,60 @@ enum Message {
         window: *mut ndk_sys::ANativeWindow,
     },
     SurfaceDestroyed,
+    // of the input events after it, SystemClock.uptimeMillis
+    EventTimestamp {
+        milliseconds: i64,
+    },
     Touch {
         phase: TouchPhase,
         touch_id: u64,
         x: f32,
         y: f32,
+        details: TouchDetails,
     },
     Character {
         character: u32,
     },
     KeyDown {
         keycode: KeyCode,
+        scancode: ScanCode,
+        keymods: KeyMods,
+        repeat: bool,
     },
     KeyUp {
         keycode: KeyCode,
+        scancode: ScanCode,
+        keymods: KeyMods,
+    },
+    GamepadConnected {
+        device_id: i32,
+        name: String,
+    },
+    GamepadDisconnected {
+        device_id: i32,
     },
+    GamepadButton {
+        device_id: i32,
+        button: GamepadButton,
+        down: bool,
+    },
+    GamepadAxes {
+        device_id: i32,
+        axes: [(GamepadAxis, f32); 6],
+        hat: (f32, f32),
+    },
+    PowerChanged(crate::PowerInfo),
+    KeyboardInsets(f32),
+    SafeAreaInsets(crate::SafeAreaInsets),
+    // onStart and onStop, the activity is visible between them
+    Start,
+    Stop,
     Pause,
     Resume,
+    LowMemory,
+    SaveState,
+    BackPressed,
+    BackProgress(f32),
     Destroy,
 }
 unsafe impl Send for Message {}
@@ -131,12 +175,21 @@ struct MainThreadState {
     egl_display: egl::EGLDisplay,
     egl_config: egl::EGLConfig,
     egl_context: egl::EGLContext,
+    gles_version: crate::conf::GlesVersion,
+    gl_debug: bool,
+    // for the surfaces of every new window
+    surface_attributes: Vec<u32>,
     surface: egl::EGLSurface,
+    // the interval is per surface, the new ones get it again
+    swap_interval: i32,
     window: *mut ndk_sys::ANativeWindow,
     event_handler: Box<dyn EventHandler>,
     quit: bool,
     fullscreen: bool,
-    keymods: KeyMods,
+    immersive: bool,
+    touch_ids: crate::native::TouchIds,
+    // the gamepad id and state, by InputDevice id
+    gamepads: HashMap<i32, (u64, GamepadState)>,
 }
 
 impl MainThreadState {
@@ -164,7 +217,7 @@ impl MainThreadState {
             self.egl_display,
             self.egl_config,
             window as _,
-            std::ptr::null_mut(),
+            self.surface_attributes.as_ptr() as _,
         );
 
         assert!(!self.surface.is_null());
@@ -176,7 +229,47 @@ impl MainThreadState {
             self.egl_context,
         );
 
+        if res == 0 && (self.libegl.eglGetError.unwrap())() as u32 == egl::EGL_CONTEXT_LOST {
+            self.recreate_context();
+            return;
+        }
         assert!(res != 0);
+        egl::swap_interval(&mut self.libegl, self.egl_display, self.swap_interval);
+    }
+
+    // all GL objects are gone with the lost context, the best we can do is a new
+    // context and letting the application know
+    unsafe fn recreate_context(&mut self) {
+        self.event_handler.context_lost();
+
+        (self.libegl.eglDestroyContext.unwrap())(self.egl_display, self.egl_context);
+        self.egl_context = egl::create_context(
+            &mut self.libegl,
+            self.egl_display,
+            self.egl_config,
+            self.gles_version,
+            self.gl_debug,
+        )
+        .expect("Cant create EGL context");
+        if !self.surface.is_null() {
+            let res = (self.libegl.eglMakeCurrent.unwrap())(
+                self.egl_display,
+                self.surface,
+                self.surface,
+                self.egl_context,
+            );
+            assert!(res != 0);
+        }
+
+        self.event_handler.context_restored();
+    }
+
+    // the gamepads are known from the InputDeviceListener, but the events of
+    // a device might come first
+    fn gamepad(&mut self, device_id: i32) -> &mut (u64, GamepadState) {
+        self.gamepads
+            .entry(device_id)
+            .or_insert_with(|| (crate::native::new_gamepad_id(), Default::default()))
     }
 
     fn process_message(&mut self, msg: Message) {
@@ -201,44 +294,104 @@ impl MainThreadState {
                     d.screen_width = width as _;
                     d.screen_height = height as _;
                 }
+                crate::native::update_orientation(&mut *self.event_handler);
                 self.event_handler.resize_event(width as _, height as _);
             }
+            Message::EventTimestamp { milliseconds } => {
+                crate::native::update_event_timestamp(milliseconds as f64 / 1000.);
+            }
             Message::Touch {
                 phase,
                 touch_id,
                 x,
                 y,
+                details,
             } => {
-                self.event_handler.touch_event(phase, touch_id, x, y);
+                let id = self.touch_ids.get(phase, touch_id);
+                self.event_handler
+                    .touch_details_event(phase, id, x, y, details);
             }
             Message::Character { character } => {
-                if let Some(character) = char::from_u32(character) {
+                if let Some(character) = char::from_u32(character).filter(|c| !c.is_control()) {
                     self.event_handler
                         .char_event(character, Default::default(), false);
                 }
             }
-            Message::KeyDown { keycode } => {
-                match keycode {
-                    KeyCode::LeftShift | KeyCode::RightShift => self.keymods.shift = true,
-                    KeyCode::LeftControl | KeyCode::RightControl => self.keymods.ctrl = true,
-                    KeyCode::LeftAlt | KeyCode::RightAlt => self.keymods.alt = true,
-                    KeyCode::LeftSuper | KeyCode::RightSuper => self.keymods.logo = true,
-                    _ => {}
+            Message::KeyDown {
+                keycode,
+                scancode,
+                keymods,
+                repeat,
+            } => {
+                crate::native::update_keymods(keymods);
+                let event_handler = &mut *self.event_handler;
+                crate::native::key_down(keycode, scancode, keymods, repeat, event_handler);
+            }
+            Message::KeyUp {
+                keycode,
+                scancode,
+                keymods,
+            } => {
+                crate::native::update_keymods(keymods);
+                crate::native::key_up(keycode, scancode, keymods, &mut *self.event_handler);
+            }
+            Message::GamepadConnected { device_id, name } => {
+                let (id, state) = self.gamepad(device_id);
+                state.name = name;
+                let (id, state) = (*id, state.clone());
+                crate::native::update_gamepad(id, Some(&state), &mut *self.event_handler);
+            }
+            Message::GamepadDisconnected { device_id } => {
+                if let Some((id, _)) = self.gamepads.remove(&device_id) {
+                    crate::native::update_gamepad(id, None, &mut *self.event_handler);
                 }
-                self.event_handler
-                    .key_down_event(keycode, self.keymods, false);
             }
-            Message::KeyUp { keycode } => {
-                match keycode {
-                    KeyCode::LeftShift | KeyCode::RightShift => self.keymods.shift = false,
-                    KeyCode::LeftControl | KeyCode::RightControl => self.keymods.ctrl = false,
-                    KeyCode::LeftAlt | KeyCode::RightAlt => self.keymods.alt = false,
-                    KeyCode::LeftSuper | KeyCode::RightSuper => self.keymods.logo = false,
-                    _ => {}
+            Message::GamepadButton {
+                device_id,
+                button,
+                down,
+            } => {
+                let (id, state) = self.gamepad(device_id);
+                state.set_button(button, down);
+                let (id, state) = (*id, state.clone());
+                crate::native::update_gamepad(id, Some(&state), &mut *self.event_handler);
+            }
+            Message::GamepadAxes {
+                device_id,
+                axes,
+                hat: (hat_x, hat_y),
+            } => {
+                let (id, state) = self.gamepad(device_id);
+                for (axis, value) in axes {
+                    state.set_axis(axis, value);
                 }
-                self.event_handler.key_up_event(keycode, self.keymods);
+                state.set_button(GamepadButton::DPadLeft, hat_x < -0.5);
+                state.set_button(GamepadButton::DPadRight, hat_x > 0.5);
+                state.set_button(GamepadButton::DPadUp, hat_y < -0.5);
+                state.set_button(GamepadButton::DPadDown, hat_y > 0.5);
+                let (id, state) = (*id, state.clone());
+                crate::native::update_gamepad(id, Some(&state), &mut *self.event_handler);
+            }
+            Message::PowerChanged(power_info) => {
+                crate::native::update_power_info(power_info, &mut *self.event_handler);
+            }
+            Message::KeyboardInsets(height) => {
+                crate::native::update_keyboard_height(height, &mut *self.event_handler);
+            }
+            Message::SafeAreaInsets(insets) => {
+                crate::native::update_safe_area_insets(insets, &mut *self.event_handler);
+            }
+            Message::Start => {
+                crate::native::update_occluded(false, &mut *self.event_handler);
+            }
+            Message::Stop => {
+                crate::native::update_occluded(true, &mut *self.event_handler);
+            }
+            Message::Pause => {
+                self.event_handler.app_paused();
+                crate::native::update_focus(false, &mut *self.event_handler);
+                self.event_handler.window_minimized_event()
             }
-            Message::Pause => self.event_handler.window_minimized_event(),
             Message::Resume => {
                 if self.fullscreen {
                     unsafe {
@@ -246,23 +399,73 @@ impl MainThreadState {
                         set_full_screen(env, true);
                     }
                 }
+                if self.immersive {
+                    unsafe {
+                        let env = attach_jni_env();
+                        set_immersive(env, true);
+                    }
+                }
 
+                crate::native::update_focus(true, &mut *self.event_handler);
+                self.event_handler.app_resumed();
                 self.event_handler.window_restored_event()
             }
+            Message::LowMemory => self.event_handler.low_memory(),
+            Message::SaveState => self.event_handler.save_state(),
+            Message::BackPressed => {
+                crate::native_display().lock().unwrap().back_requested = true;
+                self.event_handler.back_requested_event();
+                let mut d = crate::native_display().lock().unwrap();
+                let finish = std::mem::take(&mut d.back_requested);
+                drop(d);
+                if finish {
+                    unsafe {
+                        let env = attach_jni_env();
+                        ndk_utils::call_void_method!(env, ACTIVITY, "finish", "()V");
+                    }
+                }
+            }
+            Message::BackProgress(progress) => self.event_handler.back_progress_event(progress),
             Message::Destroy => {
+                // Android does not wait, cancel_quit is too late here
+                let mut d = crate::native_display().lock().unwrap();
+                if !d.quit_ordered {
+                    d.quit_requested = true;
+                    drop(d);
+                    self.event_handler.quit_requested_event();
+                }
                 self.quit = true;
             }
         }
     }
 
     fn frame(&mut self) {
+        // order_quit finishes the Activity, the main loop ends here
+        crate::native::handle_quit_request(&mut *self.event_handler);
+        if crate::native_display().lock().unwrap().quit_ordered {
+            self.quit = true;
+            unsafe {
+                let env = attach_jni_env();
+                ndk_utils::call_void_method!(env, ACTIVITY, "finish", "()V");
+            }
+            return;
+        }
+
+        crate::native::dispatch_custom_events(&mut *self.event_handler);
+        if crate::native_display().lock().unwrap().frames_paused() {
+            return;
+        }
         self.event_handler.update();
 
         if self.surface.is_null() == false {
             self.event_handler.draw();
 
             unsafe {
-                (self.libegl.eglSwapBuffers.unwrap())(self.egl_display, self.surface);
+                if (self.libegl.eglSwapBuffers.unwrap())(self.egl_display, self.surface) == 0
+                    && (self.libegl.eglGetError.unwrap())() as u32 == egl::EGL_CONTEXT_LOST
+                {
+                    self.recreate_context();
+                }
             }
         }
     }
@@ -278,7 +481,14 @@ impl MainThreadState {
                     }
                     self.fullscreen = fullscreen;
                 }
-                ShowKeyboard(show) => unsafe {
+                SetSwapInterval(interval) => {
+                    if egl::swap_interval(&mut self.libegl, self.egl_display, interval) {
+                        self.swap_interval = interval;
+                    } else {
+                        eprintln!("Failed to set swap interval {}", interval);
+                    }
+                }
+                ShowKeyboard(show) | SetTextInputEnabled(show) => unsafe {
                     let env = attach_jni_env();
                     ndk_utils::call_void_method!(
                         env,
@@ -288,6 +498,83 @@ impl MainThreadState {
                         show as i32
                     );
                 },
+                SetKeyboardType(keyboard_type) => unsafe {
+                    // EditorInfo.inputType, TYPE_NULL for the key events
+                    let input_type = match keyboard_type {
+                        KeyboardType::Text => 0,
+                        KeyboardType::Number => 0x2,
+                        KeyboardType::Decimal => 0x2 | 0x2000,
+                        KeyboardType::Phone => 0x3,
+                        KeyboardType::Email => 0x1 | 0x20,
+                        KeyboardType::Url => 0x1 | 0x10,
+                    };
+                    let env = attach_jni_env();
+                    ndk_utils::call_void_method!(
+                        env,
+                        ACTIVITY,
+                        "setKeyboardType",
+                        "(I)V",
+                        input_type as i32
+                    );
+                },
+                LockOrientation(orientation) => unsafe {
+                    let env = attach_jni_env();
+                    set_orientation(env, orientation);
+                },
+                SetFullscreenImmersive(immersive) => unsafe {
+                    let env = attach_jni_env();
+                    set_immersive(env, immersive);
+                    self.immersive = immersive;
+                },
+                SetSystemBarsColor((r, g, b, a)) => unsafe {
+                    // ARGB
+                    let color = [a, r, g, b]
+                        .iter()
+                        .fold(0u32, |argb, c| argb << 8 | (c.clamp(0., 1.) * 255.) as u32);
+                    let env = attach_jni_env();
+                    ndk_utils::call_void_method!(
+                        env,
+                        ACTIVITY,
+                        "setSystemBarsColor",
+                        "(I)V",
+                        color as i32
+                    );
+                },
+                SetKeepScreenOn(keep_on) => unsafe {
+                    let env = attach_jni_env();
+                    ndk_utils::call_void_method!(
+                        env,
+                        ACTIVITY,
+                        "setKeepScreenOn",
+                        "(Z)V",
+                        keep_on as i32
+                    );
+                },
+                SetGamepadRumble {
+                    id,
+                    low_frequency,
+                    high_frequency,
+                    duration,
+                } => {
+                    let device_id = self
+                        .gamepads
+                        .iter()
+                        .find(|(_, (other, _))| *other == id)
+                        .map(|(&device_id, _)| device_id);
+                    if let Some(device_id) = device_id {
+                        let env = attach_jni_env();
+                        ndk_utils::call_void_method!(
+                            env,
+                            ACTIVITY,
+                            "rumbleGamepad",
+                            "(IIII)V",
+                            device_id,
+                            (low_frequency * 255.) as i32,
+                            (high_frequency * 255.) as i32,
+                            duration.as_millis().min(i32::MAX as _) as i32
+                        );
+                    }
+                }
                 _ => {}
             }
         }
@@ -325,6 +612,17 @@ pub unsafe fn attach_jni_env() -> *mut ndk_sys::JNIEnv {
     env
 }
 
+/// `window::locale`, from the configuration of the resources.
+pub unsafe fn locale() -> Vec<String> {
+    let env = attach_jni_env();
+    let tags = ndk_utils::call_object_method!(env, ACTIVITY, "getLocales", "()Ljava/lang/String;");
+    ndk_utils::get_utf_str!(env, tags)
+        .split(',')
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| tag.to_owned())
+        .collect()
+}
+
 pub struct AndroidClipboard {}
 impl AndroidClipboard {
     pub fn new() -> AndroidClipboard {
@@ -355,10 +653,25 @@ where
         }));
     }
 
+    // LayoutParams.LAYOUT_IN_DISPLAY_CUTOUT_MODE_*
+    let cutout_mode = match conf.platform.android_cutout_mode {
+        AndroidCutoutMode::Default => 0,
+        AndroidCutoutMode::ShortEdges => 1,
+        AndroidCutoutMode::Never => 2,
+        AndroidCutoutMode::Always => 3,
+    };
+    {
+        let env = attach_jni_env();
+        ndk_utils::call_void_method!(env, ACTIVITY, "setCutoutMode", "(I)V", cutout_mode as i32);
+    }
     if conf.fullscreen {
         let env = attach_jni_env();
         set_full_screen(env, true);
     }
+    if conf.supported_orientations != Orientation::Any {
+        let env = attach_jni_env();
+        set_orientation(env, conf.supported_orientations);
+    }
 
     // yeah, just adding Send to outer F will do it, but it will brake the API
     // in other backends
@@ -396,6 +709,8 @@ where
             std::ptr::null_mut(), /* EGL_DEFAULT_DISPLAY */
             conf.platform.framebuffer_alpha,
             conf.sample_count,
+            conf.platform.gles_version,
+            conf.platform.gl_debug,
         )
         .expect("Cant create EGL context");
 
@@ -407,21 +722,30 @@ where
             libegl.eglGetProcAddress.expect("non-null function pointer")(name.as_ptr() as _)
         });
 
+        let surface_attributes =
+            egl::window_surface_attributes(&libegl, egl_display, conf.platform.srgb_framebuffer);
         let surface = (libegl.eglCreateWindowSurface.unwrap())(
             egl_display,
             egl_config,
             window as _,
-            std::ptr::null_mut(),
+            surface_attributes.as_ptr() as _,
         );
 
         if (libegl.eglMakeCurrent.unwrap())(egl_display, surface, surface, egl_context) == 0 {
             panic!();
         }
+        let swap_interval = conf.platform.swap_interval.unwrap_or(1);
+        egl::swap_interval(&mut libegl, egl_display, swap_interval);
 
         let (tx, requests_rx) = std::sync::mpsc::channel();
         let clipboard = Box::new(AndroidClipboard::new());
         crate::set_display(NativeDisplayData {
             high_dpi: conf.high_dpi,
+            swap_control: libegl.eglSwapInterval.is_some(),
+            target_fps: conf.max_fps,
+            background_mode: conf.background_mode,
+            supported_orientations: conf.supported_orientations,
+            egl: true,
             ..NativeDisplayData::new(screen_width as _, screen_height as _, tx, clipboard)
         });
 
@@ -431,18 +755,23 @@ where
             egl_display,
             egl_config,
             egl_context,
+            gles_version: conf.platform.gles_version,
+            gl_debug: conf.platform.gl_debug,
+            surface_attributes,
             surface,
+            swap_interval,
             window,
             event_handler,
             quit: false,
             fullscreen: conf.fullscreen,
-            keymods: KeyMods {
-                shift: false,
-                ctrl: false,
-                alt: false,
-                logo: false,
-            },
+            immersive: false,
+            touch_ids: Default::default(),
+            gamepads: HashMap::new(),
         };
+        let mut frame_limiter = FrameLimiter::new();
+        let choreographer = choreographer::Choreographer::new();
+        // the vsync timestamp the next frame is due at, for the target fps
+        let mut next_frame = None;
 
         while !s.quit {
             while let Ok(request) = requests_rx.try_recv() {
@@ -454,11 +783,42 @@ where
                 s.process_message(msg);
             }
 
-            s.frame();
+            match &choreographer {
+                // without vsync the frames are as fast as they can be
+                Some(choreographer) if s.swap_interval != 0 => {
+                    match choreographer.wait(Duration::from_millis(100)) {
+                        Some(vsync) => {
+                            // the target fps by skipping vsyncs, with some
+                            // slack for the jitter of the vsync timestamps
+                            let frame_rate = crate::native_display().lock().unwrap().frame_rate();
+                            let frame_time = vsync.frame_time_nanos;
+                            if next_frame.map_or(false, |next| frame_time < next - 2_000_000) {
+                                continue;
+                            }
+                            next_frame = frame_rate
+                                .filter(|&fps| fps > 0)
+                                .map(|fps| frame_time + 1_000_000_000 / fps as i64);
+                            crate::native_display().lock().unwrap().frame_deadline =
+                                vsync.deadline_nanos.map(|deadline| deadline as f64 / 1e9);
+                            s.frame();
+                        }
+                        // no vsync while the screen is off, the events still
+                        // get their frames
+                        None => s.frame(),
+                    }
+                }
+                _ => {
+                    crate::native_display().lock().unwrap().frame_deadline = None;
+                    s.frame();
+                    frame_limiter.wait();
 
-            thread::yield_now();
+                    thread::yield_now();
+                }
+            }
         }
 
+        // the Drop of the event handler with its gl resources still there
+        drop(s.event_handler);
         (s.libegl.eglMakeCurrent.unwrap())(
             s.egl_display,
             std::ptr::null_mut(),
@@ -503,6 +863,22 @@ unsafe extern "C" fn Java_quad_1native_QuadNative_activityOnResume(
     send_message(Message::Resume);
 }
 
+#[no_mangle]
+unsafe extern "C" fn Java_quad_1native_QuadNative_activityOnStart(
+    _: *mut ndk_sys::JNIEnv,
+    _: ndk_sys::jobject,
+) {
+    send_message(Message::Start);
+}
+
+#[no_mangle]
+unsafe extern "C" fn Java_quad_1native_QuadNative_activityOnStop(
+    _: *mut ndk_sys::JNIEnv,
+    _: ndk_sys::jobject,
+) {
+    send_message(Message::Stop);
+}
+
 #[no_mangle]
 unsafe extern "C" fn Java_quad_1native_QuadNative_activityOnPause(
     _: *mut ndk_sys::JNIEnv,
@@ -511,6 +887,65 @@ unsafe extern "C" fn Java_quad_1native_QuadNative_activityOnPause(
     send_message(Message::Pause);
 }
 
+#[no_mangle]
+unsafe extern "C" fn Java_quad_1native_QuadNative_activityOnPowerChanged(
+    _: *mut ndk_sys::JNIEnv,
+    _: ndk_sys::jobject,
+    present: ndk_sys::jboolean,
+    level: ndk_sys::jfloat,
+    status: ndk_sys::jint,
+    plugged: ndk_sys::jboolean,
+    power_saver: ndk_sys::jboolean,
+) {
+    // BatteryManager.BATTERY_STATUS_*
+    let state = match status {
+        _ if present == 0 => PowerState::NoBattery,
+        2 => PowerState::Charging,
+        3 => PowerState::OnBattery,
+        4 | 5 if plugged != 0 => PowerState::Charged,
+        4 | 5 => PowerState::OnBattery,
+        _ => PowerState::Unknown,
+    };
+    send_message(Message::PowerChanged(crate::PowerInfo {
+        state,
+        battery_level: (present != 0 && level >= 0.).then(|| level),
+        power_saver: power_saver != 0,
+    }));
+}
+
+#[no_mangle]
+unsafe extern "C" fn Java_quad_1native_QuadNative_activityOnLowMemory(
+    _: *mut ndk_sys::JNIEnv,
+    _: ndk_sys::jobject,
+) {
+    send_message(Message::LowMemory);
+}
+
+#[no_mangle]
+unsafe extern "C" fn Java_quad_1native_QuadNative_activityOnSaveState(
+    _: *mut ndk_sys::JNIEnv,
+    _: ndk_sys::jobject,
+) {
+    send_message(Message::SaveState);
+}
+
+#[no_mangle]
+unsafe extern "C" fn Java_quad_1native_QuadNative_activityOnBackPressed(
+    _: *mut ndk_sys::JNIEnv,
+    _: ndk_sys::jobject,
+) {
+    send_message(Message::BackPressed);
+}
+
+#[no_mangle]
+unsafe extern "C" fn Java_quad_1native_QuadNative_activityOnBackProgress(
+    _: *mut ndk_sys::JNIEnv,
+    _: ndk_sys::jobject,
+    progress: ndk_sys::jfloat,
+) {
+    send_message(Message::BackProgress(progress));
+}
+
 #[no_mangle]
 unsafe extern "C" fn Java_quad_1native_QuadNative_activityOnDestroy(
     _: *mut ndk_sys::JNIEnv,
@@ -562,6 +997,11 @@ extern "C" fn Java_quad_1native_QuadNative_surfaceOnTouch(
     action: ndk_sys::jint,
     x: ndk_sys::jfloat,
     y: ndk_sys::jfloat,
+    pressure: ndk_sys::jfloat,
+    touch_major: ndk_sys::jfloat,
+    touch_minor: ndk_sys::jfloat,
+    device_id: ndk_sys::jint,
+    event_time: ndk_sys::jlong,
 ) {
     let phase = match action {
         0 => TouchPhase::Moved,
@@ -570,12 +1010,21 @@ extern "C" fn Java_quad_1native_QuadNative_surfaceOnTouch(
         3 => TouchPhase::Cancelled,
         x => panic!("Unsupported touch phase: {}", x),
     };
+    send_message(Message::EventTimestamp {
+        milliseconds: event_time,
+    });
 
     send_message(Message::Touch {
         phase,
         touch_id: touch_id as _,
         x: x as f32,
         y: y as f32,
+        // the axes are diameters, and the pressure can go over 1
+        details: TouchDetails {
+            pressure: Some((pressure as f32).min(1.)),
+            radius: (touch_major as f32 / 2., touch_minor as f32 / 2.),
+            device_id: device_id as _,
+        },
     });
 }
 
@@ -584,10 +1033,24 @@ extern "C" fn Java_quad_1native_QuadNative_surfaceOnKeyDown(
     _: *mut ndk_sys::JNIEnv,
     _: ndk_sys::jobject,
     keycode: ndk_sys::jint,
+    scancode: ndk_sys::jint,
+    meta_state: ndk_sys::jint,
+    repeat: ndk_sys::jboolean,
+    event_time: ndk_sys::jlong,
 ) {
+    send_message(Message::EventTimestamp {
+        milliseconds: event_time,
+    });
     let keycode = keycodes::translate_keycode(keycode as _);
-
-    send_message(Message::KeyDown { keycode });
+    let scancode = translate_scancode(keycode, scancode);
+    let keymods = translate_keymods(meta_state);
+
+    send_message(Message::KeyDown {
+        keycode,
+        scancode,
+        keymods,
+        repeat: repeat != 0,
+    });
 }
 
 #[no_mangle]
@@ -595,10 +1058,49 @@ extern "C" fn Java_quad_1native_QuadNative_surfaceOnKeyUp(
     _: *mut ndk_sys::JNIEnv,
     _: ndk_sys::jobject,
     keycode: ndk_sys::jint,
+    scancode: ndk_sys::jint,
+    meta_state: ndk_sys::jint,
+    event_time: ndk_sys::jlong,
 ) {
+    send_message(Message::EventTimestamp {
+        milliseconds: event_time,
+    });
     let keycode = keycodes::translate_keycode(keycode as _);
+    let scancode = translate_scancode(keycode, scancode);
+    let keymods = translate_keymods(meta_state);
 
-    send_message(Message::KeyUp { keycode });
+    send_message(Message::KeyUp {
+        keycode,
+        scancode,
+        keymods,
+    });
+}
+
+// KeyEvent.getMetaState, with the key of the event already in it
+fn translate_keymods(meta_state: ndk_sys::jint) -> KeyMods {
+    const META_SHIFT_ON: i32 = 0x1;
+    const META_ALT_ON: i32 = 0x2;
+    const META_CTRL_ON: i32 = 0x1000;
+    const META_META_ON: i32 = 0x10000;
+    const META_CAPS_LOCK_ON: i32 = 0x100000;
+    const META_NUM_LOCK_ON: i32 = 0x200000;
+
+    KeyMods {
+        shift: meta_state & META_SHIFT_ON != 0,
+        ctrl: meta_state & META_CTRL_ON != 0,
+        alt: meta_state & META_ALT_ON != 0,
+        logo: meta_state & META_META_ON != 0,
+        caps_lock: meta_state & META_CAPS_LOCK_ON != 0,
+        num_lock: meta_state & META_NUM_LOCK_ON != 0,
+    }
+}
+
+// KeyEvent.getScanCode is the evdev code, 0 for the onscreen keyboard
+fn translate_scancode(keycode: KeyCode, scancode: ndk_sys::jint) -> ScanCode {
+    match scancodes::from_evdev(scancode as _) {
+        ScanCode(0) => scancodes::keycode_to_scancode(keycode),
+        scancode => scancode,
+    }
 }
 
 #[no_mangle]
@@ -612,10 +1114,144 @@ extern "C" fn Java_quad_1native_QuadNative_surfaceOnCharacter(
     });
 }
 
+#[no_mangle]
+extern "C" fn Java_quad_1native_QuadNative_surfaceOnKeyboardInsets(
+    _: *mut ndk_sys::JNIEnv,
+    _: ndk_sys::jobject,
+    height: ndk_sys::jint,
+) {
+    send_message(Message::KeyboardInsets(height as f32));
+}
+
+#[no_mangle]
+extern "C" fn Java_quad_1native_QuadNative_surfaceOnSafeAreaInsets(
+    _: *mut ndk_sys::JNIEnv,
+    _: ndk_sys::jobject,
+    left: ndk_sys::jint,
+    top: ndk_sys::jint,
+    right: ndk_sys::jint,
+    bottom: ndk_sys::jint,
+) {
+    send_message(Message::SafeAreaInsets(crate::SafeAreaInsets {
+        left: left as f32,
+        top: top as f32,
+        right: right as f32,
+        bottom: bottom as f32,
+    }));
+}
+
+#[no_mangle]
+extern "C" fn Java_quad_1native_QuadNative_surfaceOnGamepadConnected(
+    env: *mut ndk_sys::JNIEnv,
+    _: ndk_sys::jobject,
+    device_id: ndk_sys::jint,
+    name: ndk_sys::jstring,
+) {
+    let name = unsafe { ndk_utils::get_utf_str!(env, name) }.to_owned();
+    send_message(Message::GamepadConnected { device_id, name });
+}
+
+#[no_mangle]
+extern "C" fn Java_quad_1native_QuadNative_surfaceOnGamepadDisconnected(
+    _: *mut ndk_sys::JNIEnv,
+    _: ndk_sys::jobject,
+    device_id: ndk_sys::jint,
+) {
+    send_message(Message::GamepadDisconnected { device_id });
+}
+
+// KeyEvent.KEYCODE_BUTTON_* and KEYCODE_DPAD_*
+fn translate_gamepad_button(keycode: ndk_sys::jint) -> Option<GamepadButton> {
+    Some(match keycode {
+        19 => GamepadButton::DPadUp,
+        20 => GamepadButton::DPadDown,
+        21 => GamepadButton::DPadLeft,
+        22 => GamepadButton::DPadRight,
+        96 => GamepadButton::South,
+        97 => GamepadButton::East,
+        99 => GamepadButton::West,
+        100 => GamepadButton::North,
+        102 => GamepadButton::LeftShoulder,
+        103 => GamepadButton::RightShoulder,
+        106 => GamepadButton::LeftStick,
+        107 => GamepadButton::RightStick,
+        108 => GamepadButton::Start,
+        109 => GamepadButton::Select,
+        110 => GamepadButton::Guide,
+        _ => return None,
+    })
+}
+
+#[no_mangle]
+extern "C" fn Java_quad_1native_QuadNative_surfaceOnGamepadButton(
+    _: *mut ndk_sys::JNIEnv,
+    _: ndk_sys::jobject,
+    device_id: ndk_sys::jint,
+    keycode: ndk_sys::jint,
+    down: ndk_sys::jboolean,
+) {
+    if let Some(button) = translate_gamepad_button(keycode) {
+        send_message(Message::GamepadButton {
+            device_id,
+            button,
+            down: down != 0,
+        });
+    }
+}
+
+#[no_mangle]
+extern "C" fn Java_quad_1native_QuadNative_surfaceOnGamepadAxes(
+    _: *mut ndk_sys::JNIEnv,
+    _: ndk_sys::jobject,
+    device_id: ndk_sys::jint,
+    x: ndk_sys::jfloat,
+    y: ndk_sys::jfloat,
+    z: ndk_sys::jfloat,
+    rz: ndk_sys::jfloat,
+    hat_x: ndk_sys::jfloat,
+    hat_y: ndk_sys::jfloat,
+    left_trigger: ndk_sys::jfloat,
+    right_trigger: ndk_sys::jfloat,
+) {
+    // the right stick is on Z and RZ, down is positive as everywhere else
+    send_message(Message::GamepadAxes {
+        device_id,
+        axes: [
+            (GamepadAxis::LeftX, x),
+            (GamepadAxis::LeftY, y),
+            (GamepadAxis::RightX, z),
+            (GamepadAxis::RightY, rz),
+            (GamepadAxis::LeftTrigger, left_trigger),
+            (GamepadAxis::RightTrigger, right_trigger),
+        ],
+        hat: (hat_x, hat_y),
+    });
+}
+
 unsafe fn set_full_screen(env: *mut ndk_sys::JNIEnv, fullscreen: bool) {
     ndk_utils::call_void_method!(env, ACTIVITY, "setFullScreen", "(Z)V", fullscreen as i32);
 }
 
+unsafe fn set_immersive(env: *mut ndk_sys::JNIEnv, immersive: bool) {
+    ndk_utils::call_void_method!(env, ACTIVITY, "setImmersive", "(Z)V", immersive as i32);
+}
+
+// ActivityInfo.SCREEN_ORIENTATION_*
+unsafe fn set_orientation(env: *mut ndk_sys::JNIEnv, orientation: Orientation) {
+    let requested_orientation = match orientation {
+        Orientation::Any => -1,
+        Orientation::Portrait => 7,
+        Orientation::Landscape => 6,
+    };
+    ndk_utils::call_void_method!(
+        env,
+        ACTIVITY,
+        "setRequestedOrientation",
+        "(I)V",
+        requested_orientation as i32
+    );
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct android_asset {
@@ -633,7 +1269,7 @@ extern "C" {
     ) -> *mut ndk_sys::AAssetManager;
 }
 
-pub(crate) unsafe fn load_asset(filepath: *const ::std::os::raw::c_char, out: *mut android_asset) {
+unsafe fn asset_manager() -> *mut ndk_sys::AAssetManager {
     let env = attach_jni_env();
 
     let get_method_id = (**env).GetMethodID.unwrap();
@@ -647,7 +1283,11 @@ pub(crate) unsafe fn load_asset(filepath: *const ::std::os::raw::c_char, out: *m
         b"()Landroid/content/res/AssetManager;\0".as_ptr() as _,
     );
     let asset_manager = (call_object_method)(env, ACTIVITY, mid);
-    let mgr = AAssetManager_fromJava(env, asset_manager);
+    AAssetManager_fromJava(env, asset_manager)
+}
+
+pub(crate) unsafe fn load_asset(filepath: *const ::std::os::raw::c_char, out: *mut android_asset) {
+    let mgr = asset_manager();
     let asset = ndk_sys::AAssetManager_open(mgr, filepath, ndk_sys::AASSET_MODE_BUFFER as _);
     if asset.is_null() {
         return;
@@ -662,3 +1302,85 @@ pub(crate) unsafe fn load_asset(filepath: *const ::std::os::raw::c_char, out: *m
         (*out).content = buffer as _;
     }
 }
+
+/// An asset read a part at a time, for `fs::open_file`. Compressed in the
+/// APK it is inflated as it is read, so seeking backwards starts over.
+pub(crate) struct Asset(*mut ndk_sys::AAsset);
+
+// AAsset is for one thread at a time
+unsafe impl Send for Asset {}
+
+impl Asset {
+    pub(crate) fn open(path: &str) -> Option<Asset> {
+        let filename = std::ffi::CString::new(path).ok()?;
+        unsafe {
+            let asset = ndk_sys::AAssetManager_open(
+                asset_manager(),
+                filename.as_ptr(),
+                ndk_sys::AASSET_MODE_STREAMING as _,
+            );
+            if asset.is_null() {
+                return None;
+            }
+            Some(Asset(asset))
+        }
+    }
+
+    pub(crate) fn len(&self) -> u64 {
+        unsafe { ndk_sys::AAsset_getLength64(self.0) as u64 }
+    }
+}
+
+impl std::io::Read for Asset {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = unsafe { ndk_sys::AAsset_read(self.0, buf.as_mut_ptr() as _, buf.len() as _) };
+        if read < 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "AAsset_read failed",
+            ));
+        }
+        Ok(read as usize)
+    }
+}
+
+impl std::io::Seek for Asset {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let (offset, whence) = match pos {
+            std::io::SeekFrom::Start(offset) => (offset as i64, libc::SEEK_SET),
+            std::io::SeekFrom::End(offset) => (offset, libc::SEEK_END),
+            std::io::SeekFrom::Current(offset) => (offset, libc::SEEK_CUR),
+        };
+        let position = unsafe { ndk_sys::AAsset_seek64(self.0, offset as _, whence) };
+        if position < 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "AAsset_seek64 failed",
+            ));
+        }
+        Ok(position as u64)
+    }
+}
+
+impl Drop for Asset {
+    fn drop(&mut self) {
+        unsafe { ndk_sys::AAsset_close(self.0) };
+    }
+}
+
+/// Context.getFilesDir and getCacheDir, in the internal storage of the
+/// application.
+pub(crate) unsafe fn storage_dir(cache: bool) -> Option<std::path::PathBuf> {
+    let env = attach_jni_env();
+    let path = ndk_utils::call_object_method!(
+        env,
+        ACTIVITY,
+        "getStoragePath",
+        "(Z)Ljava/lang/String;",
+        cache as i32
+    );
+    if path.is_null() {
+        return None;
+    }
+    Some(ndk_utils::get_utf_str!(env, path).into())
+}
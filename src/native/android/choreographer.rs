@@ -0,0 +1,174 @@
+//! The vsync from AChoreographer, for the main loop to start the frames
+//! right after it instead of whenever eglSwapBuffers returns.
+//! Loaded from libandroid.so: the 32 bit AChoreographer_postFrameCallback is
+//! from Android 7, AChoreographer_postFrameCallback64 from Android 10 and
+//! AChoreographer_postVsyncCallback, with the frame deadline, from Android 13.
+
+use crate::native::module::linux::Module;
+use std::{
+    cell::Cell,
+    ffi::c_void,
+    os::raw::{c_int, c_long},
+    time::{Duration, Instant},
+};
+
+type FrameCallback = extern "C" fn(frame_time_nanos: c_long, data: *mut c_void);
+type FrameCallback64 = extern "C" fn(frame_time_nanos: i64, data: *mut c_void);
+// the AChoreographerFrameCallbackData is only valid in the callback
+type VsyncCallback = extern "C" fn(callback_data: *const c_void, data: *mut c_void);
+
+type ALooperPrepare = extern "C" fn(opts: c_int) -> *mut c_void;
+type ALooperPollOnce = extern "C" fn(
+    timeout_millis: c_int,
+    out_fd: *mut c_int,
+    out_events: *mut c_int,
+    out_data: *mut *mut c_void,
+) -> c_int;
+type GetInstance = extern "C" fn() -> *mut c_void;
+type PostFrameCallback = extern "C" fn(*mut c_void, FrameCallback, *mut c_void);
+type PostFrameCallback64 = extern "C" fn(*mut c_void, FrameCallback64, *mut c_void);
+type PostVsyncCallback = extern "C" fn(*mut c_void, VsyncCallback, *mut c_void) -> c_int;
+type GetFrameTimeNanos = extern "C" fn(*const c_void) -> i64;
+type GetPreferredFrameTimelineIndex = extern "C" fn(*const c_void) -> usize;
+type GetFrameTimelineDeadlineNanos = extern "C" fn(*const c_void, usize) -> i64;
+
+/// On CLOCK_MONOTONIC, like `native::timestamp_now`.
+#[derive(Clone, Copy)]
+pub struct Vsync {
+    pub frame_time_nanos: i64,
+    /// When the frame has to be done to be presented at the next vsync,
+    /// from Android 13.
+    pub deadline_nanos: Option<i64>,
+}
+
+struct FrameTimeline {
+    get_frame_time: GetFrameTimeNanos,
+    get_preferred_index: GetPreferredFrameTimelineIndex,
+    get_deadline: GetFrameTimelineDeadlineNanos,
+}
+
+enum Post {
+    Vsync(PostVsyncCallback, FrameTimeline),
+    Frame64(PostFrameCallback64),
+    Frame(PostFrameCallback),
+}
+
+pub struct Choreographer {
+    _module: Module,
+    poll_once: ALooperPollOnce,
+    instance: *mut c_void,
+    post: Post,
+    // a callback is posted and not called yet, there is one at a time
+    pending: Cell<bool>,
+    vsync: Cell<Option<Vsync>>,
+}
+
+extern "C" fn frame_callback(frame_time_nanos: c_long, data: *mut c_void) {
+    frame_callback64(frame_time_nanos as i64, data);
+}
+
+extern "C" fn frame_callback64(frame_time_nanos: i64, data: *mut c_void) {
+    let choreographer = unsafe { &*(data as *const Choreographer) };
+    choreographer.pending.set(false);
+    choreographer.vsync.set(Some(Vsync {
+        frame_time_nanos,
+        deadline_nanos: None,
+    }));
+}
+
+extern "C" fn vsync_callback(callback_data: *const c_void, data: *mut c_void) {
+    let choreographer = unsafe { &*(data as *const Choreographer) };
+    let timeline = match &choreographer.post {
+        Post::Vsync(_, timeline) => timeline,
+        _ => return,
+    };
+    let index = (timeline.get_preferred_index)(callback_data);
+    choreographer.pending.set(false);
+    choreographer.vsync.set(Some(Vsync {
+        frame_time_nanos: (timeline.get_frame_time)(callback_data),
+        deadline_nanos: Some((timeline.get_deadline)(callback_data, index)),
+    }));
+}
+
+impl Choreographer {
+    /// Gives the calling thread a looper, where the callbacks are called
+    /// from. Boxed, the callbacks get its address. None before Android 7.
+    pub fn new() -> Option<Box<Choreographer>> {
+        let module = Module::load("libandroid.so").ok()?;
+        let prepare: ALooperPrepare = module.get_symbol("ALooper_prepare").ok()?;
+        let poll_once = module.get_symbol("ALooper_pollOnce").ok()?;
+        let get_instance: GetInstance = module.get_symbol("AChoreographer_getInstance").ok()?;
+
+        let timeline = || {
+            Some(FrameTimeline {
+                get_frame_time: module
+                    .get_symbol("AChoreographerFrameCallbackData_getFrameTimeNanos")
+                    .ok()?,
+                get_preferred_index: module
+                    .get_symbol("AChoreographerFrameCallbackData_getPreferredFrameTimelineIndex")
+                    .ok()?,
+                get_deadline: module
+                    .get_symbol("AChoreographerFrameCallbackData_getFrameTimelineDeadlineNanos")
+                    .ok()?,
+            })
+        };
+        let post = if let (Ok(post), Some(timeline)) = (
+            module.get_symbol("AChoreographer_postVsyncCallback"),
+            timeline(),
+        ) {
+            Post::Vsync(post, timeline)
+        } else if let Ok(post) = module.get_symbol("AChoreographer_postFrameCallback64") {
+            Post::Frame64(post)
+        } else {
+            Post::Frame(module.get_symbol("AChoreographer_postFrameCallback").ok()?)
+        };
+
+        prepare(0);
+        let instance = get_instance();
+        if instance.is_null() {
+            return None;
+        }
+        Some(Box::new(Choreographer {
+            _module: module,
+            poll_once,
+            instance,
+            post,
+            pending: Cell::new(false),
+            vsync: Cell::new(None),
+        }))
+    }
+
+    /// The next vsync, None if there was none for `timeout`, like while
+    /// the screen is off.
+    pub fn wait(&self, timeout: Duration) -> Option<Vsync> {
+        if !self.pending.get() {
+            self.pending.set(true);
+            let data = self as *const Choreographer as *mut c_void;
+            match self.post {
+                Post::Vsync(post, _) => {
+                    post(self.instance, vsync_callback, data);
+                }
+                Post::Frame64(post) => post(self.instance, frame_callback64, data),
+                Post::Frame(post) => post(self.instance, frame_callback, data),
+            }
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(vsync) = self.vsync.take() {
+                return Some(vsync);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            let timeout = (deadline - now).as_millis().max(1) as c_int;
+            (self.poll_once)(
+                timeout,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            );
+        }
+    }
+}
@@ -1,2 +1,4 @@
 pub mod apple_util;
 pub mod frameworks;
+pub mod gamepad;
+pub mod power;
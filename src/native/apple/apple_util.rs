@@ -144,14 +144,19 @@ pub fn get_event_char(event: ObjcId) -> Option<char> {
     }
 }
 
+/// Also keeps `window::modifiers` current.
 pub fn get_event_key_modifier(event: ObjcId) -> KeyMods {
     let flags: u64 = unsafe { msg_send![event, modifierFlags] };
-    KeyMods {
+    let mods = KeyMods {
         shift: flags & NSEventModifierFlags::NSShiftKeyMask as u64 != 0,
         ctrl: flags & NSEventModifierFlags::NSControlKeyMask as u64 != 0,
         alt: flags & NSEventModifierFlags::NSAlternateKeyMask as u64 != 0,
         logo: flags & NSEventModifierFlags::NSCommandKeyMask as u64 != 0,
-    }
+        caps_lock: flags & NSEventModifierFlags::NSAlphaShiftKeyMask as u64 != 0,
+        num_lock: false,
+    };
+    crate::native::update_keymods(mods);
+    mods
 }
 
 pub fn get_event_keycode(event: ObjcId) -> Option<KeyCode> {
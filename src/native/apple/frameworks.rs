@@ -116,10 +116,38 @@ extern "C" {
     pub static NSPasteboardTypeFileURL: ObjcId;
 }
 
+// the power sources, for apple::power, and the power assertions
+#[cfg(target_os = "macos")]
+#[link(name = "IOKit", kind = "framework")]
+extern "C" {
+    pub fn IOPSCopyPowerSourcesInfo() -> ObjcId;
+    pub fn IOPSCopyPowerSourcesList(blob: ObjcId) -> ObjcId;
+    pub fn IOPSGetPowerSourceDescription(blob: ObjcId, ps: ObjcId) -> ObjcId;
+    pub fn IOPMAssertionCreateWithName(
+        assertion_type: ObjcId,
+        assertion_level: u32,
+        assertion_name: ObjcId,
+        assertion_id: *mut u32,
+    ) -> i32;
+    pub fn IOPMAssertionRelease(assertion_id: u32) -> i32;
+}
+
+#[cfg(target_os = "macos")]
+pub const kIOPMAssertionLevelOn: u32 = 255;
+
 #[cfg(target_os = "ios")]
 #[link(name = "GLKit", kind = "framework")]
 extern "C" {}
 
+// CADisplayLink, for the frames on iOS
+#[cfg(target_os = "ios")]
+#[link(name = "QuartzCore", kind = "framework")]
+extern "C" {}
+
+// GCController, for apple::gamepad
+#[link(name = "GameController", kind = "framework")]
+extern "C" {}
+
 pub const GLKViewDrawableColorFormatRGBA8888: i32 = 0;
 
 #[repr(i32)]
@@ -139,6 +167,7 @@ extern "C" {
     pub static UIKeyboardDidShowNotification: ObjcId;
     pub static UIKeyboardWillHideNotification: ObjcId;
     pub static UIKeyboardDidChangeFrameNotification: ObjcId;
+    pub static UIKeyboardFrameEndUserInfoKey: ObjcId;
     pub fn UIApplicationMain(
         argc: i32,
         argv: *mut *mut i8,
@@ -192,6 +221,18 @@ extern "C" {
     pub fn CGColorCreateGenericRGB(red: f64, green: f64, blue: f64, alpha: f64) -> ObjcId;
     pub fn CGAssociateMouseAndMouseCursorPosition(connected: bool);
     pub fn CGWarpMouseCursorPosition(newCursorPosition: NSPoint);
+    pub fn CGDisplayScreenSize(display: u32) -> CGSize;
+    pub fn CGDisplayCopyDisplayMode(display: u32) -> ObjcId;
+    pub fn CGDisplayModeGetPixelWidth(mode: ObjcId) -> usize;
+    pub fn CGDisplayModeGetPixelHeight(mode: ObjcId) -> usize;
+    pub fn CGDisplayModeGetRefreshRate(mode: ObjcId) -> f64;
+    pub fn CGDisplayModeRelease(mode: ObjcId);
+    pub fn CGDisplayModeRetain(mode: ObjcId) -> ObjcId;
+    pub fn CGDisplayCopyAllDisplayModes(display: u32, options: ObjcId) -> ObjcId;
+    pub fn CGDisplaySetDisplayMode(display: u32, mode: ObjcId, options: ObjcId) -> i32;
+    pub fn CGDisplayCapture(display: u32) -> i32;
+    pub fn CGDisplayRelease(display: u32) -> i32;
+    pub fn CGShieldingWindowLevel() -> i32;
 
     pub fn CGImageCreate(
         width: usize,
@@ -314,6 +355,48 @@ unsafe impl Encode for NSRect {
     }
 }
 
+#[repr(C)]
+#[derive(Copy, Debug, Clone)]
+pub struct UIEdgeInsets {
+    pub top: f64,
+    pub left: f64,
+    pub bottom: f64,
+    pub right: f64,
+}
+
+unsafe impl Encode for UIEdgeInsets {
+    fn encode() -> Encoding {
+        let encoding = format!(
+            "{{UIEdgeInsets={}{}{}{}}}",
+            f64::encode().as_str(),
+            f64::encode().as_str(),
+            f64::encode().as_str(),
+            f64::encode().as_str()
+        );
+        unsafe { Encoding::from_str(&encoding) }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Debug, Clone)]
+pub struct CAFrameRateRange {
+    pub minimum: f32,
+    pub maximum: f32,
+    pub preferred: f32,
+}
+
+unsafe impl Encode for CAFrameRateRange {
+    fn encode() -> Encoding {
+        let encoding = format!(
+            "{{CAFrameRateRange={}{}{}}}",
+            f32::encode().as_str(),
+            f32::encode().as_str(),
+            f32::encode().as_str()
+        );
+        unsafe { Encoding::from_str(&encoding) }
+    }
+}
+
 #[repr(u64)] // NSUInteger
 pub enum NSEventModifierFlags {
     NSAlphaShiftKeyMask = 1 << 16,
@@ -333,6 +416,14 @@ pub const NSTrackingMouseEnteredAndExited: u64 = 0x01;
 pub const NSTrackingMouseMoved: u64 = 0x02;
 pub const NSTrackingCursorUpdate: u64 = 0x04;
 
+// NSEventSubtype, of the mouse events
+pub const NSTabletPointEventSubtype: i16 = 1;
+pub const NSTabletProximityEventSubtype: i16 = 2;
+pub const NSPenTipMask: u64 = 1;
+pub const NSPenLowerSideMask: u64 = 2;
+pub const NSPenUpperSideMask: u64 = 4;
+pub const NSPointingDeviceTypeEraser: u64 = 3;
+
 pub const UTF8_ENCODING: usize = 4;
 
 #[repr(u64)]
@@ -464,6 +555,8 @@ impl NSRange {
     }
 }
 
+pub const NSNotFound: u64 = i64::MAX as u64;
+
 unsafe impl Encode for NSRange {
     fn encode() -> Encoding {
         let encoding = format!(
@@ -523,6 +616,13 @@ impl NSMutableAttributedString for ObjcId {
 
 #[repr(u64)]
 #[derive(Clone, Debug)]
+pub enum MTLTriangleFillMode {
+    Fill = 0,
+    Lines = 1,
+}
+
+#[repr(u64)]
+#[derive(Clone, Copy, Debug)]
 pub enum MTLLoadAction {
     DontCare = 0,
     Load = 1,
@@ -530,7 +630,7 @@ pub enum MTLLoadAction {
 }
 
 #[repr(u64)]
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug)]
 pub enum MTLStoreAction {
     DontCare = 0,
     Store = 1,
@@ -870,6 +970,17 @@ pub struct MTLScissorRect {
     pub height: u64,
 }
 
+#[repr(C)]
+#[derive(Copy, Debug, Clone)]
+pub struct MTLViewport {
+    pub originX: f64,
+    pub originY: f64,
+    pub width: f64,
+    pub height: f64,
+    pub znear: f64,
+    pub zfar: f64,
+}
+
 // CORE AUDIO
 
 pub const kAudioUnitManufacturer_Apple: u32 = 1634758764;
@@ -1337,6 +1448,7 @@ extern "C" {
 }
 
 pub const NSOpenGLContextParameterSwapInterval: i32 = 222;
+pub const NSOpenGLContextParameterSurfaceOpacity: i32 = 236;
 
 #[repr(u64)]
 #[derive(Clone, Copy, Debug, PartialEq)]
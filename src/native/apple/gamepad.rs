@@ -0,0 +1,274 @@
+//! GameController, for macOS and iOS, polled like the gamepads elsewhere.
+
+use crate::{
+    native::apple::{
+        apple_util::{nsstring_to_string, str_to_nsstring},
+        frameworks::*,
+    },
+    EventHandler, GamepadAxis, GamepadButton, GamepadState,
+};
+use std::{collections::HashMap, time::Duration};
+
+// nil for the elements the controller or the OS version does not have,
+// like buttonHome before macOS 11 and iOS 14
+unsafe fn element(object: ObjcId, name: &str) -> ObjcId {
+    if object.is_null() {
+        return nil;
+    }
+    let sel = Sel::register(name);
+    let responds: BOOL = msg_send![object, respondsToSelector: sel];
+    if responds != YES {
+        return nil;
+    }
+    msg_send![object, performSelector: sel]
+}
+
+unsafe fn is_pressed(object: ObjcId, name: &str) -> bool {
+    let button = element(object, name);
+    if button.is_null() {
+        return false;
+    }
+    let pressed: BOOL = msg_send![button, isPressed];
+    pressed == YES
+}
+
+unsafe fn value(object: ObjcId, name: &str) -> f32 {
+    let axis = element(object, name);
+    if axis.is_null() {
+        return 0.;
+    }
+    msg_send![axis, value]
+}
+
+unsafe fn read_state(controller: ObjcId, gamepad: ObjcId) -> GamepadState {
+    let vendor_name = element(controller, "vendorName");
+    let mut state = GamepadState {
+        name: if vendor_name.is_null() {
+            String::new()
+        } else {
+            nsstring_to_string(vendor_name)
+        },
+        ..Default::default()
+    };
+    for (name, button) in [
+        ("buttonA", GamepadButton::South),
+        ("buttonB", GamepadButton::East),
+        ("buttonX", GamepadButton::West),
+        ("buttonY", GamepadButton::North),
+        ("leftShoulder", GamepadButton::LeftShoulder),
+        ("rightShoulder", GamepadButton::RightShoulder),
+        ("buttonOptions", GamepadButton::Select),
+        ("buttonMenu", GamepadButton::Start),
+        ("buttonHome", GamepadButton::Guide),
+        ("leftThumbstickButton", GamepadButton::LeftStick),
+        ("rightThumbstickButton", GamepadButton::RightStick),
+    ] {
+        state.set_button(button, is_pressed(gamepad, name));
+    }
+    let dpad = element(gamepad, "dpad");
+    for (name, button) in [
+        ("up", GamepadButton::DPadUp),
+        ("down", GamepadButton::DPadDown),
+        ("left", GamepadButton::DPadLeft),
+        ("right", GamepadButton::DPadRight),
+    ] {
+        state.set_button(button, is_pressed(dpad, name));
+    }
+
+    // up is positive in GameController
+    let left = element(gamepad, "leftThumbstick");
+    let right = element(gamepad, "rightThumbstick");
+    state.set_axis(GamepadAxis::LeftX, value(left, "xAxis"));
+    state.set_axis(GamepadAxis::LeftY, -value(left, "yAxis"));
+    state.set_axis(GamepadAxis::RightX, value(right, "xAxis"));
+    state.set_axis(GamepadAxis::RightY, -value(right, "yAxis"));
+    state.set_axis(GamepadAxis::LeftTrigger, value(gamepad, "leftTrigger"));
+    state.set_axis(GamepadAxis::RightTrigger, value(gamepad, "rightTrigger"));
+    state
+}
+
+// Core Haptics, loaded by GameController on macOS 11 and iOS 14 for
+// GCController.haptics. The constants are by their values, so nothing is
+// linked for the older versions.
+struct Haptics {
+    engine: ObjcId,
+    // the last pattern, stopped by the next rumble
+    player: ObjcId,
+}
+
+impl Haptics {
+    unsafe fn new(controller: ObjcId) -> Option<Haptics> {
+        let haptics = element(controller, "haptics");
+        if haptics.is_null() {
+            return None;
+        }
+        let engine: ObjcId =
+            msg_send![haptics, createEngineWithLocality: str_to_nsstring("Default")];
+        if engine.is_null() {
+            return None;
+        }
+        let mut error: ObjcId = nil;
+        let started: BOOL = msg_send![engine, startAndReturnError: &mut error];
+        if started != YES {
+            return None;
+        }
+        let _: ObjcId = msg_send![engine, retain];
+        Some(Haptics {
+            engine,
+            player: nil,
+        })
+    }
+
+    unsafe fn parameter(id: &str, value: f32) -> ObjcId {
+        let parameter: ObjcId = msg_send![class!(CHHapticEventParameter), alloc];
+        let parameter: ObjcId =
+            msg_send![parameter, initWithParameterID: str_to_nsstring(id) value: value];
+        let _: () = msg_send![parameter, autorelease];
+        parameter
+    }
+
+    // one motor for the controller, as strong as the strongest of the two and
+    // as sharp as the high frequency one is of both
+    unsafe fn play(&mut self, low_frequency: f32, high_frequency: f32, duration: Duration) {
+        let mut error: ObjcId = nil;
+        if !self.player.is_null() {
+            let _: BOOL = msg_send![self.player, stopAtTime: 0f64 error: &mut error];
+            let () = msg_send![self.player, release];
+            self.player = nil;
+        }
+        let intensity = low_frequency.max(high_frequency);
+        if intensity == 0. || duration.is_zero() {
+            return;
+        }
+        let sharpness = high_frequency / (low_frequency + high_frequency);
+
+        let parameters = [
+            Self::parameter("HapticIntensity", intensity),
+            Self::parameter("HapticSharpness", sharpness),
+        ];
+        let parameters: ObjcId = msg_send![
+            class!(NSArray),
+            arrayWithObjects: parameters.as_ptr()
+            count: parameters.len()
+        ];
+        let event: ObjcId = msg_send![class!(CHHapticEvent), alloc];
+        let event: ObjcId = msg_send![
+            event,
+            initWithEventType: str_to_nsstring("HapticContinuous")
+            parameters: parameters
+            relativeTime: 0f64
+            duration: duration.as_secs_f64()
+        ];
+        let events: ObjcId = msg_send![class!(NSArray), arrayWithObject: event];
+        let () = msg_send![event, release];
+        let pattern: ObjcId = msg_send![class!(CHHapticPattern), alloc];
+        let no_parameters: ObjcId = msg_send![class!(NSArray), array];
+        let pattern: ObjcId = msg_send![
+            pattern,
+            initWithEvents: events
+            parameters: no_parameters
+            error: &mut error
+        ];
+        if pattern.is_null() {
+            return;
+        }
+        let player: ObjcId =
+            msg_send![self.engine, createPlayerWithPattern: pattern error: &mut error];
+        let () = msg_send![pattern, release];
+        if player.is_null() {
+            return;
+        }
+        let _: BOOL = msg_send![player, startAtTime: 0f64 error: &mut error];
+        let _: ObjcId = msg_send![player, retain];
+        self.player = player;
+    }
+}
+
+impl Drop for Haptics {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.player.is_null() {
+                let () = msg_send![self.player, release];
+            }
+            let () = msg_send![self.engine, stopWithCompletionHandler: nil];
+            let () = msg_send![self.engine, release];
+        }
+    }
+}
+
+pub struct Gamepads {
+    // retained, so the pointers are not reused while they are here
+    connected: Vec<(ObjcId, u64)>,
+    // by id, made by the first rumble
+    haptics: HashMap<u64, Option<Haptics>>,
+}
+
+impl Gamepads {
+    pub fn new() -> Gamepads {
+        Gamepads {
+            connected: vec![],
+            haptics: HashMap::new(),
+        }
+    }
+
+    pub fn rumble(&mut self, id: u64, low_frequency: f32, high_frequency: f32, duration: Duration) {
+        let controller = match self.connected.iter().find(|&&(_, other)| other == id) {
+            Some(&(controller, _)) => controller,
+            None => return,
+        };
+        unsafe {
+            let haptics = self
+                .haptics
+                .entry(id)
+                .or_insert_with(|| Haptics::new(controller));
+            if let Some(haptics) = haptics {
+                haptics.play(low_frequency, high_frequency, duration);
+            }
+        }
+    }
+
+    /// The controllers with an extendedGamepad profile, the others have too
+    /// few buttons for the standard layout.
+    pub fn poll(&mut self, event_handler: &mut dyn EventHandler) {
+        unsafe {
+            let array: ObjcId = msg_send![class!(GCController), controllers];
+            let count: u64 = msg_send![array, count];
+            let mut controllers = vec![];
+            for i in 0..count {
+                let controller: ObjcId = msg_send![array, objectAtIndex: i];
+                let gamepad: ObjcId = msg_send![controller, extendedGamepad];
+                if !gamepad.is_null() {
+                    controllers.push((controller, gamepad));
+                }
+            }
+
+            let haptics = &mut self.haptics;
+            self.connected.retain(|&(controller, id)| {
+                let connected = controllers.iter().any(|&(other, _)| other == controller);
+                if !connected {
+                    haptics.remove(&id);
+                    crate::native::update_gamepad(id, None, event_handler);
+                    let () = msg_send![controller, release];
+                }
+                connected
+            });
+            for (controller, gamepad) in controllers {
+                let id = match self
+                    .connected
+                    .iter()
+                    .find(|&&(other, _)| other == controller)
+                {
+                    Some(&(_, id)) => id,
+                    None => {
+                        let id = crate::native::new_gamepad_id();
+                        let _: ObjcId = msg_send![controller, retain];
+                        self.connected.push((controller, id));
+                        id
+                    }
+                };
+                let state = read_state(controller, gamepad);
+                crate::native::update_gamepad(id, Some(&state), event_handler);
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! `window::power_info` for macOS and iOS, looked at every few seconds.
+
+use crate::{
+    native::apple::{apple_util::str_to_nsstring, frameworks::*},
+    EventHandler, PowerInfo, PowerState,
+};
+use std::time::{Duration, Instant};
+
+// NSProcessInfo, macOS 12 and iOS 9 and newer
+unsafe fn low_power_mode() -> bool {
+    let process_info: ObjcId = msg_send![class!(NSProcessInfo), processInfo];
+    let responds: BOOL = msg_send![process_info, respondsToSelector: sel!(isLowPowerModeEnabled)];
+    if responds != YES {
+        return false;
+    }
+    let enabled: BOOL = msg_send![process_info, isLowPowerModeEnabled];
+    enabled == YES
+}
+
+/// The internal battery of the IOKit power sources, which are toll-free
+/// bridged NSArray and NSDictionary.
+#[cfg(target_os = "macos")]
+pub unsafe fn power_info() -> PowerInfo {
+    let mut power_info = PowerInfo {
+        state: PowerState::NoBattery,
+        power_saver: low_power_mode(),
+        ..Default::default()
+    };
+    let info = IOPSCopyPowerSourcesInfo();
+    if info.is_null() {
+        return power_info;
+    }
+    let list = IOPSCopyPowerSourcesList(info);
+    let count: u64 = if list.is_null() {
+        0
+    } else {
+        msg_send![list, count]
+    };
+    let get = |description: ObjcId, key: &str| -> ObjcId {
+        msg_send![description, objectForKey: str_to_nsstring(key)]
+    };
+    for i in 0..count {
+        let source: ObjcId = msg_send![list, objectAtIndex: i];
+        let description = IOPSGetPowerSourceDescription(info, source);
+        if description.is_null() {
+            continue;
+        }
+        let kind = get(description, "Type");
+        let internal: BOOL = msg_send![kind, isEqualToString: str_to_nsstring("InternalBattery")];
+        if kind.is_null() || internal != YES {
+            continue;
+        }
+        let current: i32 = msg_send![get(description, "Current Capacity"), intValue];
+        let max: i32 = msg_send![get(description, "Max Capacity"), intValue];
+        let charging: BOOL = msg_send![get(description, "Is Charging"), boolValue];
+        let source_state = get(description, "Power Source State");
+        let ac: BOOL = msg_send![source_state, isEqualToString: str_to_nsstring("AC Power")];
+        power_info.state = if charging == YES {
+            PowerState::Charging
+        } else if ac == YES {
+            PowerState::Charged
+        } else {
+            PowerState::OnBattery
+        };
+        power_info.battery_level = (max > 0).then(|| (current as f32 / max as f32).clamp(0., 1.));
+        break;
+    }
+    if !list.is_null() {
+        let () = msg_send![list, release];
+    }
+    let () = msg_send![info, release];
+    power_info
+}
+
+#[cfg(target_os = "ios")]
+pub unsafe fn power_info() -> PowerInfo {
+    let device: ObjcId = msg_send![class!(UIDevice), currentDevice];
+    let () = msg_send![device, setBatteryMonitoringEnabled: YES];
+    // -1 and UIDeviceBatteryStateUnknown in the simulator
+    let level: f32 = msg_send![device, batteryLevel];
+    let state: i64 = msg_send![device, batteryState];
+    PowerInfo {
+        state: match state {
+            1 => PowerState::OnBattery,
+            2 => PowerState::Charging,
+            3 => PowerState::Charged,
+            _ => PowerState::Unknown,
+        },
+        battery_level: (level >= 0.).then(|| level),
+        power_saver: low_power_mode(),
+    }
+}
+
+pub struct Power {
+    last_poll: Instant,
+}
+
+impl Power {
+    /// After the `power_info` of `NativeDisplayData`.
+    pub fn new() -> Power {
+        Power {
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub fn poll(&mut self, event_handler: &mut dyn EventHandler) {
+        if self.last_poll.elapsed() < Duration::from_secs(5) {
+            return;
+        }
+        self.last_poll = Instant::now();
+        crate::native::update_power_info(unsafe { power_info() }, event_handler);
+    }
+}
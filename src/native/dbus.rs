@@ -0,0 +1,381 @@
+#![allow(non_camel_case_types, non_snake_case)]
+
+//! The session bus of the X11 and Wayland main loops, for the
+//! org.freedesktop.appearance color-scheme of the XDG desktop portal, of
+//! `window::system_theme`, and org.freedesktop.ScreenSaver, of
+//! `window::set_keep_screen_on`. libdbus is loaded at runtime, without it
+//! or the services the theme is light and the screen saver is not inhibited.
+
+use crate::{EventHandler, Theme};
+use libc::{c_char, c_int, c_void};
+use std::ffi::{CStr, CString};
+
+type DBusConnection = c_void;
+type DBusMessage = c_void;
+type dbus_bool_t = u32;
+
+const DBUS_BUS_SESSION: c_int = 0;
+const DBUS_TYPE_STRING: c_int = b's' as _;
+const DBUS_TYPE_UINT32: c_int = b'u' as _;
+const DBUS_TYPE_VARIANT: c_int = b'v' as _;
+
+#[repr(C)]
+struct DBusError {
+    name: *const c_char,
+    message: *const c_char,
+    dummy: u32,
+    padding: *mut c_void,
+}
+
+// opaque, at least as big as the one of dbus/dbus-message.h
+#[repr(C)]
+struct DBusMessageIter {
+    dummy: [*mut c_void; 16],
+}
+
+type dbus_error_init = unsafe extern "C" fn(error: *mut DBusError);
+type dbus_error_free = unsafe extern "C" fn(error: *mut DBusError);
+type dbus_bus_get_private =
+    unsafe extern "C" fn(type_: c_int, error: *mut DBusError) -> *mut DBusConnection;
+type dbus_bus_add_match = unsafe extern "C" fn(
+    connection: *mut DBusConnection,
+    rule: *const c_char,
+    error: *mut DBusError,
+);
+type dbus_connection_set_exit_on_disconnect =
+    unsafe extern "C" fn(connection: *mut DBusConnection, exit_on_disconnect: dbus_bool_t);
+type dbus_connection_send_with_reply_and_block = unsafe extern "C" fn(
+    connection: *mut DBusConnection,
+    message: *mut DBusMessage,
+    timeout_milliseconds: c_int,
+    error: *mut DBusError,
+) -> *mut DBusMessage;
+type dbus_connection_read_write = unsafe extern "C" fn(
+    connection: *mut DBusConnection,
+    timeout_milliseconds: c_int,
+) -> dbus_bool_t;
+type dbus_connection_pop_message =
+    unsafe extern "C" fn(connection: *mut DBusConnection) -> *mut DBusMessage;
+type dbus_connection_close = unsafe extern "C" fn(connection: *mut DBusConnection);
+type dbus_connection_unref = unsafe extern "C" fn(connection: *mut DBusConnection);
+type dbus_message_new_method_call = unsafe extern "C" fn(
+    destination: *const c_char,
+    path: *const c_char,
+    interface: *const c_char,
+    method: *const c_char,
+) -> *mut DBusMessage;
+type dbus_message_is_signal = unsafe extern "C" fn(
+    message: *mut DBusMessage,
+    interface: *const c_char,
+    name: *const c_char,
+) -> dbus_bool_t;
+type dbus_message_unref = unsafe extern "C" fn(message: *mut DBusMessage);
+type dbus_message_iter_init =
+    unsafe extern "C" fn(message: *mut DBusMessage, iter: *mut DBusMessageIter) -> dbus_bool_t;
+type dbus_message_iter_init_append =
+    unsafe extern "C" fn(message: *mut DBusMessage, iter: *mut DBusMessageIter);
+type dbus_message_iter_append_basic = unsafe extern "C" fn(
+    iter: *mut DBusMessageIter,
+    type_: c_int,
+    value: *const c_void,
+) -> dbus_bool_t;
+type dbus_message_iter_get_arg_type = unsafe extern "C" fn(iter: *mut DBusMessageIter) -> c_int;
+type dbus_message_iter_get_basic =
+    unsafe extern "C" fn(iter: *mut DBusMessageIter, value: *mut c_void);
+type dbus_message_iter_recurse =
+    unsafe extern "C" fn(iter: *mut DBusMessageIter, sub: *mut DBusMessageIter);
+type dbus_message_iter_next = unsafe extern "C" fn(iter: *mut DBusMessageIter) -> dbus_bool_t;
+
+struct LibDBus {
+    _module: crate::native::module::Module,
+    dbus_error_init: dbus_error_init,
+    dbus_error_free: dbus_error_free,
+    dbus_bus_get_private: dbus_bus_get_private,
+    dbus_bus_add_match: dbus_bus_add_match,
+    dbus_connection_set_exit_on_disconnect: dbus_connection_set_exit_on_disconnect,
+    dbus_connection_send_with_reply_and_block: dbus_connection_send_with_reply_and_block,
+    dbus_connection_read_write: dbus_connection_read_write,
+    dbus_connection_pop_message: dbus_connection_pop_message,
+    dbus_connection_close: dbus_connection_close,
+    dbus_connection_unref: dbus_connection_unref,
+    dbus_message_new_method_call: dbus_message_new_method_call,
+    dbus_message_is_signal: dbus_message_is_signal,
+    dbus_message_unref: dbus_message_unref,
+    dbus_message_iter_init: dbus_message_iter_init,
+    dbus_message_iter_init_append: dbus_message_iter_init_append,
+    dbus_message_iter_append_basic: dbus_message_iter_append_basic,
+    dbus_message_iter_get_arg_type: dbus_message_iter_get_arg_type,
+    dbus_message_iter_get_basic: dbus_message_iter_get_basic,
+    dbus_message_iter_recurse: dbus_message_iter_recurse,
+    dbus_message_iter_next: dbus_message_iter_next,
+}
+
+impl LibDBus {
+    fn try_load() -> Option<LibDBus> {
+        let module = crate::native::module::Module::load("libdbus-1.so.3")
+            .or_else(|_| crate::native::module::Module::load("libdbus-1.so"))
+            .ok()?;
+        Some(LibDBus {
+            dbus_error_init: module.get_symbol("dbus_error_init").ok()?,
+            dbus_error_free: module.get_symbol("dbus_error_free").ok()?,
+            dbus_bus_get_private: module.get_symbol("dbus_bus_get_private").ok()?,
+            dbus_bus_add_match: module.get_symbol("dbus_bus_add_match").ok()?,
+            dbus_connection_set_exit_on_disconnect: module
+                .get_symbol("dbus_connection_set_exit_on_disconnect")
+                .ok()?,
+            dbus_connection_send_with_reply_and_block: module
+                .get_symbol("dbus_connection_send_with_reply_and_block")
+                .ok()?,
+            dbus_connection_read_write: module.get_symbol("dbus_connection_read_write").ok()?,
+            dbus_connection_pop_message: module.get_symbol("dbus_connection_pop_message").ok()?,
+            dbus_connection_close: module.get_symbol("dbus_connection_close").ok()?,
+            dbus_connection_unref: module.get_symbol("dbus_connection_unref").ok()?,
+            dbus_message_new_method_call: module.get_symbol("dbus_message_new_method_call").ok()?,
+            dbus_message_is_signal: module.get_symbol("dbus_message_is_signal").ok()?,
+            dbus_message_unref: module.get_symbol("dbus_message_unref").ok()?,
+            dbus_message_iter_init: module.get_symbol("dbus_message_iter_init").ok()?,
+            dbus_message_iter_init_append: module
+                .get_symbol("dbus_message_iter_init_append")
+                .ok()?,
+            dbus_message_iter_append_basic: module
+                .get_symbol("dbus_message_iter_append_basic")
+                .ok()?,
+            dbus_message_iter_get_arg_type: module
+                .get_symbol("dbus_message_iter_get_arg_type")
+                .ok()?,
+            dbus_message_iter_get_basic: module.get_symbol("dbus_message_iter_get_basic").ok()?,
+            dbus_message_iter_recurse: module.get_symbol("dbus_message_iter_recurse").ok()?,
+            dbus_message_iter_next: module.get_symbol("dbus_message_iter_next").ok()?,
+            _module: module,
+        })
+    }
+}
+
+const SETTINGS: &str = "org.freedesktop.portal.Settings";
+const NAMESPACE: &str = "org.freedesktop.appearance";
+const KEY: &str = "color-scheme";
+
+// 1 is dark, 0 no preference and 2 light
+fn translate_color_scheme(value: u32) -> Theme {
+    if value == 1 {
+        Theme::Dark
+    } else {
+        Theme::Light
+    }
+}
+
+enum Arg<'a> {
+    String(&'a str),
+    UInt32(u32),
+}
+
+pub struct SessionBus {
+    libdbus: LibDBus,
+    // a private one, the shared one might be popped from by other libraries
+    connection: *mut DBusConnection,
+    // of the Inhibit of org.freedesktop.ScreenSaver, undone by UnInhibit or
+    // by closing the connection
+    screensaver_cookie: Option<u32>,
+}
+
+impl SessionBus {
+    /// None without libdbus or a session bus.
+    pub unsafe fn new() -> Option<SessionBus> {
+        let libdbus = LibDBus::try_load()?;
+        let mut error: DBusError = std::mem::zeroed();
+        (libdbus.dbus_error_init)(&mut error);
+        let connection = (libdbus.dbus_bus_get_private)(DBUS_BUS_SESSION, &mut error);
+        if connection.is_null() {
+            (libdbus.dbus_error_free)(&mut error);
+            return None;
+        }
+        // libdbus would exit the process when the bus goes away
+        (libdbus.dbus_connection_set_exit_on_disconnect)(connection, false as _);
+        let rule = b"type='signal',interface='org.freedesktop.portal.Settings',\
+                     member='SettingChanged',arg0='org.freedesktop.appearance'\0";
+        (libdbus.dbus_bus_add_match)(connection, rule.as_ptr() as _, &mut error);
+        (libdbus.dbus_error_free)(&mut error);
+        Some(SessionBus {
+            libdbus,
+            connection,
+            screensaver_cookie: None,
+        })
+    }
+
+    /// The reply, null on errors and after a timeout of 500ms.
+    unsafe fn call(
+        &self,
+        destination: &str,
+        path: &str,
+        interface: &str,
+        method: &str,
+        args: &[Arg],
+    ) -> *mut DBusMessage {
+        let lib = &self.libdbus;
+        let [destination, path, interface, method] =
+            [destination, path, interface, method].map(|name| CString::new(name).unwrap());
+        let message = (lib.dbus_message_new_method_call)(
+            destination.as_ptr(),
+            path.as_ptr(),
+            interface.as_ptr(),
+            method.as_ptr(),
+        );
+        if message.is_null() {
+            return std::ptr::null_mut();
+        }
+        let mut iter: DBusMessageIter = std::mem::zeroed();
+        (lib.dbus_message_iter_init_append)(message, &mut iter);
+        for arg in args {
+            match *arg {
+                Arg::String(value) => {
+                    let value = CString::new(value).unwrap();
+                    let value = value.as_ptr();
+                    let value = &value as *const _ as _;
+                    (lib.dbus_message_iter_append_basic)(&mut iter, DBUS_TYPE_STRING, value);
+                }
+                Arg::UInt32(value) => {
+                    let value = &value as *const _ as _;
+                    (lib.dbus_message_iter_append_basic)(&mut iter, DBUS_TYPE_UINT32, value);
+                }
+            }
+        }
+
+        let mut error: DBusError = std::mem::zeroed();
+        (lib.dbus_error_init)(&mut error);
+        let reply = (lib.dbus_connection_send_with_reply_and_block)(
+            self.connection,
+            message,
+            500,
+            &mut error,
+        );
+        (lib.dbus_message_unref)(message);
+        (lib.dbus_error_free)(&mut error);
+        reply
+    }
+
+    // the first argument of the reply, unreferenced
+    unsafe fn reply_u32(&self, reply: *mut DBusMessage) -> Option<u32> {
+        if reply.is_null() {
+            return None;
+        }
+        let mut iter: DBusMessageIter = std::mem::zeroed();
+        let value = if (self.libdbus.dbus_message_iter_init)(reply, &mut iter) != 0 {
+            self.read_u32(&mut iter)
+        } else {
+            None
+        };
+        (self.libdbus.dbus_message_unref)(reply);
+        value
+    }
+
+    // the u32 in the variants of `iter`, Read wraps the value in two of them
+    unsafe fn read_u32(&self, iter: &mut DBusMessageIter) -> Option<u32> {
+        let lib = &self.libdbus;
+        match (lib.dbus_message_iter_get_arg_type)(iter) {
+            DBUS_TYPE_VARIANT => {
+                let mut sub: DBusMessageIter = std::mem::zeroed();
+                (lib.dbus_message_iter_recurse)(iter, &mut sub);
+                self.read_u32(&mut sub)
+            }
+            DBUS_TYPE_UINT32 => {
+                let mut value = 0u32;
+                (lib.dbus_message_iter_get_basic)(iter, &mut value as *mut _ as _);
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    unsafe fn read_string(&self, iter: &mut DBusMessageIter) -> Option<String> {
+        let lib = &self.libdbus;
+        if (lib.dbus_message_iter_get_arg_type)(iter) != DBUS_TYPE_STRING {
+            return None;
+        }
+        let mut value: *const c_char = std::ptr::null();
+        (lib.dbus_message_iter_get_basic)(iter, &mut value as *mut _ as _);
+        Some(CStr::from_ptr(value).to_string_lossy().into_owned())
+    }
+
+    /// The theme now, asked with a blocking call, so only once at startup.
+    pub unsafe fn theme(&self) -> Theme {
+        let reply = self.call(
+            "org.freedesktop.portal.Desktop",
+            "/org/freedesktop/portal/desktop",
+            SETTINGS,
+            "Read",
+            &[Arg::String(NAMESPACE), Arg::String(KEY)],
+        );
+        self.reply_u32(reply)
+            .map_or(Theme::Light, translate_color_scheme)
+    }
+
+    /// With the name of the executable as the application name.
+    pub unsafe fn inhibit_screensaver(&mut self, inhibit: bool) {
+        const SCREENSAVER: &str = "org.freedesktop.ScreenSaver";
+        const PATH: &str = "/org/freedesktop/ScreenSaver";
+        match (inhibit, self.screensaver_cookie) {
+            (true, None) => {
+                let name = std::env::current_exe()
+                    .ok()
+                    .and_then(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+                    .unwrap_or_else(|| "miniquad".to_owned());
+                let args = [Arg::String(&name), Arg::String("keep_screen_on")];
+                let reply = self.call(SCREENSAVER, PATH, SCREENSAVER, "Inhibit", &args);
+                self.screensaver_cookie = self.reply_u32(reply);
+                if self.screensaver_cookie.is_none() {
+                    eprintln!("Failed to inhibit the screen saver");
+                }
+            }
+            (false, Some(cookie)) => {
+                self.screensaver_cookie = None;
+                let args = [Arg::UInt32(cookie)];
+                let reply = self.call(SCREENSAVER, PATH, SCREENSAVER, "UnInhibit", &args);
+                if !reply.is_null() {
+                    (self.libdbus.dbus_message_unref)(reply);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The SettingChanged signals since the last poll.
+    pub unsafe fn poll(&mut self, event_handler: &mut dyn EventHandler) {
+        let lib = &self.libdbus;
+        let settings = CString::new(SETTINGS).unwrap();
+        (lib.dbus_connection_read_write)(self.connection, 0);
+        loop {
+            let message = (lib.dbus_connection_pop_message)(self.connection);
+            if message.is_null() {
+                break;
+            }
+            let mut theme = None;
+            let mut iter: DBusMessageIter = std::mem::zeroed();
+            if (lib.dbus_message_is_signal)(
+                message,
+                settings.as_ptr(),
+                b"SettingChanged\0".as_ptr() as _,
+            ) != 0
+                && (lib.dbus_message_iter_init)(message, &mut iter) != 0
+                && self.read_string(&mut iter).as_deref() == Some(NAMESPACE)
+                && (lib.dbus_message_iter_next)(&mut iter) != 0
+                && self.read_string(&mut iter).as_deref() == Some(KEY)
+                && (lib.dbus_message_iter_next)(&mut iter) != 0
+            {
+                theme = self.read_u32(&mut iter).map(translate_color_scheme);
+            }
+            (lib.dbus_message_unref)(message);
+            if let Some(theme) = theme {
+                crate::native::update_system_theme(theme, event_handler);
+            }
+        }
+    }
+}
+
+impl Drop for SessionBus {
+    fn drop(&mut self) {
+        unsafe {
+            (self.libdbus.dbus_connection_close)(self.connection);
+            (self.libdbus.dbus_connection_unref)(self.connection);
+        }
+    }
+}
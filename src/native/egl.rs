@@ -16,11 +16,21 @@ pub type EGLNativePixmapType = ::std::os::raw::c_ulong;
 #[cfg(target_os = "android")]
 pub type EGLNativeWindowType = ::std::os::raw::c_ulong;
 
+#[cfg(target_os = "windows")]
+pub type EGLNativeDisplayType = winapi::shared::windef::HDC;
+#[cfg(target_os = "windows")]
+pub type EGLNativePixmapType = winapi::shared::windef::HBITMAP;
+#[cfg(target_os = "windows")]
+pub type EGLNativeWindowType = winapi::shared::windef::HWND;
+
 pub use core::ptr::null_mut;
 
 pub const EGL_SUCCESS: u32 = 12288;
+pub const EGL_CONTEXT_LOST: u32 = 12302;
 
 pub const EGL_WINDOW_BIT: u32 = 4;
+pub const EGL_PBUFFER_BIT: u32 = 1;
+pub const EGL_OPENGL_ES2_BIT: u32 = 4;
 
 pub const EGL_ALPHA_SIZE: u32 = 12321;
 pub const EGL_BLUE_SIZE: u32 = 12322;
@@ -35,6 +45,13 @@ pub const EGL_HEIGHT: u32 = 12374;
 pub const EGL_SURFACE_TYPE: u32 = 12339;
 pub const EGL_NONE: u32 = 12344;
 pub const EGL_CONTEXT_CLIENT_VERSION: u32 = 12440;
+pub const EGL_RENDERABLE_TYPE: u32 = 12352;
+pub const EGL_PLATFORM_SURFACELESS_MESA: u32 = 0x31DD;
+pub const EGL_EXTENSIONS: u32 = 0x3055;
+pub const EGL_CONTEXT_FLAGS_KHR: u32 = 0x30FC;
+pub const EGL_CONTEXT_OPENGL_DEBUG_BIT_KHR: u32 = 0x1;
+pub const EGL_GL_COLORSPACE_KHR: u32 = 0x309D;
+pub const EGL_GL_COLORSPACE_SRGB_KHR: u32 = 0x3089;
 
 pub type NativeDisplayType = EGLNativeDisplayType;
 pub type NativePixmapType = EGLNativePixmapType;
@@ -175,6 +192,13 @@ pub type PFNEGLSURFACEATTRIBPROC = ::std::option::Option<
         value: EGLint,
     ) -> EGLBoolean,
 >;
+pub type PFNEGLGETPLATFORMDISPLAYEXTPROC = ::std::option::Option<
+    unsafe extern "C" fn(
+        platform: ::std::os::raw::c_uint,
+        native_display: *mut ::std::os::raw::c_void,
+        attrib_list: *const EGLint,
+    ) -> EGLDisplay,
+>;
 pub type PFNEGLSWAPINTERVALPROC =
     ::std::option::Option<unsafe extern "C" fn(dpy: EGLDisplay, interval: EGLint) -> EGLBoolean>;
 
@@ -212,8 +236,13 @@ pub struct LibEgl {
 
 impl LibEgl {
     pub fn try_load() -> Option<LibEgl> {
-        module::Module::load("libEGL.so")
-            .or_else(|_| module::Module::load("libEGL.so.1"))
+        #[cfg(not(target_os = "windows"))]
+        let module =
+            module::Module::load("libEGL.so").or_else(|_| module::Module::load("libEGL.so.1"));
+        #[cfg(target_os = "windows")]
+        let module = module::Module::load("libEGL.dll");
+
+        module
             .map(|module| LibEgl {
                 eglChooseConfig: module.get_symbol("eglChooseConfig").ok(),
                 eglCopyBuffers: module.get_symbol("eglCopyBuffers").ok(),
@@ -249,20 +278,53 @@ impl LibEgl {
     }
 }
 
+/// eglSwapInterval for the current context, EGL has no adaptive vsync.
+pub unsafe fn swap_interval(egl: &mut LibEgl, display: EGLDisplay, interval: i32) -> bool {
+    match egl.eglSwapInterval {
+        Some(swap_interval) => swap_interval(display, interval) != 0,
+        None => false,
+    }
+}
+
 #[derive(Debug)]
 pub enum EglError {
     NoDisplay,
     InitializeFailed,
+    NoConfig,
     CreateContextFailed,
+    CreateSurfaceFailed,
+    MakeCurrentFailed,
 }
 
 pub struct Egl {}
 
+unsafe fn has_extension(egl: &LibEgl, display: EGLDisplay, name: &str) -> bool {
+    let extensions = (egl.eglQueryString.unwrap())(display, EGL_EXTENSIONS as _);
+    !extensions.is_null()
+        && std::ffi::CStr::from_ptr(extensions)
+            .to_string_lossy()
+            .split(' ')
+            .any(|extension| extension == name)
+}
+
+/// For eglCreateWindowSurface, an sRGB surface for `Platform::srgb_framebuffer`.
+pub unsafe fn window_surface_attributes(egl: &LibEgl, display: EGLDisplay, srgb: bool) -> Vec<u32> {
+    if srgb {
+        if has_extension(egl, display, "EGL_KHR_gl_colorspace") {
+            return vec![EGL_GL_COLORSPACE_KHR, EGL_GL_COLORSPACE_SRGB_KHR, EGL_NONE];
+        }
+        eprintln!("EGL: No sRGB window surface without EGL_KHR_gl_colorspace");
+    }
+    vec![EGL_NONE]
+}
+
 pub unsafe fn create_egl_context(
     egl: &mut LibEgl,
     display: *mut std::ffi::c_void,
     alpha: bool,
     sample_count: i32,
+    gles_version: crate::conf::GlesVersion,
+    debug: bool,
 ) -> Result<(EGLContext, EGLConfig, EGLDisplay), EglError> {
     let display = (egl.eglGetDisplay.unwrap())(display as _);
     if display == /* EGL_NO_DISPLAY */ null_mut() {
@@ -327,16 +389,137 @@ pub unsafe fn create_egl_context(
     if !exact_cfg_found {
         config = available_cfgs[0];
     }
-    let ctx_attributes = vec![EGL_CONTEXT_CLIENT_VERSION, 2, EGL_NONE];
-    let context = (egl.eglCreateContext.unwrap())(
+    let context = create_context(egl, display, config, gles_version, debug)?;
+
+    return Ok((context, config, display));
+}
+
+/// New context for an already chosen config, also used to replace a lost one.
+pub unsafe fn create_context(
+    egl: &mut LibEgl,
+    display: EGLDisplay,
+    config: EGLConfig,
+    gles_version: crate::conf::GlesVersion,
+    debug: bool,
+) -> Result<EGLContext, EglError> {
+    let debug = debug && {
+        let supported = has_extension(egl, display, "EGL_KHR_create_context");
+        if !supported {
+            eprintln!("EGL: No debug context without EGL_KHR_create_context");
+        }
+        supported
+    };
+    let create_context = |version: u32| {
+        let mut ctx_attributes = vec![EGL_CONTEXT_CLIENT_VERSION, version];
+        if debug {
+            ctx_attributes
+                .extend_from_slice(&[EGL_CONTEXT_FLAGS_KHR, EGL_CONTEXT_OPENGL_DEBUG_BIT_KHR]);
+        }
+        ctx_attributes.push(EGL_NONE);
+        (egl.eglCreateContext.unwrap())(
+            display,
+            config,
+            /* EGL_NO_CONTEXT */ null_mut(),
+            ctx_attributes.as_ptr() as _,
+        )
+    };
+    let mut context = null_mut();
+    if gles_version == crate::conf::GlesVersion::Gles3 {
+        context = create_context(3);
+    }
+    if context.is_null() {
+        context = create_context(2);
+    }
+    if context.is_null() {
+        return Err(EglError::CreateContextFailed);
+    }
+    Ok(context)
+}
+
+/// GLES context with a pbuffer surface and no window at all, for tests and CI.
+/// Prefers GLES3, falls back to GLES2.
+pub unsafe fn create_headless_egl_context(
+    egl: &mut LibEgl,
+    width: i32,
+    height: i32,
+) -> Result<(EGLContext, EGLSurface, EGLDisplay), EglError> {
+    // without a display server the default display would try X11 and fail,
+    // while Mesa's surfaceless platform needs nothing at all
+    let name = std::ffi::CString::new("eglGetPlatformDisplayEXT").unwrap();
+    let get_platform_display: PFNEGLGETPLATFORMDISPLAYEXTPROC =
+        std::mem::transmute((egl.eglGetProcAddress.unwrap())(name.as_ptr() as _));
+    let mut display = /* EGL_NO_DISPLAY */ null_mut();
+    if let Some(get_platform_display) = get_platform_display {
+        display = get_platform_display(EGL_PLATFORM_SURFACELESS_MESA, null_mut(), null_mut());
+        if !display.is_null() && (egl.eglInitialize.unwrap())(display, null_mut(), null_mut()) == 0
+        {
+            display = null_mut();
+        }
+    }
+    if display.is_null() {
+        display = (egl.eglGetDisplay.unwrap())(/* EGL_DEFAULT_DISPLAY */ null_mut());
+        if display == /* EGL_NO_DISPLAY */ null_mut() {
+            return Err(EglError::NoDisplay);
+        }
+        if (egl.eglInitialize.unwrap())(display, null_mut(), null_mut()) == 0 {
+            return Err(EglError::InitializeFailed);
+        }
+    }
+
+    #[rustfmt::skip]
+    let cfg_attributes = vec![
+        EGL_SURFACE_TYPE, EGL_PBUFFER_BIT,
+        EGL_RENDERABLE_TYPE, EGL_OPENGL_ES2_BIT,
+        EGL_RED_SIZE, 8,
+        EGL_GREEN_SIZE, 8,
+        EGL_BLUE_SIZE, 8,
+        EGL_ALPHA_SIZE, 8,
+        EGL_DEPTH_SIZE, 16,
+        EGL_NONE,
+    ];
+    let mut config: EGLConfig = null_mut();
+    let mut cfg_count = 0;
+    (egl.eglChooseConfig.unwrap())(
         display,
-        config,
-        /* EGL_NO_CONTEXT */ null_mut(),
-        ctx_attributes.as_ptr() as _,
+        cfg_attributes.as_ptr() as _,
+        &mut config as *mut _,
+        1,
+        &mut cfg_count as *mut _ as *mut _,
     );
+    if cfg_count == 0 {
+        return Err(EglError::NoConfig);
+    }
+
+    let create_context = |version: u32| {
+        let ctx_attributes = vec![EGL_CONTEXT_CLIENT_VERSION, version, EGL_NONE];
+        (egl.eglCreateContext.unwrap())(
+            display,
+            config,
+            /* EGL_NO_CONTEXT */ null_mut(),
+            ctx_attributes.as_ptr() as _,
+        )
+    };
+    let mut context = create_context(3);
+    if context.is_null() {
+        context = create_context(2);
+    }
     if context.is_null() {
         return Err(EglError::CreateContextFailed);
     }
 
-    return Ok((context, config, display));
+    let surface_attributes = vec![EGL_WIDTH, width as u32, EGL_HEIGHT, height as u32, EGL_NONE];
+    let surface =
+        (egl.eglCreatePbufferSurface.unwrap())(display, config, surface_attributes.as_ptr() as _);
+    if surface == /* EGL_NO_SURFACE */ null_mut() {
+        (egl.eglDestroyContext.unwrap())(display, context);
+        return Err(EglError::CreateSurfaceFailed);
+    }
+
+    if (egl.eglMakeCurrent.unwrap())(display, surface, surface, context) == 0 {
+        (egl.eglDestroySurface.unwrap())(display, surface);
+        (egl.eglDestroyContext.unwrap())(display, context);
+        return Err(EglError::MakeCurrentFailed);
+    }
+
+    Ok((context, surface, display))
 }
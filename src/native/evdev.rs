@@ -0,0 +1,319 @@
+//! Gamepads for the X11 and Wayland main loops, read from the evdev devices
+//! in /dev/input, which are looked up again every second for hotplug.
+
+use crate::{EventHandler, GamepadAxis, GamepadButton, GamepadState};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+// linux/input-event-codes.h
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+const EV_FF: u16 = 0x15;
+const FF_RUMBLE: u16 = 0x50;
+const KEY_MAX: usize = 0x2ff;
+const BTN_GAMEPAD: usize = 0x130;
+
+const ABS_X: u16 = 0x00;
+const ABS_Y: u16 = 0x01;
+const ABS_Z: u16 = 0x02;
+const ABS_RX: u16 = 0x03;
+const ABS_RY: u16 = 0x04;
+const ABS_RZ: u16 = 0x05;
+const ABS_GAS: u16 = 0x09;
+const ABS_BRAKE: u16 = 0x0a;
+const ABS_HAT0X: u16 = 0x10;
+const ABS_HAT0Y: u16 = 0x11;
+
+const BTN_TL2: u16 = 0x138;
+const BTN_TR2: u16 = 0x139;
+
+// the face buttons by where they are, as the kernel documents them, a few
+// drivers swap North and West
+fn translate_button(code: u16) -> Option<GamepadButton> {
+    Some(match code {
+        0x130 => GamepadButton::South,
+        0x131 => GamepadButton::East,
+        0x133 => GamepadButton::North,
+        0x134 => GamepadButton::West,
+        0x136 => GamepadButton::LeftShoulder,
+        0x137 => GamepadButton::RightShoulder,
+        0x13a => GamepadButton::Select,
+        0x13b => GamepadButton::Start,
+        0x13c => GamepadButton::Guide,
+        0x13d => GamepadButton::LeftStick,
+        0x13e => GamepadButton::RightStick,
+        0x220 => GamepadButton::DPadUp,
+        0x221 => GamepadButton::DPadDown,
+        0x222 => GamepadButton::DPadLeft,
+        0x223 => GamepadButton::DPadRight,
+        _ => return None,
+    })
+}
+
+fn translate_axis(code: u16) -> Option<GamepadAxis> {
+    Some(match code {
+        ABS_X => GamepadAxis::LeftX,
+        ABS_Y => GamepadAxis::LeftY,
+        ABS_RX => GamepadAxis::RightX,
+        ABS_RY => GamepadAxis::RightY,
+        ABS_Z | ABS_BRAKE => GamepadAxis::LeftTrigger,
+        ABS_RZ | ABS_GAS => GamepadAxis::RightTrigger,
+        _ => return None,
+    })
+}
+
+// _IOC(_IOC_READ, 'E', nr, size) of linux/input.h
+fn eviocg(nr: usize, size: usize) -> libc::c_ulong {
+    (2 << 30 | size << 16 | (b'E' as usize) << 8 | nr) as libc::c_ulong
+}
+
+// EVIOCSFF, _IOW('E', 0x80, struct ff_effect)
+fn eviocsff() -> libc::c_ulong {
+    let size = std::mem::size_of::<libc::ff_effect>();
+    (1 << 30 | size << 16 | (b'E' as usize) << 8 | 0x80) as libc::c_ulong
+}
+
+struct Device {
+    fd: libc::c_int,
+    id: u64,
+    state: GamepadState,
+    // the ranges of the axes, by ABS_ code
+    ranges: HashMap<u16, (i32, i32)>,
+    // read since the last update_gamepad
+    changed: bool,
+    // the uploaded FF_RUMBLE effect, replaced by every rumble
+    rumble_effect: Option<i16>,
+}
+
+impl Device {
+    /// None and `fd` closed if it is not a gamepad.
+    unsafe fn new(fd: libc::c_int) -> Option<Device> {
+        let mut keys = [0u8; KEY_MAX / 8 + 1];
+        let is_gamepad = libc::ioctl(
+            fd,
+            eviocg(0x20 + EV_KEY as usize, keys.len()) as _,
+            keys.as_mut_ptr(),
+        ) >= 0
+            && keys[BTN_GAMEPAD / 8] & (1 << (BTN_GAMEPAD % 8)) != 0;
+        if !is_gamepad {
+            libc::close(fd);
+            return None;
+        }
+
+        let mut name = [0u8; 256];
+        libc::ioctl(fd, eviocg(0x06, name.len()) as _, name.as_mut_ptr());
+        let name = std::ffi::CStr::from_bytes_until_nul(&name)
+            .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+        let mut device = Device {
+            fd,
+            id: crate::native::new_gamepad_id(),
+            state: GamepadState {
+                name,
+                ..Default::default()
+            },
+            ranges: HashMap::new(),
+            changed: true,
+            rumble_effect: None,
+        };
+        for code in [
+            ABS_X, ABS_Y, ABS_Z, ABS_RX, ABS_RY, ABS_RZ, ABS_GAS, ABS_BRAKE, ABS_HAT0X, ABS_HAT0Y,
+        ] {
+            let mut info: libc::input_absinfo = std::mem::zeroed();
+            let size = std::mem::size_of::<libc::input_absinfo>();
+            if libc::ioctl(fd, eviocg(0x40 + code as usize, size) as _, &mut info) >= 0 {
+                device.ranges.insert(code, (info.minimum, info.maximum));
+                device.abs_event(code, info.value);
+            }
+        }
+        Some(device)
+    }
+
+    fn has_axis(&self, axis: GamepadAxis) -> bool {
+        self.ranges
+            .keys()
+            .any(|&code| translate_axis(code) == Some(axis))
+    }
+
+    fn key_event(&mut self, code: u16, value: i32) {
+        let down = value != 0;
+        if let Some(button) = translate_button(code) {
+            self.state.set_button(button, down);
+        }
+        // the digital triggers, of the gamepads without the analog ones
+        let trigger = match code {
+            BTN_TL2 if !self.has_axis(GamepadAxis::LeftTrigger) => Some(GamepadAxis::LeftTrigger),
+            BTN_TR2 if !self.has_axis(GamepadAxis::RightTrigger) => Some(GamepadAxis::RightTrigger),
+            _ => None,
+        };
+        if let Some(trigger) = trigger {
+            self.state.set_axis(trigger, if down { 1. } else { 0. });
+        }
+    }
+
+    /// Only with the device opened for writing.
+    unsafe fn rumble(&mut self, low_frequency: f32, high_frequency: f32, duration: Duration) {
+        let mut effect: libc::ff_effect = std::mem::zeroed();
+        effect.type_ = FF_RUMBLE;
+        effect.id = self.rumble_effect.unwrap_or(-1);
+        effect.replay.length = duration.as_millis().min(u16::MAX as _) as u16;
+        let rumble = &mut effect.u as *mut _ as *mut libc::ff_rumble_effect;
+        (*rumble).strong_magnitude = (low_frequency * u16::MAX as f32) as u16;
+        (*rumble).weak_magnitude = (high_frequency * u16::MAX as f32) as u16;
+        if libc::ioctl(self.fd, eviocsff() as _, &mut effect) < 0 {
+            self.rumble_effect = None;
+            return;
+        }
+        self.rumble_effect = Some(effect.id);
+
+        // a length of 0 would be forever
+        let playing = (low_frequency > 0. || high_frequency > 0.) && !duration.is_zero();
+        let mut event: libc::input_event = std::mem::zeroed();
+        event.type_ = EV_FF;
+        event.code = effect.id as u16;
+        event.value = playing as i32;
+        let size = std::mem::size_of::<libc::input_event>();
+        libc::write(self.fd, &event as *const _ as _, size);
+    }
+
+    fn abs_event(&mut self, code: u16, value: i32) {
+        if code == ABS_HAT0X || code == ABS_HAT0Y {
+            let (negative, positive) = if code == ABS_HAT0X {
+                (GamepadButton::DPadLeft, GamepadButton::DPadRight)
+            } else {
+                (GamepadButton::DPadUp, GamepadButton::DPadDown)
+            };
+            self.state.set_button(negative, value < 0);
+            self.state.set_button(positive, value > 0);
+            return;
+        }
+        let (axis, &(min, max)) = match (translate_axis(code), self.ranges.get(&code)) {
+            (Some(axis), Some(range)) if range.1 > range.0 => (axis, range),
+            _ => return,
+        };
+        let value = (value - min) as f32 / (max - min) as f32;
+        let value = match axis {
+            GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger => value,
+            _ => value * 2. - 1.,
+        };
+        self.state.set_axis(axis, value.clamp(-1., 1.));
+    }
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+pub struct Gamepads {
+    devices: HashMap<PathBuf, Device>,
+    // the other /dev/input devices, not opened again
+    ignored: HashSet<PathBuf>,
+    last_scan: Option<Instant>,
+}
+
+impl Gamepads {
+    pub fn new() -> Gamepads {
+        Gamepads {
+            devices: HashMap::new(),
+            ignored: HashSet::new(),
+            last_scan: None,
+        }
+    }
+
+    unsafe fn scan(&mut self) {
+        use std::os::unix::ffi::OsStrExt;
+
+        self.last_scan = Some(Instant::now());
+        let entries = match std::fs::read_dir("/dev/input") {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        let paths: HashSet<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("event"))
+            .map(|entry| entry.path())
+            .collect();
+        self.ignored.retain(|path| paths.contains(path));
+        for path in paths {
+            if self.devices.contains_key(&path) || self.ignored.contains(&path) {
+                continue;
+            }
+            let cpath = std::ffi::CString::new(path.as_os_str().as_bytes()).unwrap();
+            // for writing for the rumble, when it is allowed
+            let flags = libc::O_NONBLOCK | libc::O_CLOEXEC;
+            let mut fd = libc::open(cpath.as_ptr(), libc::O_RDWR | flags);
+            if fd < 0 {
+                fd = libc::open(cpath.as_ptr(), libc::O_RDONLY | flags);
+            }
+            // tried again on the next scan, udev might not have given the
+            // access to a new device yet
+            if fd < 0 {
+                continue;
+            }
+            match Device::new(fd) {
+                Some(device) => {
+                    self.devices.insert(path, device);
+                }
+                None => {
+                    self.ignored.insert(path);
+                }
+            }
+        }
+    }
+
+    pub unsafe fn rumble(
+        &mut self,
+        id: u64,
+        low_frequency: f32,
+        high_frequency: f32,
+        duration: Duration,
+    ) {
+        if let Some(device) = self.devices.values_mut().find(|device| device.id == id) {
+            device.rumble(low_frequency, high_frequency, duration);
+        }
+    }
+
+    /// The events of the gamepads since the last poll.
+    pub unsafe fn poll(&mut self, event_handler: &mut dyn EventHandler) {
+        let scan_due = self.last_scan.map_or(true, |last_scan| {
+            last_scan.elapsed() >= Duration::from_secs(1)
+        });
+        if scan_due {
+            self.scan();
+        }
+
+        let mut unplugged = vec![];
+        for (path, device) in &mut self.devices {
+            loop {
+                let mut event: libc::input_event = std::mem::zeroed();
+                let size = std::mem::size_of::<libc::input_event>();
+                if libc::read(device.fd, &mut event as *mut _ as _, size) != size as isize {
+                    // EAGAIN when there is nothing to read, ENODEV once unplugged
+                    let error = std::io::Error::last_os_error().raw_os_error();
+                    if error != Some(libc::EAGAIN) {
+                        unplugged.push(path.clone());
+                    }
+                    break;
+                }
+                match event.type_ {
+                    EV_KEY => device.key_event(event.code, event.value),
+                    EV_ABS => device.abs_event(event.code, event.value),
+                    _ => continue,
+                }
+                device.changed = true;
+            }
+            if std::mem::replace(&mut device.changed, false) {
+                crate::native::update_gamepad(device.id, Some(&device.state), event_handler);
+            }
+        }
+        for path in unplugged {
+            if let Some(device) = self.devices.remove(&path) {
+                crate::native::update_gamepad(device.id, None, event_handler);
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! `conf.wait_for_events` for the X11 and Wayland main loops: poll on the
+//! display connection, plus a pipe for `window::request_redraw` and
+//! `window::schedule_update` from other threads to wake the loop up.
+
+use std::{
+    sync::atomic::{AtomicI32, Ordering},
+    time::Duration,
+};
+
+// the write end of the pipe for the signal handlers, -1 without an EventWait
+static SIGNAL_WAKE_FD: AtomicI32 = AtomicI32::new(-1);
+
+/// The waker for signal handlers, only async-signal-safe calls here.
+pub fn wake_from_signal() {
+    let fd = SIGNAL_WAKE_FD.load(Ordering::Relaxed);
+    if fd >= 0 {
+        unsafe { libc::write(fd, [1u8].as_ptr() as _, 1) };
+    }
+}
+
+pub struct EventWait {
+    read: libc::c_int,
+    write: libc::c_int,
+}
+
+impl EventWait {
+    /// Also installs the waker into NativeDisplayData.
+    pub unsafe fn new() -> EventWait {
+        let mut fds = [-1; 2];
+        if libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) != 0 {
+            eprintln!("Failed to create the wake up pipe");
+        }
+        let (read, write) = (fds[0], fds[1]);
+        SIGNAL_WAKE_FD.store(write, Ordering::Relaxed);
+        crate::native_display().lock().unwrap().waker = Some(Box::new(move || {
+            // a full pipe is awake enough already
+            unsafe { libc::write(write, [1u8].as_ptr() as _, 1) };
+        }));
+        EventWait { read, write }
+    }
+
+    /// Until there is something to read on `fd`, the waker is called or
+    /// the timeout is over, None is no timeout. True if `fd` is readable.
+    pub unsafe fn wait(&self, fd: libc::c_int, timeout: Option<Duration>) -> bool {
+        let mut fds = [
+            libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: self.read,
+                events: libc::POLLIN,
+                revents: 0,
+            },
+        ];
+        // rounded up, to not wake up a bit early and spin on the last millisecond
+        let timeout = timeout.map_or(-1, |timeout| {
+            ((timeout.as_micros() as u64 + 999) / 1000).min(i32::MAX as u64) as i32
+        });
+        libc::poll(fds.as_mut_ptr(), fds.len() as _, timeout);
+
+        let mut buf = [0u8; 64];
+        while libc::read(self.read, buf.as_mut_ptr() as _, buf.len()) > 0 {}
+        fds[0].revents & libc::POLLIN != 0
+    }
+}
+
+impl Drop for EventWait {
+    fn drop(&mut self) {
+        if let Ok(mut d) = crate::native_display().try_lock() {
+            d.waker = None;
+        }
+        SIGNAL_WAKE_FD.store(-1, Ordering::Relaxed);
+        unsafe {
+            libc::close(self.read);
+            libc::close(self.write);
+        }
+    }
+}
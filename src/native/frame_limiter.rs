@@ -0,0 +1,93 @@
+//! `window::set_target_fps` and `conf.background_mode`, a sleep at the end of
+//! each frame of the main loops.
+//! `thread::sleep` alone overshoots by a scheduler tick, so it sleeps for most of
+//! the frame and spins for the rest.
+
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "windows")]
+use winapi::um::timeapi::{timeBeginPeriod, timeEndPeriod};
+
+// how much of the wait is spinning, the sleep accuracy with a 1ms timer period
+#[cfg(target_os = "windows")]
+const SPIN: Duration = Duration::from_micros(2000);
+#[cfg(not(target_os = "windows"))]
+const SPIN: Duration = Duration::from_micros(1000);
+
+pub struct FrameLimiter {
+    // when the next frame should start, None if the last one was late
+    deadline: Option<Instant>,
+    // timeBeginPeriod was called, the 15.6ms default is way too coarse
+    #[cfg(target_os = "windows")]
+    timer_period: bool,
+}
+
+impl FrameLimiter {
+    pub fn new() -> FrameLimiter {
+        FrameLimiter {
+            deadline: None,
+            #[cfg(target_os = "windows")]
+            timer_period: false,
+        }
+    }
+
+    /// Right after the frame is presented.
+    pub fn wait(&mut self) {
+        let target_fps = crate::native_display().lock().unwrap().frame_rate();
+        let frame = match target_fps {
+            Some(fps) if fps > 0 => Duration::from_secs_f64(1. / fps as f64),
+            _ => {
+                self.deadline = None;
+                self.set_timer_period(false);
+                return;
+            }
+        };
+        self.set_timer_period(true);
+
+        let now = Instant::now();
+        let deadline = match self.deadline {
+            Some(deadline) if deadline > now => deadline,
+            // late already, vsync or a slow frame, start pacing from here
+            _ => {
+                self.deadline = Some(now + frame);
+                return;
+            }
+        };
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            let left = deadline - now;
+            if left > SPIN {
+                std::thread::sleep(left - SPIN);
+            } else {
+                std::thread::yield_now();
+            }
+        }
+        self.deadline = Some(deadline + frame);
+    }
+
+    #[cfg(target_os = "windows")]
+    fn set_timer_period(&mut self, enabled: bool) {
+        if self.timer_period != enabled {
+            self.timer_period = enabled;
+            unsafe {
+                if enabled {
+                    timeBeginPeriod(1);
+                } else {
+                    timeEndPeriod(1);
+                }
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn set_timer_period(&mut self, _enabled: bool) {}
+}
+
+impl Drop for FrameLimiter {
+    fn drop(&mut self) {
+        self.set_timer_period(false);
+    }
+}
@@ -13,6 +13,17 @@ pub type GLuint = ::std::os::raw::c_uint;
 pub type GLuint64 = ::std::os::raw::c_ulonglong;
 pub type GLsizei = ::std::os::raw::c_int;
 pub type GLchar = ::std::os::raw::c_char;
+pub type GLDEBUGPROC = Option<
+    extern "system" fn(
+        source: GLenum,
+        type_: GLenum,
+        id: GLuint,
+        severity: GLenum,
+        length: GLsizei,
+        message: *const GLchar,
+        userParam: *mut ::std::os::raw::c_void,
+    ),
+>;
 
 pub type khronos_ssize_t = ::std::os::raw::c_long;
 pub type khronos_usize_t = ::std::os::raw::c_ulong;
@@ -67,6 +78,10 @@ pub const GL_UNSIGNED_SHORT_5_5_5_1: u32 = 0x8034;
 pub const GL_NEAREST_MIPMAP_NEAREST: u32 = 0x2700;
 pub const GL_UNSIGNED_SHORT_4_4_4_4: u32 = 0x8033;
 pub const GL_SRC_ALPHA_SATURATE: u32 = 0x0308;
+pub const GL_SRC1_COLOR: u32 = 0x88F9;
+pub const GL_SRC1_ALPHA: u32 = 0x8589;
+pub const GL_ONE_MINUS_SRC1_COLOR: u32 = 0x88FA;
+pub const GL_ONE_MINUS_SRC1_ALPHA: u32 = 0x88FB;
 pub const GL_STREAM_DRAW: u32 = 0x88E0;
 pub const GL_ONE: u32 = 1;
 pub const GL_NEAREST_MIPMAP_LINEAR: u32 = 0x2702;
@@ -117,6 +132,11 @@ pub const GL_TEXTURE_MIN_FILTER: u32 = 0x2801;
 pub const GL_LINEAR_MIPMAP_NEAREST: u32 = 0x2701;
 pub const GL_EXTENSIONS: u32 = 0x1F03;
 pub const GL_NO_ERROR: u32 = 0;
+pub const GL_INVALID_ENUM: u32 = 0x0500;
+pub const GL_INVALID_VALUE: u32 = 0x0501;
+pub const GL_INVALID_OPERATION: u32 = 0x0502;
+pub const GL_OUT_OF_MEMORY: u32 = 0x0505;
+pub const GL_INVALID_FRAMEBUFFER_OPERATION: u32 = 0x0506;
 pub const GL_REPLACE: u32 = 0x1E01;
 pub const GL_KEEP: u32 = 0x1E00;
 pub const GL_CCW: u32 = 0x0901;
@@ -228,6 +248,8 @@ pub const GL_RGB16_SNORM: u32 = 0x8F9A;
 pub const GL_RGBA16_SNORM: u32 = 0x8F9B;
 pub const GL_RGBA16: u32 = 0x805B;
 pub const GL_MAX_TEXTURE_SIZE: u32 = 0x0D33;
+pub const GL_MAX_SAMPLES: u32 = 0x8D57;
+pub const GL_MAX_COLOR_ATTACHMENTS: u32 = 0x8CDF;
 pub const GL_MAX_CUBE_MAP_TEXTURE_SIZE: u32 = 0x851C;
 pub const GL_MAX_3D_TEXTURE_SIZE: u32 = 0x8073;
 pub const GL_MAX_ARRAY_TEXTURE_LAYERS: u32 = 0x88FF;
@@ -235,6 +257,7 @@ pub const GL_MAX_VERTEX_ATTRIBS: u32 = 0x8869;
 pub const GL_CLAMP_TO_BORDER: u32 = 0x812D;
 pub const GL_TEXTURE_BORDER_COLOR: u32 = 0x1004;
 pub const GL_UNPACK_ALIGNMENT: u32 = 3317;
+pub const GL_UNPACK_ROW_LENGTH: u32 = 3314;
 pub const GL_TEXTURE_SWIZZLE_R: u32 = 36418;
 pub const GL_TEXTURE_SWIZZLE_G: u32 = 36419;
 pub const GL_TEXTURE_SWIZZLE_B: u32 = 36420;
@@ -245,11 +268,55 @@ pub const GL_TIME_ELAPSED: u32 = 35007;
 pub const GL_QUERY_RESULT: u32 = 34918;
 pub const GL_QUERY_RESULT_AVAILABLE: u32 = 34919;
 pub const GL_VENDOR: u32 = 0x1F00;
+pub const GL_RENDERER: u32 = 0x1F01;
 pub const GL_VERSION: u32 = 0x1F02;
 pub const GL_SHADING_LANGUAGE_VERSION: GLenum = 0x8B8C;
 pub const GL_FRONT_AND_BACK: GLenum = 0x0408;
 pub const GL_FILL: GLenum = 0x1B02;
 pub const GL_LINE: GLenum = 0x1B01;
+pub const GL_POINT: GLenum = 0x1B00;
+pub const GL_PRIMITIVE_RESTART: GLenum = 0x8F9D;
+pub const GL_PRIMITIVE_RESTART_FIXED_INDEX: GLenum = 0x8D69;
+pub const GL_TRANSFORM_FEEDBACK_BUFFER: GLenum = 0x8C8E;
+pub const GL_INTERLEAVED_ATTRIBS: GLenum = 0x8C8C;
+pub const GL_SEPARATE_ATTRIBS: GLenum = 0x8C8D;
+pub const GL_RASTERIZER_DISCARD: GLenum = 0x8C89;
+pub const GL_SAMPLES_PASSED: GLenum = 0x8914;
+pub const GL_ANY_SAMPLES_PASSED: GLenum = 0x8C2F;
+pub const GL_QUERY_WAIT: GLenum = 0x8E13;
+pub const GL_QUERY_NO_WAIT: GLenum = 0x8E14;
+pub const GL_TIMESTAMP: GLenum = 0x8E28;
+pub const GL_ACTIVE_UNIFORMS: GLenum = 0x8B86;
+pub const GL_ACTIVE_ATTRIBUTES: GLenum = 0x8B89;
+pub const GL_FLOAT_VEC2: GLenum = 0x8B50;
+pub const GL_FLOAT_VEC3: GLenum = 0x8B51;
+pub const GL_FLOAT_VEC4: GLenum = 0x8B52;
+pub const GL_INT_VEC2: GLenum = 0x8B53;
+pub const GL_INT_VEC3: GLenum = 0x8B54;
+pub const GL_INT_VEC4: GLenum = 0x8B55;
+pub const GL_BOOL: GLenum = 0x8B56;
+pub const GL_FLOAT_MAT2: GLenum = 0x8B5A;
+pub const GL_FLOAT_MAT3: GLenum = 0x8B5B;
+pub const GL_FLOAT_MAT4: GLenum = 0x8B5C;
+pub const GL_SAMPLER_2D: GLenum = 0x8B5E;
+pub const GL_SAMPLER_3D: GLenum = 0x8B5F;
+pub const GL_SAMPLER_CUBE: GLenum = 0x8B60;
+pub const GL_SAMPLER_2D_ARRAY: GLenum = 0x8DC1;
+pub const GL_COMPUTE_SHADER: GLenum = 0x91B9;
+pub const GL_GEOMETRY_SHADER: GLenum = 0x8DD9;
+pub const GL_PROGRAM_BINARY_LENGTH: GLenum = 0x8741;
+pub const GL_NUM_PROGRAM_BINARY_FORMATS: GLenum = 0x87FE;
+pub const GL_PROGRAM_BINARY_RETRIEVABLE_HINT: GLenum = 0x8257;
+pub const GL_DEBUG_OUTPUT: GLenum = 0x92E0;
+pub const GL_DEBUG_OUTPUT_SYNCHRONOUS: GLenum = 0x8242;
+pub const GL_DEBUG_SEVERITY_NOTIFICATION: GLenum = 0x826B;
+pub const GL_DEBUG_SOURCE_APPLICATION: GLenum = 0x824A;
+pub const GL_BUFFER: GLenum = 0x82E0;
+pub const GL_PROGRAM: GLenum = 0x82E2;
+pub const GL_TEXTURE: GLenum = 0x1702;
+pub const GL_SHADER_STORAGE_BUFFER: GLenum = 0x90D2;
+pub const GL_ALL_BARRIER_BITS: GLbitfield = 0xFFFFFFFF;
+pub const GL_READ_WRITE: GLenum = 0x88BA;
 pub const GL_TEXTURE_BASE_LEVEL: GLenum = 0x813C;
 pub const GL_TEXTURE_MAX_LEVEL: GLenum = 0x813D;
 pub const GL_TEXTURE_CUBE_MAP_SEAMLESS: GLenum = 0x884F;
@@ -318,7 +385,12 @@ macro_rules! gl_loader {
 
         $(
             pub unsafe fn $fn($($arg: $t),*) -> $res {
-                __pfns::$fn.unwrap()( $($arg),* )
+                let res = __pfns::$fn.unwrap()( $($arg),* );
+                #[cfg(feature = "gl-debug")]
+                if stringify!($fn) != "glGetError" {
+                    check_gl_error(stringify!($fn), __pfns::glGetError);
+                }
+                res
             }
         )*
 
@@ -384,6 +456,24 @@ gl_loader!(
         infoLog: *mut GLchar
     ) -> (),
     fn glGetAttribLocation(program: GLuint, name: *const GLchar) -> GLint,
+    fn glGetActiveUniform(
+        program: GLuint,
+        index: GLuint,
+        bufSize: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar
+    ) -> (),
+    fn glGetActiveAttrib(
+        program: GLuint,
+        index: GLuint,
+        bufSize: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar
+    ) -> (),
     fn glDisableVertexAttribArray(index: GLuint) -> (),
     fn glDeleteShader(shader: GLuint) -> (),
     fn glDeleteProgram(program: GLuint) -> (),
@@ -398,6 +488,21 @@ gl_loader!(
         height: GLsizei
     ) -> (),
     fn glDrawBuffers(n: GLsizei, bufs: *const GLenum) -> (),
+    fn glEnablei(target: GLenum, index: GLuint) -> (),
+    fn glDisablei(target: GLenum, index: GLuint) -> (),
+    fn glBlendFuncSeparatei(
+        buf: GLuint,
+        srcRGB: GLenum,
+        dstRGB: GLenum,
+        srcAlpha: GLenum,
+        dstAlpha: GLenum
+    ) -> (),
+    fn glBlendEquationSeparatei(buf: GLuint, modeRGB: GLenum, modeAlpha: GLenum) -> (),
+    fn glInvalidateFramebuffer(
+        target: GLenum,
+        numAttachments: GLsizei,
+        attachments: *const GLenum
+    ) -> (),
     fn glVertexAttribDivisor(index: GLuint, divisor: GLuint) -> (),
     fn glBufferSubData(
         target: GLenum,
@@ -636,20 +741,186 @@ gl_loader!(
     fn glGenQueries(n: GLsizei, ids: *mut GLuint) -> (),
     fn glGetQueryObjectiv(id: GLuint, pname: GLenum, params: *mut GLint) -> (),
     fn glGetQueryObjectui64v(id: GLuint, pname: GLenum, params: *mut GLuint64) -> (),
+    fn glGetQueryObjectuiv(id: GLuint, pname: GLenum, params: *mut GLuint) -> (),
+    fn glQueryCounter(id: GLuint, target: GLenum) -> (),
+    // GL_EXT_disjoint_timer_query, GLES3 has the other query functions in core
+    fn glGetQueryObjectui64vEXT(id: GLuint, pname: GLenum, params: *mut GLuint64) -> (),
+    fn glQueryCounterEXT(id: GLuint, target: GLenum) -> (),
+    fn glBeginConditionalRender(id: GLuint, mode: GLenum) -> (),
+    fn glEndConditionalRender() -> (),
     fn glFlush() -> (),
     fn glFinish() -> (),
-    fn glPolygonMode(face: GLenum, mode: GLenum) -> ()
+    fn glPolygonMode(face: GLenum, mode: GLenum) -> (),
+    fn glPrimitiveRestartIndex(index: GLuint) -> (),
+    fn glTransformFeedbackVaryings(
+        program: GLuint,
+        count: GLsizei,
+        varyings: *const *const GLchar,
+        bufferMode: GLenum
+    ) -> (),
+    fn glBeginTransformFeedback(primitiveMode: GLenum) -> (),
+    fn glEndTransformFeedback() -> (),
+    fn glBindBufferBase(target: GLenum, index: GLuint, buffer: GLuint) -> (),
+    fn glDispatchCompute(num_groups_x: GLuint, num_groups_y: GLuint, num_groups_z: GLuint) -> (),
+    fn glMemoryBarrier(barriers: GLbitfield) -> (),
+    fn glBindImageTexture(
+        unit: GLuint,
+        texture: GLuint,
+        level: GLint,
+        layered: GLboolean,
+        layer: GLint,
+        access: GLenum,
+        format: GLenum
+    ) -> (),
+    fn glTexStorage2D(
+        target: GLenum,
+        levels: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei
+    ) -> (),
+    fn glGetProgramBinary(
+        program: GLuint,
+        bufSize: GLsizei,
+        length: *mut GLsizei,
+        binaryFormat: *mut GLenum,
+        binary: *mut ::std::os::raw::c_void
+    ) -> (),
+    fn glProgramBinary(
+        program: GLuint,
+        binaryFormat: GLenum,
+        binary: *const ::std::os::raw::c_void,
+        length: GLsizei
+    ) -> (),
+    fn glProgramParameteri(program: GLuint, pname: GLenum, value: GLint) -> (),
+    fn glDebugMessageCallback(callback: GLDEBUGPROC, userParam: *const ::std::os::raw::c_void) -> (),
+    fn glPushDebugGroup(source: GLenum, id: GLuint, length: GLsizei, message: *const GLchar) -> (),
+    fn glPopDebugGroup() -> (),
+    fn glObjectLabel(identifier: GLenum, name: GLuint, length: GLsizei, label: *const GLchar) -> ()
 );
 
+#[cfg(feature = "gl-debug")]
+fn check_gl_error(function: &str, get_error: Option<extern "C" fn() -> GLenum>) {
+    let get_error = match get_error {
+        Some(get_error) => get_error,
+        None => return,
+    };
+    // there might be multiple error flags set, but with a lost context
+    // glGetError may never return GL_NO_ERROR
+    for _ in 0..8 {
+        let error = get_error();
+        if error == GL_NO_ERROR {
+            return;
+        }
+        let error = match error {
+            GL_INVALID_ENUM => "GL_INVALID_ENUM".to_string(),
+            GL_INVALID_VALUE => "GL_INVALID_VALUE".to_string(),
+            GL_INVALID_OPERATION => "GL_INVALID_OPERATION".to_string(),
+            GL_OUT_OF_MEMORY => "GL_OUT_OF_MEMORY".to_string(),
+            GL_INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION".to_string(),
+            error => format!("0x{:X}", error),
+        };
+        eprintln!(
+            "{} failed with {}\n{}",
+            function,
+            error,
+            std::backtrace::Backtrace::force_capture()
+        );
+    }
+}
+
+#[cfg(feature = "gl-debug")]
+extern "system" fn debug_message_callback(
+    _source: GLenum,
+    _type: GLenum,
+    _id: GLuint,
+    severity: GLenum,
+    length: GLsizei,
+    message: *const GLchar,
+    _user_param: *mut ::std::os::raw::c_void,
+) {
+    if severity == GL_DEBUG_SEVERITY_NOTIFICATION || message.is_null() {
+        return;
+    }
+    let message = unsafe { std::slice::from_raw_parts(message as *const u8, length.max(0) as _) };
+    eprintln!(
+        "GL: {}\n{}",
+        String::from_utf8_lossy(message),
+        std::backtrace::Backtrace::force_capture()
+    );
+}
+
+/// With the "gl-debug" feature, route KHR_debug messages to stderr.
+/// Synchronous output keeps the backtrace pointing to the offending call.
+pub unsafe fn enable_debug_output() {
+    #[cfg(feature = "gl-debug")]
+    {
+        if has_khr_debug() {
+            glEnable(GL_DEBUG_OUTPUT);
+            glEnable(GL_DEBUG_OUTPUT_SYNCHRONOUS);
+            glDebugMessageCallback(Some(debug_message_callback), std::ptr::null());
+        }
+    }
+}
+
+/// Debug output, debug groups and object labels: core in GLES3.2 and GL4.3.
+pub unsafe fn has_khr_debug() -> bool {
+    if is_gles() {
+        gl_version() >= (3, 2)
+    } else {
+        gl_version() >= (4, 3) || has_extension("GL_KHR_debug")
+    }
+}
+
 // note that glGetString only works after first glSwapBuffer,
 // not just after context creation
 pub unsafe fn is_gl2() -> bool {
     let version_string = glGetString(super::gl::GL_VERSION);
-    let version_string = std::ffi::CStr::from_ptr(version_string as _)
-        .to_str()
-        .unwrap();
+    let version_string = std::ffi::CStr::from_ptr(version_string as _).to_string_lossy();
 
     version_string.is_empty()
         || version_string.starts_with("2")
         || version_string.starts_with("OpenGL ES 2")
 }
+
+pub unsafe fn is_gles() -> bool {
+    let version_string = glGetString(super::gl::GL_VERSION);
+    let version_string = std::ffi::CStr::from_ptr(version_string as _).to_string_lossy();
+
+    version_string.contains("OpenGL ES")
+}
+
+/// (major, minor) version of the current context, the same for both GL and GLES.
+pub unsafe fn gl_version() -> (u32, u32) {
+    let version_string = glGetString(super::gl::GL_VERSION);
+    if version_string.is_null() {
+        return (0, 0);
+    }
+    // lossy, a non UTF-8 vendor suffix is not worth a panic
+    let version_string = std::ffi::CStr::from_ptr(version_string as _).to_string_lossy();
+    let version_string = version_string.trim_start_matches("OpenGL ES ");
+    let mut numbers = version_string
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|n| n.parse::<u32>().unwrap_or(0));
+
+    (numbers.next().unwrap_or(0), numbers.next().unwrap_or(0))
+}
+
+pub unsafe fn has_extension(name: &str) -> bool {
+    if is_gl2() {
+        let extensions = glGetString(GL_EXTENSIONS);
+        if extensions.is_null() {
+            return false;
+        }
+        let extensions = std::ffi::CStr::from_ptr(extensions as _).to_string_lossy();
+        return extensions.split(' ').any(|extension| extension == name);
+    }
+
+    let mut count = 0;
+    glGetIntegerv(GL_NUM_EXTENSIONS, &mut count);
+    (0..count as GLuint).any(|i| {
+        let extension = glGetStringi(GL_EXTENSIONS, i);
+        !extension.is_null()
+            && std::ffi::CStr::from_ptr(extension as _).to_bytes() == name.as_bytes()
+    })
+}
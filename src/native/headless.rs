@@ -0,0 +1,35 @@
+use crate::native::{egl, Clipboard, NativeDisplayData};
+
+struct HeadlessClipboard;
+
+impl Clipboard for HeadlessClipboard {
+    fn get(&mut self) -> Option<String> {
+        None
+    }
+
+    fn set(&mut self, _string: &str) {}
+}
+
+/// Make a pbuffer EGL context current on the calling thread and load GL with it.
+pub(crate) fn init(width: i32, height: i32) -> Option<()> {
+    unsafe {
+        let mut libegl = egl::LibEgl::try_load()?;
+        egl::create_headless_egl_context(&mut libegl, width, height).ok()?;
+
+        crate::native::gl::load_gl_funcs(|proc| {
+            let name = std::ffi::CString::new(proc).unwrap();
+            libegl.eglGetProcAddress.expect("non-null function pointer")(name.as_ptr() as _)
+        });
+        // the context lives till the end of the process, libEGL should stay loaded
+        std::mem::forget(libegl);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        // nothing processes window requests, but sending them should still work
+        std::mem::forget(rx);
+        let mut display = NativeDisplayData::new(width, height, tx, Box::new(HeadlessClipboard));
+        display.egl = true;
+        // a no-op for all but the first context of the process
+        crate::set_display(display);
+    }
+    Some(())
+}
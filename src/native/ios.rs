@@ -5,23 +5,26 @@
 use {
     crate::{
         conf::{self, AppleGfxApi, Conf},
-        event::{EventHandler, TouchPhase},
+        event::{EventHandler, TouchDetails, TouchPhase},
         fs,
         native::{
             apple::{
                 apple_util::{self, *},
                 frameworks::{self, *},
             },
-            NativeDisplayData,
+            NativeDisplayData, Request,
         },
         native_display,
     },
-    std::os::raw::c_void,
+    std::{os::raw::c_void, sync::mpsc::Receiver},
 };
 
 struct IosDisplay {
     view: ObjcId,
     view_ctrl: ObjcId,
+    display_link: ObjcId,
+    // the preferredFramesPerSecond of display_link
+    frame_rate: u32,
     _textfield_dlg: ObjcId,
     textfield: ObjcId,
     gfx_api: conf::AppleGfxApi,
@@ -29,9 +32,51 @@ struct IosDisplay {
     event_handler: Option<Box<dyn EventHandler>>,
     _gles2: bool,
     f: Option<Box<dyn 'static + FnOnce() -> Box<dyn EventHandler>>>,
+    native_requests: Receiver<Request>,
+    touch_ids: crate::native::TouchIds,
+    gamepads: crate::native::apple::gamepad::Gamepads,
+    power: crate::native::apple::power::Power,
 }
 
 impl IosDisplay {
+    /// `window::set_target_fps`, 60 by default like GLKViewController and
+    /// MTKView. Over 60 with ProMotion only for the applications with
+    /// CADisableMinimumFrameDurationOnPhone in their Info.plist.
+    fn update_frame_rate(&mut self) {
+        let frame_rate = native_display().lock().unwrap().frame_rate();
+        unsafe {
+            let main_screen: ObjcId = msg_send![class!(UIScreen), mainScreen];
+            let max_frame_rate: i64 = msg_send![main_screen, maximumFramesPerSecond];
+            let frame_rate = frame_rate
+                .filter(|&fps| fps > 0)
+                .unwrap_or(60)
+                .min(max_frame_rate.max(1) as u32);
+            if frame_rate == self.frame_rate {
+                return;
+            }
+            self.frame_rate = frame_rate;
+
+            // iOS 15, preferredFramesPerSecond stays at 60 on ProMotion
+            let responds: BOOL = msg_send![
+                self.display_link,
+                respondsToSelector: sel!(setPreferredFrameRateRange:)
+            ];
+            if responds == YES {
+                let range = CAFrameRateRange {
+                    minimum: frame_rate as f32,
+                    maximum: frame_rate as f32,
+                    preferred: frame_rate as f32,
+                };
+                let () = msg_send![self.display_link, setPreferredFrameRateRange: range];
+            } else {
+                let () = msg_send![
+                    self.display_link,
+                    setPreferredFramesPerSecond: frame_rate as i64
+                ];
+            }
+        }
+    }
+
     fn show_keyboard(&mut self, show: bool) {
         unsafe {
             if show {
@@ -41,6 +86,71 @@ impl IosDisplay {
             }
         }
     }
+
+    fn process_requests(&mut self) {
+        while let Ok(request) = self.native_requests.try_recv() {
+            match request {
+                Request::ShowKeyboard(show) | Request::SetTextInputEnabled(show) => {
+                    self.show_keyboard(show)
+                }
+                Request::SetKeyboardType(keyboard_type) => unsafe {
+                    // UIKeyboardType
+                    let keyboard_type: i64 = match keyboard_type {
+                        crate::KeyboardType::Text => 0,
+                        crate::KeyboardType::Number => 4,
+                        crate::KeyboardType::Decimal => 8,
+                        crate::KeyboardType::Phone => 5,
+                        crate::KeyboardType::Email => 7,
+                        crate::KeyboardType::Url => 3,
+                    };
+                    let () = msg_send![self.textfield, setKeyboardType: keyboard_type];
+                    // the keyboard shown changes only with this
+                    let () = msg_send![self.textfield, reloadInputViews];
+                },
+                Request::SetGamepadRumble {
+                    id,
+                    low_frequency,
+                    high_frequency,
+                    duration,
+                } => self
+                    .gamepads
+                    .rumble(id, low_frequency, high_frequency, duration),
+                Request::LockOrientation(_) => unsafe {
+                    // the view controller asks for the orientations again
+                    let responds: BOOL = msg_send![
+                        self.view_ctrl,
+                        respondsToSelector: sel!(setNeedsUpdateOfSupportedInterfaceOrientations)
+                    ];
+                    if responds == YES {
+                        let () = msg_send![
+                            self.view_ctrl,
+                            setNeedsUpdateOfSupportedInterfaceOrientations
+                        ];
+                    } else {
+                        let () =
+                            msg_send![class!(UIViewController), attemptRotationToDeviceOrientation];
+                    }
+                },
+                Request::SetFullscreenImmersive(immersive) => unsafe {
+                    // the view controller asks again, from iOS 11
+                    let immersive = if immersive { YES } else { NO };
+                    (*self.view_ctrl).set_ivar("immersive", immersive);
+                    let () = msg_send![self.view_ctrl, setNeedsStatusBarAppearanceUpdate];
+                    let () = msg_send![self.view_ctrl, setNeedsUpdateOfHomeIndicatorAutoHidden];
+                    let () = msg_send![
+                        self.view_ctrl,
+                        setNeedsUpdateOfScreenEdgesDeferringSystemGestures
+                    ];
+                },
+                Request::SetKeepScreenOn(keep_on) => unsafe {
+                    let application: ObjcId = msg_send![class!(UIApplication), sharedApplication];
+                    let disabled = if keep_on { YES } else { NO };
+                    let () = msg_send![application, setIdleTimerDisabled: disabled];
+                },
+                _ => {}
+            }
+        }
+    }
 }
 
 fn get_window_payload(this: &Object) -> &mut IosDisplay {
@@ -53,56 +163,62 @@ fn get_window_payload(this: &Object) -> &mut IosDisplay {
 pub fn define_glk_or_mtk_view(superclass: &Class) -> *const Class {
     let mut decl = ClassDecl::new("QuadView", superclass).unwrap();
 
-    fn on_touch(this: &Object, event: ObjcId, mut callback: impl FnMut(u64, f32, f32)) {
+    // the touches that changed, not all of the event
+    fn on_touch(this: &Object, touches: ObjcId, phase: TouchPhase) {
+        let payload = get_window_payload(this);
+        let scale = native_display().lock().unwrap().dpi_scale as f64;
+
         unsafe {
-            let enumerator: ObjcId = msg_send![event, allTouches];
-            let size: u64 = msg_send![enumerator, count];
-            let enumerator: ObjcId = msg_send![enumerator, objectEnumerator];
+            let enumerator: ObjcId = msg_send![touches, objectEnumerator];
 
-            for touch_id in 0..size {
+            loop {
                 let ios_touch: ObjcId = msg_send![enumerator, nextObject];
-                let mut ios_pos: NSPoint = msg_send![ios_touch, locationInView: this];
-
-                if native_display().lock().unwrap().high_dpi {
-                    ios_pos.x *= 2.;
-                    ios_pos.y *= 2.;
+                if ios_touch.is_null() {
+                    break;
+                }
+                let ios_pos: NSPoint = msg_send![ios_touch, locationInView: this];
+                let force: f64 = msg_send![ios_touch, force];
+                let max_force: f64 = msg_send![ios_touch, maximumPossibleForce];
+                let radius: f64 = msg_send![ios_touch, majorRadius];
+                // the seconds since the boot of the device
+                let timestamp: f64 = msg_send![ios_touch, timestamp];
+                crate::native::update_event_timestamp(timestamp);
+                let details = TouchDetails {
+                    // no 3D Touch on most devices
+                    pressure: (max_force > 0.).then(|| (force / max_force) as f32),
+                    radius: ((radius * scale) as f32, (radius * scale) as f32),
+                    device_id: 0,
+                };
+
+                // a UITouch is the same object for the whole touch
+                let id = payload.touch_ids.get(phase, ios_touch as u64);
+                if let Some(ref mut event_handler) = payload.event_handler {
+                    event_handler.touch_details_event(
+                        phase,
+                        id,
+                        (ios_pos.x * scale) as _,
+                        (ios_pos.y * scale) as _,
+                        details,
+                    );
                 }
-
-                callback(touch_id, ios_pos.x as _, ios_pos.y as _);
             }
         }
     }
-    extern "C" fn touches_began(this: &Object, _: Sel, _: ObjcId, event: ObjcId) {
-        let payload = get_window_payload(this);
-
-        if let Some(ref mut event_handler) = payload.event_handler {
-            on_touch(this, event, |id, x, y| {
-                event_handler.touch_event(TouchPhase::Started, id, x as _, y as _);
-            });
-        }
+    extern "C" fn touches_began(this: &Object, _: Sel, touches: ObjcId, _: ObjcId) {
+        on_touch(this, touches, TouchPhase::Started);
     }
 
-    extern "C" fn touches_moved(this: &Object, _: Sel, _: ObjcId, event: ObjcId) {
-        let payload = get_window_payload(this);
-
-        if let Some(ref mut event_handler) = payload.event_handler {
-            on_touch(this, event, |id, x, y| {
-                event_handler.touch_event(TouchPhase::Moved, id, x as _, y as _);
-            });
-        }
+    extern "C" fn touches_moved(this: &Object, _: Sel, touches: ObjcId, _: ObjcId) {
+        on_touch(this, touches, TouchPhase::Moved);
     }
 
-    extern "C" fn touches_ended(this: &Object, _: Sel, _: ObjcId, event: ObjcId) {
-        let payload = get_window_payload(this);
-
-        if let Some(ref mut event_handler) = payload.event_handler {
-            on_touch(this, event, |id, x, y| {
-                event_handler.touch_event(TouchPhase::Ended, id, x as _, y as _);
-            });
-        }
+    extern "C" fn touches_ended(this: &Object, _: Sel, touches: ObjcId, _: ObjcId) {
+        on_touch(this, touches, TouchPhase::Ended);
     }
 
-    extern "C" fn touches_canceled(_: &Object, _: Sel, _: ObjcId, _: ObjcId) {}
+    extern "C" fn touches_canceled(this: &Object, _: Sel, touches: ObjcId, _: ObjcId) {
+        on_touch(this, touches, TouchPhase::Cancelled);
+    }
 
     unsafe {
         decl.add_method(sel!(isOpaque), yes as extern "C" fn(&Object, Sel) -> BOOL);
@@ -177,19 +293,9 @@ pub fn define_glk_or_mtk_view_dlg(superclass: &Class) -> *const Class {
 
         let main_screen: ObjcId = unsafe { msg_send![class!(UIScreen), mainScreen] };
         let screen_rect: NSRect = unsafe { msg_send![main_screen, bounds] };
-        let high_dpi = native_display().lock().unwrap().high_dpi;
-
-        let (screen_width, screen_height) = if high_dpi {
-            (
-                screen_rect.size.width as i32 * 2,
-                screen_rect.size.height as i32 * 2,
-            )
-        } else {
-            (
-                screen_rect.size.width as i32,
-                screen_rect.size.height as i32,
-            )
-        };
+        let scale = native_display().lock().unwrap().dpi_scale as f64;
+        let screen_width = (screen_rect.size.width * scale) as i32;
+        let screen_height = (screen_rect.size.height * scale) as i32;
 
         if native_display().lock().unwrap().screen_width != screen_width
             || native_display().lock().unwrap().screen_height != screen_height
@@ -200,13 +306,32 @@ pub fn define_glk_or_mtk_view_dlg(superclass: &Class) -> *const Class {
                 d.screen_height = screen_height;
             }
             if let Some(ref mut event_handler) = payload.event_handler {
+                crate::native::update_orientation(&mut **event_handler);
                 event_handler.resize_event(screen_width as _, screen_height as _);
             }
         }
 
+        // iOS 11, changed by the rotations
+        let safe_area: UIEdgeInsets = unsafe { msg_send![payload.view, safeAreaInsets] };
+        let insets = crate::SafeAreaInsets {
+            left: (safe_area.left * scale) as f32,
+            top: (safe_area.top * scale) as f32,
+            right: (safe_area.right * scale) as f32,
+            bottom: (safe_area.bottom * scale) as f32,
+        };
         if let Some(ref mut event_handler) = payload.event_handler {
-            event_handler.update();
-            event_handler.draw();
+            crate::native::update_safe_area_insets(insets, &mut **event_handler);
+        }
+
+        payload.process_requests();
+        if let Some(ref mut event_handler) = payload.event_handler {
+            payload.gamepads.poll(&mut **event_handler);
+            payload.power.poll(&mut **event_handler);
+            crate::native::dispatch_custom_events(&mut **event_handler);
+            if !native_display().lock().unwrap().frames_paused() {
+                event_handler.update();
+                event_handler.draw();
+            }
         }
     }
     // wrapper to make sel! macros happy
@@ -214,6 +339,18 @@ pub fn define_glk_or_mtk_view_dlg(superclass: &Class) -> *const Class {
         draw_in_rect(this, s, o, nil);
     }
 
+    // the views only draw when they are told to, here
+    extern "C" fn display_link_fired(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        payload.update_frame_rate();
+        unsafe {
+            match payload.gfx_api {
+                AppleGfxApi::OpenGl => msg_send_![payload.view, display],
+                AppleGfxApi::Metal => msg_send_![payload.view, draw],
+            };
+        }
+    }
+
     unsafe {
         decl.add_method(
             sel!(glkView: drawInRect:),
@@ -224,12 +361,67 @@ pub fn define_glk_or_mtk_view_dlg(superclass: &Class) -> *const Class {
             sel!(drawInMTKView:),
             draw_in_rect2 as extern "C" fn(&Object, Sel, ObjcId),
         );
+        decl.add_method(
+            sel!(displayLinkFired:),
+            display_link_fired as extern "C" fn(&Object, Sel, ObjcId),
+        );
     }
 
     decl.add_ivar::<*mut c_void>("display_ptr");
     return decl.register();
 }
 
+/// The view controller with the orientations of `window::lock_orientation`
+/// and the status bar, home indicator and edge gestures of
+/// `window::set_fullscreen_immersive`.
+fn define_view_ctrl() -> *const Class {
+    let mut decl = ClassDecl::new("QuadViewCtrl", class!(UIViewController)).unwrap();
+
+    // UIInterfaceOrientationMask
+    extern "C" fn supported_interface_orientations(_: &Object, _: Sel) -> u64 {
+        match native_display().lock().unwrap().supported_orientations {
+            conf::Orientation::Any => 30,
+            conf::Orientation::Portrait => 2 | 4,
+            conf::Orientation::Landscape => 8 | 16,
+        }
+    }
+
+    extern "C" fn prefers_hidden(this: &Object, _: Sel) -> BOOL {
+        unsafe { *this.get_ivar::<BOOL>("immersive") }
+    }
+
+    // UIRectEdgeAll, the first swipe from an edge only grabs the attention
+    extern "C" fn preferred_screen_edges_deferring_system_gestures(this: &Object, _: Sel) -> u64 {
+        if prefers_hidden(this, sel!(prefersStatusBarHidden)) == YES {
+            15
+        } else {
+            0
+        }
+    }
+
+    unsafe {
+        decl.add_method(
+            sel!(supportedInterfaceOrientations),
+            supported_interface_orientations as extern "C" fn(&Object, Sel) -> u64,
+        );
+        decl.add_method(
+            sel!(prefersStatusBarHidden),
+            prefers_hidden as extern "C" fn(&Object, Sel) -> BOOL,
+        );
+        decl.add_method(
+            sel!(prefersHomeIndicatorAutoHidden),
+            prefers_hidden as extern "C" fn(&Object, Sel) -> BOOL,
+        );
+        decl.add_method(
+            sel!(preferredScreenEdgesDeferringSystemGestures),
+            preferred_screen_edges_deferring_system_gestures as extern "C" fn(&Object, Sel) -> u64,
+        );
+    }
+
+    decl.add_ivar::<BOOL>("immersive");
+    return decl.register();
+}
+
 // metal or opengl view and the objects required to collect all the window events
 struct View {
     view: ObjcId,
@@ -239,7 +431,7 @@ struct View {
     _gles2: bool,
 }
 
-unsafe fn create_opengl_view(screen_rect: NSRect, _sample_count: i32, high_dpi: bool) -> View {
+unsafe fn create_opengl_view(screen_rect: NSRect, _sample_count: i32, scale: f64) -> View {
     let glk_view_obj: ObjcId = msg_send![define_glk_or_mtk_view(class!(GLKView)), alloc];
     let glk_view_obj: ObjcId = msg_send![glk_view_obj, initWithFrame: screen_rect];
 
@@ -272,17 +464,12 @@ unsafe fn create_opengl_view(screen_rect: NSRect, _sample_count: i32, high_dpi:
     msg_send_![glk_view_obj, setEnableSetNeedsDisplay: NO];
     msg_send_![glk_view_obj, setUserInteractionEnabled: YES];
     msg_send_![glk_view_obj, setMultipleTouchEnabled: YES];
-    if high_dpi {
-        msg_send_![glk_view_obj, setContentScaleFactor: 2.0];
-    } else {
-        msg_send_![glk_view_obj, setContentScaleFactor: 1.0];
-    }
+    msg_send_![glk_view_obj, setContentScaleFactor: scale];
 
-    let view_ctrl_obj: ObjcId = msg_send![class!(GLKViewController), alloc];
+    let view_ctrl_obj: ObjcId = msg_send![define_view_ctrl(), alloc];
     let view_ctrl_obj: ObjcId = msg_send![view_ctrl_obj, init];
 
     msg_send_![view_ctrl_obj, setView: glk_view_obj];
-    msg_send_![view_ctrl_obj, setPreferredFramesPerSecond:60];
 
     View {
         view: glk_view_obj,
@@ -292,19 +479,21 @@ unsafe fn create_opengl_view(screen_rect: NSRect, _sample_count: i32, high_dpi:
     }
 }
 
-unsafe fn create_metal_view(screen_rect: NSRect, _sample_count: i32, _high_dpi: bool) -> View {
+unsafe fn create_metal_view(screen_rect: NSRect, _sample_count: i32, scale: f64) -> View {
     let mtk_view_obj: ObjcId = msg_send![define_glk_or_mtk_view(class!(MTKView)), alloc];
     let mtk_view_obj: ObjcId = msg_send![mtk_view_obj, initWithFrame: screen_rect];
 
     let mtk_view_dlg_obj: ObjcId = msg_send![define_glk_or_mtk_view_dlg(class!(NSObject)), alloc];
     let mtk_view_dlg_obj: ObjcId = msg_send![mtk_view_dlg_obj, init];
 
-    let view_ctrl_obj: ObjcId = msg_send![class!(UIViewController), alloc];
+    let view_ctrl_obj: ObjcId = msg_send![define_view_ctrl(), alloc];
     let view_ctrl_obj: ObjcId = msg_send![view_ctrl_obj, init];
 
     msg_send_![view_ctrl_obj, setView: mtk_view_obj];
 
-    msg_send_![mtk_view_obj, setPreferredFramesPerSecond:60];
+    msg_send_![mtk_view_obj, setPaused: YES];
+    msg_send_![mtk_view_obj, setEnableSetNeedsDisplay: NO];
+    msg_send_![mtk_view_obj, setContentScaleFactor: scale];
     msg_send_![mtk_view_obj, setDelegate: mtk_view_dlg_obj];
     let device = MTLCreateSystemDefaultDevice();
     msg_send_![mtk_view_obj, setDevice: device];
@@ -332,7 +521,7 @@ pub fn define_app_delegate() -> *const Class {
     let mut decl = ClassDecl::new("NSAppDelegate", superclass).unwrap();
 
     extern "C" fn did_finish_launching_with_options(
-        _: &Object,
+        this: &Object,
         _: Sel,
         _: ObjcId,
         _: ObjcId,
@@ -342,29 +531,19 @@ pub fn define_app_delegate() -> *const Class {
 
             let main_screen: ObjcId = msg_send![class!(UIScreen), mainScreen];
             let screen_rect: NSRect = msg_send![main_screen, bounds];
-
-            let (screen_width, screen_height) = if conf.high_dpi {
-                (
-                    screen_rect.size.width as i32 * 2,
-                    screen_rect.size.height as i32 * 2,
-                )
+            // 2 or 3, the pixels of the panel
+            let scale: f64 = if conf.high_dpi {
+                msg_send![main_screen, nativeScale]
             } else {
-                (
-                    screen_rect.size.width as i32,
-                    screen_rect.size.height as i32,
-                )
+                1.
             };
 
             let window_obj: ObjcId = msg_send![class!(UIWindow), alloc];
             let window_obj: ObjcId = msg_send![window_obj, initWithFrame: screen_rect];
 
             let view = match conf.platform.apple_gfx_api {
-                AppleGfxApi::OpenGl => {
-                    create_opengl_view(screen_rect, conf.sample_count, conf.high_dpi)
-                }
-                AppleGfxApi::Metal => {
-                    create_metal_view(screen_rect, conf.sample_count, conf.high_dpi)
-                }
+                AppleGfxApi::OpenGl => create_opengl_view(screen_rect, conf.sample_count, scale),
+                AppleGfxApi::Metal => create_metal_view(screen_rect, conf.sample_count, scale),
             };
 
             let (textfield_dlg, textfield) = {
@@ -398,13 +577,28 @@ pub fn define_app_delegate() -> *const Class {
             let clipboard = Box::new(IosClipboard);
             crate::set_display(NativeDisplayData {
                 high_dpi: conf.high_dpi,
+                dpi_scale: scale as f32,
+                target_fps: conf.max_fps,
                 gfx_api: conf.platform.apple_gfx_api,
+                power_info: crate::native::apple::power::power_info(),
+                background_mode: conf.background_mode,
+                supported_orientations: conf.supported_orientations,
                 ..NativeDisplayData::new(conf.window_width, conf.window_height, tx, clipboard)
             });
 
+            let display_link: ObjcId = msg_send![
+                class!(CADisplayLink),
+                displayLinkWithTarget: view.view_dlg
+                selector: sel!(displayLinkFired:)
+            ];
+            let main_run_loop: ObjcId = msg_send![class!(NSRunLoop), mainRunLoop];
+            msg_send_![display_link, addToRunLoop: main_run_loop forMode: NSRunLoopCommonModes];
+
             let payload = Box::new(IosDisplay {
                 view: view.view,
                 view_ctrl: view.view_ctrl,
+                display_link,
+                frame_rate: 0,
                 textfield,
                 _textfield_dlg: textfield_dlg,
                 gfx_api: conf.platform.apple_gfx_api,
@@ -412,12 +606,17 @@ pub fn define_app_delegate() -> *const Class {
                 f: Some(Box::new(f)),
                 event_handler: None,
                 _gles2: view._gles2,
+                native_requests: rx,
+                touch_ids: Default::default(),
+                gamepads: crate::native::apple::gamepad::Gamepads::new(),
+                power: crate::native::apple::power::Power::new(),
             });
             let payload_ptr = Box::into_raw(payload) as *mut std::ffi::c_void;
 
             (*view.view).set_ivar("display_ptr", payload_ptr);
             (*view.view_dlg).set_ivar("display_ptr", payload_ptr);
             (*textfield_dlg).set_ivar("display_ptr", payload_ptr);
+            (*(this as *const _ as ObjcId)).set_ivar("display_ptr", payload_ptr);
 
             msg_send_![window_obj, addSubview: view.view];
 
@@ -428,13 +627,90 @@ pub fn define_app_delegate() -> *const Class {
         YES
     }
 
+    // iOS does not wait for the application to quit, quit_requested_event is
+    // only to save, and the Drop of the event handler is the last thing
+    extern "C" fn application_will_terminate(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        if let Some(mut event_handler) = payload.event_handler.take() {
+            native_display().lock().unwrap().quit_requested = true;
+            event_handler.quit_requested_event();
+        }
+    }
+
+    // no OpenGL in the background, iOS would kill the application for it
+    extern "C" fn application_did_enter_background(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        unsafe { msg_send_![payload.display_link, setPaused: YES] };
+        if let Some(ref mut event_handler) = payload.event_handler {
+            event_handler.save_state();
+            crate::native::update_occluded(true, &mut **event_handler);
+        }
+    }
+
+    extern "C" fn application_will_enter_foreground(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        unsafe { msg_send_![payload.display_link, setPaused: NO] };
+        if let Some(ref mut event_handler) = payload.event_handler {
+            crate::native::update_occluded(false, &mut **event_handler);
+        }
+    }
+
+    // also for the notification center and the incoming calls
+    extern "C" fn application_will_resign_active(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        if let Some(ref mut event_handler) = payload.event_handler {
+            event_handler.app_paused();
+            crate::native::update_focus(false, &mut **event_handler);
+        }
+    }
+
+    extern "C" fn application_did_become_active(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        if let Some(ref mut event_handler) = payload.event_handler {
+            crate::native::update_focus(true, &mut **event_handler);
+            event_handler.app_resumed();
+        }
+    }
+
+    extern "C" fn application_did_receive_memory_warning(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        if let Some(ref mut event_handler) = payload.event_handler {
+            event_handler.low_memory();
+        }
+    }
+
     unsafe {
         decl.add_method(
             sel!(application: didFinishLaunchingWithOptions:),
             did_finish_launching_with_options
                 as extern "C" fn(&Object, Sel, ObjcId, ObjcId) -> BOOL,
         );
+        decl.add_method(
+            sel!(applicationWillTerminate:),
+            application_will_terminate as extern "C" fn(&Object, Sel, ObjcId),
+        );
+        decl.add_method(
+            sel!(applicationDidEnterBackground:),
+            application_did_enter_background as extern "C" fn(&Object, Sel, ObjcId),
+        );
+        decl.add_method(
+            sel!(applicationWillEnterForeground:),
+            application_will_enter_foreground as extern "C" fn(&Object, Sel, ObjcId),
+        );
+        decl.add_method(
+            sel!(applicationWillResignActive:),
+            application_will_resign_active as extern "C" fn(&Object, Sel, ObjcId),
+        );
+        decl.add_method(
+            sel!(applicationDidBecomeActive:),
+            application_did_become_active as extern "C" fn(&Object, Sel, ObjcId),
+        );
+        decl.add_method(
+            sel!(applicationDidReceiveMemoryWarning:),
+            application_did_receive_memory_warning as extern "C" fn(&Object, Sel, ObjcId),
+        );
     }
+    decl.add_ivar::<*mut c_void>("display_ptr");
 
     return decl.register();
 }
@@ -443,11 +719,42 @@ fn define_textfield_dlg() -> *const Class {
     let superclass = class!(NSObject);
     let mut decl = ClassDecl::new("NSTexfieldDlg", superclass).unwrap();
 
-    // those 3 callbacks are for resizing the canvas when keyboard is opened
-    // which is not currenlty supported by miniquad
-    extern "C" fn keyboard_was_shown(_: &Object, _: Sel, _notif: ObjcId) {}
-    extern "C" fn keyboard_will_be_hidden(_: &Object, _: Sel, _notif: ObjcId) {}
-    extern "C" fn keyboard_did_change_frame(_: &Object, _: Sel, _notif: ObjcId) {}
+    // the part of the view under the end frame of the keyboard, the frame is
+    // in the coordinates of the screen
+    fn keyboard_insets_changed(this: &Object, notif: ObjcId) {
+        let payload = get_window_payload(this);
+        let scale = native_display().lock().unwrap().dpi_scale as f64;
+        let height = unsafe {
+            let user_info: ObjcId = msg_send![notif, userInfo];
+            let frame: ObjcId = msg_send![user_info, objectForKey: UIKeyboardFrameEndUserInfoKey];
+            let frame: NSRect = msg_send![frame, CGRectValue];
+            let frame: NSRect = msg_send![payload.view, convertRect: frame fromView: nil];
+            let bounds: NSRect = msg_send![payload.view, bounds];
+            (bounds.size.height - frame.origin.y).max(0.)
+        };
+        if let Some(ref mut event_handler) = payload.event_handler {
+            crate::native::update_keyboard_height((height * scale) as f32, &mut **event_handler);
+        }
+    }
+    extern "C" fn keyboard_was_shown(this: &Object, _: Sel, notif: ObjcId) {
+        keyboard_insets_changed(this, notif);
+    }
+    extern "C" fn keyboard_will_be_hidden(this: &Object, _: Sel, _notif: ObjcId) {
+        let payload = get_window_payload(this);
+        if let Some(ref mut event_handler) = payload.event_handler {
+            crate::native::update_keyboard_height(0., &mut **event_handler);
+        }
+    }
+    extern "C" fn keyboard_did_change_frame(this: &Object, _: Sel, notif: ObjcId) {
+        keyboard_insets_changed(this, notif);
+    }
+
+    // the onscreen keyboard has no key up and down
+    fn key_press(event_handler: &mut dyn EventHandler, keycode: crate::event::KeyCode) {
+        let scancode = crate::native::scancodes::keycode_to_scancode(keycode);
+        event_handler.key_down_scancode_event(keycode, scancode, Default::default(), false);
+        event_handler.key_up_scancode_event(keycode, scancode, Default::default());
+    }
 
     extern "C" fn should_change_characters_in_range(
         this: &Object,
@@ -461,37 +768,23 @@ fn define_textfield_dlg() -> *const Class {
         unsafe {
             let len: u64 = msg_send![string, length];
             if len > 0 {
-                for i in 0..len {
-                    let c: u16 = msg_send![string, characterAtIndex: i];
-
+                // by chars, not utf-16 units, for the emojis
+                for c in apple_util::nsstring_to_string(string).chars() {
                     match c {
-                        c if c >= 32 && (c < 0xD800 || c > 0xDFFF) => {
-                            let c: char = char::from_u32(c as u32).unwrap();
+                        '\n' => {
                             if let Some(ref mut event_handler) = payload.event_handler {
-                                event_handler.char_event(c, Default::default(), false);
+                                key_press(&mut **event_handler, crate::event::KeyCode::Enter);
                             }
                         }
-                        10 => {
+                        ' ' => {
                             if let Some(ref mut event_handler) = payload.event_handler {
-                                event_handler.key_down_event(
-                                    crate::event::KeyCode::Enter,
-                                    Default::default(),
-                                    false,
-                                );
-                                event_handler
-                                    .key_up_event(crate::event::KeyCode::Enter, Default::default());
+                                event_handler.char_event(' ', Default::default(), false);
+                                key_press(&mut **event_handler, crate::event::KeyCode::Space);
                             }
                         }
-                        32 => {
+                        c if !c.is_control() => {
                             if let Some(ref mut event_handler) = payload.event_handler {
-                                event_handler.char_event(' ', Default::default(), false);
-                                event_handler.key_down_event(
-                                    crate::event::KeyCode::Space,
-                                    Default::default(),
-                                    false,
-                                );
-                                event_handler
-                                    .key_up_event(crate::event::KeyCode::Space, Default::default());
+                                event_handler.char_event(c, Default::default(), false);
                             }
                         }
                         _ => {}
@@ -499,13 +792,7 @@ fn define_textfield_dlg() -> *const Class {
                 }
             } else {
                 if let Some(ref mut event_handler) = payload.event_handler {
-                    event_handler.key_down_event(
-                        crate::event::KeyCode::Backspace,
-                        Default::default(),
-                        false,
-                    );
-                    event_handler
-                        .key_up_event(crate::event::KeyCode::Backspace, Default::default());
+                    key_press(&mut **event_handler, crate::event::KeyCode::Backspace);
                 }
             }
         }
@@ -577,6 +864,44 @@ pub fn load_file<F: Fn(crate::fs::Response) + 'static>(path: &str, on_loaded: F)
     }
 }
 
+/// Where `path` is in the main bundle, like `load_file`, for `fs::open_file`.
+pub fn resource_path(path: &str) -> Option<std::path::PathBuf> {
+    let path = std::path::Path::new(&path);
+    let path_without_extension = path.with_extension("");
+    let extension = path.extension().unwrap_or_default().to_str()?;
+
+    unsafe {
+        let main_bundle: ObjcId = msg_send![class!(NSBundle), mainBundle];
+        let resource = apple_util::str_to_nsstring(path_without_extension.to_str()?);
+        let type_ = apple_util::str_to_nsstring(extension);
+        let file_path: ObjcId = msg_send![main_bundle, pathForResource:resource ofType:type_];
+        if file_path.is_null() {
+            return None;
+        }
+        Some(apple_util::nsstring_to_string(file_path).into())
+    }
+}
+
+/// Caches or Application Support, in the container of the application.
+pub fn storage_dir(cache: bool) -> Option<std::path::PathBuf> {
+    unsafe {
+        // NSCachesDirectory, NSApplicationSupportDirectory
+        let directory: u64 = if cache { 13 } else { 14 };
+        let file_manager: ObjcId = msg_send![class!(NSFileManager), defaultManager];
+        // NSUserDomainMask
+        let urls: ObjcId = msg_send![file_manager, URLsForDirectory: directory inDomains: 1u64];
+        let url: ObjcId = msg_send![urls, firstObject];
+        if url.is_null() {
+            return None;
+        }
+        let path: ObjcId = msg_send![url, path];
+        let path = std::path::PathBuf::from(apple_util::nsstring_to_string(path));
+        // Application Support is not there until something makes it
+        let _ = std::fs::create_dir_all(&path);
+        Some(path)
+    }
+}
+
 // this is the way to pass argument to UiApplicationMain
 // this static will be used exactly once, to .take() the "run" arguments
 static mut RUN_ARGS: Option<(Box<dyn FnOnce() -> Box<dyn EventHandler>>, Conf)> = None;
@@ -1,21 +1,29 @@
 #![allow(dead_code)]
 
 mod libwayland_client;
+mod libwayland_cursor;
 mod libwayland_egl;
 mod libxkbcommon;
 
 mod decorations;
+mod drag_n_drop;
 mod extensions;
 mod keycodes;
+mod monitors;
 mod shm;
+mod text_input;
 
 use libwayland_client::*;
+use libwayland_cursor::*;
 use libwayland_egl::*;
 use libxkbcommon::*;
 
 use crate::{
-    event::{EventHandler, KeyCode, KeyMods, MouseButton},
-    native::{egl, NativeDisplayData, Request},
+    event::{EventHandler, KeyCode, KeyMods, MouseButton, ScanCode, ScrollDelta},
+    native::{
+        egl, event_wait::EventWait, frame_limiter::FrameLimiter, scancodes, NativeDisplayData,
+        Request,
+    },
 };
 
 use std::collections::HashSet;
@@ -38,21 +46,67 @@ struct WaylandPayload {
     surface: *mut wl_surface,
     decoration_manager: *mut extensions::xdg_decoration::zxdg_decoration_manager_v1,
     viewporter: *mut extensions::viewporter::wp_viewporter,
+    // null without the idle-inhibit protocol
+    idle_inhibit_manager: *mut extensions::idle_inhibit::zwp_idle_inhibit_manager_v1,
+    // window::set_keep_screen_on, null when it is off
+    idle_inhibitor: *mut extensions::idle_inhibit::zwp_idle_inhibitor_v1,
+    // null without xdg-activation, for window::request_user_attention
+    activation: *mut extensions::xdg_activation::xdg_activation_v1,
+    // null without the relative-pointer protocol, for raw_mouse_motion
+    relative_pointer_manager: *mut extensions::relative_pointer::zwp_relative_pointer_manager_v1,
+    relative_pointer: *mut extensions::relative_pointer::zwp_relative_pointer_v1,
+    // null without the pointer-constraints protocol, for window::set_cursor_grab
+    pointer_constraints: *mut extensions::pointer_constraints::zwp_pointer_constraints_v1,
+    // the locked or confined pointer of cursor_grab, null with CursorGrabMode::None
+    pointer_constraint: *mut wl_proxy,
+    cursor_grab: crate::CursorGrabMode,
     shm: *mut wl_shm,
     seat: *mut wl_seat,
     xkb_context: *mut xkb_context,
     keymap: *mut xkb_keymap,
     xkb_state: *mut xkb_state,
+    // null without a compose table for the locale
+    compose_state: *mut xkb_compose_state,
 
     egl_window: *mut wl_egl_window,
     pointer: *mut wl_pointer,
+    // of the last enter of the window, wl_pointer.set_cursor takes it
+    pointer_serial: u32,
+    // None without libwayland-cursor.so
+    cursor: Option<LibWaylandCursor>,
+    // loaded when the cursor is first shown again after it was locked,
+    // at the buffer scale of then, null if it could not be
+    cursor_theme: *mut wl_cursor_theme,
+    cursor_scale: i32,
+    cursor_surface: *mut wl_surface,
+    // the buffer scale cursor_surface has now
+    cursor_buffer_scale: i32,
+    // of window::set_mouse_cursor
+    cursor_icon: crate::CursorIcon,
+    // the buffer and hotspot of window::set_mouse_cursor_from_rgba,
+    // shown instead of cursor_icon
+    custom_cursor: Option<(*mut wl_buffer, (i32, i32))>,
     keyboard: *mut wl_keyboard,
     focused_window: *mut wl_surface,
+    // the wheel notches of the axis event that follows, vertical and horizontal
+    axis_discrete: [Option<i32>; 2],
     //xkb_state: xkb::XkbState,
     decorations: Option<decorations::Decorations>,
+    dnd: drag_n_drop::DragAndDrop,
+    monitors: monitors::Monitors,
+    text_input: text_input::TextInput,
 
     event_handler: Option<Box<dyn EventHandler>>,
-    closed: bool,
+    // an interactive resize is going on, from the configure states
+    resizing: bool,
+    resizable: bool,
+    // the surface size from configure, the buffer is this times buffer_scale
+    window_size: (i32, i32),
+    // the wl_surface buffer scale, always 1 without high_dpi
+    buffer_scale: i32,
+    // null without the decoration manager
+    server_decoration: *mut extensions::xdg_decoration::zxdg_toplevel_decoration_v1,
+    use_fallback_decorations: bool,
 }
 
 #[macro_export]
@@ -114,6 +168,8 @@ unsafe extern "C" fn seat_handle_capabilities(
         assert!(!id.is_null());
         // wl_pointer_add_listener (pointer, &pointer_listener, NULL);
         (display.client.wl_proxy_add_listener)(id, &POINTER_LISTENER as *const _ as _, data);
+        display.pointer = id as _;
+        create_relative_pointer(display);
     }
 
     if caps & wl_seat_capability_WL_SEAT_CAPABILITY_KEYBOARD != 0 {
@@ -131,10 +187,25 @@ unsafe extern "C" fn seat_handle_capabilities(
 }
 
 enum WaylandEvent {
-    KeyboardKey(KeyCode, bool),
+    // the milliseconds of the input events after it
+    Timestamp(u32),
+    KeyboardKey(KeyCode, ScanCode, bool),
+    Modifiers(KeyMods),
+    // keyboard enter and leave
+    Focus(bool),
+    Char(char),
     PointerMotion(f32, f32),
+    PointerEntered,
+    PointerLeft,
     PointerButton(MouseButton, bool),
-    PointerAxis(f32, f32),
+    PointerAxis(f32, f32, ScrollDelta),
+    // the unaccelerated relative-pointer motion
+    RawMotion(f32, f32),
+    FilesHovered,
+    FilesHoverCancelled,
+    FilesDropped,
+    ImeComposition(String, usize),
+    ImeCommit(String),
 }
 
 static mut EVENTS: Vec<WaylandEvent> = Vec::new();
@@ -184,28 +255,97 @@ unsafe extern "C" fn keyboard_handle_enter(
     _surface: *mut wl_surface,
     _keys: *mut wl_array,
 ) {
+    EVENTS.push(WaylandEvent::Focus(true));
 }
 unsafe extern "C" fn keyboard_handle_leave(
-    _data: *mut ::std::os::raw::c_void,
+    data: *mut ::std::os::raw::c_void,
     _wl_keyboard: *mut wl_keyboard,
     _serial: u32,
     _surface: *mut wl_surface,
 ) {
+    let display: &mut WaylandPayload = &mut *(data as *mut _);
+    if !display.compose_state.is_null() {
+        (display.xkb.xkb_compose_state_reset)(display.compose_state);
+    }
+    // no more key or modifiers events until the next enter
+    EVENTS.push(WaylandEvent::Focus(false));
 }
 unsafe extern "C" fn keyboard_handle_key(
     data: *mut ::std::os::raw::c_void,
     _wl_keyboard: *mut wl_keyboard,
     _serial: u32,
-    _time: u32,
+    time: u32,
     key: u32,
     state: u32,
 ) {
     let display: &mut WaylandPayload = &mut *(data as *mut _);
+    EVENTS.push(WaylandEvent::Timestamp(time));
     // https://wayland-book.com/seat/keyboard.html
     // To translate this to an XKB scancode, you must add 8 to the evdev scancode.
     let keysym = (display.xkb.xkb_state_key_get_one_sym)(display.xkb_state, key + 8);
     let keycode = keycodes::translate(keysym);
-    EVENTS.push(WaylandEvent::KeyboardKey(keycode, state == 1));
+    let scancode = scancodes::from_evdev(key);
+    EVENTS.push(WaylandEvent::KeyboardKey(keycode, scancode, state == 1));
+    if state == 1 && display.text_input.on {
+        for character in key_text(display, keysym).chars() {
+            if !character.is_control() {
+                EVENTS.push(WaylandEvent::Char(character));
+            }
+        }
+    }
+}
+
+/// The text of a pressed key, after dead keys and compose sequences.
+unsafe fn key_text(display: &mut WaylandPayload, keysym: u32) -> String {
+    let xkb = &display.xkb;
+    let compose_state = display.compose_state;
+    if !compose_state.is_null()
+        && (xkb.xkb_compose_state_feed)(compose_state, keysym) == XKB_COMPOSE_FEED_ACCEPTED
+    {
+        match (xkb.xkb_compose_state_get_status)(compose_state) {
+            XKB_COMPOSE_COMPOSING => return String::new(),
+            XKB_COMPOSE_COMPOSED => {
+                let mut buffer = [0u8; 64];
+                let len = (xkb.xkb_compose_state_get_utf8)(
+                    compose_state,
+                    buffer.as_mut_ptr() as _,
+                    buffer.len(),
+                );
+                (xkb.xkb_compose_state_reset)(compose_state);
+                // the length without the truncation, like snprintf
+                let len = (len.max(0) as usize).min(buffer.len() - 1);
+                return String::from_utf8_lossy(&buffer[..len]).into_owned();
+            }
+            XKB_COMPOSE_CANCELLED => {
+                (xkb.xkb_compose_state_reset)(compose_state);
+                return String::new();
+            }
+            _ => {}
+        }
+    }
+    // 0 without a character, a control character for is_control
+    std::char::from_u32((xkb.xkb_keysym_to_utf32)(keysym)).map_or(String::new(), String::from)
+}
+
+/// The compose table of the locale, the same LC_ALL, LC_CTYPE and LANG
+/// lookup as setlocale.
+unsafe fn new_compose_state(
+    xkb: &LibXkbCommon,
+    context: *mut xkb_context,
+) -> *mut xkb_compose_state {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|locale| !locale.is_empty())
+        .unwrap_or_else(|| "C".to_string());
+    let locale = std::ffi::CString::new(locale).unwrap_or_default();
+    let table = (xkb.xkb_compose_table_new_from_locale)(context, locale.as_ptr(), 0);
+    if table.is_null() {
+        return std::ptr::null_mut();
+    }
+    let compose_state = (xkb.xkb_compose_state_new)(table, 0);
+    (xkb.xkb_compose_table_unref)(table);
+    compose_state
 }
 unsafe extern "C" fn keyboard_handle_modifiers(
     data: *mut ::std::os::raw::c_void,
@@ -226,6 +366,21 @@ unsafe extern "C" fn keyboard_handle_modifiers(
         0,
         group,
     );
+    let is_active = |name: &str| {
+        (display.xkb.xkb_state_mod_name_is_active)(
+            display.xkb_state,
+            name.as_ptr() as _,
+            XKB_STATE_MODS_EFFECTIVE,
+        ) > 0
+    };
+    EVENTS.push(WaylandEvent::Modifiers(KeyMods {
+        shift: is_active(XKB_MOD_NAME_SHIFT),
+        ctrl: is_active(XKB_MOD_NAME_CTRL),
+        alt: is_active(XKB_MOD_NAME_ALT),
+        logo: is_active(XKB_MOD_NAME_LOGO),
+        caps_lock: is_active(XKB_MOD_NAME_CAPS),
+        num_lock: is_active(XKB_MOD_NAME_NUM),
+    }));
 }
 unsafe extern "C" fn keyboard_handle_repeat_info(
     _data: *mut ::std::os::raw::c_void,
@@ -249,70 +404,106 @@ static mut POINTER_LISTENER: wl_pointer_listener = wl_pointer_listener {
     axis_relative_direction: Some(pointer_handle_axis_relative_direction),
 };
 
+// the fallback decorations are surfaces of their own, not the window
 unsafe extern "C" fn pointer_handle_enter(
-    _data: *mut ::std::os::raw::c_void,
+    data: *mut ::std::os::raw::c_void,
     _wl_pointer: *mut wl_pointer,
-    _serial: u32,
-    _surface: *mut wl_surface,
+    serial: u32,
+    surface: *mut wl_surface,
     _surface_x: i32,
     _surface_y: i32,
 ) {
+    let display: &mut WaylandPayload = &mut *(data as *mut _);
+    if surface == display.surface {
+        display.pointer_serial = serial;
+        // the cursor image is undefined on enter
+        update_cursor(display);
+        EVENTS.push(WaylandEvent::PointerEntered);
+    }
 }
 unsafe extern "C" fn pointer_handle_leave(
-    _data: *mut ::std::os::raw::c_void,
+    data: *mut ::std::os::raw::c_void,
     _wl_pointer: *mut wl_pointer,
     _serial: u32,
-    _surface: *mut wl_surface,
+    surface: *mut wl_surface,
 ) {
+    let display: &mut WaylandPayload = &mut *(data as *mut _);
+    if surface == display.surface {
+        EVENTS.push(WaylandEvent::PointerLeft);
+    }
 }
 unsafe extern "C" fn pointer_handle_motion(
-    _data: *mut ::std::os::raw::c_void,
+    data: *mut ::std::os::raw::c_void,
     _wl_pointer: *mut wl_pointer,
-    _time: u32,
+    time: u32,
     surface_x: i32,
     surface_y: i32,
 ) {
+    EVENTS.push(WaylandEvent::Timestamp(time));
+    let display: &mut WaylandPayload = &mut *(data as *mut _);
     // From wl_fixed_to_double(), it simply divides by 256
     let (x, y) = (wl_fixed_to_double(surface_x), wl_fixed_to_double(surface_y));
-    EVENTS.push(WaylandEvent::PointerMotion(x, y));
+    // surface coordinates to framebuffer pixels
+    let scale = display.buffer_scale as f32;
+    EVENTS.push(WaylandEvent::PointerMotion(x * scale, y * scale));
 }
 unsafe extern "C" fn pointer_handle_button(
     _data: *mut ::std::os::raw::c_void,
     _wl_pointer: *mut wl_pointer,
     _serial: u32,
-    _time: u32,
+    time: u32,
     button: u32,
     state: u32,
 ) {
+    EVENTS.push(WaylandEvent::Timestamp(time));
     // The code is defined in the kernel's linux/input-event-codes.h header file, e.g. BTN_LEFT
     let button = match button {
         272 => MouseButton::Left,
         273 => MouseButton::Right,
         274 => MouseButton::Middle,
+        // BTN_SIDE and BTN_EXTRA, and the rare BTN_FORWARD and BTN_BACK
+        275 | 278 => MouseButton::Back,
+        276 | 277 => MouseButton::Forward,
+        // BTN_TASK and the numbered ones up to 0x11f
+        279..=287 => MouseButton::Other((button - 273) as u8),
         _ => MouseButton::Unknown,
     };
     EVENTS.push(WaylandEvent::PointerButton(button, state == 1));
 }
 unsafe extern "C" fn pointer_handle_axis(
-    _data: *mut ::std::os::raw::c_void,
+    data: *mut ::std::os::raw::c_void,
     _wl_pointer: *mut wl_pointer,
-    _time: u32,
+    time: u32,
     axis: u32,
     value: i32,
 ) {
-    let mut value = wl_fixed_to_double(value);
+    EVENTS.push(WaylandEvent::Timestamp(time));
+    let display: &mut WaylandPayload = &mut *(data as *mut _);
+    let pixels = wl_fixed_to_double(value) * display.buffer_scale as f32;
     // Normalize the value to {-1, 0, 1}
-    value /= value.abs();
+    let mut value = pixels / pixels.abs();
+    let discrete = display
+        .axis_discrete
+        .get_mut(axis as usize)
+        .and_then(Option::take);
 
     // https://wayland-book.com/seat/pointer.html
     if axis == 0 {
         // Vertical scroll
         // Wayland defines the direction differently to miniquad so lets flip it
         value = -value;
-        EVENTS.push(WaylandEvent::PointerAxis(0.0, value));
+        let delta = match discrete {
+            Some(discrete) => ScrollDelta::Lines(0.0, -discrete as f32),
+            None => ScrollDelta::Pixels(0.0, -pixels),
+        };
+        EVENTS.push(WaylandEvent::PointerAxis(0.0, value, delta));
     } else if axis == 1 {
         // Horizontal scroll
-        EVENTS.push(WaylandEvent::PointerAxis(value, 0.0));
+        let delta = match discrete {
+            Some(discrete) => ScrollDelta::Lines(-discrete as f32, 0.0),
+            None => ScrollDelta::Pixels(-pixels, 0.0),
+        };
+        EVENTS.push(WaylandEvent::PointerAxis(value, 0.0, delta));
     }
 }
 unsafe extern "C" fn pointer_handle_frame(
@@ -333,12 +524,17 @@ unsafe extern "C" fn pointer_handle_axis_stop(
     _axis: u32,
 ) {
 }
+// a wheel, from wl_seat version 5, always right before its axis event
 unsafe extern "C" fn pointer_handle_axis_discrete(
-    _data: *mut ::std::os::raw::c_void,
+    data: *mut ::std::os::raw::c_void,
     _wl_pointer: *mut wl_pointer,
-    _axis: u32,
-    _discrete: i32,
+    axis: u32,
+    discrete: i32,
 ) {
+    let display: &mut WaylandPayload = &mut *(data as *mut _);
+    if let Some(axis_discrete) = display.axis_discrete.get_mut(axis as usize) {
+        *axis_discrete = Some(discrete);
+    }
 }
 unsafe extern "C" fn pointer_handle_axis_value120(
     _data: *mut ::std::os::raw::c_void,
@@ -378,7 +574,7 @@ unsafe extern "C" fn registry_add_object(
                 registry,
                 name,
                 display.client.wl_compositor_interface,
-                1,
+                WL_SURFACE_SET_BUFFER_SCALE_SINCE_VERSION.min(version),
             ) as _;
         }
         "wl_subcompositor" => {
@@ -394,7 +590,7 @@ unsafe extern "C" fn registry_add_object(
                 registry,
                 name,
                 &extensions::xdg_shell::xdg_wm_base_interface,
-                1,
+                6.min(version),
             ) as _;
         }
         "zxdg_decoration_manager" |
@@ -422,7 +618,7 @@ unsafe extern "C" fn registry_add_object(
                     as _;
         }
         "wl_seat" => {
-            let seat_version = 4.min(version);
+            let seat_version = 5.min(version);
             display.seat = display.client.wl_registry_bind(
                 registry,
                 name,
@@ -435,16 +631,69 @@ unsafe extern "C" fn registry_add_object(
                 data,
             );
         }
+        "wl_data_device_manager" => {
+            display.dnd.version = 3.min(version);
+            display.dnd.manager = display.client.wl_registry_bind(
+                registry,
+                name,
+                display.client.wl_data_device_manager_interface,
+                display.dnd.version,
+            ) as _;
+        }
+        "wl_output" => monitors::bind_output(display, registry, name, version),
+        "zwp_idle_inhibit_manager_v1" => {
+            display.idle_inhibit_manager = display.client.wl_registry_bind(
+                registry,
+                name,
+                &extensions::idle_inhibit::zwp_idle_inhibit_manager_v1_interface,
+                1,
+            ) as _;
+        }
+        "xdg_activation_v1" => {
+            display.activation = display.client.wl_registry_bind(
+                registry,
+                name,
+                &extensions::xdg_activation::xdg_activation_v1_interface,
+                1,
+            ) as _;
+        }
+        "zwp_text_input_manager_v3" => {
+            display.text_input.manager = display.client.wl_registry_bind(
+                registry,
+                name,
+                &extensions::text_input::zwp_text_input_manager_v3_interface,
+                1,
+            ) as _;
+        }
+        "zwp_relative_pointer_manager_v1" => {
+            display.relative_pointer_manager = display.client.wl_registry_bind(
+                registry,
+                name,
+                &extensions::relative_pointer::zwp_relative_pointer_manager_v1_interface,
+                1,
+            ) as _;
+            create_relative_pointer(display);
+        }
+        "zwp_pointer_constraints_v1" => {
+            display.pointer_constraints = display.client.wl_registry_bind(
+                registry,
+                name,
+                &extensions::pointer_constraints::zwp_pointer_constraints_v1_interface,
+                1,
+            ) as _;
+        }
 
         _ => {}
     }
 }
 
 unsafe extern "C" fn registry_remove_object(
-    _data: *mut std::ffi::c_void,
+    data: *mut std::ffi::c_void,
     _registry: *mut wl_registry,
-    _name: u32,
+    name: u32,
 ) {
+    let display: &mut WaylandPayload = &mut *(data as *mut _);
+    monitors::remove_output(display, name);
 }
 
 unsafe extern "C" fn xdg_surface_handle_configure(
@@ -469,8 +718,7 @@ unsafe extern "C" fn xdg_toplevel_handle_close(
     _xdg_toplevel: *mut extensions::xdg_shell::xdg_toplevel,
 ) {
     assert!(!data.is_null());
-    let payload: &mut WaylandPayload = &mut *(data as *mut _);
-    payload.closed = true;
+    crate::native_display().lock().unwrap().quit_requested = true;
 }
 
 unsafe extern "C" fn xdg_toplevel_handle_configure(
@@ -478,37 +726,71 @@ unsafe extern "C" fn xdg_toplevel_handle_configure(
     _toplevel: *mut extensions::xdg_shell::xdg_toplevel,
     width: i32,
     height: i32,
-    _states: *mut wl_array,
+    states: *mut wl_array,
 ) -> () {
     assert!(!data.is_null());
     let payload: &mut WaylandPayload = &mut *(data as *mut _);
-    let mut d = crate::native_display().lock().unwrap();
 
-    if width != 0 && height != 0 {
-        let (egl_w, egl_h) = if payload.decorations.is_some() {
-            // Otherwise window will resize iteself on sway
-            // I have no idea why
-            (
-                width - decorations::Decorations::WIDTH * 2,
-                height - decorations::Decorations::BAR_HEIGHT - decorations::Decorations::WIDTH,
-            )
-        } else {
-            (width, height)
-        };
-        (payload.egl.wl_egl_window_resize)(payload.egl_window, egl_w, egl_h, 0, 0);
+    // there is no minimized state, the compositor does not tell
+    let states: &[u32] = match (*states).size {
+        0 => &[],
+        size => std::slice::from_raw_parts((*states).data as *const u32, size / 4),
+    };
+    let state = if states.contains(&extensions::xdg_shell::XDG_TOPLEVEL_STATE_MAXIMIZED) {
+        crate::WindowState::Maximized
+    } else {
+        crate::WindowState::Normal
+    };
+    let resizing = states.contains(&extensions::xdg_shell::XDG_TOPLEVEL_STATE_RESIZING);
+    // not visible at all, swap_buffers would wait for a frame callback until
+    // it is not suspended anymore
+    let suspended = states.contains(&extensions::xdg_shell::XDG_TOPLEVEL_STATE_SUSPENDED);
+    if let Some(ref mut event_handler) = payload.event_handler {
+        crate::native::update_window_state(state, event_handler.as_mut());
+        crate::native::update_occluded(suspended, event_handler.as_mut());
+        if resizing && !payload.resizing {
+            event_handler.resize_started_event();
+        }
+    }
 
-        d.screen_width = width;
-        d.screen_height = height;
+    if width != 0 && height != 0 {
+        payload.window_size = (width, height);
+        resize_egl_window(payload);
 
         if let Some(ref decorations) = payload.decorations {
-            drop(d);
             decorations.resize(&mut payload.client, width, height);
         }
 
         if let Some(ref mut event_handler) = payload.event_handler {
-            event_handler.resize_event(width as _, height as _);
+            let scale = payload.buffer_scale;
+            event_handler.resize_event((width * scale) as _, (height * scale) as _);
         }
     }
+
+    // after the resize_event with the final size
+    if !resizing && payload.resizing {
+        if let Some(ref mut event_handler) = payload.event_handler {
+            event_handler.resize_ended_event();
+        }
+    }
+    payload.resizing = resizing;
+}
+
+// the suggested size limits and the window menu, minimize and such, that
+// the compositor has, miniquad has no use for them
+unsafe extern "C" fn xdg_toplevel_handle_configure_bounds(
+    _data: *mut std::ffi::c_void,
+    _toplevel: *mut extensions::xdg_shell::xdg_toplevel,
+    _width: i32,
+    _height: i32,
+) {
+}
+
+unsafe extern "C" fn xdg_toplevel_handle_wm_capabilities(
+    _data: *mut std::ffi::c_void,
+    _toplevel: *mut extensions::xdg_shell::xdg_toplevel,
+    _capabilities: *mut wl_array,
+) {
 }
 
 unsafe extern "C" fn xdg_wm_base_handle_ping(
@@ -527,6 +809,58 @@ unsafe extern "C" fn xdg_wm_base_handle_ping(
     );
 }
 
+/// The egl window and `window::screen_size` from the window size and the buffer
+/// scale, the fallback decorations are around the egl window.
+unsafe fn resize_egl_window(payload: &mut WaylandPayload) {
+    let (width, height) = payload.window_size;
+    let (egl_w, egl_h) = if payload.decorations.is_some() {
+        // Otherwise window will resize iteself on sway
+        // I have no idea why
+        (
+            width - decorations::Decorations::WIDTH * 2,
+            height - decorations::Decorations::BAR_HEIGHT - decorations::Decorations::WIDTH,
+        )
+    } else {
+        (width, height)
+    };
+    let scale = payload.buffer_scale;
+    (payload.egl.wl_egl_window_resize)(payload.egl_window, egl_w * scale, egl_h * scale, 0, 0);
+
+    let mut d = crate::native_display().lock().unwrap();
+    d.screen_width = width * scale;
+    d.screen_height = height * scale;
+}
+
+/// With high_dpi, the buffer scale is the largest scale of the monitors the
+/// window is on, to look sharp on the sharpest of them.
+unsafe fn update_buffer_scale(payload: &mut WaylandPayload) {
+    if !crate::native_display().lock().unwrap().high_dpi {
+        return;
+    }
+    let scale = payload.monitors.scale();
+    if scale == payload.buffer_scale {
+        return;
+    }
+    payload.buffer_scale = scale;
+    // along with the resized buffer, on the next commit
+    wl_request!(
+        payload.client,
+        payload.surface,
+        WL_SURFACE_SET_BUFFER_SCALE,
+        scale
+    );
+    resize_egl_window(payload);
+
+    match payload.event_handler {
+        Some(ref mut event_handler) => {
+            crate::native::update_dpi_scale(scale as f32, event_handler.as_mut());
+            let (width, height) = payload.window_size;
+            event_handler.resize_event((width * scale) as _, (height * scale) as _);
+        }
+        None => crate::native_display().lock().unwrap().dpi_scale = scale as f32,
+    }
+}
+
 struct WaylandClipboard;
 impl crate::native::Clipboard for WaylandClipboard {
     fn get(&mut self) -> Option<String> {
@@ -535,6 +869,441 @@ impl crate::native::Clipboard for WaylandClipboard {
     fn set(&mut self, _data: &str) {}
 }
 
+/// xdg_toplevel min and max size, 0 is no limit. A not resizable window
+/// is pinned to its current size. Applied with the commit of the next frame.
+unsafe fn set_size_constraints(
+    display: &mut WaylandPayload,
+    mut constraints: crate::native::SizeConstraints,
+) {
+    if !display.resizable {
+        let size = (display.window_size.0 as u32, display.window_size.1 as u32);
+        constraints.min = Some(size);
+        constraints.max = Some(size);
+    }
+    let (min_width, min_height) = constraints.min.unwrap_or((0, 0));
+    let (max_width, max_height) = constraints.max.unwrap_or((0, 0));
+    wl_request!(
+        display.client,
+        display.xdg_toplevel,
+        extensions::xdg_shell::xdg_toplevel::set_min_size,
+        min_width as i32,
+        min_height as i32
+    );
+    wl_request!(
+        display.client,
+        display.xdg_toplevel,
+        extensions::xdg_shell::xdg_toplevel::set_max_size,
+        max_width as i32,
+        max_height as i32
+    );
+}
+
+/// The compositor does not blank the screen while the surface is visible.
+unsafe fn set_idle_inhibitor(display: &mut WaylandPayload, keep_on: bool) {
+    if keep_on == !display.idle_inhibitor.is_null() {
+        return;
+    }
+    if keep_on {
+        display.idle_inhibitor = wl_request_constructor!(
+            display.client,
+            display.idle_inhibit_manager,
+            extensions::idle_inhibit::zwp_idle_inhibit_manager_v1::create_inhibitor,
+            &extensions::idle_inhibit::zwp_idle_inhibitor_v1_interface,
+            display.surface
+        ) as _;
+    } else {
+        wl_request!(
+            display.client,
+            display.idle_inhibitor,
+            extensions::idle_inhibit::zwp_idle_inhibitor_v1::destroy
+        );
+        (display.client.wl_proxy_destroy)(display.idle_inhibitor as _);
+        display.idle_inhibitor = std::ptr::null_mut();
+    }
+}
+
+static mut RELATIVE_POINTER_LISTENER:
+    extensions::relative_pointer::zwp_relative_pointer_v1_listener =
+    extensions::relative_pointer::zwp_relative_pointer_v1_listener {
+        relative_motion: Some(relative_pointer_handle_relative_motion),
+    };
+
+unsafe extern "C" fn relative_pointer_handle_relative_motion(
+    _data: *mut std::ffi::c_void,
+    _relative_pointer: *mut extensions::relative_pointer::zwp_relative_pointer_v1,
+    utime_hi: u32,
+    utime_lo: u32,
+    _dx: i32,
+    _dy: i32,
+    dx_unaccel: i32,
+    dy_unaccel: i32,
+) {
+    let utime = ((utime_hi as u64) << 32) | utime_lo as u64;
+    EVENTS.push(WaylandEvent::Timestamp((utime / 1000) as u32));
+    // not scaled, like the XInput2 raw events they are in device units
+    let dx = wl_fixed_to_double(dx_unaccel);
+    let dy = wl_fixed_to_double(dy_unaccel);
+    EVENTS.push(WaylandEvent::RawMotion(dx, dy));
+}
+
+/// From whichever comes last, the manager or the pointer.
+unsafe fn create_relative_pointer(display: &mut WaylandPayload) {
+    if display.relative_pointer_manager.is_null()
+        || display.pointer.is_null()
+        || !display.relative_pointer.is_null()
+    {
+        return;
+    }
+    display.relative_pointer = wl_request_constructor!(
+        display.client,
+        display.relative_pointer_manager,
+        extensions::relative_pointer::zwp_relative_pointer_manager_v1::get_relative_pointer,
+        &extensions::relative_pointer::zwp_relative_pointer_v1_interface,
+        display.pointer
+    ) as _;
+    (display.client.wl_proxy_add_listener)(
+        display.relative_pointer as _,
+        &RELATIVE_POINTER_LISTENER as *const _ as _,
+        display as *mut _ as _,
+    );
+}
+
+/// The constraints are persistent, the compositor lifts them while the
+/// window is not focused and puts them back when the pointer enters again.
+unsafe fn set_cursor_grab(display: &mut WaylandPayload, mode: crate::CursorGrabMode) {
+    if !display.pointer_constraint.is_null() {
+        // destroy is the first request of the confined pointer too
+        wl_request!(
+            display.client,
+            display.pointer_constraint,
+            extensions::pointer_constraints::zwp_locked_pointer_v1::destroy
+        );
+        (display.client.wl_proxy_destroy)(display.pointer_constraint);
+        display.pointer_constraint = std::ptr::null_mut();
+    }
+
+    let was_locked = display.cursor_grab == crate::CursorGrabMode::Locked;
+    display.cursor_grab = mode;
+    if was_locked != (mode == crate::CursorGrabMode::Locked) {
+        update_cursor(display);
+    }
+
+    if display.pointer_constraints.is_null() || display.pointer.is_null() {
+        return;
+    }
+    let (request, interface) = match mode {
+        crate::CursorGrabMode::None => return,
+        crate::CursorGrabMode::Confined => (
+            extensions::pointer_constraints::zwp_pointer_constraints_v1::confine_pointer,
+            &extensions::pointer_constraints::zwp_confined_pointer_v1_interface as *const _,
+        ),
+        crate::CursorGrabMode::Locked => (
+            extensions::pointer_constraints::zwp_pointer_constraints_v1::lock_pointer,
+            &extensions::pointer_constraints::zwp_locked_pointer_v1_interface as *const _,
+        ),
+    };
+    display.pointer_constraint = wl_request_constructor!(
+        display.client,
+        display.pointer_constraints,
+        request,
+        interface,
+        display.surface,
+        display.pointer,
+        std::ptr::null_mut::<wl_region>(),
+        extensions::pointer_constraints::ZWP_POINTER_CONSTRAINTS_V1_LIFETIME_PERSISTENT
+    );
+}
+
+/// No cursor while it is locked, the custom cursor or `cursor_icon` from
+/// the cursor theme otherwise.
+unsafe fn update_cursor(display: &mut WaylandPayload) {
+    if display.pointer.is_null() {
+        return;
+    }
+    if display.cursor_grab == crate::CursorGrabMode::Locked {
+        wl_request!(
+            display.client,
+            display.pointer,
+            WL_POINTER_SET_CURSOR,
+            display.pointer_serial,
+            std::ptr::null_mut::<wl_surface>(),
+            0,
+            0
+        );
+        return;
+    }
+
+    if display.cursor_surface.is_null() {
+        if let Some(ref cursor) = display.cursor {
+            // the same theme and size as the rest of the desktop
+            let name = std::env::var("XCURSOR_THEME")
+                .ok()
+                .and_then(|name| std::ffi::CString::new(name).ok());
+            let size = std::env::var("XCURSOR_SIZE")
+                .ok()
+                .and_then(|size| size.parse::<i32>().ok())
+                .unwrap_or(24);
+            display.cursor_scale = display.buffer_scale;
+            display.cursor_theme = (cursor.wl_cursor_theme_load)(
+                name.as_ref().map_or(std::ptr::null(), |name| name.as_ptr()),
+                size * display.cursor_scale,
+                display.shm,
+            );
+        }
+        display.cursor_surface = wl_request_constructor!(
+            display.client,
+            display.compositor,
+            WL_COMPOSITOR_CREATE_SURFACE,
+            display.client.wl_surface_interface
+        );
+    }
+    let (buffer, (hotspot_x, hotspot_y), scale) = match display.custom_cursor {
+        Some((buffer, hotspot)) => (buffer, hotspot, 1),
+        None => match theme_cursor(display) {
+            Some(cursor) => cursor,
+            None => return,
+        },
+    };
+    // set_buffer_scale is from wl_compositor 3, the buffer scale is 1 before
+    if scale != display.cursor_buffer_scale {
+        wl_request!(
+            display.client,
+            display.cursor_surface,
+            WL_SURFACE_SET_BUFFER_SCALE,
+            scale
+        );
+        display.cursor_buffer_scale = scale;
+    }
+    wl_request!(
+        display.client,
+        display.cursor_surface,
+        WL_SURFACE_ATTACH,
+        buffer,
+        0,
+        0
+    );
+    wl_request!(
+        display.client,
+        display.cursor_surface,
+        WL_SURFACE_DAMAGE,
+        0,
+        0,
+        i32::MAX,
+        i32::MAX
+    );
+    wl_request!(display.client, display.cursor_surface, WL_SURFACE_COMMIT);
+    wl_request!(
+        display.client,
+        display.pointer,
+        WL_POINTER_SET_CURSOR,
+        display.pointer_serial,
+        display.cursor_surface,
+        hotspot_x,
+        hotspot_y
+    );
+}
+
+/// The buffer, hotspot in surface coordinates and buffer scale of
+/// `cursor_icon`, None without a cursor theme or a cursor of that name in it.
+unsafe fn theme_cursor(display: &WaylandPayload) -> Option<(*mut wl_buffer, (i32, i32), i32)> {
+    let cursor = display.cursor.as_ref()?;
+    if display.cursor_theme.is_null() {
+        return None;
+    }
+    // the CSS names of the newer themes first, the X11 ones otherwise
+    let names: &[&[u8]] = match display.cursor_icon {
+        crate::CursorIcon::Default => &[b"default\0", b"left_ptr\0"],
+        crate::CursorIcon::Help => &[b"help\0", b"question_arrow\0"],
+        crate::CursorIcon::Pointer => &[b"pointer\0", b"hand2\0"],
+        crate::CursorIcon::Wait => &[b"wait\0", b"watch\0"],
+        crate::CursorIcon::Crosshair => &[b"crosshair\0"],
+        crate::CursorIcon::Text => &[b"text\0", b"xterm\0"],
+        crate::CursorIcon::Move => &[b"move\0", b"fleur\0"],
+        crate::CursorIcon::NotAllowed => &[b"not-allowed\0", b"crossed_circle\0"],
+        crate::CursorIcon::EWResize => &[b"ew-resize\0", b"sb_h_double_arrow\0"],
+        crate::CursorIcon::NSResize => &[b"ns-resize\0", b"sb_v_double_arrow\0"],
+        crate::CursorIcon::NESWResize => &[b"nesw-resize\0", b"fd_double_arrow\0"],
+        crate::CursorIcon::NWSEResize => &[b"nwse-resize\0", b"bd_double_arrow\0"],
+    };
+    let wl_cursor = names
+        .iter()
+        .map(|name| (cursor.wl_cursor_theme_get_cursor)(display.cursor_theme, name.as_ptr() as _))
+        .find(|wl_cursor| !wl_cursor.is_null() && (**wl_cursor).image_count != 0)?;
+    let image = *(*wl_cursor).images;
+    let scale = display.cursor_scale;
+    Some((
+        (cursor.wl_cursor_image_get_buffer)(image),
+        (
+            (*image).hotspot_x as i32 / scale,
+            (*image).hotspot_y as i32 / scale,
+        ),
+        scale,
+    ))
+}
+
+unsafe fn set_mouse_cursor(display: &mut WaylandPayload, icon: crate::CursorIcon) {
+    display.cursor_icon = icon;
+    let custom_cursor = display.custom_cursor.take();
+    update_cursor(display);
+    if let Some((buffer, _)) = custom_cursor {
+        wl_request!(display.client, buffer, WL_BUFFER_DESTROY);
+        (display.client.wl_proxy_destroy)(buffer as _);
+    }
+}
+
+unsafe fn set_custom_cursor(
+    display: &mut WaylandPayload,
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+    hotspot: (u32, u32),
+) {
+    if display.shm.is_null() {
+        return;
+    }
+    // wl_shm ARGB8888 is premultiplied BGRA in memory
+    let mut bgra = Vec::with_capacity(rgba.len());
+    for pixel in rgba.chunks_exact(4) {
+        let alpha = pixel[3] as u32;
+        let premultiply = |c: u8| (c as u32 * alpha / 255) as u8;
+        bgra.extend_from_slice(&[
+            premultiply(pixel[2]),
+            premultiply(pixel[1]),
+            premultiply(pixel[0]),
+            pixel[3],
+        ]);
+    }
+    let buffer = shm::create_shm_buffer(
+        &mut display.client,
+        display.shm,
+        width as i32,
+        height as i32,
+        &bgra,
+    );
+    let old_cursor = display
+        .custom_cursor
+        .replace((buffer, (hotspot.0 as i32, hotspot.1 as i32)));
+    update_cursor(display);
+    if let Some((buffer, _)) = old_cursor {
+        wl_request!(display.client, buffer, WL_BUFFER_DESTROY);
+        (display.client.wl_proxy_destroy)(buffer as _);
+    }
+}
+
+static mut ACTIVATION_TOKEN_LISTENER: extensions::xdg_activation::xdg_activation_token_v1_listener =
+    extensions::xdg_activation::xdg_activation_token_v1_listener {
+        done: Some(activation_token_handle_done),
+    };
+
+/// An activation token without the serial of an input event, the
+/// compositor does not focus the window for it but marks it as urgent.
+unsafe fn request_user_attention(display: &mut WaylandPayload) {
+    if display.activation.is_null() {
+        return;
+    }
+    let token: *mut extensions::xdg_activation::xdg_activation_token_v1 = wl_request_constructor!(
+        display.client,
+        display.activation,
+        extensions::xdg_activation::xdg_activation_v1::get_activation_token,
+        &extensions::xdg_activation::xdg_activation_token_v1_interface
+    );
+    (display.client.wl_proxy_add_listener)(
+        token as _,
+        &ACTIVATION_TOKEN_LISTENER as *const _ as _,
+        display as *mut _ as _,
+    );
+    wl_request!(
+        display.client,
+        token,
+        extensions::xdg_activation::xdg_activation_token_v1::set_surface,
+        display.surface
+    );
+    wl_request!(
+        display.client,
+        token,
+        extensions::xdg_activation::xdg_activation_token_v1::commit
+    );
+}
+
+unsafe extern "C" fn activation_token_handle_done(
+    data: *mut std::ffi::c_void,
+    token: *mut extensions::xdg_activation::xdg_activation_token_v1,
+    token_string: *const std::os::raw::c_char,
+) {
+    let display: &mut WaylandPayload = &mut *(data as *mut _);
+    wl_request!(
+        display.client,
+        display.activation,
+        extensions::xdg_activation::xdg_activation_v1::activate,
+        token_string,
+        display.surface
+    );
+    wl_request!(
+        display.client,
+        token,
+        extensions::xdg_activation::xdg_activation_token_v1::destroy
+    );
+    (display.client.wl_proxy_destroy)(token as _);
+}
+
+/// Server side decorations when the compositor has them, the fallback
+/// ones otherwise. Client side mode is asking for no decorations at all,
+/// the client would draw them and miniquad does not.
+unsafe fn set_decorations(display: &mut WaylandPayload, decorations: bool) {
+    if !display.server_decoration.is_null() {
+        let mode = if decorations {
+            extensions::xdg_decoration::ZXDG_TOPLEVEL_DECORATION_V1_MODE_SERVER_SIDE
+        } else {
+            extensions::xdg_decoration::ZXDG_TOPLEVEL_DECORATION_V1_MODE_CLIENT_SIDE
+        };
+        wl_request!(
+            display.client,
+            display.server_decoration,
+            extensions::xdg_decoration::zxdg_toplevel_decoration_v1::set_mode,
+            mode
+        );
+        return;
+    }
+    if !display.use_fallback_decorations || decorations == display.decorations.is_some() {
+        return;
+    }
+
+    let (width, height) = display.window_size;
+    match display.decorations.take() {
+        Some(old) => old.destroy(&mut display.client),
+        None => {
+            let new = decorations::Decorations::new(display, width, height);
+            new.resize(&mut display.client, width, height);
+            display.decorations = Some(new);
+        }
+    }
+    resize_egl_window(display);
+}
+
+/// With no events, waits for the next one unless a frame is due,
+/// false is a frame to draw now.
+unsafe fn wait_for_events(
+    client: &LibWaylandClient,
+    display: *mut wl_display,
+    event_wait: &EventWait,
+) -> bool {
+    let timeout = crate::native_display().lock().unwrap().frame_timeout();
+    if timeout == Some(std::time::Duration::ZERO) {
+        return false;
+    }
+    // some events are read already, they are for the next dispatch_pending
+    if (client.wl_display_prepare_read)(display) != 0 {
+        return true;
+    }
+    (client.wl_display_flush)(display);
+    if event_wait.wait((client.wl_display_get_fd)(display), timeout) {
+        (client.wl_display_read_events)(display);
+    } else {
+        (client.wl_display_cancel_read)(display);
+    }
+    true
+}
+
 pub fn run<F>(conf: &crate::conf::Conf, f: &mut Option<F>) -> Option<()>
 where
     F: 'static + FnOnce() -> Box<dyn EventHandler>,
@@ -564,6 +1333,7 @@ where
         };
 
         let xkb_context = (xkb.xkb_context_new)(0);
+        let compose_state = new_compose_state(&xkb, xkb_context);
 
         let mut display = WaylandPayload {
             client: client.clone(),
@@ -576,23 +1346,57 @@ where
             surface: std::ptr::null_mut(),
             decoration_manager: std::ptr::null_mut(),
             viewporter: std::ptr::null_mut(),
+            idle_inhibit_manager: std::ptr::null_mut(),
+            idle_inhibitor: std::ptr::null_mut(),
+            activation: std::ptr::null_mut(),
+            relative_pointer_manager: std::ptr::null_mut(),
+            relative_pointer: std::ptr::null_mut(),
+            pointer_constraints: std::ptr::null_mut(),
+            pointer_constraint: std::ptr::null_mut(),
+            cursor_grab: crate::CursorGrabMode::None,
             shm: std::ptr::null_mut(),
             seat: std::ptr::null_mut(),
             xkb_context,
             keymap: std::ptr::null_mut(),
             xkb_state: std::ptr::null_mut(),
+            compose_state,
             egl_window: std::ptr::null_mut(),
             pointer: std::ptr::null_mut(),
+            pointer_serial: 0,
+            cursor: LibWaylandCursor::try_load(),
+            cursor_theme: std::ptr::null_mut(),
+            cursor_scale: 1,
+            cursor_surface: std::ptr::null_mut(),
+            cursor_buffer_scale: 1,
+            cursor_icon: crate::CursorIcon::Default,
+            custom_cursor: None,
             keyboard: std::ptr::null_mut(),
             focused_window: std::ptr::null_mut(),
+            axis_discrete: [None; 2],
             decorations: None,
+            dnd: drag_n_drop::DragAndDrop::new(),
+            monitors: monitors::Monitors::new(),
+            text_input: text_input::TextInput::new(),
             event_handler: None,
-            closed: false,
+            resizing: false,
+            resizable: conf.window_resizable,
+            window_size: (conf.window_width, conf.window_height),
+            buffer_scale: 1,
+            server_decoration: std::ptr::null_mut(),
+            use_fallback_decorations: conf.platform.wayland_use_fallback_decorations,
         };
 
         let (tx, rx) = std::sync::mpsc::channel();
         let clipboard = Box::new(WaylandClipboard);
+        let mut session_bus = crate::native::dbus::SessionBus::new();
         crate::set_display(NativeDisplayData {
+            high_dpi: conf.high_dpi,
+            target_fps: conf.max_fps,
+            background_mode: conf.background_mode,
+            system_theme: session_bus
+                .as_ref()
+                .map_or(crate::Theme::Light, |session_bus| session_bus.theme()),
+            power_info: crate::native::power_supply::power_info(),
             ..NativeDisplayData::new(conf.window_width, conf.window_height, tx, clipboard)
         });
 
@@ -607,6 +1411,8 @@ where
         assert!(display.xdg_wm_base.is_null() == false);
         assert!(display.subcompositor.is_null() == false);
         assert!(display.seat.is_null() == false);
+        drag_n_drop::init(&mut display, wdisplay);
+        text_input::init(&mut display);
         //assert!(display.keymap.is_null() == false);
         //assert!(display.xkb_state.is_null() == false);
 
@@ -628,8 +1434,10 @@ where
         let (context, config, egl_display) = egl::create_egl_context(
             &mut libegl,
             wdisplay as *mut _,
-            conf.platform.framebuffer_alpha,
+            conf.platform.framebuffer_alpha || conf.transparent,
             conf.sample_count,
+            conf.platform.gles_version,
+            conf.platform.gl_debug,
         )
         .unwrap();
 
@@ -640,6 +1448,13 @@ where
             display.client.wl_surface_interface
         );
         assert!(display.surface.is_null() == false);
+        monitors::init_surface(&mut display);
+        #[cfg(feature = "raw-window-handle")]
+        {
+            crate::native_display().lock().unwrap().window_handle = Some(
+                crate::native::WindowHandle::wayland(wdisplay as _, display.surface as _),
+            );
+        }
 
         let xdg_surface: *mut extensions::xdg_shell::xdg_surface = wl_request_constructor!(
             display.client,
@@ -671,6 +1486,8 @@ where
         let xdg_toplevel_listener = extensions::xdg_shell::xdg_toplevel_listener {
             configure: Some(xdg_toplevel_handle_configure),
             close: Some(xdg_toplevel_handle_close),
+            configure_bounds: Some(xdg_toplevel_handle_configure_bounds),
+            wm_capabilities: Some(xdg_toplevel_handle_wm_capabilities),
         };
 
         (display.client.wl_proxy_add_listener)(
@@ -687,6 +1504,15 @@ where
             extensions::xdg_shell::xdg_toplevel::set_title,
             title.as_ptr()
         );
+        if let Some(app_id) = &conf.platform.app_id {
+            let app_id = std::ffi::CString::new(app_id.as_str()).unwrap();
+            wl_request!(
+                display.client,
+                display.xdg_toplevel,
+                extensions::xdg_shell::xdg_toplevel::set_app_id,
+                app_id.as_ptr()
+            );
+        }
 
         wl_request!(display.client, display.surface, WL_SURFACE_COMMIT);
         (display.client.wl_display_roundtrip)(wdisplay);
@@ -697,11 +1523,13 @@ where
             conf.window_height as _,
         );
 
+        let surface_attributes =
+            egl::window_surface_attributes(&libegl, egl_display, conf.platform.srgb_framebuffer);
         let egl_surface = (libegl.eglCreateWindowSurface.unwrap())(
             egl_display,
             config,
             display.egl_window as _,
-            std::ptr::null_mut(),
+            surface_attributes.as_ptr() as _,
         );
 
         if egl_surface == /* EGL_NO_SURFACE  */ std::ptr::null_mut() {
@@ -710,6 +1538,9 @@ where
         if (libegl.eglMakeCurrent.unwrap())(egl_display, egl_surface, egl_surface, context) == 0 {
             panic!("eglMakeCurrent failed");
         }
+        let swap_interval = conf.platform.swap_interval.unwrap_or(1);
+        egl::swap_interval(&mut libegl, egl_display, swap_interval);
+        crate::native_display().lock().unwrap().swap_control = libegl.eglSwapInterval.is_some();
 
         // For some reason, setting fullscreen before egl_window is created leads
         // to segfault because wl_egl_window_create returns NULL.
@@ -726,20 +1557,21 @@ where
             let name = std::ffi::CString::new(proc).unwrap();
             libegl.eglGetProcAddress.expect("non-null function pointer")(name.as_ptr() as _)
         });
+        crate::native_display().lock().unwrap().egl = true;
 
         if display.decoration_manager.is_null() == false {
-            let server_decoration: *mut extensions::xdg_decoration::zxdg_toplevel_decoration_v1 = wl_request_constructor!(
+            display.server_decoration = wl_request_constructor!(
                 display.client,
                 display.decoration_manager,
                 extensions::xdg_decoration::zxdg_decoration_manager_v1::get_toplevel_decoration,
                 &extensions::xdg_decoration::zxdg_toplevel_decoration_v1_interface,
                 display.xdg_toplevel
             );
-            assert!(server_decoration.is_null() == false);
+            assert!(display.server_decoration.is_null() == false);
 
             wl_request!(
                 display.client,
-                server_decoration,
+                display.server_decoration,
                 extensions::xdg_decoration::zxdg_toplevel_decoration_v1::set_mode,
                 extensions::xdg_decoration::ZXDG_TOPLEVEL_DECORATION_V1_MODE_SERVER_SIDE
             );
@@ -751,51 +1583,142 @@ where
             ));
         }
 
+        if !display.resizable {
+            set_size_constraints(&mut display, Default::default());
+        }
+
         let event_handler = (f.take().unwrap())();
         display.event_handler = Some(event_handler);
 
-        let mut keymods = KeyMods {
-            shift: false,
-            ctrl: false,
-            alt: false,
-            logo: false,
-        };
-        let mut repeated_keys: HashSet<KeyCode> = HashSet::new();
+        let mut keymods = KeyMods::default();
+        let mut repeated_keys: HashSet<(KeyCode, ScanCode)> = HashSet::new();
         let (mut last_mouse_x, mut last_mouse_y) = (0.0, 0.0);
+        let mut frame_limiter = FrameLimiter::new();
+        let mut gamepads = crate::native::evdev::Gamepads::new();
+        let mut power_supply = crate::native::power_supply::PowerSupply::new();
+        let event_wait = conf.wait_for_events.then(|| EventWait::new());
+
+        while !crate::native_display().lock().unwrap().quit_ordered {
+            let dispatched = (client.wl_display_dispatch_pending)(wdisplay);
+            crate::native::take_sigterm();
+            if let Some(ref event_wait) = event_wait {
+                // held keys repeat every frame
+                if dispatched == 0
+                    && repeated_keys.is_empty()
+                    && wait_for_events(&client, wdisplay, event_wait)
+                {
+                    continue;
+                }
+            }
 
-        while display.closed == false {
-            (client.wl_display_dispatch_pending)(wdisplay);
+            while let Ok(request) = rx.try_recv() {
+                match request {
+                    Request::SetFullscreen(full) => if full {
+                        wl_request!(
+                            display.client,
+                            display.xdg_toplevel,
+                            extensions::xdg_shell::xdg_toplevel::set_fullscreen,
+                            std::ptr::null_mut::<*mut wl_output>()
+                        );
+                    } else {
+                        wl_request!(
+                            display.client,
+                            display.xdg_toplevel,
+                            extensions::xdg_shell::xdg_toplevel::unset_fullscreen
+                        );
+                    },
+                    // the mode is up to the compositor, only the monitor is taken
+                    Request::SetExclusiveFullscreen { monitor, .. } => {
+                        wl_request!(
+                            display.client,
+                            display.xdg_toplevel,
+                            extensions::xdg_shell::xdg_toplevel::set_fullscreen,
+                            display.monitors.wl_output(monitor)
+                        );
+                    }
+                    Request::SetSwapInterval(interval) => {
+                        if !egl::swap_interval(&mut libegl, egl_display, interval) {
+                            eprintln!("Failed to set swap interval {}", interval);
+                        }
+                    }
+                    Request::SetWindowState(state) => match state {
+                        crate::WindowState::Maximized => wl_request!(
+                            display.client,
+                            display.xdg_toplevel,
+                            extensions::xdg_shell::xdg_toplevel::set_maximized
+                        ),
+                        crate::WindowState::Minimized => wl_request!(
+                            display.client,
+                            display.xdg_toplevel,
+                            extensions::xdg_shell::xdg_toplevel::set_minimized
+                        ),
+                        // only the user can bring a minimized window back
+                        crate::WindowState::Normal => wl_request!(
+                            display.client,
+                            display.xdg_toplevel,
+                            extensions::xdg_shell::xdg_toplevel::unset_maximized
+                        ),
+                    },
+                    // xdg_toplevel has nothing for the aspect ratio
+                    Request::SetSizeConstraints(constraints) => {
+                        set_size_constraints(&mut display, constraints)
+                    }
+                    Request::SetResizable(resizable) => {
+                        display.resizable = resizable;
+                        let constraints = crate::native_display().lock().unwrap().size_constraints;
+                        set_size_constraints(&mut display, constraints);
+                    }
+                    Request::SetDecorations(decorations) => {
+                        set_decorations(&mut display, decorations)
+                    }
+                    Request::SetImeCursorArea {
+                        x,
+                        y,
+                        width,
+                        height,
+                    } => text_input::set_cursor_area(&mut display, x, y, width, height),
+                    Request::SetTextInputEnabled(enabled) => {
+                        text_input::set_enabled(&mut display, enabled)
+                    }
+                    Request::SetGamepadRumble {
+                        id,
+                        low_frequency,
+                        high_frequency,
+                        duration,
+                    } => gamepads.rumble(id, low_frequency, high_frequency, duration),
+                    Request::RequestUserAttention(_) => request_user_attention(&mut display),
+                    Request::SetCursorGrab(mode) => set_cursor_grab(&mut display, mode),
+                    Request::SetMouseCursor(icon) => set_mouse_cursor(&mut display, icon),
+                    Request::SetCustomMouseCursor {
+                        rgba,
+                        width,
+                        height,
+                        hotspot,
+                    } => set_custom_cursor(&mut display, &rgba, width, height, hotspot),
+                    Request::SetKeepScreenOn(keep_on) => {
+                        if !display.idle_inhibit_manager.is_null() {
+                            set_idle_inhibitor(&mut display, keep_on);
+                        } else if let Some(ref mut session_bus) = session_bus {
+                            session_bus.inhibit_screensaver(keep_on);
+                        }
+                    }
 
-            if let Some(ref mut event_handler) = display.event_handler {
-                for keycode in &repeated_keys {
-                    event_handler.key_down_event(keycode.clone(), keymods, true);
+                    // TODO: implement the other events
+                    _ => (),
                 }
+            }
 
-                while let Ok(request) = rx.try_recv() {
-                    match request {
-                        Request::SetFullscreen(full) => if full {
-                            wl_request!(
-                                display.client,
-                                display.xdg_toplevel,
-                                extensions::xdg_shell::xdg_toplevel::set_fullscreen,
-                                std::ptr::null_mut::<*mut wl_output>()
-                            );
-                        } else {
-                            wl_request!(
-                                display.client,
-                                display.xdg_toplevel,
-                                extensions::xdg_shell::xdg_toplevel::unset_fullscreen
-                            );
-                        },
-
-                        // TODO: implement the other events
-                        _ => (),
-                    }
+            if let Some(ref mut event_handler) = display.event_handler {
+                for (keycode, scancode) in &repeated_keys {
+                    event_handler.key_down_scancode_event(*keycode, *scancode, keymods, true);
                 }
 
                 for event in EVENTS.drain(..) {
                     match event {
-                        WaylandEvent::KeyboardKey(keycode, state) => {
+                        WaylandEvent::Timestamp(time) => {
+                            crate::native::update_event_timestamp_ms(time)
+                        }
+                        WaylandEvent::KeyboardKey(keycode, scancode, state) => {
                             match keycode {
                                 KeyCode::LeftShift | KeyCode::RightShift => keymods.shift = state,
                                 KeyCode::LeftControl | KeyCode::RightControl => {
@@ -805,43 +1728,99 @@ where
                                 KeyCode::LeftSuper | KeyCode::RightSuper => keymods.logo = state,
                                 _ => {}
                             }
+                            crate::native::update_keymods(keymods);
 
                             if state {
-                                event_handler.key_down_event(keycode, keymods, false);
-                                repeated_keys.insert(keycode);
+                                crate::native::key_down(
+                                    keycode,
+                                    scancode,
+                                    keymods,
+                                    false,
+                                    &mut **event_handler,
+                                );
+                                repeated_keys.insert((keycode, scancode));
                             } else {
-                                event_handler.key_up_event(keycode, keymods);
-                                repeated_keys.remove(&keycode);
+                                crate::native::key_up(
+                                    keycode,
+                                    scancode,
+                                    keymods,
+                                    &mut **event_handler,
+                                );
+                                repeated_keys.remove(&(keycode, scancode));
                             }
                         }
+                        WaylandEvent::Modifiers(mods) => {
+                            keymods = mods;
+                            crate::native::update_keymods(keymods);
+                        }
+                        WaylandEvent::Focus(focused) => {
+                            if !focused {
+                                keymods = KeyMods::default();
+                                repeated_keys.clear();
+                            }
+                            crate::native::update_focus(focused, &mut **event_handler);
+                        }
+                        WaylandEvent::Char(character) => {
+                            event_handler.char_event(character, keymods, false)
+                        }
                         WaylandEvent::PointerMotion(x, y) => {
-                            event_handler.mouse_motion_event(x, y);
+                            event_handler.mouse_motion_keymods_event(x, y, keymods);
                             (last_mouse_x, last_mouse_y) = (x, y);
                         }
+                        WaylandEvent::PointerEntered => event_handler.mouse_entered_event(),
+                        WaylandEvent::PointerLeft => event_handler.mouse_left_event(),
                         WaylandEvent::PointerButton(button, state) => {
                             if state {
-                                event_handler.mouse_button_down_event(
+                                event_handler.mouse_button_down_keymods_event(
                                     button,
                                     last_mouse_x,
                                     last_mouse_y,
+                                    keymods,
                                 );
                             } else {
-                                event_handler.mouse_button_up_event(
+                                event_handler.mouse_button_up_keymods_event(
                                     button,
                                     last_mouse_x,
                                     last_mouse_y,
+                                    keymods,
                                 );
                             }
                         }
-                        WaylandEvent::PointerAxis(x, y) => event_handler.mouse_wheel_event(x, y),
+                        WaylandEvent::PointerAxis(x, y, delta) => {
+                            event_handler.mouse_wheel_keymods_event(x, y, keymods);
+                            event_handler.mouse_scroll_event(delta, keymods);
+                            crate::native::wheel_magnify(delta, keymods, &mut **event_handler);
+                        }
+                        WaylandEvent::RawMotion(dx, dy) => event_handler.raw_mouse_motion(dx, dy),
+                        WaylandEvent::FilesHovered => event_handler.files_hovered_event(),
+                        WaylandEvent::FilesHoverCancelled => {
+                            event_handler.files_hover_cancelled_event()
+                        }
+                        WaylandEvent::FilesDropped => event_handler.files_dropped_event(),
+                        WaylandEvent::ImeComposition(text, cursor) => {
+                            event_handler.ime_composition_event(&text, cursor)
+                        }
+                        WaylandEvent::ImeCommit(text) => event_handler.ime_commit_event(&text),
                     }
                 }
 
+                crate::native::handle_quit_request(&mut **event_handler);
+                gamepads.poll(&mut **event_handler);
+                power_supply.poll(&mut **event_handler);
+                if let Some(ref mut session_bus) = session_bus {
+                    session_bus.poll(&mut **event_handler);
+                }
+                crate::native::dispatch_custom_events(&mut **event_handler);
+                if crate::native_display().lock().unwrap().frames_paused() {
+                    frame_limiter.wait();
+                    continue;
+                }
                 event_handler.update();
                 event_handler.draw();
             }
 
             (libegl.eglSwapBuffers.unwrap())(egl_display, egl_surface);
+            frame_limiter.wait();
         }
     }
 
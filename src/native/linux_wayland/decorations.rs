@@ -77,6 +77,17 @@ unsafe fn create_decoration(
     }
 }
 
+impl Decoration {
+    unsafe fn destroy(&self, client: &mut LibWaylandClient) {
+        wl_request!(client, self.viewport, wp_viewport::destroy);
+        (client.wl_proxy_destroy)(self.viewport as _);
+        wl_request!(client, self.subsurface, WL_SUBSURFACE_DESTROY);
+        (client.wl_proxy_destroy)(self.subsurface as _);
+        wl_request!(client, self.surface, WL_SURFACE_DESTROY);
+        (client.wl_proxy_destroy)(self.surface as _);
+    }
+}
+
 impl Decorations {
     pub const WIDTH: i32 = 2;
     pub const BAR_HEIGHT: i32 = 15;
@@ -139,6 +150,16 @@ impl Decorations {
         }
     }
 
+    /// For `window::set_decorations(false)`.
+    pub unsafe fn destroy(self, client: &mut LibWaylandClient) {
+        self.top_decoration.destroy(client);
+        self.bottom_decoration.destroy(client);
+        self.left_decoration.destroy(client);
+        self.right_decoration.destroy(client);
+        wl_request!(client, self.buffer, WL_BUFFER_DESTROY);
+        (client.wl_proxy_destroy)(self.buffer as _);
+    }
+
     pub unsafe fn resize(&self, client: &mut LibWaylandClient, width: i32, height: i32) {
         wl_request!(
             client,
@@ -0,0 +1,236 @@
+//! Files drag and drop with wl_data_device. Only the "text/uri-list"
+//! offers are accepted, everything else is rejected right away.
+
+use super::*;
+use crate::{wl_request, wl_request_constructor};
+
+const URI_LIST: &[u8] = b"text/uri-list\0";
+
+pub(super) struct DragAndDrop {
+    pub manager: *mut wl_data_device_manager,
+    pub version: u32,
+    display: *mut wl_display,
+    uri_list_offers: HashSet<*mut wl_data_offer>,
+    // the offer currently dragged over the window, accepted or not
+    current: *mut wl_data_offer,
+}
+
+impl DragAndDrop {
+    pub fn new() -> DragAndDrop {
+        DragAndDrop {
+            manager: std::ptr::null_mut(),
+            version: 0,
+            display: std::ptr::null_mut(),
+            uri_list_offers: HashSet::new(),
+            current: std::ptr::null_mut(),
+        }
+    }
+}
+
+static mut DATA_DEVICE_LISTENER: wl_data_device_listener = wl_data_device_listener {
+    data_offer: Some(data_device_handle_data_offer),
+    enter: Some(data_device_handle_enter),
+    leave: Some(data_device_handle_leave),
+    motion: Some(data_device_handle_motion),
+    drop: Some(data_device_handle_drop),
+    selection: Some(data_device_handle_selection),
+};
+
+static mut DATA_OFFER_LISTENER: wl_data_offer_listener = wl_data_offer_listener {
+    offer: Some(data_offer_handle_offer),
+    source_actions: Some(data_offer_handle_source_actions),
+    action: Some(data_offer_handle_action),
+};
+
+/// Start listening for the drag and drop events, needs both the seat and the
+/// data device manager bound. Compositors without a data device manager
+/// just do not get drag and drop.
+pub(super) unsafe fn init(payload: &mut WaylandPayload, display: *mut wl_display) {
+    if payload.dnd.manager.is_null() {
+        return;
+    }
+    payload.dnd.display = display;
+    let data_device: *mut wl_data_device = wl_request_constructor!(
+        payload.client,
+        payload.dnd.manager,
+        WL_DATA_DEVICE_MANAGER_GET_DATA_DEVICE,
+        payload.client.wl_data_device_interface,
+        payload.seat
+    );
+    assert!(!data_device.is_null());
+    (payload.client.wl_proxy_add_listener)(
+        data_device as _,
+        &DATA_DEVICE_LISTENER as *const _ as _,
+        payload as *mut _ as _,
+    );
+}
+
+unsafe fn destroy_offer(payload: &mut WaylandPayload, offer: *mut wl_data_offer) {
+    payload.dnd.uri_list_offers.remove(&offer);
+    wl_request!(payload.client, offer, WL_DATA_OFFER_DESTROY);
+    (payload.client.wl_proxy_destroy)(offer as _);
+}
+
+unsafe extern "C" fn data_device_handle_data_offer(
+    data: *mut std::ffi::c_void,
+    _data_device: *mut wl_data_device,
+    offer: *mut wl_data_offer,
+) {
+    let payload: &mut WaylandPayload = &mut *(data as *mut _);
+    (payload.client.wl_proxy_add_listener)(offer as _, &DATA_OFFER_LISTENER as *const _ as _, data);
+}
+
+unsafe extern "C" fn data_device_handle_enter(
+    data: *mut std::ffi::c_void,
+    _data_device: *mut wl_data_device,
+    serial: u32,
+    _surface: *mut wl_surface,
+    _x: i32,
+    _y: i32,
+    offer: *mut wl_data_offer,
+) {
+    let payload: &mut WaylandPayload = &mut *(data as *mut _);
+    if offer.is_null() {
+        return;
+    }
+    payload.dnd.current = offer;
+    if !payload.dnd.uri_list_offers.contains(&offer) {
+        wl_request!(
+            payload.client,
+            offer,
+            WL_DATA_OFFER_ACCEPT,
+            serial,
+            std::ptr::null::<libc::c_char>()
+        );
+        return;
+    }
+
+    wl_request!(
+        payload.client,
+        offer,
+        WL_DATA_OFFER_ACCEPT,
+        serial,
+        URI_LIST.as_ptr()
+    );
+    if payload.dnd.version >= WL_DATA_OFFER_SET_ACTIONS_SINCE_VERSION {
+        wl_request!(
+            payload.client,
+            offer,
+            WL_DATA_OFFER_SET_ACTIONS,
+            WL_DATA_DEVICE_MANAGER_DND_ACTION_COPY,
+            WL_DATA_DEVICE_MANAGER_DND_ACTION_COPY
+        );
+    }
+    EVENTS.push(WaylandEvent::FilesHovered);
+}
+
+unsafe extern "C" fn data_device_handle_leave(
+    data: *mut std::ffi::c_void,
+    _data_device: *mut wl_data_device,
+) {
+    let payload: &mut WaylandPayload = &mut *(data as *mut _);
+    // also sent right after the drop, when the current offer is already gone
+    let offer = std::mem::replace(&mut payload.dnd.current, std::ptr::null_mut());
+    if !offer.is_null() {
+        if payload.dnd.uri_list_offers.contains(&offer) {
+            EVENTS.push(WaylandEvent::FilesHoverCancelled);
+        }
+        destroy_offer(payload, offer);
+    }
+}
+
+unsafe extern "C" fn data_device_handle_motion(
+    _data: *mut std::ffi::c_void,
+    _data_device: *mut wl_data_device,
+    _time: u32,
+    _x: i32,
+    _y: i32,
+) {
+}
+
+unsafe extern "C" fn data_device_handle_drop(
+    data: *mut std::ffi::c_void,
+    _data_device: *mut wl_data_device,
+) {
+    let payload: &mut WaylandPayload = &mut *(data as *mut _);
+    let offer = std::mem::replace(&mut payload.dnd.current, std::ptr::null_mut());
+    if offer.is_null() {
+        return;
+    }
+    if !payload.dnd.uri_list_offers.contains(&offer) {
+        destroy_offer(payload, offer);
+        return;
+    }
+
+    let mut fds = [0; 2];
+    if libc::pipe(fds.as_mut_ptr()) == 0 {
+        wl_request!(
+            payload.client,
+            offer,
+            WL_DATA_OFFER_RECEIVE,
+            URI_LIST.as_ptr(),
+            fds[1]
+        );
+        // the source client writes into the pipe only after getting the
+        // request, and the write end should be closed here to get EOF
+        (payload.client.wl_display_flush)(payload.dnd.display);
+        libc::close(fds[1]);
+
+        let mut list = vec![];
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = libc::read(fds[0], buf.as_mut_ptr() as _, buf.len());
+            if n <= 0 {
+                break;
+            }
+            list.extend_from_slice(&buf[..n as usize]);
+        }
+        libc::close(fds[0]);
+
+        let mut d = crate::native_display().lock().unwrap();
+        d.dropped_files = crate::native::DroppedFiles::from_uri_list(&list);
+        EVENTS.push(WaylandEvent::FilesDropped);
+    }
+
+    if payload.dnd.version >= WL_DATA_OFFER_FINISH_SINCE_VERSION {
+        wl_request!(payload.client, offer, WL_DATA_OFFER_FINISH);
+    }
+    destroy_offer(payload, offer);
+}
+
+unsafe extern "C" fn data_device_handle_selection(
+    data: *mut std::ffi::c_void,
+    _data_device: *mut wl_data_device,
+    offer: *mut wl_data_offer,
+) {
+    // clipboard offers, not used by miniquad
+    if !offer.is_null() {
+        let payload: &mut WaylandPayload = &mut *(data as *mut _);
+        destroy_offer(payload, offer);
+    }
+}
+
+unsafe extern "C" fn data_offer_handle_offer(
+    data: *mut std::ffi::c_void,
+    offer: *mut wl_data_offer,
+    mime_type: *const libc::c_char,
+) {
+    let payload: &mut WaylandPayload = &mut *(data as *mut _);
+    if std::ffi::CStr::from_ptr(mime_type).to_bytes_with_nul() == URI_LIST {
+        payload.dnd.uri_list_offers.insert(offer);
+    }
+}
+
+unsafe extern "C" fn data_offer_handle_source_actions(
+    _data: *mut std::ffi::c_void,
+    _offer: *mut wl_data_offer,
+    _source_actions: u32,
+) {
+}
+
+unsafe extern "C" fn data_offer_handle_action(
+    _data: *mut std::ffi::c_void,
+    _offer: *mut wl_data_offer,
+    _dnd_action: u32,
+) {
+}
@@ -1,6 +1,11 @@
 #![allow(unused_variables, dead_code, non_upper_case_globals)]
 
+pub mod idle_inhibit;
+pub mod pointer_constraints;
+pub mod relative_pointer;
+pub mod text_input;
 pub mod viewporter;
+pub mod xdg_activation;
 pub mod xdg_decoration;
 pub mod xdg_shell;
 
@@ -0,0 +1,23 @@
+// idle-inhibit-unstable-v1.xml
+
+use super::super::libwayland_client::{wl_interface, wl_message};
+use crate::wayland_interface;
+
+wayland_interface!(
+    zwp_idle_inhibit_manager_v1_interface,
+    zwp_idle_inhibit_manager_v1,
+    1,
+    [
+        (destroy, "", ()),
+        (create_inhibitor, "no", (zwp_idle_inhibitor_v1_interface))
+    ],
+    []
+);
+
+wayland_interface!(
+    zwp_idle_inhibitor_v1_interface,
+    zwp_idle_inhibitor_v1,
+    1,
+    [(destroy, "", ())],
+    []
+);
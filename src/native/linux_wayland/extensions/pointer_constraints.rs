@@ -0,0 +1,43 @@
+// pointer-constraints-unstable-v1.xml
+
+use super::super::libwayland_client::{wl_interface, wl_message};
+use crate::wayland_interface;
+
+/// The constraint comes back every time the pointer enters the surface.
+pub const ZWP_POINTER_CONSTRAINTS_V1_LIFETIME_PERSISTENT: u32 = 2;
+
+wayland_interface!(
+    zwp_pointer_constraints_v1_interface,
+    zwp_pointer_constraints_v1,
+    1,
+    [
+        (destroy, "", ()),
+        (lock_pointer, "noo?ou", (zwp_locked_pointer_v1_interface)),
+        (
+            confine_pointer,
+            "noo?ou",
+            (zwp_confined_pointer_v1_interface)
+        )
+    ],
+    []
+);
+
+wayland_interface!(
+    zwp_locked_pointer_v1_interface,
+    zwp_locked_pointer_v1,
+    1,
+    [
+        (destroy, "", ()),
+        (set_cursor_position_hint, "ff", ()),
+        (set_region, "?o", ())
+    ],
+    [("locked", ""), ("unlocked", "")]
+);
+
+wayland_interface!(
+    zwp_confined_pointer_v1_interface,
+    zwp_confined_pointer_v1,
+    1,
+    [(destroy, "", ()), (set_region, "?o", ())],
+    [("confined", ""), ("unconfined", "")]
+);
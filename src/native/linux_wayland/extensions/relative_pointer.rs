@@ -0,0 +1,44 @@
+// relative-pointer-unstable-v1.xml
+
+use super::super::libwayland_client::{wl_interface, wl_message};
+use crate::wayland_interface;
+
+wayland_interface!(
+    zwp_relative_pointer_manager_v1_interface,
+    zwp_relative_pointer_manager_v1,
+    1,
+    [
+        (destroy, "", ()),
+        (
+            get_relative_pointer,
+            "no",
+            (zwp_relative_pointer_v1_interface)
+        )
+    ],
+    []
+);
+
+wayland_interface!(
+    zwp_relative_pointer_v1_interface,
+    zwp_relative_pointer_v1,
+    1,
+    [(destroy, "", ())],
+    [("relative_motion", "uuffff")]
+);
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub(crate) struct zwp_relative_pointer_v1_listener {
+    pub relative_motion: Option<
+        unsafe extern "C" fn(
+            _: *mut std::ffi::c_void,
+            _: *mut zwp_relative_pointer_v1,
+            utime_hi: u32,
+            utime_lo: u32,
+            dx: i32,
+            dy: i32,
+            dx_unaccel: i32,
+            dy_unaccel: i32,
+        ),
+    >,
+}
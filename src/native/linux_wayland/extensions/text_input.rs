@@ -0,0 +1,132 @@
+// text-input-unstable-v3.xml
+
+use super::{
+    super::libwayland_client::{wl_interface, wl_message, wl_surface},
+    wayland_protocol::wl_seat_interface,
+};
+use crate::wayland_interface;
+
+wayland_interface!(
+    zwp_text_input_manager_v3_interface,
+    zwp_text_input_manager_v3,
+    1,
+    [
+        (destroy, "", ()),
+        (
+            get_text_input,
+            "no",
+            (zwp_text_input_v3_interface, wl_seat_interface)
+        )
+    ],
+    []
+);
+
+// by hand, the enter and leave events have object arguments and libwayland
+// looks at their types, wayland_interface! has no types for the events
+mod zwp_text_input_v3_interface {
+    use super::*;
+
+    static mut NO_TYPES: [*const wl_interface; 4] = [std::ptr::null(); 4];
+
+    macro_rules! message {
+        ($name:expr, $signature:expr) => {
+            wl_message {
+                name: concat!($name, '\0').as_ptr() as _,
+                signature: concat!($signature, '\0').as_ptr() as _,
+                types: unsafe { NO_TYPES.as_ptr() as _ },
+            }
+        };
+    }
+
+    static mut requests: [wl_message; 8] = [
+        message!("destroy", ""),
+        message!("enable", ""),
+        message!("disable", ""),
+        message!("set_surrounding_text", "sii"),
+        message!("set_text_change_cause", "u"),
+        message!("set_content_type", "uu"),
+        message!("set_cursor_rectangle", "iiii"),
+        message!("commit", ""),
+    ];
+
+    static mut events: [wl_message; 6] = [
+        message!("enter", "o"),
+        message!("leave", "o"),
+        message!("preedit_string", "?sii"),
+        message!("commit_string", "?s"),
+        message!("delete_surrounding_text", "uu"),
+        message!("done", "u"),
+    ];
+
+    pub static mut zwp_text_input_v3_interface: wl_interface = wl_interface {
+        name: "zwp_text_input_v3\0".as_ptr() as *const _,
+        version: 1,
+        method_count: 8,
+        methods: unsafe { requests.as_ptr() },
+        event_count: 6,
+        events: unsafe { events.as_ptr() },
+    };
+}
+pub use zwp_text_input_v3_interface::zwp_text_input_v3_interface;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct zwp_text_input_v3 {
+    _unused: [u8; 0],
+}
+
+impl zwp_text_input_v3 {
+    crate::method_consts!(
+        0,
+        (
+            destroy,
+            enable,
+            disable,
+            set_surrounding_text,
+            set_text_change_cause,
+            set_content_type,
+            set_cursor_rectangle,
+            commit,
+        )
+    );
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub(crate) struct zwp_text_input_v3_listener {
+    pub enter: Option<
+        unsafe extern "C" fn(
+            _: *mut std::ffi::c_void,
+            _: *mut zwp_text_input_v3,
+            _: *mut wl_surface,
+        ),
+    >,
+    pub leave: Option<
+        unsafe extern "C" fn(
+            _: *mut std::ffi::c_void,
+            _: *mut zwp_text_input_v3,
+            _: *mut wl_surface,
+        ),
+    >,
+    pub preedit_string: Option<
+        unsafe extern "C" fn(
+            _: *mut std::ffi::c_void,
+            _: *mut zwp_text_input_v3,
+            _: *const std::os::raw::c_char,
+            _: i32,
+            _: i32,
+        ),
+    >,
+    pub commit_string: Option<
+        unsafe extern "C" fn(
+            _: *mut std::ffi::c_void,
+            _: *mut zwp_text_input_v3,
+            _: *const std::os::raw::c_char,
+        ),
+    >,
+    pub delete_surrounding_text: Option<
+        unsafe extern "C" fn(_: *mut std::ffi::c_void, _: *mut zwp_text_input_v3, _: u32, _: u32),
+    >,
+    pub done:
+        Option<unsafe extern "C" fn(_: *mut std::ffi::c_void, _: *mut zwp_text_input_v3, _: u32)>,
+}
@@ -0,0 +1,49 @@
+// xdg-activation-v1.xml
+
+use super::{
+    super::libwayland_client::{wl_interface, wl_message},
+    wayland_protocol::wl_seat_interface,
+};
+use crate::wayland_interface;
+
+wayland_interface!(
+    xdg_activation_v1_interface,
+    xdg_activation_v1,
+    1,
+    [
+        (destroy, "", ()),
+        (
+            get_activation_token,
+            "n",
+            (xdg_activation_token_v1_interface)
+        ),
+        (activate, "so", ())
+    ],
+    []
+);
+
+wayland_interface!(
+    xdg_activation_token_v1_interface,
+    xdg_activation_token_v1,
+    1,
+    [
+        (set_serial, "uo", (wl_seat_interface)),
+        (set_app_id, "s", ()),
+        (set_surface, "o", ()),
+        (commit, "", ()),
+        (destroy, "", ())
+    ],
+    [("done", "s")]
+);
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub(crate) struct xdg_activation_token_v1_listener {
+    pub done: Option<
+        unsafe extern "C" fn(
+            _: *mut std::ffi::c_void,
+            _: *mut xdg_activation_token_v1,
+            _: *const std::os::raw::c_char,
+        ),
+    >,
+}
@@ -6,10 +6,11 @@ use super::{
 };
 use crate::wayland_interface;
 
+// version 6 for the suspended state, it has the events of 4 and 5 too
 wayland_interface!(
     xdg_wm_base_interface,
     xdg_wm_base,
-    3,
+    6,
     [
         (destroy, "", ()),
         (create_positioner, "n", (xdg_positioner_interface)),
@@ -22,7 +23,7 @@ wayland_interface!(
 wayland_interface!(
     xdg_surface_interface,
     xdg_surface,
-    3,
+    6,
     [
         (destroy, "", ()),
         (get_toplevel, "n", (xdg_toplevel_interface)),
@@ -36,7 +37,7 @@ wayland_interface!(
 wayland_interface!(
     xdg_toplevel_interface,
     xdg_toplevel,
-    3,
+    6,
     [
         (destroy, "", ()),
         (set_parent, "?o", (xdg_toplevel_interface)),
@@ -53,7 +54,12 @@ wayland_interface!(
         (unset_fullscreen, "", ()),
         (set_minimized, "", ())
     ],
-    [("configure", "iia"), ("close", "")]
+    [
+        ("configure", "iia"),
+        ("close", ""),
+        ("configure_bounds", "4ii"),
+        ("wm_capabilities", "5a")
+    ]
 );
 
 wayland_interface!(
@@ -87,6 +93,11 @@ wayland_interface!(
     [("configure", "iiii"), ("popup_done", "")]
 );
 
+// xdg_toplevel.state, in the configure states array
+pub(crate) const XDG_TOPLEVEL_STATE_MAXIMIZED: u32 = 1;
+pub(crate) const XDG_TOPLEVEL_STATE_RESIZING: u32 = 3;
+pub(crate) const XDG_TOPLEVEL_STATE_SUSPENDED: u32 = 9;
+
 #[derive(Copy, Clone)]
 #[repr(C)]
 pub(crate) struct xdg_wm_base_listener {
@@ -114,4 +125,10 @@ pub(crate) struct xdg_toplevel_listener {
         ) -> (),
     >,
     pub close: Option<unsafe extern "C" fn(_: *mut std::ffi::c_void, _: *mut xdg_toplevel) -> ()>,
+    pub configure_bounds: Option<
+        unsafe extern "C" fn(_: *mut std::ffi::c_void, _: *mut xdg_toplevel, _: i32, _: i32),
+    >,
+    pub wm_capabilities: Option<
+        unsafe extern "C" fn(_: *mut std::ffi::c_void, _: *mut xdg_toplevel, _: *mut wl_array),
+    >,
 }
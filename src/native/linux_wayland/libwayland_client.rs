@@ -565,6 +565,144 @@ pub struct wl_pointer_listener {
     >,
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct wl_data_offer_listener {
+    pub offer: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_offer: *mut wl_data_offer,
+            mime_type: *const ::std::os::raw::c_char,
+        ),
+    >,
+    pub source_actions: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_offer: *mut wl_data_offer,
+            source_actions: u32,
+        ),
+    >,
+    pub action: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_offer: *mut wl_data_offer,
+            dnd_action: u32,
+        ),
+    >,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct wl_data_device_listener {
+    pub data_offer: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_device: *mut wl_data_device,
+            id: *mut wl_data_offer,
+        ),
+    >,
+    pub enter: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_device: *mut wl_data_device,
+            serial: u32,
+            surface: *mut wl_surface,
+            x: i32,
+            y: i32,
+            id: *mut wl_data_offer,
+        ),
+    >,
+    pub leave: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_device: *mut wl_data_device,
+        ),
+    >,
+    pub motion: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_device: *mut wl_data_device,
+            time: u32,
+            x: i32,
+            y: i32,
+        ),
+    >,
+    pub drop: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_device: *mut wl_data_device,
+        ),
+    >,
+    pub selection: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_device: *mut wl_data_device,
+            id: *mut wl_data_offer,
+        ),
+    >,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct wl_surface_listener {
+    pub enter: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_surface: *mut wl_surface,
+            output: *mut wl_output,
+        ),
+    >,
+    pub leave: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_surface: *mut wl_surface,
+            output: *mut wl_output,
+        ),
+    >,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct wl_output_listener {
+    pub geometry: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_output: *mut wl_output,
+            x: i32,
+            y: i32,
+            physical_width: i32,
+            physical_height: i32,
+            subpixel: i32,
+            make: *const ::std::os::raw::c_char,
+            model: *const ::std::os::raw::c_char,
+            transform: i32,
+        ),
+    >,
+    pub mode: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_output: *mut wl_output,
+            flags: u32,
+            width: i32,
+            height: i32,
+            refresh: i32,
+        ),
+    >,
+    pub done: ::std::option::Option<
+        unsafe extern "C" fn(data: *mut ::std::os::raw::c_void, wl_output: *mut wl_output),
+    >,
+    pub scale: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_output: *mut wl_output,
+            factor: i32,
+        ),
+    >,
+}
+
+pub const WL_DATA_DEVICE_MANAGER_DND_ACTION_COPY: u32 = 1;
+pub const WL_OUTPUT_MODE_CURRENT: u32 = 1;
+
 pub type wl_display_connect =
     unsafe extern "C" fn(name: *const ::std::os::raw::c_char) -> *mut wl_display;
 pub type wl_proxy_destroy = unsafe extern "C" fn(proxy: *mut wl_proxy);
@@ -588,10 +726,18 @@ pub type wl_proxy_add_listener = unsafe extern "C" fn(
     data: *mut ::std::os::raw::c_void,
 ) -> ::std::os::raw::c_int;
 
+pub type wl_display_flush = unsafe extern "C" fn(display: *mut wl_display) -> ::std::os::raw::c_int;
 pub type wl_display_roundtrip =
     unsafe extern "C" fn(display: *mut wl_display) -> ::std::os::raw::c_int;
 pub type wl_display_dispatch_pending =
     unsafe extern "C" fn(display: *mut wl_display) -> ::std::os::raw::c_int;
+pub type wl_display_get_fd =
+    unsafe extern "C" fn(display: *mut wl_display) -> ::std::os::raw::c_int;
+pub type wl_display_prepare_read =
+    unsafe extern "C" fn(display: *mut wl_display) -> ::std::os::raw::c_int;
+pub type wl_display_read_events =
+    unsafe extern "C" fn(display: *mut wl_display) -> ::std::os::raw::c_int;
+pub type wl_display_cancel_read = unsafe extern "C" fn(display: *mut wl_display);
 
 #[derive(Clone)]
 pub struct LibWaylandClient {
@@ -604,6 +750,11 @@ pub struct LibWaylandClient {
     pub wl_display_dispatch_pending: wl_display_dispatch_pending,
     pub wl_proxy_add_listener: wl_proxy_add_listener,
     pub wl_display_roundtrip: wl_display_roundtrip,
+    pub wl_display_flush: wl_display_flush,
+    pub wl_display_get_fd: wl_display_get_fd,
+    pub wl_display_prepare_read: wl_display_prepare_read,
+    pub wl_display_read_events: wl_display_read_events,
+    pub wl_display_cancel_read: wl_display_cancel_read,
     pub wl_registry_interface: *mut wl_interface,
     pub wl_compositor_interface: *mut wl_interface,
     pub wl_subcompositor_interface: *mut wl_interface,
@@ -615,6 +766,10 @@ pub struct LibWaylandClient {
     pub wl_shm_pool_interface: *mut wl_interface,
     pub wl_keyboard_interface: *mut wl_interface,
     pub wl_pointer_interface: *mut wl_interface,
+    pub wl_data_device_manager_interface: *mut wl_interface,
+    pub wl_data_device_interface: *mut wl_interface,
+    pub wl_data_offer_interface: *mut wl_interface,
+    pub wl_output_interface: *mut wl_interface,
 }
 
 impl LibWaylandClient {
@@ -637,6 +792,11 @@ impl LibWaylandClient {
                     .get_symbol("wl_proxy_marshal_constructor_versioned")
                     .unwrap(),
                 wl_display_roundtrip: module.get_symbol("wl_display_roundtrip").unwrap(),
+                wl_display_flush: module.get_symbol("wl_display_flush").unwrap(),
+                wl_display_get_fd: module.get_symbol("wl_display_get_fd").unwrap(),
+                wl_display_prepare_read: module.get_symbol("wl_display_prepare_read").unwrap(),
+                wl_display_read_events: module.get_symbol("wl_display_read_events").unwrap(),
+                wl_display_cancel_read: module.get_symbol("wl_display_cancel_read").unwrap(),
 
                 wl_registry_interface: module.get_symbol("wl_registry_interface").unwrap(),
                 wl_compositor_interface: module.get_symbol("wl_compositor_interface").unwrap(),
@@ -651,6 +811,12 @@ impl LibWaylandClient {
                 wl_shm_pool_interface: module.get_symbol("wl_shm_pool_interface").unwrap(),
                 wl_keyboard_interface: module.get_symbol("wl_keyboard_interface").unwrap(),
                 wl_pointer_interface: module.get_symbol("wl_pointer_interface").unwrap(),
+                wl_data_device_manager_interface: module
+                    .get_symbol("wl_data_device_manager_interface")
+                    .unwrap(),
+                wl_data_device_interface: module.get_symbol("wl_data_device_interface").unwrap(),
+                wl_data_offer_interface: module.get_symbol("wl_data_offer_interface").unwrap(),
+                wl_output_interface: module.get_symbol("wl_output_interface").unwrap(),
 
                 _module: std::rc::Rc::new(module),
             })
@@ -15,6 +15,30 @@ pub struct xkb_keymap {
 pub struct xkb_state {
     _unused: [u8; 0],
 }
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct xkb_compose_table {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct xkb_compose_state {
+    _unused: [u8; 0],
+}
+
+pub const XKB_STATE_MODS_EFFECTIVE: ::std::os::raw::c_int = 1 << 3;
+pub const XKB_MOD_NAME_SHIFT: &str = "Shift\0";
+pub const XKB_MOD_NAME_CAPS: &str = "Lock\0";
+pub const XKB_MOD_NAME_CTRL: &str = "Control\0";
+pub const XKB_MOD_NAME_ALT: &str = "Mod1\0";
+pub const XKB_MOD_NAME_NUM: &str = "Mod2\0";
+pub const XKB_MOD_NAME_LOGO: &str = "Mod4\0";
+
+pub const XKB_COMPOSE_FEED_ACCEPTED: ::std::os::raw::c_int = 1;
+pub const XKB_COMPOSE_NOTHING: ::std::os::raw::c_int = 0;
+pub const XKB_COMPOSE_COMPOSING: ::std::os::raw::c_int = 1;
+pub const XKB_COMPOSE_COMPOSED: ::std::os::raw::c_int = 2;
+pub const XKB_COMPOSE_CANCELLED: ::std::os::raw::c_int = 3;
 
 pub type xkb_context_new = unsafe extern "C" fn(flags: ::std::os::raw::c_int) -> *mut xkb_context;
 pub type xkb_context_unref = unsafe extern "C" fn(context: *mut xkb_context);
@@ -39,6 +63,34 @@ pub type xkb_state_update_mask = unsafe extern "C" fn(
     latched_layout: u32,
     locked_layout: u32,
 ) -> ::std::os::raw::c_int;
+pub type xkb_state_mod_name_is_active = unsafe extern "C" fn(
+    state: *mut xkb_state,
+    name: *const ::std::os::raw::c_char,
+    type_: ::std::os::raw::c_int,
+) -> ::std::os::raw::c_int;
+pub type xkb_keysym_to_utf32 = unsafe extern "C" fn(keysym: u32) -> u32;
+
+pub type xkb_compose_table_new_from_locale = unsafe extern "C" fn(
+    context: *mut xkb_context,
+    locale: *const ::std::os::raw::c_char,
+    flags: ::std::os::raw::c_int,
+) -> *mut xkb_compose_table;
+pub type xkb_compose_table_unref = unsafe extern "C" fn(table: *mut xkb_compose_table);
+pub type xkb_compose_state_new = unsafe extern "C" fn(
+    table: *mut xkb_compose_table,
+    flags: ::std::os::raw::c_int,
+) -> *mut xkb_compose_state;
+pub type xkb_compose_state_unref = unsafe extern "C" fn(state: *mut xkb_compose_state);
+pub type xkb_compose_state_feed =
+    unsafe extern "C" fn(state: *mut xkb_compose_state, keysym: u32) -> ::std::os::raw::c_int;
+pub type xkb_compose_state_reset = unsafe extern "C" fn(state: *mut xkb_compose_state);
+pub type xkb_compose_state_get_status =
+    unsafe extern "C" fn(state: *mut xkb_compose_state) -> ::std::os::raw::c_int;
+pub type xkb_compose_state_get_utf8 = unsafe extern "C" fn(
+    state: *mut xkb_compose_state,
+    buffer: *mut ::std::os::raw::c_char,
+    size: usize,
+) -> ::std::os::raw::c_int;
 
 #[derive(Clone)]
 pub struct LibXkbCommon {
@@ -51,6 +103,16 @@ pub struct LibXkbCommon {
     pub xkb_state_unref: xkb_state_unref,
     pub xkb_state_key_get_one_sym: xkb_state_key_get_one_sym,
     pub xkb_state_update_mask: xkb_state_update_mask,
+    pub xkb_state_mod_name_is_active: xkb_state_mod_name_is_active,
+    pub xkb_keysym_to_utf32: xkb_keysym_to_utf32,
+    pub xkb_compose_table_new_from_locale: xkb_compose_table_new_from_locale,
+    pub xkb_compose_table_unref: xkb_compose_table_unref,
+    pub xkb_compose_state_new: xkb_compose_state_new,
+    pub xkb_compose_state_unref: xkb_compose_state_unref,
+    pub xkb_compose_state_feed: xkb_compose_state_feed,
+    pub xkb_compose_state_reset: xkb_compose_state_reset,
+    pub xkb_compose_state_get_status: xkb_compose_state_get_status,
+    pub xkb_compose_state_get_utf8: xkb_compose_state_get_utf8,
 }
 
 impl LibXkbCommon {
@@ -68,6 +130,24 @@ impl LibXkbCommon {
                 xkb_state_unref: module.get_symbol("xkb_state_unref").unwrap(),
                 xkb_state_key_get_one_sym: module.get_symbol("xkb_state_key_get_one_sym").unwrap(),
                 xkb_state_update_mask: module.get_symbol("xkb_state_update_mask").unwrap(),
+                xkb_state_mod_name_is_active: module
+                    .get_symbol("xkb_state_mod_name_is_active")
+                    .unwrap(),
+                xkb_keysym_to_utf32: module.get_symbol("xkb_keysym_to_utf32").unwrap(),
+                xkb_compose_table_new_from_locale: module
+                    .get_symbol("xkb_compose_table_new_from_locale")
+                    .unwrap(),
+                xkb_compose_table_unref: module.get_symbol("xkb_compose_table_unref").unwrap(),
+                xkb_compose_state_new: module.get_symbol("xkb_compose_state_new").unwrap(),
+                xkb_compose_state_unref: module.get_symbol("xkb_compose_state_unref").unwrap(),
+                xkb_compose_state_feed: module.get_symbol("xkb_compose_state_feed").unwrap(),
+                xkb_compose_state_reset: module.get_symbol("xkb_compose_state_reset").unwrap(),
+                xkb_compose_state_get_status: module
+                    .get_symbol("xkb_compose_state_get_status")
+                    .unwrap(),
+                xkb_compose_state_get_utf8: module
+                    .get_symbol("xkb_compose_state_get_utf8")
+                    .unwrap(),
 
                 _module: std::rc::Rc::new(module),
             })
@@ -0,0 +1,243 @@
+//! wl_output globals as `crate::Monitor`s, and the surface enter/leave
+//! events to know which of them the window is on.
+
+use super::*;
+use crate::wl_request;
+
+struct Output {
+    // the registry name, for global_remove
+    name: u32,
+    output: *mut wl_output,
+    version: u32,
+    monitor: crate::Monitor,
+    // the mode is not rotated, the monitor resolution is
+    mode: (u32, u32),
+    transform: i32,
+    // the first "done" is not there yet, the monitor is incomplete
+    done: bool,
+}
+
+pub(super) struct Monitors {
+    outputs: Vec<Output>,
+    // outputs the surface is on, the last one is the current monitor
+    entered: Vec<*mut wl_output>,
+}
+
+impl Monitors {
+    pub fn new() -> Monitors {
+        Monitors {
+            outputs: vec![],
+            entered: vec![],
+        }
+    }
+
+    fn output(&mut self, output: *mut wl_output) -> Option<&mut Output> {
+        self.outputs.iter_mut().find(|o| o.output == output)
+    }
+
+    /// The `monitor`-th of `window::monitors()`, null if there is no such monitor.
+    pub fn wl_output(&self, monitor: usize) -> *mut wl_output {
+        self.outputs
+            .iter()
+            .filter(|o| o.done)
+            .nth(monitor)
+            .map_or(std::ptr::null_mut(), |o| o.output)
+    }
+
+    /// The largest scale of the monitors the surface is on, 1 when on none.
+    pub fn scale(&self) -> i32 {
+        self.outputs
+            .iter()
+            .filter(|o| self.entered.contains(&o.output))
+            .map(|o| o.monitor.dpi_scale as i32)
+            .max()
+            .unwrap_or(1)
+    }
+
+    // copy everything to NativeDisplayData, the only place the user can see it
+    fn publish(&self) {
+        let outputs: Vec<&Output> = self.outputs.iter().filter(|o| o.done).collect();
+        let mut d = crate::native_display().lock().unwrap();
+        d.monitors = outputs.iter().map(|o| o.monitor.clone()).collect();
+        d.current_monitor = self
+            .entered
+            .last()
+            .and_then(|entered| outputs.iter().position(|o| o.output == *entered));
+    }
+}
+
+static mut OUTPUT_LISTENER: wl_output_listener = wl_output_listener {
+    geometry: Some(output_handle_geometry),
+    mode: Some(output_handle_mode),
+    done: Some(output_handle_done),
+    scale: Some(output_handle_scale),
+};
+
+static mut SURFACE_LISTENER: wl_surface_listener = wl_surface_listener {
+    enter: Some(surface_handle_enter),
+    leave: Some(surface_handle_leave),
+};
+
+/// A wl_output global from the registry.
+pub(super) unsafe fn bind_output(
+    payload: &mut WaylandPayload,
+    registry: *mut wl_registry,
+    name: u32,
+    version: u32,
+) {
+    // without "done" there is no way to tell when the output info is complete
+    if version < WL_OUTPUT_DONE_SINCE_VERSION {
+        return;
+    }
+    let version = 3.min(version);
+    let output: *mut wl_output =
+        payload
+            .client
+            .wl_registry_bind(registry, name, payload.client.wl_output_interface, version)
+            as _;
+    (payload.client.wl_proxy_add_listener)(
+        output as _,
+        &OUTPUT_LISTENER as *const _ as _,
+        payload as *mut _ as _,
+    );
+    payload.monitors.outputs.push(Output {
+        name,
+        output,
+        version,
+        monitor: crate::Monitor {
+            name: String::new(),
+            position: (0, 0),
+            physical_size: (0, 0),
+            resolution: (0, 0),
+            refresh_rate: None,
+            // no video modes for wayland clients
+            video_modes: vec![],
+            dpi_scale: 1.,
+        },
+        mode: (0, 0),
+        transform: 0,
+        done: false,
+    });
+}
+
+/// global_remove, for any global, the unplugged monitors are among them.
+pub(super) unsafe fn remove_output(payload: &mut WaylandPayload, name: u32) {
+    let index = match payload.monitors.outputs.iter().position(|o| o.name == name) {
+        Some(index) => index,
+        None => return,
+    };
+    let output = payload.monitors.outputs.remove(index);
+    payload.monitors.entered.retain(|o| *o != output.output);
+    if output.version >= WL_OUTPUT_RELEASE_SINCE_VERSION {
+        wl_request!(payload.client, output.output, WL_OUTPUT_RELEASE);
+    }
+    (payload.client.wl_proxy_destroy)(output.output as _);
+    payload.monitors.publish();
+    update_buffer_scale(payload);
+}
+
+/// Start listening for the surface entering and leaving outputs.
+pub(super) unsafe fn init_surface(payload: &mut WaylandPayload) {
+    (payload.client.wl_proxy_add_listener)(
+        payload.surface as _,
+        &SURFACE_LISTENER as *const _ as _,
+        payload as *mut _ as _,
+    );
+}
+
+unsafe extern "C" fn output_handle_geometry(
+    data: *mut std::ffi::c_void,
+    output: *mut wl_output,
+    x: i32,
+    y: i32,
+    physical_width: i32,
+    physical_height: i32,
+    _subpixel: i32,
+    make: *const libc::c_char,
+    model: *const libc::c_char,
+    transform: i32,
+) {
+    let payload: &mut WaylandPayload = &mut *(data as *mut _);
+    if let Some(output) = payload.monitors.output(output) {
+        let make = std::ffi::CStr::from_ptr(make).to_string_lossy();
+        let model = std::ffi::CStr::from_ptr(model).to_string_lossy();
+        output.monitor.name = format!("{} {}", make, model).trim().to_string();
+        output.monitor.position = (x, y);
+        output.monitor.physical_size = (physical_width.max(0) as _, physical_height.max(0) as _);
+        output.transform = transform;
+    }
+}
+
+unsafe extern "C" fn output_handle_mode(
+    data: *mut std::ffi::c_void,
+    output: *mut wl_output,
+    flags: u32,
+    width: i32,
+    height: i32,
+    refresh: i32,
+) {
+    let payload: &mut WaylandPayload = &mut *(data as *mut _);
+    if flags & WL_OUTPUT_MODE_CURRENT == 0 {
+        return;
+    }
+    if let Some(output) = payload.monitors.output(output) {
+        output.mode = (width as _, height as _);
+        // in mHz, 0 when it does not make sense, like for virtual outputs
+        output.monitor.refresh_rate = if refresh > 0 {
+            Some(refresh as f32 / 1000.)
+        } else {
+            None
+        };
+    }
+}
+
+unsafe extern "C" fn output_handle_done(data: *mut std::ffi::c_void, output: *mut wl_output) {
+    let payload: &mut WaylandPayload = &mut *(data as *mut _);
+    if let Some(output) = payload.monitors.output(output) {
+        let (width, height) = output.mode;
+        // odd transforms are 90 and 270 degrees rotations
+        output.monitor.resolution = if output.transform % 2 == 1 {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        output.done = true;
+    }
+    payload.monitors.publish();
+    // the scale of a monitor the window is on could have changed
+    update_buffer_scale(payload);
+}
+
+unsafe extern "C" fn output_handle_scale(
+    data: *mut std::ffi::c_void,
+    output: *mut wl_output,
+    factor: i32,
+) {
+    let payload: &mut WaylandPayload = &mut *(data as *mut _);
+    if let Some(output) = payload.monitors.output(output) {
+        output.monitor.dpi_scale = factor as f32;
+    }
+}
+
+unsafe extern "C" fn surface_handle_enter(
+    data: *mut std::ffi::c_void,
+    _surface: *mut wl_surface,
+    output: *mut wl_output,
+) {
+    let payload: &mut WaylandPayload = &mut *(data as *mut _);
+    payload.monitors.entered.retain(|o| *o != output);
+    payload.monitors.entered.push(output);
+    payload.monitors.publish();
+    update_buffer_scale(payload);
+}
+
+unsafe extern "C" fn surface_handle_leave(
+    data: *mut std::ffi::c_void,
+    _surface: *mut wl_surface,
+    output: *mut wl_output,
+) {
+    let payload: &mut WaylandPayload = &mut *(data as *mut _);
+    payload.monitors.entered.retain(|o| *o != output);
+    payload.monitors.publish();
+    update_buffer_scale(payload);
+}
@@ -0,0 +1,232 @@
+//! zwp_text_input_v3, the input method behind `ime_composition_event` and
+//! `ime_commit_event`, and `window::set_ime_cursor_area`.
+
+use super::*;
+use crate::{wl_request, wl_request_constructor};
+use extensions::text_input::{
+    zwp_text_input_manager_v3, zwp_text_input_v3, zwp_text_input_v3_interface,
+    zwp_text_input_v3_listener,
+};
+
+pub(super) struct TextInput {
+    // null when the compositor has no text input
+    pub manager: *mut zwp_text_input_manager_v3,
+    text_input: *mut zwp_text_input_v3,
+    // window::set_text_input_enabled
+    pub on: bool,
+    // between enter and leave
+    entered: bool,
+    // in surface coordinates
+    cursor_area: (i32, i32, i32, i32),
+    // double buffered, applied on "done"
+    preedit: Option<(String, usize)>,
+    commit: Option<String>,
+    // the last composition sent was not empty
+    composing: bool,
+}
+
+impl TextInput {
+    pub fn new() -> TextInput {
+        TextInput {
+            manager: std::ptr::null_mut(),
+            text_input: std::ptr::null_mut(),
+            on: true,
+            entered: false,
+            cursor_area: (0, 0, 0, 0),
+            preedit: None,
+            commit: None,
+            composing: false,
+        }
+    }
+
+    fn end_composition(&mut self) {
+        if self.composing {
+            self.composing = false;
+            unsafe { EVENTS.push(WaylandEvent::ImeComposition(String::new(), 0)) };
+        }
+    }
+}
+
+static mut TEXT_INPUT_LISTENER: zwp_text_input_v3_listener = zwp_text_input_v3_listener {
+    enter: Some(text_input_handle_enter),
+    leave: Some(text_input_handle_leave),
+    preedit_string: Some(text_input_handle_preedit_string),
+    commit_string: Some(text_input_handle_commit_string),
+    delete_surrounding_text: Some(text_input_handle_delete_surrounding_text),
+    done: Some(text_input_handle_done),
+};
+
+/// The text input for the seat, once the globals are bound.
+pub(super) unsafe fn init(payload: &mut WaylandPayload) {
+    if payload.text_input.manager.is_null() {
+        return;
+    }
+    payload.text_input.text_input = wl_request_constructor!(
+        payload.client,
+        payload.text_input.manager,
+        zwp_text_input_manager_v3::get_text_input,
+        &zwp_text_input_v3_interface,
+        payload.seat
+    );
+    assert!(!payload.text_input.text_input.is_null());
+    (payload.client.wl_proxy_add_listener)(
+        payload.text_input.text_input as _,
+        &TEXT_INPUT_LISTENER as *const _ as _,
+        payload as *mut _ as _,
+    );
+}
+
+/// `window::set_ime_cursor_area`, in framebuffer pixels.
+pub(super) unsafe fn set_cursor_area(
+    payload: &mut WaylandPayload,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+) {
+    let scale = payload.buffer_scale as f32;
+    payload.text_input.cursor_area = (
+        (x / scale) as i32,
+        (y / scale) as i32,
+        (width / scale) as i32,
+        (height / scale) as i32,
+    );
+    if payload.text_input.entered && payload.text_input.on {
+        send_cursor_area(payload);
+        wl_request!(
+            payload.client,
+            payload.text_input.text_input,
+            zwp_text_input_v3::commit
+        );
+    }
+}
+
+/// `window::set_text_input_enabled`, the input method is disabled while off.
+pub(super) unsafe fn set_enabled(payload: &mut WaylandPayload, on: bool) {
+    if payload.text_input.on == on {
+        return;
+    }
+    payload.text_input.on = on;
+    if payload.text_input.entered {
+        if on {
+            enable(payload);
+        } else {
+            disable(payload);
+        }
+    }
+}
+
+unsafe fn enable(payload: &mut WaylandPayload) {
+    let text_input = payload.text_input.text_input;
+    wl_request!(payload.client, text_input, zwp_text_input_v3::enable);
+    send_cursor_area(payload);
+    wl_request!(payload.client, text_input, zwp_text_input_v3::commit);
+}
+
+unsafe fn disable(payload: &mut WaylandPayload) {
+    let text_input = payload.text_input.text_input;
+    payload.text_input.end_composition();
+    wl_request!(payload.client, text_input, zwp_text_input_v3::disable);
+    wl_request!(payload.client, text_input, zwp_text_input_v3::commit);
+}
+
+unsafe fn send_cursor_area(payload: &mut WaylandPayload) {
+    let (x, y, width, height) = payload.text_input.cursor_area;
+    wl_request!(
+        payload.client,
+        payload.text_input.text_input,
+        zwp_text_input_v3::set_cursor_rectangle,
+        x,
+        y,
+        width,
+        height
+    );
+}
+
+unsafe extern "C" fn text_input_handle_enter(
+    data: *mut std::ffi::c_void,
+    _text_input: *mut zwp_text_input_v3,
+    _surface: *mut wl_surface,
+) {
+    let payload: &mut WaylandPayload = &mut *(data as *mut _);
+    payload.text_input.entered = true;
+    if payload.text_input.on {
+        enable(payload);
+    }
+}
+
+unsafe extern "C" fn text_input_handle_leave(
+    data: *mut std::ffi::c_void,
+    _text_input: *mut zwp_text_input_v3,
+    _surface: *mut wl_surface,
+) {
+    let payload: &mut WaylandPayload = &mut *(data as *mut _);
+    payload.text_input.entered = false;
+    if payload.text_input.on {
+        disable(payload);
+    }
+}
+
+unsafe extern "C" fn text_input_handle_preedit_string(
+    data: *mut std::ffi::c_void,
+    _text_input: *mut zwp_text_input_v3,
+    text: *const std::os::raw::c_char,
+    cursor_begin: i32,
+    _cursor_end: i32,
+) {
+    let payload: &mut WaylandPayload = &mut *(data as *mut _);
+    payload.text_input.preedit = (!text.is_null()).then(|| {
+        let text = std::ffi::CStr::from_ptr(text)
+            .to_string_lossy()
+            .into_owned();
+        // -1 is a hidden cursor, at the end then
+        let cursor = if cursor_begin >= 0 {
+            (cursor_begin as usize).min(text.len())
+        } else {
+            text.len()
+        };
+        (text, cursor)
+    });
+}
+
+unsafe extern "C" fn text_input_handle_commit_string(
+    data: *mut std::ffi::c_void,
+    _text_input: *mut zwp_text_input_v3,
+    text: *const std::os::raw::c_char,
+) {
+    let payload: &mut WaylandPayload = &mut *(data as *mut _);
+    payload.text_input.commit = (!text.is_null()).then(|| {
+        std::ffi::CStr::from_ptr(text)
+            .to_string_lossy()
+            .into_owned()
+    });
+}
+
+// miniquad has no surrounding text to delete from
+unsafe extern "C" fn text_input_handle_delete_surrounding_text(
+    _data: *mut std::ffi::c_void,
+    _text_input: *mut zwp_text_input_v3,
+    _before_length: u32,
+    _after_length: u32,
+) {
+}
+
+unsafe extern "C" fn text_input_handle_done(
+    data: *mut std::ffi::c_void,
+    _text_input: *mut zwp_text_input_v3,
+    _serial: u32,
+) {
+    let payload: &mut WaylandPayload = &mut *(data as *mut _);
+    let text_input = &mut payload.text_input;
+    if let Some(text) = text_input.commit.take() {
+        text_input.end_composition();
+        EVENTS.push(WaylandEvent::ImeCommit(text));
+    }
+    match text_input.preedit.take() {
+        Some((text, cursor)) if !text.is_empty() => {
+            text_input.composing = true;
+            EVENTS.push(WaylandEvent::ImeComposition(text, cursor));
+        }
+        _ => text_input.end_composition(),
+    }
+}
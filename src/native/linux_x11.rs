@@ -1,17 +1,23 @@
 // Spiritual successor of an X11 part of https://github.com/floooh/sokol/blob/master/sokol_app.h
 
 mod clipboard;
+mod drag_n_drop;
 mod glx;
 mod keycodes;
 pub mod libx11;
 mod libx11_ex;
 mod x_cursor;
 mod xi_input;
+mod xim;
+mod xrandr;
 
 use crate::{
-    event::EventHandler,
-    native::{egl, gl, NativeDisplayData, Request},
-    CursorIcon,
+    event::{EventHandler, ScrollDelta},
+    native::{
+        egl, event_wait::EventWait, frame_limiter::FrameLimiter, gl, scancodes, NativeDisplayData,
+        Request, SizeConstraints,
+    },
+    CursorGrabMode, CursorIcon, WindowState,
 };
 
 use libx11::*;
@@ -27,62 +33,161 @@ pub struct X11Display {
     repeated_keycodes: [bool; 256],
     empty_cursor: libx11::Cursor,
     cursor_cache: HashMap<CursorIcon, libx11::Cursor>,
+    // loaded lazily, only needed for custom cursors
+    libxcursor: Option<x_cursor::LibXcursor>,
+    custom_cursor: Option<libx11::Cursor>,
+    // the last cursor set by the user, hidden while the grab is Locked
+    cursor: libx11::Cursor,
+    cursor_grab: CursorGrabMode,
+    dnd: drag_n_drop::Dnd,
+    libxrandr: Option<xrandr::LibXrandr>,
+    // the size hints of a not resizable window pin it to its conf size
+    window_resizable: bool,
+    // None without an input method
+    xim: Option<xim::Xim>,
+    // window::set_text_input_enabled
+    text_input: bool,
+    focused: bool,
+    // fully obscured or unmapped, from VisibilityNotify and UnmapNotify
+    obscured: bool,
+    gamepads: crate::native::evdev::Gamepads,
+    // window::system_theme and set_keep_screen_on, None without D-Bus
+    session_bus: Option<crate::native::dbus::SessionBus>,
+    power_supply: crate::native::power_supply::PowerSupply,
 }
 
 impl X11Display {
+    #[cfg(feature = "raw-window-handle")]
+    unsafe fn window_handle(&self) -> crate::native::WindowHandle {
+        crate::native::WindowHandle::xlib(
+            self.display as _,
+            (*(self.display as _XPrivDisplay)).default_screen,
+            self.window,
+        )
+    }
+
+    // the text of a KeyPress
+    unsafe fn char_events(
+        &mut self,
+        event: &mut XKeyEvent,
+        mods: crate::KeyMods,
+        repeat: bool,
+        event_handler: &mut dyn EventHandler,
+    ) {
+        let mut keysym: KeySym = 0;
+        // the text of the dead keys and compose sequences, with an input method
+        let mut text = String::new();
+        match self.xim {
+            Some(ref mut xim) => (text, keysym) = xim.lookup_string(event),
+            None => {
+                (self.libx11.XLookupString)(
+                    event,
+                    std::ptr::null_mut(),
+                    0 as libc::c_int,
+                    &mut keysym,
+                    std::ptr::null_mut(),
+                );
+            }
+        }
+        let chr = keycodes::keysym_to_unicode(keysym);
+        if chr > 0 {
+            if let Some(chr) = std::char::from_u32(chr as u32) {
+                event_handler.char_event(chr, mods, repeat);
+            }
+        } else {
+            for chr in text.chars().filter(|chr| !chr.is_control()) {
+                event_handler.char_event(chr, mods, repeat);
+            }
+        }
+    }
+
     unsafe fn process_event(&mut self, event: &mut XEvent, event_handler: &mut dyn EventHandler) {
+        // KeyPress to LeaveNotify, the time is at the same place in all of them
+        if matches!((*event).type_0, 2..=8) {
+            crate::native::update_event_timestamp_ms((*event).xkey.time as u32);
+        }
+        // with the text input off the keys do not go to the input method
+        let key_event = matches!((*event).type_0, 2 | 3);
+        match self.xim {
+            Some(ref mut xim) if self.text_input || !key_event => {
+                // the preedit callbacks are called from XFilterEvent
+                let filtered = xim.filter_event(event);
+                if let Some((text, cursor)) = xim.take_composition() {
+                    event_handler.ime_composition_event(&text, cursor);
+                }
+                if filtered {
+                    return;
+                }
+            }
+            _ => {}
+        }
         match (*event).type_0 {
+            // a KeyPress the input method made up for the text it committed
+            2 if (*event).xkey.keycode == 0 => {
+                if let Some(ref mut xim) = self.xim {
+                    let (text, _) = xim.lookup_string(&mut (*event).xkey);
+                    if self.text_input && !text.is_empty() {
+                        event_handler.ime_commit_event(&text);
+                    }
+                }
+            }
             2 => {
                 let keycode = (*event).xkey.keycode as libc::c_int;
                 let key = keycodes::translate_key(&mut self.libx11, self.display, keycode);
                 let repeat = self.repeated_keycodes[(keycode & 0xff) as usize];
                 self.repeated_keycodes[(keycode & 0xff) as usize] = true;
                 let mods = keycodes::translate_mod((*event).xkey.state as libc::c_int);
-                let mut keysym: KeySym = 0;
-                (self.libx11.XLookupString)(
-                    &mut (*event).xkey,
-                    std::ptr::null_mut(),
-                    0 as libc::c_int,
-                    &mut keysym,
-                    std::ptr::null_mut(),
-                );
-                let chr = keycodes::keysym_to_unicode(keysym);
-                if chr > 0 {
-                    if let Some(chr) = std::char::from_u32(chr as u32) {
-                        event_handler.char_event(chr, mods, repeat);
-                    }
+                // with the evdev driver, the keycodes are the evdev ones plus 8
+                let scancode = scancodes::from_evdev((keycode as u32).wrapping_sub(8));
+                crate::native::update_keymods(keycodes::mods_after_key(mods, key, true));
+                crate::native::key_down(key, scancode, mods, repeat, event_handler);
+                if self.text_input {
+                    self.char_events(&mut (*event).xkey, mods, repeat, event_handler);
+                }
+            }
+            // FocusIn and FocusOut
+            9 | 10 => {
+                self.focused = (*event).type_0 == 9;
+                if !self.focused {
+                    self.repeated_keycodes = [false; 256];
+                }
+                crate::native::update_focus(self.focused, event_handler);
+                if let Some(ref mut xim) = self.xim {
+                    xim.set_focus(self.focused && self.text_input);
                 }
-                event_handler.key_down_event(key, mods, repeat);
             }
             3 => {
                 let keycode = (*event).xkey.keycode;
                 let key = keycodes::translate_key(&mut self.libx11, self.display, keycode as _);
                 self.repeated_keycodes[(keycode & 0xff) as usize] = false;
                 let mods = keycodes::translate_mod((*event).xkey.state as libc::c_int);
-                event_handler.key_up_event(key, mods);
+                let scancode = scancodes::from_evdev(keycode.wrapping_sub(8));
+                crate::native::update_keymods(keycodes::mods_after_key(mods, key, false));
+                crate::native::key_up(key, scancode, mods, event_handler);
             }
             4 => {
                 let btn = keycodes::translate_mouse_button((*event).xbutton.button as _);
                 let x = (*event).xmotion.x as libc::c_float;
                 let y = (*event).xmotion.y as libc::c_float;
+                let mods = keycodes::translate_mod((*event).xbutton.state as libc::c_int);
+                crate::native::update_keymods(mods);
 
                 if btn != crate::event::MouseButton::Unknown {
-                    event_handler.mouse_button_down_event(btn, x, y);
+                    event_handler.mouse_button_down_keymods_event(btn, x, y, mods);
                 } else {
-                    match (*event).xbutton.button {
-                        4 => {
-                            event_handler.mouse_wheel_event(0.0, 1.0);
-                        }
-                        5 => {
-                            event_handler.mouse_wheel_event(0.0, -1.0);
-                        }
-                        6 => {
-                            event_handler.mouse_wheel_event(1.0, 0.0);
-                        }
-                        7 => {
-                            event_handler.mouse_wheel_event(-1.0, 0.0);
-                        }
-                        _ => {}
+                    let wheel = match (*event).xbutton.button {
+                        4 => Some((0.0, 1.0)),
+                        5 => Some((0.0, -1.0)),
+                        6 => Some((1.0, 0.0)),
+                        7 => Some((-1.0, 0.0)),
+                        _ => None,
+                    };
+                    // a click per notch, no smooth scrolling without XInput2
+                    if let Some((dx, dy)) = wheel {
+                        event_handler.mouse_wheel_keymods_event(dx, dy, mods);
+                        let delta = ScrollDelta::Lines(dx, dy);
+                        event_handler.mouse_scroll_event(delta, mods);
+                        crate::native::wheel_magnify(delta, mods, event_handler);
                     }
                 }
             }
@@ -90,43 +195,65 @@ impl X11Display {
                 let btn = keycodes::translate_mouse_button((*event).xbutton.button as _);
                 let x = (*event).xmotion.x as libc::c_float;
                 let y = (*event).xmotion.y as libc::c_float;
+                let mods = keycodes::translate_mod((*event).xbutton.state as libc::c_int);
+                crate::native::update_keymods(mods);
 
                 if btn != crate::event::MouseButton::Unknown {
-                    event_handler.mouse_button_up_event(btn, x, y);
+                    event_handler.mouse_button_up_keymods_event(btn, x, y, mods);
                 }
             }
-            7 => {
-                // Mouse Enter
-            }
-            8 => {
-                // Mouse Leave
-            }
+            // EnterNotify and LeaveNotify, in pairs, also around the grabs
+            7 => event_handler.mouse_entered_event(),
+            8 => event_handler.mouse_left_event(),
             6 => {
+                if self.cursor_grab == CursorGrabMode::Locked {
+                    // keep the pointer away from the window borders,
+                    // XI_RawMotion deltas are delivered regardless
+                    self.warp_to_center((*event).xmotion.x, (*event).xmotion.y);
+                    return;
+                }
                 let x = (*event).xmotion.x as libc::c_float;
                 let y = (*event).xmotion.y as libc::c_float;
-                event_handler.mouse_motion_event(x, y);
+                let mods = keycodes::translate_mod((*event).xmotion.state as libc::c_int);
+                crate::native::update_keymods(mods);
+                event_handler.mouse_motion_keymods_event(x, y, mods);
+            }
+            // PropertyNotify
+            28 if (*event).xproperty.atom == self.libx11.extensions.net_wm_state => {
+                let state = self.read_window_state((*event).xproperty.window);
+                crate::native::update_window_state(state, event_handler);
+                self.update_occluded(event_handler);
             }
-            9 => {
-                event_handler.window_restored_event();
+            // VisibilityNotify, VisibilityFullyObscured is 2
+            15 => {
+                self.obscured = (*event).xvisibility.state == 2;
+                self.update_occluded(event_handler);
             }
-            10 => {
-                event_handler.window_minimized_event();
+            // UnmapNotify, the VisibilityNotify of the next map tells the rest
+            18 => {
+                self.obscured = true;
+                self.update_occluded(event_handler);
             }
             22 => {
-                let mut d = crate::native_display().try_lock().unwrap();
-                if (*event).xconfigure.width != d.screen_width
-                    || (*event).xconfigure.height != d.screen_height
-                {
-                    let width = (*event).xconfigure.width;
-                    let height = (*event).xconfigure.height;
-                    d.screen_width = width;
-                    d.screen_height = height;
-                    drop(d);
+                let width = (*event).xconfigure.width;
+                let height = (*event).xconfigure.height;
+                let mut d = crate::native_display().lock().unwrap();
+                let resized = width != d.screen_width || height != d.screen_height;
+                d.screen_width = width;
+                d.screen_height = height;
+                drop(d);
+                // moved or resized, either could change the monitor
+                let (x, y) = self.update_current_monitor();
+                if resized {
                     event_handler.resize_event(width as _, height as _);
                 }
+                crate::native::update_window_position((x as _, y as _), event_handler);
+            }
+            33 if (*event).xclient.message_type != self.libx11.extensions.wm_protocols => {
+                self.dnd_client_message(&(*event).xclient, event_handler);
             }
             33 => {
-                let mut d = crate::native_display().try_lock().unwrap();
+                let mut d = crate::native_display().lock().unwrap();
                 if (*event).xclient.message_type == self.libx11.extensions.wm_protocols {
                     let protocol = (*event).xclient.data.l[0 as libc::c_int as usize] as Atom;
                     if protocol == self.libx11.extensions.wm_delete_window {
@@ -141,6 +268,8 @@ impl X11Display {
                 // // only UTF8_STRING request is actually supported
                 clipboard::respond_to_clipboard_request(&mut self.libx11, self.display, event);
             }
+            // SelectionNotify
+            31 => self.dnd_selection_notify(&(*event).xselection, event_handler),
             // SelectionClear
             29 => {}
             17 => {}
@@ -150,27 +279,109 @@ impl X11Display {
                 if (*event).xcookie.evtype == xi_input::XI_RawMotion {
                     let (dx, dy) = self.libxi.read_cookie(&mut (*event).xcookie, self.display);
                     event_handler.raw_mouse_motion(dx as f32, dy as f32);
+                } else if let Some((x, y, pen)) = self
+                    .libxi
+                    .read_pen_cookie(&mut (*event).xcookie, self.display)
+                {
+                    event_handler.pen_event(x, y, pen);
                 }
             }
+            ty if self
+                .libxrandr
+                .as_ref()
+                .map_or(false, |libxrandr| libxrandr.is_screen_change_event(ty)) =>
+            {
+                self.update_monitors();
+            }
             _ => {}
         };
 
-        let mut d = crate::native_display().try_lock().unwrap();
-        if d.quit_requested && !d.quit_ordered {
-            drop(d);
-            event_handler.quit_requested_event();
-            let mut d = crate::native_display().try_lock().unwrap();
-            if d.quit_requested {
-                d.quit_ordered = true
-            }
+        crate::native::handle_quit_request(event_handler);
+    }
+
+    /// Query the monitors list again, on start and after RandR changes.
+    unsafe fn update_monitors(&mut self) {
+        let mut d = crate::native_display().lock().unwrap();
+        d.monitors = match self.libxrandr {
+            Some(ref libxrandr) => libxrandr.monitors(self.display, self.root, d.dpi_scale),
+            None => vec![],
+        };
+        drop(d);
+        self.update_current_monitor();
+    }
+
+    /// Returns the window position, relative to the root window.
+    unsafe fn update_current_monitor(&mut self) -> (i32, i32) {
+        let mut x = 0;
+        let mut y = 0;
+        let mut child = 0;
+        (self.libx11.XTranslateCoordinates)(
+            self.display,
+            self.window,
+            self.root,
+            0,
+            0,
+            &mut x,
+            &mut y,
+            &mut child,
+        );
+        let mut d = crate::native_display().lock().unwrap();
+        let size = (d.screen_width, d.screen_height);
+        d.current_monitor = crate::native::overlapping_monitor(&d.monitors, (x, y), size);
+        (x, y)
+    }
+
+    /// _NET_WM_ICON with all the given (width, height, rgba) images,
+    /// the WM picks the best fitting size.
+    unsafe fn set_icon(&mut self, window: Window, images: &[(u32, u32, &[u8])]) {
+        let net_wm_icon = (self.libx11.XInternAtom)(
+            self.display,
+            b"_NET_WM_ICON\x00" as *const u8 as *const _,
+            false as _,
+        );
+
+        // format 32 properties are arrays of longs, even on 64 bit platforms
+        let mut data: Vec<libc::c_ulong> = vec![];
+        for (width, height, rgba) in images {
+            data.push(*width as _);
+            data.push(*height as _);
+            data.extend(rgba.chunks_exact(4).map(|pixel| {
+                let [r, g, b, a] = [pixel[0], pixel[1], pixel[2], pixel[3]];
+                u32::from_be_bytes([a, r, g, b]) as libc::c_ulong
+            }));
+        }
+        (self.libx11.XChangeProperty)(
+            self.display,
+            window,
+            net_wm_icon,
+            6 as _, // XA_CARDINAL
+            32,
+            PropModeReplace,
+            data.as_mut_ptr() as *mut _,
+            data.len() as _,
+        );
+        (self.libx11.XFlush)(self.display);
+    }
+
+    fn set_conf_icon(&mut self, icon: &Option<crate::conf::Icon>) {
+        if let Some(icon) = icon {
+            unsafe {
+                self.set_icon(
+                    self.window,
+                    &[
+                        (16, 16, &icon.small[..]),
+                        (32, 32, &icon.medium[..]),
+                        (64, 64, &icon.big[..]),
+                    ],
+                )
+            };
         }
     }
 
-    // TODO: right now it just exits early if fullscreen is false.
-    // should be able to able to go back from fullscreen to windowed instead
     unsafe fn set_fullscreen(&mut self, window: Window, fullscreen: bool) {
-        if !fullscreen {
-            return;
+        if let Some(ref mut libxrandr) = self.libxrandr {
+            // from set_exclusive_fullscreen, if any
+            libxrandr.restore();
         }
         let wm_state = (self.libx11.XInternAtom)(
             self.display,
@@ -186,7 +397,7 @@ impl X11Display {
         // this is the first method to make window fullscreen
         // hide it, change _NET_WM_STATE_FULLSCREEN property and than show it back
         // someone on stackoverflow mentioned that this is not working on ubuntu/unity though
-        {
+        if fullscreen {
             (self.libx11.XLowerWindow)(self.display, window);
             (self.libx11.XUnmapWindow)(self.display, window);
             (self.libx11.XSync)(self.display, false as _);
@@ -212,7 +423,8 @@ impl X11Display {
         {
             let mut data = [0isize; 5];
 
-            data[0] = 1;
+            // _NET_WM_STATE_ADD or _NET_WM_STATE_REMOVE
+            data[0] = fullscreen as isize;
             data[1] = wm_fullscreen as isize;
             data[2] = 0;
 
@@ -238,11 +450,265 @@ impl X11Display {
         }
     }
 
+    unsafe fn set_resizable(&mut self, window: Window, resizable: bool) {
+        self.window_resizable = resizable;
+        let constraints = crate::native_display().lock().unwrap().size_constraints;
+        self.set_size_constraints(window, constraints);
+    }
+
+    /// _MOTIF_WM_HINTS, there is nothing newer for it and all the WMs know it.
+    unsafe fn set_decorations(&mut self, window: Window, decorations: bool) {
+        let motif_wm_hints = (self.libx11.XInternAtom)(
+            self.display,
+            b"_MOTIF_WM_HINTS\x00" as *const u8 as *const _,
+            false as _,
+        );
+        // flags, functions, decorations, input mode and status,
+        // the flags are MWM_HINTS_DECORATIONS, only the decorations are set
+        let mut hints: [libc::c_long; 5] = [2, 0, decorations as _, 0, 0];
+        (self.libx11.XChangeProperty)(
+            self.display,
+            window,
+            motif_wm_hints,
+            motif_wm_hints,
+            32,
+            PropModeReplace,
+            hints.as_mut_ptr() as *mut _,
+            hints.len() as _,
+        );
+        (self.libx11.XFlush)(self.display);
+    }
+
+    unsafe fn set_always_on_top(&mut self, window: Window, always_on_top: bool) {
+        let wm_above = (self.libx11.XInternAtom)(
+            self.display,
+            b"_NET_WM_STATE_ABOVE\x00" as *const u8 as *const _,
+            false as _,
+        );
+        // _NET_WM_STATE_ADD or _NET_WM_STATE_REMOVE
+        let data = [always_on_top as isize, wm_above as isize, 0, 1, 0];
+        let wm_state = self.libx11.extensions.net_wm_state;
+        self.send_wm_message(window, wm_state, data);
+    }
+
+    /// Cleared by the window manager when the window is focused.
+    unsafe fn request_user_attention(&mut self, window: Window) {
+        if self.focused {
+            return;
+        }
+        let wm_demands_attention = (self.libx11.XInternAtom)(
+            self.display,
+            b"_NET_WM_STATE_DEMANDS_ATTENTION\x00" as *const u8 as *const _,
+            false as _,
+        );
+        // _NET_WM_STATE_ADD
+        let data = [1, wm_demands_attention as isize, 0, 1, 0];
+        let wm_state = self.libx11.extensions.net_wm_state;
+        self.send_wm_message(window, wm_state, data);
+    }
+
+    unsafe fn set_opacity(&mut self, window: Window, opacity: f32) {
+        let wm_opacity = (self.libx11.XInternAtom)(
+            self.display,
+            b"_NET_WM_WINDOW_OPACITY\x00" as *const u8 as *const _,
+            false as _,
+        );
+        // the compositor reads it as a fraction of 0xffffffff
+        let mut opacity = (opacity as f64 * u32::MAX as f64) as libc::c_ulong;
+        (self.libx11.XChangeProperty)(
+            self.display,
+            window,
+            wm_opacity,
+            6 as _, // XA_CARDINAL
+            32,
+            PropModeReplace,
+            &mut opacity as *mut _ as *mut _,
+            1,
+        );
+        (self.libx11.XFlush)(self.display);
+    }
+
+    /// A compositing window manager never says the window is obscured, but
+    /// a minimized window is occluded anyway.
+    fn update_occluded(&mut self, event_handler: &mut dyn EventHandler) {
+        let minimized = crate::window::window_state() == crate::WindowState::Minimized;
+        crate::native::update_occluded(self.obscured || minimized, event_handler);
+    }
+
+    /// From _NET_WM_STATE, set by the window manager.
+    unsafe fn read_window_state(&mut self, window: Window) -> WindowState {
+        let mut actual_type = 0;
+        let mut actual_format = 0;
+        let mut count = 0;
+        let mut bytes_after = 0;
+        let mut atoms: *mut libc::c_uchar = std::ptr::null_mut();
+        (self.libx11.XGetWindowProperty)(
+            self.display,
+            window,
+            self.libx11.extensions.net_wm_state,
+            0,
+            libc::c_long::MAX,
+            false as _,
+            4 as Atom, // XA_ATOM
+            &mut actual_type,
+            &mut actual_format,
+            &mut count,
+            &mut bytes_after,
+            &mut atoms,
+        );
+        if atoms.is_null() {
+            return WindowState::Normal;
+        }
+        let atoms = std::slice::from_raw_parts(atoms as *const Atom, count as _);
+        let extensions = &self.libx11.extensions;
+        let state = if atoms.contains(&extensions.net_wm_state_hidden) {
+            WindowState::Minimized
+        } else if atoms.contains(&extensions.net_wm_state_maximized_vert)
+            && atoms.contains(&extensions.net_wm_state_maximized_horz)
+        {
+            WindowState::Maximized
+        } else {
+            WindowState::Normal
+        };
+        (self.libx11.XFree)(atoms.as_ptr() as _);
+        state
+    }
+
+    /// A client message for the window manager, about `window`.
+    unsafe fn send_wm_message(&mut self, window: Window, message_type: Atom, data: [isize; 5]) {
+        let mut ev = XClientMessageEvent {
+            type_0: 33,
+            serial: 0,
+            send_event: true as _,
+            message_type,
+            window,
+            display: self.display,
+            format: 32,
+            data: ClientMessageData {
+                l: std::mem::transmute(data),
+            },
+        };
+        // SubstructureRedirectMask | SubstructureNotifyMask
+        (self.libx11.XSendEvent)(
+            self.display as _,
+            self.root,
+            false as _,
+            (1048576 | 524288) as _,
+            &mut ev as *mut XClientMessageEvent as *mut _,
+        );
+        (self.libx11.XFlush)(self.display);
+    }
+
+    unsafe fn set_window_state(&mut self, window: Window, state: WindowState) {
+        let extensions = self.libx11.extensions.clone();
+        let maximized = [
+            extensions.net_wm_state_maximized_vert as isize,
+            extensions.net_wm_state_maximized_horz as isize,
+        ];
+        let minimized =
+            crate::native_display().lock().unwrap().window_state == WindowState::Minimized;
+        match state {
+            WindowState::Maximized => {
+                if minimized {
+                    (self.libx11.XMapWindow)(self.display, window);
+                }
+                // _NET_WM_STATE_ADD
+                let data = [1, maximized[0], maximized[1], 1, 0];
+                self.send_wm_message(window, extensions.net_wm_state, data);
+            }
+            WindowState::Minimized => {
+                // what XIconifyWindow does, IconicState
+                let wm_change_state = (self.libx11.XInternAtom)(
+                    self.display,
+                    b"WM_CHANGE_STATE\x00" as *const u8 as *const _,
+                    false as _,
+                );
+                self.send_wm_message(window, wm_change_state, [3, 0, 0, 0, 0]);
+            }
+            // mapping brings it back the way it was, maybe maximized
+            WindowState::Normal if minimized => {
+                (self.libx11.XMapWindow)(self.display, window);
+                (self.libx11.XRaiseWindow)(self.display, window);
+                (self.libx11.XFlush)(self.display);
+            }
+            WindowState::Normal => {
+                // _NET_WM_STATE_REMOVE
+                let data = [0, maximized[0], maximized[1], 1, 0];
+                self.send_wm_message(window, extensions.net_wm_state, data);
+            }
+        }
+    }
+
+    unsafe fn set_exclusive_fullscreen(&mut self, monitor: usize, mode: crate::VideoMode) {
+        let position = crate::native_display()
+            .lock()
+            .unwrap()
+            .monitors
+            .get(monitor)
+            .map(|monitor| monitor.position);
+        let position = match position {
+            Some(position) => position,
+            None => return,
+        };
+        // the window manager makes the window fullscreen on its current monitor
+        self.set_window_position(self.window, position.0, position.1);
+        self.set_fullscreen(self.window, true);
+
+        let switched = match self.libxrandr {
+            Some(ref mut libxrandr) => {
+                libxrandr.set_video_mode(self.display, self.root, monitor, mode)
+            }
+            None => false,
+        };
+        if !switched {
+            eprintln!("Failed to switch to {:?}, the mode is unchanged", mode);
+        }
+    }
+
     unsafe fn set_window_size(&mut self, window: Window, new_width: i32, new_height: i32) {
         (self.libx11.XResizeWindow)(self.display, window, new_width, new_height);
         (self.libx11.XFlush)(self.display);
     }
 
+    unsafe fn set_window_position(&mut self, window: Window, new_x: i32, new_y: i32) {
+        (self.libx11.XMoveWindow)(self.display, window, new_x, new_y);
+        (self.libx11.XFlush)(self.display);
+    }
+
+    unsafe fn set_size_constraints(&mut self, window: Window, mut constraints: SizeConstraints) {
+        if !self.window_resizable {
+            let d = crate::native_display().lock().unwrap();
+            let size = (d.screen_width as u32, d.screen_height as u32);
+            constraints.min = Some(size);
+            constraints.max = Some(size);
+        }
+        // the hints are replaced as a whole, so the gravity from create_window again
+        let hints = (self.libx11.XAllocSizeHints)();
+        (*hints).flags = PWinGravity;
+        (*hints).win_gravity = StaticGravity;
+        if let Some((width, height)) = constraints.min {
+            (*hints).flags |= PMinSize;
+            (*hints).min_width = width as _;
+            (*hints).min_height = height as _;
+        }
+        if let Some((width, height)) = constraints.max {
+            (*hints).flags |= PMaxSize;
+            (*hints).max_width = width as _;
+            (*hints).max_height = height as _;
+        }
+        if let Some((x, y)) = constraints.aspect {
+            (*hints).flags |= PAspect;
+            (*hints).min_aspect = C2RustUnnamed_2 {
+                x: x as _,
+                y: y as _,
+            };
+            (*hints).max_aspect = (*hints).min_aspect;
+        }
+        (self.libx11.XSetWMNormalHints)(self.display, window, hints);
+        (self.libx11.XFree)(hints as *mut libc::c_void);
+        (self.libx11.XFlush)(self.display);
+    }
+
     fn show_mouse(&mut self, shown: bool) {
         unsafe {
             if shown {
@@ -253,10 +719,18 @@ impl X11Display {
         }
     }
 
-    pub unsafe fn set_cursor_grab(&mut self, window: Window, grab: bool) {
+    pub unsafe fn set_cursor_grab(&mut self, window: Window, mode: CursorGrabMode) {
         (self.libx11.XUngrabPointer)(self.display, 0);
 
-        if grab {
+        self.cursor_grab = mode;
+        if mode == CursorGrabMode::Locked {
+            (self.libx11.XDefineCursor)(self.display, window, self.empty_cursor);
+            self.warp_to_center(-1, -1);
+        } else {
+            (self.libx11.XDefineCursor)(self.display, window, self.cursor);
+        }
+
+        if mode != CursorGrabMode::None {
             (self.libx11.XGrabPointer)(
                 self.display,
                 window,
@@ -284,6 +758,30 @@ impl X11Display {
 
         (self.libx11.XFlush)(self.display);
     }
+
+    /// Move the pointer to the window center, unless it's already there.
+    unsafe fn warp_to_center(&mut self, x: i32, y: i32) {
+        let (width, height) = {
+            let d = crate::native_display().lock().unwrap();
+            (d.screen_width, d.screen_height)
+        };
+        let (center_x, center_y) = (width / 2, height / 2);
+        if (x, y) != (center_x, center_y) {
+            (self.libx11.XWarpPointer)(
+                self.display,
+                0,
+                self.window,
+                0,
+                0,
+                0,
+                0,
+                center_x,
+                center_y,
+            );
+            (self.libx11.XFlush)(self.display);
+        }
+    }
+
     pub unsafe fn set_cursor(&mut self, window: Window, cursor: Option<CursorIcon>) {
         let libx11 = &mut self.libx11;
         let display = self.display;
@@ -310,7 +808,54 @@ impl X11Display {
                 )
             }),
         };
-        (libx11.XDefineCursor)(display, window, cursor);
+        self.cursor = cursor;
+        if self.cursor_grab != CursorGrabMode::Locked {
+            (self.libx11.XDefineCursor)(display, window, cursor);
+        }
+    }
+
+    unsafe fn set_custom_cursor(
+        &mut self,
+        window: Window,
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+        hotspot: (u32, u32),
+    ) {
+        if self.libxcursor.is_none() {
+            self.libxcursor = x_cursor::LibXcursor::try_load();
+        }
+        let libxcursor = match &self.libxcursor {
+            Some(libxcursor) => libxcursor,
+            None => {
+                eprintln!("libXcursor is not available, custom cursors are not supported");
+                return;
+            }
+        };
+        let cursor = libxcursor.create_cursor(self.display, rgba, width, height, hotspot);
+        self.cursor = cursor;
+        if self.cursor_grab != CursorGrabMode::Locked {
+            (self.libx11.XDefineCursor)(self.display, window, cursor);
+        }
+        if let Some(old) = self.custom_cursor.replace(cursor) {
+            (self.libx11.XFreeCursor)(self.display, old);
+        }
+        (self.libx11.XFlush)(self.display);
+    }
+
+    /// With no events, waits for the next one unless a frame is due,
+    /// false is a frame to draw now.
+    unsafe fn wait_for_events(&mut self, event_wait: &EventWait) -> bool {
+        let timeout = crate::native_display().lock().unwrap().frame_timeout();
+        if timeout == Some(std::time::Duration::ZERO) {
+            return false;
+        }
+        // the event handlers could have read more events into the queue
+        if (self.libx11.XPending)(self.display) != 0 {
+            return true;
+        }
+        event_wait.wait((*(self.display as _XPrivDisplay)).fd, timeout);
+        true
     }
 
     fn process_request(&mut self, request: Request) {
@@ -324,10 +869,89 @@ impl X11Display {
                     new_width,
                     new_height,
                 } => self.set_window_size(self.window, new_width as _, new_height as _),
+                SetWindowPosition { new_x, new_y } => {
+                    self.set_window_position(self.window, new_x as _, new_y as _)
+                }
                 SetFullscreen(fullscreen) => self.set_fullscreen(self.window, fullscreen),
+                SetExclusiveFullscreen { monitor, mode } => {
+                    self.set_exclusive_fullscreen(monitor, mode)
+                }
+                // the gl context belongs to the main loop, it takes this one
+                SetSwapInterval(_) => {}
+                SetSizeConstraints(constraints) => {
+                    self.set_size_constraints(self.window, constraints)
+                }
+                SetWindowState(state) => self.set_window_state(self.window, state),
+                SetDecorations(decorations) => self.set_decorations(self.window, decorations),
+                SetResizable(resizable) => self.set_resizable(self.window, resizable),
+                SetAlwaysOnTop(always_on_top) => self.set_always_on_top(self.window, always_on_top),
+                SetOpacity(opacity) => self.set_opacity(self.window, opacity),
+                RequestUserAttention(_) => self.request_user_attention(self.window),
                 ShowKeyboard(show) => {
                     eprintln!("Not implemented for X11")
                 }
+                SetKeyboardType(_) => {}
+                LockOrientation(_) => {}
+                SetFullscreenImmersive(_) => {}
+                SetSystemBarsColor(_) => {}
+                SetMenus(_) => {}
+                SetDockMenu(_) => {}
+                SetTaskbarProgress(_) => {}
+                SetTaskbarOverlay(_) => {}
+                SetTextInputEnabled(enabled) => {
+                    self.text_input = enabled;
+                    if let Some(ref mut xim) = self.xim {
+                        xim.set_focus(self.focused && enabled);
+                    }
+                }
+                SetImeCursorArea {
+                    x,
+                    y,
+                    width,
+                    height,
+                } => {
+                    if let Some(ref mut xim) = self.xim {
+                        xim.set_cursor_area(x, y, width, height);
+                    }
+                }
+                SetWindowIcon {
+                    rgba,
+                    width,
+                    height,
+                } => self.set_icon(self.window, &[(width, height, &rgba)]),
+                SetCustomMouseCursor {
+                    rgba,
+                    width,
+                    height,
+                    hotspot,
+                } => self.set_custom_cursor(self.window, &rgba, width, height, hotspot),
+                SetMousePosition { x, y } => {
+                    (self.libx11.XWarpPointer)(
+                        self.display,
+                        0,
+                        self.window,
+                        0,
+                        0,
+                        0,
+                        0,
+                        x as _,
+                        y as _,
+                    );
+                    (self.libx11.XFlush)(self.display);
+                }
+                SetGamepadRumble {
+                    id,
+                    low_frequency,
+                    high_frequency,
+                    duration,
+                } => self
+                    .gamepads
+                    .rumble(id, low_frequency, high_frequency, duration),
+                SetKeepScreenOn(keep_on) => {
+                    if let Some(ref mut session_bus) = self.session_bus {
+                        session_bus.inhibit_screensaver(keep_on);
+                    }
+                }
             }
         }
     }
@@ -353,7 +977,8 @@ where
             .libx11
             .create_window(display.root, display.display, visual, depth, conf);
 
-    let (glx_context, glx_window) = glx.create_context(display.display, display.window);
+    let (glx_context, glx_window) =
+        glx.create_context(display.display, display.window, &conf.platform);
     glx.swap_interval(
         display.display,
         glx_window,
@@ -362,6 +987,12 @@ where
     );
     gl::load_gl_funcs(|proc| glx.libgl.get_procaddr(proc));
 
+    display.set_conf_icon(&conf.icon);
+    display.set_dnd_aware(display.window);
+    display.xim = xim::Xim::new(&display.libx11, display.display, display.window);
+    display
+        .libxi
+        .select_pen_events(&mut display.libx11, display.display, display.window);
     display.libx11.show_window(display.display, display.window);
 
     (display.libx11.XFlush)(display.display);
@@ -379,17 +1010,38 @@ where
     crate::set_display(NativeDisplayData {
         high_dpi: conf.high_dpi,
         dpi_scale: display.libx11.update_system_dpi(display.display),
+        #[cfg(feature = "raw-window-handle")]
+        window_handle: Some(display.window_handle()),
+        swap_control: glx.swap_control(),
+        adaptive_vsync: glx.adaptive_vsync(),
+        target_fps: conf.max_fps,
+        background_mode: conf.background_mode,
+        system_theme: display
+            .session_bus
+            .as_ref()
+            .map_or(crate::Theme::Light, |session_bus| session_bus.theme()),
+        power_info: crate::native::power_supply::power_info(),
         ..NativeDisplayData::new(w, h, tx, clipboard)
     });
+    display.update_monitors();
     if conf.fullscreen {
         display.set_fullscreen(display.window, true);
     }
 
     let mut event_handler = (f.take().unwrap())();
+    let mut frame_limiter = FrameLimiter::new();
+    let event_wait = conf.wait_for_events.then(|| EventWait::new());
 
-    while !crate::native_display().try_lock().unwrap().quit_ordered {
+    while !crate::native_display().lock().unwrap().quit_ordered {
         while let Ok(request) = rx.try_recv() {
-            display.process_request(request);
+            match request {
+                Request::SetSwapInterval(interval) => {
+                    if !glx.swap_interval(display.display, glx_window, glx_context, interval) {
+                        eprintln!("Failed to set swap interval {}", interval);
+                    }
+                }
+                request => display.process_request(request),
+            }
         }
         glx.make_current(display.display, glx_window, glx_context);
         let count = (display.libx11.XPending)(display.display);
@@ -399,15 +1051,38 @@ where
             (display.libx11.XNextEvent)(display.display, &mut xevent);
             display.process_event(&mut xevent, &mut *event_handler);
         }
+        crate::native::take_sigterm();
+        if let Some(ref event_wait) = event_wait {
+            if count == 0 && display.wait_for_events(event_wait) {
+                continue;
+            }
+        }
 
+        crate::native::handle_quit_request(&mut *event_handler);
+        display.gamepads.poll(&mut *event_handler);
+        display.power_supply.poll(&mut *event_handler);
+        if let Some(ref mut session_bus) = display.session_bus {
+            session_bus.poll(&mut *event_handler);
+        }
+        crate::native::dispatch_custom_events(&mut *event_handler);
+        if crate::native_display().lock().unwrap().frames_paused() {
+            frame_limiter.wait();
+            continue;
+        }
         event_handler.update();
         event_handler.draw();
 
         glx.swap_buffers(display.display, glx_window);
         (display.libx11.XFlush)(display.display);
+        frame_limiter.wait();
     }
 
+    // while the GL context is there for the Drop of the event handler
+    drop(event_handler);
     glx.destroy_context(display.display, glx_window, glx_context);
+    if let Some(ref mut libxrandr) = display.libxrandr {
+        libxrandr.restore();
+    }
     (display.libx11.XUnmapWindow)(display.display, display.window);
     (display.libx11.XDestroyWindow)(display.display, display.window);
     (display.libx11.XCloseDisplay)(display.display);
@@ -419,6 +1094,7 @@ unsafe fn egl_main_loop<F>(
     mut display: X11Display,
     conf: &crate::conf::Conf,
     f: &mut Option<F>,
+    screen: i32,
 ) -> Result<(), X11Display>
 where
     F: 'static + FnOnce() -> Box<dyn EventHandler>,
@@ -428,24 +1104,41 @@ where
         _ => return Err(display),
     };
 
+    let mut visual: *mut Visual = std::ptr::null_mut();
+    let mut depth = 0;
+    if conf.transparent {
+        // the default visual has no alpha, compositors blend only the 32 bit ones
+        let mut info: XVisualInfo = std::mem::zeroed();
+        let libx11 = &display.libx11;
+        if (libx11.XMatchVisualInfo)(display.display, screen, 32, TrueColor, &mut info) != 0 {
+            visual = info.visual;
+            depth = 32;
+        } else {
+            eprintln!("X11: No 32 bit visual, the window will not be transparent");
+        }
+    }
     display.window =
         display
             .libx11
-            .create_window(display.root, display.display, std::ptr::null_mut(), 0, conf);
+            .create_window(display.root, display.display, visual, depth, conf);
 
     let (context, config, egl_display) = egl::create_egl_context(
         &mut egl_lib,
         display.display as *mut _,
-        conf.platform.framebuffer_alpha,
+        conf.platform.framebuffer_alpha || conf.transparent,
         conf.sample_count,
+        conf.platform.gles_version,
+        conf.platform.gl_debug,
     )
     .unwrap();
 
+    let surface_attributes =
+        egl::window_surface_attributes(&egl_lib, egl_display, conf.platform.srgb_framebuffer);
     let egl_surface = (egl_lib.eglCreateWindowSurface.unwrap())(
         egl_display,
         config,
         display.window,
-        std::ptr::null_mut(),
+        surface_attributes.as_ptr() as _,
     );
 
     if egl_surface == /* EGL_NO_SURFACE  */ std::ptr::null_mut() {
@@ -454,6 +1147,8 @@ where
     if (egl_lib.eglMakeCurrent.unwrap())(egl_display, egl_surface, egl_surface, context) == 0 {
         panic!("eglMakeCurrent failed");
     }
+    let swap_interval = conf.platform.swap_interval.unwrap_or(1);
+    egl::swap_interval(&mut egl_lib, egl_display, swap_interval);
 
     crate::native::gl::load_gl_funcs(|proc| {
         let name = std::ffi::CString::new(proc).unwrap();
@@ -462,6 +1157,12 @@ where
             .expect("non-null function pointer")(name.as_ptr() as _)
     });
 
+    display.set_conf_icon(&conf.icon);
+    display.set_dnd_aware(display.window);
+    display.xim = xim::Xim::new(&display.libx11, display.display, display.window);
+    display
+        .libxi
+        .select_pen_events(&mut display.libx11, display.display, display.window);
     display.libx11.show_window(display.display, display.window);
     let (w, h) = display
         .libx11
@@ -476,8 +1177,20 @@ where
     crate::set_display(NativeDisplayData {
         high_dpi: conf.high_dpi,
         dpi_scale: display.libx11.update_system_dpi(display.display),
+        #[cfg(feature = "raw-window-handle")]
+        window_handle: Some(display.window_handle()),
+        swap_control: egl_lib.eglSwapInterval.is_some(),
+        target_fps: conf.max_fps,
+        background_mode: conf.background_mode,
+        system_theme: display
+            .session_bus
+            .as_ref()
+            .map_or(crate::Theme::Light, |session_bus| session_bus.theme()),
+        power_info: crate::native::power_supply::power_info(),
+        egl: true,
         ..NativeDisplayData::new(w, h, tx, clipboard)
     });
+    display.update_monitors();
     if conf.fullscreen {
         display.set_fullscreen(display.window, true)
     }
@@ -485,10 +1198,19 @@ where
     (display.libx11.XFlush)(display.display);
 
     let mut event_handler = (f.take().unwrap())();
+    let mut frame_limiter = FrameLimiter::new();
+    let event_wait = conf.wait_for_events.then(|| EventWait::new());
 
-    while !crate::native_display().try_lock().unwrap().quit_ordered {
+    while !crate::native_display().lock().unwrap().quit_ordered {
         while let Ok(request) = rx.try_recv() {
-            display.process_request(request);
+            match request {
+                Request::SetSwapInterval(interval) => {
+                    if !egl::swap_interval(&mut egl_lib, egl_display, interval) {
+                        eprintln!("Failed to set swap interval {}", interval);
+                    }
+                }
+                request => display.process_request(request),
+            }
         }
 
         let count = (display.libx11.XPending)(display.display);
@@ -498,14 +1220,36 @@ where
             (display.libx11.XNextEvent)(display.display, &mut xevent);
             display.process_event(&mut xevent, &mut *event_handler);
         }
+        crate::native::take_sigterm();
+        if let Some(ref event_wait) = event_wait {
+            if count == 0 && display.wait_for_events(event_wait) {
+                continue;
+            }
+        }
 
+        crate::native::handle_quit_request(&mut *event_handler);
+        display.gamepads.poll(&mut *event_handler);
+        display.power_supply.poll(&mut *event_handler);
+        if let Some(ref mut session_bus) = display.session_bus {
+            session_bus.poll(&mut *event_handler);
+        }
+        crate::native::dispatch_custom_events(&mut *event_handler);
+        if crate::native_display().lock().unwrap().frames_paused() {
+            frame_limiter.wait();
+            continue;
+        }
         event_handler.update();
         event_handler.draw();
 
         (egl_lib.eglSwapBuffers.unwrap())(egl_display, egl_surface);
         (display.libx11.XFlush)(display.display);
+        frame_limiter.wait();
     }
 
+    drop(event_handler);
+    if let Some(ref mut libxrandr) = display.libxrandr {
+        libxrandr.restore();
+    }
     (display.libx11.XUnmapWindow)(display.display, display.window);
     (display.libx11.XDestroyWindow)(display.display, display.window);
     (display.libx11.XCloseDisplay)(display.display);
@@ -549,6 +1293,7 @@ where
         libx11.load_extensions(x11_display);
         let mut display = X11Display {
             empty_cursor: x_cursor::create_empty_cursor(x11_display, x11_root, &mut libx11),
+            dnd: drag_n_drop::Dnd::new(&mut libx11, x11_display),
             display: x11_display,
             root: x11_root,
             window: 0,
@@ -556,26 +1301,46 @@ where
             libxi,
             repeated_keycodes: [false; 256],
             cursor_cache: HashMap::new(),
+            libxcursor: None,
+            custom_cursor: None,
+            cursor: 0,
+            cursor_grab: CursorGrabMode::None,
+            libxrandr: xrandr::LibXrandr::try_load()
+                .and_then(|libxrandr| libxrandr.init(x11_display, x11_root)),
+            window_resizable: conf.window_resizable,
+            xim: None,
+            text_input: true,
+            focused: false,
+            obscured: false,
+            gamepads: crate::native::evdev::Gamepads::new(),
+            session_bus: crate::native::dbus::SessionBus::new(),
+            power_supply: crate::native::power_supply::PowerSupply::new(),
         };
 
         display
             .libxi
             .query_xi_extension(&mut display.libx11, display.display);
 
-        match conf.platform.linux_x11_gl {
+        // MINIQUAD_X11_GL, the choice of the user over the one of the application
+        let x11_gl = match std::env::var("MINIQUAD_X11_GL").as_deref() {
+            Ok("glx") => &crate::conf::LinuxX11Gl::GLXOnly,
+            Ok("egl") => &crate::conf::LinuxX11Gl::EGLOnly,
+            _ => &conf.platform.linux_x11_gl,
+        };
+        match x11_gl {
             crate::conf::LinuxX11Gl::GLXOnly => {
                 glx_main_loop(display, &conf, f, x11_screen).ok().unwrap();
             }
             crate::conf::LinuxX11Gl::EGLOnly => {
-                egl_main_loop(display, &conf, f).ok().unwrap();
+                egl_main_loop(display, &conf, f, x11_screen).ok().unwrap();
             }
             crate::conf::LinuxX11Gl::GLXWithEGLFallback => {
                 if let Err(display) = glx_main_loop(display, &conf, f, x11_screen) {
-                    egl_main_loop(display, &conf, f).ok().unwrap();
+                    egl_main_loop(display, &conf, f, x11_screen).ok().unwrap();
                 }
             }
             crate::conf::LinuxX11Gl::EGLWithGLXFallback => {
-                if let Err(display) = egl_main_loop(display, &conf, f) {
+                if let Err(display) = egl_main_loop(display, &conf, f, x11_screen) {
                     glx_main_loop(display, &conf, f, x11_screen).ok().unwrap();
                 }
             }
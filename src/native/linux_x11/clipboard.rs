@@ -6,6 +6,8 @@
 
 use super::libx11::*;
 
+use std::convert::TryInto;
+
 const CurrentTime: libc::c_long = 0 as libc::c_long;
 const SelectionRequest: libc::c_int = 30 as libc::c_int;
 const SelectionNotify: libc::c_int = 31 as libc::c_int;
@@ -19,7 +21,7 @@ unsafe fn get_clipboard(
     window: Window,
     bufname: *const libc::c_char,
     fmtname: *const libc::c_char,
-) -> Option<String> {
+) -> Option<Vec<u8>> {
     let mut result = 0 as *mut libc::c_char;
     let mut ressize: libc::c_ulong = 0;
     let mut restail: libc::c_ulong = 0;
@@ -81,13 +83,20 @@ unsafe fn get_clipboard(
                 (libx11.XFree)(result as *mut libc::c_void);
                 panic!("Buffer is too large and INCR reading is not implemented yet.");
             } else {
-                let slice = std::slice::from_raw_parts(result as *const _, ressize as _);
+                // ressize is in items, and 32 bit items are longs
+                let item_size = match resbits {
+                    16 => std::mem::size_of::<libc::c_short>(),
+                    32 => std::mem::size_of::<libc::c_long>(),
+                    _ => 1,
+                };
+                let slice =
+                    std::slice::from_raw_parts(result as *const _, ressize as usize * item_size);
                 bytes.extend(slice);
 
                 (libx11.XFree)(result as *mut libc::c_void);
 
                 if restail == 0 {
-                    return std::str::from_utf8(&bytes[..]).map(|s| s.to_owned()).ok();
+                    return Some(bytes);
                 } else {
                     offset += read_size;
                 }
@@ -100,6 +109,8 @@ unsafe fn get_clipboard(
 
 // Next message for clipboard request
 static mut MESSAGE: Option<String> = None;
+// text/html, along with the MESSAGE
+static mut HTML: Option<String> = None;
 
 /// Claim that our app is X11 clipboard owner
 /// Now when some other linux app will ask X11 for clipboard content - it will be redirected to our app
@@ -109,6 +120,7 @@ unsafe fn claim_clipboard_ownership(
     window: Window,
     bufname: *const libc::c_char,
     message: String,
+    html: Option<String>,
 ) {
     let selection = (libx11.XInternAtom)(
         display,
@@ -119,6 +131,7 @@ unsafe fn claim_clipboard_ownership(
     (libx11.XSetSelectionOwner)(display, selection, window, 0 as libc::c_int as Time);
 
     MESSAGE = Some(message);
+    HTML = html;
 }
 
 /// this function is supposed to be called from sapp's event loop
@@ -139,6 +152,16 @@ pub(crate) unsafe fn respond_to_clipboard_request(
         b"UTF8_STRING\x00" as *const u8 as *const libc::c_char,
         1 as libc::c_int,
     );
+    let TARGETS = (libx11.XInternAtom)(
+        display,
+        b"TARGETS\x00" as *const u8 as *const libc::c_char,
+        0 as libc::c_int,
+    );
+    let HTML_ATOM = (libx11.XInternAtom)(
+        display,
+        b"text/html\x00" as *const u8 as *const libc::c_char,
+        0 as libc::c_int,
+    );
     let xselectionrequest = (*event).xselectionrequest;
     let mut ev = XSelectionEvent {
         type_0: super::clipboard::SelectionNotify,
@@ -152,17 +175,46 @@ pub(crate) unsafe fn respond_to_clipboard_request(
         time: xselectionrequest.time,
     };
 
-    // only UTF8 requests are supported
-    if xselectionrequest.target == UTF8 {
+    let response = match &HTML {
+        Some(html) if xselectionrequest.target == HTML_ATOM => Some((HTML_ATOM, html)),
+        _ if xselectionrequest.target == UTF8 => Some((UTF8, message)),
+        _ => None,
+    };
+
+    // applications ask for the TARGETS first to pick the best format
+    if xselectionrequest.target == TARGETS {
+        let mut targets = vec![TARGETS, UTF8];
+        if HTML.is_some() {
+            targets.push(HTML_ATOM);
+        }
         (libx11.XChangeProperty)(
             xselectionrequest.display,
             xselectionrequest.requestor,
             xselectionrequest.property,
-            UTF8,
+            4 as Atom, // XA_ATOM
+            32 as libc::c_int,
+            PropModeReplace,
+            targets.as_ptr() as *const _,
+            targets.len() as _,
+        );
+
+        (libx11.XSendEvent)(
+            display,
+            ev.requestor,
+            0 as libc::c_int,
+            0 as libc::c_int as libc::c_long,
+            &mut ev as *mut XSelectionEvent as *mut XEvent,
+        );
+    } else if let Some((target, data)) = response {
+        (libx11.XChangeProperty)(
+            xselectionrequest.display,
+            xselectionrequest.requestor,
+            xselectionrequest.property,
+            target,
             8 as libc::c_int,
             PropModeReplace,
-            message.as_bytes().as_ptr() as *const u8 as *const _,
-            message.as_bytes().len() as _,
+            data.as_bytes().as_ptr() as *const u8 as *const _,
+            data.as_bytes().len() as _,
         );
 
         (libx11.XSendEvent)(
@@ -203,12 +255,12 @@ impl X11Clipboard {
         }
     }
 }
-impl crate::native::Clipboard for X11Clipboard {
-    fn get(&mut self) -> Option<String> {
+impl X11Clipboard {
+    fn get_format(&mut self, format: &str) -> Option<Vec<u8>> {
         use std::ffi::CString;
 
         let bufname = CString::new("CLIPBOARD").unwrap();
-        let fmtname = CString::new("UTF8_STRING").unwrap();
+        let fmtname = CString::new(format).unwrap();
 
         unsafe {
             get_clipboard(
@@ -221,7 +273,7 @@ impl crate::native::Clipboard for X11Clipboard {
         }
     }
 
-    fn set(&mut self, data: &str) {
+    fn claim(&mut self, message: String, html: Option<String>) {
         use std::ffi::CString;
 
         let bufname = CString::new("CLIPBOARD").unwrap();
@@ -232,8 +284,55 @@ impl crate::native::Clipboard for X11Clipboard {
                 self.display,
                 self.window,
                 bufname.as_ptr(),
-                data.to_owned(),
+                message,
+                html,
             );
         };
     }
 }
+
+impl crate::native::Clipboard for X11Clipboard {
+    fn get(&mut self) -> Option<String> {
+        let bytes = self.get_format("UTF8_STRING")?;
+        String::from_utf8(bytes).ok()
+    }
+
+    fn set(&mut self, data: &str) {
+        self.claim(data.to_owned(), None);
+    }
+
+    fn has(&mut self, format: crate::ClipboardFormat) -> bool {
+        let name = match format {
+            crate::ClipboardFormat::Text => "UTF8_STRING",
+            crate::ClipboardFormat::Html => "text/html",
+            // would be "image/png", but there is no png decoder in miniquad
+            crate::ClipboardFormat::Image => return false,
+        };
+        let targets = match self.get_format("TARGETS") {
+            Some(targets) => targets,
+            None => return false,
+        };
+        let name = std::ffi::CString::new(name).unwrap();
+        let atom = unsafe { (self.libx11.XInternAtom)(self.display, name.as_ptr(), false as _) };
+        targets
+            .chunks_exact(std::mem::size_of::<Atom>())
+            .any(|target| Atom::from_ne_bytes(target.try_into().unwrap()) == atom)
+    }
+
+    fn get_html(&mut self) -> Option<String> {
+        let bytes = self.get_format("text/html")?;
+        // firefox puts UTF-16 html to the clipboard
+        if let [0xff, 0xfe, utf16 @ ..] = &bytes[..] {
+            let utf16: Vec<u16> = utf16
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            return String::from_utf16(&utf16).ok();
+        }
+        String::from_utf8(bytes).ok()
+    }
+
+    fn set_html(&mut self, html: &str, text: &str) {
+        self.claim(text.to_owned(), Some(html.to_owned()));
+    }
+}
@@ -0,0 +1,223 @@
+//! XDND, files drag and drop from other X11 applications.
+//! https://www.freedesktop.org/wiki/Specifications/XDND/
+//! Only "text/uri-list" is accepted, that is what file managers offer.
+
+use super::*;
+
+const XDND_VERSION: libc::c_long = 5;
+const XA_ATOM: Atom = 4;
+const ANY_PROPERTY_TYPE: Atom = 0;
+
+pub struct Dnd {
+    aware: Atom,
+    enter: Atom,
+    position: Atom,
+    status: Atom,
+    leave: Atom,
+    drop: Atom,
+    finished: Atom,
+    selection: Atom,
+    type_list: Atom,
+    action_copy: Atom,
+    uri_list: Atom,
+    // the window the drag comes from
+    source: Window,
+    version: libc::c_long,
+    accepted: bool,
+}
+
+impl Dnd {
+    pub unsafe fn new(libx11: &mut LibX11, display: *mut Display) -> Dnd {
+        let mut atom = |name: &[u8]| (libx11.XInternAtom)(display, name.as_ptr() as _, false as _);
+        Dnd {
+            aware: atom(b"XdndAware\0"),
+            enter: atom(b"XdndEnter\0"),
+            position: atom(b"XdndPosition\0"),
+            status: atom(b"XdndStatus\0"),
+            leave: atom(b"XdndLeave\0"),
+            drop: atom(b"XdndDrop\0"),
+            finished: atom(b"XdndFinished\0"),
+            selection: atom(b"XdndSelection\0"),
+            type_list: atom(b"XdndTypeList\0"),
+            action_copy: atom(b"XdndActionCopy\0"),
+            uri_list: atom(b"text/uri-list\0"),
+            source: 0,
+            version: 0,
+            accepted: false,
+        }
+    }
+}
+
+impl X11Display {
+    /// Tell drag sources that the window takes XDND messages.
+    pub(super) unsafe fn set_dnd_aware(&mut self, window: Window) {
+        let mut version = XDND_VERSION as libc::c_ulong;
+        (self.libx11.XChangeProperty)(
+            self.display,
+            window,
+            self.dnd.aware,
+            XA_ATOM,
+            32,
+            PropModeReplace,
+            &mut version as *mut _ as *mut _,
+            1,
+        );
+    }
+
+    unsafe fn send_dnd_message(&mut self, message_type: Atom, data: [libc::c_long; 5]) {
+        let mut ev = XClientMessageEvent {
+            type_0: 33,
+            serial: 0,
+            send_event: true as _,
+            display: self.display,
+            window: self.dnd.source,
+            message_type,
+            format: 32,
+            data: ClientMessageData { l: data },
+        };
+        (self.libx11.XSendEvent)(
+            self.display,
+            self.dnd.source,
+            false as _,
+            0, // NoEventMask
+            &mut ev as *mut XClientMessageEvent as *mut _,
+        );
+        (self.libx11.XFlush)(self.display);
+    }
+
+    /// Whether the source offers "text/uri-list", with more than three
+    /// types offered the list is in the XdndTypeList property.
+    unsafe fn dnd_offers_uri_list(&mut self, data: &[libc::c_long; 5]) -> bool {
+        if data[1] & 1 == 0 {
+            return data[2..].iter().any(|&ty| ty as Atom == self.dnd.uri_list);
+        }
+
+        let mut actual_type = 0;
+        let mut actual_format = 0;
+        let mut count = 0;
+        let mut bytes_after = 0;
+        let mut types: *mut libc::c_uchar = std::ptr::null_mut();
+        (self.libx11.XGetWindowProperty)(
+            self.display,
+            self.dnd.source,
+            self.dnd.type_list,
+            0,
+            libc::c_long::MAX,
+            false as _,
+            XA_ATOM,
+            &mut actual_type,
+            &mut actual_format,
+            &mut count,
+            &mut bytes_after,
+            &mut types,
+        );
+        if types.is_null() {
+            return false;
+        }
+        let offers_uri_list = std::slice::from_raw_parts(types as *const Atom, count as _)
+            .contains(&self.dnd.uri_list);
+        (self.libx11.XFree)(types as _);
+        offers_uri_list
+    }
+
+    /// XdndEnter/Position/Leave/Drop client messages, ignores everything else.
+    pub(super) unsafe fn dnd_client_message(
+        &mut self,
+        event: &XClientMessageEvent,
+        event_handler: &mut dyn EventHandler,
+    ) {
+        let data = event.data.l;
+        let message_type = event.message_type;
+
+        if message_type == self.dnd.enter {
+            self.dnd.source = data[0] as Window;
+            self.dnd.version = data[1] >> 24;
+            self.dnd.accepted = self.dnd.version <= XDND_VERSION && self.dnd_offers_uri_list(&data);
+            if self.dnd.accepted {
+                event_handler.files_hovered_event();
+            }
+        } else if message_type == self.dnd.position {
+            let (accepted, action) = if self.dnd.accepted {
+                (1, self.dnd.action_copy as libc::c_long)
+            } else {
+                (0, 0)
+            };
+            self.send_dnd_message(self.dnd.status, [event.window as _, accepted, 0, 0, action]);
+        } else if message_type == self.dnd.leave {
+            if self.dnd.accepted {
+                self.dnd.accepted = false;
+                event_handler.files_hover_cancelled_event();
+            }
+        } else if message_type == self.dnd.drop {
+            if !self.dnd.accepted {
+                self.send_dnd_message(self.dnd.finished, [event.window as _, 0, 0, 0, 0]);
+                return;
+            }
+            // the data comes later, with SelectionNotify
+            let time = if self.dnd.version >= 1 { data[2] } else { 0 };
+            (self.libx11.XConvertSelection)(
+                self.display,
+                self.dnd.selection,
+                self.dnd.uri_list,
+                self.dnd.selection,
+                event.window,
+                time as _,
+            );
+        }
+    }
+
+    /// SelectionNotify for the XdndDrop's XConvertSelection.
+    pub(super) unsafe fn dnd_selection_notify(
+        &mut self,
+        event: &XSelectionEvent,
+        event_handler: &mut dyn EventHandler,
+    ) {
+        if event.property != self.dnd.selection || !self.dnd.accepted {
+            return;
+        }
+        self.dnd.accepted = false;
+
+        let mut actual_type = 0;
+        let mut actual_format = 0;
+        let mut count = 0;
+        let mut bytes_after = 0;
+        let mut list: *mut libc::c_uchar = std::ptr::null_mut();
+        (self.libx11.XGetWindowProperty)(
+            self.display,
+            event.requestor,
+            self.dnd.selection,
+            0,
+            libc::c_long::MAX,
+            false as _,
+            ANY_PROPERTY_TYPE,
+            &mut actual_type,
+            &mut actual_format,
+            &mut count,
+            &mut bytes_after,
+            &mut list,
+        );
+        let dropped = !list.is_null();
+        if dropped {
+            let files = std::slice::from_raw_parts(list, count as _);
+            crate::native_display().lock().unwrap().dropped_files =
+                crate::native::DroppedFiles::from_uri_list(files);
+            (self.libx11.XFree)(list as _);
+        }
+
+        if self.dnd.version >= 2 {
+            self.send_dnd_message(
+                self.dnd.finished,
+                [
+                    event.requestor as _,
+                    dropped as _,
+                    self.dnd.action_copy as _,
+                    0,
+                    0,
+                ],
+            );
+        }
+        if dropped {
+            event_handler.files_dropped_event();
+        }
+    }
+}
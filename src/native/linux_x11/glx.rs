@@ -27,8 +27,11 @@ pub const GLX_CONTEXT_MAJOR_VERSION_ARB: libc::c_int = 0x2091 as libc::c_int;
 pub const GLX_CONTEXT_MINOR_VERSION_ARB: libc::c_int = 0x2092 as libc::c_int;
 pub const GLX_CONTEXT_PROFILE_MASK_ARB: libc::c_int = 0x9126 as libc::c_int;
 pub const GLX_CONTEXT_CORE_PROFILE_BIT_ARB: libc::c_int = 0x1 as libc::c_int;
+pub const GLX_CONTEXT_COMPATIBILITY_PROFILE_BIT_ARB: libc::c_int = 0x2 as libc::c_int;
+pub const GLX_CONTEXT_DEBUG_BIT_ARB: libc::c_int = 0x1 as libc::c_int;
 pub const GLX_CONTEXT_FLAGS_ARB: libc::c_int = 0x2094 as libc::c_int;
 pub const GLX_CONTEXT_FORWARD_COMPATIBLE_BIT_ARB: libc::c_int = 0x2 as libc::c_int;
+pub const GLX_FRAMEBUFFER_SRGB_CAPABLE_ARB: libc::c_int = 0x20b2 as libc::c_int;
 
 pub type GLenum = ::std::os::raw::c_uint;
 pub type GLboolean = ::std::os::raw::c_uchar;
@@ -199,6 +202,8 @@ pub struct GlxExtensions {
     pub extensions_string: String,
     pub glxSwapIntervalExt: PFNGLXSWAPINTERVALEXTPROC,
     pub glxSwapIntervalMesa: PFNGLXSWAPINTERVALMESAPROC,
+    // negative intervals for glXSwapIntervalEXT, adaptive vsync
+    pub swap_control_tear: bool,
     pub glxCreateContextAttribsARB: PFNGLXCREATECONTEXTATTRIBSARBPROC,
 }
 
@@ -239,10 +244,8 @@ impl Glx {
         let extensions = std::ffi::CStr::from_ptr(exts).to_str().unwrap().to_owned();
 
         let multisample = extensions.contains("GLX_ARB_multisample");
-        // let glx_ARB_framebuffer_sRGB =
-        //     _sapp_glx_extsupported(b"GLX_ARB_framebuffer_sRGB\x00", exts);
-        // _sapp_glx_EXT_framebuffer_sRGB =
-        //     _sapp_glx_extsupported(b"GLX_EXT_framebuffer_sRGB\x00", exts);
+        let framebuffer_srgb = extensions.contains("GLX_ARB_framebuffer_sRGB")
+            || extensions.contains("GLX_EXT_framebuffer_sRGB");
         // if _sapp_glx_extsupported(b"GLX_ARB_create_context\x00", exts) {
         //     _sapp_glx_CreateContextAttribsARB =
         //         _sapp_glx_getprocaddr(b"glXCreateContextAttribsARB\x00");
@@ -251,7 +254,36 @@ impl Glx {
         // _sapp_glx_ARB_create_context_profile =
         //     _sapp_glx_extsupported(b"GLX_ARB_create_context_profile\x00", exts);
 
-        let fbconfig = choose_fbconfig(&mut libgl, libx11, display, screen, multisample, conf.sample_count);
+        let mut fbconfig = std::ptr::null_mut();
+        if conf.platform.srgb_framebuffer {
+            if framebuffer_srgb {
+                fbconfig = choose_fbconfig(
+                    &mut libgl,
+                    libx11,
+                    display,
+                    screen,
+                    multisample,
+                    conf.sample_count,
+                    conf.transparent,
+                    true,
+                );
+            }
+            if fbconfig.is_null() {
+                eprintln!("GLX: No sRGB capable GLXFBConfig");
+            }
+        }
+        if fbconfig.is_null() {
+            fbconfig = choose_fbconfig(
+                &mut libgl,
+                libx11,
+                display,
+                screen,
+                multisample,
+                conf.sample_count,
+                conf.transparent,
+                false,
+            );
+        }
         assert!(
             !fbconfig.is_null(),
             "GLX: Failed to find a suitable GLXFBConfig"
@@ -273,11 +305,14 @@ impl Glx {
             extensions_string: extensions_string.clone(),
             glxSwapIntervalExt: None,
             glxSwapIntervalMesa: None,
+            swap_control_tear: false,
             glxCreateContextAttribsARB: None,
         };
         if extensions_string.contains("GLX_EXT_swap_control") {
             extensions.glxSwapIntervalExt =
                 std::mem::transmute_copy(&libgl.get_procaddr("glXSwapIntervalEXT"));
+            extensions.swap_control_tear = extensions.glxSwapIntervalExt.is_some()
+                && extensions_string.contains("GLX_EXT_swap_control_tear");
         }
         if extensions_string.contains("GLX_MESA_swap_control") {
             extensions.glxSwapIntervalMesa =
@@ -305,19 +340,38 @@ impl Glx {
         &mut self,
         display: *mut Display,
         window: Window,
+        platform: &crate::conf::Platform,
     ) -> (GLXContext, GLXWindow) {
         if self.extensions.glxCreateContextAttribsARB.is_none() {
             panic!("GLX: ARB_create_context and ARB_create_context_profile required");
         }
 
+        let (major, minor) = platform.gl_version.unwrap_or((2, 1));
+        let mut flags = 0;
+        if platform.gl_debug {
+            flags |= GLX_CONTEXT_DEBUG_BIT_ARB;
+        }
+        // the profile is ignored before 3.2
+        let profile = match platform.gl_profile {
+            crate::conf::GlProfile::Core => {
+                if major >= 3 {
+                    flags |= GLX_CONTEXT_FORWARD_COMPATIBLE_BIT_ARB;
+                }
+                GLX_CONTEXT_CORE_PROFILE_BIT_ARB
+            }
+            crate::conf::GlProfile::Compatibility => GLX_CONTEXT_COMPATIBILITY_PROFILE_BIT_ARB,
+        };
+
         // _sapp_x11_grab_error_handler(libx11);
-        let attribs: [libc::c_int; 8] = [
+        let attribs: [libc::c_int; 10] = [
             GLX_CONTEXT_MAJOR_VERSION_ARB,
-            2,
+            major,
             GLX_CONTEXT_MINOR_VERSION_ARB,
-            1,
+            minor,
             GLX_CONTEXT_FLAGS_ARB,
-            GLX_CONTEXT_CORE_PROFILE_BIT_ARB,
+            flags,
+            GLX_CONTEXT_PROFILE_MASK_ARB,
+            profile,
             0,
             0,
         ];
@@ -328,7 +382,12 @@ impl Glx {
             true as _,
             attribs.as_ptr(),
         );
-        assert!(!glx_ctx.is_null(), "GLX: failed to create GL context");
+        assert!(
+            !glx_ctx.is_null(),
+            "GLX: failed to create OpenGL {}.{} context",
+            major,
+            minor
+        );
         // _sapp_x11_release_error_handler(libx11);
 
         let glx_window =
@@ -365,20 +424,35 @@ impl Glx {
         self.libgl.glxSwapBuffers.unwrap()(display, window);
     }
 
+    /// Whether there is any swap interval extension, for 0 and 1 intervals.
+    pub fn swap_control(&self) -> bool {
+        self.extensions.glxSwapIntervalExt.is_some()
+            || self.extensions.glxSwapIntervalMesa.is_some()
+    }
+
+    /// Whether -1, adaptive vsync, is supported.
+    pub fn adaptive_vsync(&self) -> bool {
+        self.extensions.swap_control_tear
+    }
+
     pub unsafe fn swap_interval(
         &mut self,
         display: *mut Display,
         window: GLXWindow,
         ctx: GLXContext,
         interval: i32,
-    ) {
+    ) -> bool {
         self.libgl.glxMakeCurrent.unwrap()(display, window, ctx);
 
         if self.extensions.glxSwapIntervalExt.is_some() {
             self.extensions.glxSwapIntervalExt.unwrap()(display, window, interval);
+            true
         } else if self.extensions.glxSwapIntervalMesa.is_some() {
-            self.extensions.glxSwapIntervalMesa.unwrap()(interval);
-        };
+            // MESA has no negative intervals
+            self.extensions.glxSwapIntervalMesa.unwrap()(interval.max(0)) == 0
+        } else {
+            false
+        }
     }
 }
 
@@ -389,7 +463,9 @@ unsafe fn choose_fbconfig(
     display: *mut Display,
     screen: i32,
     multisample: bool,
-    desired_sample_count: i32
+    desired_sample_count: i32,
+    transparent: bool,
+    srgb: bool
 ) -> GLXFBConfig {
 
     let native_configs: *mut GLXFBConfig;
@@ -412,6 +488,8 @@ unsafe fn choose_fbconfig(
     }
 
     let mut usable_configs: Vec<GLFBConfig> = Vec::new();
+    // the configs with a 32 bit visual, the only ones composited with alpha
+    let mut argb_configs: Vec<GLFBConfig> = Vec::new();
     usable_count = 0 as libc::c_int;
 
     for i in 0..native_count {
@@ -432,6 +510,9 @@ unsafe fn choose_fbconfig(
                 continue;
             }
         }
+        if srgb && glx_attrib(n, GLX_FRAMEBUFFER_SRGB_CAPABLE_ARB) == 0 {
+            continue;
+        }
 
         u.red_bits = glx_attrib(n, GLX_RED_SIZE);
         u.green_bits = glx_attrib(n, GLX_GREEN_SIZE);
@@ -446,10 +527,28 @@ unsafe fn choose_fbconfig(
             u.samples = glx_attrib(n, GLX_SAMPLES)
         }
         u.handle = n as libc::c_ulong;
+        if transparent {
+            let visual = libgl.glxGetVisualFromFBConfig.unwrap()(display, n);
+            if !visual.is_null() {
+                if (*visual).depth == 32 {
+                    argb_configs.push(u);
+                }
+                (libx11.XFree)(visual as *mut libc::c_void);
+            }
+        }
         usable_configs.push(u);
         usable_count += 1
     }
 
+    if transparent {
+        if argb_configs.is_empty() {
+            eprintln!("GLX: No 32 bit visual, the window will not be transparent");
+        } else {
+            usable_count = argb_configs.len() as libc::c_int;
+            usable_configs = argb_configs;
+        }
+    }
+
     let mut desired = GLFBConfig::default();
     desired.red_bits = 8;
     desired.green_bits = 8;
@@ -152,14 +152,37 @@ pub unsafe fn translate_mod(x11_mods: i32) -> KeyMods {
     if x11_mods & super::libx11::Mod4Mask != 0 {
         mods.logo = true;
     }
+    if x11_mods & super::libx11::LockMask != 0 {
+        mods.caps_lock = true;
+    }
+    // where num lock is on almost every keymap
+    if x11_mods & super::libx11::Mod2Mask != 0 {
+        mods.num_lock = true;
+    }
     return mods;
 }
 
+/// The state of a key event is from before the key, the modifiers after it.
+pub fn mods_after_key(mut mods: KeyMods, key: KeyCode, down: bool) -> KeyMods {
+    match key {
+        KeyCode::LeftShift | KeyCode::RightShift => mods.shift = down,
+        KeyCode::LeftControl | KeyCode::RightControl => mods.ctrl = down,
+        KeyCode::LeftAlt | KeyCode::RightAlt => mods.alt = down,
+        KeyCode::LeftSuper | KeyCode::RightSuper => mods.logo = down,
+        _ => {}
+    }
+    mods
+}
+
 pub unsafe fn translate_mouse_button(button: i32) -> MouseButton {
     match button {
         1 => return MouseButton::Left,
         2 => return MouseButton::Middle,
         3 => return MouseButton::Right,
+        // 4 to 7 are the wheel
+        8 => return MouseButton::Back,
+        9 => return MouseButton::Forward,
+        10..=255 => return MouseButton::Other((button - 4) as u8),
         _ => return MouseButton::Unknown,
     };
 }
@@ -700,8 +700,10 @@ pub mod X_h {
     pub const Success: libc::c_int = 0 as libc::c_int;
     pub const IsViewable: libc::c_int = 2 as libc::c_int;
     pub const ShiftMask: libc::c_int = (1 as libc::c_int) << 0 as libc::c_int;
+    pub const LockMask: libc::c_int = (1 as libc::c_int) << 1 as libc::c_int;
     pub const ControlMask: libc::c_int = (1 as libc::c_int) << 2 as libc::c_int;
     pub const Mod1Mask: libc::c_int = (1 as libc::c_int) << 3 as libc::c_int;
+    pub const Mod2Mask: libc::c_int = (1 as libc::c_int) << 4 as libc::c_int;
     pub const Mod4Mask: libc::c_int = (1 as libc::c_int) << 6 as libc::c_int;
     pub const PropertyNewValue: libc::c_int = 0 as libc::c_int;
 }
@@ -775,8 +777,10 @@ pub mod Xutil_h {
     }
     pub const PMinSize: libc::c_long = (1 as libc::c_long) << 4 as libc::c_int;
     pub const PMaxSize: libc::c_long = (1 as libc::c_long) << 5 as libc::c_int;
+    pub const PAspect: libc::c_long = (1 as libc::c_long) << 7 as libc::c_int;
     pub const PWinGravity: libc::c_long = (1 as libc::c_long) << 9 as libc::c_int;
     pub const IconicState: libc::c_int = 3 as libc::c_int;
+    pub const TrueColor: libc::c_int = 4 as libc::c_int;
     pub const WithdrawnState: libc::c_int = 0 as libc::c_int;
     pub const NormalState: libc::c_int = 1 as libc::c_int;
     use super::X_h::{Pixmap, VisualID, Window, XID};
@@ -858,6 +862,8 @@ pub type XLowerWindow = unsafe extern "C" fn(_: *mut Display, _: Window) -> libc
 pub type XRaiseWindow = unsafe extern "C" fn(_: *mut Display, _: Window) -> libc::c_int;
 pub type XResizeWindow =
     unsafe extern "C" fn(_: *mut Display, _: Window, _: libc::c_int, _: libc::c_int) -> libc::c_int;
+pub type XMoveWindow =
+    unsafe extern "C" fn(_: *mut Display, _: Window, _: libc::c_int, _: libc::c_int) -> libc::c_int;
 pub type XPending = unsafe extern "C" fn(_: *mut Display) -> libc::c_int;
 pub type XNextEvent = unsafe extern "C" fn(_: *mut Display, _: *mut XEvent) -> libc::c_int;
 pub type XGetKeyboardMapping = unsafe extern "C" fn(
@@ -978,6 +984,35 @@ pub type XCreatePixmapCursor = unsafe extern "C" fn(
 ) -> Cursor;
 pub type XFreePixmap = unsafe extern "C" fn(_: *mut Display, _: Pixmap) -> libc::c_int;
 pub type XDefineCursor = unsafe extern "C" fn(_: *mut Display, _: Window, _: Cursor) -> libc::c_int;
+pub type XFreeCursor = unsafe extern "C" fn(_: *mut Display, _: Cursor) -> libc::c_int;
+pub type XWarpPointer = unsafe extern "C" fn(
+    _: *mut Display,
+    _: Window,
+    _: Window,
+    _: libc::c_int,
+    _: libc::c_int,
+    _: libc::c_uint,
+    _: libc::c_uint,
+    _: libc::c_int,
+    _: libc::c_int,
+) -> libc::c_int;
+pub type XTranslateCoordinates = unsafe extern "C" fn(
+    _: *mut Display,
+    _: Window,
+    _: Window,
+    _: libc::c_int,
+    _: libc::c_int,
+    _: *mut libc::c_int,
+    _: *mut libc::c_int,
+    _: *mut Window,
+) -> libc::c_int;
+pub type XMatchVisualInfo = unsafe extern "C" fn(
+    _: *mut Display,
+    _: libc::c_int,
+    _: libc::c_int,
+    _: libc::c_int,
+    _: *mut XVisualInfo,
+) -> libc::c_int;
 
 #[derive(Clone, Default)]
 pub struct X11Extensions {
@@ -987,6 +1022,10 @@ pub struct X11Extensions {
     pub _wm_state: Atom,
     pub net_wm_name: Atom,
     pub net_wm_icon_name: Atom,
+    pub net_wm_state: Atom,
+    pub net_wm_state_hidden: Atom,
+    pub net_wm_state_maximized_vert: Atom,
+    pub net_wm_state_maximized_horz: Atom,
 }
 
 #[derive(Clone)]
@@ -1013,6 +1052,7 @@ pub struct LibX11 {
     pub XLowerWindow: XLowerWindow,
     pub XRaiseWindow: XRaiseWindow,
     pub XResizeWindow: XResizeWindow,
+    pub XMoveWindow: XMoveWindow,
     pub XPending: XPending,
     pub XNextEvent: XNextEvent,
     pub XGetKeyboardMapping: XGetKeyboardMapping,
@@ -1038,6 +1078,10 @@ pub struct LibX11 {
     pub XCreatePixmapCursor: XCreatePixmapCursor,
     pub XFreePixmap: XFreePixmap,
     pub XDefineCursor: XDefineCursor,
+    pub XFreeCursor: XFreeCursor,
+    pub XWarpPointer: XWarpPointer,
+    pub XTranslateCoordinates: XTranslateCoordinates,
+    pub XMatchVisualInfo: XMatchVisualInfo,
 }
 
 impl LibX11 {
@@ -1065,6 +1109,7 @@ impl LibX11 {
                 XLowerWindow: module.get_symbol("XLowerWindow").unwrap(),
                 XRaiseWindow: module.get_symbol("XRaiseWindow").unwrap(),
                 XResizeWindow: module.get_symbol("XResizeWindow").unwrap(),
+                XMoveWindow: module.get_symbol("XMoveWindow").unwrap(),
                 XPending: module.get_symbol("XPending").unwrap(),
                 XNextEvent: module.get_symbol("XNextEvent").unwrap(),
                 XGetKeyboardMapping: module.get_symbol("XGetKeyboardMapping").unwrap(),
@@ -1092,6 +1137,10 @@ impl LibX11 {
                 XCreatePixmapCursor: module.get_symbol("XCreatePixmapCursor").unwrap(),
                 XFreePixmap: module.get_symbol("XFreePixmap").unwrap(),
                 XDefineCursor: module.get_symbol("XDefineCursor").unwrap(),
+                XFreeCursor: module.get_symbol("XFreeCursor").unwrap(),
+                XWarpPointer: module.get_symbol("XWarpPointer").unwrap(),
+                XTranslateCoordinates: module.get_symbol("XTranslateCoordinates").unwrap(),
+                XMatchVisualInfo: module.get_symbol("XMatchVisualInfo").unwrap(),
                 extensions: X11Extensions::default(),
                 module: std::rc::Rc::new(module),
             })
@@ -1130,6 +1179,26 @@ impl LibX11 {
                 b"_NET_WM_ICON_NAME\x00" as *const u8 as *const libc::c_char,
                 false as _,
             ),
+            net_wm_state: (self.XInternAtom)(
+                display,
+                b"_NET_WM_STATE\x00" as *const u8 as *const libc::c_char,
+                false as _,
+            ),
+            net_wm_state_hidden: (self.XInternAtom)(
+                display,
+                b"_NET_WM_STATE_HIDDEN\x00" as *const u8 as *const libc::c_char,
+                false as _,
+            ),
+            net_wm_state_maximized_vert: (self.XInternAtom)(
+                display,
+                b"_NET_WM_STATE_MAXIMIZED_VERT\x00" as *const u8 as *const libc::c_char,
+                false as _,
+            ),
+            net_wm_state_maximized_horz: (self.XInternAtom)(
+                display,
+                b"_NET_WM_STATE_MAXIMIZED_HORZ\x00" as *const u8 as *const libc::c_char,
+                false as _,
+            ),
         };
     }
 }
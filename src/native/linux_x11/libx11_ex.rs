@@ -187,6 +187,24 @@ impl LibX11 {
         (self.XFree)(hints as *mut libc::c_void);
 
         self.update_window_title(display, window, &conf.window_title);
+        if let Some(app_id) = &conf.platform.app_id {
+            let app_id = std::ffi::CString::new(app_id.as_str()).unwrap();
+            let mut class_hint = XClassHint {
+                res_name: app_id.as_ptr() as *mut _,
+                res_class: app_id.as_ptr() as *mut _,
+            };
+            (self.Xutf8SetWMProperties)(
+                display,
+                window,
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null_mut(),
+                0 as libc::c_int,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                &mut class_hint,
+            );
+        }
 
         window
     }
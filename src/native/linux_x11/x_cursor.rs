@@ -1,5 +1,70 @@
+#![allow(non_snake_case)]
+
 use super::libx11::{Cursor, Display, LibX11, Window, XColor};
 
+#[repr(C)]
+struct XcursorImage {
+    version: u32,
+    size: u32,
+    width: u32,
+    height: u32,
+    xhot: u32,
+    yhot: u32,
+    delay: u32,
+    pixels: *mut u32,
+}
+
+type XcursorImageCreate =
+    unsafe extern "C" fn(width: libc::c_int, height: libc::c_int) -> *mut XcursorImage;
+type XcursorImageDestroy = unsafe extern "C" fn(image: *mut XcursorImage);
+type XcursorImageLoadCursor =
+    unsafe extern "C" fn(display: *mut Display, image: *const XcursorImage) -> Cursor;
+
+/// libXcursor, the only way to get full color cursors on X11.
+pub struct LibXcursor {
+    _module: crate::native::module::Module,
+    XcursorImageCreate: XcursorImageCreate,
+    XcursorImageDestroy: XcursorImageDestroy,
+    XcursorImageLoadCursor: XcursorImageLoadCursor,
+}
+
+impl LibXcursor {
+    pub fn try_load() -> Option<LibXcursor> {
+        crate::native::module::Module::load("libXcursor.so")
+            .or_else(|_| crate::native::module::Module::load("libXcursor.so.1"))
+            .map(|module| LibXcursor {
+                XcursorImageCreate: module.get_symbol("XcursorImageCreate").unwrap(),
+                XcursorImageDestroy: module.get_symbol("XcursorImageDestroy").unwrap(),
+                XcursorImageLoadCursor: module.get_symbol("XcursorImageLoadCursor").unwrap(),
+                _module: module,
+            })
+            .ok()
+    }
+
+    pub unsafe fn create_cursor(
+        &self,
+        display: *mut Display,
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+        hotspot: (u32, u32),
+    ) -> Cursor {
+        let image = (self.XcursorImageCreate)(width as _, height as _);
+        (*image).xhot = hotspot.0;
+        (*image).yhot = hotspot.1;
+        let pixels = std::slice::from_raw_parts_mut((*image).pixels, (width * height) as usize);
+        // Xcursor wants premultiplied ARGB
+        for (pixel, rgba) in pixels.iter_mut().zip(rgba.chunks_exact(4)) {
+            let a = rgba[3] as u32;
+            let [r, g, b] = [rgba[0], rgba[1], rgba[2]].map(|c| c as u32 * a / 255);
+            *pixel = a << 24 | r << 16 | g << 8 | b;
+        }
+        let cursor = (self.XcursorImageLoadCursor)(display, image);
+        (self.XcursorImageDestroy)(image);
+        cursor
+    }
+}
+
 pub unsafe fn create_empty_cursor(
     display: *mut Display,
     root: Window,
@@ -8,6 +8,11 @@ use super::{
 pub const XIAllDevices: libc::c_int = 0 as libc::c_int;
 pub const XI_RawMotion: libc::c_int = 17 as libc::c_int;
 pub const XI_RawMotionMask: libc::c_int = (1 as libc::c_int) << XI_RawMotion;
+pub const XI_ButtonPress: libc::c_int = 4;
+pub const XI_ButtonRelease: libc::c_int = 5;
+pub const XI_Motion: libc::c_int = 6;
+pub const XISlavePointer: libc::c_int = 3;
+pub const XIValuatorClass: libc::c_int = 2;
 
 #[derive(Copy, Clone)]
 #[repr(C)]
@@ -27,6 +32,82 @@ pub struct XIValuatorState {
 
 pub type Time = libc::c_ulong;
 
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct XIButtonState {
+    pub mask_len: libc::c_int,
+    pub mask: *mut libc::c_uchar,
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct XIModifierState {
+    pub base: libc::c_int,
+    pub latched: libc::c_int,
+    pub locked: libc::c_int,
+    pub effective: libc::c_int,
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct XIDeviceEvent {
+    pub type_0: libc::c_int,
+    pub serial: libc::c_ulong,
+    pub send_event: libc::c_int,
+    pub display: *mut Display,
+    pub extension: libc::c_int,
+    pub evtype: libc::c_int,
+    pub time: Time,
+    pub deviceid: libc::c_int,
+    pub sourceid: libc::c_int,
+    pub detail: libc::c_int,
+    pub root: Window,
+    pub event: Window,
+    pub child: Window,
+    pub root_x: libc::c_double,
+    pub root_y: libc::c_double,
+    pub event_x: libc::c_double,
+    pub event_y: libc::c_double,
+    pub flags: libc::c_int,
+    pub buttons: XIButtonState,
+    pub valuators: XIValuatorState,
+    pub mods: XIModifierState,
+    pub group: XIModifierState,
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct XIAnyClassInfo {
+    pub type_0: libc::c_int,
+    pub sourceid: libc::c_int,
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct XIValuatorClassInfo {
+    pub type_0: libc::c_int,
+    pub sourceid: libc::c_int,
+    pub number: libc::c_int,
+    pub label: libx11::Atom,
+    pub min: libc::c_double,
+    pub max: libc::c_double,
+    pub value: libc::c_double,
+    pub resolution: libc::c_int,
+    pub mode: libc::c_int,
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct XIDeviceInfo {
+    pub deviceid: libc::c_int,
+    pub name: *mut libc::c_char,
+    pub use_0: libc::c_int,
+    pub attachment: libc::c_int,
+    pub enabled: libc::c_int,
+    pub num_classes: libc::c_int,
+    pub classes: *mut *mut XIAnyClassInfo,
+}
+
 #[derive(Copy, Clone)]
 #[repr(C)]
 pub struct XIRawEvent {
@@ -61,6 +142,88 @@ type XISelectEvents =
     fn(dpy: *mut Display, win: Window, masks: *mut XIEventMask, num_masks: libc::c_int);
 type XGetEventData = fn(_: *mut Display, _: *mut libx11::XGenericEventCookie) -> libc::c_int;
 type XFreeEventData = fn(_: *mut Display, _: *mut libx11::XGenericEventCookie);
+type XIQueryDevice =
+    unsafe extern "C" fn(_: *mut Display, _: libc::c_int, _: *mut libc::c_int) -> *mut XIDeviceInfo;
+type XIFreeDeviceInfo = unsafe extern "C" fn(_: *mut XIDeviceInfo);
+
+// a valuator of a pen device, and its last value, the events only have
+// the changed ones
+#[derive(Clone, Copy)]
+struct Valuator {
+    number: i32,
+    min: f64,
+    max: f64,
+    value: f64,
+}
+
+impl Valuator {
+    fn normalized(&self) -> f64 {
+        if self.max > self.min {
+            (self.value - self.min) / (self.max - self.min)
+        } else {
+            0.
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Pen {
+    deviceid: i32,
+    pressure: Valuator,
+    tilt_x: Option<Valuator>,
+    tilt_y: Option<Valuator>,
+    eraser: bool,
+}
+
+impl Pen {
+    unsafe fn update(&mut self, valuators: &XIValuatorState) {
+        // the values of the set bits only
+        let mut value = valuators.values;
+        for number in 0..valuators.mask_len * 8 {
+            if !is_set(valuators.mask, valuators.mask_len, number) {
+                continue;
+            }
+            let valuator = if number == self.pressure.number {
+                Some(&mut self.pressure)
+            } else if self.tilt_x.map(|tilt| tilt.number) == Some(number) {
+                self.tilt_x.as_mut()
+            } else if self.tilt_y.map(|tilt| tilt.number) == Some(number) {
+                self.tilt_y.as_mut()
+            } else {
+                None
+            };
+            if let Some(valuator) = valuator {
+                valuator.value = *value;
+            }
+            value = value.offset(1);
+        }
+    }
+
+    unsafe fn state(&self, event: &XIDeviceEvent) -> crate::PenState {
+        // the button state is the one before the event
+        let button = |button| match event.evtype {
+            XI_ButtonPress if event.detail == button => true,
+            XI_ButtonRelease if event.detail == button => false,
+            _ => is_set(event.buttons.mask, event.buttons.mask_len, button),
+        };
+        // the drivers report degrees
+        let tilt = |valuator: Option<Valuator>| {
+            valuator.map_or(0., |valuator| valuator.value.clamp(-90., 90.) as f32)
+        };
+        crate::PenState {
+            pressure: self.pressure.normalized() as f32,
+            tilt: (tilt(self.tilt_x), tilt(self.tilt_y)),
+            contact: button(1),
+            barrel_button: button(2) || button(3),
+            eraser: self.eraser,
+        }
+    }
+}
+
+// a bit of the XIButtonState or XIValuatorState mask
+unsafe fn is_set(mask: *const libc::c_uchar, mask_len: libc::c_int, bit: libc::c_int) -> bool {
+    bit < mask_len * 8 && *mask.offset(bit as isize / 8) & (1 << (bit % 8)) != 0
+}
 
 #[derive(Clone)]
 pub struct LibXi {
@@ -70,7 +233,11 @@ pub struct LibXi {
     XISelectEvents: XISelectEvents,
     XGetEventData: XGetEventData,
     XFreeEventData: XFreeEventData,
+    XIQueryDevice: XIQueryDevice,
+    XIFreeDeviceInfo: XIFreeDeviceInfo,
     pub xi_extension_opcode: Option<i32>,
+    // the tablet pens plugged in at the start
+    pens: Vec<Pen>,
 }
 
 impl LibXi {
@@ -83,7 +250,10 @@ impl LibXi {
                 XISelectEvents: module.get_symbol("XISelectEvents").unwrap(),
                 XGetEventData: module.get_symbol("XGetEventData").unwrap(),
                 XFreeEventData: module.get_symbol("XFreeEventData").unwrap(),
+                XIQueryDevice: module.get_symbol("XIQueryDevice").unwrap(),
+                XIFreeDeviceInfo: module.get_symbol("XIFreeDeviceInfo").unwrap(),
                 xi_extension_opcode: None,
+                pens: vec![],
                 _module: std::rc::Rc::new(module),
             })
             .ok()
@@ -148,6 +318,7 @@ impl LibXi {
         (self.XGetEventData)(display, xcookie);
 
         let raw_event = (*xcookie).data as *mut xi_input::XIRawEvent;
+        crate::native::update_event_timestamp_ms((*raw_event).time as u32);
 
         let dx = *(*raw_event).raw_values;
         let dy = *(*raw_event).raw_values.offset(1);
@@ -156,4 +327,102 @@ impl LibXi {
 
         (dx, dy)
     }
+
+    /// Select the pen events on the window, for the XInput2 devices with a
+    /// pressure valuator.
+    pub unsafe fn select_pen_events(
+        &mut self,
+        libx11: &mut libx11::LibX11,
+        display: *mut Display,
+        window: Window,
+    ) {
+        if self.xi_extension_opcode.is_none() {
+            return;
+        }
+        let atom = |name: &[u8]| (libx11.XInternAtom)(display, name.as_ptr() as _, true as _);
+        let pressure_label = atom(b"Abs Pressure\0");
+        let tilt_x_label = atom(b"Abs Tilt X\0");
+        let tilt_y_label = atom(b"Abs Tilt Y\0");
+        if pressure_label == 0 {
+            return;
+        }
+
+        let mut count = 0;
+        let devices = (self.XIQueryDevice)(display, XIAllDevices, &mut count);
+        if devices.is_null() {
+            return;
+        }
+        for device in std::slice::from_raw_parts(devices, count as usize) {
+            if device.use_0 != XISlavePointer {
+                continue;
+            }
+            let mut pressure = None;
+            let mut tilt_x = None;
+            let mut tilt_y = None;
+            for &class in std::slice::from_raw_parts(device.classes, device.num_classes as usize) {
+                if (*class).type_0 != XIValuatorClass {
+                    continue;
+                }
+                let class = &*(class as *const XIValuatorClassInfo);
+                let valuator = Some(Valuator {
+                    number: class.number,
+                    min: class.min,
+                    max: class.max,
+                    value: class.value,
+                });
+                if class.label == pressure_label {
+                    pressure = valuator;
+                } else if class.label == tilt_x_label {
+                    tilt_x = valuator;
+                } else if class.label == tilt_y_label {
+                    tilt_y = valuator;
+                }
+            }
+            if let Some(pressure) = pressure {
+                // the wacom and libinput drivers have a device for each end
+                let name = std::ffi::CStr::from_ptr(device.name).to_string_lossy();
+                self.pens.push(Pen {
+                    deviceid: device.deviceid,
+                    pressure,
+                    tilt_x,
+                    tilt_y,
+                    eraser: name.to_lowercase().contains("eraser"),
+                });
+            }
+        }
+        (self.XIFreeDeviceInfo)(devices);
+
+        // along with the core events of the master pointer
+        let mut mask = (1 << XI_ButtonPress) | (1 << XI_ButtonRelease) | (1 << XI_Motion);
+        for pen in &self.pens {
+            let mut masks = XIEventMask {
+                deviceid: pen.deviceid,
+                mask_len: ::std::mem::size_of::<libc::c_int>() as _,
+                mask: &mut mask as *mut _ as *mut _,
+            };
+            (self.XISelectEvents)(display, window, &mut masks, 1);
+        }
+    }
+
+    /// The position and state of a pen from XI_ButtonPress, XI_ButtonRelease
+    /// or XI_Motion.
+    pub unsafe fn read_pen_cookie(
+        &mut self,
+        xcookie: &mut libx11::XGenericEventCookie,
+        display: *mut Display,
+    ) -> Option<(f32, f32, crate::PenState)> {
+        (self.XGetEventData)(display, xcookie);
+        let event = &*(xcookie.data as *const XIDeviceEvent);
+        crate::native::update_event_timestamp_ms(event.time as u32);
+        let pen = self
+            .pens
+            .iter_mut()
+            .find(|pen| pen.deviceid == event.deviceid);
+        let result = pen.map(|pen| {
+            pen.update(&event.valuators);
+            (event.event_x as f32, event.event_y as f32, pen.state(event))
+        });
+        (self.XFreeEventData)(display, xcookie);
+        result
+    }
 }
@@ -0,0 +1,393 @@
+//! XIM input methods, for `ime_composition_event` and `ime_commit_event`.
+//! The composition is on the spot, from the preedit callbacks, when the input
+//! method has them, the input method draws it in its own window otherwise.
+
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use super::libx11::{Display, KeySym, LibX11, Window, XEvent, XFree, XKeyEvent, XPointer};
+use std::ffi::{c_void, CStr};
+
+type XIM = *mut c_void;
+type XIC = *mut c_void;
+type XIMStyle = libc::c_ulong;
+
+const XIMPreeditCallbacks: XIMStyle = 0x0002;
+const XIMPreeditNothing: XIMStyle = 0x0008;
+const XIMStatusNothing: XIMStyle = 0x0400;
+const XBufferOverflow: libc::c_int = -1;
+const XLookupChars: libc::c_int = 2;
+const XLookupBoth: libc::c_int = 4;
+// XIMCaretDirection
+const XIMForwardChar: libc::c_int = 0;
+const XIMBackwardChar: libc::c_int = 1;
+const XIMAbsolutePosition: libc::c_int = 10;
+
+const XNQueryInputStyle: &[u8] = b"queryInputStyle\0";
+const XNInputStyle: &[u8] = b"inputStyle\0";
+const XNClientWindow: &[u8] = b"clientWindow\0";
+const XNFocusWindow: &[u8] = b"focusWindow\0";
+const XNPreeditAttributes: &[u8] = b"preeditAttributes\0";
+const XNPreeditStartCallback: &[u8] = b"preeditStartCallback\0";
+const XNPreeditDoneCallback: &[u8] = b"preeditDoneCallback\0";
+const XNPreeditDrawCallback: &[u8] = b"preeditDrawCallback\0";
+const XNPreeditCaretCallback: &[u8] = b"preeditCaretCallback\0";
+const XNSpotLocation: &[u8] = b"spotLocation\0";
+
+#[repr(C)]
+struct XIMStyles {
+    count_styles: libc::c_ushort,
+    supported_styles: *mut XIMStyle,
+}
+
+#[repr(C)]
+struct XIMCallback {
+    client_data: XPointer,
+    callback: *mut c_void,
+}
+
+#[repr(C)]
+struct XIMText {
+    length: libc::c_ushort,
+    feedback: *mut libc::c_ulong,
+    encoding_is_wchar: libc::c_int,
+    // multi_byte or wide_char
+    string: *mut c_void,
+}
+
+#[repr(C)]
+struct XIMPreeditDrawCallbackStruct {
+    caret: libc::c_int,
+    chg_first: libc::c_int,
+    chg_length: libc::c_int,
+    text: *mut XIMText,
+}
+
+#[repr(C)]
+struct XIMPreeditCaretCallbackStruct {
+    position: libc::c_int,
+    direction: libc::c_int,
+    style: libc::c_int,
+}
+
+#[repr(C)]
+struct XPoint {
+    x: libc::c_short,
+    y: libc::c_short,
+}
+
+type XSetLocaleModifiers =
+    unsafe extern "C" fn(modifiers: *const libc::c_char) -> *mut libc::c_char;
+type XOpenIM = unsafe extern "C" fn(
+    display: *mut Display,
+    db: *mut c_void,
+    res_name: *mut libc::c_char,
+    res_class: *mut libc::c_char,
+) -> XIM;
+type XGetIMValues = unsafe extern "C" fn(im: XIM, ...) -> *mut libc::c_char;
+type XCreateIC = unsafe extern "C" fn(im: XIM, ...) -> XIC;
+type XSetICValues = unsafe extern "C" fn(ic: XIC, ...) -> *mut libc::c_char;
+type XVaCreateNestedList = unsafe extern "C" fn(unused: libc::c_int, ...) -> *mut c_void;
+type XSetICFocus = unsafe extern "C" fn(ic: XIC);
+type XUnsetICFocus = unsafe extern "C" fn(ic: XIC);
+type XFilterEvent = unsafe extern "C" fn(event: *mut XEvent, window: Window) -> libc::c_int;
+type Xutf8LookupString = unsafe extern "C" fn(
+    ic: XIC,
+    event: *mut XKeyEvent,
+    buffer: *mut libc::c_char,
+    bytes: libc::c_int,
+    keysym: *mut KeySym,
+    status: *mut libc::c_int,
+) -> libc::c_int;
+
+// the text being composed, written by the preedit callbacks
+#[derive(Default)]
+struct Preedit {
+    text: Vec<char>,
+    caret: usize,
+    // since the last take_composition
+    changed: bool,
+}
+
+pub struct Xim {
+    XSetICValues: XSetICValues,
+    XVaCreateNestedList: XVaCreateNestedList,
+    XSetICFocus: XSetICFocus,
+    XUnsetICFocus: XUnsetICFocus,
+    XFilterEvent: XFilterEvent,
+    Xutf8LookupString: Xutf8LookupString,
+    XFree: XFree,
+    ic: XIC,
+    // the callbacks point into it, boxed to stay where it is
+    preedit: Box<Preedit>,
+    _callbacks: Box<[XIMCallback; 4]>,
+}
+
+impl Xim {
+    /// None without an input method, the keys are looked up with
+    /// XLookupString then.
+    pub unsafe fn new(libx11: &LibX11, display: *mut Display, window: Window) -> Option<Xim> {
+        let module = &libx11.module;
+        let XSetLocaleModifiers: XSetLocaleModifiers =
+            module.get_symbol("XSetLocaleModifiers").ok()?;
+        let XOpenIM: XOpenIM = module.get_symbol("XOpenIM").ok()?;
+        let XGetIMValues: XGetIMValues = module.get_symbol("XGetIMValues").ok()?;
+        let XCreateIC: XCreateIC = module.get_symbol("XCreateIC").ok()?;
+        let XVaCreateNestedList: XVaCreateNestedList =
+            module.get_symbol("XVaCreateNestedList").ok()?;
+
+        // the input method is for the locale it is opened with, the user's
+        // and not the "C" one programs start with
+        let old_locale = libc::setlocale(libc::LC_CTYPE, std::ptr::null());
+        let old_locale = (!old_locale.is_null()).then(|| CStr::from_ptr(old_locale).to_owned());
+        libc::setlocale(libc::LC_CTYPE, b"\0".as_ptr() as _);
+        (XSetLocaleModifiers)(b"\0".as_ptr() as _);
+        let im = XOpenIM(
+            display,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        );
+        if let Some(old_locale) = old_locale {
+            libc::setlocale(libc::LC_CTYPE, old_locale.as_ptr());
+        }
+        if im.is_null() {
+            return None;
+        }
+
+        let mut styles: *mut XIMStyles = std::ptr::null_mut();
+        let failed = XGetIMValues(
+            im,
+            XNQueryInputStyle.as_ptr(),
+            &mut styles as *mut *mut XIMStyles,
+            std::ptr::null_mut::<c_void>(),
+        );
+        if !failed.is_null() || styles.is_null() {
+            return None;
+        }
+        let supported =
+            std::slice::from_raw_parts((*styles).supported_styles, (*styles).count_styles as usize);
+        let on_the_spot = supported.contains(&(XIMPreeditCallbacks | XIMStatusNothing));
+        let root = supported.contains(&(XIMPreeditNothing | XIMStatusNothing));
+        (libx11.XFree)(styles as *mut _);
+
+        let mut preedit = Box::<Preedit>::default();
+        let client_data = &mut *preedit as *mut Preedit as XPointer;
+        let callbacks = Box::new([
+            XIMCallback {
+                client_data,
+                callback: preedit_start as *mut _,
+            },
+            XIMCallback {
+                client_data,
+                callback: preedit_done as *mut _,
+            },
+            XIMCallback {
+                client_data,
+                callback: preedit_draw as *mut _,
+            },
+            XIMCallback {
+                client_data,
+                callback: preedit_caret as *mut _,
+            },
+        ]);
+
+        let ic = if on_the_spot {
+            let attributes = XVaCreateNestedList(
+                0,
+                XNPreeditStartCallback.as_ptr(),
+                &callbacks[0] as *const XIMCallback,
+                XNPreeditDoneCallback.as_ptr(),
+                &callbacks[1] as *const XIMCallback,
+                XNPreeditDrawCallback.as_ptr(),
+                &callbacks[2] as *const XIMCallback,
+                XNPreeditCaretCallback.as_ptr(),
+                &callbacks[3] as *const XIMCallback,
+                std::ptr::null_mut::<c_void>(),
+            );
+            let ic = XCreateIC(
+                im,
+                XNInputStyle.as_ptr(),
+                XIMPreeditCallbacks | XIMStatusNothing,
+                XNClientWindow.as_ptr(),
+                window,
+                XNFocusWindow.as_ptr(),
+                window,
+                XNPreeditAttributes.as_ptr(),
+                attributes,
+                std::ptr::null_mut::<c_void>(),
+            );
+            (libx11.XFree)(attributes);
+            ic
+        } else if root {
+            XCreateIC(
+                im,
+                XNInputStyle.as_ptr(),
+                XIMPreeditNothing | XIMStatusNothing,
+                XNClientWindow.as_ptr(),
+                window,
+                XNFocusWindow.as_ptr(),
+                window,
+                std::ptr::null_mut::<c_void>(),
+            )
+        } else {
+            std::ptr::null_mut()
+        };
+        if ic.is_null() {
+            eprintln!("X11: Failed to create an input context, no IME");
+            return None;
+        }
+
+        Some(Xim {
+            XSetICValues: module.get_symbol("XSetICValues").ok()?,
+            XVaCreateNestedList,
+            XSetICFocus: module.get_symbol("XSetICFocus").ok()?,
+            XUnsetICFocus: module.get_symbol("XUnsetICFocus").ok()?,
+            XFilterEvent: module.get_symbol("XFilterEvent").ok()?,
+            Xutf8LookupString: module.get_symbol("Xutf8LookupString").ok()?,
+            XFree: libx11.XFree,
+            ic,
+            preedit,
+            _callbacks: callbacks,
+        })
+    }
+
+    /// True if the event is for the input method, and not for the window.
+    pub unsafe fn filter_event(&mut self, event: &mut XEvent) -> bool {
+        (self.XFilterEvent)(event, 0) != 0
+    }
+
+    pub unsafe fn set_focus(&mut self, focused: bool) {
+        if focused {
+            (self.XSetICFocus)(self.ic);
+        } else {
+            (self.XUnsetICFocus)(self.ic);
+        }
+    }
+
+    /// The text of a KeyPress and its keysym.
+    pub unsafe fn lookup_string(&mut self, event: &mut XKeyEvent) -> (String, KeySym) {
+        let mut buffer = vec![0u8; 32];
+        let mut keysym = 0;
+        let mut status = 0;
+        let mut len = (self.Xutf8LookupString)(
+            self.ic,
+            event,
+            buffer.as_mut_ptr() as _,
+            buffer.len() as _,
+            &mut keysym,
+            &mut status,
+        );
+        if status == XBufferOverflow {
+            buffer.resize(len as usize, 0);
+            len = (self.Xutf8LookupString)(
+                self.ic,
+                event,
+                buffer.as_mut_ptr() as _,
+                buffer.len() as _,
+                &mut keysym,
+                &mut status,
+            );
+        }
+        if status != XLookupChars && status != XLookupBoth {
+            len = 0;
+        }
+        buffer.truncate(len.max(0) as usize);
+        (String::from_utf8_lossy(&buffer).into_owned(), keysym)
+    }
+
+    /// The composition if it changed since the last time, with the
+    /// cursor as a byte offset in the text.
+    pub fn take_composition(&mut self) -> Option<(String, usize)> {
+        let preedit = &mut *self.preedit;
+        if !std::mem::take(&mut preedit.changed) {
+            return None;
+        }
+        let text: String = preedit.text.iter().collect();
+        let cursor = preedit.text[..preedit.caret]
+            .iter()
+            .map(|c| c.len_utf8())
+            .sum();
+        Some((text, cursor))
+    }
+
+    /// Where the candidate window goes, below the area.
+    pub unsafe fn set_cursor_area(&mut self, x: f32, y: f32, _width: f32, height: f32) {
+        let mut spot = XPoint {
+            x: x as _,
+            y: (y + height) as _,
+        };
+        let attributes = (self.XVaCreateNestedList)(
+            0,
+            XNSpotLocation.as_ptr(),
+            &mut spot as *mut XPoint,
+            std::ptr::null_mut::<c_void>(),
+        );
+        (self.XSetICValues)(
+            self.ic,
+            XNPreeditAttributes.as_ptr(),
+            attributes,
+            std::ptr::null_mut::<c_void>(),
+        );
+        (self.XFree)(attributes);
+    }
+}
+
+unsafe extern "C" fn preedit_start(_ic: XIC, client_data: XPointer, _: XPointer) -> libc::c_int {
+    let preedit = &mut *(client_data as *mut Preedit);
+    preedit.text.clear();
+    preedit.caret = 0;
+    preedit.changed = true;
+    // no limit on the length
+    -1
+}
+
+unsafe extern "C" fn preedit_done(_ic: XIC, client_data: XPointer, _: XPointer) {
+    let preedit = &mut *(client_data as *mut Preedit);
+    preedit.text.clear();
+    preedit.caret = 0;
+    preedit.changed = true;
+}
+
+unsafe extern "C" fn preedit_draw(_ic: XIC, client_data: XPointer, call_data: XPointer) {
+    let preedit = &mut *(client_data as *mut Preedit);
+    let draw = &*(call_data as *const XIMPreeditDrawCallbackStruct);
+    let first = (draw.chg_first.max(0) as usize).min(preedit.text.len());
+    let last = (first + draw.chg_length.max(0) as usize).min(preedit.text.len());
+    // a text without a string is only a change of the highlighting
+    let text = draw.text.as_ref();
+    if text.map_or(true, |text| !text.string.is_null()) {
+        let chars = text.map_or(vec![], |text| xim_text_chars(text));
+        preedit.text.splice(first..last, chars);
+    }
+    preedit.caret = (draw.caret.max(0) as usize).min(preedit.text.len());
+    preedit.changed = true;
+}
+
+unsafe extern "C" fn preedit_caret(_ic: XIC, client_data: XPointer, call_data: XPointer) {
+    let preedit = &mut *(client_data as *mut Preedit);
+    let caret = &mut *(call_data as *mut XIMPreeditCaretCallbackStruct);
+    preedit.caret = match caret.direction {
+        XIMForwardChar => preedit.caret + 1,
+        XIMBackwardChar => preedit.caret.saturating_sub(1),
+        XIMAbsolutePosition => caret.position.max(0) as usize,
+        // lines and words, there is nothing like it in the composition
+        _ => preedit.caret,
+    }
+    .min(preedit.text.len());
+    caret.position = preedit.caret as _;
+    preedit.changed = true;
+}
+
+unsafe fn xim_text_chars(text: &XIMText) -> Vec<char> {
+    if text.encoding_is_wchar != 0 {
+        // wchar_t is utf-32 on linux
+        std::slice::from_raw_parts(text.string as *const u32, text.length as usize)
+            .iter()
+            .filter_map(|c| char::from_u32(*c))
+            .collect()
+    } else {
+        // in the locale of the input method, utf-8 nowadays
+        let string = CStr::from_ptr(text.string as *const libc::c_char);
+        string.to_string_lossy().chars().collect()
+    }
+}
@@ -0,0 +1,395 @@
+#![allow(non_snake_case)]
+
+use super::libx11::{Display, Time, Window, XID};
+
+type RRCrtc = XID;
+type RROutput = XID;
+type RRMode = XID;
+
+pub const RRScreenChangeNotifyMask: libc::c_int = 1;
+// RRScreenChangeNotify, relative to the extension event base
+const RRScreenChangeNotify: libc::c_int = 0;
+const RR_Connected: libc::c_ushort = 0;
+const RR_Interlace: libc::c_ulong = 0x10;
+const RR_DoubleScan: libc::c_ulong = 0x20;
+const RR_Rotate_90: libc::c_ushort = 2;
+const RR_Rotate_270: libc::c_ushort = 8;
+const CurrentTime: Time = 0;
+
+#[repr(C)]
+struct XRRModeInfo {
+    id: RRMode,
+    width: libc::c_uint,
+    height: libc::c_uint,
+    dotClock: libc::c_ulong,
+    hSyncStart: libc::c_uint,
+    hSyncEnd: libc::c_uint,
+    hTotal: libc::c_uint,
+    hSkew: libc::c_uint,
+    vSyncStart: libc::c_uint,
+    vSyncEnd: libc::c_uint,
+    vTotal: libc::c_uint,
+    name: *mut libc::c_char,
+    nameLength: libc::c_uint,
+    modeFlags: libc::c_ulong,
+}
+
+#[repr(C)]
+struct XRRScreenResources {
+    timestamp: Time,
+    configTimestamp: Time,
+    ncrtc: libc::c_int,
+    crtcs: *mut RRCrtc,
+    noutput: libc::c_int,
+    outputs: *mut RROutput,
+    nmode: libc::c_int,
+    modes: *mut XRRModeInfo,
+}
+
+#[repr(C)]
+struct XRROutputInfo {
+    timestamp: Time,
+    crtc: RRCrtc,
+    name: *mut libc::c_char,
+    nameLen: libc::c_int,
+    mm_width: libc::c_ulong,
+    mm_height: libc::c_ulong,
+    connection: libc::c_ushort,
+    subpixel_order: libc::c_ushort,
+    ncrtc: libc::c_int,
+    crtcs: *mut RRCrtc,
+    nclone: libc::c_int,
+    clones: *mut RROutput,
+    nmode: libc::c_int,
+    npreferred: libc::c_int,
+    modes: *mut RRMode,
+}
+
+#[repr(C)]
+struct XRRCrtcInfo {
+    timestamp: Time,
+    x: libc::c_int,
+    y: libc::c_int,
+    width: libc::c_uint,
+    height: libc::c_uint,
+    mode: RRMode,
+    rotation: libc::c_ushort,
+    noutput: libc::c_int,
+    outputs: *mut RROutput,
+    rotations: libc::c_ushort,
+    npossible: libc::c_int,
+    possible: *mut RROutput,
+}
+
+type XRRQueryExtension = unsafe extern "C" fn(
+    dpy: *mut Display,
+    event_base: *mut libc::c_int,
+    error_base: *mut libc::c_int,
+) -> libc::c_int;
+type XRRSelectInput = unsafe extern "C" fn(dpy: *mut Display, window: Window, mask: libc::c_int);
+type XRRGetScreenResourcesCurrent =
+    unsafe extern "C" fn(dpy: *mut Display, window: Window) -> *mut XRRScreenResources;
+type XRRFreeScreenResources = unsafe extern "C" fn(resources: *mut XRRScreenResources);
+type XRRGetOutputInfo = unsafe extern "C" fn(
+    dpy: *mut Display,
+    resources: *mut XRRScreenResources,
+    output: RROutput,
+) -> *mut XRROutputInfo;
+type XRRFreeOutputInfo = unsafe extern "C" fn(output_info: *mut XRROutputInfo);
+type XRRGetCrtcInfo = unsafe extern "C" fn(
+    dpy: *mut Display,
+    resources: *mut XRRScreenResources,
+    crtc: RRCrtc,
+) -> *mut XRRCrtcInfo;
+type XRRFreeCrtcInfo = unsafe extern "C" fn(crtc_info: *mut XRRCrtcInfo);
+type XRRGetOutputPrimary = unsafe extern "C" fn(dpy: *mut Display, window: Window) -> RROutput;
+type XRRSetCrtcConfig = unsafe extern "C" fn(
+    dpy: *mut Display,
+    resources: *mut XRRScreenResources,
+    crtc: RRCrtc,
+    timestamp: Time,
+    x: libc::c_int,
+    y: libc::c_int,
+    mode: RRMode,
+    rotation: libc::c_ushort,
+    outputs: *mut RROutput,
+    noutputs: libc::c_int,
+) -> libc::c_int;
+
+// the crtc configuration from before set_video_mode
+struct SavedCrtc {
+    display: *mut Display,
+    root: Window,
+    crtc: RRCrtc,
+    x: libc::c_int,
+    y: libc::c_int,
+    mode: RRMode,
+    rotation: libc::c_ushort,
+    outputs: Vec<RROutput>,
+}
+
+/// libXrandr, for the monitors list and the video modes. Without it there
+/// are no monitors.
+pub struct LibXrandr {
+    _module: crate::native::module::Module,
+    XRRQueryExtension: XRRQueryExtension,
+    XRRSelectInput: XRRSelectInput,
+    XRRGetScreenResourcesCurrent: XRRGetScreenResourcesCurrent,
+    XRRFreeScreenResources: XRRFreeScreenResources,
+    XRRGetOutputInfo: XRRGetOutputInfo,
+    XRRFreeOutputInfo: XRRFreeOutputInfo,
+    XRRGetCrtcInfo: XRRGetCrtcInfo,
+    XRRFreeCrtcInfo: XRRFreeCrtcInfo,
+    XRRGetOutputPrimary: XRRGetOutputPrimary,
+    XRRSetCrtcConfig: XRRSetCrtcConfig,
+    event_base: libc::c_int,
+    saved: Option<SavedCrtc>,
+}
+
+impl LibXrandr {
+    pub fn try_load() -> Option<LibXrandr> {
+        crate::native::module::Module::load("libXrandr.so")
+            .or_else(|_| crate::native::module::Module::load("libXrandr.so.2"))
+            .map(|module| LibXrandr {
+                XRRQueryExtension: module.get_symbol("XRRQueryExtension").unwrap(),
+                XRRSelectInput: module.get_symbol("XRRSelectInput").unwrap(),
+                XRRGetScreenResourcesCurrent: module
+                    .get_symbol("XRRGetScreenResourcesCurrent")
+                    .unwrap(),
+                XRRFreeScreenResources: module.get_symbol("XRRFreeScreenResources").unwrap(),
+                XRRGetOutputInfo: module.get_symbol("XRRGetOutputInfo").unwrap(),
+                XRRFreeOutputInfo: module.get_symbol("XRRFreeOutputInfo").unwrap(),
+                XRRGetCrtcInfo: module.get_symbol("XRRGetCrtcInfo").unwrap(),
+                XRRFreeCrtcInfo: module.get_symbol("XRRFreeCrtcInfo").unwrap(),
+                XRRGetOutputPrimary: module.get_symbol("XRRGetOutputPrimary").unwrap(),
+                XRRSetCrtcConfig: module.get_symbol("XRRSetCrtcConfig").unwrap(),
+                event_base: 0,
+                saved: None,
+                _module: module,
+            })
+            .ok()
+    }
+
+    /// Ask for RRScreenChangeNotify on monitors configuration changes.
+    /// None if the server has no RandR extension.
+    pub unsafe fn init(mut self, display: *mut Display, root: Window) -> Option<LibXrandr> {
+        let mut error_base = 0;
+        if (self.XRRQueryExtension)(display, &mut self.event_base, &mut error_base) == 0 {
+            return None;
+        }
+        (self.XRRSelectInput)(display, root, RRScreenChangeNotifyMask);
+        Some(self)
+    }
+
+    pub fn is_screen_change_event(&self, event_type: libc::c_int) -> bool {
+        event_type == self.event_base + RRScreenChangeNotify
+    }
+
+    /// Calls `f` for every connected and enabled output, the primary one first.
+    unsafe fn for_each_output(
+        &self,
+        display: *mut Display,
+        root: Window,
+        mut f: impl FnMut(*mut XRRScreenResources, &XRROutputInfo, &XRRCrtcInfo),
+    ) {
+        let resources = (self.XRRGetScreenResourcesCurrent)(display, root);
+        if resources.is_null() {
+            return;
+        }
+        let mut outputs =
+            std::slice::from_raw_parts((*resources).outputs, (*resources).noutput as _).to_vec();
+        let primary = (self.XRRGetOutputPrimary)(display, root);
+        outputs.sort_by_key(|output| *output != primary);
+
+        for output in outputs {
+            let output_info = (self.XRRGetOutputInfo)(display, resources, output);
+            if output_info.is_null() {
+                continue;
+            }
+            // disconnected or disabled outputs have no crtc
+            if (*output_info).connection == RR_Connected && (*output_info).crtc != 0 {
+                let crtc_info = (self.XRRGetCrtcInfo)(display, resources, (*output_info).crtc);
+                if !crtc_info.is_null() {
+                    f(resources, &*output_info, &*crtc_info);
+                    (self.XRRFreeCrtcInfo)(crtc_info);
+                }
+            }
+            (self.XRRFreeOutputInfo)(output_info);
+        }
+        (self.XRRFreeScreenResources)(resources);
+    }
+
+    pub unsafe fn monitors(
+        &self,
+        display: *mut Display,
+        root: Window,
+        dpi_scale: f32,
+    ) -> Vec<crate::Monitor> {
+        let mut monitors = vec![];
+        self.for_each_output(display, root, |resources, output_info, crtc_info| {
+            let name =
+                std::slice::from_raw_parts(output_info.name as *const u8, output_info.nameLen as _);
+            let modes = output_modes(resources, output_info, crtc_info.rotation);
+            let refresh_rate = modes
+                .iter()
+                .find(|(id, _)| *id == crtc_info.mode)
+                .and_then(|(_, mode)| mode.refresh_rate);
+            let mut video_modes: Vec<crate::VideoMode> = vec![];
+            for (_, mode) in modes {
+                if !video_modes.contains(&mode) {
+                    video_modes.push(mode);
+                }
+            }
+            monitors.push(crate::Monitor {
+                name: String::from_utf8_lossy(name).into_owned(),
+                position: (crtc_info.x, crtc_info.y),
+                physical_size: (output_info.mm_width as _, output_info.mm_height as _),
+                // already rotated, unlike the mode size
+                resolution: (crtc_info.width, crtc_info.height),
+                refresh_rate,
+                video_modes,
+                dpi_scale,
+            });
+        });
+        monitors
+    }
+
+    /// Switch the `monitor`-th output to the mode with the requested resolution
+    /// and the closest refresh rate, the highest one without a requested rate.
+    /// The previous mode is restored by `restore` or on drop.
+    pub unsafe fn set_video_mode(
+        &mut self,
+        display: *mut Display,
+        root: Window,
+        monitor: usize,
+        mode: crate::VideoMode,
+    ) -> bool {
+        self.restore();
+
+        let mut saved = None;
+        let mut index = 0;
+        self.for_each_output(display, root, |resources, output_info, crtc_info| {
+            index += 1;
+            if index - 1 != monitor {
+                return;
+            }
+            let distance =
+                |candidate: &crate::VideoMode| match (mode.refresh_rate, candidate.refresh_rate) {
+                    (Some(requested), Some(rate)) => (requested - rate).abs(),
+                    (None, Some(rate)) => -rate,
+                    _ => f32::MAX,
+                };
+            let best = output_modes(resources, output_info, crtc_info.rotation)
+                .into_iter()
+                .filter(|(_, candidate)| candidate.resolution == mode.resolution)
+                .min_by(|(_, a), (_, b)| distance(a).partial_cmp(&distance(b)).unwrap());
+            let id = match best {
+                Some((id, _)) => id,
+                None => return,
+            };
+
+            let mut outputs =
+                std::slice::from_raw_parts(crtc_info.outputs, crtc_info.noutput as _).to_vec();
+            let res = (self.XRRSetCrtcConfig)(
+                display,
+                resources,
+                output_info.crtc,
+                CurrentTime,
+                crtc_info.x,
+                crtc_info.y,
+                id,
+                crtc_info.rotation,
+                outputs.as_mut_ptr(),
+                outputs.len() as _,
+            );
+            // RRSetConfigSuccess
+            if res == 0 {
+                saved = Some(SavedCrtc {
+                    display,
+                    root,
+                    crtc: output_info.crtc,
+                    x: crtc_info.x,
+                    y: crtc_info.y,
+                    mode: crtc_info.mode,
+                    rotation: crtc_info.rotation,
+                    outputs,
+                });
+            }
+        });
+
+        self.saved = saved;
+        self.saved.is_some()
+    }
+
+    /// Put back the mode from before `set_video_mode`, if any.
+    pub unsafe fn restore(&mut self) {
+        let mut saved = match self.saved.take() {
+            Some(saved) => saved,
+            None => return,
+        };
+        let resources = (self.XRRGetScreenResourcesCurrent)(saved.display, saved.root);
+        if resources.is_null() {
+            return;
+        }
+        (self.XRRSetCrtcConfig)(
+            saved.display,
+            resources,
+            saved.crtc,
+            CurrentTime,
+            saved.x,
+            saved.y,
+            saved.mode,
+            saved.rotation,
+            saved.outputs.as_mut_ptr(),
+            saved.outputs.len() as _,
+        );
+        (self.XRRFreeScreenResources)(resources);
+    }
+}
+
+// X keeps the mode after the process is gone, this covers the panics
+// unwinding through the event loop
+impl Drop for LibXrandr {
+    fn drop(&mut self) {
+        unsafe { self.restore() };
+    }
+}
+
+/// The output's modes, with the resolution rotated like the crtc.
+unsafe fn output_modes(
+    resources: *mut XRRScreenResources,
+    output_info: &XRROutputInfo,
+    rotation: libc::c_ushort,
+) -> Vec<(RRMode, crate::VideoMode)> {
+    let modes = std::slice::from_raw_parts((*resources).modes, (*resources).nmode as _);
+    let ids = std::slice::from_raw_parts(output_info.modes, output_info.nmode as _);
+    ids.iter()
+        .filter_map(|id| modes.iter().find(|mode| mode.id == *id))
+        .map(|mode| {
+            let resolution = if rotation & (RR_Rotate_90 | RR_Rotate_270) != 0 {
+                (mode.height, mode.width)
+            } else {
+                (mode.width, mode.height)
+            };
+            let video_mode = crate::VideoMode {
+                resolution,
+                refresh_rate: mode_refresh_rate(mode),
+            };
+            (mode.id, video_mode)
+        })
+        .collect()
+}
+
+fn mode_refresh_rate(mode: &XRRModeInfo) -> Option<f32> {
+    let mut v_total = mode.vTotal as f32;
+    if mode.modeFlags & RR_DoubleScan != 0 {
+        v_total *= 2.;
+    }
+    if mode.modeFlags & RR_Interlace != 0 {
+        v_total /= 2.;
+    }
+    if mode.hTotal == 0 || v_total == 0. {
+        return None;
+    }
+    Some(mode.dotClock as f32 / (mode.hTotal as f32 * v_total))
+}
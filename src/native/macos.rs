@@ -5,20 +5,33 @@
 use {
     crate::{
         conf::{AppleGfxApi, Icon},
-        event::{EventHandler, MouseButton},
+        event::{EventHandler, MouseButton, PenState, ScrollDelta},
         native::{
             apple::{apple_util::*, frameworks::*},
-            gl, NativeDisplayData, Request,
+            frame_limiter::FrameLimiter,
+            gl,
+            scancodes::keycode_to_scancode,
+            NativeDisplayData, Request, SizeConstraints,
         },
-        native_display, CursorIcon,
+        native_display, CursorIcon, Menu, MenuItem, WindowState,
     },
-    std::{collections::HashMap, os::raw::c_void, sync::mpsc::Receiver},
+    std::{collections::HashMap, os::raw::c_void, sync::mpsc::Receiver, time::Duration},
 };
 
+// what set_exclusive_fullscreen changed, to put it back
+struct ExclusiveFullscreen {
+    display_id: u32,
+    // retained
+    mode: ObjcId,
+    style_mask: u64,
+    frame: NSRect,
+}
+
 pub struct MacosDisplay {
     window: ObjcId,
     view: ObjcId,
     fullscreen: bool,
+    exclusive: Option<ExclusiveFullscreen>,
     // [NSCursor hide]/unhide calls should be balanced
     // hide/hide/unhide will keep cursor hidden
     // so need to keep internal cursor state to avoid problems from
@@ -27,7 +40,36 @@ pub struct MacosDisplay {
     current_cursor: CursorIcon,
     cursor_grabbed: bool,
     cursors: HashMap<CursorIcon, ObjcId>,
+    // set_mouse_cursor_from_rgba, takes priority over current_cursor
+    custom_cursor: Option<ObjcId>,
     gfx_api: crate::conf::AppleGfxApi,
+    // for the GL context, created later in prepareOpenGL
+    swap_interval: i32,
+    frame_limiter: FrameLimiter,
+    // conf.wait_for_events, and whether there were events since the last frame
+    wait_for_events: bool,
+    had_events: bool,
+    decorated: bool,
+    resizable: bool,
+    always_on_top: bool,
+    // NSTextInputClient state: the utf-16 length of the marked text while
+    // composing, the keyDown being interpreted, with its mods and repeat,
+    // until insertText: takes it, and set_ime_cursor_area, in mouse pixels
+    marked_text: Option<u64>,
+    key_text: Option<(crate::KeyMods, bool)>,
+    ime_cursor_area: (f32, f32, f32, f32),
+    // window::set_text_input_enabled
+    text_input: bool,
+    // the tablet pen in proximity is an eraser
+    pen_eraser: bool,
+    gamepads: crate::native::apple::gamepad::Gamepads,
+    power: crate::native::apple::power::Power,
+    // the IOKit power assertion of window::set_keep_screen_on
+    sleep_assertion: Option<u32>,
+    fullsize_content_view: bool,
+    traffic_light_position: Option<(f32, f32)>,
+    // window::set_dock_menu, nil for none
+    dock_menu: ObjcId,
 
     event_handler: Option<Box<dyn EventHandler>>,
     f: Option<Box<dyn 'static + FnOnce() -> Box<dyn EventHandler>>>,
@@ -68,7 +110,13 @@ impl MacosDisplay {
         self.cursor_shown = show;
     }
     fn set_mouse_cursor(&mut self, cursor: crate::CursorIcon) {
-        if self.current_cursor != cursor {
+        let custom_cursor = self.custom_cursor.take();
+        if let Some(custom_cursor) = custom_cursor {
+            unsafe {
+                let () = msg_send![custom_cursor, release];
+            }
+        }
+        if self.current_cursor != cursor || custom_cursor.is_some() {
             self.current_cursor = cursor;
             unsafe {
                 let _: () = msg_send![
@@ -89,6 +137,7 @@ impl MacosDisplay {
         let () = unsafe { msg_send![self.window, setFrame:frame display:true animate:true] };
     }
     fn set_fullscreen(&mut self, fullscreen: bool) {
+        unsafe { self.restore_video_mode() };
         if self.fullscreen != fullscreen {
             self.fullscreen = fullscreen;
             unsafe {
@@ -96,32 +145,268 @@ impl MacosDisplay {
             }
         }
     }
-    fn clipboard_get(&mut self) -> Option<String> {
-        unsafe {
-            let pasteboard: ObjcId = msg_send![class!(NSPasteboard), generalPasteboard];
-            let content: ObjcId = msg_send![pasteboard, stringForType: NSStringPboardType];
-            let string = nsstring_to_string(content);
-            if string.is_empty() {
-                return None;
+    unsafe fn set_exclusive_fullscreen(&mut self, monitor: usize, video_mode: crate::VideoMode) {
+        let screens: ObjcId = msg_send![class!(NSScreen), screens];
+        let count: u64 = msg_send![screens, count];
+        if monitor as u64 >= count {
+            eprintln!("No monitor {}", monitor);
+            return;
+        }
+        let screen: ObjcId = msg_send![screens, objectAtIndex: monitor as u64];
+        let display_id = screen_display_id(screen);
+        self.restore_video_mode();
+
+        // the matching resolution with the closest refresh rate, or the highest one
+        let mut best: Option<(ObjcId, f64)> = None;
+        let modes = CGDisplayCopyAllDisplayModes(display_id, nil);
+        let modes_count: u64 = msg_send![modes, count];
+        for i in 0..modes_count {
+            let mode: ObjcId = msg_send![modes, objectAtIndex: i];
+            let resolution = (
+                CGDisplayModeGetPixelWidth(mode) as u32,
+                CGDisplayModeGetPixelHeight(mode) as u32,
+            );
+            if resolution != video_mode.resolution {
+                continue;
+            }
+            let rate = CGDisplayModeGetRefreshRate(mode);
+            let score = match video_mode.refresh_rate {
+                Some(wanted) => -(rate - wanted as f64).abs(),
+                None => rate,
+            };
+            if best.map_or(true, |(_, best)| score > best) {
+                best = Some((mode, score));
             }
-            Some(string)
         }
+        let mode = best.map(|(mode, _)| CGDisplayModeRetain(mode));
+        let () = msg_send![modes, release];
+        let mode = match mode {
+            Some(mode) => mode,
+            None => {
+                eprintln!(
+                    "Failed to switch to {:?}, the mode is unchanged",
+                    video_mode
+                );
+                return;
+            }
+        };
+
+        let original = CGDisplayCopyDisplayMode(display_id);
+        // the capture is released by the system if the application quits or crashes
+        if CGDisplayCapture(display_id) != 0 {
+            eprintln!("Failed to capture the display");
+            CGDisplayModeRelease(original);
+            CGDisplayModeRelease(mode);
+            return;
+        }
+        let res = CGDisplaySetDisplayMode(display_id, mode, nil);
+        CGDisplayModeRelease(mode);
+        if res != 0 {
+            eprintln!(
+                "Failed to switch to {:?}, the mode is unchanged",
+                video_mode
+            );
+            CGDisplayRelease(display_id);
+            CGDisplayModeRelease(original);
+            return;
+        }
+
+        let style_mask: u64 = msg_send![self.window, styleMask];
+        let frame: NSRect = msg_send![self.window, frame];
+        self.exclusive = Some(ExclusiveFullscreen {
+            display_id,
+            mode: original,
+            style_mask,
+            frame,
+        });
+        // captured displays are covered by a shielding window, the window goes on top of it
+        let () = msg_send![
+            self.window,
+            setStyleMask: NSWindowStyleMask::NSBorderlessWindowMask as u64
+        ];
+        let () = msg_send![self.window, setLevel: CGShieldingWindowLevel() as i64];
+        let screen_frame: NSRect = msg_send![screen, frame];
+        let () = msg_send![self.window, setFrame: screen_frame display: YES];
     }
-    fn clipboard_set(&mut self, data: &str) {
-        let str: ObjcId = str_to_nsstring(data);
-        unsafe {
-            let pasteboard: ObjcId = msg_send![class!(NSPasteboard), generalPasteboard];
-            let () = msg_send![pasteboard, clearContents];
-            let arr: ObjcId = msg_send![class!(NSArray), arrayWithObject: str];
-            let () = msg_send![pasteboard, writeObjects: arr];
+    fn window_level(&self) -> i64 {
+        // NSFloatingWindowLevel or NSNormalWindowLevel
+        if self.always_on_top {
+            3
+        } else {
+            0
         }
     }
+    fn style_mask(&self) -> u64 {
+        let mut mask = NSWindowStyleMask::NSClosableWindowMask as u64
+            | NSWindowStyleMask::NSMiniaturizableWindowMask as u64;
+        if self.decorated {
+            mask |= NSWindowStyleMask::NSTitledWindowMask as u64;
+        }
+        if self.resizable {
+            mask |= NSWindowStyleMask::NSResizableWindowMask as u64;
+        }
+        if self.fullsize_content_view {
+            mask |= NSWindowStyleMask::NSFullSizeContentViewWindowMask as u64;
+        }
+        mask
+    }
+    /// `conf.platform.traffic_light_position`, again after every resize, the
+    /// titlebar puts the buttons back in their place.
+    unsafe fn position_traffic_lights(&self) {
+        let (x, y) = match self.traffic_light_position {
+            Some(position) => position,
+            None => return,
+        };
+        // NSWindowCloseButton, NSWindowMiniaturizeButton, NSWindowZoomButton
+        let close: ObjcId = msg_send![self.window, standardWindowButton: 0u64];
+        let miniaturize: ObjcId = msg_send![self.window, standardWindowButton: 1u64];
+        let zoom: ObjcId = msg_send![self.window, standardWindowButton: 2u64];
+        // borderless
+        if close.is_null() || miniaturize.is_null() || zoom.is_null() {
+            return;
+        }
 
+        // the buttons keep their distance to the top of the titlebar
+        // container, a taller one puts them lower
+        let titlebar: ObjcId = msg_send![close, superview];
+        let container: ObjcId = msg_send![titlebar, superview];
+        let close_frame: NSRect = msg_send![close, frame];
+        let window_frame: NSRect = msg_send![self.window, frame];
+        let mut container_frame: NSRect = msg_send![container, frame];
+        container_frame.size.height = close_frame.size.height + y as f64;
+        container_frame.origin.y = window_frame.size.height - container_frame.size.height;
+        let () = msg_send![container, setFrame: container_frame];
+
+        let miniaturize_frame: NSRect = msg_send![miniaturize, frame];
+        let spacing = miniaturize_frame.origin.x - close_frame.origin.x;
+        for (i, &button) in [close, miniaturize, zoom].iter().enumerate() {
+            let mut frame: NSRect = msg_send![button, frame];
+            frame.origin.x = x as f64 + i as f64 * spacing;
+            let () = msg_send![button, setFrameOrigin: frame.origin];
+        }
+    }
+    /// `window::set_decorations` and `set_resizable`, the content keeps its size.
+    /// Fullscreen has a style of its own, the new one is remembered for later.
+    unsafe fn update_style_mask(&mut self) {
+        let style_mask = self.style_mask();
+        if let Some(ref mut exclusive) = self.exclusive {
+            exclusive.style_mask = style_mask;
+            return;
+        }
+        if self.fullscreen {
+            return;
+        }
+        let frame: NSRect = msg_send![self.window, frame];
+        let content: NSRect = msg_send![self.window, contentRectForFrameRect: frame];
+        let () = msg_send![self.window, setStyleMask: style_mask];
+        let frame: NSRect = msg_send![self.window, frameRectForContentRect: content];
+        let () = msg_send![self.window, setFrame: frame display: YES];
+    }
+    unsafe fn set_swap_interval(&mut self, interval: i32) {
+        self.swap_interval = interval;
+        match self.gfx_api {
+            AppleGfxApi::OpenGl => {
+                let mut interval = interval;
+                let ctx: ObjcId = msg_send![self.view, openGLContext];
+                let () = msg_send![ctx,
+                                   setValues:&mut interval
+                                   forParameter:NSOpenGLContextParameterSwapInterval];
+            }
+            // there is only on or off for metal, 10.13+
+            AppleGfxApi::Metal => {
+                let layer: ObjcId = msg_send![self.view, layer];
+                let has_sync: BOOL =
+                    msg_send![layer, respondsToSelector: sel!(setDisplaySyncEnabled:)];
+                if has_sync == YES {
+                    let enabled = if interval != 0 { YES } else { NO };
+                    let () = msg_send![layer, setDisplaySyncEnabled: enabled];
+                }
+            }
+        }
+    }
+    unsafe fn set_size_constraints(&mut self, constraints: SizeConstraints) {
+        let size = |size: Option<(u32, u32)>, default: f64| match size {
+            Some((width, height)) => NSSize {
+                width: width as f64,
+                height: height as f64,
+            },
+            None => NSSize {
+                width: default,
+                height: default,
+            },
+        };
+        // FLT_MAX is the NSWindow default, no limit
+        let (min, max) = (
+            size(constraints.min, 0.),
+            size(constraints.max, f32::MAX as f64),
+        );
+        let () = msg_send![self.window, setContentMinSize: min];
+        let () = msg_send![self.window, setContentMaxSize: max];
+        match constraints.aspect {
+            Some(aspect) => {
+                let () = msg_send![self.window, setContentAspectRatio: size(Some(aspect), 1.)];
+            }
+            // resize increments are the only way to clear the aspect ratio
+            None => {
+                let () = msg_send![self.window, setContentResizeIncrements: size(None, 1.)];
+            }
+        }
+    }
+    unsafe fn restore_video_mode(&mut self) {
+        if let Some(exclusive) = self.exclusive.take() {
+            CGDisplaySetDisplayMode(exclusive.display_id, exclusive.mode, nil);
+            CGDisplayModeRelease(exclusive.mode);
+            CGDisplayRelease(exclusive.display_id);
+            let () = msg_send![self.window, setLevel: self.window_level()];
+            let () = msg_send![self.window, setStyleMask: exclusive.style_mask];
+            let () = msg_send![self.window, setFrame: exclusive.frame display: YES];
+        }
+    }
+    /// The event handler, for the events, they are what makes the
+    /// frames with `conf.wait_for_events`.
     pub fn context(&mut self) -> Option<&mut dyn EventHandler> {
+        self.had_events = true;
         let event_handler = self.event_handler.as_deref_mut()?;
 
         Some(event_handler)
     }
+    fn frame_due(&mut self) -> bool {
+        if !self.wait_for_events {
+            return true;
+        }
+        let timeout = native_display().lock().unwrap().frame_timeout();
+        std::mem::replace(&mut self.had_events, false) || timeout == Some(Duration::ZERO)
+    }
+}
+
+unsafe fn screen_display_id(screen: ObjcId) -> u32 {
+    let description: ObjcId = msg_send![screen, deviceDescription];
+    let number: ObjcId = msg_send![description, objectForKey: str_to_nsstring("NSScreenNumber")];
+    msg_send![number, unsignedIntValue]
+}
+
+// effectiveAppearance, macOS 10.14 and newer, light before
+unsafe fn system_theme() -> crate::Theme {
+    let app: ObjcId = msg_send![class!(NSApplication), sharedApplication];
+    let responds: BOOL = msg_send![app, respondsToSelector: sel!(effectiveAppearance)];
+    if responds != YES {
+        return crate::Theme::Light;
+    }
+    let appearance: ObjcId = msg_send![app, effectiveAppearance];
+    let dark_aqua = str_to_nsstring("NSAppearanceNameDarkAqua");
+    let names = [str_to_nsstring("NSAppearanceNameAqua"), dark_aqua];
+    let names: ObjcId = msg_send![
+        class!(NSArray),
+        arrayWithObjects: names.as_ptr()
+        count: names.len()
+    ];
+    let name: ObjcId = msg_send![appearance, bestMatchFromAppearancesWithNames: names];
+    let dark: BOOL = msg_send![name, isEqualToString: dark_aqua];
+    if dark == YES {
+        crate::Theme::Dark
+    } else {
+        crate::Theme::Light
+    }
 }
 
 impl MacosDisplay {
@@ -145,16 +430,120 @@ impl MacosDisplay {
         }
     }
 
-    unsafe fn update_dimensions(&mut self) -> Option<(i32, i32)> {
-        let mut d = native_display().lock().unwrap();
-        if d.high_dpi {
-            let screen: ObjcId = msg_send![self.window, screen];
-            let dpi_scale: f64 = msg_send![screen, backingScaleFactor];
-            d.dpi_scale = dpi_scale as f32;
-        } else {
-            d.dpi_scale = 1.0;
+    fn set_mouse_position(&self, x: f32, y: f32) {
+        unsafe {
+            let dpi_scale = native_display().lock().unwrap().dpi_scale as f64;
+            let bounds: NSRect = msg_send![self.view, bounds];
+            // back from the transform_mouse_point coordinates to the view ones
+            let point = NSPoint {
+                x: x as f64 / dpi_scale,
+                y: bounds.size.height - (y as f64 + 1.) / dpi_scale,
+            };
+            let point: NSPoint = msg_send![self.view, convertPoint: point toView: nil];
+            let rect = NSRect {
+                origin: point,
+                size: NSSize {
+                    width: 0.,
+                    height: 0.,
+                },
+            };
+            let rect: NSRect = msg_send![self.window, convertRectToScreen: rect];
+            // CoreGraphics global coordinates have the origin at the top left
+            // of the main screen
+            let screens: ObjcId = msg_send![class!(NSScreen), screens];
+            let main_screen: ObjcId = msg_send![screens, objectAtIndex: 0];
+            let main_frame: NSRect = msg_send![main_screen, frame];
+            CGWarpMouseCursorPosition(NSPoint {
+                x: rect.origin.x,
+                y: main_frame.size.height - rect.origin.y,
+            });
         }
+    }
 
+    /// The top left corner of the window, in points from the top left
+    /// corner of the first screen, like the `Monitor` positions.
+    unsafe fn window_position(&mut self) -> (u32, u32) {
+        let screens: ObjcId = msg_send![class!(NSScreen), screens];
+        let primary: ObjcId = msg_send![screens, objectAtIndex: 0u64];
+        let primary_frame: NSRect = msg_send![primary, frame];
+        let frame: NSRect = msg_send![self.window, frame];
+        let top = primary_frame.size.height - (frame.origin.y + frame.size.height);
+        (frame.origin.x as i32 as u32, top as i32 as u32)
+    }
+    unsafe fn update_window_state(&mut self) {
+        let miniaturized: BOOL = msg_send![self.window, isMiniaturized];
+        let zoomed: BOOL = msg_send![self.window, isZoomed];
+        // a fullscreen window is zoomed too
+        let state = if miniaturized == YES {
+            WindowState::Minimized
+        } else if zoomed == YES && !self.fullscreen {
+            WindowState::Maximized
+        } else {
+            WindowState::Normal
+        };
+        if let Some(event_handler) = self.context() {
+            crate::native::update_window_state(state, event_handler);
+        }
+    }
+    // released by the OS when the process exits
+    unsafe fn set_keep_screen_on(&mut self, keep_on: bool) {
+        match (keep_on, self.sleep_assertion) {
+            (true, None) => {
+                let mut assertion = 0;
+                let result = IOPMAssertionCreateWithName(
+                    str_to_nsstring("PreventUserIdleDisplaySleep"),
+                    kIOPMAssertionLevelOn,
+                    str_to_nsstring("keep_screen_on"),
+                    &mut assertion,
+                );
+                if result == 0 {
+                    self.sleep_assertion = Some(assertion);
+                } else {
+                    eprintln!("Failed to create the power assertion: {}", result);
+                }
+            }
+            (false, Some(assertion)) => {
+                IOPMAssertionRelease(assertion);
+                self.sleep_assertion = None;
+            }
+            _ => {}
+        }
+    }
+    unsafe fn set_window_state(&mut self, state: WindowState) {
+        let miniaturized: BOOL = msg_send![self.window, isMiniaturized];
+        if miniaturized == YES && state != WindowState::Minimized {
+            let () = msg_send![self.window, deminiaturize: nil];
+        }
+        let zoomed: BOOL = msg_send![self.window, isZoomed];
+        match state {
+            WindowState::Minimized => {
+                let () = msg_send![self.window, miniaturize: nil];
+            }
+            // zoom: toggles, deminiaturizing is enough to restore a minimized window
+            WindowState::Maximized if zoomed == NO => {
+                let () = msg_send![self.window, zoom: nil];
+            }
+            WindowState::Normal if zoomed == YES && miniaturized == NO => {
+                let () = msg_send![self.window, zoom: nil];
+            }
+            _ => {}
+        }
+    }
+    /// backingScaleFactor of the window, 1 without high_dpi.
+    unsafe fn update_dpi_scale(&mut self) {
+        let dpi_scale = if native_display().lock().unwrap().high_dpi {
+            let dpi_scale: f64 = msg_send![self.window, backingScaleFactor];
+            dpi_scale as f32
+        } else {
+            1.0
+        };
+        match self.context() {
+            Some(event_handler) => crate::native::update_dpi_scale(dpi_scale, event_handler),
+            None => native_display().lock().unwrap().dpi_scale = dpi_scale,
+        }
+    }
+    unsafe fn update_dimensions(&mut self) -> Option<(i32, i32)> {
+        let mut d = native_display().lock().unwrap();
         let bounds: NSRect = msg_send![self.view, bounds];
         let screen_width = (bounds.size.width as f32 * d.dpi_scale) as i32;
         let screen_height = (bounds.size.height as f32 * d.dpi_scale) as i32;
@@ -171,11 +560,104 @@ impl MacosDisplay {
         }
     }
 
+    /// [NSScreen screens], the first one is the screen with the menu bar.
+    unsafe fn update_monitors(&mut self) {
+        let screens: ObjcId = msg_send![class!(NSScreen), screens];
+        let count: u64 = msg_send![screens, count];
+        let window_screen: ObjcId = msg_send![self.window, screen];
+
+        let mut monitors = vec![];
+        let mut current_monitor = None;
+        // frames are bottom-up, from the bottom left corner of the first screen
+        let mut primary_height = 0.;
+        for i in 0..count {
+            let screen: ObjcId = msg_send![screens, objectAtIndex: i];
+            let frame: NSRect = msg_send![screen, frame];
+            if i == 0 {
+                primary_height = frame.size.height;
+            }
+            let dpi_scale: f64 = msg_send![screen, backingScaleFactor];
+
+            let display_id = screen_display_id(screen);
+
+            // 10.15+
+            let has_name: BOOL = msg_send![screen, respondsToSelector: sel!(localizedName)];
+            let name = if has_name == YES {
+                nsstring_to_string(msg_send![screen, localizedName])
+            } else {
+                String::new()
+            };
+
+            let mut resolution = (
+                (frame.size.width * dpi_scale) as u32,
+                (frame.size.height * dpi_scale) as u32,
+            );
+            let mut refresh_rate = None;
+            let mode = CGDisplayCopyDisplayMode(display_id);
+            if !mode.is_null() {
+                resolution = (
+                    CGDisplayModeGetPixelWidth(mode) as u32,
+                    CGDisplayModeGetPixelHeight(mode) as u32,
+                );
+                // 0 for the built-in displays without a fixed rate
+                let rate = CGDisplayModeGetRefreshRate(mode);
+                if rate > 0. {
+                    refresh_rate = Some(rate as f32);
+                }
+                CGDisplayModeRelease(mode);
+            }
+            let size = CGDisplayScreenSize(display_id);
+
+            let mut video_modes: Vec<crate::VideoMode> = vec![];
+            let modes = CGDisplayCopyAllDisplayModes(display_id, nil);
+            let modes_count: u64 = msg_send![modes, count];
+            for i in 0..modes_count {
+                let mode: ObjcId = msg_send![modes, objectAtIndex: i];
+                let rate = CGDisplayModeGetRefreshRate(mode);
+                let video_mode = crate::VideoMode {
+                    resolution: (
+                        CGDisplayModeGetPixelWidth(mode) as u32,
+                        CGDisplayModeGetPixelHeight(mode) as u32,
+                    ),
+                    refresh_rate: Some(rate as f32).filter(|rate| *rate > 0.),
+                };
+                // scaled modes have the same pixel size as the native ones
+                if !video_modes.contains(&video_mode) {
+                    video_modes.push(video_mode);
+                }
+            }
+            let () = msg_send![modes, release];
+
+            if screen == window_screen {
+                current_monitor = Some(monitors.len());
+            }
+            monitors.push(crate::Monitor {
+                name,
+                position: (
+                    frame.origin.x as i32,
+                    (primary_height - frame.origin.y - frame.size.height) as i32,
+                ),
+                physical_size: (size.width as u32, size.height as u32),
+                resolution,
+                refresh_rate,
+                video_modes,
+                dpi_scale: dpi_scale as f32,
+            });
+        }
+
+        let mut d = native_display().lock().unwrap();
+        d.monitors = monitors;
+        d.current_monitor = current_monitor;
+    }
+
     fn process_request(&mut self, request: Request) {
         use Request::*;
         unsafe {
             match request {
-                SetCursorGrab(grab) => self.set_cursor_grab(self.window, grab),
+                // macOS can't confine the cursor, both modes lock it in place
+                SetCursorGrab(mode) => {
+                    self.set_cursor_grab(self.window, mode != crate::CursorGrabMode::None)
+                }
                 ShowMouse(show) => self.show_mouse(show),
                 SetMouseCursor(icon) => self.set_mouse_cursor(icon),
                 SetWindowSize {
@@ -183,6 +665,127 @@ impl MacosDisplay {
                     new_height,
                 } => self.set_window_size(new_width as _, new_height as _),
                 SetFullscreen(fullscreen) => self.set_fullscreen(fullscreen),
+                SetExclusiveFullscreen { monitor, mode } => {
+                    self.set_exclusive_fullscreen(monitor, mode)
+                }
+                SetSwapInterval(interval) => self.set_swap_interval(interval),
+                SetSizeConstraints(constraints) => self.set_size_constraints(constraints),
+                SetWindowState(state) => self.set_window_state(state),
+                SetDecorations(decorations) => {
+                    self.decorated = decorations;
+                    self.update_style_mask();
+                }
+                SetResizable(resizable) => {
+                    self.resizable = resizable;
+                    self.update_style_mask();
+                }
+                SetAlwaysOnTop(always_on_top) => {
+                    self.always_on_top = always_on_top;
+                    // exclusive fullscreen is above everything already
+                    if self.exclusive.is_none() {
+                        let () = msg_send![self.window, setLevel: self.window_level()];
+                    }
+                }
+                SetOpacity(opacity) => {
+                    let () = msg_send![self.window, setAlphaValue: opacity as f64];
+                }
+                RequestUserAttention(attention) => {
+                    // NSCriticalRequest or NSInformationalRequest, canceled
+                    // when the app is activated, ignored while it is active
+                    let request_type: u64 = match attention {
+                        crate::UserAttentionType::Critical => 0,
+                        crate::UserAttentionType::Informational => 10,
+                    };
+                    let ns_app: ObjcId = msg_send![class!(NSApplication), sharedApplication];
+                    let _: i64 = msg_send![ns_app, requestUserAttention: request_type];
+                }
+                SetWindowIcon {
+                    rgba,
+                    width,
+                    height,
+                } => {
+                    let ns_app: ObjcId = msg_send![class!(NSApplication), sharedApplication];
+                    set_icon(ns_app, width as _, height as _, &rgba);
+                }
+                SetCustomMouseCursor {
+                    rgba,
+                    width,
+                    height,
+                    hotspot,
+                } => {
+                    let ns_image = ns_image_from_rgba(width as _, height as _, &rgba);
+                    let hotspot = NSPoint {
+                        x: hotspot.0 as f64,
+                        y: hotspot.1 as f64,
+                    };
+                    let cursor: ObjcId = msg_send![class!(NSCursor), alloc];
+                    let cursor: ObjcId =
+                        msg_send![cursor, initWithImage: ns_image hotSpot: hotspot];
+                    let () = msg_send![ns_image, release];
+                    if let Some(old) = self.custom_cursor.replace(cursor) {
+                        let () = msg_send![old, release];
+                    }
+                    let _: () = msg_send![
+                        self.window,
+                        invalidateCursorRectsForView: self.view
+                    ];
+                }
+                SetMousePosition { x, y } => self.set_mouse_position(x, y),
+                SetImeCursorArea {
+                    x,
+                    y,
+                    width,
+                    height,
+                } => self.ime_cursor_area = (x, y, width, height),
+                SetTextInputEnabled(enabled) => {
+                    self.text_input = enabled;
+                    if !enabled && self.marked_text.take().is_some() {
+                        let input_context: ObjcId = msg_send![self.view, inputContext];
+                        let () = msg_send![input_context, discardMarkedText];
+                        if let Some(event_handler) = self.context() {
+                            event_handler.ime_composition_event("", 0);
+                        }
+                    }
+                }
+                SetGamepadRumble {
+                    id,
+                    low_frequency,
+                    high_frequency,
+                    duration,
+                } => self
+                    .gamepads
+                    .rumble(id, low_frequency, high_frequency, duration),
+                SetKeepScreenOn(keep_on) => self.set_keep_screen_on(keep_on),
+                SetMenus(menus) => {
+                    let ns_app: ObjcId = msg_send![class!(NSApplication), sharedApplication];
+                    let menu_bar: ObjcId = msg_send![ns_app, mainMenu];
+                    let target: ObjcId = msg_send![ns_app, delegate];
+                    // the application and Edit menus stay
+                    loop {
+                        let count: i64 = msg_send![menu_bar, numberOfItems];
+                        if count <= 2 {
+                            break;
+                        }
+                        let () = msg_send![menu_bar, removeItemAtIndex: count - 1];
+                    }
+                    for menu in &menus {
+                        let item = create_submenu_item(menu, target);
+                        let () = msg_send![menu_bar, addItem: item];
+                        let () = msg_send![item, release];
+                    }
+                }
+                SetDockMenu(items) => {
+                    let ns_app: ObjcId = msg_send![class!(NSApplication), sharedApplication];
+                    let target: ObjcId = msg_send![ns_app, delegate];
+                    if !self.dock_menu.is_null() {
+                        let () = msg_send![self.dock_menu, release];
+                    }
+                    let menu = Menu {
+                        title: String::new(),
+                        items,
+                    };
+                    self.dock_menu = create_menu(&menu, target);
+                }
                 _ => {}
             }
         }
@@ -227,7 +830,153 @@ impl Modifiers {
         }
     }
 }
+/// The close button, Cmd+Q and Quit from the Dock, true to quit.
+fn quit_requested(payload: &mut MacosDisplay) -> bool {
+    // only give user-code a chance to intervene when sapp_quit() wasn't already called
+    if !native_display().lock().unwrap().quit_ordered {
+        // if window should be closed and event handling is enabled, give user code
+        // a chance to intervene via sapp_cancel_quit()
+        native_display().lock().unwrap().quit_requested = true;
+        if let Some(event_handler) = payload.context() {
+            event_handler.quit_requested_event();
+        }
+
+        // user code hasn't intervened, quit the app
+        if native_display().lock().unwrap().quit_requested {
+            native_display().lock().unwrap().quit_ordered = true;
+        }
+    }
+    native_display().lock().unwrap().quit_ordered
+}
+
+/// The items send menuItemSelected: to `target`, the application delegate,
+/// with their id as the tag.
+unsafe fn create_menu(menu: &Menu, target: ObjcId) -> ObjcId {
+    let ns_menu: ObjcId = msg_send![class!(NSMenu), alloc];
+    let ns_menu: ObjcId = msg_send![ns_menu, initWithTitle: str_to_nsstring(&menu.title)];
+    for item in &menu.items {
+        let ns_item: ObjcId = match item {
+            MenuItem::Action {
+                id,
+                title,
+                shortcut,
+            } => {
+                let key = shortcut.map(|key| key.to_string()).unwrap_or_default();
+                let ns_item: ObjcId = msg_send![class!(NSMenuItem), alloc];
+                let ns_item: ObjcId = msg_send![
+                    ns_item,
+                    initWithTitle: str_to_nsstring(title)
+                    action: sel!(menuItemSelected:)
+                    keyEquivalent: str_to_nsstring(&key)
+                ];
+                let () = msg_send![ns_item, setTarget: target];
+                let () = msg_send![ns_item, setTag: *id as i64];
+                ns_item
+            }
+            MenuItem::Separator => {
+                let ns_item: ObjcId = msg_send![class!(NSMenuItem), separatorItem];
+                msg_send![ns_item, retain]
+            }
+            MenuItem::Submenu(menu) => create_submenu_item(menu, target),
+        };
+        let () = msg_send![ns_menu, addItem: ns_item];
+        let () = msg_send![ns_item, release];
+    }
+    ns_menu
+}
+
+unsafe fn create_submenu_item(menu: &Menu, target: ObjcId) -> ObjcId {
+    let item: ObjcId = msg_send![class!(NSMenuItem), new];
+    let () = msg_send![item, setTitle: str_to_nsstring(&menu.title)];
+    let submenu = create_menu(menu, target);
+    let () = msg_send![item, setSubmenu: submenu];
+    let () = msg_send![submenu, release];
+    item
+}
+
+/// The application menu and the Edit menu, the actions go to the first
+/// responder, so the clipboard shortcuts work in the text fields of the
+/// dialogs and do nothing in the view.
+unsafe fn create_menu_bar(app_name: &str) -> ObjcId {
+    unsafe fn add_item(menu: ObjcId, title: &str, action: Sel, key: &str) -> ObjcId {
+        msg_send![
+            menu,
+            addItemWithTitle: str_to_nsstring(title)
+            action: action
+            keyEquivalent: str_to_nsstring(key)
+        ]
+    }
+    unsafe fn add_separator(menu: ObjcId) {
+        let separator: ObjcId = msg_send![class!(NSMenuItem), separatorItem];
+        let () = msg_send![menu, addItem: separator];
+    }
+
+    let menu_bar: ObjcId = msg_send![class!(NSMenu), new];
+
+    let app_menu_item: ObjcId = msg_send![class!(NSMenuItem), new];
+    let () = msg_send![menu_bar, addItem: app_menu_item];
+    let app_menu: ObjcId = msg_send![class!(NSMenu), new];
+    let about = format!("About {}", app_name);
+    add_item(app_menu, &about, sel!(orderFrontStandardAboutPanel:), "");
+    add_separator(app_menu);
+    let hide = format!("Hide {}", app_name);
+    add_item(app_menu, &hide, sel!(hide:), "h");
+    let hide_others = add_item(app_menu, "Hide Others", sel!(hideOtherApplications:), "h");
+    let () = msg_send![
+        hide_others,
+        setKeyEquivalentModifierMask: NSEventModifierFlags::NSCommandKeyMask as u64
+            | NSEventModifierFlags::NSAlternateKeyMask as u64
+    ];
+    add_item(app_menu, "Show All", sel!(unhideAllApplications:), "");
+    add_separator(app_menu);
+    let quit = format!("Quit {}", app_name);
+    add_item(app_menu, &quit, sel!(terminate:), "q");
+    let () = msg_send![app_menu_item, setSubmenu: app_menu];
+
+    let edit_menu_item: ObjcId = msg_send![class!(NSMenuItem), new];
+    let () = msg_send![menu_bar, addItem: edit_menu_item];
+    let edit_menu: ObjcId = msg_send![class!(NSMenu), alloc];
+    let edit_menu: ObjcId = msg_send![edit_menu, initWithTitle: str_to_nsstring("Edit")];
+    add_item(edit_menu, "Undo", sel!(undo:), "z");
+    add_item(edit_menu, "Redo", sel!(redo:), "Z");
+    add_separator(edit_menu);
+    add_item(edit_menu, "Cut", sel!(cut:), "x");
+    add_item(edit_menu, "Copy", sel!(copy:), "c");
+    add_item(edit_menu, "Paste", sel!(paste:), "v");
+    add_item(edit_menu, "Select All", sel!(selectAll:), "a");
+    let () = msg_send![edit_menu_item, setSubmenu: edit_menu];
+
+    menu_bar
+}
+
 pub fn define_app_delegate() -> *const Class {
+    // NSApplicationTerminateReply
+    const NS_TERMINATE_CANCEL: u64 = 0;
+    const NS_TERMINATE_NOW: u64 = 1;
+
+    extern "C" fn application_should_terminate(this: &Object, _: Sel, _: ObjcId) -> u64 {
+        if quit_requested(get_window_payload(this)) {
+            NS_TERMINATE_NOW
+        } else {
+            NS_TERMINATE_CANCEL
+        }
+    }
+    // terminate: exits the process right after, this is the last chance
+    // for the Drop of the event handler
+    extern "C" fn application_will_terminate(this: &Object, _: Sel, _: ObjcId) {
+        drop(get_window_payload(this).event_handler.take());
+    }
+    extern "C" fn menu_item_selected(this: &Object, _: Sel, item: ObjcId) {
+        let id: i64 = unsafe { msg_send![item, tag] };
+        if let Some(event_handler) = get_window_payload(this).context() {
+            event_handler.menu_event(id as u32);
+        }
+    }
+    // the standard items come after these
+    extern "C" fn application_dock_menu(this: &Object, _: Sel, _: ObjcId) -> ObjcId {
+        get_window_payload(this).dock_menu
+    }
+
     let superclass = class!(NSObject);
     let mut decl = ClassDecl::new("NSAppDelegate", superclass).unwrap();
     unsafe {
@@ -235,6 +984,41 @@ pub fn define_app_delegate() -> *const Class {
             sel!(applicationShouldTerminateAfterLastWindowClosed:),
             yes1 as extern "C" fn(&Object, Sel, ObjcId) -> BOOL,
         );
+        decl.add_method(
+            sel!(applicationShouldTerminate:),
+            application_should_terminate as extern "C" fn(&Object, Sel, ObjcId) -> u64,
+        );
+        decl.add_method(
+            sel!(applicationWillTerminate:),
+            application_will_terminate as extern "C" fn(&Object, Sel, ObjcId),
+        );
+        decl.add_method(
+            sel!(menuItemSelected:),
+            menu_item_selected as extern "C" fn(&Object, Sel, ObjcId),
+        );
+        decl.add_method(
+            sel!(applicationDockMenu:),
+            application_dock_menu as extern "C" fn(&Object, Sel, ObjcId) -> ObjcId,
+        );
+    }
+    decl.add_ivar::<*mut c_void>("display_ptr");
+
+    return decl.register();
+}
+
+/// Borderless NSWindows can't become key by default, no keyboard for them.
+pub fn define_cocoa_window() -> *const Class {
+    let superclass = class!(NSWindow);
+    let mut decl = ClassDecl::new("RenderWindow", superclass).unwrap();
+    unsafe {
+        decl.add_method(
+            sel!(canBecomeKeyWindow),
+            yes as extern "C" fn(&Object, Sel) -> BOOL,
+        );
+        decl.add_method(
+            sel!(canBecomeMainWindow),
+            yes as extern "C" fn(&Object, Sel) -> BOOL,
+        );
     }
 
     return decl.register();
@@ -249,24 +1033,10 @@ pub fn define_cocoa_window_delegate() -> *const Class {
             msg_send_![capture_manager, stopCapture];
         }
 
-        // only give user-code a chance to intervene when sapp_quit() wasn't already called
-        if !native_display().lock().unwrap().quit_ordered {
-            // if window should be closed and event handling is enabled, give user code
-            // a chance to intervene via sapp_cancel_quit()
-            native_display().lock().unwrap().quit_requested = true;
-            if let Some(event_handler) = payload.context() {
-                event_handler.quit_requested_event();
-            }
-
-            // user code hasn't intervened, quit the app
-            if native_display().lock().unwrap().quit_requested {
-                native_display().lock().unwrap().quit_ordered = true;
-            }
-        }
-        if native_display().lock().unwrap().quit_ordered {
-            return YES;
+        if quit_requested(payload) {
+            YES
         } else {
-            return NO;
+            NO
         }
     }
 
@@ -277,10 +1047,71 @@ pub fn define_cocoa_window_delegate() -> *const Class {
                 event_handler.resize_event(w as _, h as _);
             }
         }
+        // zooming is a resize too
+        unsafe { payload.update_window_state() };
+        unsafe { payload.position_traffic_lights() };
+    }
+    extern "C" fn window_did_move(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        let position = unsafe { payload.window_position() };
+        if let Some(event_handler) = payload.context() {
+            crate::native::update_window_position(position, event_handler);
+        }
+    }
+    extern "C" fn window_will_start_live_resize(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        if let Some(event_handler) = payload.context() {
+            event_handler.resize_started_event();
+        }
+    }
+    extern "C" fn window_did_end_live_resize(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        if let Some(event_handler) = payload.context() {
+            event_handler.resize_ended_event();
+        }
+    }
+    extern "C" fn window_did_change_miniaturized(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        unsafe { payload.update_window_state() };
+    }
+
+    // covered by other windows, minimized, on another space or with the
+    // display asleep
+    extern "C" fn window_did_change_occlusion_state(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        // NSWindowOcclusionStateVisible
+        let state: u64 = unsafe { msg_send![payload.window, occlusionState] };
+        if let Some(event_handler) = payload.context() {
+            crate::native::update_occluded(state & (1 << 1) == 0, event_handler);
+        }
     }
 
     extern "C" fn window_did_change_screen(this: &Object, _: Sel, _: ObjcId) {
         let payload = get_window_payload(this);
+        unsafe { payload.update_monitors() };
+        if let Some((w, h)) = unsafe { payload.update_dimensions() } {
+            if let Some(event_handler) = payload.context() {
+                event_handler.resize_event(w as _, h as _);
+            }
+        }
+    }
+    extern "C" fn window_did_become_key(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        if let Some(event_handler) = payload.context() {
+            crate::native::update_focus(true, event_handler);
+        }
+    }
+    // no flagsChanged: for the modifiers released in another window
+    extern "C" fn window_did_resign_key(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        payload.modifiers = Modifiers::default();
+        if let Some(event_handler) = payload.context() {
+            crate::native::update_focus(false, event_handler);
+        }
+    }
+    extern "C" fn window_did_change_backing_properties(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        unsafe { payload.update_dpi_scale() };
         if let Some((w, h)) = unsafe { payload.update_dimensions() } {
             if let Some(event_handler) = payload.context() {
                 event_handler.resize_event(w as _, h as _);
@@ -294,6 +1125,9 @@ pub fn define_cocoa_window_delegate() -> *const Class {
     extern "C" fn window_did_exit_fullscreen(this: &Object, _: Sel, _: ObjcId) {
         let payload = get_window_payload(this);
         payload.fullscreen = false;
+        // the style from before fullscreen is back, set_decorations could have changed it since
+        unsafe { payload.update_style_mask() };
+        unsafe { payload.position_traffic_lights() };
     }
     let superclass = class!(NSObject);
     let mut decl = ClassDecl::new("RenderWindowDelegate", superclass).unwrap();
@@ -309,10 +1143,46 @@ pub fn define_cocoa_window_delegate() -> *const Class {
             sel!(windowDidResize:),
             window_did_resize as extern "C" fn(&Object, Sel, ObjcId),
         );
+        decl.add_method(
+            sel!(windowDidMove:),
+            window_did_move as extern "C" fn(&Object, Sel, ObjcId),
+        );
+        decl.add_method(
+            sel!(windowWillStartLiveResize:),
+            window_will_start_live_resize as extern "C" fn(&Object, Sel, ObjcId),
+        );
+        decl.add_method(
+            sel!(windowDidEndLiveResize:),
+            window_did_end_live_resize as extern "C" fn(&Object, Sel, ObjcId),
+        );
+        decl.add_method(
+            sel!(windowDidMiniaturize:),
+            window_did_change_miniaturized as extern "C" fn(&Object, Sel, ObjcId),
+        );
+        decl.add_method(
+            sel!(windowDidDeminiaturize:),
+            window_did_change_miniaturized as extern "C" fn(&Object, Sel, ObjcId),
+        );
+        decl.add_method(
+            sel!(windowDidChangeOcclusionState:),
+            window_did_change_occlusion_state as extern "C" fn(&Object, Sel, ObjcId),
+        );
         decl.add_method(
             sel!(windowDidChangeScreen:),
             window_did_change_screen as extern "C" fn(&Object, Sel, ObjcId),
         );
+        decl.add_method(
+            sel!(windowDidBecomeKey:),
+            window_did_become_key as extern "C" fn(&Object, Sel, ObjcId),
+        );
+        decl.add_method(
+            sel!(windowDidResignKey:),
+            window_did_resign_key as extern "C" fn(&Object, Sel, ObjcId),
+        );
+        decl.add_method(
+            sel!(windowDidChangeBackingProperties:),
+            window_did_change_backing_properties as extern "C" fn(&Object, Sel, ObjcId),
+        );
         decl.add_method(
             sel!(windowDidEnterFullScreen:),
             window_did_enter_fullscreen as extern "C" fn(&Object, Sel, ObjcId),
@@ -359,6 +1229,7 @@ unsafe fn get_proc_address(name: *const u8) -> Option<unsafe extern "C" fn()> {
 // methods for both metal or OPENGL view
 unsafe fn view_base_decl(decl: &mut ClassDecl) {
     extern "C" fn mouse_moved(this: &Object, _sel: Sel, event: ObjcId) {
+        update_event_timestamp(event);
         let payload = get_window_payload(this);
 
         unsafe {
@@ -371,24 +1242,71 @@ unsafe fn view_base_decl(decl: &mut ClassDecl) {
             } else {
                 let point: NSPoint = msg_send!(event, locationInWindow);
                 let point = payload.transform_mouse_point(&point);
+                let mods = get_event_key_modifier(event);
+                fire_pen_event(payload, event, point);
                 if let Some(event_handler) = payload.context() {
-                    event_handler.mouse_motion_event(point.0, point.1);
+                    event_handler.mouse_motion_keymods_event(point.0, point.1, mods);
                 }
             }
         }
     }
 
+    // window::event_timestamp, the seconds since the boot of the Mac
+    fn update_event_timestamp(event: ObjcId) {
+        let timestamp: f64 = unsafe { msg_send!(event, timestamp) };
+        crate::native::update_event_timestamp(timestamp);
+    }
+
+    // the tablet data of a mouse event, tablets move the mouse too
+    fn fire_pen_event(payload: &mut MacosDisplay, event: ObjcId, point: (f32, f32)) {
+        unsafe {
+            let subtype: i16 = msg_send!(event, subtype);
+            if subtype == NSTabletProximityEventSubtype {
+                update_pen_eraser(payload, event);
+            }
+            if subtype != NSTabletPointEventSubtype {
+                return;
+            }
+            let pressure: f32 = msg_send!(event, pressure);
+            let tilt: NSPoint = msg_send!(event, tilt);
+            let buttons: u64 = msg_send!(event, buttonMask);
+            let pen = PenState {
+                pressure,
+                // from -1 to 1, of the 60 degrees of Wacom pens, up is positive
+                tilt: (tilt.x as f32 * 60., -tilt.y as f32 * 60.),
+                contact: buttons & NSPenTipMask != 0,
+                barrel_button: buttons & (NSPenLowerSideMask | NSPenUpperSideMask) != 0,
+                eraser: payload.pen_eraser,
+            };
+            if let Some(event_handler) = payload.context() {
+                event_handler.pen_event(point.0, point.1, pen);
+            }
+        }
+    }
+    fn update_pen_eraser(payload: &mut MacosDisplay, event: ObjcId) {
+        unsafe {
+            let device: u64 = msg_send!(event, pointingDeviceType);
+            let entering: BOOL = msg_send!(event, isEnteringProximity);
+            payload.pen_eraser = entering == YES && device == NSPointingDeviceTypeEraser;
+        }
+    }
+    extern "C" fn tablet_proximity(this: &Object, _sel: Sel, event: ObjcId) {
+        update_pen_eraser(get_window_payload(this), event);
+    }
     fn fire_mouse_event(this: &Object, event: ObjcId, down: bool, btn: MouseButton) {
+        update_event_timestamp(event);
         let payload = get_window_payload(this);
 
         unsafe {
             let point: NSPoint = msg_send!(event, locationInWindow);
             let point = payload.transform_mouse_point(&point);
+            let mods = get_event_key_modifier(event);
+            fire_pen_event(payload, event, point);
             if let Some(event_handler) = payload.context() {
                 if down {
-                    event_handler.mouse_button_down_event(btn, point.0, point.1);
+                    event_handler.mouse_button_down_keymods_event(btn, point.0, point.1, mods);
                 } else {
-                    event_handler.mouse_button_up_event(btn, point.0, point.1);
+                    event_handler.mouse_button_up_keymods_event(btn, point.0, point.1, mods);
                 }
             }
         }
@@ -405,32 +1323,146 @@ unsafe fn view_base_decl(decl: &mut ClassDecl) {
     extern "C" fn right_mouse_up(this: &Object, _sel: Sel, event: ObjcId) {
         fire_mouse_event(this, event, false, MouseButton::Right);
     }
+    // all the buttons but left and right
+    fn other_mouse_button(event: ObjcId) -> MouseButton {
+        let button: i64 = unsafe { msg_send![event, buttonNumber] };
+        match button {
+            2 => MouseButton::Middle,
+            3 => MouseButton::Back,
+            4 => MouseButton::Forward,
+            5..=254 => MouseButton::Other(button as u8 + 1),
+            _ => MouseButton::Unknown,
+        }
+    }
     extern "C" fn other_mouse_down(this: &Object, _sel: Sel, event: ObjcId) {
-        fire_mouse_event(this, event, true, MouseButton::Middle);
+        fire_mouse_event(this, event, true, other_mouse_button(event));
     }
     extern "C" fn other_mouse_up(this: &Object, _sel: Sel, event: ObjcId) {
-        fire_mouse_event(this, event, false, MouseButton::Middle);
+        fire_mouse_event(this, event, false, other_mouse_button(event));
+    }
+    // from the tracking area of run()
+    extern "C" fn mouse_entered(this: &Object, _sel: Sel, event: ObjcId) {
+        update_event_timestamp(event);
+        let payload = get_window_payload(this);
+        if let Some(event_handler) = payload.context() {
+            event_handler.mouse_entered_event();
+        }
+    }
+    extern "C" fn mouse_exited(this: &Object, _sel: Sel, event: ObjcId) {
+        update_event_timestamp(event);
+        let payload = get_window_payload(this);
+        if let Some(event_handler) = payload.context() {
+            event_handler.mouse_left_event();
+        }
     }
     extern "C" fn scroll_wheel(this: &Object, _sel: Sel, event: ObjcId) {
+        update_event_timestamp(event);
         let payload = get_window_payload(this);
         unsafe {
-            let mut dx: f64 = msg_send![event, scrollingDeltaX];
-            let mut dy: f64 = msg_send![event, scrollingDeltaY];
-
-            if !msg_send![event, hasPreciseScrollingDeltas] {
-                dx *= 10.0;
-                dy *= 10.0;
-            }
+            let dx: f64 = msg_send![event, scrollingDeltaX];
+            let dy: f64 = msg_send![event, scrollingDeltaY];
+
+            // in points from trackpads and magic mice, in lines from wheels
+            let precise: bool = msg_send![event, hasPreciseScrollingDeltas];
+            let (delta, wheel_scale) = if precise {
+                let dpi_scale = native_display().lock().unwrap().dpi_scale as f64;
+                let pixels = ((dx * dpi_scale) as f32, (dy * dpi_scale) as f32);
+                (ScrollDelta::Pixels(pixels.0, pixels.1), 1.0)
+            } else {
+                (ScrollDelta::Lines(dx as f32, dy as f32), 10.0)
+            };
+            let (dx, dy) = (dx * wheel_scale, dy * wheel_scale);
+            let mods = get_event_key_modifier(event);
             if let Some(event_handler) = payload.context() {
-                event_handler.mouse_wheel_event(dx as f32, dy as f32);
+                event_handler.mouse_wheel_keymods_event(dx as f32, dy as f32, mods);
+                event_handler.mouse_scroll_event(delta, mods);
             }
         }
     }
+    // the trackpad gestures, as deltas since the last event of each
+    extern "C" fn magnify(this: &Object, _sel: Sel, event: ObjcId) {
+        update_event_timestamp(event);
+        let payload = get_window_payload(this);
+        let magnification: f64 = unsafe { msg_send![event, magnification] };
+        if let Some(event_handler) = payload.context() {
+            event_handler.magnify_event(magnification as f32);
+        }
+    }
+    extern "C" fn rotate(this: &Object, _sel: Sel, event: ObjcId) {
+        update_event_timestamp(event);
+        let payload = get_window_payload(this);
+        let rotation: f32 = unsafe { msg_send![event, rotation] };
+        if let Some(event_handler) = payload.context() {
+            event_handler.rotate_event(rotation);
+        }
+    }
+    extern "C" fn smart_magnify(this: &Object, _sel: Sel, event: ObjcId) {
+        update_event_timestamp(event);
+        let payload = get_window_payload(this);
+        if let Some(event_handler) = payload.context() {
+            event_handler.smart_magnify_event();
+        }
+    }
+    // during the clicks on a Force Touch trackpad
+    extern "C" fn pressure_change(this: &Object, _sel: Sel, event: ObjcId) {
+        update_event_timestamp(event);
+        let payload = get_window_payload(this);
+        let (pressure, stage): (f32, i64) =
+            unsafe { (msg_send![event, pressure], msg_send![event, stage]) };
+        if let Some(event_handler) = payload.context() {
+            event_handler.force_touch_event(pressure, stage as i32);
+        }
+    }
+    // NSDraggingDestination, with "public.file-url" registered in run()
+    extern "C" fn dragging_entered(this: &Object, _sel: Sel, _sender: ObjcId) -> u64 {
+        let payload = get_window_payload(this);
+        if let Some(event_handler) = payload.context() {
+            event_handler.files_hovered_event();
+        }
+        1 // NSDragOperationCopy
+    }
+    extern "C" fn dragging_exited(this: &Object, _sel: Sel, _sender: ObjcId) {
+        let payload = get_window_payload(this);
+        if let Some(event_handler) = payload.context() {
+            event_handler.files_hover_cancelled_event();
+        }
+    }
+    extern "C" fn perform_drag_operation(this: &Object, _sel: Sel, sender: ObjcId) -> BOOL {
+        let payload = get_window_payload(this);
+        unsafe {
+            let pasteboard: ObjcId = msg_send![sender, draggingPasteboard];
+            let classes: ObjcId = msg_send![class!(NSArray), arrayWithObject: class!(NSURL)];
+            let urls: ObjcId = msg_send![pasteboard, readObjectsForClasses: classes options: nil];
+            if urls.is_null() {
+                return NO;
+            }
+            let count: u64 = msg_send![urls, count];
+            let mut paths = vec![];
+            for i in 0..count {
+                let url: ObjcId = msg_send![urls, objectAtIndex: i];
+                let is_file: BOOL = msg_send![url, isFileURL];
+                if is_file == YES {
+                    let path: ObjcId = msg_send![url, path];
+                    paths.push(nsstring_to_string(path).into());
+                }
+            }
+            native_display().lock().unwrap().dropped_files = crate::native::DroppedFiles {
+                paths,
+                bytes: vec![],
+            };
+        }
+        if let Some(event_handler) = payload.context() {
+            event_handler.files_dropped_event();
+        }
+        YES
+    }
     extern "C" fn reset_cursor_rects(this: &Object, _sel: Sel) {
         let payload = get_window_payload(this);
 
         unsafe {
-            let cursor_id = {
+            let cursor_id = if let Some(custom_cursor) = payload.custom_cursor {
+                custom_cursor
+            } else {
                 let current_cursor = payload.current_cursor;
                 let cursor_id = *payload
                     .cursors
@@ -450,28 +1482,193 @@ unsafe fn view_base_decl(decl: &mut ClassDecl) {
     }
 
     extern "C" fn key_down(this: &Object, _sel: Sel, event: ObjcId) {
+        update_event_timestamp(event);
         let payload = get_window_payload(this);
         let mods = get_event_key_modifier(event);
         let repeat: bool = unsafe { msg_send!(event, isARepeat) };
+        // the keycodes are the places of the keys on macOS already
         if let Some(key) = get_event_keycode(event) {
             if let Some(event_handler) = payload.context() {
-                event_handler.key_down_event(key, mods, repeat);
+                let scancode = keycode_to_scancode(key);
+                crate::native::key_down(key, scancode, mods, repeat, event_handler);
             }
         }
 
-        if let Some(character) = get_event_char(event) {
+        if !payload.text_input {
+            return;
+        }
+
+        // text comes back through insertText: and setMarkedText:
+        payload.key_text = Some((mods, repeat));
+        let was_composing = payload.marked_text.is_some();
+        unsafe {
+            let events: ObjcId = msg_send![class!(NSArray), arrayWithObject: event];
+            let () = msg_send![this, interpretKeyEvents: events];
+        }
+
+        // not text for the input method, Cmd shortcuts mostly
+        let payload = get_window_payload(this);
+        if payload.key_text.take().is_some() && !was_composing && payload.marked_text.is_none() {
+            // the arrows and function keys are in the private use area
+            if let Some(character) = get_event_char(event)
+                .filter(|c| !c.is_control() && !('\u{F700}'..='\u{F8FF}').contains(c))
+            {
+                if let Some(event_handler) = payload.context() {
+                    event_handler.char_event(character, mods, repeat);
+                }
+            }
+        }
+    }
+
+    // NSTextInputClient
+    fn text_input_string(string: ObjcId) -> String {
+        unsafe {
+            let attributed: BOOL = msg_send![string, isKindOfClass: class!(NSAttributedString)];
+            let string = if attributed == YES {
+                msg_send![string, string]
+            } else {
+                string
+            };
+            nsstring_to_string(string)
+        }
+    }
+
+    extern "C" fn has_marked_text(this: &Object, _sel: Sel) -> BOOL {
+        if get_window_payload(this).marked_text.is_some() {
+            YES
+        } else {
+            NO
+        }
+    }
+
+    extern "C" fn marked_range(this: &Object, _sel: Sel) -> NSRange {
+        match get_window_payload(this).marked_text {
+            Some(length) => NSRange::new(0, length),
+            None => NSRange::new(NSNotFound, 0),
+        }
+    }
+
+    extern "C" fn selected_range(_this: &Object, _sel: Sel) -> NSRange {
+        NSRange::new(NSNotFound, 0)
+    }
+
+    extern "C" fn set_marked_text(
+        this: &Object,
+        _sel: Sel,
+        string: ObjcId,
+        selected_range: NSRange,
+        _replacement_range: NSRange,
+    ) {
+        let payload = get_window_payload(this);
+        let text = text_input_string(string);
+        payload.key_text = None;
+        if text.is_empty() {
+            if payload.marked_text.take().is_none() {
+                return;
+            }
+        } else {
+            payload.marked_text = Some(text.encode_utf16().count() as u64);
+        }
+
+        // the selection is in utf-16 code units, the cursor in bytes
+        let mut units = 0;
+        let cursor = text
+            .char_indices()
+            .find(|(_, c)| {
+                let before = units;
+                units += c.len_utf16() as u64;
+                before >= selected_range.location
+            })
+            .map_or(text.len(), |(i, _)| i);
+        if let Some(event_handler) = payload.context() {
+            event_handler.ime_composition_event(&text, cursor);
+        }
+    }
+
+    extern "C" fn unmark_text(this: &Object, _sel: Sel) {
+        let payload = get_window_payload(this);
+        if payload.marked_text.take().is_some() {
             if let Some(event_handler) = payload.context() {
-                event_handler.char_event(character, mods, repeat);
+                event_handler.ime_composition_event("", 0);
             }
         }
     }
 
+    extern "C" fn valid_attributes_for_marked_text(_this: &Object, _sel: Sel) -> ObjcId {
+        unsafe { msg_send![class!(NSArray), array] }
+    }
+
+    extern "C" fn attributed_substring_for_proposed_range(
+        _this: &Object,
+        _sel: Sel,
+        _range: NSRange,
+        _actual_range: *mut c_void,
+    ) -> ObjcId {
+        nil
+    }
+
+    extern "C" fn insert_text(
+        this: &Object,
+        _sel: Sel,
+        string: ObjcId,
+        _replacement_range: NSRange,
+    ) {
+        let payload = get_window_payload(this);
+        let text = text_input_string(string);
+        let key_text = payload.key_text.take();
+        let composing = payload.marked_text.take().is_some();
+        if let Some(event_handler) = payload.context() {
+            if composing {
+                event_handler.ime_composition_event("", 0);
+                event_handler.ime_commit_event(&text);
+            } else if let Some((mods, repeat)) = key_text {
+                // plain typing, same as before IME support
+                for character in text.chars().filter(|c| !c.is_control()) {
+                    event_handler.char_event(character, mods, repeat);
+                }
+            } else {
+                // the emoji and symbols palette
+                event_handler.ime_commit_event(&text);
+            }
+        }
+    }
+
+    extern "C" fn character_index_for_point(_this: &Object, _sel: Sel, _point: NSPoint) -> u64 {
+        NSNotFound
+    }
+
+    extern "C" fn first_rect_for_character_range(
+        this: &Object,
+        _sel: Sel,
+        _range: NSRange,
+        _actual_range: *mut c_void,
+    ) -> NSRect {
+        let payload = get_window_payload(this);
+        let (x, y, width, height) = payload.ime_cursor_area;
+        unsafe {
+            let dpi_scale = native_display().lock().unwrap().dpi_scale as f64;
+            let bounds: NSRect = msg_send![payload.view, bounds];
+            // from the mouse pixels, top left origin, to the view points
+            let rect = NSRect::new(
+                x as f64 / dpi_scale,
+                bounds.size.height - (y + height) as f64 / dpi_scale,
+                width as f64 / dpi_scale,
+                height as f64 / dpi_scale,
+            );
+            let rect: NSRect = msg_send![payload.view, convertRect: rect toView: nil];
+            msg_send![payload.window, convertRectToScreen: rect]
+        }
+    }
+
+    extern "C" fn do_command_by_selector(_this: &Object, _sel: Sel, _command: Sel) {}
+
     extern "C" fn key_up(this: &Object, _sel: Sel, event: ObjcId) {
+        update_event_timestamp(event);
         let payload = get_window_payload(this);
         let mods = get_event_key_modifier(event);
         if let Some(key) = get_event_keycode(event) {
             if let Some(event_handler) = payload.context() {
-                event_handler.key_up_event(key, mods);
+                crate::native::key_up(key, keycode_to_scancode(key), mods, event_handler);
             }
         }
     }
@@ -487,16 +1684,19 @@ unsafe fn view_base_decl(decl: &mut ClassDecl) {
             if new_pressed ^ old_pressed {
                 if new_pressed {
                     if let Some(event_handler) = payload.context() {
-                        event_handler.key_down_event(keycode, mods, false);
+                        let scancode = keycode_to_scancode(keycode);
+                        crate::native::key_down(keycode, scancode, mods, false, event_handler);
                     }
                 } else {
                     if let Some(event_handler) = payload.context() {
-                        event_handler.key_up_event(keycode, mods);
+                        let scancode = keycode_to_scancode(keycode);
+                        crate::native::key_up(keycode, scancode, mods, event_handler);
                     }
                 }
             }
         }
 
+        update_event_timestamp(event);
         let payload = get_window_payload(this);
         let mods = get_event_key_modifier(event);
         let flags: u64 = unsafe { msg_send![event, modifierFlags] };
@@ -561,6 +1761,14 @@ unsafe fn view_base_decl(decl: &mut ClassDecl) {
 
         payload.modifiers = new_modifiers;
     }
+    // the view follows the appearance of the app
+    extern "C" fn view_did_change_effective_appearance(this: &Object, _sel: Sel) {
+        let payload = get_window_payload(this);
+        let theme = unsafe { system_theme() };
+        if let Some(event_handler) = payload.context() {
+            crate::native::update_system_theme(theme, event_handler);
+        }
+    }
     decl.add_method(
         sel!(canBecomeKey),
         yes as extern "C" fn(&Object, Sel) -> BOOL,
@@ -614,10 +1822,38 @@ unsafe fn view_base_decl(decl: &mut ClassDecl) {
         sel!(otherMouseUp:),
         other_mouse_up as extern "C" fn(&Object, Sel, ObjcId),
     );
+    decl.add_method(
+        sel!(mouseEntered:),
+        mouse_entered as extern "C" fn(&Object, Sel, ObjcId),
+    );
+    decl.add_method(
+        sel!(mouseExited:),
+        mouse_exited as extern "C" fn(&Object, Sel, ObjcId),
+    );
+    decl.add_method(
+        sel!(tabletProximity:),
+        tablet_proximity as extern "C" fn(&Object, Sel, ObjcId),
+    );
     decl.add_method(
         sel!(scrollWheel:),
         scroll_wheel as extern "C" fn(&Object, Sel, ObjcId),
     );
+    decl.add_method(
+        sel!(magnifyWithEvent:),
+        magnify as extern "C" fn(&Object, Sel, ObjcId),
+    );
+    decl.add_method(
+        sel!(rotateWithEvent:),
+        rotate as extern "C" fn(&Object, Sel, ObjcId),
+    );
+    decl.add_method(
+        sel!(smartMagnifyWithEvent:),
+        smart_magnify as extern "C" fn(&Object, Sel, ObjcId),
+    );
+    decl.add_method(
+        sel!(pressureChangeWithEvent:),
+        pressure_change as extern "C" fn(&Object, Sel, ObjcId),
+    );
     decl.add_method(
         sel!(keyDown:),
         key_down as extern "C" fn(&Object, Sel, ObjcId),
@@ -627,6 +1863,66 @@ unsafe fn view_base_decl(decl: &mut ClassDecl) {
         flags_changed as extern "C" fn(&Object, Sel, ObjcId),
     );
     decl.add_method(sel!(keyUp:), key_up as extern "C" fn(&Object, Sel, ObjcId));
+    decl.add_protocol(Protocol::get("NSTextInputClient").unwrap());
+    decl.add_method(
+        sel!(hasMarkedText),
+        has_marked_text as extern "C" fn(&Object, Sel) -> BOOL,
+    );
+    decl.add_method(
+        sel!(markedRange),
+        marked_range as extern "C" fn(&Object, Sel) -> NSRange,
+    );
+    decl.add_method(
+        sel!(selectedRange),
+        selected_range as extern "C" fn(&Object, Sel) -> NSRange,
+    );
+    decl.add_method(
+        sel!(setMarkedText:selectedRange:replacementRange:),
+        set_marked_text as extern "C" fn(&Object, Sel, ObjcId, NSRange, NSRange),
+    );
+    decl.add_method(sel!(unmarkText), unmark_text as extern "C" fn(&Object, Sel));
+    decl.add_method(
+        sel!(validAttributesForMarkedText),
+        valid_attributes_for_marked_text as extern "C" fn(&Object, Sel) -> ObjcId,
+    );
+    decl.add_method(
+        sel!(attributedSubstringForProposedRange:actualRange:),
+        attributed_substring_for_proposed_range
+            as extern "C" fn(&Object, Sel, NSRange, *mut c_void) -> ObjcId,
+    );
+    decl.add_method(
+        sel!(insertText:replacementRange:),
+        insert_text as extern "C" fn(&Object, Sel, ObjcId, NSRange),
+    );
+    decl.add_method(
+        sel!(characterIndexForPoint:),
+        character_index_for_point as extern "C" fn(&Object, Sel, NSPoint) -> u64,
+    );
+    decl.add_method(
+        sel!(firstRectForCharacterRange:actualRange:),
+        first_rect_for_character_range
+            as extern "C" fn(&Object, Sel, NSRange, *mut c_void) -> NSRect,
+    );
+    decl.add_method(
+        sel!(doCommandBySelector:),
+        do_command_by_selector as extern "C" fn(&Object, Sel, Sel),
+    );
+    decl.add_method(
+        sel!(draggingEntered:),
+        dragging_entered as extern "C" fn(&Object, Sel, ObjcId) -> u64,
+    );
+    decl.add_method(
+        sel!(draggingExited:),
+        dragging_exited as extern "C" fn(&Object, Sel, ObjcId),
+    );
+    decl.add_method(
+        sel!(performDragOperation:),
+        perform_drag_operation as extern "C" fn(&Object, Sel, ObjcId) -> BOOL,
+    );
+    decl.add_method(
+        sel!(viewDidChangeEffectiveAppearance),
+        view_did_change_effective_appearance as extern "C" fn(&Object, Sel),
+    );
 }
 
 pub fn define_opengl_view_class() -> *const Class {
@@ -654,9 +1950,14 @@ pub fn define_opengl_view_class() -> *const Class {
             payload.process_request(request);
         }
 
-        if let Some(event_handler) = payload.context() {
-            event_handler.update();
-            event_handler.draw();
+        if let Some(event_handler) = payload.event_handler.as_deref_mut() {
+            payload.gamepads.poll(event_handler);
+            payload.power.poll(event_handler);
+            crate::native::dispatch_custom_events(event_handler);
+            if !native_display().lock().unwrap().frames_paused() {
+                event_handler.update();
+                event_handler.draw();
+            }
         }
 
         unsafe {
@@ -670,6 +1971,7 @@ pub fn define_opengl_view_class() -> *const Class {
                 let () = msg_send![payload.window, performClose: nil];
             }
         }
+        payload.frame_limiter.wait();
     }
 
     extern "C" fn prepare_open_gl(this: &Object, _sel: Sel) {
@@ -677,7 +1979,7 @@ pub fn define_opengl_view_class() -> *const Class {
         unsafe {
             let superclass = superclass(this);
             let () = msg_send![super(this, superclass), prepareOpenGL];
-            let mut swap_interval = 1;
+            let mut swap_interval = payload.swap_interval;
             let ctx: ObjcId = msg_send![this, openGLContext];
             let () = msg_send![ctx,
                                setValues:&mut swap_interval
@@ -696,8 +1998,11 @@ pub fn define_opengl_view_class() -> *const Class {
     }
 
     extern "C" fn timer_fired(this: &Object, _sel: Sel, _: ObjcId) {
-        unsafe {
-            let () = msg_send!(this, setNeedsDisplay: YES);
+        let payload = get_window_payload(this);
+        if payload.frame_due() {
+            unsafe {
+                let () = msg_send!(this, setNeedsDisplay: YES);
+            }
         }
     }
     let superclass = class!(NSOpenGLView);
@@ -733,8 +2038,11 @@ pub fn define_metal_view_class() -> *const Class {
     decl.add_ivar::<*mut c_void>("display_ptr");
 
     extern "C" fn timer_fired(this: &Object, _sel: Sel, _: ObjcId) {
-        unsafe {
-            let () = msg_send!(this, setNeedsDisplay: YES);
+        let payload = get_window_payload(this);
+        if payload.frame_due() {
+            unsafe {
+                let () = msg_send!(this, setNeedsDisplay: YES);
+            }
         }
     }
 
@@ -750,9 +2058,14 @@ pub fn define_metal_view_class() -> *const Class {
             payload.process_request(request);
         }
 
-        if let Some(event_handler) = payload.context() {
-            event_handler.update();
-            event_handler.draw();
+        if let Some(event_handler) = payload.event_handler.as_deref_mut() {
+            payload.gamepads.poll(event_handler);
+            payload.power.poll(event_handler);
+            crate::native::dispatch_custom_events(event_handler);
+            if !native_display().lock().unwrap().frames_paused() {
+                event_handler.update();
+                event_handler.draw();
+            }
         }
 
         unsafe {
@@ -762,6 +2075,7 @@ pub fn define_metal_view_class() -> *const Class {
                 let () = msg_send![payload.window, performClose: nil];
             }
         }
+        payload.frame_limiter.wait();
     }
 
     unsafe {
@@ -857,21 +2171,157 @@ unsafe fn create_opengl_view(window_frame: NSRect, sample_count: i32, high_dpi:
 }
 
 struct MacosClipboard;
+impl MacosClipboard {
+    unsafe fn pasteboard() -> ObjcId {
+        msg_send![class!(NSPasteboard), generalPasteboard]
+    }
+
+    unsafe fn get_string(ty: ObjcId) -> Option<String> {
+        let content: ObjcId = msg_send![Self::pasteboard(), stringForType: ty];
+        if content.is_null() {
+            return None;
+        }
+        Some(nsstring_to_string(content))
+    }
+}
 impl crate::native::Clipboard for MacosClipboard {
     fn get(&mut self) -> Option<String> {
-        None
+        unsafe { Self::get_string(NSStringPboardType) }
+    }
+
+    fn set(&mut self, data: &str) {
+        unsafe {
+            let pasteboard = Self::pasteboard();
+            let () = msg_send![pasteboard, clearContents];
+            let arr: ObjcId = msg_send![class!(NSArray), arrayWithObject: str_to_nsstring(data)];
+            let () = msg_send![pasteboard, writeObjects: arr];
+        }
+    }
+
+    fn has(&mut self, format: crate::ClipboardFormat) -> bool {
+        unsafe {
+            let pasteboard = Self::pasteboard();
+            let ty = match format {
+                crate::ClipboardFormat::Text => NSStringPboardType,
+                crate::ClipboardFormat::Html => str_to_nsstring("public.html"),
+                crate::ClipboardFormat::Image => {
+                    let can_init: BOOL =
+                        msg_send![class!(NSImage), canInitWithPasteboard: pasteboard];
+                    return can_init == YES;
+                }
+            };
+            let types: ObjcId = msg_send![class!(NSArray), arrayWithObject: ty];
+            let available: ObjcId = msg_send![pasteboard, availableTypeFromArray: types];
+            !available.is_null()
+        }
+    }
+
+    fn get_html(&mut self) -> Option<String> {
+        unsafe { Self::get_string(str_to_nsstring("public.html")) }
+    }
+
+    fn set_html(&mut self, html: &str, text: &str) {
+        unsafe {
+            let pasteboard = Self::pasteboard();
+            let () = msg_send![pasteboard, clearContents];
+            let html = str_to_nsstring(html);
+            let text = str_to_nsstring(text);
+            let () = msg_send![pasteboard, setString: html forType: str_to_nsstring("public.html")];
+            let () = msg_send![pasteboard, setString: text forType: NSStringPboardType];
+        }
+    }
+
+    fn get_image(&mut self) -> Option<crate::ClipboardImage> {
+        unsafe {
+            let pasteboard = Self::pasteboard();
+            let image: ObjcId = msg_send![class!(NSImage), alloc];
+            let image: ObjcId = msg_send![image, initWithPasteboard: pasteboard];
+            if image.is_null() {
+                return None;
+            }
+            // the image size is in points, the first representation has the pixels
+            let reps: ObjcId = msg_send![image, representations];
+            let count: u64 = msg_send![reps, count];
+            if count == 0 {
+                let () = msg_send![image, release];
+                return None;
+            }
+            let rep: ObjcId = msg_send![reps, objectAtIndex: 0u64];
+            let width: i64 = msg_send![rep, pixelsWide];
+            let height: i64 = msg_send![rep, pixelsHigh];
+
+            // draw into a bitmap with a known pixel format
+            let bitmap: ObjcId = msg_send![class!(NSBitmapImageRep), alloc];
+            let bitmap: ObjcId = msg_send![bitmap,
+                initWithBitmapDataPlanes: std::ptr::null_mut::<*mut u8>()
+                pixelsWide: width
+                pixelsHigh: height
+                bitsPerSample: 8i64
+                samplesPerPixel: 4i64
+                hasAlpha: YES
+                isPlanar: NO
+                colorSpaceName: str_to_nsstring("NSDeviceRGBColorSpace")
+                bytesPerRow: width * 4
+                bitsPerPixel: 32i64];
+            let context: ObjcId =
+                msg_send![class!(NSGraphicsContext), graphicsContextWithBitmapImageRep: bitmap];
+            let () = msg_send![class!(NSGraphicsContext), saveGraphicsState];
+            let () = msg_send![class!(NSGraphicsContext), setCurrentContext: context];
+            let rect = NSRect::new(0., 0., width as f64, height as f64);
+            let () = msg_send![image, drawInRect: rect];
+            let () = msg_send![context, flushGraphics];
+            let () = msg_send![class!(NSGraphicsContext), restoreGraphicsState];
+
+            let data: *const u8 = msg_send![bitmap, bitmapData];
+            let mut rgba = std::slice::from_raw_parts(data, (width * height * 4) as usize).to_vec();
+            let () = msg_send![bitmap, release];
+            let () = msg_send![image, release];
+
+            // the bitmap is premultiplied
+            for pixel in rgba.chunks_mut(4) {
+                let alpha = pixel[3] as u32;
+                if alpha != 0 && alpha != 255 {
+                    for c in &mut pixel[0..3] {
+                        *c = ((*c as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+                    }
+                }
+            }
+
+            Some(crate::ClipboardImage {
+                width: width as u32,
+                height: height as u32,
+                rgba,
+            })
+        }
+    }
+
+    fn set_image(&mut self, rgba: &[u8], width: u32, height: u32) {
+        unsafe {
+            let pasteboard = Self::pasteboard();
+            let image = ns_image_from_rgba(width as usize, height as usize, rgba);
+            let () = msg_send![pasteboard, clearContents];
+            let arr: ObjcId = msg_send![class!(NSArray), arrayWithObject: image];
+            let () = msg_send![pasteboard, writeObjects: arr];
+            let () = msg_send![image, release];
+        }
     }
-    fn set(&mut self, _data: &str) {}
 }
 
 unsafe extern "C" fn release_data(info: *mut &[u8], _: *const c_void, _: usize) {
-    Box::from_raw(info);
+    // both the reference and the pixels it points to are owned by the provider
+    let colors = Box::from_raw(info);
+    drop(Box::from_raw(*colors as *const [u8] as *mut [u8]));
+}
+
+unsafe fn set_icon(ns_app: ObjcId, width: usize, height: usize, colors: &[u8]) {
+    let ns_image = ns_image_from_rgba(width, height, colors);
+    let () = msg_send![ns_app, setApplicationIconImage: ns_image];
+    let () = msg_send![ns_image, release];
 }
 
-unsafe fn set_icon(ns_app: ObjcId, icon: &Icon) {
-    let width = 64 as usize;
-    let height = 64 as usize;
-    let colors = &icon.big[..];
+unsafe fn ns_image_from_rgba(width: usize, height: usize, colors: &[u8]) -> ObjcId {
+    // the image may outlive the caller's data
+    let colors: &[u8] = Box::leak(colors.to_vec().into_boxed_slice());
     let rgb = CGColorSpaceCreateDeviceRGB();
     let bits_per_component: usize = 8; // number of bits in UInt8
     let bits_per_pixel = 4 * bits_per_component; // ARGB uses 4 components
@@ -901,12 +2351,12 @@ unsafe fn set_icon(ns_app: ObjcId, icon: &Icon) {
         height: height as f64,
     };
     let ns_image: ObjcId = msg_send![class!(NSImage), alloc];
-    let () = msg_send![ns_image, initWithCGImage: image size: size];
+    let ns_image: ObjcId = msg_send![ns_image, initWithCGImage: image size: size];
 
-    let () = msg_send![ns_app, setApplicationIconImage: ns_image];
     CGDataProviderRelease(provider);
     CGColorSpaceRelease(rgb);
     CGImageRelease(image);
+    ns_image
 }
 
 pub unsafe fn run<F>(conf: crate::conf::Conf, f: F)
@@ -918,6 +2368,11 @@ where
     crate::set_display(NativeDisplayData {
         high_dpi: conf.high_dpi,
         gfx_api: conf.platform.apple_gfx_api,
+        swap_control: true,
+        target_fps: conf.max_fps,
+        background_mode: conf.background_mode,
+        system_theme: system_theme(),
+        power_info: crate::native::apple::power::power_info(),
         ..NativeDisplayData::new(conf.window_width, conf.window_height, tx, clipboard)
     });
 
@@ -925,11 +2380,31 @@ where
         view: std::ptr::null_mut(),
         window: std::ptr::null_mut(),
         fullscreen: false,
+        exclusive: None,
         cursor_shown: true,
         current_cursor: CursorIcon::Default,
         cursor_grabbed: false,
         cursors: HashMap::new(),
+        custom_cursor: None,
         gfx_api: conf.platform.apple_gfx_api,
+        swap_interval: conf.platform.swap_interval.unwrap_or(1),
+        frame_limiter: FrameLimiter::new(),
+        wait_for_events: conf.wait_for_events,
+        had_events: false,
+        decorated: true,
+        resizable: conf.window_resizable,
+        fullsize_content_view: conf.platform.fullsize_content_view,
+        traffic_light_position: conf.platform.traffic_light_position,
+        always_on_top: false,
+        marked_text: None,
+        key_text: None,
+        ime_cursor_area: (0., 0., 0., 0.),
+        text_input: true,
+        pen_eraser: false,
+        gamepads: crate::native::apple::gamepad::Gamepads::new(),
+        power: crate::native::apple::power::Power::new(),
+        sleep_assertion: None,
+        dock_menu: nil,
         f: Some(Box::new(f)),
         event_handler: None,
         native_requests: rx,
@@ -939,8 +2414,19 @@ where
     let app_delegate_class = define_app_delegate();
     let app_delegate_instance: ObjcId = msg_send![app_delegate_class, new];
 
+    (*app_delegate_instance).set_ivar("display_ptr", &mut display as *mut _ as *mut c_void);
+
+    // the name of the executable otherwise, without a bundle
+    if let Some(app_id) = &conf.platform.app_id {
+        let process_info: ObjcId = msg_send![class!(NSProcessInfo), processInfo];
+        let () = msg_send![process_info, setProcessName: str_to_nsstring(app_id)];
+    }
+
     let ns_app: ObjcId = msg_send![class!(NSApplication), sharedApplication];
     let () = msg_send![ns_app, setDelegate: app_delegate_instance];
+
+    let menu_bar = create_menu_bar(&conf.window_title);
+    let () = msg_send![ns_app, setMainMenu: menu_bar];
     let () = msg_send![
         ns_app,
         setActivationPolicy: NSApplicationActivationPolicy::NSApplicationActivationPolicyRegular
@@ -949,13 +2435,10 @@ where
     let () = msg_send![ns_app, activateIgnoringOtherApps: YES];
 
     if let Some(icon) = &conf.icon {
-        set_icon(ns_app, icon);
+        set_icon(ns_app, 64, 64, &icon.big);
     }
 
-    let window_masks = NSWindowStyleMask::NSTitledWindowMask as u64
-        | NSWindowStyleMask::NSClosableWindowMask as u64
-        | NSWindowStyleMask::NSMiniaturizableWindowMask as u64
-        | NSWindowStyleMask::NSResizableWindowMask as u64;
+    let window_masks = display.style_mask();
     //| NSWindowStyleMask::NSFullSizeContentViewWindowMask as u64;
 
     let window_frame = NSRect {
@@ -966,7 +2449,7 @@ where
         },
     };
 
-    let window: ObjcId = msg_send![class!(NSWindow), alloc];
+    let window: ObjcId = msg_send![define_cocoa_window(), alloc];
     let window: ObjcId = msg_send![
         window,
         initWithContentRect: window_frame
@@ -987,6 +2470,9 @@ where
     let () = msg_send![window, setTitle: title];
     let () = msg_send![window, center];
     let () = msg_send![window, setAcceptsMouseMovedEvents: YES];
+    if conf.platform.titlebar_transparent {
+        let () = msg_send![window, setTitlebarAppearsTransparent: YES];
+    }
 
     let view = match conf.platform.apple_gfx_api {
         AppleGfxApi::OpenGl => create_opengl_view(window_frame, conf.sample_count, conf.high_dpi),
@@ -995,14 +2481,63 @@ where
     {
         let mut d = native_display().lock().unwrap();
         d.view = view;
+        #[cfg(feature = "raw-window-handle")]
+        {
+            d.window_handle = Some(crate::native::WindowHandle::app_kit(window as _, view as _));
+        }
     }
     (*view).set_ivar("display_ptr", &mut display as *mut _ as *mut c_void);
 
     display.window = window;
     display.view = view;
+    display.position_traffic_lights();
+    if conf.platform.apple_gfx_api == AppleGfxApi::Metal {
+        display.set_swap_interval(display.swap_interval);
+        // MTKView draws continuously on its own, the timer does it instead
+        if conf.wait_for_events {
+            let () = msg_send![view, setPaused: YES];
+            let () = msg_send![view, setEnableSetNeedsDisplay: YES];
+        }
+    }
+
+    if conf.transparent {
+        let () = msg_send![window, setOpaque: NO];
+        let clear_color: ObjcId = msg_send![class!(NSColor), clearColor];
+        let () = msg_send![window, setBackgroundColor: clear_color];
+        match conf.platform.apple_gfx_api {
+            AppleGfxApi::OpenGl => {
+                let mut opacity: i32 = 0;
+                let ctx: ObjcId = msg_send![view, openGLContext];
+                let () = msg_send![ctx,
+                                   setValues:&mut opacity
+                                   forParameter:NSOpenGLContextParameterSurfaceOpacity];
+            }
+            AppleGfxApi::Metal => {
+                let layer: ObjcId = msg_send![view, layer];
+                let () = msg_send![layer, setOpaque: NO];
+            }
+        }
+    }
 
     let () = msg_send![window, setContentView: view];
 
+    // mouseEntered: and mouseExited:, the visible rect follows the view size
+    let tracking_options =
+        NSTrackingMouseEnteredAndExited | NSTrackignActiveAlways | NSTrackingInVisibleRect;
+    let tracking_area: ObjcId = msg_send![class!(NSTrackingArea), alloc];
+    let tracking_area: ObjcId = msg_send![tracking_area,
+                                          initWithRect: NSRect::new(0., 0., 0., 0.)
+                                          options: tracking_options
+                                          owner: view
+                                          userInfo: nil];
+    let () = msg_send![view, addTrackingArea: tracking_area];
+
+    // NSPasteboardTypeFileURL
+    let file_url = str_to_nsstring("public.file-url");
+    let dragged_types: ObjcId = msg_send![class!(NSArray), arrayWithObject: file_url];
+    let () = msg_send![view, registerForDraggedTypes: dragged_types];
+
+    display.update_dpi_scale();
     let _ = display.update_dimensions();
 
     let nstimer: ObjcId = msg_send![
@@ -1024,6 +2559,10 @@ where
     }
 
     let () = msg_send![window, makeKeyAndOrderFront: nil];
+    // [window screen] is nil until the window is on screen
+    display.update_monitors();
+    // windowDidMove: keeps it up to date from here
+    native_display().lock().unwrap().screen_position = display.window_position();
 
     let ns_app: ObjcId = msg_send![class!(NSApplication), sharedApplication];
 
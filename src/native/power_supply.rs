@@ -0,0 +1,81 @@
+//! `window::power_info` for the X11 and Wayland main loops, from
+//! /sys/class/power_supply, which is read again every few seconds.
+
+use crate::{EventHandler, PowerInfo, PowerState};
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+fn read(path: &Path, name: &str) -> Option<String> {
+    let value = std::fs::read_to_string(path.join(name)).ok()?;
+    Some(value.trim().to_owned())
+}
+
+/// The first system battery and the AC adapters.
+pub fn power_info() -> PowerInfo {
+    let entries = match std::fs::read_dir("/sys/class/power_supply") {
+        Ok(entries) => entries,
+        Err(_) => return PowerInfo::default(),
+    };
+    let mut online = false;
+    let mut battery = None;
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        // the batteries of mice and gamepads
+        if read(&path, "scope").as_deref() == Some("Device") {
+            continue;
+        }
+        match read(&path, "type").as_deref() {
+            Some("Mains") => online |= read(&path, "online").as_deref() == Some("1"),
+            Some("Battery") if battery.is_none() => {
+                let capacity = read(&path, "capacity").and_then(|value| value.parse::<f32>().ok());
+                battery = Some((read(&path, "status"), capacity));
+            }
+            _ => {}
+        }
+    }
+
+    let (status, capacity) = match battery {
+        Some(battery) => battery,
+        None => {
+            return PowerInfo {
+                state: PowerState::NoBattery,
+                ..Default::default()
+            }
+        }
+    };
+    let state = match status.as_deref() {
+        Some("Charging") => PowerState::Charging,
+        Some("Discharging") => PowerState::OnBattery,
+        Some("Full") | Some("Not charging") => PowerState::Charged,
+        _ if online => PowerState::Charging,
+        _ => PowerState::OnBattery,
+    };
+    PowerInfo {
+        state,
+        battery_level: capacity.map(|capacity| (capacity / 100.).clamp(0., 1.)),
+        power_saver: false,
+    }
+}
+
+pub struct PowerSupply {
+    last_poll: Instant,
+}
+
+impl PowerSupply {
+    /// After the `power_info` of `NativeDisplayData`.
+    pub fn new() -> PowerSupply {
+        PowerSupply {
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub fn poll(&mut self, event_handler: &mut dyn EventHandler) {
+        if self.last_poll.elapsed() < Duration::from_secs(5) {
+            return;
+        }
+        self.last_poll = Instant::now();
+        crate::native::update_power_info(power_info(), event_handler);
+    }
+}
@@ -0,0 +1,222 @@
+//! `ScanCode`s, the USB HID usages of the physical keys, from what the
+//! platforms have.
+
+use crate::event::{KeyCode, ScanCode};
+
+// the place of each KeyCode on a US QWERTY keyboard
+const US_LAYOUT: &[(KeyCode, u16)] = &[
+    (KeyCode::Space, 0x2C),
+    (KeyCode::Apostrophe, 0x34),
+    (KeyCode::Comma, 0x36),
+    (KeyCode::Minus, 0x2D),
+    (KeyCode::Period, 0x37),
+    (KeyCode::Slash, 0x38),
+    (KeyCode::Key0, 0x27),
+    (KeyCode::Key1, 0x1E),
+    (KeyCode::Key2, 0x1F),
+    (KeyCode::Key3, 0x20),
+    (KeyCode::Key4, 0x21),
+    (KeyCode::Key5, 0x22),
+    (KeyCode::Key6, 0x23),
+    (KeyCode::Key7, 0x24),
+    (KeyCode::Key8, 0x25),
+    (KeyCode::Key9, 0x26),
+    (KeyCode::Semicolon, 0x33),
+    (KeyCode::Equal, 0x2E),
+    (KeyCode::A, 0x04),
+    (KeyCode::B, 0x05),
+    (KeyCode::C, 0x06),
+    (KeyCode::D, 0x07),
+    (KeyCode::E, 0x08),
+    (KeyCode::F, 0x09),
+    (KeyCode::G, 0x0A),
+    (KeyCode::H, 0x0B),
+    (KeyCode::I, 0x0C),
+    (KeyCode::J, 0x0D),
+    (KeyCode::K, 0x0E),
+    (KeyCode::L, 0x0F),
+    (KeyCode::M, 0x10),
+    (KeyCode::N, 0x11),
+    (KeyCode::O, 0x12),
+    (KeyCode::P, 0x13),
+    (KeyCode::Q, 0x14),
+    (KeyCode::R, 0x15),
+    (KeyCode::S, 0x16),
+    (KeyCode::T, 0x17),
+    (KeyCode::U, 0x18),
+    (KeyCode::V, 0x19),
+    (KeyCode::W, 0x1A),
+    (KeyCode::X, 0x1B),
+    (KeyCode::Y, 0x1C),
+    (KeyCode::Z, 0x1D),
+    (KeyCode::LeftBracket, 0x2F),
+    (KeyCode::Backslash, 0x31),
+    (KeyCode::RightBracket, 0x30),
+    (KeyCode::GraveAccent, 0x35),
+    (KeyCode::World1, 0x32),
+    (KeyCode::World2, 0x64),
+    (KeyCode::Escape, 0x29),
+    (KeyCode::Enter, 0x28),
+    (KeyCode::Tab, 0x2B),
+    (KeyCode::Backspace, 0x2A),
+    (KeyCode::Insert, 0x49),
+    (KeyCode::Delete, 0x4C),
+    (KeyCode::Right, 0x4F),
+    (KeyCode::Left, 0x50),
+    (KeyCode::Down, 0x51),
+    (KeyCode::Up, 0x52),
+    (KeyCode::PageUp, 0x4B),
+    (KeyCode::PageDown, 0x4E),
+    (KeyCode::Home, 0x4A),
+    (KeyCode::End, 0x4D),
+    (KeyCode::CapsLock, 0x39),
+    (KeyCode::ScrollLock, 0x47),
+    (KeyCode::NumLock, 0x53),
+    (KeyCode::PrintScreen, 0x46),
+    (KeyCode::Pause, 0x48),
+    (KeyCode::F1, 0x3A),
+    (KeyCode::F2, 0x3B),
+    (KeyCode::F3, 0x3C),
+    (KeyCode::F4, 0x3D),
+    (KeyCode::F5, 0x3E),
+    (KeyCode::F6, 0x3F),
+    (KeyCode::F7, 0x40),
+    (KeyCode::F8, 0x41),
+    (KeyCode::F9, 0x42),
+    (KeyCode::F10, 0x43),
+    (KeyCode::F11, 0x44),
+    (KeyCode::F12, 0x45),
+    (KeyCode::F13, 0x68),
+    (KeyCode::F14, 0x69),
+    (KeyCode::F15, 0x6A),
+    (KeyCode::F16, 0x6B),
+    (KeyCode::F17, 0x6C),
+    (KeyCode::F18, 0x6D),
+    (KeyCode::F19, 0x6E),
+    (KeyCode::F20, 0x6F),
+    (KeyCode::F21, 0x70),
+    (KeyCode::F22, 0x71),
+    (KeyCode::F23, 0x72),
+    (KeyCode::F24, 0x73),
+    (KeyCode::Kp0, 0x62),
+    (KeyCode::Kp1, 0x59),
+    (KeyCode::Kp2, 0x5A),
+    (KeyCode::Kp3, 0x5B),
+    (KeyCode::Kp4, 0x5C),
+    (KeyCode::Kp5, 0x5D),
+    (KeyCode::Kp6, 0x5E),
+    (KeyCode::Kp7, 0x5F),
+    (KeyCode::Kp8, 0x60),
+    (KeyCode::Kp9, 0x61),
+    (KeyCode::KpDecimal, 0x63),
+    (KeyCode::KpDivide, 0x54),
+    (KeyCode::KpMultiply, 0x55),
+    (KeyCode::KpSubtract, 0x56),
+    (KeyCode::KpAdd, 0x57),
+    (KeyCode::KpEnter, 0x58),
+    (KeyCode::KpEqual, 0x67),
+    (KeyCode::LeftShift, 0xE1),
+    (KeyCode::LeftControl, 0xE0),
+    (KeyCode::LeftAlt, 0xE2),
+    (KeyCode::LeftSuper, 0xE3),
+    (KeyCode::RightShift, 0xE5),
+    (KeyCode::RightControl, 0xE4),
+    (KeyCode::RightAlt, 0xE6),
+    (KeyCode::RightSuper, 0xE7),
+    (KeyCode::Menu, 0x65),
+];
+
+pub fn keycode_to_scancode(keycode: KeyCode) -> ScanCode {
+    US_LAYOUT
+        .iter()
+        .find(|(k, _)| *k == keycode)
+        .map_or(ScanCode(0), |(_, usage)| ScanCode(*usage))
+}
+
+pub fn scancode_to_keycode(scancode: ScanCode) -> KeyCode {
+    US_LAYOUT
+        .iter()
+        .find(|(_, usage)| *usage == scancode.0)
+        .map_or(KeyCode::Unknown, |(k, _)| *k)
+}
+
+// PC/AT set 1 scancodes without the E0 prefix, the first 89 evdev codes are the same
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "windows"))]
+#[rustfmt::skip]
+const SET1: [u16; 89] = [
+    0x00, 0x29, 0x1E, 0x1F, 0x20, 0x21, 0x22, 0x23,
+    0x24, 0x25, 0x26, 0x27, 0x2D, 0x2E, 0x2A, 0x2B,
+    0x14, 0x1A, 0x08, 0x15, 0x17, 0x1C, 0x18, 0x0C,
+    0x12, 0x13, 0x2F, 0x30, 0x28, 0xE0, 0x04, 0x16,
+    0x07, 0x09, 0x0A, 0x0B, 0x0D, 0x0E, 0x0F, 0x33,
+    0x34, 0x35, 0xE1, 0x31, 0x1D, 0x1B, 0x06, 0x19,
+    0x05, 0x11, 0x10, 0x36, 0x37, 0x38, 0xE5, 0x55,
+    0xE2, 0x2C, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E,
+    0x3F, 0x40, 0x41, 0x42, 0x43, 0x53, 0x47, 0x5F,
+    0x60, 0x61, 0x56, 0x5C, 0x5D, 0x5E, 0x57, 0x59,
+    0x5A, 0x5B, 0x62, 0x63, 0x00, 0x00, 0x64, 0x44,
+    0x45,
+];
+
+/// A set 1 scancode from WM_KEYDOWN, plus 0x100 with the E0 prefix.
+#[cfg(target_os = "windows")]
+pub fn from_windows(code: u32) -> ScanCode {
+    ScanCode(match code {
+        // the other way around from set 1
+        0x045 => 0x48,
+        0x145 => 0x53,
+        0x054 => 0x46,
+        0x059 => 0x67,
+        0x064..=0x06E => 0x68 + (code - 0x064) as u16,
+        0x076 => 0x73,
+        0x11C => 0x58,
+        0x11D => 0xE4,
+        0x135 => 0x54,
+        0x137 => 0x46,
+        0x138 => 0xE6,
+        0x146 => 0x48,
+        0x147 => 0x4A,
+        0x148 => 0x52,
+        0x149 => 0x4B,
+        0x14B => 0x50,
+        0x14D => 0x4F,
+        0x14F => 0x4D,
+        0x150 => 0x51,
+        0x151 => 0x4E,
+        0x152 => 0x49,
+        0x153 => 0x4C,
+        0x15B => 0xE3,
+        0x15C => 0xE7,
+        0x15D => 0x65,
+        _ => SET1.get(code as usize).copied().unwrap_or(0),
+    })
+}
+
+/// A KEY_* code from linux/input-event-codes.h, the X11 keycode minus 8.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn from_evdev(code: u32) -> ScanCode {
+    ScanCode(match code {
+        96 => 0x58,
+        97 => 0xE4,
+        98 => 0x54,
+        99 => 0x46,
+        100 => 0xE6,
+        102 => 0x4A,
+        103 => 0x52,
+        104 => 0x4B,
+        105 => 0x50,
+        106 => 0x4F,
+        107 => 0x4D,
+        108 => 0x51,
+        109 => 0x4E,
+        110 => 0x49,
+        111 => 0x4C,
+        117 => 0x67,
+        119 => 0x48,
+        125 => 0xE3,
+        126 => 0xE7,
+        127 => 0x65,
+        183..=194 => 0x68 + (code - 183) as u16,
+        _ => SET1.get(code as usize).copied().unwrap_or(0),
+    })
+}
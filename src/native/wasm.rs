@@ -13,7 +13,9 @@ use std::{
 };
 
 use crate::{
-    event::EventHandler,
+    event::{
+        EventHandler, GamepadAxis, GamepadButton, GamepadState, PenState, ScrollDelta, TouchDetails,
+    },
     native::{NativeDisplayData, Request},
 };
 
@@ -30,6 +32,9 @@ use crate::{
 thread_local! {
     static EVENT_HANDLER: RefCell<Option<Box<dyn EventHandler>>> = RefCell::new(None);
     static REQUESTS: RefCell<Option<Receiver<Request>>> = RefCell::new(None);
+    static TOUCH_IDS: RefCell<crate::native::TouchIds> = RefCell::new(Default::default());
+    // the id of the gamepad at each index of navigator.getGamepads()
+    static GAMEPAD_IDS: RefCell<Vec<Option<(u64, String)>>> = RefCell::new(vec![]);
 }
 fn tl_event_handler<T, F: FnOnce(&mut dyn EventHandler) -> T>(f: F) -> T {
     EVENT_HANDLER.with(|globals| {
@@ -40,7 +45,10 @@ fn tl_event_handler<T, F: FnOnce(&mut dyn EventHandler) -> T>(f: F) -> T {
 }
 
 static mut CURSOR_ICON: crate::CursorIcon = crate::CursorIcon::Default;
+static mut CURSOR_CUSTOM: bool = false;
 static mut CURSOR_SHOW: bool = true;
+// window::set_text_input_enabled
+static mut TEXT_INPUT: bool = true;
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -67,6 +75,13 @@ where
         }));
     }
 
+    unsafe {
+        init_webgl(match conf.platform.gles_version {
+            crate::conf::GlesVersion::Gles2 => 1,
+            crate::conf::GlesVersion::Gles3 => 2,
+        });
+    }
+
     // setup initial canvas size
     unsafe {
         setup_canvas_size(conf.high_dpi);
@@ -77,9 +92,20 @@ where
     let w = unsafe { canvas_width() as _ };
     let h = unsafe { canvas_height() as _ };
     let clipboard = Box::new(Clipboard);
+    let dark = unsafe { sapp_dark_theme() };
     crate::set_display(NativeDisplayData {
+        system_theme: if dark {
+            crate::Theme::Dark
+        } else {
+            crate::Theme::Light
+        },
+        background_mode: conf.background_mode,
+        supported_orientations: conf.supported_orientations,
         ..NativeDisplayData::new(w, h, tx, clipboard)
     });
+    if conf.supported_orientations != crate::conf::Orientation::Any {
+        unsafe { sapp_lock_orientation(conf.supported_orientations as u32) };
+    }
     EVENT_HANDLER.with(|g| {
         *g.borrow_mut() = Some(f());
     });
@@ -99,6 +125,9 @@ pub unsafe fn sapp_height() -> ::std::os::raw::c_int {
 }
 
 extern "C" {
+    /// Create WebGL1 or WebGL2 context, WebGL2 falls back to WebGL1.
+    pub fn init_webgl(version: i32);
+    pub fn webgl_version() -> i32;
     pub fn setup_canvas_size(high_dpi: bool);
     pub fn run_animation_loop();
     pub fn canvas_width() -> i32;
@@ -120,14 +149,46 @@ extern "C" {
     pub fn sapp_set_cursor_grab(grab: bool);
 
     pub fn sapp_set_cursor(cursor: *const u8, len: usize);
+    /// Convert RGBA pixels to a css cursor, used by `sapp_set_custom_cursor`.
+    pub fn sapp_create_custom_cursor(
+        rgba: *const u8,
+        width: u32,
+        height: u32,
+        hotspot_x: u32,
+        hotspot_y: u32,
+    );
+    pub fn sapp_set_custom_cursor();
 
     pub fn sapp_is_elapsed_timer_supported() -> bool;
 
     pub fn sapp_set_fullscreen(fullscreen: bool);
+    /// 0 for any, 1 for portrait and 2 for landscape, locked from the next
+    /// fullscreen on if not now.
+    pub fn sapp_lock_orientation(orientation: u32);
     pub fn sapp_is_fullscreen() -> bool;
     pub fn sapp_set_window_size(new_width: u32, new_height: u32);
 
+    /// Takes navigator.getGamepads() for the sapp_gamepad functions, the
+    /// length of the array.
+    pub fn sapp_gamepads_poll() -> u32;
+    pub fn sapp_gamepad_connected(index: u32) -> bool;
+    pub fn sapp_gamepad_button(index: u32, button: u32) -> bool;
+    pub fn sapp_gamepad_button_value(index: u32, button: u32) -> f32;
+    pub fn sapp_gamepad_axis(index: u32, axis: u32) -> f32;
+    pub fn sapp_gamepad_id(index: u32, id: *mut u8, len: usize) -> usize;
+    /// The duration in milliseconds.
+    pub fn sapp_gamepad_rumble(index: u32, low_frequency: f32, high_frequency: f32, duration: u32);
+
     pub fn now() -> f64;
+    /// performance.now, in seconds.
+    pub fn sapp_timestamp_now() -> f64;
+    /// prefers-color-scheme: dark
+    pub fn sapp_dark_theme() -> bool;
+    /// The screen Wake Lock, while the page is visible.
+    pub fn sapp_set_keep_screen_on(keep_on: bool);
+    /// navigator.languages, comma separated, written to languages if it fits
+    /// in len. Returns the length.
+    pub fn sapp_locale(languages: *mut u8, len: usize) -> usize;
 }
 
 unsafe fn show_mouse(shown: bool) {
@@ -138,8 +199,9 @@ unsafe fn show_mouse(shown: bool) {
 }
 
 unsafe fn set_mouse_cursor(icon: crate::CursorIcon) {
-    if CURSOR_ICON != icon {
+    if CURSOR_ICON != icon || CURSOR_CUSTOM {
         CURSOR_ICON = icon;
+        CURSOR_CUSTOM = false;
         if CURSOR_SHOW {
             update_cursor();
         }
@@ -147,6 +209,10 @@ unsafe fn set_mouse_cursor(icon: crate::CursorIcon) {
 }
 
 pub unsafe fn update_cursor() {
+    if CURSOR_SHOW && CURSOR_CUSTOM {
+        sapp_set_custom_cursor();
+        return;
+    }
     let css_name = if !CURSOR_SHOW {
         "none"
     } else {
@@ -216,7 +282,10 @@ pub extern "C" fn frame() {
         while let Ok(request) = r.borrow_mut().as_mut().unwrap().try_recv() {
             use Request::*;
             match request {
-                Request::SetCursorGrab(grab) => unsafe { sapp_set_cursor_grab(grab) },
+                // no way to confine without pointer lock in the browser
+                Request::SetCursorGrab(mode) => unsafe {
+                    sapp_set_cursor_grab(mode != crate::CursorGrabMode::None)
+                },
                 Request::ShowMouse(show) => unsafe { show_mouse(show) },
                 Request::SetMouseCursor(cursor) => unsafe {
                     set_mouse_cursor(cursor);
@@ -224,23 +293,184 @@ pub extern "C" fn frame() {
                 Request::SetFullscreen(fullscreen) => unsafe {
                     sapp_set_fullscreen(fullscreen);
                 },
+                Request::LockOrientation(orientation) => unsafe {
+                    sapp_lock_orientation(orientation as u32);
+                },
+                Request::SetCustomMouseCursor {
+                    rgba,
+                    width,
+                    height,
+                    hotspot,
+                } => unsafe {
+                    sapp_create_custom_cursor(rgba.as_ptr(), width, height, hotspot.0, hotspot.1);
+                    CURSOR_CUSTOM = true;
+                    if CURSOR_SHOW {
+                        update_cursor();
+                    }
+                },
+                Request::SetTextInputEnabled(enabled) => unsafe { TEXT_INPUT = enabled },
+                Request::SetKeepScreenOn(keep_on) => unsafe { sapp_set_keep_screen_on(keep_on) },
+                Request::SetGamepadRumble {
+                    id,
+                    low_frequency,
+                    high_frequency,
+                    duration,
+                } => {
+                    let index = GAMEPAD_IDS.with(|ids| {
+                        ids.borrow()
+                            .iter()
+                            .position(|gamepad| matches!(gamepad, Some((other, _)) if *other == id))
+                    });
+                    if let Some(index) = index {
+                        let duration = duration.as_millis().min(u32::MAX as _) as u32;
+                        unsafe {
+                            sapp_gamepad_rumble(index as _, low_frequency, high_frequency, duration)
+                        };
+                    }
+                }
                 _ => {}
             }
         }
     });
     tl_event_handler(|event_handler| {
-        event_handler.update();
-        event_handler.draw();
+        unsafe { poll_gamepads(event_handler) };
+        crate::native::dispatch_custom_events(event_handler);
+        if !crate::native_display().lock().unwrap().frames_paused() {
+            event_handler.update();
+            event_handler.draw();
+        }
+    });
+}
+
+unsafe fn gamepad_name(index: u32) -> String {
+    let len = sapp_gamepad_id(index, std::ptr::null_mut(), 0);
+    let mut id = vec![0u8; len];
+    sapp_gamepad_id(index, id.as_mut_ptr(), len);
+    String::from_utf8_lossy(&id).into_owned()
+}
+
+// of the standard mapping, by index
+const GAMEPAD_BUTTONS: [(u32, GamepadButton); 15] = [
+    (0, GamepadButton::South),
+    (1, GamepadButton::East),
+    (2, GamepadButton::West),
+    (3, GamepadButton::North),
+    (4, GamepadButton::LeftShoulder),
+    (5, GamepadButton::RightShoulder),
+    (8, GamepadButton::Select),
+    (9, GamepadButton::Start),
+    (10, GamepadButton::LeftStick),
+    (11, GamepadButton::RightStick),
+    (12, GamepadButton::DPadUp),
+    (13, GamepadButton::DPadDown),
+    (14, GamepadButton::DPadLeft),
+    (15, GamepadButton::DPadRight),
+    (16, GamepadButton::Guide),
+];
+
+unsafe fn poll_gamepads(event_handler: &mut dyn EventHandler) {
+    let count = sapp_gamepads_poll() as usize;
+    GAMEPAD_IDS.with(|ids| {
+        let mut ids = ids.borrow_mut();
+        let len = ids.len().max(count);
+        ids.resize(len, None);
+        for (index, gamepad) in ids.iter_mut().enumerate() {
+            let index = index as u32;
+            if index as usize >= count || !sapp_gamepad_connected(index) {
+                if let Some((id, _)) = gamepad.take() {
+                    crate::native::update_gamepad(id, None, event_handler);
+                }
+                continue;
+            }
+            let (id, name) = gamepad
+                .get_or_insert_with(|| (crate::native::new_gamepad_id(), gamepad_name(index)));
+            let mut state = GamepadState {
+                name: name.clone(),
+                ..Default::default()
+            };
+            for (button_index, button) in GAMEPAD_BUTTONS {
+                state.set_button(button, sapp_gamepad_button(index, button_index));
+            }
+            state.set_axis(GamepadAxis::LeftX, sapp_gamepad_axis(index, 0));
+            state.set_axis(GamepadAxis::LeftY, sapp_gamepad_axis(index, 1));
+            state.set_axis(GamepadAxis::RightX, sapp_gamepad_axis(index, 2));
+            state.set_axis(GamepadAxis::RightY, sapp_gamepad_axis(index, 3));
+            let left_trigger = sapp_gamepad_button_value(index, 6);
+            let right_trigger = sapp_gamepad_button_value(index, 7);
+            state.set_axis(GamepadAxis::LeftTrigger, left_trigger);
+            state.set_axis(GamepadAxis::RightTrigger, right_trigger);
+            crate::native::update_gamepad(*id, Some(&state), event_handler);
+        }
     });
 }
 
 #[no_mangle]
-pub extern "C" fn mouse_move(x: i32, y: i32) {
+pub extern "C" fn mouse_move(x: i32, y: i32, modifiers: u32) {
+    let mods = keycodes::translate_mod(modifiers as _);
+    crate::native::update_keymods(mods);
     tl_event_handler(|event_handler| {
-        event_handler.mouse_motion_event(x as _, y as _);
+        event_handler.mouse_motion_keymods_event(x as _, y as _, mods);
     });
 }
 
+/// beforeunload, true for the event handlers that called cancel_quit.
+#[no_mangle]
+pub extern "C" fn quit_requested() -> bool {
+    crate::native_display().lock().unwrap().quit_requested = true;
+    tl_event_handler(|event_handler| event_handler.quit_requested_event());
+    let mut d = crate::native_display().lock().unwrap();
+    !std::mem::replace(&mut d.quit_requested, false)
+}
+
+/// prefers-color-scheme changes.
+#[no_mangle]
+pub extern "C" fn theme_changed(dark: bool) {
+    let theme = if dark {
+        crate::Theme::Dark
+    } else {
+        crate::Theme::Light
+    };
+    tl_event_handler(|event_handler| crate::native::update_system_theme(theme, event_handler));
+}
+
+/// The Battery Status API, desktops are charging and full there.
+#[no_mangle]
+pub extern "C" fn power_changed(level: f32, charging: bool) {
+    let state = match (charging, level >= 1.) {
+        (true, true) => crate::PowerState::Charged,
+        (true, false) => crate::PowerState::Charging,
+        (false, _) => crate::PowerState::OnBattery,
+    };
+    let power_info = crate::PowerInfo {
+        state,
+        battery_level: Some(level),
+        power_saver: false,
+    };
+    tl_event_handler(|event_handler| crate::native::update_power_info(power_info, event_handler));
+}
+
+/// document.hidden, from visibilitychange.
+#[no_mangle]
+pub extern "C" fn occluded(hidden: bool) {
+    tl_event_handler(|event_handler| crate::native::update_occluded(hidden, event_handler));
+}
+
+/// event.timeStamp, in seconds.
+#[no_mangle]
+pub extern "C" fn event_timestamp(timestamp: f64) {
+    crate::native::update_event_timestamp(timestamp);
+}
+
+#[no_mangle]
+pub extern "C" fn mouse_enter() {
+    tl_event_handler(|event_handler| event_handler.mouse_entered_event());
+}
+
+#[no_mangle]
+pub extern "C" fn mouse_leave() {
+    tl_event_handler(|event_handler| event_handler.mouse_left_event());
+}
+
 #[no_mangle]
 pub extern "C" fn raw_mouse_move(dx: i32, dy: i32) {
     tl_event_handler(|event_handler| {
@@ -249,27 +479,45 @@ pub extern "C" fn raw_mouse_move(dx: i32, dy: i32) {
 }
 
 #[no_mangle]
-pub extern "C" fn mouse_down(x: i32, y: i32, btn: i32) {
+pub extern "C" fn mouse_down(x: i32, y: i32, btn: i32, modifiers: u32) {
     let btn = keycodes::translate_mouse_button(btn);
+    let mods = keycodes::translate_mod(modifiers as _);
+    crate::native::update_keymods(mods);
 
     tl_event_handler(|event_handler| {
-        event_handler.mouse_button_down_event(btn, x as _, y as _);
+        event_handler.mouse_button_down_keymods_event(btn, x as _, y as _, mods);
     });
 }
 
 #[no_mangle]
-pub extern "C" fn mouse_up(x: i32, y: i32, btn: i32) {
+pub extern "C" fn mouse_up(x: i32, y: i32, btn: i32, modifiers: u32) {
     let btn = keycodes::translate_mouse_button(btn);
+    let mods = keycodes::translate_mod(modifiers as _);
+    crate::native::update_keymods(mods);
 
     tl_event_handler(|event_handler| {
-        event_handler.mouse_button_up_event(btn, x as _, y as _);
+        event_handler.mouse_button_up_keymods_event(btn, x as _, y as _, mods);
     });
 }
 
 #[no_mangle]
-pub extern "C" fn mouse_wheel(dx: i32, dy: i32) {
+pub extern "C" fn mouse_wheel(dx: f32, dy: f32, delta_mode: u32, modifiers: u32) {
+    const DOM_DELTA_PIXEL: u32 = 0;
+
+    let mods = keycodes::translate_mod(modifiers as _);
+    crate::native::update_keymods(mods);
+    // css pixels, or lines, or pages that are rare and scroll as lines
+    let delta = if delta_mode == DOM_DELTA_PIXEL {
+        let dpi_scale = unsafe { dpi_scale() };
+        ScrollDelta::Pixels(dx * dpi_scale, dy * dpi_scale)
+    } else {
+        ScrollDelta::Lines(dx, dy)
+    };
     tl_event_handler(|event_handler| {
-        event_handler.mouse_wheel_event(dx as _, dy as _);
+        // whole numbers, as when mouse_wheel took integers
+        event_handler.mouse_wheel_keymods_event(dx.trunc(), dy.trunc(), mods);
+        event_handler.mouse_scroll_event(delta, mods);
+        crate::native::wheel_magnify(delta, mods, event_handler);
     });
 }
 
@@ -277,15 +525,23 @@ pub extern "C" fn mouse_wheel(dx: i32, dy: i32) {
 pub extern "C" fn key_down(key: u32, modifiers: u32, repeat: bool) {
     let key = keycodes::translate_keycode(key as _);
     let mods = keycodes::translate_mod(modifiers as _);
+    crate::native::update_keymods(mods);
+
+    // from KeyboardEvent.code, the places of the keys already
+    let scancode = crate::native::scancodes::keycode_to_scancode(key);
 
     tl_event_handler(|event_handler| {
-        event_handler.key_down_event(key, mods, repeat);
+        crate::native::key_down(key, scancode, mods, repeat, event_handler);
     });
 }
 
 #[no_mangle]
 pub extern "C" fn key_press(key: u32) {
-    if let Some(key) = char::from_u32(key) {
+    if !unsafe { TEXT_INPUT } {
+        return;
+    }
+    // Enter comes as a keypress too
+    if let Some(key) = char::from_u32(key).filter(|key| !key.is_control()) {
         tl_event_handler(|event_handler| {
             event_handler.char_event(key, crate::KeyMods::default(), false);
         });
@@ -296,9 +552,12 @@ pub extern "C" fn key_press(key: u32) {
 pub extern "C" fn key_up(key: u32, modifiers: u32) {
     let key = keycodes::translate_keycode(key as _);
     let mods = keycodes::translate_mod(modifiers as _);
+    crate::native::update_keymods(mods);
+
+    let scancode = crate::native::scancodes::keycode_to_scancode(key);
 
     tl_event_handler(|event_handler| {
-        event_handler.key_up_event(key, mods);
+        crate::native::key_up(key, scancode, mods, event_handler);
     });
 }
 
@@ -310,21 +569,75 @@ pub extern "C" fn resize(width: i32, height: i32) {
         d.screen_height = height as _;
     }
     tl_event_handler(|event_handler| {
+        crate::native::update_orientation(event_handler);
         event_handler.resize_event(width as _, height as _);
     });
 }
 
 #[no_mangle]
-pub extern "C" fn touch(phase: u32, id: u32, x: f32, y: f32) {
+pub extern "C" fn safe_area_changed(left: f32, top: f32, right: f32, bottom: f32) {
+    let insets = crate::SafeAreaInsets {
+        left,
+        top,
+        right,
+        bottom,
+    };
+    tl_event_handler(|event_handler| crate::native::update_safe_area_insets(insets, event_handler));
+}
+
+#[no_mangle]
+pub extern "C" fn touch(
+    phase: u32,
+    id: u32,
+    x: f32,
+    y: f32,
+    force: f32,
+    radius_x: f32,
+    radius_y: f32,
+) {
     let phase = keycodes::translate_touch_phase(phase as _);
+    // browsers may reuse an identifier once its touch is over
+    let id = TOUCH_IDS.with(|ids| ids.borrow_mut().get(phase, id as _));
+    let dpi_scale = unsafe { dpi_scale() };
+    let details = TouchDetails {
+        // 0 when the device can't tell
+        pressure: (force > 0.).then_some(force),
+        radius: (radius_x * dpi_scale, radius_y * dpi_scale),
+        device_id: 0,
+    };
+    tl_event_handler(|event_handler| {
+        event_handler.touch_details_event(phase, id, x as _, y as _, details);
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn pen(x: f32, y: f32, pressure: f32, tilt_x: f32, tilt_y: f32, buttons: u32) {
+    let pen = PenState {
+        pressure,
+        tilt: (tilt_x, tilt_y),
+        contact: buttons & (1 | 32) != 0,
+        barrel_button: buttons & 2 != 0,
+        eraser: buttons & 32 != 0,
+    };
     tl_event_handler(|event_handler| {
-        event_handler.touch_event(phase, id as _, x as _, y as _);
+        event_handler.pen_event(x, y, pen);
     });
 }
 
+#[no_mangle]
+pub extern "C" fn context_lost() {
+    tl_event_handler(|event_handler| event_handler.context_lost());
+}
+
+#[no_mangle]
+pub extern "C" fn context_restored() {
+    tl_event_handler(|event_handler| event_handler.context_restored());
+}
+
 #[no_mangle]
 pub extern "C" fn focus(has_focus: bool) {
     tl_event_handler(|event_handler| {
+        crate::native::update_focus(has_focus, event_handler);
         if has_focus {
             event_handler.window_restored_event();
         } else {
@@ -333,6 +646,16 @@ pub extern "C" fn focus(has_focus: bool) {
     });
 }
 
+#[no_mangle]
+pub extern "C" fn on_files_hovered() {
+    tl_event_handler(|event_handler| event_handler.files_hovered_event());
+}
+
+#[no_mangle]
+pub extern "C" fn on_files_hover_cancelled() {
+    tl_event_handler(|event_handler| event_handler.files_hover_cancelled_event());
+}
+
 #[no_mangle]
 pub extern "C" fn on_files_dropped_start() {
     let mut d = crate::native_display().lock().unwrap();
@@ -9,6 +9,9 @@ pub fn translate_mouse_button(button: i32) -> MouseButton {
         0 => return MouseButton::Left,
         1 => return MouseButton::Right,
         2 => return MouseButton::Middle,
+        3 => return MouseButton::Back,
+        4 => return MouseButton::Forward,
+        5..=254 => return MouseButton::Other((button + 1) as u8),
         _ => return MouseButton::Unknown,
     };
 }
@@ -17,6 +20,8 @@ pub fn translate_mod(wasm_mods: i32) -> KeyMods {
     const SAPP_MODIFIER_CTRL: i32 = 2;
     const SAPP_MODIFIER_ALT: i32 = 4;
     const SAPP_MODIFIER_SUPER: i32 = 8;
+    const SAPP_MODIFIER_CAPS_LOCK: i32 = 16;
+    const SAPP_MODIFIER_NUM_LOCK: i32 = 32;
 
     let mut mods = KeyMods::default();
     if wasm_mods & SAPP_MODIFIER_SHIFT != 0 {
@@ -31,6 +36,12 @@ pub fn translate_mod(wasm_mods: i32) -> KeyMods {
     if wasm_mods & SAPP_MODIFIER_SUPER != 0 {
         mods.logo = true;
     }
+    if wasm_mods & SAPP_MODIFIER_CAPS_LOCK != 0 {
+        mods.caps_lock = true;
+    }
+    if wasm_mods & SAPP_MODIFIER_NUM_LOCK != 0 {
+        mods.num_lock = true;
+    }
     return mods;
 }
 
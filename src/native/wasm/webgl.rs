@@ -72,6 +72,10 @@ pub const GL_UNSIGNED_SHORT_5_5_5_1: u32 = 0x8034;
 pub const GL_NEAREST_MIPMAP_NEAREST: u32 = 0x2700;
 pub const GL_UNSIGNED_SHORT_4_4_4_4: u32 = 0x8033;
 pub const GL_SRC_ALPHA_SATURATE: u32 = 0x0308;
+pub const GL_SRC1_COLOR: u32 = 0x88F9;
+pub const GL_SRC1_ALPHA: u32 = 0x8589;
+pub const GL_ONE_MINUS_SRC1_COLOR: u32 = 0x88FA;
+pub const GL_ONE_MINUS_SRC1_ALPHA: u32 = 0x88FB;
 pub const GL_STREAM_DRAW: u32 = 0x88E0;
 pub const GL_ONE: u32 = 1;
 pub const GL_NEAREST_MIPMAP_LINEAR: u32 = 0x2702;
@@ -91,6 +95,10 @@ pub const GL_RGBA: u32 = 0x1908;
 pub const GL_TEXTURE_CUBE_MAP_POSITIVE_X: u32 = 0x8515;
 pub const GL_DECR: u32 = 0x1E03;
 pub const GL_FRAGMENT_SHADER: u32 = 0x8B30;
+pub const GL_GEOMETRY_SHADER: u32 = 0x8DD9;
+pub const GL_BUFFER: u32 = 0x82E0;
+pub const GL_PROGRAM: u32 = 0x82E2;
+pub const GL_TEXTURE: u32 = 0x1702;
 pub const GL_FLOAT: u32 = 0x1406;
 pub const GL_TEXTURE_MAX_LOD: u32 = 0x813B;
 pub const GL_DEPTH_COMPONENT: u32 = 0x1902;
@@ -152,6 +160,7 @@ pub const GL_LESS: u32 = 0x0201;
 pub const GL_MULTISAMPLE: u32 = 0x809D;
 pub const GL_FRAMEBUFFER_BINDING: u32 = 0x8CA6;
 pub const GL_BACK: u32 = 0x0405;
+pub const GL_FRONT_AND_BACK: u32 = 0x0408;
 pub const GL_ALWAYS: u32 = 0x0207;
 pub const GL_FUNC_ADD: u32 = 0x8006;
 pub const GL_ONE_MINUS_DST_COLOR: u32 = 0x0307;
@@ -233,13 +242,19 @@ pub const GL_RGB16_SNORM: u32 = 0x8F9A;
 pub const GL_RGBA16_SNORM: u32 = 0x8F9B;
 pub const GL_RGBA16: u32 = 0x805B;
 pub const GL_MAX_TEXTURE_SIZE: u32 = 0x0D33;
+pub const GL_MAX_SAMPLES: u32 = 0x8D57;
+pub const GL_MAX_COLOR_ATTACHMENTS: u32 = 0x8CDF;
 pub const GL_MAX_CUBE_MAP_TEXTURE_SIZE: u32 = 0x851C;
 pub const GL_MAX_3D_TEXTURE_SIZE: u32 = 0x8073;
 pub const GL_MAX_ARRAY_TEXTURE_LAYERS: u32 = 0x88FF;
 pub const GL_MAX_VERTEX_ATTRIBS: u32 = 0x8869;
+pub const GL_TRANSFORM_FEEDBACK_BUFFER: u32 = 0x8C8E;
+pub const GL_SEPARATE_ATTRIBS: u32 = 0x8C8D;
+pub const GL_RASTERIZER_DISCARD: u32 = 0x8C89;
 pub const GL_CLAMP_TO_BORDER: u32 = 0x812D;
 pub const GL_TEXTURE_BORDER_COLOR: u32 = 0x1004;
 pub const GL_UNPACK_ALIGNMENT: u32 = 3317;
+pub const GL_UNPACK_ROW_LENGTH: u32 = 3314;
 pub const GL_TEXTURE_SWIZZLE_R: u32 = 36418;
 pub const GL_TEXTURE_SWIZZLE_G: u32 = 36419;
 pub const GL_TEXTURE_SWIZZLE_B: u32 = 36420;
@@ -247,9 +262,29 @@ pub const GL_TEXTURE_SWIZZLE_A: u32 = 36421;
 pub const GL_TEXTURE_SWIZZLE_RGBA: u32 = 36422;
 pub const GL_DRAW_FRAMEBUFFER_BINDING: u32 = 36006;
 pub const GL_TIME_ELAPSED: u32 = 35007;
+pub const GL_TIMESTAMP: u32 = 0x8E28;
+pub const GL_ACTIVE_UNIFORMS: u32 = 0x8B86;
+pub const GL_ACTIVE_ATTRIBUTES: u32 = 0x8B89;
+pub const GL_FLOAT_VEC2: u32 = 0x8B50;
+pub const GL_FLOAT_VEC3: u32 = 0x8B51;
+pub const GL_FLOAT_VEC4: u32 = 0x8B52;
+pub const GL_INT_VEC2: u32 = 0x8B53;
+pub const GL_INT_VEC3: u32 = 0x8B54;
+pub const GL_INT_VEC4: u32 = 0x8B55;
+pub const GL_BOOL: u32 = 0x8B56;
+pub const GL_FLOAT_MAT2: u32 = 0x8B5A;
+pub const GL_FLOAT_MAT3: u32 = 0x8B5B;
+pub const GL_FLOAT_MAT4: u32 = 0x8B5C;
+pub const GL_SAMPLER_2D: u32 = 0x8B5E;
+pub const GL_SAMPLER_3D: u32 = 0x8B5F;
+pub const GL_SAMPLER_CUBE: u32 = 0x8B60;
+pub const GL_SAMPLER_2D_ARRAY: u32 = 0x8DC1;
+// not available on webgl1, but `BufferType::Storage` still needs a target
+pub const GL_SHADER_STORAGE_BUFFER: u32 = 0x90D2;
 pub const GL_QUERY_RESULT: u32 = 34918;
 pub const GL_QUERY_RESULT_AVAILABLE: u32 = 34919;
 pub const GL_VENDOR: u32 = 0x1F00;
+pub const GL_RENDERER: u32 = 0x1F01;
 pub const GL_VERSION: u32 = 0x1F02;
 pub const GL_SHADING_LANGUAGE_VERSION: GLenum = 0x8B8C;
 pub const GL_TEXTURE_BASE_LEVEL: GLenum = 0x813C;
@@ -1483,3 +1518,23 @@ extern "C" {
 pub unsafe fn is_gl2() -> bool {
     false
 }
+
+pub unsafe fn is_gles() -> bool {
+    true
+}
+
+/// WebGL has no KHR_debug, "gl-debug" feature does nothing on web.
+pub unsafe fn enable_debug_output() {}
+
+pub unsafe fn has_khr_debug() -> bool {
+    false
+}
+
+/// WebGL1 is roughly equivalent to GLES2 and WebGL2 to GLES3.
+pub unsafe fn gl_version() -> (u32, u32) {
+    if crate::native::wasm::webgl_version() == 2 {
+        (3, 0)
+    } else {
+        (2, 0)
+    }
+}
@@ -1,64 +1,160 @@
 use crate::{
-    conf::{Conf, Icon},
-    event::{KeyMods, MouseButton},
-    native::{NativeDisplayData, Request},
-    CursorIcon, EventHandler,
+    conf::{Conf, Icon, WindowsGl},
+    event::{KeyMods, MouseButton, PenState, ScrollDelta, TouchDetails, TouchPhase},
+    native::{
+        egl, frame_limiter::FrameLimiter, scancodes, NativeDisplayData, Request, SizeConstraints,
+    },
+    CursorGrabMode, CursorIcon, EventHandler, WindowArea, WindowState,
 };
 
 use winapi::{
     shared::{
         hidusage::{HID_USAGE_GENERIC_MOUSE, HID_USAGE_PAGE_GENERIC},
-        minwindef::{DWORD, HIWORD, LOWORD, LPARAM, LRESULT, UINT, WPARAM},
-        ntdef::NULL,
-        windef::{HCURSOR, HDC, HICON, HWND, POINT, RECT},
+        minwindef::{BOOL, DWORD, HIWORD, LOWORD, LPARAM, LRESULT, UINT, WPARAM},
+        ntdef::{HRESULT, LPCWSTR, NULL},
+        windef::{HCURSOR, HDC, HICON, HMONITOR, HWND, LPRECT, POINT, RECT},
         windowsx::{GET_X_LPARAM, GET_Y_LPARAM},
     },
     um::{
+        dwmapi::{
+            DwmEnableBlurBehindWindow, DwmSetWindowAttribute, DWM_BB_BLURREGION, DWM_BB_ENABLE,
+            DWM_BLURBEHIND,
+        },
         libloaderapi::{GetModuleHandleW, GetProcAddress},
+        shellapi::{DragAcceptFiles, DragFinish, DragQueryFileW, HDROP},
         shellscalingapi::*,
+        winbase::{GetSystemPowerStatus, SetThreadExecutionState, INFINITE, SYSTEM_POWER_STATUS},
         wingdi::*,
+        winnt::{ES_CONTINUOUS, ES_DISPLAY_REQUIRED, ES_SYSTEM_REQUIRED},
+        winreg::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD},
         winuser::*,
     },
 };
 
 mod clipboard;
 mod keycodes;
+mod libimm32;
 mod libopengl32;
+mod libpointer;
+mod taskbar;
 mod wgl;
+mod xinput;
 
+use libimm32::LibImm32;
 use libopengl32::LibOpengl32;
+use libpointer::LibPointer;
+
+struct EglContext {
+    libegl: egl::LibEgl,
+    display: egl::EGLDisplay,
+    surface: egl::EGLSurface,
+    context: egl::EGLContext,
+}
 
 pub(crate) struct WindowsDisplay {
     fullscreen: bool,
     dpi_aware: bool,
     window_resizable: bool,
-    cursor_grabbed: bool,
-    iconified: bool,
+    window_decorated: bool,
+    cursor_grab: CursorGrabMode,
     content_scale: f32,
     window_scale: f32,
     mouse_scale: f32,
     show_cursor: bool,
     user_cursor: bool,
+    // self.cursor is created by set_mouse_cursor_from_rgba and should be destroyed
+    custom_cursor: bool,
     mouse_x: f32,
     mouse_y: f32,
+    // TrackMouseEvent was called for the WM_MOUSELEAVE
+    mouse_tracked: bool,
     cursor: HCURSOR,
     libopengl32: LibOpengl32,
+    // Some if the context was created with EGL instead of WGL
+    egl: Option<EglContext>,
+    // Some otherwise, for the swap interval extensions
+    wgl: Option<wgl::Wgl>,
     _msg_wnd: HWND,
     msg_dc: HDC,
     wnd: HWND,
     dc: HDC,
     event_handler: Option<Box<dyn EventHandler>>,
     modal_resizing_timer: usize,
+    // between the first WM_SIZING and WM_EXITSIZEMOVE
+    resize_dragging: bool,
+    // the monitor with a changed video mode, set by set_exclusive_fullscreen
+    exclusive_device: Option<[u16; 32]>,
+    // checked by WM_GETMINMAXINFO and WM_SIZING
+    size_constraints: SizeConstraints,
+    libimm32: Option<LibImm32>,
+    // from set_ime_cursor_area, in client coordinates
+    ime_cursor_area: RECT,
+    // window::set_text_input_enabled
+    text_input: bool,
+    // the first half of a WM_CHAR surrogate pair
+    high_surrogate: Option<u16>,
+    // None before Windows 8, with no touch WM_POINTER messages
+    libpointer: Option<LibPointer>,
+    touch_ids: crate::native::TouchIds,
+    gamepads: xinput::Gamepads,
+    // conf.platform.fullsize_content_view, WM_NCCALCSIZE leaves the titlebar
+    // to the client area
+    fullsize_content: bool,
+    taskbar: taskbar::Taskbar,
+    // for GetRawInputBuffer, u64 for the alignment of the RAWINPUTs
+    raw_input_buffer: Vec<u64>,
+    // the last position of a device with absolute WM_INPUTs
+    raw_absolute: Option<(f32, f32)>,
 }
 
 impl WindowsDisplay {
-    fn set_cursor_grab(&mut self, grab: bool) {
-        self.cursor_grabbed = grab;
+    /// The WM_INPUTs in the queue all at once, instead of a message each,
+    /// for the mice polling at up to 8 kHz in the Locked mode. Every report
+    /// is a `raw_mouse_motion`, none are merged. False if there were none.
+    unsafe fn read_raw_input(&mut self) -> bool {
+        let header_size = std::mem::size_of::<RAWINPUTHEADER>() as UINT;
+        let buffer = self.raw_input_buffer.as_mut_ptr() as *mut u8;
+        let mut any = false;
+        loop {
+            let mut size = (self.raw_input_buffer.len() * 8) as UINT;
+            let count = GetRawInputBuffer(buffer as PRAWINPUT, &mut size, header_size);
+            // -1 on errors
+            if count == 0 || count == UINT::MAX {
+                return any;
+            }
+            any = true;
+            let mut offset = 0;
+            for _ in 0..count {
+                let raw = buffer.add(offset) as *const RAWINPUT;
+                if (*raw).header.dwType == RIM_TYPEMOUSE {
+                    let mouse = (*raw).data.mouse();
+                    if let Some((dx, dy)) =
+                        raw_mouse_delta(mouse, &mut self.raw_absolute, self.mouse_scale)
+                    {
+                        let event_handler = self.event_handler.as_mut().unwrap();
+                        event_handler.raw_mouse_motion(dx, dy);
+                    }
+                }
+                // NEXTRAWINPUTBLOCK, aligned to the pointer size
+                let align = std::mem::size_of::<usize>();
+                offset += ((*raw).header.dwSize as usize + align - 1) & !(align - 1);
+            }
+        }
+    }
+
+    fn set_cursor_grab(&mut self, mode: CursorGrabMode) {
+        let was_locked = self.cursor_grab == CursorGrabMode::Locked;
+        self.cursor_grab = mode;
         unsafe {
-            if grab {
-                update_clip_rect(self.wnd);
-            } else {
+            if mode == CursorGrabMode::None {
                 ClipCursor(NULL as _);
+            } else {
+                update_clip_rect(self.wnd, mode);
+            }
+            // ShowCursor is a counter, so this is independent of show_mouse
+            let locked = mode == CursorGrabMode::Locked;
+            if locked != was_locked {
+                ShowCursor((!locked).into());
             }
         }
     }
@@ -69,6 +165,10 @@ impl WindowsDisplay {
         }
     }
     fn set_mouse_cursor(&mut self, cursor_icon: CursorIcon) {
+        if self.custom_cursor {
+            unsafe { DestroyCursor(self.cursor) };
+            self.custom_cursor = false;
+        }
         let cursor_name = match cursor_icon {
             CursorIcon::Default => IDC_ARROW,
             CursorIcon::Help => IDC_HELP,
@@ -103,7 +203,7 @@ impl WindowsDisplay {
         rect.right = (rect.left + new_width as i32) as _;
         rect.top = (rect.bottom - new_height as i32) as _;
 
-        let win_style = get_win_style(self.fullscreen, self.window_resizable);
+        let win_style = self.win_style();
         let win_style_ex: DWORD = unsafe { GetWindowLongA(self.wnd, GWL_EXSTYLE) as _ };
         if unsafe {
             AdjustWindowRectEx(
@@ -130,6 +230,55 @@ impl WindowsDisplay {
             )
         };
     }
+    fn win_style(&self) -> DWORD {
+        get_win_style(
+            self.fullscreen,
+            self.window_resizable,
+            self.window_decorated,
+        )
+    }
+
+    /// `window::set_decorations` and `set_resizable`, the client area keeps its size.
+    /// In fullscreen only remembered for later.
+    unsafe fn update_win_style(&mut self) {
+        if self.fullscreen {
+            return;
+        }
+        let mut rect: RECT = std::mem::zeroed();
+        GetClientRect(self.wnd, &mut rect as *mut _ as _);
+        let win_style = self.win_style();
+        #[cfg(target_arch = "x86_64")]
+        SetWindowLongPtrA(self.wnd, GWL_STYLE, win_style as _);
+        #[cfg(target_arch = "i686")]
+        SetWindowLong(self.wnd, GWL_STYLE, win_style as _);
+
+        let (width, height) = self.frame_size(rect.right as _, rect.bottom as _);
+        SetWindowPos(
+            self.wnd,
+            NULL as _,
+            0,
+            0,
+            width,
+            height,
+            SWP_NOMOVE | SWP_NOZORDER | SWP_FRAMECHANGED,
+        );
+        ShowWindow(self.wnd, SW_SHOW);
+    }
+
+    /// The size of the window with its borders for this size of the client area.
+    fn frame_size(&self, width: u32, height: u32) -> (i32, i32) {
+        let mut rect = RECT {
+            left: 0,
+            top: 0,
+            right: width as _,
+            bottom: height as _,
+        };
+        let win_style = self.win_style();
+        let win_style_ex: DWORD = unsafe { GetWindowLongA(self.wnd, GWL_EXSTYLE) as _ };
+        unsafe { AdjustWindowRectEx(&mut rect, win_style, false as _, win_style_ex) };
+        (rect.right - rect.left, rect.bottom - rect.top)
+    }
+
     /// Set the window position in screen coordinates.
     fn set_window_position(&mut self, new_x: u32, new_y: u32) {
         let mut rect: RECT = unsafe { std::mem::zeroed() };
@@ -142,9 +291,10 @@ impl WindowsDisplay {
     }
 
     fn set_fullscreen(&mut self, fullscreen: bool) {
+        unsafe { self.restore_video_mode() };
         self.fullscreen = fullscreen as _;
 
-        let win_style: DWORD = get_win_style(self.fullscreen, self.window_resizable);
+        let win_style: DWORD = self.win_style();
 
         unsafe {
             #[cfg(target_arch = "x86_64")]
@@ -183,9 +333,90 @@ impl WindowsDisplay {
             ShowWindow(self.wnd, SW_SHOW);
         };
     }
+
+    unsafe fn set_exclusive_fullscreen(&mut self, monitor: usize, video_mode: crate::VideoMode) {
+        let device = match monitor_infos().get(monitor) {
+            Some((_, info)) => info.szDevice,
+            None => {
+                eprintln!("No monitor {}", monitor);
+                return;
+            }
+        };
+        self.restore_video_mode();
+
+        let mut mode: DEVMODEW = std::mem::zeroed();
+        mode.dmSize = std::mem::size_of::<DEVMODEW>() as _;
+        mode.dmPelsWidth = video_mode.resolution.0;
+        mode.dmPelsHeight = video_mode.resolution.1;
+        mode.dmFields = DM_PELSWIDTH | DM_PELSHEIGHT;
+        if let Some(rate) = video_mode.refresh_rate {
+            mode.dmDisplayFrequency = rate.round() as _;
+            mode.dmFields |= DM_DISPLAYFREQUENCY;
+        }
+        // CDS_FULLSCREEN is temporary, windows restores the mode when the process exits
+        let res = ChangeDisplaySettingsExW(
+            device.as_ptr(),
+            &mut mode,
+            NULL as _,
+            CDS_FULLSCREEN,
+            NULL as _,
+        );
+        if res != DISP_CHANGE_SUCCESSFUL {
+            eprintln!(
+                "Failed to switch to {:?}, the mode is unchanged",
+                video_mode
+            );
+            return;
+        }
+        self.exclusive_device = Some(device);
+        self.fullscreen = true;
+
+        // the monitor rect is new after the mode switch
+        let infos = monitor_infos();
+        let rect = match infos.iter().find(|(_, info)| info.szDevice == device) {
+            Some((_, info)) => info.rcMonitor,
+            None => return,
+        };
+        let win_style: DWORD = get_win_style(true, self.window_resizable, self.window_decorated);
+        #[cfg(target_arch = "x86_64")]
+        SetWindowLongPtrA(self.wnd, GWL_STYLE, win_style as _);
+        #[cfg(target_arch = "i686")]
+        SetWindowLong(self.wnd, GWL_STYLE, win_style as _);
+        SetWindowPos(
+            self.wnd,
+            HWND_TOP,
+            rect.left,
+            rect.top,
+            rect.right - rect.left,
+            rect.bottom - rect.top,
+            SWP_FRAMECHANGED,
+        );
+        ShowWindow(self.wnd, SW_SHOW);
+    }
+
+    unsafe fn restore_video_mode(&mut self) {
+        if let Some(device) = self.exclusive_device.take() {
+            ChangeDisplaySettingsExW(device.as_ptr(), NULL as _, NULL as _, 0, NULL as _);
+        }
+    }
+
+    /// With no messages, waits for the next one unless a frame is due,
+    /// false is a frame to draw now.
+    unsafe fn wait_for_events(&mut self) -> bool {
+        let timeout = match crate::native_display().lock().unwrap().frame_timeout() {
+            Some(timeout) if timeout == std::time::Duration::ZERO => return false,
+            // rounded up, to not wake up a bit early and spin on the last millisecond
+            Some(timeout) => {
+                ((timeout.as_micros() + 999) / 1000).min(INFINITE as u128 - 1) as DWORD
+            }
+            None => INFINITE,
+        };
+        MsgWaitForMultipleObjects(0, std::ptr::null(), false as _, timeout, QS_ALLINPUT);
+        true
+    }
 }
 
-fn get_win_style(is_fullscreen: bool, is_resizable: bool) -> DWORD {
+fn get_win_style(is_fullscreen: bool, is_resizable: bool, is_decorated: bool) -> DWORD {
     if is_fullscreen {
         WS_POPUP | WS_SYSMENU | WS_VISIBLE
     } else {
@@ -195,12 +426,18 @@ fn get_win_style(is_fullscreen: bool, is_resizable: bool) -> DWORD {
         if is_resizable {
             win_style |= WS_MAXIMIZEBOX | WS_SIZEBOX;
         }
+        // no caption and no sizing border, it still can be maximized
+        if !is_decorated {
+            win_style = (win_style & !(WS_CAPTION | WS_SIZEBOX)) | WS_POPUP;
+        }
 
         win_style
     }
 }
 
-unsafe fn update_clip_rect(hwnd: HWND) {
+// Locked keeps the cursor in a single pixel in the window center, WM_INPUT
+// raw deltas are still delivered
+unsafe fn update_clip_rect(hwnd: HWND, mode: CursorGrabMode) {
     // Retrieve the screen coordinates of the client area,
     // and convert them into client coordinates.
     let mut rect: RECT = std::mem::zeroed();
@@ -218,6 +455,13 @@ unsafe fn update_clip_rect(hwnd: HWND) {
     ClientToScreen(hwnd, &mut upper_left as *mut _ as _);
     ClientToScreen(hwnd, &mut lower_right as *mut _ as _);
 
+    if mode == CursorGrabMode::Locked {
+        upper_left.x = (upper_left.x + lower_right.x) / 2;
+        upper_left.y = (upper_left.y + lower_right.y) / 2;
+        lower_right.x = upper_left.x + 1;
+        lower_right.y = upper_left.y + 1;
+    }
+
     SetRect(
         &mut rect as *mut _ as _,
         upper_left.x,
@@ -228,6 +472,216 @@ unsafe fn update_clip_rect(hwnd: HWND) {
     ClipCursor(&mut rect as *mut _ as _);
 }
 
+/// The motion of a WM_INPUT, None for the reports of the buttons only.
+/// Tablets, remote desktops and VMs send absolute positions, the motion is
+/// from the one before.
+unsafe fn raw_mouse_delta(
+    mouse: &RAWMOUSE,
+    last_absolute: &mut Option<(f32, f32)>,
+    mouse_scale: f32,
+) -> Option<(f32, f32)> {
+    let (dx, dy) = if mouse.usFlags & MOUSE_MOVE_ABSOLUTE != 0 {
+        // normalised from 0 to 65535
+        let (width, height) = if mouse.usFlags & MOUSE_VIRTUAL_DESKTOP != 0 {
+            (SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN)
+        } else {
+            (SM_CXSCREEN, SM_CYSCREEN)
+        };
+        let x = mouse.lLastX as f32 / 65535. * GetSystemMetrics(width) as f32;
+        let y = mouse.lLastY as f32 / 65535. * GetSystemMetrics(height) as f32;
+        let (last_x, last_y) = last_absolute.replace((x, y))?;
+        (x - last_x, y - last_y)
+    } else {
+        *last_absolute = None;
+        (mouse.lLastX as f32, mouse.lLastY as f32)
+    };
+    if dx == 0. && dy == 0. {
+        return None;
+    }
+    Some((dx * mouse_scale, dy * mouse_scale))
+}
+
+/// All the monitors, the primary one first, in the `window::monitors()` order.
+unsafe fn monitor_infos() -> Vec<(HMONITOR, MONITORINFOEXW)> {
+    unsafe extern "system" fn push_monitor(
+        monitor: HMONITOR,
+        _: HDC,
+        _: LPRECT,
+        monitors: LPARAM,
+    ) -> BOOL {
+        let monitors = &mut *(monitors as *mut Vec<HMONITOR>);
+        monitors.push(monitor);
+        true as _
+    }
+    let mut handles: Vec<HMONITOR> = vec![];
+    EnumDisplayMonitors(
+        NULL as _,
+        NULL as _,
+        Some(push_monitor),
+        &mut handles as *mut _ as LPARAM,
+    );
+
+    let mut infos = vec![];
+    for handle in handles {
+        let mut info: MONITORINFOEXW = std::mem::zeroed();
+        info.cbSize = std::mem::size_of::<MONITORINFOEXW>() as _;
+        if GetMonitorInfoW(handle, &mut info as *mut _ as _) == 0 {
+            continue;
+        }
+        if info.dwFlags & MONITORINFOF_PRIMARY != 0 {
+            infos.insert(0, (handle, info));
+        } else {
+            infos.push((handle, info));
+        }
+    }
+    infos
+}
+
+// AppsUseLightTheme, Windows 10 and newer, light without it
+unsafe fn system_theme() -> crate::Theme {
+    let key = "Software\\Microsoft\\Windows\\CurrentVersion\\Themes\\Personalize\0"
+        .encode_utf16()
+        .collect::<Vec<u16>>();
+    let value = "AppsUseLightTheme\0".encode_utf16().collect::<Vec<u16>>();
+    let mut light: DWORD = 1;
+    let mut size = std::mem::size_of::<DWORD>() as DWORD;
+    RegGetValueW(
+        HKEY_CURRENT_USER,
+        key.as_ptr(),
+        value.as_ptr(),
+        RRF_RT_REG_DWORD,
+        std::ptr::null_mut(),
+        &mut light as *mut _ as _,
+        &mut size,
+    );
+    if light == 0 {
+        crate::Theme::Dark
+    } else {
+        crate::Theme::Light
+    }
+}
+
+// DWMWA_USE_IMMERSIVE_DARK_MODE, 20 since Windows 10 20H1 and 19 before it,
+// for a titlebar following the theme
+unsafe fn set_dark_titlebar(hwnd: HWND, theme: crate::Theme) {
+    let dark = (theme == crate::Theme::Dark) as BOOL;
+    for attribute in [20, 19].iter() {
+        let size = std::mem::size_of::<BOOL>() as DWORD;
+        if DwmSetWindowAttribute(hwnd, *attribute, &dark as *const _ as _, size) >= 0 {
+            break;
+        }
+    }
+}
+
+unsafe fn power_info() -> crate::PowerInfo {
+    use crate::PowerState;
+
+    let mut status: SYSTEM_POWER_STATUS = std::mem::zeroed();
+    if GetSystemPowerStatus(&mut status) == 0 {
+        return Default::default();
+    }
+    // 8 is charging, 128 no battery and 255 unknown
+    let state = match (status.ACLineStatus, status.BatteryFlag) {
+        (_, 255) => PowerState::Unknown,
+        (_, flag) if flag & 128 != 0 => PowerState::NoBattery,
+        (_, flag) if flag & 8 != 0 => PowerState::Charging,
+        (1, _) => PowerState::Charged,
+        _ => PowerState::OnBattery,
+    };
+    let percent = status.BatteryLifePercent;
+    crate::PowerInfo {
+        state,
+        battery_level: (percent <= 100 && state != PowerState::NoBattery)
+            .then(|| percent as f32 / 100.),
+        // SystemStatusFlag, the battery saver of Windows 10 and newer
+        power_saver: status.Reserved1 & 1 != 0,
+    }
+}
+
+unsafe fn monitors() -> Vec<crate::Monitor> {
+    let mut monitors = vec![];
+    for (handle, info) in monitor_infos() {
+        let rect = info.rcMonitor;
+
+        let mut mode: DEVMODEW = std::mem::zeroed();
+        mode.dmSize = std::mem::size_of::<DEVMODEW>() as _;
+        EnumDisplaySettingsW(info.szDevice.as_ptr(), ENUM_CURRENT_SETTINGS, &mut mode);
+        // 0 and 1 are "the hardware default"
+        let refresh_rate = if mode.dmDisplayFrequency > 1 {
+            Some(mode.dmDisplayFrequency as f32)
+        } else {
+            None
+        };
+
+        // szDevice is the adapter, like "\\.\DISPLAY1", the first device
+        // of the adapter is the monitor itself
+        let mut device: DISPLAY_DEVICEW = std::mem::zeroed();
+        device.cb = std::mem::size_of::<DISPLAY_DEVICEW>() as _;
+        let name = if EnumDisplayDevicesW(info.szDevice.as_ptr(), 0, &mut device, 0) != 0 {
+            &device.DeviceString[..]
+        } else {
+            &info.szDevice[..]
+        };
+        let len = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+        let name = String::from_utf16_lossy(&name[..len]);
+
+        let dc = CreateDCW(
+            info.szDevice.as_ptr(),
+            info.szDevice.as_ptr(),
+            NULL as _,
+            NULL as _,
+        );
+        let physical_size = if dc.is_null() {
+            (0, 0)
+        } else {
+            let size = (
+                GetDeviceCaps(dc, HORZSIZE) as u32,
+                GetDeviceCaps(dc, VERTSIZE) as u32,
+            );
+            DeleteDC(dc);
+            size
+        };
+
+        let mut dpix: UINT = 96;
+        let mut dpiy: UINT = 96;
+        GetDpiForMonitor(
+            handle,
+            MDT_EFFECTIVE_DPI,
+            &mut dpix as *mut _ as _,
+            &mut dpiy as *mut _ as _,
+        );
+
+        let mut video_modes: Vec<crate::VideoMode> = vec![];
+        let mut i = 0;
+        while EnumDisplaySettingsW(info.szDevice.as_ptr(), i, &mut mode) != 0 {
+            i += 1;
+            let video_mode = crate::VideoMode {
+                resolution: (mode.dmPelsWidth, mode.dmPelsHeight),
+                refresh_rate: Some(mode.dmDisplayFrequency as f32).filter(|rate| *rate > 1.),
+            };
+            // the same mode is listed for every color depth
+            if !video_modes.contains(&video_mode) {
+                video_modes.push(video_mode);
+            }
+        }
+
+        monitors.push(crate::Monitor {
+            name,
+            position: (rect.left, rect.top),
+            physical_size,
+            resolution: (
+                (rect.right - rect.left) as u32,
+                (rect.bottom - rect.top) as u32,
+            ),
+            refresh_rate,
+            video_modes,
+            dpi_scale: dpix as f32 / 96.0,
+        });
+    }
+    monitors
+}
+
+// also keeps window::modifiers current
 unsafe fn key_mods() -> KeyMods {
     let mut mods = KeyMods::default();
 
@@ -243,10 +697,29 @@ unsafe fn key_mods() -> KeyMods {
     if (GetKeyState(VK_LWIN) | GetKeyState(VK_RWIN)) as u32 & (1u32 << 31) != 0 {
         mods.logo = true;
     }
+    // the low bit is the toggle
+    if GetKeyState(VK_CAPITAL) & 1 != 0 {
+        mods.caps_lock = true;
+    }
+    if GetKeyState(VK_NUMLOCK) & 1 != 0 {
+        mods.num_lock = true;
+    }
+    crate::native::update_keymods(mods);
 
     mods
 }
 
+// the drags that leave the window keep their WM_MOUSEMOVEs and button up
+unsafe fn update_mouse_capture(hwnd: HWND, wparam: WPARAM) {
+    const BUTTONS: WPARAM = MK_LBUTTON | MK_RBUTTON | MK_MBUTTON | MK_XBUTTON1 | MK_XBUTTON2;
+    // the buttons held after the message, in the low word for WM_XBUTTON*
+    if LOWORD(wparam as _) as WPARAM & BUTTONS != 0 {
+        SetCapture(hwnd);
+    } else {
+        ReleaseCapture();
+    }
+}
+
 unsafe extern "system" fn win32_wndproc(
     hwnd: HWND,
     umsg: UINT,
@@ -271,6 +744,24 @@ unsafe extern "system" fn win32_wndproc(
     let payload = &mut *(display_ptr as *mut WindowsDisplay);
     let event_handler = payload.event_handler.as_mut().unwrap();
 
+    // GetMessageTime is of the last message from the queue, the input
+    // messages are all posted ones
+    if matches!(
+        umsg,
+        WM_KEYFIRST..=WM_KEYLAST
+            | WM_MOUSEFIRST..=WM_MOUSELAST
+            | WM_INPUT
+            | libpointer::WM_POINTERUPDATE..=libpointer::WM_POINTERUP
+    ) {
+        crate::native::update_event_timestamp_ms(GetMessageTime() as u32);
+    }
+
+    // registered at runtime, it can't be a match arm
+    if umsg == payload.taskbar.button_created_message && umsg != 0 {
+        payload.taskbar.button_created(hwnd);
+        return 0;
+    }
+
     match umsg {
         WM_CLOSE => {
             let mut quit_requested = false;
@@ -315,19 +806,108 @@ unsafe extern "system" fn win32_wndproc(
         WM_ERASEBKGND => {
             return 1;
         }
+        WM_NCCALCSIZE if wparam != 0 && payload.fullsize_content => {
+            if payload.window_decorated && !payload.fullscreen {
+                // the resize borders stay, the titlebar goes
+                let params = &mut *(lparam as *mut NCCALCSIZE_PARAMS);
+                let top = params.rgrc[0].top;
+                DefWindowProcW(hwnd, umsg, wparam, lparam);
+                params.rgrc[0].top = top;
+                // a maximized window goes past the monitor by its borders
+                if IsZoomed(hwnd) != 0 {
+                    params.rgrc[0].top +=
+                        GetSystemMetrics(SM_CYSIZEFRAME) + GetSystemMetrics(SM_CXPADDEDBORDER);
+                }
+                return 0;
+            }
+        }
+        WM_NCHITTEST => {
+            let hit = DefWindowProcW(hwnd, umsg, wparam, lparam);
+            if hit != HTCLIENT {
+                return hit;
+            }
+            let mut point = POINT {
+                x: GET_X_LPARAM(lparam),
+                y: GET_Y_LPARAM(lparam),
+            };
+            ScreenToClient(hwnd, &mut point);
+            let x = point.x as f32 * payload.mouse_scale;
+            let y = point.y as f32 * payload.mouse_scale;
+            // the caption keeps the drag, the snapping and the double click maximize
+            return match event_handler.hit_test(x, y) {
+                WindowArea::Client => HTCLIENT,
+                WindowArea::Caption => HTCAPTION,
+                WindowArea::Top => HTTOP,
+                WindowArea::Bottom => HTBOTTOM,
+                WindowArea::Left => HTLEFT,
+                WindowArea::Right => HTRIGHT,
+                WindowArea::TopLeft => HTTOPLEFT,
+                WindowArea::TopRight => HTTOPRIGHT,
+                WindowArea::BottomLeft => HTBOTTOMLEFT,
+                WindowArea::BottomRight => HTBOTTOMRIGHT,
+            };
+        }
         WM_SIZE => {
-            if payload.cursor_grabbed {
-                update_clip_rect(hwnd);
+            if payload.cursor_grab != CursorGrabMode::None {
+                update_clip_rect(hwnd, payload.cursor_grab);
             }
 
-            let iconified = wparam == SIZE_MINIMIZED;
-            if iconified != payload.iconified {
-                payload.iconified = iconified;
-                if iconified {
-                    event_handler.window_minimized_event();
-                } else {
-                    event_handler.window_restored_event();
+            let state = match wparam {
+                SIZE_MINIMIZED => Some(WindowState::Minimized),
+                SIZE_MAXIMIZED => Some(WindowState::Maximized),
+                SIZE_RESTORED => Some(WindowState::Normal),
+                // some other window was maximized or restored
+                _ => None,
+            };
+            if let Some(state) = state {
+                crate::native::update_window_state(state, event_handler.as_mut());
+                let minimized = state == WindowState::Minimized;
+                crate::native::update_occluded(minimized, event_handler.as_mut());
+            }
+        }
+        WM_GETMINMAXINFO => {
+            // a fullscreen window is the size of its monitor, whatever the constraints
+            if !payload.fullscreen {
+                let info = &mut *(lparam as *mut MINMAXINFO);
+                if let Some((width, height)) = payload.size_constraints.min {
+                    let (x, y) = payload.frame_size(width, height);
+                    info.ptMinTrackSize = POINT { x, y };
+                }
+                if let Some((width, height)) = payload.size_constraints.max {
+                    let (x, y) = payload.frame_size(width, height);
+                    info.ptMaxTrackSize = POINT { x, y };
+                }
+                return 0;
+            }
+        }
+        WM_SIZING => {
+            // only a resize drag sends these, a move does not
+            if !payload.resize_dragging {
+                payload.resize_dragging = true;
+                event_handler.resize_started_event();
+            }
+            if let Some((aspect_x, aspect_y)) = payload.size_constraints.aspect {
+                let rect = &mut *(lparam as *mut RECT);
+                let (frame_width, frame_height) = payload.frame_size(0, 0);
+                let width = (rect.right - rect.left - frame_width) as i64;
+                let height = (rect.bottom - rect.top - frame_height) as i64;
+                let (aspect_x, aspect_y) = (aspect_x.max(1) as i64, aspect_y.max(1) as i64);
+                // the edge being dragged decides which side follows the other
+                match wparam as u32 {
+                    WMSZ_TOP | WMSZ_BOTTOM => {
+                        let width = height * aspect_x / aspect_y;
+                        rect.right = rect.left + width as i32 + frame_width;
+                    }
+                    WMSZ_TOPLEFT | WMSZ_TOPRIGHT => {
+                        let height = width * aspect_y / aspect_x;
+                        rect.top = rect.bottom - height as i32 - frame_height;
+                    }
+                    _ => {
+                        let height = width * aspect_y / aspect_x;
+                        rect.bottom = rect.top + height as i32 + frame_height;
+                    }
                 }
+                return 1;
             }
         }
         WM_SETCURSOR => {
@@ -338,68 +918,165 @@ unsafe extern "system" fn win32_wndproc(
             }
         }
         WM_LBUTTONDOWN => {
+            update_mouse_capture(hwnd, wparam);
             let mouse_x = payload.mouse_x;
             let mouse_y = payload.mouse_y;
-            event_handler.mouse_button_down_event(MouseButton::Left, mouse_x, mouse_y);
+            let mods = key_mods();
+            event_handler.mouse_button_down_keymods_event(
+                MouseButton::Left,
+                mouse_x,
+                mouse_y,
+                mods,
+            );
         }
         WM_RBUTTONDOWN => {
+            update_mouse_capture(hwnd, wparam);
             let mouse_x = payload.mouse_x;
             let mouse_y = payload.mouse_y;
-
-            event_handler.mouse_button_down_event(MouseButton::Right, mouse_x, mouse_y);
+            let mods = key_mods();
+            event_handler.mouse_button_down_keymods_event(
+                MouseButton::Right,
+                mouse_x,
+                mouse_y,
+                mods,
+            );
         }
         WM_MBUTTONDOWN => {
+            update_mouse_capture(hwnd, wparam);
             let mouse_x = payload.mouse_x;
             let mouse_y = payload.mouse_y;
-
-            event_handler.mouse_button_down_event(MouseButton::Middle, mouse_x, mouse_y);
+            let mods = key_mods();
+            event_handler.mouse_button_down_keymods_event(
+                MouseButton::Middle,
+                mouse_x,
+                mouse_y,
+                mods,
+            );
         }
         WM_LBUTTONUP => {
+            update_mouse_capture(hwnd, wparam);
             let mouse_x = payload.mouse_x;
             let mouse_y = payload.mouse_y;
-
-            event_handler.mouse_button_up_event(MouseButton::Left, mouse_x, mouse_y);
+            let mods = key_mods();
+            event_handler.mouse_button_up_keymods_event(MouseButton::Left, mouse_x, mouse_y, mods);
         }
         WM_RBUTTONUP => {
+            update_mouse_capture(hwnd, wparam);
             let mouse_x = payload.mouse_x;
             let mouse_y = payload.mouse_y;
-
-            event_handler.mouse_button_up_event(MouseButton::Right, mouse_x, mouse_y);
+            let mods = key_mods();
+            event_handler.mouse_button_up_keymods_event(MouseButton::Right, mouse_x, mouse_y, mods);
         }
         WM_MBUTTONUP => {
+            update_mouse_capture(hwnd, wparam);
             let mouse_x = payload.mouse_x;
             let mouse_y = payload.mouse_y;
-
-            event_handler.mouse_button_up_event(MouseButton::Middle, mouse_x, mouse_y);
+            let mods = key_mods();
+            event_handler.mouse_button_up_keymods_event(
+                MouseButton::Middle,
+                mouse_x,
+                mouse_y,
+                mods,
+            );
+        }
+        WM_XBUTTONDOWN | WM_XBUTTONUP => {
+            update_mouse_capture(hwnd, wparam);
+            let mouse_x = payload.mouse_x;
+            let mouse_y = payload.mouse_y;
+            let mods = key_mods();
+            let btn = match HIWORD(wparam as _) {
+                XBUTTON1 => MouseButton::Back,
+                XBUTTON2 => MouseButton::Forward,
+                _ => MouseButton::Unknown,
+            };
+            if umsg == WM_XBUTTONDOWN {
+                event_handler.mouse_button_down_keymods_event(btn, mouse_x, mouse_y, mods);
+            } else {
+                event_handler.mouse_button_up_keymods_event(btn, mouse_x, mouse_y, mods);
+            }
+            // handled, no WM_APPCOMMAND browser back and forward from DefWindowProc
+            return 1;
         }
 
         WM_MOUSEMOVE => {
             payload.mouse_x = GET_X_LPARAM(lparam) as f32 * payload.mouse_scale;
             payload.mouse_y = GET_Y_LPARAM(lparam) as f32 * payload.mouse_scale;
-            // mouse enter was not handled by miniquad anyway
-            // if !_sapp.win32_mouse_tracked {
-            //     _sapp.win32_mouse_tracked = true;
-
-            //     let mut tme: TRACKMOUSEEVENT = std::mem::zeroed();
-
-            //     tme.cbSize = std::mem::size_of_val(&tme) as _;
-            //     tme.dwFlags = TME_LEAVE;
-            //     tme.hwndTrack = wnd;
-            //     TrackMouseEvent(&mut tme as *mut _);
-            //     _sapp_win32_mouse_event(
-            //         sapp_event_type_SAPP_EVENTTYPE_MOUSE_ENTER,
-            //         sapp_mousebutton_SAPP_MOUSEBUTTON_INVALID,
-            //     );
-            // }
+            // no enter message, the first move after a WM_MOUSELEAVE is the enter
+            if !payload.mouse_tracked {
+                payload.mouse_tracked = true;
+
+                let mut tme: TRACKMOUSEEVENT = std::mem::zeroed();
+
+                tme.cbSize = std::mem::size_of_val(&tme) as _;
+                tme.dwFlags = TME_LEAVE;
+                tme.hwndTrack = hwnd;
+                TrackMouseEvent(&mut tme as *mut _);
+                event_handler.mouse_entered_event();
+            }
 
             let mouse_x = payload.mouse_x;
             let mouse_y = payload.mouse_y;
 
-            event_handler.mouse_motion_event(mouse_x, mouse_y);
+            // the cursor is pinned to the center, only raw motion makes sense
+            if payload.cursor_grab != CursorGrabMode::Locked {
+                let mods = key_mods();
+                event_handler.mouse_motion_keymods_event(mouse_x, mouse_y, mods);
+            }
+        }
+
+        WM_DPICHANGED => {
+            // only sent to per monitor dpi aware processes, with high_dpi
+            if payload.dpi_aware {
+                payload.window_scale = HIWORD(wparam as _) as f32 / 96.0;
+                payload.content_scale = payload.window_scale;
+                // the suggested rect, for the window to look the same size
+                let rect = &*(lparam as *const RECT);
+                SetWindowPos(
+                    hwnd,
+                    NULL as _,
+                    rect.left,
+                    rect.top,
+                    rect.right - rect.left,
+                    rect.bottom - rect.top,
+                    SWP_NOZORDER | SWP_NOACTIVATE,
+                );
+                let dpi_scale = payload.window_scale;
+                crate::native::update_dpi_scale(dpi_scale, event_handler.as_mut());
+            }
+            return 0;
+        }
+
+        WM_DISPLAYCHANGE => {
+            crate::native_display().lock().unwrap().monitors = monitors();
+        }
+
+        WM_POWERBROADCAST if wparam == PBT_APMPOWERSTATUSCHANGE => {
+            crate::native::update_power_info(power_info(), event_handler.as_mut());
+        }
+
+        WM_SETTINGCHANGE => {
+            // "ImmersiveColorSet" for the light and dark theme
+            let setting = lparam as *const u16;
+            if !setting.is_null() {
+                let len = (0..).take_while(|&i| *setting.add(i) != 0).count();
+                let setting = String::from_utf16_lossy(std::slice::from_raw_parts(setting, len));
+                if setting == "ImmersiveColorSet" {
+                    let theme = system_theme();
+                    set_dark_titlebar(hwnd, theme);
+                    crate::native::update_system_theme(theme, event_handler.as_mut());
+                }
+            }
         }
 
-        WM_MOVE if payload.cursor_grabbed => {
-            update_clip_rect(hwnd);
+        WM_MOVE => {
+            if payload.cursor_grab != CursorGrabMode::None {
+                update_clip_rect(hwnd, payload.cursor_grab);
+            }
+            let mut rect: RECT = std::mem::zeroed();
+            if GetWindowRect(hwnd, &mut rect as *mut _ as _) != 0 {
+                let position = (rect.left as u32, rect.top as u32);
+                crate::native::update_window_position(position, event_handler.as_mut());
+            }
         }
 
         WM_INPUT => {
@@ -416,61 +1093,218 @@ unsafe extern "system" fn win32_wndproc(
                 panic!("failed to retrieve raw input data");
             }
 
-            let mouse_scale = payload.mouse_scale;
-            let mut dx = data.data.mouse().lLastX as f32 * mouse_scale;
-            let mut dy = data.data.mouse().lLastY as f32 * mouse_scale;
-
-            // convert from normalised absolute coordinates
-            if (data.data.mouse().usFlags & MOUSE_MOVE_ABSOLUTE) == MOUSE_MOVE_ABSOLUTE {
-                let (width, height) = {
-                    let d = crate::native_display().lock().unwrap();
-                    (d.screen_width as f32, d.screen_height as f32)
-                };
+            let mouse = data.data.mouse();
+            if let Some((dx, dy)) =
+                raw_mouse_delta(mouse, &mut payload.raw_absolute, payload.mouse_scale)
+            {
+                event_handler.raw_mouse_motion(dx, dy);
+            }
+        }
 
-                dx = dx / 65535.0 * width;
-                dy = dy / 65535.0 * height;
+        WM_DROPFILES => {
+            use std::os::windows::ffi::OsStringExt;
+
+            let hdrop = wparam as HDROP;
+            let count = DragQueryFileW(hdrop, 0xFFFFFFFF, NULL as _, 0);
+            let mut paths = Vec::with_capacity(count as usize);
+            for i in 0..count {
+                let len = DragQueryFileW(hdrop, i, NULL as _, 0) as usize;
+                let mut path = vec![0u16; len + 1];
+                DragQueryFileW(hdrop, i, path.as_mut_ptr(), path.len() as _);
+                paths.push(std::ffi::OsString::from_wide(&path[..len]).into());
             }
+            DragFinish(hdrop);
 
-            event_handler.raw_mouse_motion(dx as f32, dy as f32);
+            crate::native_display().lock().unwrap().dropped_files = crate::native::DroppedFiles {
+                paths,
+                bytes: vec![],
+            };
+            event_handler.files_dropped_event();
+        }
+
+        libpointer::WM_POINTERDOWN | libpointer::WM_POINTERUPDATE | libpointer::WM_POINTERUP => {
+            let libpointer = match payload.libpointer {
+                Some(ref libpointer) => libpointer,
+                None => return DefWindowProcW(hwnd, umsg, wparam, lparam),
+            };
+            let scale = payload.mouse_scale;
+            // the mouse is left to DefWindowProc
+            match libpointer.pointer_type(wparam) {
+                Some(libpointer::PT_TOUCH) => {
+                    if let Some(info) = libpointer.touch_info(wparam) {
+                        let pointer = &info.pointerInfo;
+                        let phase = if pointer.pointerFlags & libpointer::POINTER_FLAG_CANCELED != 0
+                        {
+                            TouchPhase::Cancelled
+                        } else if umsg == libpointer::WM_POINTERDOWN {
+                            TouchPhase::Started
+                        } else if umsg == libpointer::WM_POINTERUP {
+                            TouchPhase::Ended
+                        } else {
+                            TouchPhase::Moved
+                        };
+                        let mut point = pointer.ptPixelLocation;
+                        ScreenToClient(hwnd, &mut point);
+                        let contact = info.rcContact;
+                        let details = TouchDetails {
+                            pressure: (info.touchMask & libpointer::TOUCH_MASK_PRESSURE != 0)
+                                .then(|| info.pressure as f32 / 1024.),
+                            radius: if info.touchMask & libpointer::TOUCH_MASK_CONTACTAREA != 0 {
+                                (
+                                    (contact.right - contact.left) as f32 * scale / 2.,
+                                    (contact.bottom - contact.top) as f32 * scale / 2.,
+                                )
+                            } else {
+                                (0., 0.)
+                            },
+                            device_id: pointer.sourceDevice as u64,
+                        };
+                        let id = payload.touch_ids.get(phase, pointer.pointerId as u64);
+                        event_handler.touch_details_event(
+                            phase,
+                            id,
+                            point.x as f32 * scale,
+                            point.y as f32 * scale,
+                            details,
+                        );
+                        // handled, no emulated mouse messages, touch_event emulates them
+                        return 0;
+                    }
+                }
+                Some(libpointer::PT_PEN) => {
+                    if let Some(info) = libpointer.pen_info(wparam) {
+                        let pointer = &info.pointerInfo;
+                        let mut point = pointer.ptPixelLocation;
+                        ScreenToClient(hwnd, &mut point);
+                        let flags = info.penFlags;
+                        let pressure = if info.penMask & libpointer::PEN_MASK_PRESSURE != 0 {
+                            info.pressure as f32 / 1024.
+                        } else {
+                            0.
+                        };
+                        let tilt_x = if info.penMask & libpointer::PEN_MASK_TILT_X != 0 {
+                            info.tiltX as f32
+                        } else {
+                            0.
+                        };
+                        let tilt_y = if info.penMask & libpointer::PEN_MASK_TILT_Y != 0 {
+                            info.tiltY as f32
+                        } else {
+                            0.
+                        };
+                        let pen = PenState {
+                            pressure,
+                            tilt: (tilt_x, tilt_y),
+                            contact: pointer.pointerFlags & libpointer::POINTER_FLAG_INCONTACT != 0,
+                            barrel_button: flags & libpointer::PEN_FLAG_BARREL != 0,
+                            eraser: flags
+                                & (libpointer::PEN_FLAG_INVERTED | libpointer::PEN_FLAG_ERASER)
+                                != 0,
+                        };
+                        event_handler.pen_event(
+                            point.x as f32 * scale,
+                            point.y as f32 * scale,
+                            pen,
+                        );
+                        // DefWindowProc still makes the mouse messages
+                    }
+                }
+                _ => {}
+            }
         }
 
         WM_MOUSELEAVE => {
-            // mouse leave was not handled by miniquad anyway
-            // _sapp.win32_mouse_tracked = false;
-            // _sapp_win32_mouse_event(
-            //     sapp_event_type_SAPP_EVENTTYPE_MOUSE_LEAVE,
-            //     sapp_mousebutton_SAPP_MOUSEBUTTON_INVALID,
-            // );
+            payload.mouse_tracked = false;
+            event_handler.mouse_left_event();
         }
+        // WHEEL_DELTA per notch, less at a time on high resolution wheels
         WM_MOUSEWHEEL => {
-            event_handler.mouse_wheel_event(0.0, (HIWORD(wparam as _) as i16) as f32);
+            let mods = key_mods();
+            let delta = (HIWORD(wparam as _) as i16) as f32;
+            event_handler.mouse_wheel_keymods_event(0.0, delta, mods);
+            let delta = ScrollDelta::Lines(0.0, delta / WHEEL_DELTA as f32);
+            event_handler.mouse_scroll_event(delta, mods);
+            crate::native::wheel_magnify(delta, mods, event_handler.as_mut());
         }
 
+        // right is positive
         WM_MOUSEHWHEEL => {
-            event_handler.mouse_wheel_event((HIWORD(wparam as _) as i16) as f32, 0.0);
+            let mods = key_mods();
+            let delta = (HIWORD(wparam as _) as i16) as f32;
+            event_handler.mouse_wheel_keymods_event(delta, 0.0, mods);
+            let lines = -delta / WHEEL_DELTA as f32;
+            event_handler.mouse_scroll_event(ScrollDelta::Lines(lines, 0.0), mods);
+        }
+        WM_SETFOCUS => crate::native::update_focus(true, event_handler.as_mut()),
+        // the key ups go to the next window
+        WM_KILLFOCUS => crate::native::update_focus(false, event_handler.as_mut()),
+        // the composition is drawn by the application, not in an IME window
+        WM_IME_SETCONTEXT => {
+            let lparam = lparam & !libimm32::ISC_SHOWUICOMPOSITIONWINDOW;
+            return DefWindowProcW(hwnd, umsg, wparam, lparam);
         }
-        WM_CHAR => {
-            let chr = wparam as u32;
+        WM_IME_STARTCOMPOSITION => payload.update_ime_cursor_area(),
+        WM_IME_COMPOSITION => {
+            if let Some(ref libimm32) = payload.libimm32 {
+                let himc = (libimm32.ImmGetContext)(hwnd);
+                if lparam as DWORD & libimm32::GCS_RESULTSTR != 0 {
+                    let text = libimm32.composition_string(himc, libimm32::GCS_RESULTSTR);
+                    event_handler.ime_commit_event(&String::from_utf16_lossy(&text));
+                }
+                if lparam as DWORD & libimm32::GCS_COMPSTR != 0 {
+                    let text = libimm32.composition_string(himc, libimm32::GCS_COMPSTR);
+                    // in utf-16 units
+                    let cursor = (libimm32.ImmGetCompositionStringW)(
+                        himc,
+                        libimm32::GCS_CURSORPOS,
+                        std::ptr::null_mut(),
+                        0,
+                    );
+                    let cursor = (cursor.max(0) as usize).min(text.len());
+                    let before_cursor = String::from_utf16_lossy(&text[..cursor]);
+                    let text = String::from_utf16_lossy(&text);
+                    event_handler.ime_composition_event(&text, before_cursor.len());
+                }
+                (libimm32.ImmReleaseContext)(hwnd, himc);
+                // no WM_IME_CHAR and WM_CHAR for the result
+                return 0;
+            }
+        }
+        WM_IME_ENDCOMPOSITION => event_handler.ime_composition_event("", 0),
+        WM_CHAR if payload.text_input => {
+            let unit = wparam as u16;
             let repeat = !!(lparam & 0x40000000) != 0;
             let mods = key_mods();
-            if chr > 0 {
-                if let Some(chr) = std::char::from_u32(chr as u32) {
+            // characters outside of the BMP come in two WM_CHARs
+            if (0xD800..0xDC00).contains(&unit) {
+                payload.high_surrogate = Some(unit);
+                return 0;
+            }
+            let units = match payload.high_surrogate.take() {
+                Some(high) => vec![high, unit],
+                None => vec![unit],
+            };
+            for chr in std::char::decode_utf16(units).filter_map(Result::ok) {
+                // Backspace, Enter, Tab and Escape, and Ctrl+letter
+                if !chr.is_control() {
                     event_handler.char_event(chr, mods, repeat);
                 }
             }
         }
         WM_KEYDOWN | WM_SYSKEYDOWN => {
-            let keycode = HIWORD(lparam as _) as u32 & 0x1FF;
-            let keycode = keycodes::translate_keycode(keycode);
+            let scancode = HIWORD(lparam as _) as u32 & 0x1FF;
+            let keycode = keycodes::translate_keycode(scancode);
+            let scancode = scancodes::from_windows(scancode);
             let mods = key_mods();
             let repeat = !!(lparam & 0x40000000) != 0;
-            event_handler.key_down_event(keycode, mods, repeat);
+            crate::native::key_down(keycode, scancode, mods, repeat, event_handler.as_mut());
         }
         WM_KEYUP | WM_SYSKEYUP => {
-            let keycode = HIWORD(lparam as _) as u32 & 0x1FF;
-            let keycode = keycodes::translate_keycode(keycode);
+            let scancode = HIWORD(lparam as _) as u32 & 0x1FF;
+            let keycode = keycodes::translate_keycode(scancode);
+            let scancode = scancodes::from_windows(scancode);
             let mods = key_mods();
-            event_handler.key_up_event(keycode, mods);
+            crate::native::key_up(keycode, scancode, mods, event_handler.as_mut());
         }
         WM_ENTERSIZEMOVE | WM_ENTERMENULOOP => {
             SetTimer(
@@ -482,10 +1316,13 @@ unsafe extern "system" fn win32_wndproc(
         }
         WM_TIMER => {
             if wparam == &mut payload.modal_resizing_timer as *mut _ as usize {
+                let event_handler = &mut **payload.event_handler.as_mut().unwrap();
+                payload.gamepads.poll(event_handler);
+                crate::native::dispatch_custom_events(event_handler);
                 payload.event_handler.as_mut().unwrap().update();
                 payload.event_handler.as_mut().unwrap().draw();
 
-                SwapBuffers(payload.dc);
+                payload.swap_buffers();
 
                 if payload.update_dimensions(hwnd) {
                     let d = crate::native_display().lock().unwrap();
@@ -502,6 +1339,9 @@ unsafe extern "system" fn win32_wndproc(
         }
         WM_EXITSIZEMOVE | WM_EXITMENULOOP => {
             KillTimer(hwnd, &mut payload.modal_resizing_timer as *mut _ as usize);
+            if std::mem::replace(&mut payload.resize_dragging, false) {
+                event_handler.resize_ended_event();
+            }
         }
         _ => {}
     }
@@ -509,7 +1349,13 @@ unsafe extern "system" fn win32_wndproc(
     DefWindowProcW(hwnd, umsg, wparam, lparam)
 }
 
-unsafe fn create_win_icon_from_image(width: u32, height: u32, colors: &[u8]) -> Option<HICON> {
+/// `cursor_hotspot` makes it a cursor instead of an icon.
+unsafe fn create_win_icon_from_image(
+    width: u32,
+    height: u32,
+    colors: &[u8],
+    cursor_hotspot: Option<(u32, u32)>,
+) -> Option<HICON> {
     let mut bi: BITMAPV5HEADER = std::mem::zeroed();
 
     bi.bV5Size = std::mem::size_of::<BITMAPV5HEADER>() as _;
@@ -555,9 +1401,10 @@ unsafe fn create_win_icon_from_image(width: u32, height: u32, colors: &[u8]) ->
     }
 
     let mut icon_info: ICONINFO = std::mem::zeroed();
-    icon_info.fIcon = 1;
-    icon_info.xHotspot = 0;
-    icon_info.yHotspot = 0;
+    icon_info.fIcon = cursor_hotspot.is_none() as _;
+    let (x_hotspot, y_hotspot) = cursor_hotspot.unwrap_or((0, 0));
+    icon_info.xHotspot = x_hotspot;
+    icon_info.yHotspot = y_hotspot;
     icon_info.hbmMask = mask;
     icon_info.hbmColor = color;
     let icon_handle = CreateIconIndirect(&mut icon_info);
@@ -585,8 +1432,8 @@ unsafe fn set_icon(wnd: HWND, icon: &Icon) {
         (&icon.medium[..], 32, 32)
     };
 
-    let big_icon = create_win_icon_from_image(big_icon.1, big_icon.2, big_icon.0);
-    let small_icon = create_win_icon_from_image(small_icon.1, small_icon.2, small_icon.0);
+    let big_icon = create_win_icon_from_image(big_icon.1, big_icon.2, big_icon.0, None);
+    let small_icon = create_win_icon_from_image(small_icon.1, small_icon.2, small_icon.0, None);
     if let Some(icon) = big_icon {
         SendMessageW(wnd, WM_SETICON, ICON_BIG as _, icon as LPARAM);
     }
@@ -680,6 +1527,9 @@ unsafe fn create_window(
         "Win32: failed to register for raw mouse input!"
     );
 
+    // WM_DROPFILES
+    DragAcceptFiles(hwnd, true as _);
+
     ShowWindow(hwnd, SW_SHOW);
     let dc = GetDC(hwnd);
     assert!(dc.is_null() == false);
@@ -687,6 +1537,43 @@ unsafe fn create_window(
     (hwnd, dc)
 }
 
+/// Composite the framebuffer alpha with the desktop. An empty blur region,
+/// DWM blends with the alpha and does not blur anything.
+unsafe fn set_transparent(hwnd: HWND) {
+    let region = CreateRectRgn(0, 0, -1, -1);
+    let blur_behind = DWM_BLURBEHIND {
+        dwFlags: DWM_BB_ENABLE | DWM_BB_BLURREGION,
+        fEnable: true as _,
+        hRgnBlur: region,
+        fTransitionOnMaximized: false as _,
+    };
+    if DwmEnableBlurBehindWindow(hwnd, &blur_behind) < 0 {
+        eprintln!("Win32: failed to make the window transparent");
+    }
+    DeleteObject(region as _);
+}
+
+/// The AppUserModelID, before the window is shown. From shell32, Windows 7
+/// and later.
+unsafe fn set_app_id(app_id: &str) {
+    type SetCurrentProcessExplicitAppUserModelID = extern "system" fn(_: LPCWSTR) -> HRESULT;
+
+    let shell32 = match crate::native::module::Module::load("shell32.dll") {
+        Ok(shell32) => shell32,
+        Err(_) => return,
+    };
+    let set_app_id: SetCurrentProcessExplicitAppUserModelID =
+        match shell32.get_symbol("SetCurrentProcessExplicitAppUserModelID") {
+            Ok(set_app_id) => set_app_id,
+            Err(_) => return,
+        };
+    let mut app_id = app_id.encode_utf16().collect::<Vec<u16>>();
+    app_id.push(0);
+    if set_app_id(app_id.as_ptr()) < 0 {
+        eprintln!("Win32: failed to set the app id");
+    }
+}
+
 unsafe fn create_msg_window() -> (HWND, HDC) {
     let class_name = "MINIQUADAPP\0".encode_utf16().collect::<Vec<u16>>();
     let window_name = "miniquad message window\0"
@@ -728,6 +1615,9 @@ unsafe fn create_msg_window() -> (HWND, HDC) {
 impl WindowsDisplay {
     unsafe fn get_proc_address(&mut self, proc: &str) -> Option<unsafe extern "C" fn() -> ()> {
         let proc = std::ffi::CString::new(proc).unwrap();
+        if let Some(egl) = &self.egl {
+            return (egl.libegl.eglGetProcAddress.unwrap())(proc.as_ptr() as _);
+        }
         let mut proc_ptr = (self.libopengl32.wglGetProcAddress)(proc.as_ptr());
         if proc_ptr.is_null() {
             proc_ptr = GetProcAddress(self.libopengl32.module.0, proc.as_ptr());
@@ -739,15 +1629,74 @@ impl WindowsDisplay {
         Some(std::mem::transmute(proc_ptr))
     }
 
+    unsafe fn swap_buffers(&mut self) {
+        match &self.egl {
+            Some(egl) => {
+                (egl.libegl.eglSwapBuffers.unwrap())(egl.display, egl.surface);
+            }
+            None => {
+                SwapBuffers(self.dc);
+            }
+        }
+    }
+
+    /// Try to create a GLES context with libEGL.dll, None if anything went wrong
+    /// and WGL should be used instead.
+    unsafe fn create_egl_context(&mut self, conf: &Conf) -> Option<EglContext> {
+        let mut libegl = egl::LibEgl::try_load()?;
+        let (context, config, display) = egl::create_egl_context(
+            &mut libegl,
+            self.dc as *mut _,
+            conf.platform.framebuffer_alpha || conf.transparent,
+            conf.sample_count,
+            conf.platform.gles_version,
+            conf.platform.gl_debug,
+        )
+        .ok()?;
+
+        let surface_attributes =
+            egl::window_surface_attributes(&libegl, display, conf.platform.srgb_framebuffer);
+        let surface = (libegl.eglCreateWindowSurface.unwrap())(
+            display,
+            config,
+            self.wnd,
+            surface_attributes.as_ptr() as _,
+        );
+        if surface == /* EGL_NO_SURFACE  */ std::ptr::null_mut() {
+            (libegl.eglDestroyContext.unwrap())(display, context);
+            return None;
+        }
+        if (libegl.eglMakeCurrent.unwrap())(display, surface, surface, context) == 0 {
+            (libegl.eglDestroySurface.unwrap())(display, surface);
+            (libegl.eglDestroyContext.unwrap())(display, context);
+            return None;
+        }
+        let swap_interval = conf.platform.swap_interval.unwrap_or(1);
+        egl::swap_interval(&mut libegl, display, swap_interval);
+
+        Some(EglContext {
+            libegl,
+            display,
+            surface,
+            context,
+        })
+    }
+
     /// updates current window and framebuffer size from the window's client rect,
-    /// and window position from the window's rect.
-    /// returns true if size or position has changed
+    /// the position is up to WM_MOVE.
+    /// returns true if size has changed
     unsafe fn update_dimensions(&mut self, hwnd: HWND) -> bool {
         let mut d = crate::native_display().lock().unwrap();
         let mut rect: RECT = std::mem::zeroed();
 
         // Get the outer rectangle of the window in screen coordinates
         if GetWindowRect(hwnd, &mut rect as *mut _ as _) != 0 {
+            d.current_monitor = crate::native::overlapping_monitor(
+                &d.monitors,
+                (rect.left, rect.top),
+                (rect.right - rect.left, rect.bottom - rect.top),
+            );
+
             // Get the client area rectangle in client coordinates
             let mut client_rect: RECT = std::mem::zeroed();
             if GetClientRect(hwnd, &mut client_rect as *mut _ as _) != 0 {
@@ -765,12 +1714,6 @@ impl WindowsDisplay {
                     d.screen_height = fb_height;
                     return true;
                 }
-
-                // Check for position changes
-                if (rect.left as u32, rect.top as u32) != d.screen_position {
-                    d.screen_position = (rect.left as u32, rect.top as u32);
-                    return true;
-                }
             } else {
                 // Handle error or default case
                 d.screen_width = 1;
@@ -784,12 +1727,43 @@ impl WindowsDisplay {
         false
     }
 
+    /// The composition and the candidate windows of the IME go at the
+    /// text cursor, the candidates are kept from covering it.
+    unsafe fn update_ime_cursor_area(&mut self) {
+        let libimm32 = match self.libimm32 {
+            Some(ref libimm32) => libimm32,
+            None => return,
+        };
+        let himc = (libimm32.ImmGetContext)(self.wnd);
+        if himc.is_null() {
+            return;
+        }
+        let area = self.ime_cursor_area;
+        let position = POINT {
+            x: area.left,
+            y: area.top,
+        };
+        let mut composition = libimm32::COMPOSITIONFORM {
+            dwStyle: libimm32::CFS_POINT,
+            ptCurrentPos: position,
+            rcArea: area,
+        };
+        (libimm32.ImmSetCompositionWindow)(himc, &mut composition);
+        let mut candidate = libimm32::CANDIDATEFORM {
+            dwIndex: 0,
+            dwStyle: libimm32::CFS_EXCLUDE,
+            ptCurrentPos: position,
+            rcArea: area,
+        };
+        (libimm32.ImmSetCandidateWindow)(himc, &mut candidate);
+        (libimm32.ImmReleaseContext)(self.wnd, himc);
+    }
+
     unsafe fn init_dpi(&mut self, high_dpi: bool) {
         self.dpi_aware = high_dpi;
-        // get dpi scale factor for main monitor
+        // get dpi scale factor for the monitor the window is on
         if self.dpi_aware {
-            let pt = POINT { x: 1, y: 1 };
-            let hm = MonitorFromPoint(pt, MONITOR_DEFAULTTONEAREST);
+            let hm = MonitorFromWindow(self.wnd, MONITOR_DEFAULTTONEAREST);
             let mut dpix: UINT = 0;
             let mut dpiy: UINT = 0;
             let hr = GetDpiForMonitor(
@@ -824,22 +1798,202 @@ impl WindowsDisplay {
                 } => self.set_window_size(new_width as _, new_height as _),
                 SetWindowPosition { new_x, new_y } => self.set_window_position(new_x, new_y),
                 SetFullscreen(fullscreen) => self.set_fullscreen(fullscreen),
+                SetExclusiveFullscreen { monitor, mode } => {
+                    self.set_exclusive_fullscreen(monitor, mode)
+                }
+                SetSwapInterval(interval) => {
+                    let res = match (&mut self.egl, &self.wgl) {
+                        (Some(egl), _) => {
+                            egl::swap_interval(&mut egl.libegl, egl.display, interval)
+                        }
+                        (None, Some(wgl)) => wgl.swap_interval(interval),
+                        (None, None) => false,
+                    };
+                    if !res {
+                        eprintln!("Failed to set swap interval {}", interval);
+                    }
+                }
+                SetSizeConstraints(constraints) => self.size_constraints = constraints,
+                SetDecorations(decorations) => {
+                    self.window_decorated = decorations;
+                    self.update_win_style();
+                }
+                SetResizable(resizable) => {
+                    self.window_resizable = resizable;
+                    self.update_win_style();
+                }
+                SetAlwaysOnTop(always_on_top) => {
+                    let insert_after = if always_on_top {
+                        HWND_TOPMOST
+                    } else {
+                        HWND_NOTOPMOST
+                    };
+                    SetWindowPos(self.wnd, insert_after, 0, 0, 0, 0, SWP_NOMOVE | SWP_NOSIZE);
+                }
+                SetOpacity(opacity) => {
+                    // layered only while it is see-through, it is slower to composite
+                    let mut ex_style = GetWindowLongA(self.wnd, GWL_EXSTYLE) as DWORD;
+                    if opacity < 1. {
+                        ex_style |= WS_EX_LAYERED;
+                    } else {
+                        ex_style &= !WS_EX_LAYERED;
+                    }
+                    #[cfg(target_arch = "x86_64")]
+                    SetWindowLongPtrA(self.wnd, GWL_EXSTYLE, ex_style as _);
+                    #[cfg(target_arch = "i686")]
+                    SetWindowLong(self.wnd, GWL_EXSTYLE, ex_style as _);
+                    if opacity < 1. {
+                        let alpha = (opacity * 255.).round() as u8;
+                        SetLayeredWindowAttributes(self.wnd, 0, alpha, LWA_ALPHA);
+                    }
+                }
+                RequestUserAttention(attention) => {
+                    if GetForegroundWindow() != self.wnd {
+                        // until the window comes to the foreground
+                        let flags = match attention {
+                            crate::UserAttentionType::Critical => FLASHW_ALL,
+                            crate::UserAttentionType::Informational => FLASHW_TRAY,
+                        };
+                        let mut flash_info = FLASHWINFO {
+                            cbSize: std::mem::size_of::<FLASHWINFO>() as _,
+                            hwnd: self.wnd,
+                            dwFlags: flags | FLASHW_TIMERNOFG,
+                            uCount: 0,
+                            dwTimeout: 0,
+                        };
+                        FlashWindowEx(&mut flash_info);
+                    }
+                }
+                SetWindowState(state) => {
+                    let command = match state {
+                        WindowState::Normal => SW_RESTORE,
+                        WindowState::Minimized => SW_MINIMIZE,
+                        WindowState::Maximized => SW_MAXIMIZE,
+                    };
+                    ShowWindow(self.wnd, command);
+                }
                 ShowKeyboard(show) => {
                     eprintln!("Not implemented for windows")
                 }
+                SetKeyboardType(_) => {}
+                LockOrientation(_) => {}
+                SetFullscreenImmersive(_) => {}
+                SetSystemBarsColor(_) => {}
+                SetMenus(_) => {}
+                SetDockMenu(_) => {}
+                SetTaskbarProgress(progress) => self.taskbar.set_progress(self.wnd, progress),
+                SetTaskbarOverlay(overlay) => {
+                    let (icon, description) = match overlay {
+                        Some((rgba, width, height, description)) => {
+                            let icon = create_win_icon_from_image(width, height, &rgba, None);
+                            (icon.unwrap_or(std::ptr::null_mut()), description)
+                        }
+                        None => (std::ptr::null_mut(), String::new()),
+                    };
+                    self.taskbar.set_overlay(self.wnd, icon, &description);
+                }
+                SetTextInputEnabled(enabled) => {
+                    self.text_input = enabled;
+                    if let Some(ref libimm32) = self.libimm32 {
+                        // no input context is no IME
+                        let flags = if enabled { libimm32::IACE_DEFAULT } else { 0 };
+                        (libimm32.ImmAssociateContextEx)(self.wnd, NULL as _, flags);
+                    }
+                }
+                SetImeCursorArea {
+                    x,
+                    y,
+                    width,
+                    height,
+                } => {
+                    // from the mouse event pixels
+                    let scale = self.mouse_scale;
+                    self.ime_cursor_area = RECT {
+                        left: (x / scale) as i32,
+                        top: (y / scale) as i32,
+                        right: ((x + width) / scale) as i32,
+                        bottom: ((y + height) / scale) as i32,
+                    };
+                    self.update_ime_cursor_area();
+                }
+                SetWindowIcon {
+                    rgba,
+                    width,
+                    height,
+                } => {
+                    // no levels of detail, windows will scale the same image
+                    if let Some(icon) = create_win_icon_from_image(width, height, &rgba, None) {
+                        SendMessageW(self.wnd, WM_SETICON, ICON_BIG as _, icon as LPARAM);
+                        SendMessageW(self.wnd, WM_SETICON, ICON_SMALL as _, icon as LPARAM);
+                    }
+                }
+                SetCustomMouseCursor {
+                    rgba,
+                    width,
+                    height,
+                    hotspot,
+                } => {
+                    if let Some(cursor) =
+                        create_win_icon_from_image(width, height, &rgba, Some(hotspot))
+                    {
+                        if self.custom_cursor {
+                            DestroyCursor(self.cursor);
+                        }
+                        self.cursor = cursor;
+                        self.custom_cursor = true;
+                        self.user_cursor = true;
+                        SetCursor(self.cursor);
+                    }
+                }
+                SetMousePosition { x, y } => {
+                    let mut point = POINT {
+                        x: (x / self.mouse_scale) as i32,
+                        y: (y / self.mouse_scale) as i32,
+                    };
+                    ClientToScreen(self.wnd, &mut point as *mut _ as _);
+                    SetCursorPos(point.x, point.y);
+                }
+                SetGamepadRumble {
+                    id,
+                    low_frequency,
+                    high_frequency,
+                    duration,
+                } => self
+                    .gamepads
+                    .rumble(id, low_frequency, high_frequency, duration),
+                // for the main thread, which is the one calling it
+                SetKeepScreenOn(keep_on) => {
+                    let flags = if keep_on {
+                        ES_CONTINUOUS | ES_DISPLAY_REQUIRED | ES_SYSTEM_REQUIRED
+                    } else {
+                        ES_CONTINUOUS
+                    };
+                    SetThreadExecutionState(flags);
+                }
             }
         }
     }
 }
 
                     //crate::native_display().lock().unwrap().window_position = (x, y);
+/// `window::mouse_button_count`, 0 without a mouse.
+pub(crate) fn mouse_button_count() -> u32 {
+    unsafe { GetSystemMetrics(SM_CMOUSEBUTTONS) as u32 }
+}
+
 pub fn run<F>(conf: &Conf, f: F)
 where
     F: 'static + FnOnce() -> Box<dyn EventHandler>,
 {
     unsafe {
         if conf.high_dpi {
-            SetProcessDPIAware();
+            // per monitor for WM_DPICHANGED, windows 8.1+
+            if SetProcessDpiAwareness(PROCESS_PER_MONITOR_DPI_AWARE) < 0 {
+                SetProcessDPIAware();
+            }
+        }
+        if let Some(app_id) = &conf.platform.app_id {
+            set_app_id(app_id);
         }
         let (wnd, dc) = create_window(
             &conf.window_title,
@@ -851,6 +2005,10 @@ where
         if let Some(icon) = &conf.icon {
             set_icon(wnd, icon);
         }
+        if conf.transparent {
+            set_transparent(wnd);
+        }
+        set_dark_titlebar(wnd, system_theme());
 
         let libopengl32 = LibOpengl32::try_load().expect("Failed to load opengl32.dll.");
 
@@ -859,23 +2017,41 @@ where
             fullscreen: false,
             dpi_aware: false,
             window_resizable: conf.window_resizable,
-            cursor_grabbed: false,
-            iconified: false,
+            window_decorated: true,
+            cursor_grab: CursorGrabMode::None,
             content_scale: 1.,
             mouse_scale: 1.,
             window_scale: 1.,
             mouse_x: 0.,
             mouse_y: 0.,
+            mouse_tracked: false,
             show_cursor: true,
             user_cursor: false,
+            custom_cursor: false,
             cursor: std::ptr::null_mut(),
             libopengl32,
+            egl: None,
+            wgl: None,
             _msg_wnd: msg_wnd,
             msg_dc,
             wnd,
             dc,
             event_handler: None,
             modal_resizing_timer: 0,
+            resize_dragging: false,
+            exclusive_device: None,
+            size_constraints: Default::default(),
+            libimm32: LibImm32::try_load(),
+            libpointer: LibPointer::try_load(),
+            touch_ids: Default::default(),
+            gamepads: xinput::Gamepads::new(),
+            ime_cursor_area: std::mem::zeroed(),
+            text_input: true,
+            high_surrogate: None,
+            fullsize_content: conf.platform.fullsize_content_view,
+            taskbar: taskbar::Taskbar::new(wnd),
+            raw_input_buffer: vec![0; 8192],
+            raw_absolute: None,
         };
         display.init_dpi(conf.high_dpi);
 
@@ -884,19 +2060,57 @@ where
         crate::set_display(NativeDisplayData {
             high_dpi: conf.high_dpi,
             dpi_scale: display.window_scale,
+            #[cfg(feature = "raw-window-handle")]
+            window_handle: Some(crate::native::WindowHandle::win32(
+                wnd as _,
+                GetModuleHandleW(NULL as _) as _,
+            )),
+            monitors: monitors(),
+            target_fps: conf.max_fps,
+            background_mode: conf.background_mode,
+            system_theme: system_theme(),
+            power_info: power_info(),
             ..NativeDisplayData::new(conf.window_width, conf.window_height, tx, clipboard)
         });
 
         display.update_dimensions(wnd);
+        // WM_MOVE keeps it up to date from here
+        let mut rect: RECT = std::mem::zeroed();
+        if GetWindowRect(wnd, &mut rect as *mut _ as _) != 0 {
+            crate::native_display().lock().unwrap().screen_position =
+                (rect.left as u32, rect.top as u32);
+        }
 
-        let mut wgl = wgl::Wgl::new(&mut display);
-        let gl_ctx = wgl.create_context(
-            &mut display,
-            conf.sample_count,
-            conf.platform.swap_interval.unwrap_or(1),
-        );
+        display.egl = match conf.platform.windows_gl {
+            WindowsGl::WGLOnly => None,
+            WindowsGl::EGLWithWGLFallback => display.create_egl_context(conf),
+        };
+        let gl_ctx = if display.egl.is_none() {
+            let mut wgl = wgl::Wgl::new(&mut display);
+            let gl_ctx = wgl.create_context(
+                &mut display,
+                conf.sample_count,
+                conf.platform.swap_interval.unwrap_or(1),
+                &conf.platform,
+            );
+            display.wgl = Some(wgl);
+            Some(gl_ctx)
+        } else {
+            None
+        };
+        {
+            let mut d = crate::native_display().lock().unwrap();
+            if let Some(egl) = &display.egl {
+                d.swap_control = egl.libegl.eglSwapInterval.is_some();
+            }
+            if let Some(wgl) = &display.wgl {
+                d.swap_control = wgl.swap_control();
+                d.adaptive_vsync = wgl.adaptive_vsync();
+            }
+        }
 
         super::gl::load_gl_funcs(|proc| display.get_proc_address(proc));
+        crate::native_display().lock().unwrap().egl = display.egl.is_some();
 
         display.event_handler = Some(f());
 
@@ -905,14 +2119,35 @@ where
         #[cfg(target_arch = "i686")]
         SetWindowLong(wnd, GWLP_USERDATA, &mut display as *mut _ as isize);
 
+        if display.fullsize_content {
+            // the WM_NCCALCSIZE of CreateWindowExW came before GWLP_USERDATA
+            let flags = SWP_FRAMECHANGED | SWP_NOMOVE | SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE;
+            SetWindowPos(wnd, NULL as _, 0, 0, 0, 0, flags);
+        }
+
+        if conf.wait_for_events {
+            // anything posted ends MsgWaitForMultipleObjects
+            let hwnd = wnd as usize;
+            crate::native_display().lock().unwrap().waker = Some(Box::new(move || {
+                PostMessageW(hwnd as HWND, WM_NULL, 0, 0);
+            }));
+        }
+
+        let mut frame_limiter = FrameLimiter::new();
         let mut done = false;
         while !(done || crate::native_display().lock().unwrap().quit_ordered) {
             while let Ok(request) = rx.try_recv() {
                 display.process_request(request);
             }
 
+            let mut had_messages = false;
+            if display.cursor_grab == CursorGrabMode::Locked {
+                had_messages = display.read_raw_input();
+            }
+
             let mut msg: MSG = std::mem::zeroed();
             while PeekMessageW(&mut msg as *mut _ as _, NULL as _, 0, 0, PM_REMOVE) != 0 {
+                had_messages = true;
                 if WM_QUIT == msg.message {
                     done = true;
                     continue;
@@ -921,11 +2156,20 @@ where
                     DispatchMessageW(&mut msg as *mut _ as _);
                 }
             }
+            if conf.wait_for_events && !had_messages && display.wait_for_events() {
+                continue;
+            }
 
-            display.event_handler.as_mut().unwrap().update();
-            display.event_handler.as_mut().unwrap().draw();
+            let event_handler = &mut **display.event_handler.as_mut().unwrap();
+            display.gamepads.poll(event_handler);
+            crate::native::dispatch_custom_events(event_handler);
+            if !crate::native_display().lock().unwrap().frames_paused() {
+                display.event_handler.as_mut().unwrap().update();
+                display.event_handler.as_mut().unwrap().draw();
 
-            SwapBuffers(display.dc);
+                display.swap_buffers();
+            }
+            frame_limiter.wait();
 
             if display.update_dimensions(wnd) {
                 let d = crate::native_display().lock().unwrap();
@@ -943,7 +2187,24 @@ where
             }
         }
 
-        (display.libopengl32.wglDeleteContext)(gl_ctx);
+        display.restore_video_mode();
+
+        // while the GL context is there for the Drop of the event handler
+        drop(display.event_handler.take());
+        if let Some(gl_ctx) = gl_ctx {
+            (display.libopengl32.wglDeleteContext)(gl_ctx);
+        }
+        if let Some(egl) = &display.egl {
+            (egl.libegl.eglMakeCurrent.unwrap())(
+                egl.display,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            );
+            (egl.libegl.eglDestroySurface.unwrap())(egl.display, egl.surface);
+            (egl.libegl.eglDestroyContext.unwrap())(egl.display, egl.context);
+            (egl.libegl.eglTerminate.unwrap())(egl.display);
+        }
         DestroyWindow(wnd);
     }
 }
@@ -1,8 +1,10 @@
 use winapi::um::winbase::{GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE};
-use winapi::um::winuser::CF_UNICODETEXT;
+use winapi::um::wingdi::{BITMAPINFOHEADER, BI_BITFIELDS, BI_RGB};
 use winapi::um::winuser::{
-    CloseClipboard, EmptyClipboard, GetClipboardData, OpenClipboard, SetClipboardData,
+    CloseClipboard, EmptyClipboard, GetClipboardData, IsClipboardFormatAvailable, OpenClipboard,
+    RegisterClipboardFormatW, SetClipboardData,
 };
+use winapi::um::winuser::{CF_DIB, CF_UNICODETEXT};
 
 use std::ptr;
 
@@ -25,7 +27,13 @@ impl Drop for ClipboardGuard {
     }
 }
 
-unsafe fn get_raw_clipboard() -> Option<Vec<u16>> {
+// https://docs.microsoft.com/en-us/windows/win32/dataxchg/html-clipboard-format
+unsafe fn cf_html() -> u32 {
+    let name = "HTML Format\0".encode_utf16().collect::<Vec<u16>>();
+    RegisterClipboardFormatW(name.as_ptr())
+}
+
+unsafe fn get_raw_clipboard(format: u32) -> Option<Vec<u8>> {
     // https://docs.microsoft.com/en-us/windows/win32/dataxchg/about-the-clipboard
 
     let guard = ClipboardGuard::open();
@@ -36,29 +44,26 @@ unsafe fn get_raw_clipboard() -> Option<Vec<u16>> {
     }
 
     // Returns a handle to a clipboard object
-    let clipboard_data = GetClipboardData(CF_UNICODETEXT);
+    let clipboard_data = GetClipboardData(format);
     if clipboard_data.is_null() {
         return None;
     }
 
-    let data_ptr = GlobalLock(clipboard_data) as *const u16;
+    let data_ptr = GlobalLock(clipboard_data) as *const u8;
     if data_ptr.is_null() {
         return None;
     }
     let data_size = GlobalSize(clipboard_data) as usize;
 
-    let slice = std::slice::from_raw_parts(data_ptr, data_size);
-    let len = slice.iter().position(|b| *b == 0).unwrap_or(data_size);
-
-    // search for the first null byte to see where the string ends.
-
-    let mut res = vec![0; len];
-    ptr::copy_nonoverlapping(data_ptr, res.as_mut_ptr(), len);
+    let mut res = vec![0; data_size];
+    ptr::copy_nonoverlapping(data_ptr, res.as_mut_ptr(), data_size);
+    GlobalUnlock(clipboard_data);
 
     Some(res)
 }
 
-unsafe fn set_raw_clipboard(data: *const u8, len: usize) {
+/// Replace the clipboard content with all the given (format, data).
+unsafe fn set_raw_clipboard(formats: &[(u32, &[u8])]) {
     let guard = ClipboardGuard::open();
 
     if guard.is_none() {
@@ -66,20 +71,139 @@ unsafe fn set_raw_clipboard(data: *const u8, len: usize) {
         return;
     }
 
-    let alloc_handle = GlobalAlloc(GMEM_MOVEABLE, len);
+    EmptyClipboard();
 
-    if alloc_handle.is_null() {
-        eprintln!("Failed to set clipboard: memory not allocated");
-        return;
+    for (format, data) in formats {
+        let alloc_handle = GlobalAlloc(GMEM_MOVEABLE, data.len());
+
+        if alloc_handle.is_null() {
+            eprintln!("Failed to set clipboard: memory not allocated");
+            return;
+        }
+
+        let lock = GlobalLock(alloc_handle) as *mut u8;
+        ptr::copy_nonoverlapping(data.as_ptr(), lock, data.len());
+
+        GlobalUnlock(alloc_handle);
+
+        SetClipboardData(*format, alloc_handle);
     }
+}
 
-    let lock = GlobalLock(alloc_handle) as *mut u8;
-    ptr::copy_nonoverlapping(data, lock, len);
+fn utf16_bytes(text: &str) -> Vec<u8> {
+    format!("{}\0", text)
+        .encode_utf16()
+        .flat_map(|c| c.to_le_bytes())
+        .collect()
+}
 
-    GlobalUnlock(lock as _);
-    EmptyClipboard();
+/// CF_HTML is utf-8 html with a header of byte offsets to the actual content.
+fn html_to_cf_html(html: &str) -> Vec<u8> {
+    const HEADER_LEN: usize = 105;
+    let header = |start_html, end_html, start_fragment, end_fragment| {
+        format!(
+            "Version:0.9\r\nStartHTML:{:010}\r\nEndHTML:{:010}\r\nStartFragment:{:010}\r\nEndFragment:{:010}\r\n",
+            start_html, end_html, start_fragment, end_fragment
+        )
+    };
+    let prefix = "<html><body>\r\n<!--StartFragment-->";
+    let suffix = "<!--EndFragment-->\r\n</body></html>";
+    let start_fragment = HEADER_LEN + prefix.len();
+    let end_fragment = start_fragment + html.len();
+    let end_html = end_fragment + suffix.len();
+
+    let header = header(HEADER_LEN, end_html, start_fragment, end_fragment);
+    debug_assert_eq!(header.len(), HEADER_LEN);
+    format!("{}{}{}{}\0", header, prefix, html, suffix).into_bytes()
+}
 
-    SetClipboardData(CF_UNICODETEXT, alloc_handle);
+fn cf_html_to_html(cf_html: &[u8]) -> Option<String> {
+    let cf_html = String::from_utf8_lossy(cf_html);
+    let offset = |key: &str| -> Option<usize> {
+        let line = cf_html.lines().find(|line| line.starts_with(key))?;
+        line[key.len()..].trim().parse().ok()
+    };
+    let (start, end) = match (offset("StartFragment:"), offset("EndFragment:")) {
+        (Some(start), Some(end)) => (start, end),
+        _ => (offset("StartHTML:")?, offset("EndHTML:")?),
+    };
+    cf_html.get(start..end).map(|html| html.to_string())
+}
+
+/// CF_DIB is a BITMAPINFOHEADER followed by BGR(A) rows, usually bottom-up.
+unsafe fn dib_to_image(dib: &[u8]) -> Option<crate::ClipboardImage> {
+    if dib.len() < std::mem::size_of::<BITMAPINFOHEADER>() {
+        return None;
+    }
+    let header = ptr::read_unaligned(dib.as_ptr() as *const BITMAPINFOHEADER);
+    let bytes_per_pixel = match (header.biBitCount, header.biCompression) {
+        (32, BI_RGB) | (32, BI_BITFIELDS) => 4,
+        (24, BI_RGB) => 3,
+        _ => return None,
+    };
+    let width = header.biWidth as usize;
+    let height = header.biHeight.unsigned_abs() as usize;
+    // rows are aligned to 4 bytes
+    let stride = (width * bytes_per_pixel + 3) & !3;
+    let mut offset = header.biSize as usize;
+    if header.biCompression == BI_BITFIELDS
+        && header.biSize as usize == std::mem::size_of::<BITMAPINFOHEADER>()
+    {
+        // three color masks, assumed to be the usual BGRA ones
+        offset += 12;
+    }
+    let pixels = dib.get(offset..offset + stride * height)?;
+
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        let row = if header.biHeight > 0 {
+            height - 1 - y
+        } else {
+            y
+        };
+        for pixel in pixels[row * stride..].chunks(bytes_per_pixel).take(width) {
+            let alpha = if bytes_per_pixel == 4 { pixel[3] } else { 255 };
+            rgba.extend_from_slice(&[pixel[2], pixel[1], pixel[0], alpha]);
+        }
+    }
+    // most applications leave the alpha of 32 bit bitmaps zeroed
+    if rgba.chunks(4).all(|pixel| pixel[3] == 0) {
+        rgba.chunks_mut(4).for_each(|pixel| pixel[3] = 255);
+    }
+
+    Some(crate::ClipboardImage {
+        width: width as u32,
+        height: height as u32,
+        rgba,
+    })
+}
+
+fn image_to_dib(rgba: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let header_size = std::mem::size_of::<BITMAPINFOHEADER>();
+    let mut dib = Vec::with_capacity(header_size + rgba.len());
+    let header = BITMAPINFOHEADER {
+        biSize: header_size as u32,
+        biWidth: width as i32,
+        // positive height is bottom-up, the only one every application reads
+        biHeight: height as i32,
+        biPlanes: 1,
+        biBitCount: 32,
+        biCompression: BI_RGB,
+        biSizeImage: rgba.len() as u32,
+        biXPelsPerMeter: 0,
+        biYPelsPerMeter: 0,
+        biClrUsed: 0,
+        biClrImportant: 0,
+    };
+    dib.extend_from_slice(unsafe {
+        std::slice::from_raw_parts(&header as *const _ as *const u8, header_size)
+    });
+    for row in rgba.chunks(width as usize * 4).rev() {
+        for pixel in row.chunks(4) {
+            dib.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+        }
+    }
+    dib
 }
 
 pub struct WindowsClipboard {}
@@ -90,13 +214,50 @@ impl WindowsClipboard {
 }
 impl crate::native::Clipboard for WindowsClipboard {
     fn get(&mut self) -> Option<String> {
-        unsafe { get_raw_clipboard().map(|data| String::from_utf16_lossy(&data)) }
+        let data = unsafe { get_raw_clipboard(CF_UNICODETEXT)? };
+        let utf16: Vec<u16> = data
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|c| *c != 0)
+            .collect();
+        Some(String::from_utf16_lossy(&utf16))
     }
 
     fn set(&mut self, data: &str) {
+        unsafe { set_raw_clipboard(&[(CF_UNICODETEXT, &utf16_bytes(data))]) };
+    }
+
+    fn has(&mut self, format: crate::ClipboardFormat) -> bool {
+        let format = match format {
+            crate::ClipboardFormat::Text => CF_UNICODETEXT,
+            crate::ClipboardFormat::Html => unsafe { cf_html() },
+            crate::ClipboardFormat::Image => CF_DIB,
+        };
+        unsafe { IsClipboardFormatAvailable(format) != 0 }
+    }
+
+    fn get_html(&mut self) -> Option<String> {
+        let data = unsafe { get_raw_clipboard(cf_html())? };
+        cf_html_to_html(&data)
+    }
+
+    fn set_html(&mut self, html: &str, text: &str) {
         unsafe {
-            let text_w = format!("{}\0", data).encode_utf16().collect::<Vec<u16>>();
-            set_raw_clipboard(text_w.as_ptr() as _, text_w.len() * 2);
+            set_raw_clipboard(&[
+                (cf_html(), &html_to_cf_html(html)),
+                (CF_UNICODETEXT, &utf16_bytes(text)),
+            ])
+        };
+    }
+
+    fn get_image(&mut self) -> Option<crate::ClipboardImage> {
+        unsafe {
+            let dib = get_raw_clipboard(CF_DIB)?;
+            dib_to_image(&dib)
         }
     }
+
+    fn set_image(&mut self, rgba: &[u8], width: u32, height: u32) {
+        unsafe { set_raw_clipboard(&[(CF_DIB, &image_to_dib(rgba, width, height))]) };
+    }
 }
@@ -0,0 +1,74 @@
+#![allow(non_camel_case_types, dead_code, non_snake_case)]
+
+use winapi::shared::{minwindef::*, ntdef::LONG, windef::*};
+
+pub type HIMC = *mut std::ffi::c_void;
+
+pub const GCS_COMPSTR: DWORD = 0x0008;
+pub const GCS_CURSORPOS: DWORD = 0x0080;
+pub const GCS_RESULTSTR: DWORD = 0x0800;
+pub const CFS_POINT: DWORD = 0x0002;
+pub const CFS_EXCLUDE: DWORD = 0x0080;
+pub const IACE_DEFAULT: DWORD = 0x0010;
+pub const ISC_SHOWUICOMPOSITIONWINDOW: LPARAM = 0x80000000u32 as LPARAM;
+
+#[repr(C)]
+pub struct COMPOSITIONFORM {
+    pub dwStyle: DWORD,
+    pub ptCurrentPos: POINT,
+    pub rcArea: RECT,
+}
+
+#[repr(C)]
+pub struct CANDIDATEFORM {
+    pub dwIndex: DWORD,
+    pub dwStyle: DWORD,
+    pub ptCurrentPos: POINT,
+    pub rcArea: RECT,
+}
+
+pub type ImmGetContext = extern "system" fn(_: HWND) -> HIMC;
+pub type ImmReleaseContext = extern "system" fn(_: HWND, _: HIMC) -> BOOL;
+pub type ImmAssociateContextEx = extern "system" fn(_: HWND, _: HIMC, _: DWORD) -> BOOL;
+pub type ImmGetCompositionStringW =
+    extern "system" fn(_: HIMC, _: DWORD, _: LPVOID, _: DWORD) -> LONG;
+pub type ImmSetCompositionWindow = extern "system" fn(_: HIMC, _: *mut COMPOSITIONFORM) -> BOOL;
+pub type ImmSetCandidateWindow = extern "system" fn(_: HIMC, _: *mut CANDIDATEFORM) -> BOOL;
+
+pub struct LibImm32 {
+    pub module: crate::native::module::Module,
+    pub ImmGetContext: ImmGetContext,
+    pub ImmReleaseContext: ImmReleaseContext,
+    pub ImmAssociateContextEx: ImmAssociateContextEx,
+    pub ImmGetCompositionStringW: ImmGetCompositionStringW,
+    pub ImmSetCompositionWindow: ImmSetCompositionWindow,
+    pub ImmSetCandidateWindow: ImmSetCandidateWindow,
+}
+
+impl LibImm32 {
+    pub fn try_load() -> Option<LibImm32> {
+        crate::native::module::Module::load("imm32.dll")
+            .map(|module| LibImm32 {
+                ImmGetContext: module.get_symbol("ImmGetContext").unwrap(),
+                ImmReleaseContext: module.get_symbol("ImmReleaseContext").unwrap(),
+                ImmAssociateContextEx: module.get_symbol("ImmAssociateContextEx").unwrap(),
+                ImmGetCompositionStringW: module.get_symbol("ImmGetCompositionStringW").unwrap(),
+                ImmSetCompositionWindow: module.get_symbol("ImmSetCompositionWindow").unwrap(),
+                ImmSetCandidateWindow: module.get_symbol("ImmSetCandidateWindow").unwrap(),
+                module,
+            })
+            .ok()
+    }
+
+    /// A GCS_COMPSTR or GCS_RESULTSTR string of the input context.
+    pub fn composition_string(&self, himc: HIMC, index: DWORD) -> Vec<u16> {
+        let len = (self.ImmGetCompositionStringW)(himc, index, std::ptr::null_mut(), 0);
+        if len <= 0 {
+            return vec![];
+        }
+        // in bytes
+        let mut string = vec![0u16; len as usize / 2];
+        (self.ImmGetCompositionStringW)(himc, index, string.as_mut_ptr() as _, len as _);
+        string
+    }
+}
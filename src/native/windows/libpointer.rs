@@ -0,0 +1,108 @@
+#![allow(non_camel_case_types, dead_code, non_snake_case)]
+
+//! The WM_POINTER functions, Windows 8 and newer.
+
+use winapi::shared::{minwindef::*, ntdef::HANDLE, windef::*};
+
+pub const WM_POINTERUPDATE: UINT = 0x0245;
+pub const WM_POINTERDOWN: UINT = 0x0246;
+pub const WM_POINTERUP: UINT = 0x0247;
+pub const PT_TOUCH: DWORD = 2;
+pub const PT_PEN: DWORD = 3;
+pub const POINTER_FLAG_INCONTACT: UINT = 0x0004;
+pub const POINTER_FLAG_CANCELED: UINT = 0x8000;
+pub const TOUCH_MASK_CONTACTAREA: UINT = 0x0001;
+pub const TOUCH_MASK_PRESSURE: UINT = 0x0004;
+pub const PEN_FLAG_BARREL: UINT = 0x0001;
+pub const PEN_FLAG_INVERTED: UINT = 0x0002;
+pub const PEN_FLAG_ERASER: UINT = 0x0004;
+pub const PEN_MASK_PRESSURE: UINT = 0x0001;
+pub const PEN_MASK_TILT_X: UINT = 0x0004;
+pub const PEN_MASK_TILT_Y: UINT = 0x0008;
+
+#[repr(C)]
+pub struct POINTER_INFO {
+    pub pointerType: DWORD,
+    pub pointerId: UINT,
+    pub frameId: UINT,
+    pub pointerFlags: UINT,
+    pub sourceDevice: HANDLE,
+    pub hwndTarget: HWND,
+    pub ptPixelLocation: POINT,
+    pub ptHimetricLocation: POINT,
+    pub ptPixelLocationRaw: POINT,
+    pub ptHimetricLocationRaw: POINT,
+    pub dwTime: DWORD,
+    pub historyCount: UINT,
+    pub InputData: INT,
+    pub dwKeyStates: DWORD,
+    pub PerformanceCount: u64,
+    pub ButtonChangeType: INT,
+}
+
+#[repr(C)]
+pub struct POINTER_TOUCH_INFO {
+    pub pointerInfo: POINTER_INFO,
+    pub touchFlags: UINT,
+    pub touchMask: UINT,
+    pub rcContact: RECT,
+    pub rcContactRaw: RECT,
+    pub orientation: UINT,
+    // from 0 to 1024
+    pub pressure: UINT,
+}
+
+#[repr(C)]
+pub struct POINTER_PEN_INFO {
+    pub pointerInfo: POINTER_INFO,
+    pub penFlags: UINT,
+    pub penMask: UINT,
+    // from 0 to 1024
+    pub pressure: UINT,
+    pub rotation: UINT,
+    // in degrees
+    pub tiltX: INT,
+    pub tiltY: INT,
+}
+
+pub type GetPointerType = extern "system" fn(_: UINT, _: *mut DWORD) -> BOOL;
+pub type GetPointerTouchInfo = extern "system" fn(_: UINT, _: *mut POINTER_TOUCH_INFO) -> BOOL;
+pub type GetPointerPenInfo = extern "system" fn(_: UINT, _: *mut POINTER_PEN_INFO) -> BOOL;
+
+pub struct LibPointer {
+    pub module: crate::native::module::Module,
+    pub GetPointerType: GetPointerType,
+    pub GetPointerTouchInfo: GetPointerTouchInfo,
+    pub GetPointerPenInfo: GetPointerPenInfo,
+}
+
+impl LibPointer {
+    pub fn try_load() -> Option<LibPointer> {
+        let module = crate::native::module::Module::load("user32.dll").ok()?;
+        Some(LibPointer {
+            GetPointerType: module.get_symbol("GetPointerType").ok()?,
+            GetPointerTouchInfo: module.get_symbol("GetPointerTouchInfo").ok()?,
+            GetPointerPenInfo: module.get_symbol("GetPointerPenInfo").ok()?,
+            module,
+        })
+    }
+
+    /// The PT_ type of the pointer of a WM_POINTER message.
+    pub fn pointer_type(&self, wparam: WPARAM) -> Option<DWORD> {
+        let mut pointer_type = 0;
+        let pointer_id = LOWORD(wparam as _) as UINT;
+        ((self.GetPointerType)(pointer_id, &mut pointer_type) != 0).then_some(pointer_type)
+    }
+
+    pub fn touch_info(&self, wparam: WPARAM) -> Option<POINTER_TOUCH_INFO> {
+        let mut info: POINTER_TOUCH_INFO = unsafe { std::mem::zeroed() };
+        let pointer_id = LOWORD(wparam as _) as UINT;
+        ((self.GetPointerTouchInfo)(pointer_id, &mut info) != 0).then_some(info)
+    }
+
+    pub fn pen_info(&self, wparam: WPARAM) -> Option<POINTER_PEN_INFO> {
+        let mut info: POINTER_PEN_INFO = unsafe { std::mem::zeroed() };
+        let pointer_id = LOWORD(wparam as _) as UINT;
+        ((self.GetPointerPenInfo)(pointer_id, &mut info) != 0).then_some(info)
+    }
+}
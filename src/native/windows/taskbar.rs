@@ -0,0 +1,119 @@
+//! The progress bar and the overlay icon of the taskbar button, from
+//! ITaskbarList3. Explorer sends "TaskbarButtonCreated" when the button is
+//! made, and again for a new one when it restarts, they are set again then.
+
+use winapi::{
+    shared::{
+        minwindef::{DWORD, UINT},
+        windef::{HICON, HWND},
+        winerror::SUCCEEDED,
+        wtypesbase::CLSCTX_INPROC_SERVER,
+    },
+    um::{
+        combaseapi::{CoCreateInstance, CoInitializeEx},
+        objbase::COINIT_APARTMENTTHREADED,
+        shobjidl_core::{CLSID_TaskbarList, ITaskbarList3, TBPF_NOPROGRESS, TBPF_NORMAL},
+        winuser::{ChangeWindowMessageFilterEx, DestroyIcon, RegisterWindowMessageW},
+    },
+    Interface,
+};
+
+// not in winapi 0.3
+const MSGFLT_ALLOW: DWORD = 1;
+
+pub struct Taskbar {
+    /// The registered "TaskbarButtonCreated", 0 if it could not be.
+    pub button_created_message: UINT,
+    // null before Windows 7
+    list: *mut ITaskbarList3,
+    progress: Option<f32>,
+    overlay: HICON,
+    description: Vec<u16>,
+}
+
+impl Taskbar {
+    pub unsafe fn new(wnd: HWND) -> Taskbar {
+        // COM for CoCreateInstance, a thread initialized before keeps its mode
+        CoInitializeEx(std::ptr::null_mut(), COINIT_APARTMENTTHREADED);
+
+        let name = "TaskbarButtonCreated\0"
+            .encode_utf16()
+            .collect::<Vec<u16>>();
+        let button_created_message = RegisterWindowMessageW(name.as_ptr());
+        if button_created_message != 0 {
+            // explorer is not elevated, the message is filtered out otherwise
+            ChangeWindowMessageFilterEx(
+                wnd,
+                button_created_message,
+                MSGFLT_ALLOW,
+                std::ptr::null_mut(),
+            );
+        }
+
+        let mut list: *mut ITaskbarList3 = std::ptr::null_mut();
+        let hr = CoCreateInstance(
+            &CLSID_TaskbarList,
+            std::ptr::null_mut(),
+            CLSCTX_INPROC_SERVER,
+            &ITaskbarList3::uuidof(),
+            &mut list as *mut _ as _,
+        );
+        if !SUCCEEDED(hr) {
+            list = std::ptr::null_mut();
+        } else if !SUCCEEDED((*list).HrInit()) {
+            (*list).Release();
+            list = std::ptr::null_mut();
+        }
+
+        Taskbar {
+            button_created_message,
+            list,
+            progress: None,
+            overlay: std::ptr::null_mut(),
+            description: vec![0],
+        }
+    }
+
+    pub unsafe fn button_created(&mut self, wnd: HWND) {
+        self.update_progress(wnd);
+        self.update_overlay(wnd);
+    }
+
+    pub unsafe fn set_progress(&mut self, wnd: HWND, progress: Option<f32>) {
+        self.progress = progress;
+        self.update_progress(wnd);
+    }
+
+    /// The icon is destroyed by the next one.
+    pub unsafe fn set_overlay(&mut self, wnd: HWND, overlay: HICON, description: &str) {
+        if !self.overlay.is_null() {
+            DestroyIcon(self.overlay);
+        }
+        self.overlay = overlay;
+        self.description = description.encode_utf16().chain(Some(0)).collect();
+        self.update_overlay(wnd);
+    }
+
+    unsafe fn update_progress(&self, wnd: HWND) {
+        if self.list.is_null() {
+            return;
+        }
+        match self.progress {
+            None => {
+                (*self.list).SetProgressState(wnd, TBPF_NOPROGRESS);
+            }
+            Some(progress) => {
+                (*self.list).SetProgressState(wnd, TBPF_NORMAL);
+                let completed = (progress.clamp(0., 1.) * 1000.) as u64;
+                (*self.list).SetProgressValue(wnd, completed, 1000);
+            }
+        }
+    }
+
+    unsafe fn update_overlay(&self, wnd: HWND) {
+        if self.list.is_null() {
+            return;
+        }
+        (*self.list).SetOverlayIcon(wnd, self.overlay, self.description.as_ptr());
+    }
+}
@@ -8,6 +8,7 @@ use winapi::{
 };
 
 use super::{LibOpengl32, WindowsDisplay};
+use crate::conf::{GlProfile, Platform};
 
 pub const WGL_NUMBER_PIXEL_FORMATS_ARB: u32 = 0x2000;
 pub const WGL_SUPPORT_OPENGL_ARB: u32 = 0x2010;
@@ -197,7 +198,9 @@ pub struct Wgl {
     arb_create_context: bool,
     arb_create_context_profile: bool,
     ext_swap_control: bool,
+    ext_swap_control_tear: bool,
     arb_pixel_format: bool,
+    arb_framebuffer_srgb: bool,
 }
 
 unsafe fn get_wgl_proc_address<T>(libopengl32: &mut LibOpengl32, proc: &str) -> Option<T> {
@@ -274,7 +277,10 @@ impl Wgl {
         let arb_create_context = wgl_ext_supported("WGL_ARB_create_context");
         let arb_create_context_profile = wgl_ext_supported("WGL_ARB_create_context_profile");
         let ext_swap_control = wgl_ext_supported("WGL_EXT_swap_control");
+        let ext_swap_control_tear = wgl_ext_supported("WGL_EXT_swap_control_tear");
         let arb_pixel_format = wgl_ext_supported("WGL_ARB_pixel_format");
+        let arb_framebuffer_srgb = wgl_ext_supported("WGL_ARB_framebuffer_sRGB")
+            || wgl_ext_supported("WGL_EXT_framebuffer_sRGB");
         assert!(arb_pixel_format, "WGL_ARB_pixel_format is required");
 
         (display.libopengl32.wglDeleteContext)(rc);
@@ -290,7 +296,9 @@ impl Wgl {
             arb_create_context,
             arb_create_context_profile,
             ext_swap_control,
+            ext_swap_control_tear,
             arb_pixel_format,
+            arb_framebuffer_srgb,
         }
     }
 
@@ -314,7 +322,13 @@ impl Wgl {
         return value;
     }
 
-    unsafe fn wgl_find_pixel_format(&self, display: &mut WindowsDisplay, sample_count: i32) -> u32 {
+    /// 0 if none is usable.
+    unsafe fn wgl_find_pixel_format(
+        &self,
+        display: &mut WindowsDisplay,
+        sample_count: i32,
+        srgb: bool,
+    ) -> u32 {
         let native_count = self.wgl_attrib(display, 1, WGL_NUMBER_PIXEL_FORMATS_ARB as _);
         let mut usable_configs = vec![GlFbconfig::default(); native_count as usize];
 
@@ -336,6 +350,9 @@ impl Wgl {
             {
                 continue;
             }
+            if srgb && self.wgl_attrib(display, n, WGL_FRAMEBUFFER_SRGB_CAPABLE_ARB as _) == 0 {
+                continue;
+            }
             u.red_bits = self.wgl_attrib(display, n, WGL_RED_BITS_ARB as _);
             u.green_bits = self.wgl_attrib(display, n, WGL_GREEN_BITS_ARB as _);
             u.blue_bits = self.wgl_attrib(display, n, WGL_BLUE_BITS_ARB as _);
@@ -351,7 +368,9 @@ impl Wgl {
             u.handle = n as _;
             usable_count += 1;
         }
-        assert!(usable_count > 0);
+        if usable_count == 0 {
+            return 0;
+        }
 
         let mut desired = GlFbconfig::default();
         desired.red_bits = 8;
@@ -362,7 +381,7 @@ impl Wgl {
         desired.stencil_bits = 8;
         desired.doublebuffer = true;
         desired.samples = sample_count;
-        let closest = gl_choose_fbconfig(&mut desired, &usable_configs[..]);
+        let closest = gl_choose_fbconfig(&mut desired, &usable_configs[..usable_count]);
         let mut pixel_format = 0;
         if let Some(closest) = closest {
             pixel_format = usable_configs[closest].handle;
@@ -375,8 +394,20 @@ impl Wgl {
         display: &mut WindowsDisplay,
         sample_count: i32,
         swap_interval: i32,
+        platform: &Platform,
     ) -> HGLRC {
-        let pixel_format = self.wgl_find_pixel_format(display, sample_count);
+        let mut pixel_format = 0;
+        if platform.srgb_framebuffer {
+            if self.arb_framebuffer_srgb {
+                pixel_format = self.wgl_find_pixel_format(display, sample_count, true);
+            }
+            if pixel_format == 0 {
+                eprintln!("WGL: No sRGB capable pixel format");
+            }
+        }
+        if pixel_format == 0 {
+            pixel_format = self.wgl_find_pixel_format(display, sample_count, false);
+        }
         if 0 == pixel_format {
             panic!("WGL: Didn't find matching pixel format.");
         }
@@ -404,34 +435,35 @@ impl Wgl {
         // the highest version version possible
         // but, somehow, sometimes, it creates 2.1 context when 3.2 is in fact available
         // so this is a workaround: try to create 3.2, and if it fails, go for 2.1
-        let attrs = [
-            WGL_CONTEXT_MAJOR_VERSION_ARB,
-            3,
-            WGL_CONTEXT_MINOR_VERSION_ARB,
-            2,
-            WGL_CONTEXT_FLAGS_ARB,
-            WGL_CONTEXT_FORWARD_COMPATIBLE_BIT_ARB,
-            WGL_CONTEXT_PROFILE_MASK_ARB,
-            WGL_CONTEXT_CORE_PROFILE_BIT_ARB,
-            0,
-            0,
-        ];
-        let mut gl_ctx = self.CreateContextAttribsARB.unwrap()(
-            display.dc,
-            std::ptr::null_mut(),
-            attrs.as_ptr() as *const _,
-        );
-
-        if gl_ctx.is_null() {
-            eprintln!("WGL: failed to create 3.2 context, trying 2.1");
-
+        let versions = match platform.gl_version {
+            Some(version) => vec![version],
+            None => vec![(3, 2), (2, 1)],
+        };
+        let mut gl_ctx = std::ptr::null_mut();
+        for &(major, minor) in &versions {
+            let mut flags = 0;
+            if platform.gl_debug {
+                flags |= WGL_CONTEXT_DEBUG_BIT_ARB;
+            }
+            // the profile is ignored before 3.2
+            let profile = match platform.gl_profile {
+                GlProfile::Core => {
+                    if major >= 3 {
+                        flags |= WGL_CONTEXT_FORWARD_COMPATIBLE_BIT_ARB;
+                    }
+                    WGL_CONTEXT_CORE_PROFILE_BIT_ARB
+                }
+                GlProfile::Compatibility => WGL_CONTEXT_COMPATIBILITY_PROFILE_BIT_ARB,
+            };
             let attrs = [
                 WGL_CONTEXT_MAJOR_VERSION_ARB,
-                2,
+                major as u32,
                 WGL_CONTEXT_MINOR_VERSION_ARB,
-                1,
+                minor as u32,
                 WGL_CONTEXT_FLAGS_ARB,
-                WGL_CONTEXT_CORE_PROFILE_BIT_ARB,
+                flags,
+                WGL_CONTEXT_PROFILE_MASK_ARB,
+                profile,
                 0,
                 0,
             ];
@@ -440,11 +472,19 @@ impl Wgl {
                 std::ptr::null_mut(),
                 attrs.as_ptr() as *const _,
             );
+            if !gl_ctx.is_null() {
+                break;
+            }
+            eprintln!("WGL: failed to create {}.{} context", major, minor);
         }
         if gl_ctx.is_null() {
             let err = GetLastError();
             if err == (0xc0070000 | ERROR_INVALID_VERSION_ARB) {
-                panic!("WGL: Driver does not support OpenGL version 3.3");
+                let (major, minor) = versions[versions.len() - 1];
+                panic!(
+                    "WGL: Driver does not support OpenGL version {}.{}",
+                    major, minor
+                );
             } else if err == (0xc0070000 | ERROR_INVALID_PROFILE_ARB) {
                 panic!("WGL: Driver does not support the requested OpenGL profile");
             } else if err == (0xc0070000 | ERROR_INCOMPATIBLE_DEVICE_CONTEXTS_ARB) {
@@ -454,11 +494,23 @@ impl Wgl {
             }
         }
         (display.libopengl32.wglMakeCurrent)(display.dc, gl_ctx);
-        if self.ext_swap_control {
-            /* FIXME: DwmIsCompositionEnabled() (see GLFW) */
-            (self.SwapIntervalEXT.unwrap())(swap_interval);
-        }
+        /* FIXME: DwmIsCompositionEnabled() (see GLFW) */
+        self.swap_interval(swap_interval);
 
         gl_ctx
     }
+
+    pub(crate) fn swap_control(&self) -> bool {
+        self.ext_swap_control
+    }
+
+    /// Negative intervals, adaptive vsync.
+    pub(crate) fn adaptive_vsync(&self) -> bool {
+        self.ext_swap_control && self.ext_swap_control_tear
+    }
+
+    /// For the current context.
+    pub(crate) unsafe fn swap_interval(&self, interval: i32) -> bool {
+        self.ext_swap_control && (self.SwapIntervalEXT.unwrap())(interval)
+    }
 }
@@ -0,0 +1,196 @@
+#![allow(non_camel_case_types, dead_code, non_snake_case)]
+
+//! XInput, the Xbox and compatible controllers on the four user indices.
+
+use crate::{EventHandler, GamepadAxis, GamepadButton, GamepadState};
+use std::time::{Duration, Instant};
+use winapi::shared::{minwindef::*, ntdef::SHORT};
+
+pub const ERROR_SUCCESS: DWORD = 0;
+pub const XUSER_MAX_COUNT: DWORD = 4;
+
+pub const XINPUT_GAMEPAD_DPAD_UP: WORD = 0x0001;
+pub const XINPUT_GAMEPAD_DPAD_DOWN: WORD = 0x0002;
+pub const XINPUT_GAMEPAD_DPAD_LEFT: WORD = 0x0004;
+pub const XINPUT_GAMEPAD_DPAD_RIGHT: WORD = 0x0008;
+pub const XINPUT_GAMEPAD_START: WORD = 0x0010;
+pub const XINPUT_GAMEPAD_BACK: WORD = 0x0020;
+pub const XINPUT_GAMEPAD_LEFT_THUMB: WORD = 0x0040;
+pub const XINPUT_GAMEPAD_RIGHT_THUMB: WORD = 0x0080;
+pub const XINPUT_GAMEPAD_LEFT_SHOULDER: WORD = 0x0100;
+pub const XINPUT_GAMEPAD_RIGHT_SHOULDER: WORD = 0x0200;
+pub const XINPUT_GAMEPAD_A: WORD = 0x1000;
+pub const XINPUT_GAMEPAD_B: WORD = 0x2000;
+pub const XINPUT_GAMEPAD_X: WORD = 0x4000;
+pub const XINPUT_GAMEPAD_Y: WORD = 0x8000;
+
+#[repr(C)]
+#[derive(Default)]
+pub struct XINPUT_GAMEPAD {
+    pub wButtons: WORD,
+    pub bLeftTrigger: BYTE,
+    pub bRightTrigger: BYTE,
+    pub sThumbLX: SHORT,
+    pub sThumbLY: SHORT,
+    pub sThumbRX: SHORT,
+    pub sThumbRY: SHORT,
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct XINPUT_STATE {
+    pub dwPacketNumber: DWORD,
+    pub Gamepad: XINPUT_GAMEPAD,
+}
+
+#[repr(C)]
+pub struct XINPUT_VIBRATION {
+    pub wLeftMotorSpeed: WORD,
+    pub wRightMotorSpeed: WORD,
+}
+
+pub type XInputGetState = extern "system" fn(_: DWORD, _: *mut XINPUT_STATE) -> DWORD;
+pub type XInputSetState = extern "system" fn(_: DWORD, _: *mut XINPUT_VIBRATION) -> DWORD;
+
+pub struct LibXInput {
+    pub module: crate::native::module::Module,
+    pub XInputGetState: XInputGetState,
+    pub XInputSetState: XInputSetState,
+}
+
+impl LibXInput {
+    pub fn try_load() -> Option<LibXInput> {
+        ["xinput1_4.dll", "xinput1_3.dll", "xinput9_1_0.dll"]
+            .iter()
+            .find_map(|name| crate::native::module::Module::load(name).ok())
+            .map(|module| LibXInput {
+                XInputGetState: module.get_symbol("XInputGetState").unwrap(),
+                XInputSetState: module.get_symbol("XInputSetState").unwrap(),
+                module,
+            })
+    }
+}
+
+const BUTTONS: [(WORD, GamepadButton); 14] = [
+    (XINPUT_GAMEPAD_A, GamepadButton::South),
+    (XINPUT_GAMEPAD_B, GamepadButton::East),
+    (XINPUT_GAMEPAD_X, GamepadButton::West),
+    (XINPUT_GAMEPAD_Y, GamepadButton::North),
+    (XINPUT_GAMEPAD_LEFT_SHOULDER, GamepadButton::LeftShoulder),
+    (XINPUT_GAMEPAD_RIGHT_SHOULDER, GamepadButton::RightShoulder),
+    (XINPUT_GAMEPAD_BACK, GamepadButton::Select),
+    (XINPUT_GAMEPAD_START, GamepadButton::Start),
+    (XINPUT_GAMEPAD_LEFT_THUMB, GamepadButton::LeftStick),
+    (XINPUT_GAMEPAD_RIGHT_THUMB, GamepadButton::RightStick),
+    (XINPUT_GAMEPAD_DPAD_UP, GamepadButton::DPadUp),
+    (XINPUT_GAMEPAD_DPAD_DOWN, GamepadButton::DPadDown),
+    (XINPUT_GAMEPAD_DPAD_LEFT, GamepadButton::DPadLeft),
+    (XINPUT_GAMEPAD_DPAD_RIGHT, GamepadButton::DPadRight),
+];
+
+fn translate_state(gamepad: &XINPUT_GAMEPAD) -> GamepadState {
+    let mut state = GamepadState::default();
+    for (mask, button) in BUTTONS {
+        state.set_button(button, gamepad.wButtons & mask != 0);
+    }
+    let stick = |value: SHORT| (value as f32 / 32767.).max(-1.);
+    // up is positive in XInput
+    state.set_axis(GamepadAxis::LeftX, stick(gamepad.sThumbLX));
+    state.set_axis(GamepadAxis::LeftY, -stick(gamepad.sThumbLY));
+    state.set_axis(GamepadAxis::RightX, stick(gamepad.sThumbRX));
+    state.set_axis(GamepadAxis::RightY, -stick(gamepad.sThumbRY));
+    state.set_axis(GamepadAxis::LeftTrigger, gamepad.bLeftTrigger as f32 / 255.);
+    state.set_axis(
+        GamepadAxis::RightTrigger,
+        gamepad.bRightTrigger as f32 / 255.,
+    );
+    state
+}
+
+pub struct Gamepads {
+    libxinput: Option<LibXInput>,
+    // the id and the last packet number of the connected user indices
+    connected: [Option<(u64, DWORD)>; XUSER_MAX_COUNT as usize],
+    // XInputGetState is slow for the disconnected user indices, they are
+    // looked at once a second
+    last_scan: Option<Instant>,
+    // XInput has no duration for the vibration, it is stopped in poll
+    rumble_until: [Option<Instant>; XUSER_MAX_COUNT as usize],
+}
+
+impl Gamepads {
+    pub fn new() -> Gamepads {
+        Gamepads {
+            libxinput: LibXInput::try_load(),
+            connected: [None; XUSER_MAX_COUNT as usize],
+            last_scan: None,
+            rumble_until: [None; XUSER_MAX_COUNT as usize],
+        }
+    }
+
+    fn set_vibration(&self, index: usize, low_frequency: f32, high_frequency: f32) {
+        if let Some(ref libxinput) = self.libxinput {
+            let mut vibration = XINPUT_VIBRATION {
+                wLeftMotorSpeed: (low_frequency * WORD::MAX as f32) as WORD,
+                wRightMotorSpeed: (high_frequency * WORD::MAX as f32) as WORD,
+            };
+            (libxinput.XInputSetState)(index as _, &mut vibration);
+        }
+    }
+
+    pub fn rumble(&mut self, id: u64, low_frequency: f32, high_frequency: f32, duration: Duration) {
+        let index = match self
+            .connected
+            .iter()
+            .position(|connected| matches!(connected, Some((other, _)) if *other == id))
+        {
+            Some(index) => index,
+            None => return,
+        };
+        self.set_vibration(index, low_frequency, high_frequency);
+        self.rumble_until[index] = Some(Instant::now() + duration);
+    }
+
+    pub fn poll(&mut self, event_handler: &mut dyn EventHandler) {
+        for index in 0..XUSER_MAX_COUNT as usize {
+            if self.rumble_until[index].map_or(false, |until| Instant::now() >= until) {
+                self.rumble_until[index] = None;
+                self.set_vibration(index, 0., 0.);
+            }
+        }
+
+        let libxinput = match self.libxinput {
+            Some(ref libxinput) => libxinput,
+            None => return,
+        };
+        let scan_due = self.last_scan.map_or(true, |last_scan| {
+            last_scan.elapsed() >= Duration::from_secs(1)
+        });
+        if scan_due {
+            self.last_scan = Some(Instant::now());
+        }
+        for (index, connected) in self.connected.iter_mut().enumerate() {
+            if connected.is_none() && !scan_due {
+                continue;
+            }
+            let mut state = XINPUT_STATE::default();
+            if (libxinput.XInputGetState)(index as _, &mut state) != ERROR_SUCCESS {
+                if let Some((id, _)) = connected.take() {
+                    self.rumble_until[index] = None;
+                    crate::native::update_gamepad(id, None, event_handler);
+                }
+                continue;
+            }
+            let new = connected.is_none();
+            let packet = state.dwPacketNumber;
+            let (id, last_packet) =
+                connected.get_or_insert_with(|| (crate::native::new_gamepad_id(), packet));
+            // the packet number changes with the state
+            if new || *last_packet != packet {
+                *last_packet = packet;
+                let state = translate_state(&state.Gamepad);
+                crate::native::update_gamepad(*id, Some(&state), event_handler);
+            }
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Rendering with `Context::headless`, needs libEGL. Mesa's llvmpipe is
+//! enough: `cargo test -- --ignored`.
+#![cfg(target_os = "linux")]
+
+use miniquad::*;
+
+const SIZE: u32 = 4;
+
+const VERTEX: &str = r#"#version 100
+attribute vec2 in_pos;
+attribute vec4 in_color;
+
+varying lowp vec4 color;
+
+void main() {
+    gl_Position = vec4(in_pos, 0, 1);
+    color = in_color;
+}"#;
+
+const FRAGMENT: &str = r#"#version 100
+varying lowp vec4 color;
+
+void main() {
+    gl_FragColor = color;
+}"#;
+
+fn meta() -> ShaderMeta {
+    ShaderMeta {
+        images: vec![],
+        uniforms: UniformBlockLayout { uniforms: vec![] },
+    }
+}
+
+/// A quad covering the whole target, of a single color.
+fn quad(ctx: &mut Context, color: [f32; 4]) -> Bindings {
+    #[rustfmt::skip]
+    let vertices: [f32; 24] = [
+        -1., -1., color[0], color[1], color[2], color[3],
+         1., -1., color[0], color[1], color[2], color[3],
+         1.,  1., color[0], color[1], color[2], color[3],
+        -1.,  1., color[0], color[1], color[2], color[3],
+    ];
+    let vertex_buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&vertices),
+    );
+    let indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
+    let index_buffer = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&indices),
+    );
+    Bindings {
+        vertex_buffers: vec![vertex_buffer],
+        index_buffer,
+        images: vec![],
+    }
+}
+
+fn read_pixels(ctx: &mut Context, texture: TextureId) -> Vec<[u8; 4]> {
+    let mut bytes = vec![0; (SIZE * SIZE * 4) as usize];
+    ctx.texture_read_pixels(texture, &mut bytes);
+    bytes
+        .chunks(4)
+        .map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]])
+        .collect()
+}
+
+#[test]
+#[ignore = "needs libEGL"]
+fn clear_and_draw() {
+    let mut ctx = Context::headless(SIZE as i32, SIZE as i32).expect("no EGL");
+    let ctx = &mut *ctx;
+    let texture = ctx.new_render_texture(TextureParams {
+        width: SIZE,
+        height: SIZE,
+        ..Default::default()
+    });
+    let pass = ctx.new_render_pass(texture, None);
+
+    ctx.begin_pass(Some(pass), PassAction::clear_color(1., 0., 0., 1.));
+    ctx.end_render_pass();
+    ctx.commit_frame();
+    assert!(read_pixels(ctx, texture)
+        .iter()
+        .all(|pixel| *pixel == [255, 0, 0, 255]));
+
+    let shader = ctx
+        .new_shader(
+            ShaderSource::Glsl {
+                vertex: VERTEX,
+                fragment: FRAGMENT,
+            },
+            meta(),
+        )
+        .unwrap();
+    let pipeline = ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &[
+            VertexAttribute::new("in_pos", VertexFormat::Float2),
+            VertexAttribute::new("in_color", VertexFormat::Float4),
+        ],
+        shader,
+        PipelineParams::default(),
+    );
+    let bindings = quad(ctx, [0., 1., 0., 1.]);
+
+    ctx.begin_pass(Some(pass), PassAction::clear_color(1., 0., 0., 1.));
+    ctx.apply_pipeline(&pipeline);
+    ctx.apply_bindings(&bindings);
+    ctx.draw(0, 6, 1);
+    ctx.end_render_pass();
+    ctx.commit_frame();
+    assert!(read_pixels(ctx, texture)
+        .iter()
+        .all(|pixel| *pixel == [0, 255, 0, 255]));
+}
+
+#[test]
+#[ignore = "needs libEGL"]
+fn recompile_with_other_attribute_order() {
+    let mut ctx = Context::headless(SIZE as i32, SIZE as i32).expect("no EGL");
+    let ctx = &mut *ctx;
+    let texture = ctx.new_render_texture(TextureParams {
+        width: SIZE,
+        height: SIZE,
+        ..Default::default()
+    });
+    let pass = ctx.new_render_pass(texture, None);
+
+    let shader = ctx
+        .new_shader(
+            ShaderSource::Glsl {
+                vertex: VERTEX,
+                fragment: FRAGMENT,
+            },
+            meta(),
+        )
+        .unwrap();
+    let pipeline = ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &[
+            VertexAttribute::new("in_pos", VertexFormat::Float2),
+            VertexAttribute::new("in_color", VertexFormat::Float4),
+        ],
+        shader,
+        PipelineParams::default(),
+    );
+    let bindings = quad(ctx, [0., 1., 0., 1.]);
+
+    // the attributes are likely to get swapped locations
+    let vertex = VERTEX.replace(
+        "attribute vec2 in_pos;\nattribute vec4 in_color;",
+        "attribute vec4 in_color;\nattribute vec2 in_pos;",
+    );
+    ctx.shader_recompile(
+        shader,
+        ShaderSource::Glsl {
+            vertex: &vertex,
+            fragment: FRAGMENT,
+        },
+    )
+    .unwrap();
+
+    ctx.begin_pass(Some(pass), PassAction::clear_color(1., 0., 0., 1.));
+    ctx.apply_pipeline(&pipeline);
+    ctx.apply_bindings(&bindings);
+    ctx.draw(0, 6, 1);
+    ctx.end_render_pass();
+    ctx.commit_frame();
+    assert!(read_pixels(ctx, texture)
+        .iter()
+        .all(|pixel| *pixel == [0, 255, 0, 255]));
+}
+
+#[test]
+#[ignore = "needs libEGL"]
+fn compute_image() {
+    let mut ctx = Context::headless(SIZE as i32, SIZE as i32).expect("no EGL");
+    let ctx = &mut *ctx;
+    if !ctx.info().features.compute {
+        return;
+    }
+    let source = r#"#version 310 es
+layout(local_size_x = 1, local_size_y = 1) in;
+layout(rgba8, binding = 0) writeonly uniform highp image2D image;
+
+void main() {
+    imageStore(image, ivec2(gl_GlobalInvocationID.xy), vec4(0, 1, 0, 1));
+}"#;
+    let pipeline = ctx.new_compute_pipeline(source, meta()).unwrap();
+    let params = TextureParams {
+        width: SIZE,
+        height: SIZE,
+        ..Default::default()
+    };
+    let texture = ctx.new_texture(TextureAccess::Storage, TextureSource::Empty, params);
+    let static_texture = ctx.new_texture(TextureAccess::Static, TextureSource::Empty, params);
+
+    ctx.apply_compute_pipeline(&pipeline);
+    if ctx.info().gl_version_string.starts_with("OpenGL ES") {
+        assert!(ctx.apply_compute_bindings(&[], &[static_texture]).is_err());
+    }
+    ctx.apply_compute_bindings(&[], &[texture]).unwrap();
+    ctx.dispatch(SIZE, SIZE, 1);
+    ctx.memory_barrier();
+    assert!(read_pixels(ctx, texture)
+        .iter()
+        .all(|pixel| *pixel == [0, 255, 0, 255]));
+}
+
+#[test]
+#[ignore = "needs libEGL"]
+fn restore_unretained() {
+    let mut ctx = Context::headless(SIZE as i32, SIZE as i32).expect("no EGL");
+    let ctx = &mut *ctx;
+    // created before retaining, re-created empty
+    let texture = ctx.new_render_texture(TextureParams {
+        width: SIZE,
+        height: SIZE,
+        ..Default::default()
+    });
+    let pass = ctx.new_render_pass(texture, None);
+    ctx.retain_resources(true);
+    ctx.restore_resources();
+
+    ctx.begin_pass(Some(pass), PassAction::clear_color(0., 0., 1., 1.));
+    ctx.end_render_pass();
+    ctx.commit_frame();
+    assert!(read_pixels(ctx, texture)
+        .iter()
+        .all(|pixel| *pixel == [0, 0, 255, 255]));
+}